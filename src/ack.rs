@@ -0,0 +1,165 @@
+//! Reliable writes: a frame is written again until the peer acknowledges it or the retries run
+//! out. Like AT responses, the acknowledgement is taken from the background read when it runs.
+
+use crate::operation::Operation;
+use crate::watcher::{Matcher, WINDOW};
+use serialport::SerialPort;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// `Outcome` How one attempt ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Ack,
+    Nak,
+    Timeout,
+    /// Stopped by `cancel_operation`.
+    Cancelled,
+}
+
+/// `Expectation` The answer a reliable write waits for.
+pub struct Expectation {
+    ack: Matcher,
+    /// A negative acknowledgement retries at once instead of waiting for the timeout.
+    nak: Option<Matcher>,
+    buffer: Vec<u8>,
+    outcome: Option<Outcome>,
+}
+
+impl Expectation {
+    pub fn new(ack: Matcher, nak: Option<Matcher>) -> Self {
+        Expectation {
+            ack,
+            nak,
+            buffer: Vec::new(),
+            outcome: None,
+        }
+    }
+
+    /// `feed` Scan received bytes until the first acknowledgement of the current attempt.
+    pub fn feed(&mut self, data: &[u8]) {
+        if self.outcome.is_some() {
+            return;
+        }
+        self.buffer.extend_from_slice(data);
+        let ack = self.ack.find(&self.buffer).map(|(start, _)| start);
+        let nak = self
+            .nak
+            .as_ref()
+            .and_then(|nak| nak.find(&self.buffer))
+            .map(|(start, _)| start);
+        self.outcome = match (ack, nak) {
+            (Some(ack), Some(nak)) if nak < ack => Some(Outcome::Nak),
+            (Some(_), _) => Some(Outcome::Ack),
+            (None, Some(_)) => Some(Outcome::Nak),
+            (None, None) => None,
+        };
+        if self.buffer.len() > WINDOW {
+            let excess = self.buffer.len() - WINDOW;
+            self.buffer.drain(..excess);
+        }
+    }
+
+    fn restart(&mut self) {
+        self.buffer.clear();
+        self.outcome = None;
+    }
+}
+
+/// Fed by the read thread while a reliable write waits.
+pub type SharedExpectation = Arc<Mutex<Option<Expectation>>>;
+
+/// `wait` Wait for the outcome of the current attempt, reading the port itself unless the
+/// background read feeds the expectation.
+fn wait(
+    port: &mut dyn SerialPort,
+    shared: &SharedExpectation,
+    reading: bool,
+    timeout: Duration,
+    operation: &Operation,
+) -> Result<Outcome, String> {
+    let deadline = Instant::now() + timeout;
+    let mut buffer = [0u8; 256];
+    loop {
+        let outcome = match shared.lock() {
+            Ok(expectation) => expectation
+                .as_ref()
+                .and_then(|expectation| expectation.outcome),
+            Err(error) => return Err(format!("Cannot get acknowledgement lock: {}", error)),
+        };
+        if let Some(outcome) = outcome {
+            return Ok(outcome);
+        }
+        if operation.is_cancelled() {
+            return Ok(Outcome::Cancelled);
+        }
+        if Instant::now() >= deadline {
+            return Ok(Outcome::Timeout);
+        }
+        if reading {
+            std::thread::sleep(Duration::from_millis(5));
+            continue;
+        }
+        match port.read(&mut buffer) {
+            Ok(size) => {
+                if let Ok(mut expectation) = shared.lock() {
+                    if let Some(expectation) = expectation.as_mut() {
+                        expectation.feed(&buffer[..size]);
+                    }
+                }
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read acknowledgement: {}", error)),
+        }
+    }
+}
+
+/// `write` Write `frame` up to `retries + 1` times until `expectation` is met, returns the
+/// outcome of the last attempt and the number of attempts.
+#[allow(clippy::too_many_arguments)]
+pub fn write(
+    operation: &Operation,
+    port: &mut dyn SerialPort,
+    shared: &SharedExpectation,
+    expectation: Expectation,
+    reading: bool,
+    frame: &[u8],
+    timeout: Duration,
+    retries: u32,
+) -> Result<(Outcome, u32), String> {
+    {
+        let mut guard = shared
+            .lock()
+            .map_err(|error| format!("Cannot get acknowledgement lock: {}", error))?;
+        if guard.is_some() {
+            return Err("Another reliable write is waiting for its acknowledgement".to_string());
+        }
+        *guard = Some(expectation);
+    }
+    let mut result = Ok((Outcome::Timeout, 0));
+    for attempt in 1..=retries + 1 {
+        if operation.is_cancelled() {
+            result = Ok((Outcome::Cancelled, attempt - 1));
+            break;
+        }
+        if let Ok(mut expectation) = shared.lock() {
+            if let Some(expectation) = expectation.as_mut() {
+                expectation.restart();
+            }
+        }
+        result = port
+            .write_all(frame)
+            .and_then(|_| port.flush())
+            .map_err(|error| format!("Failed to write frame: {}", error))
+            .and_then(|_| wait(port, shared, reading, timeout, operation))
+            .map(|outcome| (outcome, attempt));
+        match result {
+            Ok((Outcome::Ack, _)) | Ok((Outcome::Cancelled, _)) | Err(_) => break,
+            Ok((outcome, attempt)) => debug!(attempt, "Reliable write got {:?}", outcome),
+        }
+    }
+    if let Ok(mut expectation) = shared.lock() {
+        *expectation = None;
+    }
+    result
+}
@@ -0,0 +1,224 @@
+//! Direct USB access to the adapter behind a serial port, for vendor specific information.
+
+use crate::error::Error;
+use crate::state::AdapterInfo;
+use nusb::{Device, DeviceInfo};
+use serialport::SerialPortType;
+use std::time::Duration;
+
+pub const FTDI_VID: u16 = 0x0403;
+const SILABS_VID: u16 = 0x10c4;
+/// CP210x product ids, other SiLabs ids belong to different chips (CP2110 is HID).
+const CP210X_PIDS: [u16; 3] = [0xea60, 0xea70, 0xea71];
+
+const FTDI_READ_EEPROM: u8 = 0x90;
+const CP210X_VENDOR_SPECIFIC: u8 = 0xff;
+const CP210X_GET_PARTNUM: u16 = 0x370b;
+const CP210X_GET_FW_VER: u16 = 0x000e;
+const CP210X_GET_FW_VER_2N: u16 = 0x0010;
+
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// `find_device` Find the USB device behind the serial port `path`.
+pub fn find_device(path: &str) -> Result<DeviceInfo, Error> {
+    let usb_info = serialport::available_ports()
+        .map_err(|error| Error::String(error.to_string()))?
+        .into_iter()
+        .find(|port| port.port_name == path)
+        .and_then(|port| match port.port_type {
+            SerialPortType::UsbPort(info) => Some(info),
+            _ => None,
+        })
+        .ok_or_else(|| Error::String(format!("{} is not a USB serial port", path)))?;
+    nusb::list_devices()?
+        .find(|device| {
+            device.vendor_id() == usb_info.vid
+                && device.product_id() == usb_info.pid
+                && (usb_info.serial_number.is_none()
+                    || device.serial_number() == usb_info.serial_number.as_deref())
+        })
+        .ok_or_else(|| Error::String(format!("USB device for {} not found", path)))
+}
+
+/// `vendor_in` Vendor request to the device; needs no claimed interface, so the serial
+/// driver keeps the port.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn vendor_in(device: &Device, request: u8, value: u16, index: u16, size: usize) -> Option<Vec<u8>> {
+    use nusb::transfer::{Control, ControlType, Recipient};
+
+    let mut data = vec![0u8; size];
+    match device.control_in_blocking(
+        Control {
+            control_type: ControlType::Vendor,
+            recipient: Recipient::Device,
+            request,
+            value,
+            index,
+        },
+        &mut data,
+        CONTROL_TIMEOUT,
+    ) {
+        Ok(size) => {
+            data.truncate(size);
+            Some(data)
+        }
+        Err(error) => {
+            debug!("Vendor request {:#04x} failed: {}", request, error);
+            None
+        }
+    }
+}
+
+/// `vendor_in` On Windows the vendor driver owns the device, only descriptor strings are known.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn vendor_in(
+    _device: &Device,
+    _request: u8,
+    _value: u16,
+    _index: u16,
+    _size: usize,
+) -> Option<Vec<u8>> {
+    None
+}
+
+fn ftdi_chip(device_version: u16) -> Option<&'static str> {
+    match device_version {
+        0x0400 => Some("FT232B"),
+        0x0500 => Some("FT2232C"),
+        0x0600 => Some("FT232R"),
+        0x0700 => Some("FT2232H"),
+        0x0800 => Some("FT4232H"),
+        0x0900 => Some("FT232H"),
+        0x1000 => Some("FT-X"),
+        _ => None,
+    }
+}
+
+/// `eeprom_string` A string descriptor stored in the EEPROM at the offset found in `pointer`.
+fn eeprom_string(eeprom: &[u8], pointer: usize) -> Option<String> {
+    let offset = eeprom[pointer] as usize & (eeprom.len() - 1);
+    let length = eeprom[offset] as usize;
+    if length < 2 || offset + length > eeprom.len() || eeprom[offset + 1] != 0x03 {
+        return None;
+    }
+    let units: Vec<u16> = eeprom[offset + 2..offset + length]
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// `eeprom_checksum_valid` The last word of the EEPROM is a rolling XOR over the others.
+fn eeprom_checksum_valid(eeprom: &[u8]) -> bool {
+    let words: Vec<u16> = eeprom
+        .chunks(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    let (stored, data) = match words.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+    let checksum = data
+        .iter()
+        .fold(0xaaaau16, |checksum, word| (checksum ^ word).rotate_left(1));
+    checksum == *stored
+}
+
+fn read_ftdi(device: &Device, info: &DeviceInfo, adapter: &mut AdapterInfo) {
+    let chip = ftdi_chip(info.device_version());
+    adapter.chip = chip.map(String::from);
+    // FT232R and older use a 128 byte EEPROM, the H series up to 256 bytes
+    let words = match chip {
+        Some("FT2232H") | Some("FT4232H") | Some("FT232H") => 128,
+        _ => 64,
+    };
+    let mut eeprom = Vec::with_capacity(words * 2);
+    for address in 0..words as u16 {
+        match vendor_in(device, FTDI_READ_EEPROM, 0, address, 2) {
+            Some(word) if word.len() == 2 => eeprom.extend(word),
+            _ => return,
+        }
+    }
+    adapter.eeprom_manufacturer = eeprom_string(&eeprom, 0x0e);
+    adapter.eeprom_product = eeprom_string(&eeprom, 0x10);
+    adapter.eeprom_serial_number = eeprom_string(&eeprom, 0x12);
+    // FT-X chips checksum a different range
+    if chip != Some("FT-X") {
+        adapter.eeprom_checksum_valid = Some(eeprom_checksum_valid(&eeprom));
+    }
+}
+
+fn cp210x_chip(part_number: u8) -> Option<&'static str> {
+    match part_number {
+        0x01 => Some("CP2101"),
+        0x02 => Some("CP2102"),
+        0x03 => Some("CP2103"),
+        0x04 => Some("CP2104"),
+        0x05 => Some("CP2105"),
+        0x08 => Some("CP2108"),
+        0x09 => Some("CP2109"),
+        0x20..=0x22 => Some("CP2102N"),
+        _ => None,
+    }
+}
+
+fn read_cp210x(device: &Device, adapter: &mut AdapterInfo) {
+    let part_number = match vendor_in(device, CP210X_VENDOR_SPECIFIC, CP210X_GET_PARTNUM, 0, 1) {
+        Some(data) if !data.is_empty() => data[0],
+        _ => return,
+    };
+    adapter.chip = cp210x_chip(part_number).map(String::from);
+    let request = match adapter.chip.as_deref() {
+        Some("CP2102N") => CP210X_GET_FW_VER_2N,
+        Some("CP2104") | Some("CP2105") | Some("CP2108") => CP210X_GET_FW_VER,
+        _ => return,
+    };
+    if let Some(version) = vendor_in(device, CP210X_VENDOR_SPECIFIC, request, 0, 3) {
+        if version.len() == 3 {
+            adapter.firmware_version =
+                Some(format!("{}.{}.{}", version[0], version[1], version[2]));
+        }
+    }
+}
+
+/// `adapter_info` USB descriptor strings plus FTDI EEPROM or CP210x data where available.
+pub fn adapter_info(path: &str) -> Result<AdapterInfo, Error> {
+    let info = find_device(path)?;
+    let mut adapter = AdapterInfo {
+        vid: Some(format!("{:04x}", info.vendor_id())),
+        pid: Some(format!("{:04x}", info.product_id())),
+        manufacturer: info.manufacturer_string().map(String::from),
+        product: info.product_string().map(String::from),
+        serial_number: info.serial_number().map(String::from),
+        ..AdapterInfo::default()
+    };
+    let is_ftdi = info.vendor_id() == FTDI_VID;
+    let is_cp210x = info.vendor_id() == SILABS_VID && CP210X_PIDS.contains(&info.product_id());
+    if !is_ftdi && !is_cp210x {
+        return Ok(adapter);
+    }
+    let device = match info.open() {
+        Ok(device) => device,
+        Err(error) => {
+            // e.g. no access to /dev/bus/usb, the descriptor strings are still useful
+            debug!(path = %path, "Cannot open USB device: {}", error);
+            return Ok(adapter);
+        }
+    };
+    if is_ftdi {
+        read_ftdi(&device, &info, &mut adapter);
+    } else {
+        read_cp210x(&device, &mut adapter);
+    }
+    Ok(adapter)
+}
+
+/// `reset` Reset the USB port of the device behind `path`; it re-enumerates afterwards and
+/// its serial port reappears, possibly under a new name.
+pub fn reset(path: &str) -> Result<(), Error> {
+    let device = find_device(path)?.open()?;
+    device.reset()?;
+    info!(path = %path, "USB device reset");
+    Ok(())
+}
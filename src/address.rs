@@ -0,0 +1,87 @@
+//! Address filtering for party-line RS-485 networks, where the host sees the traffic of every
+//! station: only frames addressed to it, or broadcast, are delivered by the read thread.
+//!
+//! The address is either a byte at a fixed offset of each frame, or in 9-bit mode the byte
+//! with the ninth bit set that starts a frame. The ninth bit is received as parity error the
+//! driver marks, like on the MDB bus, so 9-bit mode needs a native Unix port.
+
+use crate::mdb::{mode_bit, Unmarker};
+use serde::Deserialize;
+use serialport::{DataBits, Parity};
+
+/// `AddressFilter` Which frames are ours, given as `filter` to `set_address_filter`.
+#[derive(Deserialize, Clone, Copy)]
+pub struct AddressFilter {
+    pub address: u8,
+    /// Position of the address in each frame, ignored in 9-bit mode.
+    #[serde(default)]
+    pub offset: usize,
+    /// Frames start with an address byte that has the ninth bit set.
+    #[serde(default)]
+    pub nine_bit: bool,
+    /// Address of frames every station receives.
+    pub broadcast: Option<u8>,
+}
+
+/// `Filter` An `AddressFilter` with the state of the received stream.
+pub struct Filter {
+    pub settings: AddressFilter,
+    unmarker: Unmarker,
+    /// The last address byte was ours, in 9-bit mode.
+    selected: bool,
+    /// Frames or bytes of other stations dropped so far.
+    pub dropped: u64,
+    /// The data bits and parity of the port before 9-bit mode switched it to 8E1.
+    pub restore: Option<(DataBits, Parity)>,
+}
+
+impl Filter {
+    pub fn new(settings: AddressFilter) -> Self {
+        Filter {
+            settings,
+            unmarker: Unmarker::default(),
+            selected: false,
+            dropped: 0,
+            restore: None,
+        }
+    }
+
+    fn matches(&self, address: u8) -> bool {
+        address == self.settings.address || Some(address) == self.settings.broadcast
+    }
+
+    /// `feed` The received bytes to deliver: in 9-bit mode the ones from an address byte of
+    /// ours up to the address byte of another station, unchanged otherwise.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<u8> {
+        if !self.settings.nine_bit {
+            return data.to_vec();
+        }
+        let mut selected = Vec::new();
+        for (byte, error) in self.unmarker.feed(data) {
+            if mode_bit(byte, error) {
+                self.selected = self.matches(byte);
+            }
+            if self.selected {
+                selected.push(byte);
+            } else {
+                self.dropped += 1;
+            }
+        }
+        selected
+    }
+
+    /// `accepts` Whether a frame carries our address at the offset; in 9-bit mode `feed`
+    /// already dropped the other frames.
+    pub fn accepts(&mut self, frame: &[u8]) -> bool {
+        if self.settings.nine_bit {
+            return true;
+        }
+        let accepted = frame
+            .get(self.settings.offset)
+            .map_or(false, |address| self.matches(*address));
+        if !accepted {
+            self.dropped += 1;
+        }
+        accepted
+    }
+}
@@ -0,0 +1,194 @@
+//! AES-GCM (NIST SP 800-38D) with 128, 192 or 256-bit keys, 96-bit nonces and 128-bit tags,
+//! the AEAD microcontroller crypto libraries commonly offer, through the constant-time
+//! RustCrypto implementation.
+
+use aes_gcm::aead::consts::U12;
+use aes_gcm::aead::{AeadInPlace, KeyInit};
+use aes_gcm::aes::Aes192;
+use aes_gcm::{Aes128Gcm, Aes256Gcm, Nonce, Tag};
+
+pub const NONCE_SIZE: usize = 12;
+pub const TAG_SIZE: usize = 16;
+
+type Aes192Gcm = aes_gcm::AesGcm<Aes192, U12>;
+
+/// `AesGcm` An AES-GCM key.
+pub enum AesGcm {
+    Aes128(Box<Aes128Gcm>),
+    Aes192(Box<Aes192Gcm>),
+    Aes256(Box<Aes256Gcm>),
+}
+
+impl AesGcm {
+    /// `new` The cipher of a 16, 24 or 32 byte key.
+    pub fn new(key: &[u8]) -> Result<AesGcm, String> {
+        // the key sizes are checked by the match, so `new_from_slice` cannot fail
+        let cipher = match key.len() {
+            16 => Aes128Gcm::new_from_slice(key).map(|cipher| AesGcm::Aes128(Box::new(cipher))),
+            24 => Aes192Gcm::new_from_slice(key).map(|cipher| AesGcm::Aes192(Box::new(cipher))),
+            32 => Aes256Gcm::new_from_slice(key).map(|cipher| AesGcm::Aes256(Box::new(cipher))),
+            length => {
+                return Err(format!(
+                    "AES keys have 16, 24 or 32 bytes, not {}",
+                    length
+                ))
+            }
+        };
+        cipher.map_err(|_| format!("Invalid AES key of {} bytes", key.len()))
+    }
+
+    /// `seal` Encrypt `data` in place, returns the tag over it and `aad`.
+    pub fn seal(
+        &self,
+        nonce: &[u8; NONCE_SIZE],
+        aad: &[u8],
+        data: &mut [u8],
+    ) -> Result<[u8; TAG_SIZE], String> {
+        let nonce = Nonce::from_slice(nonce);
+        let tag = match self {
+            AesGcm::Aes128(cipher) => cipher.encrypt_in_place_detached(nonce, aad, data),
+            AesGcm::Aes192(cipher) => cipher.encrypt_in_place_detached(nonce, aad, data),
+            AesGcm::Aes256(cipher) => cipher.encrypt_in_place_detached(nonce, aad, data),
+        }
+        .map_err(|_| format!("Cannot encrypt {} bytes", data.len()))?;
+        Ok(tag.into())
+    }
+
+    /// `open` Decrypt `data` in place if `tag` authenticates it and `aad`, `false` otherwise,
+    /// leaving `data` as it was.
+    pub fn open(&self, nonce: &[u8; NONCE_SIZE], aad: &[u8], data: &mut [u8], tag: &[u8]) -> bool {
+        if tag.len() != TAG_SIZE {
+            return false;
+        }
+        let nonce = Nonce::from_slice(nonce);
+        let tag = Tag::from_slice(tag);
+        match self {
+            AesGcm::Aes128(cipher) => cipher.decrypt_in_place_detached(nonce, aad, data, tag),
+            AesGcm::Aes192(cipher) => cipher.decrypt_in_place_detached(nonce, aad, data, tag),
+            AesGcm::Aes256(cipher) => cipher.decrypt_in_place_detached(nonce, aad, data, tag),
+        }
+        .is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&text[index..index + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn nonce(text: &str) -> [u8; NONCE_SIZE] {
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&hex(text));
+        nonce
+    }
+
+    /// A test case of the GCM specification the SP 800-38D validation vectors are taken from.
+    fn check(key: &str, iv: &str, plaintext: &str, aad: &str, ciphertext: &str, tag: &str) {
+        let cipher = AesGcm::new(&hex(key)).unwrap();
+        let mut data = hex(plaintext);
+        let sealed = cipher.seal(&nonce(iv), &hex(aad), &mut data).unwrap();
+        assert_eq!(data, hex(ciphertext));
+        assert_eq!(sealed.to_vec(), hex(tag));
+        assert!(cipher.open(&nonce(iv), &hex(aad), &mut data, &sealed));
+        assert_eq!(data, hex(plaintext));
+    }
+
+    const PLAINTEXT: &str = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+                             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b39";
+    const AAD: &str = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+    const IV: &str = "cafebabefacedbaddecaf888";
+
+    #[test]
+    fn aes128_vectors() {
+        check(
+            "00000000000000000000000000000000",
+            "000000000000000000000000",
+            "",
+            "",
+            "",
+            "58e2fccefa7e3061367f1d57a4e7455a",
+        );
+        check(
+            "00000000000000000000000000000000",
+            "000000000000000000000000",
+            "00000000000000000000000000000000",
+            "",
+            "0388dace60b6a392f328c2b971b2fe78",
+            "ab6e47d42cec13bdf53a67b21257bddf",
+        );
+        check(
+            "feffe9928665731c6d6a8f9467308308",
+            IV,
+            PLAINTEXT,
+            AAD,
+            "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+             21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+            "5bc94fbc3221a5db94fae95ae7121a47",
+        );
+    }
+
+    #[test]
+    fn aes192_vector() {
+        check(
+            "feffe9928665731c6d6a8f9467308308feffe9928665731c",
+            IV,
+            PLAINTEXT,
+            AAD,
+            "3980ca0b3c00e841eb06fac4872a2757859e1ceaa6efd984628593b40ca1e19c\
+             7d773d00c144c525ac619d18c84a3f4718e2448b2fe324d9ccda2710",
+            "2519498e80f1478f37ba55bd6d27618c",
+        );
+    }
+
+    #[test]
+    fn aes256_vector() {
+        check(
+            "feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308",
+            IV,
+            PLAINTEXT,
+            AAD,
+            "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+             8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+            "76fc6ece0f4e1768cddf8853bb2d551b",
+        );
+    }
+
+    #[test]
+    fn forgeries_are_rejected() {
+        let cipher = AesGcm::new(&[7u8; 16]).unwrap();
+        let nonce = [1u8; NONCE_SIZE];
+        let mut data = b"switch relay 3 on".to_vec();
+        let tag = cipher.seal(&nonce, b"head", &mut data).unwrap();
+        let sealed = data.clone();
+
+        let mut wrong_tag = tag;
+        wrong_tag[15] ^= 1;
+        assert!(!cipher.open(&nonce, b"head", &mut data, &wrong_tag));
+        assert_eq!(data, sealed);
+
+        data[0] ^= 1;
+        assert!(!cipher.open(&nonce, b"head", &mut data, &tag));
+        data[0] ^= 1;
+        assert!(!cipher.open(&nonce, b"HEAD", &mut data, &tag));
+        assert!(!cipher.open(&nonce, b"head", &mut data, &tag[..15]));
+        assert!(!AesGcm::new(&[8u8; 16])
+            .unwrap()
+            .open(&nonce, b"head", &mut data, &tag));
+        assert!(cipher.open(&nonce, b"head", &mut data, &tag));
+        assert_eq!(data, b"switch relay 3 on");
+    }
+
+    #[test]
+    fn key_sizes() {
+        assert!(AesGcm::new(&[0u8; 16]).is_ok());
+        assert!(AesGcm::new(&[0u8; 24]).is_ok());
+        assert!(AesGcm::new(&[0u8; 32]).is_ok());
+        assert!(AesGcm::new(&[0u8; 20]).is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! AT command transactions with modems. Responses are taken from the background read when it
+//! runs, so unsolicited result codes (URCs) between transactions are not lost.
+
+use crate::cellular::Stack;
+use crate::lora::Module;
+use crate::state::AtResponse;
+use serialport::SerialPort;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A line longer than this is dropped, the port is probably not talking AT commands.
+const MAX_LINE: usize = 4096;
+
+/// Lines that are always unsolicited, even while a transaction waits for its response.
+const URC_PREFIXES: &[&str] = &["+CMTI:", "+CDS:", "+CDSI:", "+CMT:", "RING", "+CRING:"];
+
+/// `Urc` An unsolicited line, with the PDU line that follows `+CMT:` and `+CDS:` in PDU mode.
+pub struct Urc {
+    pub line: String,
+    pub pdu: Option<String>,
+}
+
+pub enum AtEvent {
+    /// The `> ` prompt asking for the payload of e.g. `AT+CMGS`.
+    Prompt,
+    Done(AtResponse),
+}
+
+struct Transaction {
+    command: String,
+    lines: Vec<String>,
+    events: Sender<AtEvent>,
+    /// Further lines ending the transaction, for commands without a final result code.
+    until: Option<fn(&str) -> bool>,
+}
+
+/// `is_final` Result codes that end a transaction.
+fn is_final(line: &str) -> bool {
+    matches!(
+        line,
+        "OK" | "ERROR"
+            | "NO CARRIER"
+            | "BUSY"
+            | "NO ANSWER"
+            | "NO DIALTONE"
+            | "SEND OK"
+            | "SEND FAIL"
+    ) || line.starts_with("+CME ERROR")
+        || line.starts_with("+CMS ERROR")
+        || line.starts_with("CONNECT")
+}
+
+/// `AtEngine` Splits the receive stream into lines and hands them to the waiting transaction
+/// or reports them as URCs.
+#[derive(Default)]
+pub struct AtEngine {
+    /// Set by the first AT command; the read thread only feeds the engine once it is used.
+    pub enabled: bool,
+    /// Handle `+CMTI` and `+CDS` in the read thread.
+    pub sms: bool,
+    /// Fetch data announced by the socket URCs of this stack in the read thread.
+    pub cellular: Option<Stack>,
+    /// Emit the downlinks announced by the URCs of this LoRa module in the read thread.
+    pub lora: Option<Module>,
+    /// Further unsolicited prefixes, e.g. of a module's socket stack.
+    pub urc_prefixes: Vec<String>,
+    /// What ends a written command, `\r` unless a module needs another.
+    pub line_ending: Option<&'static str>,
+    line: Vec<u8>,
+    pending: Option<Transaction>,
+    /// A `+CMT:` or `+CDS:` line waiting for its PDU.
+    urc_head: Option<String>,
+}
+
+impl AtEngine {
+    fn is_urc(&self, line: &str) -> bool {
+        URC_PREFIXES.iter().any(|prefix| line.starts_with(prefix))
+            || self
+                .urc_prefixes
+                .iter()
+                .any(|prefix| line.starts_with(prefix.as_str()))
+    }
+
+    /// `begin` Wait for the response to `command`, which the caller writes next.
+    pub fn begin(&mut self, command: &str, until: Option<fn(&str) -> bool>) -> Receiver<AtEvent> {
+        let (events, receiver) = mpsc::channel();
+        self.enabled = true;
+        self.pending = Some(Transaction {
+            command: command.to_string(),
+            lines: Vec::new(),
+            events,
+            until,
+        });
+        receiver
+    }
+
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    fn finish_line(&mut self, urcs: &mut Vec<Urc>) {
+        let line = String::from_utf8_lossy(&self.line).trim().to_string();
+        self.line.clear();
+        if line.is_empty() {
+            return;
+        }
+        if let Some(head) = self.urc_head.take() {
+            urcs.push(Urc {
+                line: head,
+                pdu: Some(line),
+            });
+            return;
+        }
+        if line.starts_with("+CMT:") || line.starts_with("+CDS:") {
+            // PDU mode, unlike `+CDS: 6,...` in text mode
+            if line.matches(',').count() <= 1 {
+                self.urc_head = Some(line);
+                return;
+            }
+        }
+        if self.is_urc(&line) {
+            urcs.push(Urc { line, pdu: None });
+            return;
+        }
+        match self.pending.as_mut() {
+            Some(pending) if line == pending.command => {}
+            // the lines of `until` may follow an `OK`, e.g. the outcome of a LoRaWAN join
+            Some(pending) if pending.until.is_some() && line == "OK" => pending.lines.push(line),
+            Some(pending)
+                if is_final(&line) || pending.until.map_or(false, |until| until(&line)) =>
+            {
+                let pending = self.pending.take().expect("pending transaction");
+                let _ = pending.events.send(AtEvent::Done(AtResponse {
+                    lines: pending.lines,
+                    result: line,
+                }));
+            }
+            Some(pending) => pending.lines.push(line),
+            None => urcs.push(Urc { line, pdu: None }),
+        }
+    }
+
+    /// `feed` Collect received bytes, returns the URCs they complete.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Urc> {
+        let mut urcs = Vec::new();
+        for byte in data {
+            match byte {
+                b'\r' | b'\n' => self.finish_line(&mut urcs),
+                _ => {
+                    self.line.push(*byte);
+                    if self.line.len() > MAX_LINE {
+                        self.line.clear();
+                    }
+                }
+            }
+        }
+        if self.line.starts_with(b">") && self.line.iter().skip(1).all(|byte| *byte == b' ') {
+            if let Some(pending) = &self.pending {
+                self.line.clear();
+                let _ = pending.events.send(AtEvent::Prompt);
+            }
+        }
+        urcs
+    }
+}
+
+/// `AtChannel` The engine of one port, with the turn that keeps transactions apart.
+#[derive(Default)]
+pub struct AtChannel {
+    pub engine: Mutex<AtEngine>,
+    turn: Mutex<()>,
+}
+
+impl AtChannel {
+    /// `transact` Write `command` and wait for its final result code. `payload` is written at
+    /// the `> ` prompt. Without the background read the response is read here, URCs seen
+    /// meanwhile are passed to `on_urc`.
+    pub fn transact(
+        &self,
+        port: &mut dyn SerialPort,
+        reading: bool,
+        command: &str,
+        payload: Option<&[u8]>,
+        timeout: Duration,
+        on_urc: &mut dyn FnMut(Urc),
+    ) -> Result<AtResponse, String> {
+        self.transact_until(port, reading, command, payload, timeout, None, on_urc)
+    }
+
+    /// `transact_until` Like `transact`, `until` also ends the transaction with lines such as
+    /// `SHUT OK` that some modules send instead of a result code. An `OK` does not end it then
+    /// and is kept with the information lines.
+    #[allow(clippy::too_many_arguments)]
+    pub fn transact_until(
+        &self,
+        port: &mut dyn SerialPort,
+        reading: bool,
+        command: &str,
+        payload: Option<&[u8]>,
+        timeout: Duration,
+        until: Option<fn(&str) -> bool>,
+        on_urc: &mut dyn FnMut(Urc),
+    ) -> Result<AtResponse, String> {
+        let _turn = self
+            .turn
+            .lock()
+            .map_err(|error| format!("Cannot get AT lock: {}", error))?;
+        let events = self
+            .engine
+            .lock()
+            .map_err(|error| format!("Cannot get AT lock: {}", error))?
+            .begin(command, until);
+        let result = self.exchange(port, reading, command, payload, timeout, &events, on_urc);
+        if result.is_err() {
+            if let Ok(mut engine) = self.engine.lock() {
+                engine.cancel();
+            }
+        }
+        result
+    }
+
+    /// `exchange` The write and wait of one transaction.
+    #[allow(clippy::too_many_arguments)]
+    fn exchange(
+        &self,
+        port: &mut dyn SerialPort,
+        reading: bool,
+        command: &str,
+        payload: Option<&[u8]>,
+        timeout: Duration,
+        events: &Receiver<AtEvent>,
+        on_urc: &mut dyn FnMut(Urc),
+    ) -> Result<AtResponse, String> {
+        let line_ending = match self.engine.lock() {
+            Ok(engine) => engine.line_ending.unwrap_or("\r"),
+            Err(_) => "\r",
+        };
+        port.write_all(format!("{}{}", command, line_ending).as_bytes())
+            .map_err(|error| format!("Failed to write {}: {}", command, error))?;
+        let deadline = Instant::now() + timeout;
+        let mut buffer = [0u8; 256];
+        loop {
+            match events.try_recv() {
+                Ok(AtEvent::Prompt) => match payload {
+                    Some(payload) => port
+                        .write_all(payload)
+                        .map_err(|error| format!("Failed to write payload: {}", error))?,
+                    None => return Err(format!("Unexpected prompt after {}", command)),
+                },
+                Ok(AtEvent::Done(response)) => return Ok(response),
+                Err(TryRecvError::Disconnected) => {
+                    return Err(format!("{} was cancelled", command));
+                }
+                Err(TryRecvError::Empty) => {}
+            }
+            if Instant::now() >= deadline {
+                return Err(format!("No response to {}", command));
+            }
+            if reading {
+                std::thread::sleep(Duration::from_millis(5));
+                continue;
+            }
+            match port.read(&mut buffer) {
+                Ok(size) => {
+                    let urcs = match self.engine.lock() {
+                        Ok(mut engine) => engine.feed(&buffer[..size]),
+                        Err(_) => Vec::new(),
+                    };
+                    urcs.into_iter().for_each(&mut *on_urc);
+                }
+                Err(error) if error.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(error) => return Err(format!("Failed to read response: {}", error)),
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Ports the plugin opens when the application starts, before any frontend code runs. They are
+//! declared with `Builder::auto_open` or under `plugins > serialport > auto_open` in
+//! `tauri.conf.json`.
+
+use crate::profile::Profile;
+use crate::selector::Selector;
+use serde::{Deserialize, Serialize};
+
+/// `AutoOpen` A port to open at startup.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AutoOpen {
+    #[serde(flatten)]
+    pub profile: Profile,
+    /// Keep looking for a port missing at startup and open it once it is plugged in.
+    #[serde(default)]
+    pub wait: bool,
+}
+
+impl AutoOpen {
+    pub fn new(selector: Selector, baud_rate: u32) -> Self {
+        AutoOpen {
+            profile: Profile::new(selector, baud_rate),
+            wait: false,
+        }
+    }
+
+    pub fn event_id(mut self, event_id: impl Into<String>) -> Self {
+        self.profile = self.profile.event_id(event_id);
+        self
+    }
+
+    pub fn wait(mut self, wait: bool) -> Self {
+        self.wait = wait;
+        self
+    }
+}
+
+/// `Config` The section of the plugin in `tauri.conf.json`.
+#[derive(Deserialize, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub auto_open: Vec<AutoOpen>,
+}
@@ -0,0 +1,172 @@
+//! Flow control of read events: the frontend acknowledges the sequence numbers it consumed, and
+//! events beyond the allowed number in flight wait in Rust, within a byte limit, until it does.
+
+use crate::reader::Stamp;
+use std::collections::VecDeque;
+
+/// Bytes buffered by default, while the frontend is behind or its window hidden.
+pub const DEFAULT_LIMIT: usize = 1024 * 1024;
+
+/// `WhenHidden` What a read does with its events while the window is hidden.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WhenHidden {
+    Emit,
+    /// Hold them back and deliver them once the window is shown again.
+    Buffer,
+    Discard,
+}
+
+impl WhenHidden {
+    pub fn parse(value: Option<&str>) -> Result<WhenHidden, String> {
+        match value {
+            None | Some("Emit") => Ok(WhenHidden::Emit),
+            Some("Buffer") => Ok(WhenHidden::Buffer),
+            Some("Discard") => Ok(WhenHidden::Discard),
+            Some(other) => Err(format!("Unknown hidden window policy: {}", other)),
+        }
+    }
+}
+
+/// `Pending` A read event waiting for the frontend to catch up.
+pub struct Pending {
+    pub data: Vec<u8>,
+    pub suppressed: u64,
+    pub stamp: Stamp,
+    pub outgoing: bool,
+}
+
+/// `Offered` What became of an offered event.
+pub enum Offered {
+    /// Emit it now.
+    Send,
+    Buffered,
+    /// Dropped, the buffer is full. `first` for the first drop since the buffer last drained.
+    Dropped {
+        first: bool,
+    },
+    /// Dropped while the window is hidden.
+    Discarded,
+}
+
+pub struct Backpressure {
+    /// Unacknowledged events allowed in flight, 0 emits without waiting.
+    window: u64,
+    /// Bytes buffered at most.
+    limit: usize,
+    /// Events with a lower sequence number are consumed.
+    acked: u64,
+    pending: VecDeque<Pending>,
+    pending_bytes: usize,
+    /// Bytes dropped since the buffer last drained.
+    dropped: u64,
+    when_hidden: WhenHidden,
+    hidden: bool,
+}
+
+impl Default for Backpressure {
+    fn default() -> Self {
+        Backpressure {
+            window: 0,
+            limit: DEFAULT_LIMIT,
+            acked: 0,
+            pending: VecDeque::new(),
+            pending_bytes: 0,
+            dropped: 0,
+            when_hidden: WhenHidden::Emit,
+            hidden: false,
+        }
+    }
+}
+
+impl Backpressure {
+    /// `configure` Start counting events in flight from `next`, the sequence number of the next
+    /// event. Events still waiting are delivered once the window allows it.
+    pub fn configure(&mut self, window: u64, limit: usize, next: u64) {
+        self.window = window;
+        self.limit = limit;
+        self.acked = next;
+    }
+
+    /// `ack` The frontend consumed every event up to `sequence`.
+    pub fn ack(&mut self, sequence: u64) {
+        self.acked = self.acked.max(sequence + 1);
+    }
+
+    pub fn set_when_hidden(&mut self, when_hidden: WhenHidden) {
+        self.when_hidden = when_hidden;
+    }
+
+    /// `set_hidden` Track the visibility of the window, `true` when it was shown again and the
+    /// waiting events can be delivered.
+    pub fn set_hidden(&mut self, hidden: bool) -> bool {
+        let shown = self.hidden && !hidden;
+        self.hidden = hidden;
+        shown
+    }
+
+    fn is_paused(&self) -> bool {
+        self.hidden && self.when_hidden != WhenHidden::Emit
+    }
+
+    fn is_open(&self, next: u64) -> bool {
+        !self.is_paused() && (self.window == 0 || next.saturating_sub(self.acked) < self.window)
+    }
+
+    /// `offer` Decide on an event of `data.len()` bytes, `next` being its sequence number if it
+    /// were emitted now. Waiting events go first, so the order is kept.
+    pub fn offer(
+        &mut self,
+        data: &[u8],
+        suppressed: u64,
+        stamp: Stamp,
+        outgoing: bool,
+        next: u64,
+    ) -> Offered {
+        if self.hidden && self.when_hidden == WhenHidden::Discard {
+            return Offered::Discarded;
+        }
+        if self.pending.is_empty() && self.is_open(next) {
+            return Offered::Send;
+        }
+        if self.pending_bytes + data.len() > self.limit {
+            let first = self.dropped == 0;
+            self.dropped += data.len() as u64;
+            return Offered::Dropped { first };
+        }
+        self.pending_bytes += data.len();
+        self.pending.push_back(Pending {
+            data: data.to_vec(),
+            suppressed,
+            stamp,
+            outgoing,
+        });
+        Offered::Buffered
+    }
+
+    /// `next` Take the oldest waiting event if the window allows emitting it.
+    pub fn next(&mut self, next: u64) -> Option<Pending> {
+        if !self.is_open(next) {
+            return None;
+        }
+        let pending = self.pending.pop_front()?;
+        self.pending_bytes -= pending.data.len();
+        Some(pending)
+    }
+
+    /// `take_dropped` Once the buffer drained after an overflow, the bytes dropped meanwhile.
+    pub fn take_dropped(&mut self) -> Option<u64> {
+        if self.pending.is_empty() && self.dropped > 0 {
+            Some(std::mem::take(&mut self.dropped))
+        } else {
+            None
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    pub fn buffered_bytes(&self) -> usize {
+        self.pending_bytes
+    }
+}
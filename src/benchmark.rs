@@ -0,0 +1,221 @@
+//! Benchmarks of a port against a device that echoes what it receives, timed in Rust so the
+//! IPC round trip of the webview does not count.
+
+use crate::linestatus::LineErrors;
+use serde::Serialize;
+use serialport::{ClearBuffer, SerialPort};
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+/// `Latency` Round-trip times in microseconds, over the iterations that were echoed.
+#[derive(Serialize, Clone, Default)]
+pub struct Latency {
+    pub iterations: u32,
+    /// Iterations whose echo did not arrive within the timeout.
+    pub lost: u32,
+    /// Iterations echoed with different bytes.
+    pub mismatched: u32,
+    pub min_us: u64,
+    pub avg_us: u64,
+    pub max_us: u64,
+    /// Mean difference between consecutive round trips.
+    pub jitter_us: u64,
+}
+
+/// `echo` Write `pattern` and read it back, the round trip if it arrived within `timeout`.
+fn echo(
+    port: &mut dyn SerialPort,
+    pattern: &[u8],
+    timeout: Duration,
+) -> Result<Option<(Duration, bool)>, String> {
+    let _ = port.clear(ClearBuffer::Input);
+    let mut received = Vec::with_capacity(pattern.len());
+    let mut buffer = vec![0u8; pattern.len()];
+    let start = Instant::now();
+    port.write_all(pattern)
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write pattern: {}", error))?;
+    while received.len() < pattern.len() {
+        if start.elapsed() >= timeout {
+            return Ok(None);
+        }
+        let missing = pattern.len() - received.len();
+        match port.read(&mut buffer[..missing]) {
+            Ok(size) => received.extend_from_slice(&buffer[..size]),
+            Err(error) if error.kind() == ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read echo: {}", error)),
+        }
+    }
+    Ok(Some((start.elapsed(), received == pattern)))
+}
+
+/// `latency` Echo `pattern` `iterations` times, one after the other.
+pub fn latency(
+    port: &mut dyn SerialPort,
+    pattern: &[u8],
+    iterations: u32,
+    timeout: Duration,
+) -> Result<Latency, String> {
+    let mut latency = Latency {
+        iterations,
+        ..Latency::default()
+    };
+    let mut samples: Vec<u64> = Vec::with_capacity(iterations as usize);
+    for _ in 0..iterations {
+        match echo(port, pattern, timeout)? {
+            Some((elapsed, matches)) => {
+                if !matches {
+                    latency.mismatched += 1;
+                }
+                samples.push(elapsed.as_micros() as u64);
+            }
+            None => latency.lost += 1,
+        }
+    }
+    if samples.is_empty() {
+        return Ok(latency);
+    }
+    latency.min_us = samples.iter().copied().min().unwrap_or(0);
+    latency.max_us = samples.iter().copied().max().unwrap_or(0);
+    latency.avg_us = samples.iter().sum::<u64>() / samples.len() as u64;
+    if samples.len() > 1 {
+        let differences: u64 = samples
+            .windows(2)
+            .map(|pair| pair[0].max(pair[1]) - pair[0].min(pair[1]))
+            .sum();
+        latency.jitter_us = differences / (samples.len() - 1) as u64;
+    }
+    Ok(latency)
+}
+
+/// Bytes written at most before their echo was read, below the buffers of common adapters.
+const LOOPBACK_WINDOW: usize = 4096;
+/// Silence after which the echo of a loopback run is complete.
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// `Direction` What a throughput benchmark saturates.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// Write and read back from a device echoing what it receives.
+    Loopback,
+    Transmit,
+    /// Read what a device sends on its own.
+    Receive,
+}
+
+impl Direction {
+    pub fn parse(value: Option<&str>) -> Result<Direction, String> {
+        match value {
+            None | Some("Loopback") => Ok(Direction::Loopback),
+            Some("Transmit") => Ok(Direction::Transmit),
+            Some("Receive") => Ok(Direction::Receive),
+            Some(other) => Err(format!("Unknown benchmark direction: {}", other)),
+        }
+    }
+}
+
+/// `Throughput` Bytes moved and the rates achieved.
+#[derive(Serialize, Clone, Default)]
+pub struct Throughput {
+    pub elapsed_ms: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub sent_per_second: u64,
+    pub received_per_second: u64,
+    /// What the baud rate allows with the configured character frame.
+    pub expected_per_second: u64,
+    /// Places where the echo differs from what was sent, in loopback.
+    pub mismatches: u64,
+    /// Errors the driver counted during the run.
+    pub line_errors: LineErrors,
+}
+
+fn per_second(bytes: u64, elapsed: Duration) -> u64 {
+    match elapsed.as_micros() as u64 {
+        0 => 0,
+        micros => bytes * 1_000_000 / micros,
+    }
+}
+
+/// `throughput` Saturate the link in `direction` for `duration`. `frame_bits` are the bits of
+/// one character on the line, start and stop bits included.
+pub fn throughput(
+    port: &mut dyn SerialPort,
+    direction: Direction,
+    duration: Duration,
+    frame_bits: u32,
+) -> Result<Throughput, String> {
+    let baud_rate = port.baud_rate().map_err(|error| error.to_string())?;
+    let mut result = Throughput {
+        expected_per_second: u64::from(baud_rate / frame_bits.max(1)),
+        ..Throughput::default()
+    };
+    let _ = port.clear(ClearBuffer::All);
+    // every byte is its position, so a changed or lost byte shows in the echo
+    let chunk: Vec<u8> = (0..=255u8).collect();
+    let mut buffer = vec![0u8; LOOPBACK_WINDOW];
+    let mut offset = 0usize;
+    let mut expected = 0u8;
+    let start = Instant::now();
+    let mut last_received = start;
+    loop {
+        let sending = start.elapsed() < duration;
+        if !sending
+            && (direction != Direction::Loopback
+                || result.bytes_received >= result.bytes_sent
+                || last_received.elapsed() >= DRAIN_TIMEOUT)
+        {
+            break;
+        }
+        let room = match direction {
+            Direction::Loopback => LOOPBACK_WINDOW
+                .saturating_sub(result.bytes_sent.saturating_sub(result.bytes_received) as usize),
+            Direction::Transmit => chunk.len(),
+            Direction::Receive => 0,
+        };
+        if sending && room > 0 {
+            let end = chunk.len().min(offset + room);
+            match port.write(&chunk[offset..end]) {
+                Ok(size) => {
+                    result.bytes_sent += size as u64;
+                    offset = (offset + size) % chunk.len();
+                }
+                Err(error) if error.kind() == ErrorKind::TimedOut => {}
+                Err(error) => return Err(format!("Failed to write: {}", error)),
+            }
+        }
+        if direction == Direction::Transmit {
+            continue;
+        }
+        match port.read(&mut buffer) {
+            Ok(size) => {
+                last_received = Instant::now();
+                result.bytes_received += size as u64;
+                if direction == Direction::Loopback {
+                    for &byte in &buffer[..size] {
+                        if byte != expected {
+                            result.mismatches += 1;
+                        }
+                        expected = byte.wrapping_add(1);
+                    }
+                }
+            }
+            Err(error) if error.kind() == ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read: {}", error)),
+        }
+    }
+    if direction == Direction::Transmit {
+        // written bytes may still wait in the driver
+        port.flush()
+            .map_err(|error| format!("Failed to flush: {}", error))?;
+    }
+    // the silence that ends a loopback run is not part of it
+    let elapsed = match direction {
+        Direction::Loopback => last_received.duration_since(start),
+        _ => start.elapsed(),
+    };
+    result.elapsed_ms = elapsed.as_millis() as u64;
+    result.sent_per_second = per_second(result.bytes_sent, elapsed);
+    result.received_per_second = per_second(result.bytes_received, elapsed);
+    Ok(result)
+}
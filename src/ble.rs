@@ -0,0 +1,329 @@
+//! Nordic UART Service (NUS) transport over Bluetooth Low Energy, opened with paths like
+//! `ble://00:11:22:33:44:55` (or `ble://<uuid>` on macOS, which hides device addresses).
+
+use crate::transport::{LineSettings, Transport, TransportPort};
+use btleplug::api::{Central, Manager as _, Peripheral as _, ScanFilter, WriteType};
+use btleplug::platform::{Adapter, Manager, Peripheral};
+use futures_util::StreamExt;
+use serialport::SerialPort;
+use std::collections::VecDeque;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use uuid::Uuid;
+
+pub const PREFIX: &str = "ble://";
+
+const NUS_SERVICE: Uuid = Uuid::from_u128(0x6e400001_b5a3_f393_e0a9_e50e24dcca9e);
+/// Written by the central, received by the device.
+const NUS_RX: Uuid = Uuid::from_u128(0x6e400002_b5a3_f393_e0a9_e50e24dcca9e);
+/// Notified by the device.
+const NUS_TX: Uuid = Uuid::from_u128(0x6e400003_b5a3_f393_e0a9_e50e24dcca9e);
+
+const SCAN_DURATION: Duration = Duration::from_secs(2);
+/// Payload of a write without response with the default ATT MTU.
+const CHUNK_SIZE: usize = 20;
+
+/// `BleDevice` A device advertising the Nordic UART Service.
+pub struct BleDevice {
+    pub path: String,
+    pub name: Option<String>,
+}
+
+type WriteRequest = (Vec<u8>, mpsc::Sender<Result<(), String>>);
+
+/// `Shared` State shared between the port handles and the connection thread.
+struct Shared {
+    incoming: Mutex<VecDeque<u8>>,
+    available: Condvar,
+    connected: AtomicBool,
+}
+
+fn runtime() -> io::Result<tokio::runtime::Runtime> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+}
+
+async fn peripheral_path(peripheral: &Peripheral) -> String {
+    if cfg!(target_vendor = "apple") {
+        format!("{}{}", PREFIX, peripheral.id())
+    } else {
+        format!("{}{}", PREFIX, peripheral.address())
+    }
+}
+
+/// `scan` Scan for NUS devices on the first adapter. The adapter is returned as well since
+/// the peripherals are only usable while it is alive.
+async fn scan() -> btleplug::Result<(Adapter, Vec<Peripheral>)> {
+    let manager = Manager::new().await?;
+    let adapter = manager
+        .adapters()
+        .await?
+        .into_iter()
+        .next()
+        .ok_or(btleplug::Error::DeviceNotFound)?;
+    adapter
+        .start_scan(ScanFilter {
+            services: vec![NUS_SERVICE],
+        })
+        .await?;
+    tokio::time::sleep(SCAN_DURATION).await;
+    let _ = adapter.stop_scan().await;
+    let peripherals = adapter.peripherals().await?;
+    Ok((adapter, peripherals))
+}
+
+/// `available_ports` Scan for devices advertising the Nordic UART Service.
+pub fn available_ports() -> Vec<BleDevice> {
+    let runtime = match runtime() {
+        Ok(runtime) => runtime,
+        Err(error) => {
+            warn!("Cannot start BLE runtime: {}", error);
+            return Vec::new();
+        }
+    };
+    runtime.block_on(async {
+        let peripherals = match scan().await {
+            Ok((_adapter, peripherals)) => peripherals,
+            Err(error) => {
+                debug!("BLE scan failed: {}", error);
+                return Vec::new();
+            }
+        };
+        let mut devices = Vec::new();
+        for peripheral in peripherals {
+            let properties = match peripheral.properties().await {
+                Ok(Some(properties)) => properties,
+                _ => continue,
+            };
+            // the scan filter is only a hint on some platforms
+            if !properties.services.contains(&NUS_SERVICE) {
+                continue;
+            }
+            devices.push(BleDevice {
+                path: peripheral_path(&peripheral).await,
+                name: properties.local_name,
+            });
+        }
+        devices
+    })
+}
+
+/// `connect` Find the device for `path`, connect and subscribe to the NUS TX characteristic.
+async fn connect(
+    path: &str,
+) -> Result<(Adapter, Peripheral, btleplug::api::Characteristic), String> {
+    let (adapter, peripherals) = scan().await.map_err(|error| error.to_string())?;
+    let mut found = None;
+    for peripheral in peripherals {
+        if peripheral_path(&peripheral)
+            .await
+            .eq_ignore_ascii_case(path)
+        {
+            found = Some(peripheral);
+            break;
+        }
+    }
+    let peripheral = found.ok_or_else(|| format!("BLE device {} not found", path))?;
+    peripheral
+        .connect()
+        .await
+        .map_err(|error| error.to_string())?;
+    peripheral
+        .discover_services()
+        .await
+        .map_err(|error| error.to_string())?;
+    let characteristics = peripheral.characteristics();
+    let rx = characteristics.iter().find(|c| c.uuid == NUS_RX).cloned();
+    let tx = characteristics.iter().find(|c| c.uuid == NUS_TX).cloned();
+    let (rx, tx) = match (rx, tx) {
+        (Some(rx), Some(tx)) => (rx, tx),
+        _ => {
+            let _ = peripheral.disconnect().await;
+            return Err(format!("{} does not offer the Nordic UART Service", path));
+        }
+    };
+    peripheral
+        .subscribe(&tx)
+        .await
+        .map_err(|error| error.to_string())?;
+    Ok((adapter, peripheral, rx))
+}
+
+/// `run` Connection loop, ends when every port handle is dropped.
+async fn run(
+    path: String,
+    shared: Arc<Shared>,
+    mut writes: UnboundedReceiver<WriteRequest>,
+    ready: mpsc::Sender<Result<(), String>>,
+) {
+    let (_adapter, peripheral, rx) = match connect(&path).await {
+        Ok(connection) => connection,
+        Err(error) => {
+            let _ = ready.send(Err(error));
+            return;
+        }
+    };
+    let mut notifications = match peripheral.notifications().await {
+        Ok(notifications) => notifications,
+        Err(error) => {
+            let _ = ready.send(Err(error.to_string()));
+            let _ = peripheral.disconnect().await;
+            return;
+        }
+    };
+    shared.connected.store(true, Ordering::SeqCst);
+    let _ = ready.send(Ok(()));
+    let receiver = shared.clone();
+    let notifications = tokio::spawn(async move {
+        while let Some(notification) = notifications.next().await {
+            if notification.uuid != NUS_TX {
+                continue;
+            }
+            if let Ok(mut incoming) = receiver.incoming.lock() {
+                incoming.extend(notification.value);
+            }
+            receiver.available.notify_all();
+        }
+        // the device went away
+        receiver.connected.store(false, Ordering::SeqCst);
+        receiver.available.notify_all();
+    });
+    while let Some((data, reply)) = writes.recv().await {
+        let mut result = Ok(());
+        for chunk in data.chunks(CHUNK_SIZE) {
+            if let Err(error) = peripheral
+                .write(&rx, chunk, WriteType::WithoutResponse)
+                .await
+            {
+                result = Err(error.to_string());
+                break;
+            }
+        }
+        let _ = reply.send(result);
+    }
+    notifications.abort();
+    shared.connected.store(false, Ordering::SeqCst);
+    shared.available.notify_all();
+    let _ = peripheral.disconnect().await;
+    debug!(path = %path, "BLE connection closed");
+}
+
+/// `Nus` A handle to a NUS connection, the connection closes with the last handle.
+struct Nus {
+    shared: Arc<Shared>,
+    writes: UnboundedSender<WriteRequest>,
+}
+
+fn disconnected() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "BLE device disconnected")
+}
+
+impl Transport for Nus {
+    fn kind(&self) -> &'static str {
+        "BLE"
+    }
+
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let incoming = self.shared.incoming.lock().map_err(|_| disconnected())?;
+        let (mut incoming, _) = self
+            .shared
+            .available
+            .wait_timeout_while(incoming, timeout, |incoming| {
+                incoming.is_empty() && self.shared.connected.load(Ordering::SeqCst)
+            })
+            .map_err(|_| disconnected())?;
+        if incoming.is_empty() {
+            if !self.shared.connected.load(Ordering::SeqCst) {
+                return Err(disconnected());
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Operation timed out",
+            ));
+        }
+        let size = buf.len().min(incoming.len());
+        for (target, byte) in buf.iter_mut().zip(incoming.drain(..size)) {
+            *target = byte;
+        }
+        Ok(size)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.shared.connected.load(Ordering::SeqCst) {
+            return Err(disconnected());
+        }
+        let (reply, result) = mpsc::channel();
+        self.writes
+            .send((buf.to_vec(), reply))
+            .map_err(|_| disconnected())?;
+        match result.recv() {
+            Ok(Ok(())) => Ok(buf.len()),
+            Ok(Err(error)) => Err(io::Error::new(io::ErrorKind::Other, error)),
+            Err(_) => Err(disconnected()),
+        }
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn Transport>> {
+        Ok(Box::new(Nus {
+            shared: self.shared.clone(),
+            writes: self.writes.clone(),
+        }))
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self
+            .shared
+            .incoming
+            .lock()
+            .map(|incoming| incoming.len() as u32)
+            .unwrap_or(0))
+    }
+
+    fn clear(&self, buffer_to_clear: serialport::ClearBuffer) -> serialport::Result<()> {
+        if buffer_to_clear != serialport::ClearBuffer::Output {
+            if let Ok(mut incoming) = self.shared.incoming.lock() {
+                incoming.clear();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `open` Connect to a NUS device, the connection runs on its own thread.
+pub fn open(
+    path: &str,
+    baud_rate: u32,
+    timeout: Duration,
+) -> serialport::Result<Box<dyn SerialPort>> {
+    let shared = Arc::new(Shared {
+        incoming: Mutex::new(VecDeque::new()),
+        available: Condvar::new(),
+        connected: AtomicBool::new(false),
+    });
+    let (writes, write_requests) = unbounded_channel();
+    let (ready, connected) = mpsc::channel();
+    let thread_path = path.to_string();
+    let thread_shared = shared.clone();
+    thread::spawn(move || match runtime() {
+        Ok(runtime) => runtime.block_on(run(thread_path, thread_shared, write_requests, ready)),
+        Err(error) => {
+            let _ = ready.send(Err(error.to_string()));
+        }
+    });
+    let no_device =
+        |message: String| serialport::Error::new(serialport::ErrorKind::NoDevice, message);
+    match connected.recv() {
+        Ok(Ok(())) => TransportPort::open(
+            path,
+            Box::new(Nus { shared, writes }),
+            LineSettings::new(baud_rate, timeout),
+        ),
+        Ok(Err(error)) => Err(no_device(error)),
+        Err(_) => Err(no_device(format!("BLE connection to {} failed", path))),
+    }
+}
@@ -0,0 +1,230 @@
+//! Bluetooth SPP (RFCOMM) transport, opened with paths like `bt://00:11:22:33:44:55`
+//! or `bt://00:11:22:33:44:55/2` to choose an RFCOMM channel other than 1.
+
+use serialport::SerialPort;
+use std::time::Duration;
+
+pub const PREFIX: &str = "bt://";
+
+/// `PairedDevice` A paired Bluetooth device offering the Serial Port Profile.
+pub struct PairedDevice {
+    pub path: String,
+    pub name: String,
+}
+
+/// `parse_path` Split `bt://MAC[/channel]` into the address bytes and the RFCOMM channel.
+pub fn parse_path(path: &str) -> serialport::Result<([u8; 6], u8)> {
+    let invalid = || {
+        serialport::Error::new(
+            serialport::ErrorKind::InvalidInput,
+            format!("Invalid Bluetooth address: {}", path),
+        )
+    };
+    let rest = path.strip_prefix(PREFIX).ok_or_else(invalid)?;
+    let (address, channel) = match rest.split_once('/') {
+        Some((address, channel)) => (address, channel.parse::<u8>().map_err(|_| invalid())?),
+        None => (rest, 1),
+    };
+    let mut bytes = [0u8; 6];
+    let parts: Vec<&str> = address.split(':').collect();
+    if parts.len() != 6 {
+        return Err(invalid());
+    }
+    for (index, part) in parts.iter().enumerate() {
+        bytes[index] = u8::from_str_radix(part, 16).map_err(|_| invalid())?;
+    }
+    Ok((bytes, channel))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use crate::transport::{LineSettings, Transport, TransportPort};
+    use std::io;
+    use std::os::raw::c_int;
+    use std::os::unix::io::RawFd;
+    use std::process::Command;
+
+    const AF_BLUETOOTH: c_int = 31;
+    const BTPROTO_RFCOMM: c_int = 3;
+
+    #[repr(C)]
+    struct SockaddrRc {
+        rc_family: libc::sa_family_t,
+        rc_bdaddr: [u8; 6],
+        rc_channel: u8,
+    }
+
+    /// `Rfcomm` A connected RFCOMM socket.
+    /// Line settings are remembered but have no effect on the radio link.
+    struct Rfcomm {
+        fd: RawFd,
+    }
+
+    impl Drop for Rfcomm {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+
+    pub fn open(
+        path: &str,
+        baud_rate: u32,
+        timeout: Duration,
+    ) -> serialport::Result<Box<dyn SerialPort>> {
+        let (address, channel) = parse_path(path)?;
+        let fd = unsafe { libc::socket(AF_BLUETOOTH, libc::SOCK_STREAM, BTPROTO_RFCOMM) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let rfcomm = Rfcomm { fd };
+        // bdaddr_t is stored least significant byte first
+        let mut bdaddr = address;
+        bdaddr.reverse();
+        let addr = SockaddrRc {
+            rc_family: AF_BLUETOOTH as libc::sa_family_t,
+            rc_bdaddr: bdaddr,
+            rc_channel: channel,
+        };
+        let result = unsafe {
+            libc::connect(
+                fd,
+                &addr as *const SockaddrRc as *const libc::sockaddr,
+                std::mem::size_of::<SockaddrRc>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        TransportPort::open(
+            path,
+            Box::new(rfcomm),
+            LineSettings::new(baud_rate, timeout),
+        )
+    }
+
+    impl Transport for Rfcomm {
+        fn kind(&self) -> &'static str {
+            "Bluetooth SPP"
+        }
+
+        fn read(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+            let mut poll_fd = libc::pollfd {
+                fd: self.fd,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let ready = unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as c_int) };
+            if ready < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if ready == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "Operation timed out",
+                ));
+            }
+            let size =
+                unsafe { libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(size as usize)
+        }
+
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let size =
+                unsafe { libc::write(self.fd, buf.as_ptr() as *const libc::c_void, buf.len()) };
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(size as usize)
+        }
+
+        fn try_clone(&self) -> serialport::Result<Box<dyn Transport>> {
+            let fd = unsafe { libc::dup(self.fd) };
+            if fd < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(Box::new(Rfcomm { fd }))
+        }
+
+        fn bytes_to_read(&self) -> serialport::Result<u32> {
+            let mut count: c_int = 0;
+            if unsafe { libc::ioctl(self.fd, libc::FIONREAD, &mut count) } < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(count as u32)
+        }
+
+        fn bytes_to_write(&self) -> serialport::Result<u32> {
+            let mut count: c_int = 0;
+            if unsafe { libc::ioctl(self.fd, libc::TIOCOUTQ, &mut count) } < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            Ok(count as u32)
+        }
+    }
+
+    fn bluetoothctl(args: &[&str]) -> String {
+        Command::new("bluetoothctl")
+            .args(args)
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+            .unwrap_or_default()
+    }
+
+    /// `paired_devices` Ask BlueZ for paired devices that advertise the Serial Port service.
+    pub fn paired_devices() -> Vec<PairedDevice> {
+        let mut output = bluetoothctl(&["devices", "Paired"]);
+        if output.trim().is_empty() {
+            // bluetoothctl before 5.65
+            output = bluetoothctl(&["paired-devices"]);
+        }
+        output
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.trim().splitn(3, ' ');
+                if fields.next() != Some("Device") {
+                    return None;
+                }
+                let address = fields.next()?.to_string();
+                let name = fields.next().unwrap_or_default().to_string();
+                Some((address, name))
+            })
+            .filter(|(address, _)| {
+                bluetoothctl(&["info", address])
+                    .lines()
+                    .any(|line| line.contains("Serial Port") || line.contains("00001101-"))
+            })
+            .map(|(address, name)| PairedDevice {
+                path: format!("{}{}", PREFIX, address),
+                name,
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::{open, paired_devices};
+
+/// `open` On Windows and macOS paired SPP devices already show up as COM / `cu.*` ports.
+#[cfg(not(target_os = "linux"))]
+pub fn open(
+    path: &str,
+    _baud_rate: u32,
+    _timeout: Duration,
+) -> serialport::Result<Box<dyn SerialPort>> {
+    parse_path(path)?;
+    Err(crate::transport::unsupported(
+        "Bluetooth SPP",
+        "Opening by Bluetooth address; use the serial port created by the system",
+    ))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn paired_devices() -> Vec<PairedDevice> {
+    Vec::new()
+}
@@ -0,0 +1,278 @@
+//! Line settings a port accepts, found by applying each and reading it back unless the driver
+//! can be asked (Windows `GetCommProperties`). The settings of the port are restored afterwards.
+
+use serde::Serialize;
+use serialport::{DataBits, FlowControl, Parity, SerialPort, StopBits};
+
+/// Rates probed, the common ones from 110 to 3 Mbaud.
+pub const BAUD_RATES: &[u32] = &[
+    110, 300, 600, 1200, 2400, 4800, 9600, 14400, 19200, 38400, 57600, 115200, 230400, 460800,
+    921600, 1000000, 2000000, 3000000,
+];
+
+/// A rate no table contains, accepted only by drivers that set the divisor freely.
+const CUSTOM_BAUD_RATE: u32 = 31250;
+
+#[derive(Serialize, Clone, Default)]
+pub struct Capabilities {
+    pub baud_rates: Vec<u32>,
+    /// Whether rates other than the listed ones are accepted too.
+    pub custom_baud_rates: bool,
+    pub data_bits: Vec<u8>,
+    pub parities: Vec<String>,
+    pub stop_bits: Vec<u8>,
+    /// `Driver` when the driver reported the settings, `Probe` when each was tried.
+    pub source: String,
+}
+
+/// `Saved` The settings of a port before probing, restored afterwards.
+struct Saved {
+    baud_rate: Option<u32>,
+    data_bits: Option<DataBits>,
+    flow_control: Option<FlowControl>,
+    parity: Option<Parity>,
+    stop_bits: Option<StopBits>,
+}
+
+impl Saved {
+    fn new(port: &dyn SerialPort) -> Self {
+        Saved {
+            baud_rate: port.baud_rate().ok(),
+            data_bits: port.data_bits().ok(),
+            flow_control: port.flow_control().ok(),
+            parity: port.parity().ok(),
+            stop_bits: port.stop_bits().ok(),
+        }
+    }
+
+    fn restore(&self, port: &mut dyn SerialPort) {
+        if let Some(rate) = self.baud_rate {
+            let _ = port.set_baud_rate(rate);
+        }
+        if let Some(bits) = self.data_bits {
+            let _ = port.set_data_bits(bits);
+        }
+        if let Some(flow_control) = self.flow_control {
+            let _ = port.set_flow_control(flow_control);
+        }
+        if let Some(parity) = self.parity {
+            let _ = port.set_parity(parity);
+        }
+        if let Some(bits) = self.stop_bits {
+            let _ = port.set_stop_bits(bits);
+        }
+    }
+}
+
+fn accepts_baud_rate(port: &mut dyn SerialPort, rate: u32) -> bool {
+    port.set_baud_rate(rate).is_ok() && port.baud_rate().ok() == Some(rate)
+}
+
+/// `probe` Try every setting on `port`. Mark and Space parity cannot be set through
+/// `serialport`, `stick_parity` tells whether the native port accepts them.
+pub fn probe(port: &mut dyn SerialPort, stick_parity: bool) -> Capabilities {
+    let saved = Saved::new(port);
+    let mut capabilities = Capabilities {
+        source: "Probe".to_string(),
+        ..Capabilities::default()
+    };
+    for &rate in BAUD_RATES {
+        if accepts_baud_rate(port, rate) {
+            capabilities.baud_rates.push(rate);
+        }
+    }
+    capabilities.custom_baud_rates = accepts_baud_rate(port, CUSTOM_BAUD_RATE);
+    for bits in [
+        DataBits::Five,
+        DataBits::Six,
+        DataBits::Seven,
+        DataBits::Eight,
+    ] {
+        if port.set_data_bits(bits).is_ok() && port.data_bits().ok() == Some(bits) {
+            capabilities.data_bits.push(u8::from(bits));
+        }
+    }
+    for value in [Parity::None, Parity::Odd, Parity::Even] {
+        if port.set_parity(value).is_ok() && port.parity().ok() == Some(value) {
+            capabilities.parities.push(value.to_string());
+        }
+    }
+    if stick_parity {
+        capabilities.parities.push("Mark".to_string());
+        capabilities.parities.push("Space".to_string());
+    }
+    for bits in [StopBits::One, StopBits::Two] {
+        if port.set_stop_bits(bits).is_ok() && port.stop_bits().ok() == Some(bits) {
+            capabilities.stop_bits.push(u8::from(bits));
+        }
+    }
+    saved.restore(port);
+    capabilities
+}
+
+/// `SettingCheck` Whether the driver took one setting.
+#[derive(Serialize, Clone)]
+pub struct SettingCheck {
+    /// `baud_rate`, `data_bits`, `flow_control`, `parity` or `stop_bits`.
+    pub setting: String,
+    pub value: String,
+    pub accepted: bool,
+    /// Why it was rejected.
+    pub message: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Validation {
+    /// Whether every setting was accepted.
+    pub valid: bool,
+    pub checks: Vec<SettingCheck>,
+}
+
+/// `Requested` The settings to validate, named and defaulted like the arguments of `open`.
+pub struct Requested {
+    pub baud_rate: u32,
+    pub data_bits: Option<usize>,
+    pub flow_control: Option<String>,
+    pub parity: Option<String>,
+    pub stop_bits: Option<usize>,
+}
+
+fn rejected(setting: &str, value: String, message: String) -> SettingCheck {
+    SettingCheck {
+        setting: setting.to_string(),
+        value,
+        accepted: false,
+        message: Some(message),
+    }
+}
+
+/// `check` Compare what the driver reports back after applying a setting, both as `show`s them.
+fn check<T: PartialEq>(
+    setting: &str,
+    value: T,
+    applied: serialport::Result<()>,
+    read_back: serialport::Result<T>,
+    show: fn(&T) -> String,
+) -> SettingCheck {
+    let message = match (applied, read_back) {
+        (Err(error), _) => Some(error.description),
+        (Ok(_), Ok(actual)) if actual != value => Some(format!("The driver set {}", show(&actual))),
+        (Ok(_), Err(error)) => Some(format!("Cannot read it back: {}", error.description)),
+        (Ok(_), Ok(_)) => None,
+    };
+    SettingCheck {
+        setting: setting.to_string(),
+        value: show(&value),
+        accepted: message.is_none(),
+        message,
+    }
+}
+
+/// `validate` Apply the settings one by one and tell which the driver rejected, then restore
+/// the previous ones. Mark and Space parity are accepted when `stick_parity`, as in `probe`.
+pub fn validate(
+    port: &mut dyn SerialPort,
+    requested: &Requested,
+    stick_parity: bool,
+) -> Validation {
+    let saved = Saved::new(port);
+    let mut checks = Vec::new();
+    let rate = requested.baud_rate;
+    checks.push(check(
+        "baud_rate",
+        rate,
+        port.set_baud_rate(rate),
+        port.baud_rate(),
+        u32::to_string,
+    ));
+    let data_bits = match requested.data_bits.unwrap_or(8) {
+        5 => Ok(DataBits::Five),
+        6 => Ok(DataBits::Six),
+        7 => Ok(DataBits::Seven),
+        8 => Ok(DataBits::Eight),
+        other => Err(other),
+    };
+    checks.push(match data_bits {
+        Ok(bits) => {
+            let applied = port.set_data_bits(bits);
+            check("data_bits", bits, applied, port.data_bits(), |bits| {
+                u8::from(*bits).to_string()
+            })
+        }
+        Err(other) => rejected("data_bits", other.to_string(), "Use 5 to 8".to_string()),
+    });
+    let flow_control = match requested.flow_control.as_deref() {
+        None | Some("None") => Ok(FlowControl::None),
+        Some("Software") => Ok(FlowControl::Software),
+        Some("Hardware") => Ok(FlowControl::Hardware),
+        Some(other) => Err(other),
+    };
+    checks.push(match flow_control {
+        Ok(value) => {
+            let applied = port.set_flow_control(value);
+            check(
+                "flow_control",
+                value,
+                applied,
+                port.flow_control(),
+                FlowControl::to_string,
+            )
+        }
+        Err(other) => rejected(
+            "flow_control",
+            other.to_string(),
+            "Use None, Software or Hardware".to_string(),
+        ),
+    });
+    checks.push(match requested.parity.as_deref().unwrap_or("None") {
+        name @ ("Mark" | "Space") if stick_parity => SettingCheck {
+            setting: "parity".to_string(),
+            value: name.to_string(),
+            accepted: true,
+            message: None,
+        },
+        name @ ("Mark" | "Space") => rejected(
+            "parity",
+            name.to_string(),
+            "The port cannot be set to Mark or Space parity".to_string(),
+        ),
+        name => {
+            let parity = match name {
+                "None" => Some(Parity::None),
+                "Odd" => Some(Parity::Odd),
+                "Even" => Some(Parity::Even),
+                _ => None,
+            };
+            match parity {
+                Some(parity) => {
+                    let applied = port.set_parity(parity);
+                    check("parity", parity, applied, port.parity(), Parity::to_string)
+                }
+                None => rejected(
+                    "parity",
+                    name.to_string(),
+                    "Use None, Odd, Even, Mark or Space".to_string(),
+                ),
+            }
+        }
+    });
+    let stop_bits = match requested.stop_bits.unwrap_or(2) {
+        1 => Ok(StopBits::One),
+        2 => Ok(StopBits::Two),
+        other => Err(other),
+    };
+    checks.push(match stop_bits {
+        Ok(bits) => {
+            let applied = port.set_stop_bits(bits);
+            check("stop_bits", bits, applied, port.stop_bits(), |bits| {
+                u8::from(*bits).to_string()
+            })
+        }
+        Err(other) => rejected("stop_bits", other.to_string(), "Use 1 or 2".to_string()),
+    });
+    saved.restore(port);
+    Validation {
+        valid: checks.iter().all(|check| check.accepted),
+        checks,
+    }
+}
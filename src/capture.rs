@@ -0,0 +1,92 @@
+//! Triggered capture: nothing is kept until a start pattern is received, then the stream is
+//! recorded until a stop pattern or a byte limit.
+
+use crate::watcher::{Matcher, WINDOW};
+
+/// Limit of a capture without `max_bytes`.
+pub const DEFAULT_MAX_BYTES: usize = 16 * 1024 * 1024;
+
+/// `Capture` A capture armed or recording on one port.
+pub struct Capture {
+    start: Matcher,
+    stop: Option<Matcher>,
+    max_bytes: usize,
+    /// Written when the capture ends, otherwise the data is sent with the event.
+    pub file: Option<String>,
+    /// Tail of the stream while waiting for the start pattern.
+    armed: Vec<u8>,
+    recording: bool,
+    data: Vec<u8>,
+    /// The stop pattern is searched from here, after the start trigger.
+    search_from: usize,
+}
+
+impl Capture {
+    pub fn new(
+        start: Matcher,
+        stop: Option<Matcher>,
+        max_bytes: Option<usize>,
+        file: Option<String>,
+    ) -> Self {
+        Capture {
+            start,
+            stop,
+            max_bytes: max_bytes.unwrap_or(DEFAULT_MAX_BYTES).max(1),
+            file,
+            armed: Vec::new(),
+            recording: false,
+            data: Vec::new(),
+            search_from: 0,
+        }
+    }
+
+    /// `feed` Scan or record received bytes. Returns whether the start pattern was seen in
+    /// them, and why the capture ended if it did (`Stop` or `Limit`).
+    pub fn feed(&mut self, data: &[u8]) -> (bool, Option<&'static str>) {
+        let mut started = false;
+        if self.recording {
+            self.data.extend_from_slice(data);
+        } else {
+            self.armed.extend_from_slice(data);
+            match self.start.find(&self.armed) {
+                Some((start, end)) => {
+                    self.recording = true;
+                    started = true;
+                    self.data = self.armed.split_off(start);
+                    self.armed = Vec::new();
+                    self.search_from = end - start;
+                }
+                None => {
+                    if self.armed.len() > WINDOW {
+                        let excess = self.armed.len() - WINDOW;
+                        self.armed.drain(..excess);
+                    }
+                    return (false, None);
+                }
+            }
+        }
+        if let Some(stop) = &self.stop {
+            if let Some((_, end)) = stop.find(&self.data[self.search_from..]) {
+                let end = (self.search_from + end).min(self.max_bytes);
+                self.data.truncate(end);
+                return (started, Some("Stop"));
+            }
+            // keep an overlap so a stop pattern split over two reads is found
+            self.search_from = self.search_from.max(self.data.len().saturating_sub(WINDOW));
+        }
+        if self.data.len() >= self.max_bytes {
+            self.data.truncate(self.max_bytes);
+            return (started, Some("Limit"));
+        }
+        (started, None)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// `take` The recorded bytes.
+    pub fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.data)
+    }
+}
@@ -0,0 +1,420 @@
+//! TCP and UDP sockets through the AT socket stacks of cellular modules. Received data is
+//! exchanged hex encoded, so binary payloads do not disturb the line based AT engine.
+
+use crate::at::{AtChannel, Urc};
+use crate::sms::{check, from_hex, to_hex};
+use serde::Deserialize;
+use serialport::SerialPort;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Activating the PDP context or connecting may take minutes on a weak network.
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(150);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// `Stack` The socket command set of a module family.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Stack {
+    /// SIMCom SIM800/SIM900, `AT+CIPSTART` in multi connection mode.
+    Sim800,
+    /// Quectel BG9x/EC2x, `AT+QIOPEN`.
+    Quectel,
+    /// u-blox SARA, `AT+USOCR`.
+    Ublox,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// `SocketUrc` What a socket URC announces.
+pub enum SocketUrc {
+    Data(u8),
+    Closed(u8),
+}
+
+impl Stack {
+    /// `urc_prefixes` The lines of the stack that are always unsolicited.
+    pub fn urc_prefixes(self) -> Vec<String> {
+        match self {
+            Stack::Sim800 => {
+                let mut prefixes = vec!["+CIPRXGET: 1,".to_string()];
+                prefixes.extend((0..6).map(|socket| format!("{}, CLOSED", socket)));
+                prefixes.extend((0..6).map(|socket| format!("{}, CONNECT", socket)));
+                prefixes
+            }
+            Stack::Quectel => vec!["+QIURC:".to_string(), "+QIOPEN:".to_string()],
+            Stack::Ublox => vec!["+UUSORD:".to_string(), "+UUSOCL:".to_string()],
+        }
+    }
+
+    /// `parse_urc` The socket event of a URC line, if it is one.
+    pub fn parse_urc(self, line: &str) -> Option<SocketUrc> {
+        let first = |value: &str| value.split(',').next()?.trim().parse().ok();
+        match self {
+            Stack::Sim800 => {
+                if let Some(rest) = line.strip_prefix("+CIPRXGET: 1,") {
+                    return rest.trim().parse().ok().map(SocketUrc::Data);
+                }
+                let socket = line.strip_suffix(", CLOSED")?;
+                socket.trim().parse().ok().map(SocketUrc::Closed)
+            }
+            Stack::Quectel => {
+                let rest = line.strip_prefix("+QIURC:")?.trim();
+                if let Some(socket) = rest.strip_prefix("\"recv\",") {
+                    first(socket).map(SocketUrc::Data)
+                } else if let Some(socket) = rest.strip_prefix("\"closed\",") {
+                    first(socket).map(SocketUrc::Closed)
+                } else {
+                    None
+                }
+            }
+            Stack::Ublox => {
+                if let Some(rest) = line.strip_prefix("+UUSORD:") {
+                    first(rest).map(SocketUrc::Data)
+                } else {
+                    first(line.strip_prefix("+UUSOCL:")?).map(SocketUrc::Closed)
+                }
+            }
+        }
+    }
+
+    /// Largest payload of one send command.
+    fn max_send(self) -> usize {
+        match self {
+            Stack::Sim800 | Stack::Quectel => 1460,
+            // hex mode
+            Stack::Ublox => 512,
+        }
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', ""))
+}
+
+/// `field` The comma separated `index`th value after the `prefix` of one of the lines.
+fn field<'a>(lines: &'a [String], prefix: &str, index: usize) -> Option<&'a str> {
+    lines
+        .iter()
+        .find_map(|line| line.strip_prefix(prefix))?
+        .split(',')
+        .nth(index)
+        .map(|value| value.trim().trim_matches('"'))
+}
+
+fn is_address(line: &str) -> bool {
+    !line.is_empty() && line.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// `connect` Attach to the packet network with the access point `apn`, returns the local IP
+/// address. The stack is configured for hex encoded receive data.
+#[allow(clippy::too_many_arguments)]
+pub fn connect(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    stack: Stack,
+    apn: &str,
+    user: &str,
+    password: &str,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<String, String> {
+    {
+        let mut engine = channel
+            .engine
+            .lock()
+            .map_err(|error| format!("Cannot get AT lock: {}", error))?;
+        for prefix in stack.urc_prefixes() {
+            if !engine.urc_prefixes.contains(&prefix) {
+                engine.urc_prefixes.push(prefix);
+            }
+        }
+        engine.cellular = Some(stack);
+    }
+    let mut run = |command: &str, timeout: Duration| {
+        check(channel.transact(port, reading, command, None, timeout, on_urc))
+    };
+    match stack {
+        Stack::Sim800 => {
+            let response = channel.transact_until(
+                port,
+                reading,
+                "AT+CIPSHUT",
+                None,
+                TIMEOUT,
+                Some(|line| line == "SHUT OK"),
+                on_urc,
+            )?;
+            if response.result != "SHUT OK" {
+                return Err(response.result);
+            }
+            let mut run = |command: &str, timeout: Duration| {
+                check(channel.transact(port, reading, command, None, timeout, on_urc))
+            };
+            run("AT+CIPMUX=1", TIMEOUT)?;
+            run("AT+CIPRXGET=1", TIMEOUT)?;
+            let command = format!("AT+CSTT={},{},{}", quote(apn), quote(user), quote(password));
+            run(&command, TIMEOUT)?;
+            run("AT+CIICR", NETWORK_TIMEOUT)?;
+            // answers with the address instead of OK
+            let response = channel.transact_until(
+                port,
+                reading,
+                "AT+CIFSR",
+                None,
+                TIMEOUT,
+                Some(is_address),
+                on_urc,
+            )?;
+            if is_address(&response.result) {
+                Ok(response.result)
+            } else {
+                Err(response.result)
+            }
+        }
+        Stack::Quectel => {
+            let authentication = if user.is_empty() { 0 } else { 1 };
+            let command = format!(
+                "AT+QICSGP=1,1,{},{},{},{}",
+                quote(apn),
+                quote(user),
+                quote(password),
+                authentication
+            );
+            run(&command, TIMEOUT)?;
+            run("AT+QICFG=\"dataformat\",0,1", TIMEOUT)?;
+            // fails when the context is already active
+            let activated = run("AT+QIACT=1", NETWORK_TIMEOUT);
+            let lines = run("AT+QIACT?", TIMEOUT)?;
+            match field(&lines, "+QIACT:", 3) {
+                Some(address) => Ok(address.to_string()),
+                None => Err(activated
+                    .err()
+                    .unwrap_or_else(|| "The PDP context is not active".to_string())),
+            }
+        }
+        Stack::Ublox => {
+            run("AT+UDCONF=1,1", TIMEOUT)?;
+            run(&format!("AT+CGDCONT=1,\"IP\",{}", quote(apn)), TIMEOUT)?;
+            if !user.is_empty() {
+                let command = format!("AT+UAUTHREQ=1,1,{},{}", quote(user), quote(password));
+                run(&command, TIMEOUT)?;
+            }
+            run("AT+CGACT=1,1", NETWORK_TIMEOUT)?;
+            let lines = run("AT+CGPADDR=1", TIMEOUT)?;
+            field(&lines, "+CGPADDR:", 1)
+                .map(str::to_string)
+                .ok_or_else(|| "The module did not return an address".to_string())
+        }
+    }
+}
+
+/// `open` Connect a socket to `host`, returns its number. `socket` picks the connection of the
+/// SIM800 and Quectel stacks, u-blox modules assign their own.
+#[allow(clippy::too_many_arguments)]
+pub fn open(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    stack: Stack,
+    protocol: Protocol,
+    host: &str,
+    remote_port: u16,
+    socket: u8,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<u8, String> {
+    let mut run = |command: &str, timeout: Duration| {
+        check(channel.transact(port, reading, command, None, timeout, on_urc))
+    };
+    let name = match protocol {
+        Protocol::Tcp => "\"TCP\"",
+        Protocol::Udp => "\"UDP\"",
+    };
+    let deadline = Instant::now() + NETWORK_TIMEOUT;
+    match stack {
+        Stack::Sim800 => {
+            let command = format!(
+                "AT+CIPSTART={},{},{},{}",
+                socket,
+                name,
+                quote(host),
+                remote_port
+            );
+            run(&command, TIMEOUT)?;
+            while Instant::now() < deadline {
+                let lines = run(&format!("AT+CIPSTATUS={}", socket), TIMEOUT)?;
+                match field(&lines, "+CIPSTATUS:", 5) {
+                    Some("CONNECTED") => return Ok(socket),
+                    Some("CLOSED") | Some("REMOTE CLOSING") => {
+                        return Err(format!("Cannot connect to {}:{}", host, remote_port));
+                    }
+                    _ => std::thread::sleep(POLL_INTERVAL),
+                }
+            }
+            Err(format!("Timeout connecting to {}:{}", host, remote_port))
+        }
+        Stack::Quectel => {
+            let command = format!(
+                "AT+QIOPEN=1,{},{},{},{},0,0",
+                socket,
+                name,
+                quote(host),
+                remote_port
+            );
+            run(&command, TIMEOUT)?;
+            while Instant::now() < deadline {
+                let lines = run(&format!("AT+QISTATE=1,{}", socket), TIMEOUT)?;
+                match field(&lines, "+QISTATE:", 5) {
+                    Some("2") => return Ok(socket),
+                    Some("1") => std::thread::sleep(POLL_INTERVAL),
+                    _ => return Err(format!("Cannot connect to {}:{}", host, remote_port)),
+                }
+            }
+            Err(format!("Timeout connecting to {}:{}", host, remote_port))
+        }
+        Stack::Ublox => {
+            let number = match protocol {
+                Protocol::Tcp => 6,
+                Protocol::Udp => 17,
+            };
+            let lines = run(&format!("AT+USOCR={}", number), TIMEOUT)?;
+            let socket = field(&lines, "+USOCR:", 0)
+                .and_then(|value| value.parse().ok())
+                .ok_or("The module did not return a socket")?;
+            // also sets the peer of UDP sockets, so they can use AT+USOWR and AT+USORD
+            let command = format!("AT+USOCO={},{},{}", socket, quote(host), remote_port);
+            if let Err(error) = run(&command, NETWORK_TIMEOUT) {
+                let _ = run(&format!("AT+USOCL={}", socket), TIMEOUT);
+                return Err(error);
+            }
+            Ok(socket)
+        }
+    }
+}
+
+/// `send` Write `data` to a connected socket, in several commands if it is long.
+pub fn send(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    stack: Stack,
+    socket: u8,
+    data: &[u8],
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<(), String> {
+    for chunk in data.chunks(stack.max_send()) {
+        match stack {
+            Stack::Sim800 | Stack::Quectel => {
+                let command = match stack {
+                    Stack::Sim800 => format!("AT+CIPSEND={},{}", socket, chunk.len()),
+                    _ => format!("AT+QISEND={},{}", socket, chunk.len()),
+                };
+                // SIM800 reports `0, SEND OK` in multi connection mode
+                let response = channel.transact_until(
+                    port,
+                    reading,
+                    &command,
+                    Some(chunk),
+                    NETWORK_TIMEOUT,
+                    Some(|line| line.ends_with("SEND OK") || line.ends_with("SEND FAIL")),
+                    on_urc,
+                )?;
+                if !response.result.ends_with("SEND OK") {
+                    return Err(response.result);
+                }
+            }
+            Stack::Ublox => {
+                let command = format!("AT+USOWR={},{},\"{}\"", socket, chunk.len(), to_hex(chunk));
+                check(channel.transact(port, reading, &command, None, NETWORK_TIMEOUT, on_urc))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `receive` Read everything the module buffered for the socket.
+pub fn receive(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    stack: Stack,
+    socket: u8,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    loop {
+        let (command, prefix) = match stack {
+            Stack::Sim800 => (format!("AT+CIPRXGET=3,{},730", socket), "+CIPRXGET:"),
+            Stack::Quectel => (format!("AT+QIRD={},1500", socket), "+QIRD:"),
+            Stack::Ublox => (format!("AT+USORD={},512", socket), "+USORD:"),
+        };
+        let lines = check(channel.transact(port, reading, &command, None, TIMEOUT, on_urc))?;
+        let header = lines
+            .iter()
+            .position(|line| line.starts_with(prefix))
+            .ok_or_else(|| format!("The module did not answer {}", command))?;
+        // `+CIPRXGET: 3,<socket>,<length>,<remaining>`, `+QIRD: <length>`,
+        // `+USORD: <socket>,<length>,"<data>"`
+        let length_index = match stack {
+            Stack::Sim800 => 2,
+            Stack::Quectel => 0,
+            Stack::Ublox => 1,
+        };
+        let length: usize = field(&lines, prefix, length_index)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(0);
+        if length == 0 {
+            break;
+        }
+        let hex = match stack {
+            Stack::Ublox => field(&lines, prefix, 2).unwrap_or(""),
+            _ => lines.get(header + 1).map(String::as_str).unwrap_or(""),
+        };
+        data.extend(from_hex(hex)?);
+        if stack == Stack::Sim800 && field(&lines, prefix, 3) == Some("0") {
+            break;
+        }
+    }
+    Ok(data)
+}
+
+/// `close` Close the socket.
+pub fn close(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    stack: Stack,
+    socket: u8,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<(), String> {
+    match stack {
+        Stack::Sim800 => {
+            let command = format!("AT+CIPCLOSE={}", socket);
+            let response = channel.transact_until(
+                port,
+                reading,
+                &command,
+                None,
+                TIMEOUT,
+                Some(|line| line.ends_with("CLOSE OK")),
+                on_urc,
+            )?;
+            if response.result.ends_with("CLOSE OK") {
+                Ok(())
+            } else {
+                Err(response.result)
+            }
+        }
+        Stack::Quectel => {
+            let command = format!("AT+QICLOSE={}", socket);
+            check(channel.transact(port, reading, &command, None, TIMEOUT, on_urc)).map(|_| ())
+        }
+        Stack::Ublox => {
+            let command = format!("AT+USOCL={}", socket);
+            check(channel.transact(port, reading, &command, None, TIMEOUT, on_urc)).map(|_| ())
+        }
+    }
+}
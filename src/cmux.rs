@@ -0,0 +1,416 @@
+//! The 3GPP TS 27.010 multiplexer (CMUX) of cellular modems, basic option: after `AT+CMUX=0`
+//! the modem port carries frames of several data link connections (DLCs), e.g. AT commands,
+//! PPP data and GNSS NMEA, each opened as its own port with a path like `mux://<port>:<dlci>`.
+//!
+//! DLC 0 is the control channel. The plugin is the initiator: it establishes the DLCs with
+//! `SABM`, announces its V.24 signals with `MSC` and answers the control commands of the modem.
+//! Stopping closes the multiplexer with `CLD`, which returns the modem to AT commands.
+
+use crate::mux::Demux;
+use crate::reader::{classify, ReadOutcome};
+use serialport::{ClearBuffer, SerialPort};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const FLAG: u8 = 0xf9;
+/// Poll/final bit of the control field.
+const PF: u8 = 0x10;
+const SABM: u8 = 0x2f;
+const UA: u8 = 0x63;
+const DM: u8 = 0x0f;
+const DISC: u8 = 0x43;
+const UIH: u8 = 0xef;
+const UI: u8 = 0x03;
+/// Types of the control channel messages, without EA and C/R bits.
+const CLD: u8 = 0x30;
+const MSC: u8 = 0x38;
+/// Largest DLCI of the basic option, DLC 0 is the control channel.
+pub const MAX_DLCI: u8 = 63;
+/// The default maximum frame size N1 of the basic option.
+pub const DEFAULT_FRAME_SIZE: usize = 31;
+/// Longest wait for the modem to answer a `SABM` (T1 and retries of the standard together).
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// Longest wait for the modem to answer the command switching it to CMUX mode.
+const SWITCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// `fcs` The frame check sequence over `data`: CRC-8 with the reversed polynomial 0xE0.
+fn fcs(data: &[u8]) -> u8 {
+    let mut crc = 0xffu8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xe0
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    0xff - crc
+}
+
+/// `push_length` A length field: one byte with the EA bit up to 127, two bytes beyond.
+fn push_length(frame: &mut Vec<u8>, length: usize) {
+    if length <= 127 {
+        frame.push((length as u8) << 1 | 1);
+    } else {
+        frame.push((length as u8 & 0x7f) << 1);
+        frame.push((length >> 7) as u8);
+    }
+}
+
+/// `frame` A frame from the initiator; commands have the C/R bit set, responses do not.
+fn frame(dlci: u8, control: u8, command: bool, data: &[u8]) -> Vec<u8> {
+    let mut frame = vec![FLAG, dlci << 2 | (command as u8) << 1 | 1, control];
+    push_length(&mut frame, data.len());
+    let header = frame.len();
+    frame.extend_from_slice(data);
+    // UIH frames are checked without their information field
+    let checked = if control & !PF == UIH {
+        &frame[1..header]
+    } else {
+        &frame[1..]
+    };
+    let fcs = fcs(checked);
+    frame.push(fcs);
+    frame.push(FLAG);
+    frame
+}
+
+/// `control_message` A message on the control channel, with its type, C/R bit and values.
+fn control_message(kind: u8, command: bool, values: &[u8]) -> Vec<u8> {
+    let mut message = vec![kind << 2 | (command as u8) << 1 | 1];
+    push_length(&mut message, values.len());
+    message.extend_from_slice(values);
+    frame(0, UIH, true, &message)
+}
+
+/// `encoder` Data written to a DLC as UIH frames of at most `frame_size` bytes.
+pub fn encoder(frame_size: usize) -> impl Fn(u8, &[u8]) -> Vec<u8> + Send + Sync {
+    move |dlci, data| {
+        data.chunks(frame_size.max(1))
+            .flat_map(|chunk| frame(dlci, UIH, true, chunk))
+            .collect()
+    }
+}
+
+/// `Frame` A frame received from the modem.
+struct Frame {
+    dlci: u8,
+    command: bool,
+    control: u8,
+    data: Vec<u8>,
+}
+
+/// `next_frame` The frame at the start of `raw` and its size, `Err` with the bytes to skip when
+/// there is none, `Ok(None)` until more is received.
+fn next_frame(raw: &[u8]) -> Result<Option<(Frame, usize)>, usize> {
+    let start = match raw.iter().position(|byte| *byte == FLAG) {
+        Some(start) => start,
+        None if raw.is_empty() => return Ok(None),
+        None => return Err(raw.len()),
+    };
+    if start > 0 {
+        return Err(start);
+    }
+    // flags between frames, or the closing flag of the previous one
+    if raw.get(1) == Some(&FLAG) {
+        return Err(1);
+    }
+    if raw.len() < 4 {
+        return Ok(None);
+    }
+    let (address, control) = (raw[1], raw[2]);
+    let (length, header) = if raw[3] & 1 == 1 {
+        ((raw[3] >> 1) as usize, 4)
+    } else {
+        match raw.get(4) {
+            Some(high) => (((raw[3] >> 1) as usize) | (*high as usize) << 7, 5),
+            None => return Ok(None),
+        }
+    };
+    let size = header + length + 2;
+    if raw.len() < size {
+        return Ok(None);
+    }
+    let checked = if control & !PF == UIH {
+        &raw[1..header]
+    } else {
+        &raw[1..header + length]
+    };
+    if address & 1 == 0 || raw[size - 1] != FLAG || fcs(checked) != raw[size - 2] {
+        return Err(1);
+    }
+    Ok(Some((
+        Frame {
+            dlci: address >> 2,
+            command: address & 2 != 0,
+            control: control & !PF,
+            data: raw[header..header + length].to_vec(),
+        },
+        size,
+    )))
+}
+
+/// `Session` A running multiplexer: its channels and the answers to the `SABM` sent.
+pub struct Session {
+    pub demux: Arc<Demux>,
+    /// `true` for `UA`, `false` for `DM`, per DLCI.
+    answers: Mutex<HashMap<u8, bool>>,
+    answered: Condvar,
+}
+
+impl Session {
+    pub fn new(demux: Arc<Demux>) -> Self {
+        Session {
+            demux,
+            answers: Mutex::new(HashMap::new()),
+            answered: Condvar::new(),
+        }
+    }
+
+    /// `connect` Establish a DLC and announce the signals of the host on it.
+    pub fn connect(&self, dlci: u8) -> Result<(), String> {
+        if let Ok(mut answers) = self.answers.lock() {
+            answers.remove(&dlci);
+        }
+        self.demux
+            .send(&frame(dlci, SABM | PF, true, &[]))
+            .map_err(|error| format!("Failed to write SABM: {}", error))?;
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        let mut answers = self
+            .answers
+            .lock()
+            .map_err(|error| format!("Cannot get lock: {}", error))?;
+        loop {
+            match answers.get(&dlci) {
+                Some(true) => break,
+                Some(false) => return Err(format!("The modem rejected DLC {}", dlci)),
+                None => {}
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(format!("The modem did not answer the SABM of DLC {}", dlci));
+            }
+            answers = self
+                .answered
+                .wait_timeout(answers, deadline - now)
+                .map_err(|error| format!("Cannot get lock: {}", error))?
+                .0;
+        }
+        drop(answers);
+        if dlci > 0 {
+            // DTR and RTS on, the data flows once the modem sees ready signals
+            let values = [dlci << 2 | 0x03, 0x0d];
+            self.demux
+                .send(&control_message(MSC, true, &values))
+                .map_err(|error| format!("Failed to write MSC: {}", error))?;
+        }
+        Ok(())
+    }
+
+    fn answer(&self, dlci: u8, accepted: bool) {
+        if let Ok(mut answers) = self.answers.lock() {
+            answers.insert(dlci, accepted);
+            self.answered.notify_all();
+        }
+    }
+
+    /// `control` Handle a message of the modem on the control channel, `false` once the modem
+    /// closed the multiplexer.
+    fn control(&self, data: &[u8]) -> bool {
+        let kind = match data.first() {
+            Some(kind) => *kind,
+            None => return true,
+        };
+        // responses of the modem to the own commands need nothing more
+        if kind & 2 == 0 {
+            return true;
+        }
+        // commands are answered with their values and the C/R bit cleared
+        let mut response = data.to_vec();
+        response[0] &= !2;
+        if let Err(error) = self.demux.send(&frame(0, UIH, true, &response)) {
+            warn!("Failed to answer CMUX control command: {}", error);
+        }
+        kind >> 2 != CLD
+    }
+
+    /// `handle` A frame of the modem, `false` once the multiplexer was closed.
+    fn handle(&self, frame: Frame) -> bool {
+        match frame.control {
+            UA => self.answer(frame.dlci, true),
+            DM => self.answer(frame.dlci, false),
+            DISC if frame.command => {
+                if let Err(error) = self.demux.send(&frame_response(frame.dlci, UA)) {
+                    warn!("Failed to answer DISC: {}", error);
+                }
+                if frame.dlci == 0 {
+                    return false;
+                }
+            }
+            UIH | UI if frame.dlci == 0 => return self.control(&frame.data),
+            UIH | UI => self.demux.deliver(frame.dlci, &frame.data),
+            _ => debug!(control = frame.control, "CMUX frame ignored"),
+        }
+        true
+    }
+}
+
+fn frame_response(dlci: u8, control: u8) -> Vec<u8> {
+    frame(dlci, control | PF, false, &[])
+}
+
+/// `switch` Send the command switching the modem to CMUX mode and wait for its `OK`.
+pub fn switch(port: &mut dyn SerialPort, command: &str) -> Result<(), String> {
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    port.write_all(format!("{}\r", command).as_bytes())
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write {}: {}", command, error))?;
+    let deadline = Instant::now() + SWITCH_TIMEOUT;
+    let mut received = Vec::new();
+    let mut buf = [0u8; 256];
+    while Instant::now() < deadline {
+        match port.read(&mut buf) {
+            Ok(size) => received.extend_from_slice(&buf[..size]),
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read the answer: {}", error)),
+        }
+        let text = String::from_utf8_lossy(&received);
+        if text.lines().any(|line| line.trim() == "OK") {
+            return Ok(());
+        }
+        if text.lines().any(|line| line.trim().contains("ERROR")) {
+            return Err(format!("The modem refused {}: {}", command, text.trim()));
+        }
+    }
+    Err(format!("The modem did not answer {}", command))
+}
+
+/// `run` Read the modem port and handle its frames until `stop` is dropped, the modem closes
+/// the multiplexer or the port fails. A stopped multiplexer is closed on the modem as well.
+pub fn run(mut port: Box<dyn SerialPort>, session: Arc<Session>, stop: Receiver<()>) {
+    let mut buf = [0u8; 4096];
+    let mut raw = Vec::new();
+    let reason = 'read: loop {
+        if !matches!(stop.try_recv(), Err(TryRecvError::Empty)) {
+            let close = control_message(CLD, true, &[]);
+            if let Err(error) = session.demux.send(&close) {
+                warn!("Failed to close the CMUX multiplexer: {}", error);
+            }
+            break "The multiplexer was stopped".to_string();
+        }
+        match classify(port.read(&mut buf)) {
+            ReadOutcome::Data(size) => raw.extend_from_slice(&buf[..size]),
+            ReadOutcome::Idle => continue,
+            ReadOutcome::Disconnected(reason) => break format!("Port disconnected: {}", reason),
+            ReadOutcome::Failed(error) => break format!("Failed to read port: {}", error),
+        }
+        let mut start = 0;
+        loop {
+            match next_frame(&raw[start..]) {
+                Ok(Some((frame, size))) => {
+                    start += size;
+                    if !session.handle(frame) {
+                        break 'read "The modem closed the multiplexer".to_string();
+                    }
+                }
+                Ok(None) => break,
+                Err(skipped) => start += skipped,
+            }
+        }
+        raw.drain(..start);
+    };
+    info!(reason = %reason, "CMUX multiplexer stopped");
+    session.demux.close(reason);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_frames() {
+        assert_eq!(fcs(&[0x03, 0x3f, 0x01]), 0x1c);
+        assert_eq!(
+            frame(0, SABM | PF, true, &[]),
+            vec![0xf9, 0x03, 0x3f, 0x01, 0x1c, 0xf9]
+        );
+        assert_eq!(
+            frame(1, SABM | PF, true, &[]),
+            vec![0xf9, 0x07, 0x3f, 0x01, 0xde, 0xf9]
+        );
+        assert_eq!(
+            frame(1, DISC | PF, true, &[]),
+            vec![0xf9, 0x07, 0x53, 0x01, 0x3f, 0xf9]
+        );
+        assert_eq!(
+            control_message(CLD, true, &[]),
+            vec![0xf9, 0x03, 0xef, 0x05, 0xc3, 0x01, 0xf2, 0xf9]
+        );
+        // the FCS of UIH frames leaves out the information field
+        assert_eq!(
+            frame(1, UIH, true, b"AT\r"),
+            vec![0xf9, 0x07, 0xef, 0x07, b'A', b'T', b'\r', 0xd3, 0xf9]
+        );
+    }
+
+    #[test]
+    fn parse_frames() {
+        let (ua, size) = next_frame(&[0xf9, 0x03, 0x73, 0x01, 0xd7, 0xf9])
+            .unwrap()
+            .unwrap();
+        assert_eq!(size, 6);
+        assert_eq!((ua.dlci, ua.command, ua.control), (0, true, UA));
+        assert!(ua.data.is_empty());
+
+        let data: Vec<u8> = (0..300).map(|index| index as u8).collect();
+        let long = frame(2, UIH, false, &data);
+        assert_eq!(&long[3..5], &[0x58, 0x02]);
+        let (uih, size) = next_frame(&long).unwrap().unwrap();
+        assert_eq!(size, long.len());
+        assert_eq!((uih.dlci, uih.command, uih.control), (2, false, UIH));
+        assert_eq!(uih.data, data);
+
+        let encoded = encoder(DEFAULT_FRAME_SIZE)(3, &data);
+        let mut raw = &encoded[..];
+        let mut received = Vec::new();
+        while let Ok(Some((frame, size))) = next_frame(raw) {
+            assert!(frame.data.len() <= DEFAULT_FRAME_SIZE);
+            received.extend(frame.data);
+            raw = &raw[size..];
+        }
+        assert!(raw.is_empty());
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn skip_noise_and_damaged_frames() {
+        assert!(matches!(next_frame(&[]), Ok(None)));
+        assert!(matches!(next_frame(b"OK\r\n"), Err(4)));
+        assert!(matches!(next_frame(b"\r\n\xf9\x03"), Err(2)));
+        assert!(matches!(next_frame(&[0xf9, 0xf9, 0x03]), Err(1)));
+        assert!(matches!(
+            next_frame(&[0xf9, 0x03, 0x73, 0x01, 0xd7]),
+            Ok(None)
+        ));
+        assert!(matches!(next_frame(&[0xf9, 0x07, 0xef, 0x00]), Ok(None)));
+        // wrong FCS, missing closing flag, address without EA bit
+        assert!(matches!(
+            next_frame(&[0xf9, 0x03, 0x73, 0x01, 0xd6, 0xf9]),
+            Err(1)
+        ));
+        assert!(matches!(
+            next_frame(&[0xf9, 0x03, 0x73, 0x01, 0xd7, 0x00]),
+            Err(1)
+        ));
+        let mut ua = frame(0, UA, true, &[]);
+        ua[1] &= !1;
+        ua[4] = fcs(&ua[1..4]);
+        assert!(matches!(next_frame(&ua), Err(1)));
+    }
+}
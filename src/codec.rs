@@ -0,0 +1,16 @@
+//! Framing of application protocols, implemented by the host application.
+
+/// `Codec` Splits the received stream into frames and frames outgoing messages.
+///
+/// Register a factory with [`Builder::codec`](crate::Builder::codec); every read of a port
+/// using the codec gets its own instance, so it may keep partial frames between reads.
+pub trait Codec: Send {
+    /// Consume received bytes, returns the frames they complete.
+    fn decode(&mut self, data: &[u8]) -> Vec<Vec<u8>>;
+
+    /// The bytes to write for one outgoing frame.
+    fn encode(&mut self, frame: &[u8]) -> Vec<u8>;
+}
+
+/// `CodecFactory` Creates a codec instance for one port.
+pub type CodecFactory = dyn Fn() -> Box<dyn Codec> + Send + Sync;
@@ -1,11 +1,84 @@
+use crate::ack::{self, Expectation, SharedExpectation};
+use crate::address::{AddressFilter, Filter};
+use crate::at::{AtChannel, Urc};
+use crate::autoopen::AutoOpen;
+use crate::backpressure::{Backpressure, Offered, WhenHidden, DEFAULT_LIMIT};
+use crate::benchmark::{self, Latency, Throughput};
+use crate::capabilities::{Capabilities, Requested, Validation};
+use crate::capture::Capture;
+use crate::cellular::{self, Protocol, SocketUrc, Stack};
+use crate::cmux::{self, Session};
+use crate::codec::{Codec, CodecFactory};
+use crate::compression::{self, Compression, CompressionStats};
+use crate::consumer::{Consumed, Stream};
+use crate::decode::{decode_frame, Decoder, Format};
+use crate::diagnostics::{find_port_holder, DiagnoseReport};
+use crate::dmx::{self, Dmx};
+use crate::encryption::{EncryptionStats, Link};
 use crate::error::Error;
-use crate::state::{ReadData, SerialportInfo, SerialportState};
-use serialport::{DataBits, FlowControl, Parity, StopBits, SerialPortType, UsbPortInfo};
+use crate::escpos::{self, PrinterStatus};
+use crate::exchange::ExchangePort;
+use crate::firmata::{self, FirmataMessage, FirmataParser, FirmataVersion};
+#[cfg(feature = "ftdi")]
+use crate::ftdi::{BitMode, FtdiDevice};
+use crate::gateway;
+use crate::label::{self, LabelLanguage, LabelStatus};
+use crate::lin::{self, ChecksumModel};
+use crate::linestatus::{self, LineErrors, LineStatus, Marked};
+use crate::log::{level_filter_to_u8, log_traffic, parse_level_filter};
+use crate::lora::{self, Activation, Module};
+use crate::mdb::{self, Bus, Mdb, MdbEvent, Response};
+use crate::meter::{self, MeterReading};
+use crate::middleware::{self, Chain, SharedChain};
+use crate::midi::{MidiMessage, MidiParser};
+use crate::mux::{self, Deframer, Demux, Mux};
+use crate::obd::{self, ObdInfo, PidValue};
+use crate::pcapng::{self, PcapWriter};
+use crate::poller::{Poll, Poller};
+use crate::profile::{is_profile_name, Profile};
+use crate::provision::{self, Document, ImportReport};
+use crate::power::Clock;
+use crate::queue::{Priority, QueueStatus, TxQueue};
+use crate::reactor;
+use crate::reader::{
+    classify, now_millis, History, ReadBatch, ReadOutcome, ReadThrottle, RingBuffer, Stamp,
+    ThrottlePolicy,
+};
+use crate::rs485::{Access, Direction, HalfDuplex, NativeRs485};
+use crate::schema::{Parsed, Schema, SchemaParser};
+use crate::scpi;
+use crate::selector::Selector;
+use crate::sms;
+use crate::store::{self, SavedSettings, SettingsStore};
+use crate::transfer::{self, Framing, Until};
+use crate::traffic::Tap;
+use crate::transform::{record_echo, Pipeline};
+use crate::ubx::{self, UbxConfig, UbxFrame, UbxMessage, UbxParser};
+use crate::watcher::{Matcher, Watcher};
+use crate::xbee::{self, XBeeFrame, XBeeParser};
+use crate::state::{
+    AckData, AdapterInfo, AtResponse, AutoOpenedData, BreakData, BufferOverflowData, CancelledData,
+    CaptureData, DataReadyData, DecodeError, DecodedData, DisconnectData, FetchData, FirmataData,
+    FrameData, HistoryFrame, IdleData, LoraDownlink, ManagedPort, MatchData, MdbData, MidiData,
+    MonitorData, NamedProfile, OverrunData, PacketData, PacketError, PollData, ProfileOpen,
+    ProgressData, QueueData, ReadData, ReadError, ResumedData, SavedOpen, SerialportInfo,
+    SerialportState, SmsMessage, SocketClosed, SocketData, SuspendedData, UbxData, UrcData,
+    Worker, XBeeData,
+};
+use serialport::{
+    ClearBuffer, DataBits, FlowControl, Parity, SerialPort, SerialPortType, StopBits, UsbPortInfo,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
 use std::sync::mpsc;
-use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
-use tauri::{command, AppHandle, Runtime, State, Window};
+use std::time::{Duration, Instant};
+use tauri::{command, AppHandle, Manager, Runtime, State, Window, WindowEvent};
+use thread_priority::{set_current_thread_priority, ThreadPriority, ThreadPriorityValue};
 use serde::Serialize;
 
 /// `get_worksheet` Get the file sheet instance according to `path` and `sheet_name`.
@@ -60,6 +133,52 @@ fn get_parity(value: Option<String>) -> Parity {
     }
 }
 
+/// `get_stick_parity` Mark (`true`) or Space (`false`) parity, which `serialport` cannot set
+fn get_stick_parity(value: &Option<String>) -> Option<bool> {
+    match value.as_deref() {
+        Some("Mark") => Some(true),
+        Some("Space") => Some(false),
+        _ => None,
+    }
+}
+
+/// `set_stick_parity` Switch a native port to Mark or Space parity through the platform API
+fn set_stick_parity(native: Option<isize>, mark: bool) -> io::Result<()> {
+    match native {
+        #[cfg(unix)]
+        Some(fd) => crate::termios::set_stick_parity(fd as i32, mark),
+        #[cfg(windows)]
+        Some(handle) => crate::commstate::set_stick_parity(handle, mark),
+        _ => Err(io::Error::new(io::ErrorKind::Unsupported, "not a native port")),
+    }
+}
+
+/// `accepts_stick_parity` Whether a native port can be set to Mark and Space parity
+fn accepts_stick_parity(native: Option<isize>) -> bool {
+    match native {
+        #[cfg(unix)]
+        Some(fd) => crate::termios::accepts_stick_parity(fd as i32).unwrap_or(false),
+        #[cfg(windows)]
+        Some(_) => true,
+        _ => false,
+    }
+}
+
+/// `set_native_rs485` Enable the RS-485 mode of the UART driver of a native Linux port
+fn set_native_rs485(native: Option<isize>, rs485: &NativeRs485) -> io::Result<()> {
+    match native {
+        #[cfg(target_os = "linux")]
+        Some(fd) => crate::termios::set_rs485(fd as i32, rs485),
+        _ => {
+            let _ = rs485;
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "TIOCSRS485 is only available on Linux",
+            ))
+        }
+    }
+}
+
 fn get_stop_bits(value: Option<usize>) -> StopBits {
     match value {
         Some(value) => match value {
@@ -70,7 +189,91 @@ fn get_stop_bits(value: Option<usize>) -> StopBits {
         None => StopBits::Two,
     }
 }
-#[derive(Debug, Clone, Serialize)]
+
+/// `get_line_settings` Line settings for ports that are not opened through `serialport`
+fn get_line_settings(
+    baud_rate: u32,
+    data_bits: Option<usize>,
+    flow_control: Option<String>,
+    parity: Option<String>,
+    stop_bits: Option<usize>,
+    timeout: Duration,
+) -> crate::transport::LineSettings {
+    crate::transport::LineSettings {
+        data_bits: get_data_bits(data_bits),
+        flow_control: get_flow_control(flow_control),
+        parity: get_parity(parity),
+        stop_bits: get_stop_bits(stop_bits),
+        ..crate::transport::LineSettings::new(baud_rate, timeout)
+    }
+}
+
+fn get_throttle_policy(value: Option<String>) -> ThrottlePolicy {
+    match value {
+        Some(value) => match value.as_str() {
+            "DropOldest" => ThrottlePolicy::DropOldest,
+            "DropNewest" => ThrottlePolicy::DropNewest,
+            _ => ThrottlePolicy::Merge,
+        },
+        None => ThrottlePolicy::Merge,
+    }
+}
+
+fn get_thread_priority(value: Option<String>) -> Option<ThreadPriority> {
+    match value {
+        Some(value) => match value.as_str() {
+            "Min" => Some(ThreadPriority::Min),
+            "Low" => ThreadPriorityValue::try_from(25).ok().map(ThreadPriority::Crossplatform),
+            "High" => ThreadPriorityValue::try_from(75).ok().map(ThreadPriority::Crossplatform),
+            "Max" | "Realtime" => Some(ThreadPriority::Max),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+/// `set_realtime_priority` Switch the current thread to a real-time scheduling class.
+#[cfg(unix)]
+fn set_realtime_priority() -> Result<(), thread_priority::Error> {
+    use thread_priority::{
+        set_thread_priority_and_policy, thread_native_id, RealtimeThreadSchedulePolicy,
+        ThreadSchedulePolicy,
+    };
+    set_thread_priority_and_policy(
+        thread_native_id(),
+        ThreadPriority::Max,
+        ThreadSchedulePolicy::Realtime(RealtimeThreadSchedulePolicy::Fifo),
+    )
+}
+
+/// `set_realtime_priority` Switch the current thread to a real-time scheduling class.
+#[cfg(windows)]
+fn set_realtime_priority() -> Result<(), thread_priority::Error> {
+    use thread_priority::WinAPIThreadPriority;
+    set_current_thread_priority(ThreadPriority::Os(WinAPIThreadPriority::TimeCritical.into()))
+}
+
+/// `set_read_thread_priority` Apply the requested priority to the current read thread.
+/// Real-time classes usually need elevated privileges, so fall back to the highest normal priority.
+fn set_read_thread_priority(value: Option<String>) {
+    let realtime = value.as_deref() == Some("Realtime");
+    let priority = match get_thread_priority(value) {
+        Some(priority) => priority,
+        None => return,
+    };
+    if realtime {
+        match set_realtime_priority() {
+            Ok(_) => return,
+            Err(error) => {
+                warn!("Cannot use real-time priority: {:?}", error);
+            }
+        }
+    }
+    if let Err(error) = set_current_thread_priority(priority) {
+        warn!("Cannot set read thread priority: {:?}", error);
+    }
+}
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct SerialPortInfo {
     port_name: String,
     port_type: String,
@@ -79,6 +282,30 @@ pub struct SerialPortInfo {
     manufacturer: Option<String>,
     product: Option<String>,
     serial_number: Option<String>,
+    /// USB bus the device is attached to.
+    bus: Option<String>,
+    /// USB device address on the bus, changes when the device is replugged.
+    address: Option<u8>,
+    /// Physical port as `<bus>-<port>.<port>...` following the hub chain, stable for a given socket.
+    port_path: Option<String>,
+    /// USB interface number, tells apart the ports of a composite device.
+    interface: Option<u8>,
+    /// Device Manager name on Windows, e.g. `USB Serial Port (COM7)`.
+    friendly_name: Option<String>,
+    /// Windows device instance ID, e.g. `FTDIBUS\VID_0403+PID_6001+A702TB52A\0000`.
+    instance_id: Option<String>,
+    /// Windows driver service, e.g. `FTDIBUS`, `usbser` or `silabser`.
+    driver: Option<String>,
+    /// Stable `/dev/serial/by-id/...` link on Linux, survives reboots and replugging.
+    by_id: Option<String>,
+    /// Stable `/dev/serial/by-path/...` link on Linux, tied to the physical socket.
+    by_path: Option<String>,
+    /// The `tty.*` node of the port on macOS, `port_name` is the `cu.*` node.
+    dialin_path: Option<String>,
+    /// USB interface name from IOKit on macOS, names the function of a composite device.
+    interface_name: Option<String>,
+    /// IOKit `locationID` on macOS, e.g. `0x14100000`.
+    location_id: Option<String>,
 }
 
 fn port_type_to_string(port_type: &SerialPortType) -> String {
@@ -97,6 +324,24 @@ fn port_info_to_serial_port_info(port_info: &UsbPortInfo, port_name: &str) -> Se
     let manufacturer = port_info.manufacturer.as_ref().unwrap_or(&default_manufacturer).to_owned();
     let product = port_info.product.as_ref().unwrap_or(&default_manufacturer).to_owned();
     let serial_number = port_info.serial_number.clone();
+    let bus = port_info.location.as_ref().map(|location| {
+        // udev reports the bus number zero padded, e.g. `001`
+        match location.bus_id().parse::<u32>() {
+            Ok(bus) => bus.to_string(),
+            Err(_) => location.bus_id().to_string(),
+        }
+    });
+    let port_path = match (&bus, &port_info.location) {
+        (Some(bus), Some(location)) if !location.port_chain().is_empty() => {
+            let chain: Vec<String> = location
+                .port_chain()
+                .iter()
+                .map(|port| port.to_string())
+                .collect();
+            Some(format!("{}-{}", bus, chain.join(".")))
+        }
+        _ => None,
+    };
     SerialPortInfo {
         port_name: port_name.to_owned(),
         port_type: format!("USB"),
@@ -105,9 +350,206 @@ fn port_info_to_serial_port_info(port_info: &UsbPortInfo, port_name: &str) -> Se
         manufacturer: Some(manufacturer),
         product: Some(product),
         serial_number,
+        bus,
+        address: usb_device_address(port_name),
+        port_path,
+        interface: port_info.interface,
+        ..SerialPortInfo::default()
+    }
+}
+
+/// `usb_device_address` Read `devnum` of the USB device above the tty in sysfs
+#[cfg(target_os = "linux")]
+fn usb_device_address(port_name: &str) -> Option<u8> {
+    let device = std::fs::canonicalize(port_name).ok()?;
+    let name = device.file_name()?.to_str()?;
+    let sysfs = std::fs::canonicalize(format!("/sys/class/tty/{}/device", name)).ok()?;
+    sysfs
+        .ancestors()
+        .find(|path| path.join("devnum").exists())
+        .and_then(|path| std::fs::read_to_string(path.join("devnum")).ok())
+        .and_then(|devnum| devnum.trim().parse().ok())
+}
+
+/// `usb_device_address` Only exposed through sysfs
+#[cfg(not(target_os = "linux"))]
+fn usb_device_address(_port_name: &str) -> Option<u8> {
+    None
+}
+
+/// `serial_links` Map device nodes to the udev links in `/dev/serial/by-id` or `by-path`
+#[cfg(target_os = "linux")]
+fn serial_links(directory: &str) -> std::collections::HashMap<std::path::PathBuf, String> {
+    let entries = match std::fs::read_dir(directory) {
+        Ok(entries) => entries,
+        // missing when no serial device is plugged in
+        Err(_) => return Default::default(),
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let link = entry.path();
+            let device = std::fs::canonicalize(&link).ok()?;
+            Some((device, link.to_str()?.to_string()))
+        })
+        .collect()
+}
+
+/// `refuse_worker` Fail when a thread of its own reads or writes the port, `allowed` excepted
+fn refuse_worker(
+    serialport_info: &SerialportInfo,
+    path: &str,
+    allowed: Option<Worker>,
+) -> Result<(), Error> {
+    match serialport_info.worker() {
+        Some(worker) if Some(worker) == allowed => Ok(()),
+        Some(Worker::Read) => Err(Error::String(format!(
+            "Port {} is reading, cancel the read first",
+            path
+        ))),
+        Some(worker) => Err(Error::String(format!(
+            "Port {} is driven by its {}, stop it first",
+            path,
+            worker.name()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// A port to probe, its native handle and the Mark or Space parity it had
+type ProbePort = (Box<dyn SerialPort>, Option<isize>, Option<bool>);
+
+/// `get_probe_port` A clone of the open port to try settings on with its native handle and
+/// stick parity, `None` when the port is closed and `open_probe_port` opens it. No thread of
+/// the port may use it meanwhile
+fn get_probe_port(
+    state: State<'_, SerialportState>,
+    path: &str,
+) -> Result<Option<ProbePort>, Error> {
+    let path = path.to_string();
+    let is_open = match state.serialports.lock() {
+        Ok(serialports) => serialports.contains_key(&path),
+        Err(error) => return Err(Error::String(format!("Cannot get lock: {}", error))),
+    };
+    if !is_open {
+        return Ok(None);
+    }
+    get_serialport(state, path.clone(), |serialport_info| {
+        refuse_worker(serialport_info, &path, None)?;
+        let port = serialport_info
+            .serialport
+            .try_clone()
+            .map_err(|error| Error::String(format!("Failed to clone port {}: {}", path, error)))?;
+        Ok(Some((
+            port,
+            serialport_info.native,
+            serialport_info.stick_parity,
+        )))
+    })
+}
+
+/// `open_probe_port` Open a closed port for a probe, it is closed again when dropped
+fn open_probe_port(path: &str) -> Result<ProbePort, Error> {
+    let builder = serialport::new(path, 9600);
+    #[cfg(unix)]
+    let opened = builder.open_native().map(|serial| {
+        let fd = std::os::unix::io::AsRawFd::as_raw_fd(&serial);
+        (Box::new(serial) as Box<dyn SerialPort>, Some(fd as isize))
+    });
+    #[cfg(windows)]
+    let opened = builder.open_native().map(|serial| {
+        let handle = std::os::windows::io::AsRawHandle::as_raw_handle(&serial);
+        (
+            Box::new(serial) as Box<dyn SerialPort>,
+            Some(handle as isize),
+        )
+    });
+    #[cfg(not(any(unix, windows)))]
+    let opened = builder.open().map(|serial| (serial, None));
+    let (port, native) = opened.map_err(|error| {
+        let os_error = io::Error::last_os_error();
+        get_open_error(path, error, os_error)
+    })?;
+    Ok((port, native, None))
+}
+
+/// `restore_stick_parity` Set a probed port back to the Mark or Space parity it had
+fn restore_stick_parity(path: &str, native: Option<isize>, stick_parity: Option<bool>) {
+    if let Some(mark) = stick_parity {
+        if let Err(error) = set_stick_parity(native, mark) {
+            warn!(path = %path, "Failed to restore Mark or Space parity: {}", error);
+        }
     }
 }
 
+/// `capabilities` The baud rates, data bits, parities and stop bits the driver of the port
+/// accepts. An open port must not be read or written by a thread of its own and keeps its
+/// settings, a closed one is opened for the probe
+#[command]
+pub async fn capabilities<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<Capabilities, Error> {
+    let open = get_probe_port(state, &path)?;
+    blocking(move || {
+        let (mut port, native, stick_parity) = match open {
+            Some(open) => open,
+            None => open_probe_port(&path)?,
+        };
+        #[cfg(windows)]
+        if let Some(handle) = native {
+            if let Ok(capabilities) = crate::commstate::capabilities(handle as _) {
+                return Ok(capabilities);
+            }
+        }
+        let capabilities = crate::capabilities::probe(port.as_mut(), accepts_stick_parity(native));
+        restore_stick_parity(&path, native, stick_parity);
+        debug!(path = %path, rates = capabilities.baud_rates.len(), "Probed line settings");
+        Ok(capabilities)
+    })
+    .await
+}
+
+/// `validate_settings` Apply the settings of `open` to the port one by one and report which the
+/// driver rejected. An open port must not be read or written by a thread of its own and keeps its
+/// settings, a closed one is opened for the check and closed again
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn validate_settings<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    baud_rate: u32,
+    data_bits: Option<usize>,
+    flow_control: Option<String>,
+    parity: Option<String>,
+    stop_bits: Option<usize>,
+) -> Result<Validation, Error> {
+    let open = get_probe_port(state, &path)?;
+    let requested = Requested {
+        baud_rate,
+        data_bits,
+        flow_control,
+        parity,
+        stop_bits,
+    };
+    blocking(move || {
+        let (mut port, native, stick_parity) = match open {
+            Some(open) => open,
+            None => open_probe_port(&path)?,
+        };
+        let validation =
+            crate::capabilities::validate(port.as_mut(), &requested, accepts_stick_parity(native));
+        restore_stick_parity(&path, native, stick_parity);
+        debug!(path = %path, valid = validation.valid, "Validated line settings");
+        Ok(validation)
+    })
+    .await
+}
+
 /// `available_ports` Get available serial ports
 #[command]
 pub fn available_ports() -> Vec<SerialPortInfo> {
@@ -116,10 +558,12 @@ pub fn available_ports() -> Vec<SerialPortInfo> {
         Err(_) => vec![],
     };
     list.sort_by(|a, b| a.port_name.cmp(&b.port_name));
-    
-    println!("Available ports: {:?}", list);
-        
-    list.iter()
+
+    debug!("Available ports: {:?}", list);
+
+    #[allow(unused_mut)]
+    let mut ports: Vec<SerialPortInfo> = list
+        .iter()
         .map(|port| {
             match &port.port_type {
                 SerialPortType::UsbPort(info) => {
@@ -132,14 +576,171 @@ pub fn available_ports() -> Vec<SerialPortInfo> {
                     pid: None,
                     manufacturer: None,
                     product: None,
-                    serial_number: None,
+                    ..SerialPortInfo::default()
                 },
             }
         })
-        .collect()
+        .collect();
+    #[cfg(target_os = "linux")]
+    {
+        let by_id = serial_links("/dev/serial/by-id");
+        let by_path = serial_links("/dev/serial/by-path");
+        for port in ports.iter_mut() {
+            if let Ok(device) = std::fs::canonicalize(&port.port_name) {
+                port.by_id = by_id.get(&device).cloned();
+                port.by_path = by_path.get(&device).cloned();
+            }
+        }
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // every port has a callout (`cu.*`) and a dialin (`tty.*`) node, list it once under
+        // the callout node, which opens without waiting for carrier detect
+        let names: Vec<String> = ports.iter().map(|port| port.port_name.clone()).collect();
+        ports.retain(|port| match port.port_name.strip_prefix("/dev/tty.") {
+            Some(name) => !names.contains(&format!("/dev/cu.{}", name)),
+            None => true,
+        });
+        let interfaces = crate::ioreg::usb_interfaces();
+        for port in ports.iter_mut() {
+            if let Some(name) = port.port_name.strip_prefix("/dev/cu.") {
+                let dialin_path = format!("/dev/tty.{}", name);
+                if names.contains(&dialin_path) {
+                    port.dialin_path = Some(dialin_path);
+                }
+            }
+            if let Some(interface) = interfaces.get(&port.port_name) {
+                port.interface_name = interface.interface_name.clone();
+                port.location_id = interface.location_id.clone();
+            }
+        }
+    }
+    #[cfg(windows)]
+    {
+        let mut details = crate::setupapi::port_details();
+        for port in ports.iter_mut() {
+            if let Some(details) = details.remove(&port.port_name) {
+                port.friendly_name = details.friendly_name;
+                port.instance_id = details.instance_id;
+                port.driver = details.driver;
+            }
+        }
+    }
+    #[cfg(feature = "bluetooth")]
+    ports.extend(
+        crate::bluetooth::paired_devices()
+            .into_iter()
+            .map(|device| SerialPortInfo {
+                port_name: device.path,
+                port_type: "Bluetooth".to_string(),
+                vid: None,
+                pid: None,
+                manufacturer: None,
+                product: Some(device.name),
+                serial_number: None,
+                ..SerialPortInfo::default()
+            }),
+    );
+    #[cfg(feature = "ble")]
+    ports.extend(
+        crate::ble::available_ports()
+            .into_iter()
+            .map(|device| SerialPortInfo {
+                port_name: device.path,
+                port_type: "BLE".to_string(),
+                vid: None,
+                pid: None,
+                manufacturer: None,
+                product: device.name,
+                serial_number: None,
+                ..SerialPortInfo::default()
+            }),
+    );
+    #[cfg(feature = "hid")]
+    ports.extend(
+        crate::hid::available_ports()
+            .into_iter()
+            .map(|device| SerialPortInfo {
+                port_name: device.path,
+                port_type: "HID".to_string(),
+                vid: Some(format!("{:04x}", device.vid)),
+                pid: Some(format!("{:04x}", device.pid)),
+                manufacturer: device.manufacturer,
+                product: device.product,
+                serial_number: device.serial_number,
+                ..SerialPortInfo::default()
+            }),
+    );
+    #[cfg(feature = "usb")]
+    ports.extend(
+        crate::usb::available_ports()
+            .into_iter()
+            .map(|device| SerialPortInfo {
+                port_name: device.path,
+                port_type: "CDC-ACM".to_string(),
+                vid: Some(format!("{:04x}", device.vid)),
+                pid: Some(format!("{:04x}", device.pid)),
+                manufacturer: device.manufacturer,
+                product: device.product,
+                serial_number: device.serial_number,
+                bus: Some(device.bus.to_string()),
+                address: Some(device.address),
+                interface: Some(device.interface),
+                ..SerialPortInfo::default()
+            }),
+    );
+    ports
+}
+
+
+
+/// `adapter_info` Read vendor data of the USB adapter behind a port (FTDI EEPROM strings, CP210x part and firmware)
+#[command]
+pub fn adapter_info(path: String) -> Result<AdapterInfo, Error> {
+    #[cfg(feature = "usb")]
+    {
+        crate::adapter::adapter_info(&path)
+    }
+    #[cfg(not(feature = "usb"))]
+    {
+        let _ = path;
+        Err(Error::String("adapter_info needs the `usb` feature".to_string()))
+    }
 }
 
+/// `usb_reset` Reset the USB device behind a closed port to recover a hanging adapter
+#[command]
+pub fn usb_reset<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<(), Error> {
+    match state.serialports.lock() {
+        Ok(serialports) if serialports.contains_key(&path) => {
+            return Err(Error::String(format!(
+                "Port {} is opened, close it before resetting the device",
+                path
+            )));
+        }
+        Ok(_) => {}
+        Err(error) => return Err(Error::String(format!("Cannot get lock: {}", error))),
+    }
+    #[cfg(feature = "usb")]
+    {
+        crate::adapter::reset(&path)
+    }
+    #[cfg(not(feature = "usb"))]
+    {
+        Err(Error::String("usb_reset needs the `usb` feature".to_string()))
+    }
+}
 
+/// `diagnose` Check device permissions, group membership, udev and brltty for a port
+#[command]
+pub fn diagnose(path: String) -> DiagnoseReport {
+    crate::diagnostics::diagnose(&path)
+}
 
 /// `cacel_read` Cancel read data from serial port
 #[command]
@@ -149,6 +750,7 @@ pub async fn cancel_read<R: Runtime>(
     state: State<'_, SerialportState>,
     path: String,
 ) -> Result<(), Error> {
+    let reactor = state.reactor.clone();
     get_serialport(state, path.clone(), |serialport_info| {
         match &serialport_info.sender {
             Some(sender) => match sender.send(1) {
@@ -160,7 +762,55 @@ pub async fn cancel_read<R: Runtime>(
             None => {}
         }
         serialport_info.sender = None;
-        println!("Canceled read data from {}", &path);
+        info!(path = %path, "Canceled read data");
+        Ok(())
+    })?;
+    reactor.wake();
+    Ok(())
+}
+
+fn forget_event_id(state: &SerialportState, path: &str) {
+    if let Ok(mut event_ids) = state.event_ids.lock() {
+        event_ids.remove(path);
+    }
+}
+
+/// `managed_ports` The open ports with their event id and metadata, sorted by path
+#[command]
+pub fn managed_ports<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+) -> Result<Vec<ManagedPort>, Error> {
+    match state.serialports.lock() {
+        Ok(serialports) => {
+            let mut ports: Vec<ManagedPort> = serialports
+                .iter()
+                .map(|(path, serialport_info)| ManagedPort {
+                    path: path.clone(),
+                    event_id: event_id_of(&state, path),
+                    reading: serialport_info.sender.is_some(),
+                    metadata: serialport_info.metadata.clone(),
+                })
+                .collect();
+            ports.sort_by(|a, b| a.path.cmp(&b.path));
+            Ok(ports)
+        }
+        Err(error) => Err(Error::String(format!("Cannot get lock: {}", error))),
+    }
+}
+
+/// `set_metadata` Attach any JSON value to an open port, replacing the previous one
+#[command]
+pub fn set_metadata<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    value: serde_json::Value,
+) -> Result<(), Error> {
+    get_serialport(state, path, |serialport_info| {
+        serialport_info.metadata = value;
         Ok(())
     })
 }
@@ -176,14 +826,17 @@ pub fn close<R: Runtime>(
     match state.serialports.lock() {
         Ok(mut serialports) => {
             if serialports.remove(&path).is_some() {
+                forget_event_id(&state, &path);
+                state.reactor.wake();
+                info!(path = %path, "Closed port");
                 Ok(())
             } else {
-                print!("Port {} is not opened", path);
+                warn!(path = %path, "Port is not opened");
                 Err(Error::String(format!("Port {} is not opened", path)))
             }
         }
         Err(error) => {
-            println!("Cannot get lock: {}", error);
+            error!("Cannot get lock: {}", error);
             Err(Error::String(format!("Cannot get lock: {}", error)))
         }
     }
@@ -203,13 +856,18 @@ pub fn close_all<R: Runtime>(
                     match sender.send(1) {
                         Ok(_) => {}
                         Err(error) => {
-                            println!("Failed to cancel read: {}", error);
+                            error!("Failed to cancel read: {}", error);
                             return Err(Error::String(format!("Failed to cancel read: {}", error)));
                         }
                     }
                 }
             }
             map.clear();
+            if let Ok(mut event_ids) = state.event_ids.lock() {
+                event_ids.clear();
+            }
+            state.reactor.wake();
+            info!("Closed all ports");
             Ok(())
         }
         Err(error) => {
@@ -233,12 +891,15 @@ pub fn force_close<R: Runtime>(
                     match sender.send(1) {
                         Ok(_) => {}
                         Err(error) => {
-                            println!("Cancel read data failed: {}", error);
+                            error!(path = %path, "Cancel read data failed: {}", error);
                             return Err(Error::String(format!("Cancel read data failed: {}", error)));
                         }
                     }
                 }
                 map.remove(&path);
+                forget_event_id(&state, &path);
+                state.reactor.wake();
+                info!(path = %path, "Force closed port");
                 Ok(())
             } else {
                 Ok(())
@@ -250,12 +911,84 @@ pub fn force_close<R: Runtime>(
     }
 }
 
-/// `open` Open serial port
+/// `get_open_error` Classify an open failure from the `serialport` error kind and the OS errno,
+/// so the frontend can tell a missing device from a busy or inaccessible one.
+fn get_open_error(path: &str, error: serialport::Error, os_error: io::Error) -> Error {
+    let path = path.to_string();
+    let message = error.description.clone();
+    // errno values on unix, `GetLastError` codes on windows
+    #[cfg(unix)]
+    let (busy, denied, missing): (&[i32], &[i32], &[i32]) = (&[16, 11], &[1, 13], &[2, 6, 19]);
+    #[cfg(windows)]
+    let (busy, denied, missing): (&[i32], &[i32], &[i32]) = (&[5, 32], &[], &[2, 3]);
+    let os_code = os_error.raw_os_error().unwrap_or(0);
+    match error.kind {
+        serialport::ErrorKind::InvalidInput => Error::InvalidSettings { path, message },
+        serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied) if !busy.contains(&os_code) => {
+            Error::PermissionDenied { path, message }
+        }
+        serialport::ErrorKind::Io(io::ErrorKind::NotFound) => Error::NoDevice { path, message },
+        serialport::ErrorKind::Io(io::ErrorKind::WouldBlock) => Error::Busy { path, message },
+        _ if busy.contains(&os_code) => Error::Busy { path, message },
+        _ if denied.contains(&os_code) => Error::PermissionDenied { path, message },
+        _ if missing.contains(&os_code) => Error::NoDevice { path, message },
+        serialport::ErrorKind::NoDevice => Error::NoDevice { path, message },
+        _ => Error::String(format!("Failed to open port {}: {}", path, message)),
+    }
+}
+
+/// `is_event_id` Tauri only accepts alphanumeric characters, `-`, `/`, `:` and `_` in event names
+fn is_event_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '/' | ':' | '_'))
+}
+
+/// `event_id_of` The id the events of a port are named with
+fn event_id_of(state: &SerialportState, path: &str) -> String {
+    match state.event_ids.lock() {
+        Ok(event_ids) => event_ids.get(path).cloned(),
+        Err(_) => None,
+    }
+    .unwrap_or_else(|| path.to_string())
+}
+
+/// `event_name` The name of a port event, `plugin-serialport-{kind}-{id}` with the event id the
+/// port was opened with, its path by default
+fn event_name<R: Runtime>(window: &Window<R>, kind: &str, path: &str) -> String {
+    let id = event_id_of(&window.state::<SerialportState>(), path);
+    format!("plugin-serialport-{}-{}", kind, id)
+}
+
+/// `get_event_id` The requested id, an opaque one if the plugin is built so, or the path
+fn get_event_id(
+    state: &SerialportState,
+    path: &str,
+    event_id: Option<String>,
+) -> Result<String, Error> {
+    match event_id {
+        Some(id) if !is_event_id(&id) => Err(Error::InvalidSettings {
+            path: path.to_string(),
+            message: format!("Invalid event id {}, use letters, digits, -, /, : and _", id),
+        }),
+        Some(id) => Ok(id),
+        None if state.opaque_event_ids => Ok(format!(
+            "port-{}",
+            state.next_event_id.fetch_add(1, Ordering::Relaxed)
+        )),
+        None => Ok(path.to_string()),
+    }
+}
+
+/// `open` Open serial port. Returns the id its events are named with, `event_id` or by default
+/// the path. `access` is `ReadWrite` (default), `ReadOnly` or `WriteOnly`
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub fn open<R: Runtime>(
-    _app: AppHandle<R>,
+    app: AppHandle<R>,
     state: State<'_, SerialportState>,
-    _window: Window<R>,
+    window: Window<R>,
     path: String,
     baud_rate: u32,
     data_bits: Option<usize>,
@@ -263,154 +996,5791 @@ pub fn open<R: Runtime>(
     parity: Option<String>,
     stop_bits: Option<usize>,
     timeout: Option<u64>,
-) -> Result<(), Error> {
-    match state.serialports.lock() {
-        Ok(mut serialports) => {
-            if serialports.contains_key(&path) {
-                return Err(Error::String(format!("Port {} is already opened", path)));
-            }
-            match serialport::new(path.clone(), baud_rate)
-                .data_bits(get_data_bits(data_bits))
-                .flow_control(get_flow_control(flow_control))
-                .parity(get_parity(parity))
-                .stop_bits(get_stop_bits(stop_bits))
-                .timeout(Duration::from_millis(timeout.unwrap_or(200)))
-                .open()
-            {
-                Ok(serial) => {
-                    let data = SerialportInfo {
-                        serialport: serial,
-                        sender: None,
-                    };
-                    serialports.insert(path, data);
-                    Ok(())
-                }
-                Err(error) => Err(Error::String(format!(
-                    "Failed to open port {}: {}",
-                    path,
-                    error.description
-                ))),
-            }
-        }
-        Err(error) => {
-            Err(Error::String(format!("Cannot get lock: {}", error)))
-        }
-    }
+    rs485: Option<NativeRs485>,
+    event_id: Option<String>,
+    access: Option<String>,
+) -> Result<String, Error> {
+    let event_id = open_managed(
+        state.clone(),
+        path,
+        baud_rate,
+        data_bits,
+        flow_control,
+        parity,
+        stop_bits,
+        timeout,
+        rs485,
+        event_id,
+        access,
+    )?;
+    watch_power(&app, &window);
+    Ok(event_id)
 }
 
-/// `read` Read data from serial port
-#[command]
-pub fn read<R: Runtime>(
-    _app: AppHandle<R>,
-    window: Window<R>,
+/// `open_managed` Open serial port with its access and event id, what `open` does without a
+/// window
+#[allow(clippy::too_many_arguments)]
+fn open_managed(
     state: State<'_, SerialportState>,
     path: String,
+    baud_rate: u32,
+    data_bits: Option<usize>,
+    flow_control: Option<String>,
+    parity: Option<String>,
+    stop_bits: Option<usize>,
     timeout: Option<u64>,
-    size: Option<usize>,
-) -> Result<(), Error> {
-    get_serialport(state.clone(), path.clone(), |serialport_info| {
-        if serialport_info.sender.is_some() {
-            println!("Port {} is already reading", path);
+    rs485: Option<NativeRs485>,
+    event_id: Option<String>,
+    access: Option<String>,
+) -> Result<String, Error> {
+    let access = Access::parse(access.as_deref()).map_err(|message| Error::InvalidSettings {
+        path: path.clone(),
+        message,
+    })?;
+    let event_id = get_event_id(&state, &path, event_id)?;
+    open_port(
+        &state,
+        path.clone(),
+        baud_rate,
+        data_bits,
+        flow_control,
+        parity,
+        stop_bits,
+        timeout,
+        rs485,
+    )?;
+    if access != Access::ReadWrite {
+        get_serialport(state.clone(), path.clone(), |serialport_info| {
+            if let Ok(mut opened) = serialport_info.access.lock() {
+                *opened = access;
+            }
+            info!(path = %path, "Port is {}", access.name());
             Ok(())
-        } else {
-            println!("Start reading data from {}", path);
-            match serialport_info.serialport.try_clone() {
-                Ok(mut serial) => {
-                    let read_event = format!("plugin-serialport-read-{}", &path);
-                    let (tx, rx): (Sender<usize>, Receiver<usize>) = mpsc::channel();
-                    serialport_info.sender = Some(tx);
-                    thread::spawn(move || loop {
-                        match rx.try_recv() {
-                            Ok(_) => {
-                                println!("Stopped reading data from {}", path);
-                                break;
-                            }
-                            Err(error) => match error {
-                                TryRecvError::Disconnected => {
-                                    println!("Port {} is disconnected", path);
-                                    break;
-                                }
-                                TryRecvError::Empty => {}
-                            },
-                        }
-                        let mut serial_buf: Vec<u8> = vec![0; size.unwrap_or(1024)];
-                        match serial.read(serial_buf.as_mut_slice()) {
-                            Ok(size) => {
-                                println!("Port {} read {} bytes", path, size);
-                                match window.emit(
-                                    &read_event,
-                                    ReadData {
-                                        data: &serial_buf[..size],
-                                        size,
-                                    },
-                                ) {
-                                    Ok(_) => {}
-                                    Err(error) => {
-                                        println!("Failed to emit event: {}", error);
-                                    }
-                                }
-                            }
-                            Err(_err) => {
-                                println!("Port {} read failed", path);
-                            }
-                        }
-                        thread::sleep(Duration::from_millis(timeout.unwrap_or(200)));
-                    });
-                }
-                Err(error) => {
-                    return Err(Error::String(format!("Failed to read port {}: {}", path, error)));
+        })?;
+    }
+    if !state.observers.is_empty() || !state.middleware.is_empty() {
+        get_serialport(state.clone(), path.clone(), |serialport_info| {
+            if !state.observers.is_empty() {
+                if let Ok(mut tap) = serialport_info.tap.lock() {
+                    *tap = Some(Tap::new(path.clone(), state.observers.clone()));
                 }
             }
+            if let Ok(mut middleware) = serialport_info.middleware.lock() {
+                *middleware = Chain::build(&state.middleware, &path);
+            }
             Ok(())
+        })?;
+    }
+    if let Some(store) = &state.settings_store {
+        remember_settings(&state, store, &path);
+    }
+    match state.event_ids.lock() {
+        Ok(mut event_ids) => {
+            event_ids.insert(path, event_id.clone());
+            Ok(event_id)
         }
-    })
+        Err(error) => Err(Error::String(format!("Cannot get event id lock: {}", error))),
+    }
+}
+
+/// `remember_settings` Save the settings a port was opened with for its device, if it has a
+/// serial number
+fn remember_settings(state: &State<'_, SerialportState>, store: &SettingsStore, path: &str) {
+    let key = match store::device_key(path) {
+        Some(key) => key,
+        None => return,
+    };
+    let settings = get_serialport(state.clone(), path.to_string(), |serialport_info| {
+        port_settings(serialport_info, path)
+    });
+    let saved = match settings {
+        Ok(settings) => SavedSettings {
+            baud_rate: settings.baud_rate,
+            data_bits: settings.data_bits,
+            flow_control: settings.flow_control,
+            parity: settings.parity,
+            stop_bits: settings.stop_bits,
+            timeout: settings.timeout,
+            path: path.to_string(),
+            saved_at: now_millis(),
+        },
+        Err(error) => {
+            warn!(path = %path, "Cannot save settings: {}", error);
+            return;
+        }
+    };
+    match store.save(key.clone(), saved) {
+        Ok(()) => debug!(path = %path, device = %key, "Saved settings"),
+        Err(error) => warn!(path = %path, "Cannot save settings: {}", error),
+    }
 }
 
-/// `write` Write data to serial port
+/// `open_with_saved_settings` Open the port `selector` matches with the settings its device was
+/// last opened with. Needs `Builder::remember_settings`
 #[command]
-pub fn write<R: Runtime>(
-    _app: AppHandle<R>,
-    _window: Window<R>,
+pub fn open_with_saved_settings<R: Runtime>(
+    app: AppHandle<R>,
     state: State<'_, SerialportState>,
-    path: String,
-    value: String,
-) -> Result<usize, Error> {
-    get_serialport(state, path.clone(), |serialport_info| {
-        match serialport_info.serialport.write(value.as_bytes()) {
-            Ok(size) => {
-                Ok(size)
+    window: Window<R>,
+    selector: Selector,
+    event_id: Option<String>,
+    access: Option<String>,
+) -> Result<SavedOpen, Error> {
+    let store = match &state.settings_store {
+        Some(store) => store.clone(),
+        None => {
+            return Err(Error::String(
+                "Saved settings are off, enable them with Builder::remember_settings".to_string(),
+            ))
         }
-            Err(error) => {
-                Err(Error::String(format!(
-                    "Failed to write data to port {}: {}",
-                    &path, error
+    };
+    let path = match selector.resolve().map_err(Error::String)? {
+        Some(path) => path,
+        None => {
+            return Err(Error::NoDevice {
+                path: selector.describe(),
+                message: "No port matches".to_string(),
+            })
+        }
+    };
+    let key = store::device_key(&path).ok_or_else(|| Error::InvalidSettings {
+        path: path.clone(),
+        message: "The device has no serial number to save settings under".to_string(),
+    })?;
+    let settings = store.get(&key).ok_or_else(|| Error::InvalidSettings {
+        path: path.clone(),
+        message: format!("No settings saved for device {}", key),
+    })?;
+    let event_id = open(
+        app,
+        state,
+        window,
+        path.clone(),
+        settings.baud_rate,
+        Some(settings.data_bits),
+        Some(settings.flow_control.clone()),
+        Some(settings.parity.clone()),
+        Some(settings.stop_bits),
+        Some(settings.timeout),
+        None,
+        event_id,
+        access,
+    )?;
+    Ok(SavedOpen {
+        path,
+        event_id,
+        device: key,
+        settings,
+    })
+}
+
+/// `watch_power` Start checking the open ports after system sleeps, once
+pub(crate) fn watch_power<R: Runtime>(app: &AppHandle<R>, window: &Window<R>) {
+    let state = app.state::<SerialportState>();
+    if !state.power_watch.swap(true, Ordering::Relaxed) {
+        let (app, window) = (app.clone(), window.clone());
+        thread::spawn(move || run_power_watch(app, window));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn open_port(
+    state: &SerialportState,
+    path: String,
+    baud_rate: u32,
+    data_bits: Option<usize>,
+    flow_control: Option<String>,
+    parity: Option<String>,
+    stop_bits: Option<usize>,
+    timeout: Option<u64>,
+    rs485: Option<NativeRs485>,
+) -> Result<(), Error> {
+    match state.serialports.lock() {
+        Ok(mut serialports) => {
+            if serialports.contains_key(&path) {
+                return Err(Error::String(format!("Port {} is already opened", path)));
+            }
+            // a `/dev/serial/by-id` link and the node it points to are the same port
+            #[cfg(target_os = "linux")]
+            if let Ok(device) = std::fs::canonicalize(&path) {
+                if let Some(opened) = serialports
+                    .keys()
+                    .find(|opened| std::fs::canonicalize(opened).ok().as_ref() == Some(&device))
+                {
+                    return Err(Error::String(format!(
+                        "Port {} is already opened as {}",
+                        path, opened
+                    )));
+                }
+            }
+            let timeout = Duration::from_millis(timeout.unwrap_or(200));
+            let stick_parity = get_stick_parity(&parity);
+            if stick_parity.is_some() && path.contains("://") {
+                return Err(Error::InvalidSettings {
+                    path,
+                    message: "Mark and Space parity are only supported on native serial ports"
+                        .to_string(),
+                });
+            }
+            if rs485.is_some() && path.contains("://") {
+                return Err(Error::InvalidSettings {
+                    path,
+                    message: "RS-485 mode is only supported on native serial ports".to_string(),
+                });
+            }
+            if let Some((port, channel)) = mux::parse_path(&path) {
+                let demux = match serialports.get(port).and_then(|split| split.mux.as_ref()) {
+                    Some(mux) => mux.demux.clone(),
+                    None => {
+                        return Err(Error::NoDevice {
+                            message: format!("Port {} is not split into channels", port),
+                            path,
+                        })
+                    }
+                };
+                if !demux.accepts(channel) {
+                    return Err(Error::NoDevice {
+                        message: format!("Channel {} of port {} is not open", channel, port),
+                        path,
+                    });
+                }
+                let settings = get_line_settings(
+                    baud_rate,
+                    data_bits,
+                    flow_control,
+                    parity,
+                    stop_bits,
+                    timeout,
+                );
+                return match mux::open(&path, demux, channel, settings) {
+                    Ok(serial) => {
+                        info!(path = %path, "Opened multiplexed channel");
+                        serialports.insert(path, SerialportInfo::new(serial));
+                        Ok(())
+                    }
+                    Err(error) => {
+                        let os_error = io::Error::last_os_error();
+                        Err(get_open_error(&path, error, os_error))
+                    }
+                };
+            }
+            #[cfg(feature = "bluetooth")]
+            if path.starts_with(crate::bluetooth::PREFIX) {
+                return match crate::bluetooth::open(&path, baud_rate, timeout) {
+                    Ok(serial) => {
+                        info!(path = %path, "Opened Bluetooth port");
+                        serialports.insert(path, SerialportInfo::new(serial));
+                        Ok(())
+                    }
+                    Err(error) => {
+                        let os_error = io::Error::last_os_error();
+                        Err(get_open_error(&path, error, os_error))
+                    }
+                };
+            }
+            #[cfg(feature = "ble")]
+            if path.starts_with(crate::ble::PREFIX) {
+                return match crate::ble::open(&path, baud_rate, timeout) {
+                    Ok(serial) => {
+                        info!(path = %path, "Opened BLE port");
+                        serialports.insert(path, SerialportInfo::new(serial));
+                        Ok(())
+                    }
+                    Err(error) => {
+                        let os_error = io::Error::last_os_error();
+                        Err(get_open_error(&path, error, os_error))
+                    }
+                };
+            }
+            #[cfg(feature = "emulator")]
+            if let Some(name) = path.strip_prefix(crate::emulator::PREFIX) {
+                let script = match state.emulators.lock() {
+                    Ok(emulators) => match emulators.get(name) {
+                        Some(script) => script.clone(),
+                        None => return Err(Error::String(format!("No emulator {}", name))),
+                    },
+                    Err(error) => return Err(Error::String(format!("Cannot get lock: {}", error))),
+                };
+                let settings = get_line_settings(
+                    baud_rate,
+                    data_bits,
+                    flow_control,
+                    parity,
+                    stop_bits,
+                    timeout,
+                );
+                return match crate::emulator::open(&path, &script, settings) {
+                    Ok(serial) => {
+                        info!(path = %path, "Opened emulated port");
+                        serialports.insert(path, SerialportInfo::new(serial));
+                        Ok(())
+                    }
+                    Err(error) => {
+                        let os_error = io::Error::last_os_error();
+                        Err(get_open_error(&path, error, os_error))
+                    }
+                };
+            }
+            #[cfg(feature = "hid")]
+            if path.starts_with(crate::hid::PREFIX) {
+                let settings = get_line_settings(
+                    baud_rate,
+                    data_bits,
+                    flow_control,
+                    parity,
+                    stop_bits,
+                    timeout,
+                );
+                return match crate::hid::open(&path, settings) {
+                    Ok(serial) => {
+                        info!(path = %path, baud_rate, "Opened HID UART port");
+                        serialports.insert(path, SerialportInfo::new(serial));
+                        Ok(())
+                    }
+                    Err(error) => {
+                        let os_error = io::Error::last_os_error();
+                        Err(get_open_error(&path, error, os_error))
+                    }
+                };
+            }
+            #[cfg(feature = "usb")]
+            if path.starts_with(crate::usb::PREFIX) {
+                let settings = get_line_settings(
+                    baud_rate,
+                    data_bits,
+                    flow_control,
+                    parity,
+                    stop_bits,
+                    timeout,
+                );
+                return match crate::usb::open(&path, settings) {
+                    Ok(serial) => {
+                        info!(path = %path, baud_rate, "Opened USB CDC-ACM port");
+                        serialports.insert(path, SerialportInfo::new(serial));
+                        Ok(())
+                    }
+                    Err(error) => {
+                        let os_error = io::Error::last_os_error();
+                        Err(get_open_error(&path, error, os_error))
+                    }
+                };
+            }
+            #[cfg(not(windows))]
+            let builder = serialport::new(path.clone(), baud_rate)
+                .data_bits(get_data_bits(data_bits))
+                .flow_control(get_flow_control(flow_control))
+                .parity(get_parity(parity))
+                .stop_bits(get_stop_bits(stop_bits))
+                .timeout(timeout);
+            // the descriptor or handle is kept for the settings `serialport` does not offer
+            #[cfg(unix)]
+            let opened = builder.open_native().map(|serial| {
+                let fd = std::os::unix::io::AsRawFd::as_raw_fd(&serial);
+                (Box::new(serial) as Box<dyn SerialPort>, Some(fd as isize))
+            });
+            // overlapped, so reads wait for data with `WaitCommEvent` and cancel at once
+            #[cfg(windows)]
+            let opened = crate::overlapped::open(
+                &path,
+                baud_rate,
+                get_data_bits(data_bits),
+                get_flow_control(flow_control),
+                get_parity(parity),
+                get_stop_bits(stop_bits),
+                timeout,
+            )
+            .map(|serial| {
+                let handle = std::os::windows::io::AsRawHandle::as_raw_handle(&serial);
+                (Box::new(serial) as Box<dyn SerialPort>, Some(handle as isize))
+            });
+            #[cfg(not(any(unix, windows)))]
+            let opened = builder.open().map(|serial| (serial, None));
+            match opened {
+                Ok((serial, native)) => {
+                    if let Some(mark) = stick_parity {
+                        if let Err(error) = set_stick_parity(native, mark) {
+                            return Err(Error::InvalidSettings {
+                                path,
+                                message: format!("Failed to set Mark or Space parity: {}", error),
+                            });
+                        }
+                    }
+                    if let Some(rs485) = &rs485 {
+                        if let Err(error) = set_native_rs485(native, rs485) {
+                            return Err(Error::InvalidSettings {
+                                path,
+                                message: format!("Failed to enable RS-485 mode: {}", error),
+                            });
+                        }
+                    }
+                    let mut data = SerialportInfo::new(serial);
+                    data.native = native;
+                    data.line_status = Arc::new(Mutex::new(LineStatus::new(native)));
+                    data.stick_parity = stick_parity;
+                    data.rs485 = rs485;
+                    info!(path = %path, baud_rate, "Opened port");
+                    serialports.insert(path, data);
+                    Ok(())
+                }
+                Err(error) => {
+                    let os_error = io::Error::last_os_error();
+                    error!(path = %path, "Failed to open port: {}", error.description);
+                    match get_open_error(&path, error, os_error) {
+                        Error::Busy { path, message } if state.busy_diagnostics => {
+                            let message = match find_port_holder(&path) {
+                                Some(holder) => {
+                                    format!("{} (held by {} pid {})", message, holder.name, holder.pid)
+                                }
+                                None => message,
+                            };
+                            Err(Error::Busy { path, message })
+                        }
+                        error => Err(error),
+                    }
+                }
+            }
+        }
+        Err(error) => {
+            Err(Error::String(format!("Cannot get lock: {}", error)))
+        }
+    }
+}
+
+/// `is_transient_open_error` Failures that go away once a freshly plugged device has
+/// finished enumerating (node missing, udev permissions not applied yet, briefly busy)
+fn is_transient_open_error(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::NoDevice { .. } | Error::PermissionDenied { .. } | Error::Busy { .. } | Error::Io(_)
+    )
+}
+
+/// `open_with_retry` Open serial port, retrying transient failures with exponential backoff
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn open_with_retry<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, SerialportState>,
+    window: Window<R>,
+    path: String,
+    baud_rate: u32,
+    data_bits: Option<usize>,
+    flow_control: Option<String>,
+    parity: Option<String>,
+    stop_bits: Option<usize>,
+    timeout: Option<u64>,
+    rs485: Option<NativeRs485>,
+    attempts: Option<u32>,
+    backoff_ms: Option<u64>,
+    event_id: Option<String>,
+    access: Option<String>,
+) -> Result<String, Error> {
+    let attempts = attempts.unwrap_or(5).max(1);
+    let mut backoff = Duration::from_millis(backoff_ms.unwrap_or(200));
+    let mut attempt = 1;
+    loop {
+        let result = open(
+            app.clone(),
+            state.clone(),
+            window.clone(),
+            path.clone(),
+            baud_rate,
+            data_bits,
+            flow_control.clone(),
+            parity.clone(),
+            stop_bits,
+            timeout,
+            rs485,
+            event_id.clone(),
+            access.clone(),
+        );
+        match result {
+            Err(error) if attempt < attempts && is_transient_open_error(&error) => {
+                warn!(path = %path, attempt, "Open failed, retrying in {:?}: {}", backoff, error);
+                blocking(move || {
+                    thread::sleep(backoff);
+                    Ok(())
+                })
+                .await?;
+                backoff = (backoff * 2).min(Duration::from_secs(5));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// How often a port to open at startup with `wait` is looked for.
+const AUTO_OPEN_INTERVAL: Duration = Duration::from_secs(1);
+/// Event of the ports opened at startup, not named by port since their id is not known yet.
+const AUTO_OPENED_EVENT: &str = "plugin-serialport-auto-opened";
+
+/// `auto_open` Open the ports declared for startup, those missing with `wait` once they appear
+pub(crate) fn auto_open<R: Runtime>(app: &AppHandle<R>) {
+    let state = app.state::<SerialportState>();
+    for (index, port) in startup_ports(&state).into_iter().enumerate() {
+        let status = try_auto_open(app, &port);
+        info!(
+            selector = %status.selector,
+            opened = status.opened,
+            "Port to open at startup {}",
+            if status.waiting { "is awaited" } else { "was handled" }
+        );
+        let waiting = status.waiting;
+        if let Ok(mut auto_opened) = state.auto_opened.lock() {
+            auto_opened.push(status);
+        }
+        if waiting {
+            let app = app.clone();
+            thread::spawn(move || loop {
+                thread::sleep(AUTO_OPEN_INTERVAL);
+                let status = try_auto_open(&app, &port);
+                if status.waiting {
+                    continue;
+                }
+                let state = app.state::<SerialportState>();
+                if let Ok(mut auto_opened) = state.auto_opened.lock() {
+                    if let Some(entry) = auto_opened.get_mut(index) {
+                        *entry = status.clone();
+                    }
+                }
+                info!(selector = %status.selector, opened = status.opened, "Awaited port appeared");
+                if let Err(error) = app.emit_all(AUTO_OPENED_EVENT, status) {
+                    error!("Failed to emit event: {}", error);
+                }
+                break;
+            });
+        }
+    }
+}
+
+/// `open_selected` Open the port a profile selects with its settings, `None` when no port matches
+fn open_selected(
+    state: State<'_, SerialportState>,
+    profile: &Profile,
+) -> Result<Option<(String, String)>, Error> {
+    let path = match profile.selector.resolve().map_err(Error::String)? {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let event_id = open_managed(
+        state,
+        path.clone(),
+        profile.baud_rate,
+        profile.data_bits,
+        profile.flow_control.clone(),
+        profile.parity.clone(),
+        profile.stop_bits,
+        profile.timeout,
+        profile.rs485,
+        profile.event_id.clone(),
+        profile.access.clone(),
+    )?;
+    Ok(Some((path, event_id)))
+}
+
+/// `startup_ports` The ports declared for startup, those imported with `import_config` after
+/// those of the application, unless the application declares the same port
+fn startup_ports(state: &SerialportState) -> Vec<AutoOpen> {
+    let mut ports = state.declared_auto_open.clone();
+    let declared: Vec<String> = ports
+        .iter()
+        .map(|port| port.profile.selector.describe())
+        .collect();
+    ports.extend(
+        state
+            .provisioned
+            .list()
+            .into_iter()
+            .filter(|(key, _)| !declared.contains(key))
+            .map(|(_, port)| port),
+    );
+    ports
+}
+
+/// `try_auto_open` Open a port declared for startup if it is there
+fn try_auto_open<R: Runtime>(app: &AppHandle<R>, port: &AutoOpen) -> AutoOpenedData {
+    let mut status = AutoOpenedData {
+        selector: port.profile.selector.describe(),
+        path: None,
+        event_id: None,
+        baud_rate: port.profile.baud_rate,
+        opened: false,
+        waiting: false,
+        error: None,
+        timestamp: now_millis(),
+    };
+    match open_selected(app.state(), &port.profile) {
+        Ok(Some((path, event_id))) => {
+            status.path = Some(path);
+            status.event_id = Some(event_id);
+            status.opened = true;
+        }
+        Ok(None) => {
+            status.waiting = port.wait;
+            status.error = Some(format!("No port matches {}", status.selector));
+        }
+        Err(error) => {
+            status.waiting = port.wait && is_transient_open_error(&error);
+            status.error = Some(error.to_string());
+        }
+    }
+    status
+}
+
+/// `save_profile` Store a connection profile under `name`, replacing one of the same name
+#[command]
+pub fn save_profile<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    name: String,
+    settings: Profile,
+) -> Result<(), Error> {
+    if !is_profile_name(&name) {
+        return Err(Error::String(format!("Invalid profile name {:?}", name)));
+    }
+    settings.selector.validate().map_err(|message| Error::InvalidSettings {
+        path: settings.selector.describe(),
+        message,
+    })?;
+    if let Some(event_id) = settings.event_id.as_ref().filter(|id| !is_event_id(id)) {
+        return Err(Error::InvalidSettings {
+            path: settings.selector.describe(),
+            message: format!("Invalid event id {}, use letters, digits, -, /, : and _", event_id),
+        });
+    }
+    state
+        .profiles
+        .save(name, settings)
+        .map_err(|error| Error::String(format!("Cannot save profile: {}", error)))
+}
+
+/// `list_profiles` The stored connection profiles, sorted by name
+#[command]
+pub fn list_profiles<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+) -> Vec<NamedProfile> {
+    state
+        .profiles
+        .list()
+        .into_iter()
+        .map(|(name, profile)| NamedProfile { name, profile })
+        .collect()
+}
+
+/// `remove_profile` Drop the profile `name`, whether it existed
+#[command]
+pub fn remove_profile<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    name: String,
+) -> Result<bool, Error> {
+    state
+        .profiles
+        .remove(&name)
+        .map_err(|error| Error::String(format!("Cannot remove profile: {}", error)))
+}
+
+/// `open_profile` Open the port of the profile `name` with its settings
+#[command]
+pub fn open_profile<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, SerialportState>,
+    window: Window<R>,
+    name: String,
+) -> Result<ProfileOpen, Error> {
+    let profile = state
+        .profiles
+        .get(&name)
+        .ok_or_else(|| Error::String(format!("No profile named {}", name)))?;
+    let (path, event_id) = open_selected(state, &profile)?.ok_or_else(|| Error::NoDevice {
+        path: profile.selector.describe(),
+        message: "No port matches".to_string(),
+    })?;
+    watch_power(&app, &window);
+    Ok(ProfileOpen {
+        name,
+        path,
+        event_id,
+        profile,
+    })
+}
+
+/// `export_config` The profiles, ports opened at startup and saved settings as one document
+#[command]
+pub fn export_config<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+) -> Document {
+    Document {
+        version: provision::VERSION,
+        profiles: state.profiles.list().into_iter().collect(),
+        auto_open: startup_ports(&state),
+        saved_settings: state
+            .settings_store
+            .as_ref()
+            .map(|store| store.list().into_iter().collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// `import_config` Store the entries of an exported document, added to the present ones or with
+/// `replace` in their place. Imported ports to open at startup are opened from the next start
+#[command]
+pub fn import_config<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    document: Document,
+    replace: Option<bool>,
+) -> Result<ImportReport, Error> {
+    document.validate().map_err(Error::String)?;
+    let replace = replace.unwrap_or(false);
+    let failed = |error: io::Error| Error::String(format!("Cannot store configuration: {}", error));
+    let mut report = ImportReport {
+        profiles: state
+            .profiles
+            .import(document.profiles.into_iter().collect(), replace)
+            .map_err(failed)?,
+        ..ImportReport::default()
+    };
+    let declared: Vec<String> = state
+        .declared_auto_open
+        .iter()
+        .map(|port| port.profile.selector.describe())
+        .collect();
+    // the ports the application declares itself are opened anyway
+    let ports: Vec<(String, AutoOpen)> = document
+        .auto_open
+        .into_iter()
+        .map(|port| (port.profile.selector.describe(), port))
+        .filter(|(key, _)| !declared.contains(key))
+        .collect();
+    report.auto_open = state.provisioned.import(ports, replace).map_err(failed)?;
+    if let Some(store) = &state.settings_store {
+        report.saved_settings = store
+            .import(document.saved_settings.into_iter().collect(), replace)
+            .map_err(failed)?;
+    }
+    info!(
+        profiles = report.profiles,
+        auto_open = report.auto_open,
+        saved_settings = report.saved_settings,
+        "Imported configuration"
+    );
+    Ok(report)
+}
+
+/// `auto_opened` The ports declared for startup, whether they were opened and under which
+/// path and event id
+#[command]
+pub fn auto_opened<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+) -> Result<Vec<AutoOpenedData>, Error> {
+    match state.auto_opened.lock() {
+        Ok(auto_opened) => Ok(auto_opened.clone()),
+        Err(error) => Err(Error::String(format!("Cannot get lock: {}", error))),
+    }
+}
+
+/// `emit_read_data` Send a chunk of received data to the frontend
+fn emit_read_data<R: Runtime>(
+    window: &Window<R>,
+    event: &str,
+    sequence: &AtomicU64,
+    data: &[u8],
+    suppressed: u64,
+    stamp: Stamp,
+    outgoing: bool,
+) {
+    if let Err(error) = window.emit(
+        event,
+        ReadData {
+            sequence: sequence.fetch_add(1, Ordering::Relaxed),
+            data,
+            size: data.len(),
+            suppressed,
+            outgoing,
+            timestamp: stamp.timestamp,
+            monotonic_us: stamp.monotonic_us,
+        },
+    ) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_buffer_overflow` Report read data dropped because the frontend fell too far behind
+fn emit_buffer_overflow<R: Runtime>(
+    window: &Window<R>,
+    event: &str,
+    backpressure: &Backpressure,
+    dropped: u64,
+    recovered: bool,
+) {
+    warn!(dropped, recovered, "Read events overflow the backpressure buffer");
+    if let Err(error) = window.emit(
+        &event.replacen("-read-", "-buffer-overflow-", 1),
+        BufferOverflowData {
+            dropped,
+            buffered_bytes: backpressure.buffered_bytes(),
+            recovered,
+            timestamp: now_millis(),
+        },
+    ) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `deliver_read_data` Emit a read event, or hold it back while the frontend has not acknowledged
+/// enough of the earlier ones
+#[allow(clippy::too_many_arguments)]
+fn deliver_read_data<R: Runtime>(
+    window: &Window<R>,
+    event: &str,
+    sequence: &AtomicU64,
+    backpressure: &Mutex<Backpressure>,
+    data: &[u8],
+    suppressed: u64,
+    stamp: Stamp,
+    outgoing: bool,
+) {
+    let mut backpressure = match backpressure.lock() {
+        Ok(backpressure) => backpressure,
+        Err(_) => return emit_read_data(window, event, sequence, data, suppressed, stamp, outgoing),
+    };
+    let next = sequence.load(Ordering::Relaxed);
+    match backpressure.offer(data, suppressed, stamp, outgoing, next) {
+        Offered::Send => emit_read_data(window, event, sequence, data, suppressed, stamp, outgoing),
+        Offered::Buffered | Offered::Discarded => {}
+        Offered::Dropped { first } => {
+            if first {
+                let dropped = backpressure.dropped();
+                emit_buffer_overflow(window, event, &backpressure, dropped, false);
+            }
+        }
+    }
+}
+
+/// `drain_read_data` Emit the held back read events the acknowledgements allow
+fn drain_read_data<R: Runtime>(
+    window: &Window<R>,
+    path: &str,
+    sequence: &AtomicU64,
+    backpressure: &Mutex<Backpressure>,
+) -> Result<(), Error> {
+    let event = event_name(window, "read", path);
+    let mut backpressure = backpressure
+        .lock()
+        .map_err(|error| Error::String(format!("Cannot get backpressure lock: {}", error)))?;
+    while let Some(pending) = backpressure.next(sequence.load(Ordering::Relaxed)) {
+        emit_read_data(
+            window,
+            &event,
+            sequence,
+            &pending.data,
+            pending.suppressed,
+            pending.stamp,
+            pending.outgoing,
+        );
+    }
+    if let Some(dropped) = backpressure.take_dropped() {
+        emit_buffer_overflow(window, &event, &backpressure, dropped, true);
+    }
+    Ok(())
+}
+
+/// `emit_local_echo` Written data as outgoing read event, so listeners see both directions in order.
+fn emit_local_echo<R: Runtime>(
+    window: &Window<R>,
+    path: &str,
+    serialport_info: &SerialportInfo,
+    data: &[u8],
+) {
+    deliver_read_data(
+        window,
+        &event_name(window, "read", path),
+        &serialport_info.sequence,
+        &serialport_info.backpressure,
+        data,
+        0,
+        Stamp::now(serialport_info.opened),
+        true,
+    );
+}
+
+/// `set_backpressure` Allow at most `max_in_flight` read events the frontend has not acknowledged
+/// with `ack_read`, and buffer up to `max_buffered_bytes` of further data meanwhile. 0 emits
+/// without waiting, as before
+#[command]
+pub fn set_backpressure<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    max_in_flight: u64,
+    max_buffered_bytes: Option<usize>,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        match serialport_info.backpressure.lock() {
+            Ok(mut backpressure) => backpressure.configure(
+                max_in_flight,
+                max_buffered_bytes.unwrap_or(DEFAULT_LIMIT),
+                serialport_info.sequence.load(Ordering::Relaxed),
+            ),
+            Err(error) => {
+                return Err(Error::String(format!(
+                    "Cannot get backpressure lock: {}",
+                    error
+                )))
+            }
+        }
+        drain_read_data(
+            &window,
+            &path,
+            &serialport_info.sequence,
+            &serialport_info.backpressure,
+        )
+    })
+}
+
+/// `watch_visibility` Pause the read events of the port while `window` is minimized, until it is
+/// restored or focused again
+fn watch_visibility<R: Runtime>(
+    window: &Window<R>,
+    path: &str,
+    serialport_info: &mut SerialportInfo,
+) {
+    serialport_info.watches_visibility = true;
+    // the handler outlives the port, it must not keep it alive
+    let backpressure = Arc::downgrade(&serialport_info.backpressure);
+    let sequence = Arc::downgrade(&serialport_info.sequence);
+    let target = window.clone();
+    let path = path.to_string();
+    window.on_window_event(move |event| {
+        let hidden = match event {
+            WindowEvent::Resized(size) => size.width == 0 || size.height == 0,
+            WindowEvent::Focused(true) => false,
+            _ => return,
+        };
+        if let (Some(backpressure), Some(sequence)) = (backpressure.upgrade(), sequence.upgrade()) {
+            set_window_hidden(&target, &path, &sequence, &backpressure, hidden);
+        }
+    });
+}
+
+/// `set_window_hidden` Pause or resume the read events of the port, delivering what was buffered
+/// once the window is shown again
+fn set_window_hidden<R: Runtime>(
+    window: &Window<R>,
+    path: &str,
+    sequence: &AtomicU64,
+    backpressure: &Mutex<Backpressure>,
+    hidden: bool,
+) {
+    let shown = match backpressure.lock() {
+        Ok(mut backpressure) => backpressure.set_hidden(hidden),
+        Err(_) => return,
+    };
+    debug!(path = %path, hidden, "Window visibility changed");
+    if shown {
+        if let Err(error) = drain_read_data(window, path, sequence, backpressure) {
+            error!("{}", error);
+        }
+    }
+}
+
+/// `set_hidden` Report the visibility of the webview, e.g. from `visibilitychange`, for reads with
+/// a `when_hidden` policy
+#[command]
+pub fn set_hidden<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    hidden: bool,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        set_window_hidden(
+            &window,
+            &path,
+            &serialport_info.sequence,
+            &serialport_info.backpressure,
+            hidden,
+        );
+        Ok(())
+    })
+}
+
+/// `ack_read` The frontend consumed every read event up to `sequence`, emit what waits for it
+#[command]
+pub fn ack_read<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    sequence: u64,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        match serialport_info.backpressure.lock() {
+            Ok(mut backpressure) => backpressure.ack(sequence),
+            Err(error) => {
+                return Err(Error::String(format!(
+                    "Cannot get backpressure lock: {}",
+                    error
                 )))
             }
         }
+        drain_read_data(
+            &window,
+            &path,
+            &serialport_info.sequence,
+            &serialport_info.backpressure,
+        )
+    })
+}
+
+/// `fetch` Take data collected by a port that is read in `Buffer` mode
+#[command]
+pub fn fetch<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    max_bytes: Option<usize>,
+) -> Result<FetchData, Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        match &serialport_info.ring_buffer {
+            Some(ring_buffer) => match ring_buffer.lock() {
+                Ok(mut ring_buffer) => {
+                    let data = ring_buffer.drain(max_bytes.unwrap_or(usize::MAX));
+                    Ok(FetchData {
+                        size: data.len(),
+                        data,
+                        available: ring_buffer.len(),
+                        overwritten: ring_buffer.overwritten(),
+                    })
+                }
+                Err(error) => Err(Error::String(format!("Cannot get buffer lock: {}", error))),
+            },
+            None => Err(Error::String(format!("Port {} is not read in buffer mode", path))),
+        }
+    })
+}
+
+/// `read_once` Read what the driver holds right now, at most `max_bytes`, without waiting; meant for
+/// ports read in `Notify` mode
+#[command]
+pub fn read_once<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    max_bytes: Option<usize>,
+) -> Result<FetchData, Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        if is_reading(serialport_info) {
+            return Err(Error::String(format!(
+                "Port {} is read in another mode than Notify",
+                path
+            )));
+        }
+        let native = serialport_info.native;
+        let queued = queued_input(
+            serialport_info.serialport.as_ref(),
+            &serialport_info.line_status,
+            native,
+        )
+        .unwrap_or(0) as usize;
+        let mut data = vec![0; queued.min(max_bytes.unwrap_or(usize::MAX))];
+        let mut size = 0;
+        if !data.is_empty() {
+            size = match classify(serialport_info.serialport.read(&mut data)) {
+                ReadOutcome::Data(size) => size,
+                ReadOutcome::Idle => 0,
+                ReadOutcome::Disconnected(reason) => {
+                    return Err(Error::String(format!("Port {} disconnected: {}", path, reason)));
+                }
+                ReadOutcome::Failed(error) => {
+                    return Err(Error::String(format!("Failed to read port {}: {}", path, error)));
+                }
+            };
+            data.truncate(size);
+            data = middleware::incoming(&serialport_info.middleware, &data)
+                .map(Cow::into_owned)
+                .unwrap_or_default();
+        }
+        // reported again if data is left or arrives
+        if let Some(data_ready) = &serialport_info.data_ready {
+            data_ready.store(false, Ordering::Relaxed);
+        }
+        if !data.is_empty() {
+            serialport_info.last_activity.store(now_millis(), Ordering::Relaxed);
+            if let Ok(mut history) = serialport_info.history.lock() {
+                history.push(&data, now_millis());
+            }
+            if serialport_info.monitor.load(Ordering::Relaxed) {
+                emit_monitor_data(&window, &path, "RX", &data);
+            }
+            log_traffic(
+                serialport_info.log_level.load(Ordering::Relaxed),
+                &path,
+                "RX",
+                &data,
+            );
+        }
+        Ok(FetchData {
+            size: data.len(),
+            // middleware may have made more or less of the data
+            available: queued.saturating_sub(size),
+            data,
+            overwritten: 0,
+        })
+    })
+}
+
+/// `get_history` Get received data recorded between `since` and `until` (milliseconds since the Unix epoch)
+#[command]
+pub fn get_history<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    since: Option<u64>,
+    until: Option<u64>,
+    limit: Option<usize>,
+) -> Result<Vec<HistoryFrame>, Error> {
+    get_serialport(state, path, |serialport_info| {
+        match serialport_info.history.lock() {
+            Ok(history) => Ok(history.query(since, until, limit)),
+            Err(error) => Err(Error::String(format!("Cannot get history lock: {}", error))),
+        }
+    })
+}
+
+/// `set_history` Keep the last `max_frames` received chunks of the port, 0 disables the history
+#[command]
+pub fn set_history<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    max_frames: usize,
+) -> Result<(), Error> {
+    get_serialport(state, path, |serialport_info| {
+        match serialport_info.history.lock() {
+            Ok(mut history) => {
+                history.set_capacity(max_frames);
+                Ok(())
+            }
+            Err(error) => Err(Error::String(format!("Cannot get history lock: {}", error))),
+        }
+    })
+}
+
+/// `add_consumer` Read the received stream under `name` at an own cursor, framed by `framing`;
+/// `from_oldest` starts at the oldest byte kept instead of the next one received
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub fn add_consumer<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    name: String,
+    framing: Option<String>,
+    codec: Option<String>,
+    from_oldest: Option<bool>,
+    buffer_size: Option<usize>,
+) -> Result<(), Error> {
+    let framing = get_framing(&state, &path, framing, codec)?;
+    get_serialport(state, path.clone(), |serialport_info| {
+        let mut stream = serialport_info
+            .stream
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get stream lock: {}", error)))?;
+        if let Some(buffer_size) = buffer_size {
+            stream.set_capacity(buffer_size);
+        }
+        stream
+            .add(&name, framing, from_oldest.unwrap_or(false))
+            .map_err(|message| Error::InvalidSettings {
+                path: path.clone(),
+                message,
+            })?;
+        debug!(path = %path, name = %name, "Consumer added");
+        Ok(())
+    })
+}
+
+/// `consume` Take the frames in the next `max_bytes` (all available by default) of a consumer
+#[command]
+pub fn consume<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    name: String,
+    max_bytes: Option<usize>,
+) -> Result<Consumed, Error> {
+    get_serialport(state, path, |serialport_info| {
+        match serialport_info.stream.lock() {
+            Ok(mut stream) => stream
+                .consume(&name, max_bytes.unwrap_or(usize::MAX))
+                .map_err(Error::String),
+            Err(error) => Err(Error::String(format!("Cannot get stream lock: {}", error))),
+        }
+    })
+}
+
+/// `remove_consumer` Stop keeping the stream for a consumer, `false` if there was none
+#[command]
+pub fn remove_consumer<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    name: String,
+) -> Result<bool, Error> {
+    get_serialport(state, path, |serialport_info| {
+        match serialport_info.stream.lock() {
+            Ok(mut stream) => Ok(stream.remove(&name)),
+            Err(error) => Err(Error::String(format!("Cannot get stream lock: {}", error))),
+        }
+    })
+}
+
+/// `list_consumers` Names of the consumers of a port
+#[command]
+pub fn list_consumers<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<Vec<String>, Error> {
+    get_serialport(state, path, |serialport_info| {
+        match serialport_info.stream.lock() {
+            Ok(stream) => Ok(stream.names()),
+            Err(error) => Err(Error::String(format!("Cannot get stream lock: {}", error))),
+        }
+    })
+}
+
+/// `emit_monitor_data` Send transmitted or received data to the monitor event stream
+fn emit_monitor_data<R: Runtime>(window: &Window<R>, path: &str, direction: &str, data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+    if let Err(error) = window.emit(
+        &event_name(window, "monitor", path),
+        MonitorData {
+            direction,
+            timestamp: now_millis(),
+            data,
+            size: data.len(),
+        },
+    ) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `set_monitor` Enable or disable the combined TX/RX monitor event stream of the port
+#[command]
+pub fn set_monitor<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    enabled: bool,
+) -> Result<(), Error> {
+    get_serialport(state, path, |serialport_info| {
+        serialport_info.monitor.store(enabled, Ordering::Relaxed);
+        Ok(())
+    })
+}
+
+/// `set_local_echo` Also emit every successful `write` and `write_binary` as outgoing read event
+#[command]
+pub fn set_local_echo<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    enabled: bool,
+) -> Result<(), Error> {
+    get_serialport(state, path, |serialport_info| {
+        serialport_info.local_echo = enabled;
+        Ok(())
+    })
+}
+
+/// `set_rs485` Drive the RS-485 transceiver direction with RTS: asserted `pre_delay_us` before
+/// each write and released `post_delay_us` after its last byte has left the UART (`invert`
+/// for transceivers enabled by a released RTS)
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub fn set_rs485<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    enabled: bool,
+    pre_delay_us: Option<u64>,
+    post_delay_us: Option<u64>,
+    invert: Option<bool>,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let direction = Direction {
+            pre_delay: Duration::from_micros(pre_delay_us.unwrap_or(0)),
+            post_delay: Duration::from_micros(post_delay_us.unwrap_or(0)),
+            invert: invert.unwrap_or(false),
+            native: serialport_info.native,
+        };
+        // receiving until the first write
+        serialport_info
+            .serialport
+            .write_request_to_send(enabled && direction.invert)
+            .map_err(|error| Error::String(format!("Failed to set RTS: {}", error)))?;
+        match serialport_info.direction.lock() {
+            Ok(mut current) => *current = if enabled { Some(direction) } else { None },
+            Err(error) => {
+                return Err(Error::String(format!("Cannot get direction lock: {}", error)))
+            }
+        }
+        info!(path = %path, enabled, "RS-485 direction control changed");
+        Ok(())
+    })
+}
+
+/// `set_half_duplex` Let transmissions and receptions take turns: a write waits until the bus
+/// was quiet for `turnaround_ms`, and its echo received until `turnaround_ms` after it is
+/// discarded byte for byte instead of read
+#[command]
+pub fn set_half_duplex<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    enabled: bool,
+    turnaround_ms: Option<u64>,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let turnaround = Duration::from_millis(turnaround_ms.unwrap_or(5));
+        match serialport_info.half_duplex.lock() {
+            Ok(mut half_duplex) => {
+                *half_duplex = if enabled {
+                    Some(HalfDuplex::new(turnaround))
+                } else {
+                    None
+                }
+            }
+            Err(error) => {
+                return Err(Error::String(format!("Cannot get half-duplex lock: {}", error)))
+            }
+        }
+        info!(path = %path, enabled, "Half-duplex mode changed");
+        Ok(())
+    })
+}
+
+/// `set_encryption` Encrypt the link with a pre-shared AES key of 32, 48 or 64 hex digits, for
+/// firmware that implements the framing described in the README; `None` sends and reads plain
+/// data again. Data of a frame still being received when the key changes is lost.
+#[command]
+pub fn set_encryption<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    key: Option<String>,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let link = match key {
+            Some(key) => {
+                let invalid = |message: String| Error::InvalidSettings {
+                    path: path.clone(),
+                    message,
+                };
+                let digits = key.trim();
+                if digits.len() % 2 != 0 || !digits.bytes().all(|digit| digit.is_ascii_hexdigit()) {
+                    return Err(invalid("The key must be given as hex digits".to_string()));
+                }
+                let key: Vec<u8> = (0..digits.len())
+                    .step_by(2)
+                    .map(|index| u8::from_str_radix(&digits[index..index + 2], 16).unwrap_or(0))
+                    .collect();
+                Some(Link::new(&key).map_err(invalid)?)
+            }
+            None => None,
+        };
+        let enabled = link.is_some();
+        match serialport_info.link.lock() {
+            Ok(mut current) => *current = link,
+            Err(error) => {
+                return Err(Error::String(format!("Cannot get encryption lock: {}", error)))
+            }
+        }
+        info!(path = %path, enabled, "Link encryption changed");
+        Ok(())
+    })
+}
+
+/// `encryption_stats` Frames the encrypted link sent, received and rejected since the key was
+/// set, `None` while the link is not encrypted
+#[command]
+pub fn encryption_stats<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<Option<EncryptionStats>, Error> {
+    get_serialport(state, path, |serialport_info| {
+        match serialport_info.link.lock() {
+            Ok(link) => Ok(link.as_ref().map(Link::stats)),
+            Err(error) => Err(Error::String(format!("Cannot get encryption lock: {}", error))),
+        }
+    })
+}
+
+/// `set_compression` Send every write as frames of zlib or LZ4 compressed payloads and
+/// decompress the frames received, for firmware with the codec described in the README; `None`
+/// sends and reads plain data again. `level` (0 to 9, 6 by default) applies to zlib
+#[command]
+pub fn set_compression<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    algorithm: Option<compression::Algorithm>,
+    level: Option<u32>,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let compression = algorithm
+            .map(|algorithm| Compression::new(algorithm, level))
+            .transpose()
+            .map_err(|message| Error::InvalidSettings {
+                path: path.clone(),
+                message,
+            })?;
+        match serialport_info.compression.lock() {
+            Ok(mut current) => *current = compression,
+            Err(error) => {
+                return Err(Error::String(format!("Cannot get compression lock: {}", error)))
+            }
+        }
+        info!(path = %path, algorithm = ?algorithm, "Compression changed");
+        Ok(())
+    })
+}
+
+/// `compression_stats` Bytes before and after compression in both directions since it was set,
+/// `None` while the port is not compressed
+#[command]
+pub fn compression_stats<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<Option<CompressionStats>, Error> {
+    get_serialport(state, path, |serialport_info| {
+        match serialport_info.compression.lock() {
+            Ok(compression) => Ok(compression.as_ref().map(Compression::stats)),
+            Err(error) => Err(Error::String(format!("Cannot get compression lock: {}", error))),
+        }
+    })
+}
+
+/// `set_address_filter` Deliver only the received frames addressed to `filter.address`, `None` delivers all again.
+/// 9-bit mode switches the port to 8E1 and marks parity errors to recover the ninth bit; its
+/// data bits and parity are restored when 9-bit mode ends.
+#[command]
+pub fn set_address_filter<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    filter: Option<AddressFilter>,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let mut guard = serialport_info
+            .address_filter
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get address filter lock: {}", error)))?;
+        let was_nine_bit = guard.as_ref().map_or(false, |active| active.settings.nine_bit);
+        let mut restore = guard.as_ref().and_then(|active| active.restore);
+        let nine_bit = filter.map_or(false, |filter| filter.nine_bit);
+        if nine_bit && is_marking_errors(serialport_info) {
+            return Err(Error::InvalidSettings {
+                path,
+                message: "9-bit addresses cannot be used with break detection or error markers"
+                    .to_string(),
+            });
+        }
+        if nine_bit != was_nine_bit {
+            let marked: io::Result<()> = match serialport_info.native {
+                #[cfg(unix)]
+                Some(fd) => crate::termios::mark_errors(fd as i32, nine_bit),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "9-bit addresses need a native Unix serial port",
+                )),
+            };
+            let port = &mut serialport_info.serialport;
+            let mut configure = || -> serialport::Result<()> {
+                if nine_bit {
+                    restore = Some((port.data_bits()?, port.parity()?));
+                    port.set_data_bits(DataBits::Eight)?;
+                    port.set_parity(Parity::Even)
+                } else if let Some((data_bits, parity)) = restore.take() {
+                    port.set_data_bits(data_bits)?;
+                    port.set_parity(parity)
+                } else {
+                    Ok(())
+                }
+            };
+            let configured = marked
+                .map_err(|error| error.to_string())
+                .and_then(|_| configure().map_err(|error| error.to_string()));
+            if let Err(message) = configured {
+                return Err(Error::InvalidSettings { path, message });
+            }
+        }
+        *guard = filter.map(|filter| {
+            let mut filter = Filter::new(filter);
+            filter.restore = restore;
+            filter
+        });
+        match filter {
+            Some(filter) => info!(path = %path, address = filter.address, "Address filter set"),
+            None => info!(path = %path, "Address filter removed"),
+        }
+        Ok(())
+    })
+}
+
+/// `is_reading` Whether the background read delivers what is received; in `Notify` mode it only
+/// announces it, so exchanges read their answers themselves
+fn is_reading(serialport_info: &SerialportInfo) -> bool {
+    serialport_info.sender.is_some() && serialport_info.data_ready.is_none()
+}
+
+fn is_marking_errors(serialport_info: &SerialportInfo) -> bool {
+    serialport_info
+        .line_status
+        .lock()
+        .map_or(false, |status| status.is_marking())
+}
+
+/// `set_line_marking` Change the break detection and error marker of a port with `change`,
+/// switching the marks of the driver on or off with them
+fn set_line_marking(
+    path: String,
+    serialport_info: &mut SerialportInfo,
+    change: impl FnOnce(bool, Option<Vec<u8>>) -> (bool, Option<Vec<u8>>),
+) -> Result<(), Error> {
+    let nine_bit = match serialport_info.address_filter.lock() {
+        Ok(filter) => filter.as_ref().map_or(false, |filter| filter.settings.nine_bit),
+        Err(error) => return Err(Error::String(format!("Cannot get address filter lock: {}", error))),
+    };
+    let mut status = serialport_info
+        .line_status
+        .lock()
+        .map_err(|error| Error::String(format!("Cannot get line status lock: {}", error)))?;
+    let was_marking = status.is_marking();
+    let (detect_breaks, marker) = change(status.detect_breaks, status.marker.clone());
+    let marking = detect_breaks || marker.is_some();
+    if marker.is_some() && !cfg!(unix) {
+        return Err(Error::InvalidSettings {
+            path,
+            message: "Error markers need a native Unix port".to_string(),
+        });
+    }
+    if marking && nine_bit {
+        return Err(Error::InvalidSettings {
+            path,
+            message: "Breaks and errors cannot be marked while 9-bit addresses are filtered"
+                .to_string(),
+        });
+    }
+    if marking != was_marking {
+        let marked = match serialport_info.native {
+            #[cfg(unix)]
+            Some(fd) => crate::termios::mark_errors(fd as i32, marking),
+            #[cfg(windows)]
+            Some(_) => Ok(()),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Breaks and errors are only reported by native serial ports",
+            )),
+        };
+        if let Err(error) = marked {
+            return Err(Error::InvalidSettings {
+                path,
+                message: error.to_string(),
+            });
+        }
+        status.reset_marks();
+    }
+    status.detect_breaks = detect_breaks;
+    status.marker = marker;
+    Ok(())
+}
+
+/// `set_break_detection` Report received breaks as break events and remove them from the data.
+/// On Unix the port marks them in the stream, so a break is told apart from a NUL byte and
+/// splits the data where it was received; Windows reports it after the data read with it.
+#[command]
+pub fn set_break_detection<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    enabled: bool,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        set_line_marking(path.clone(), serialport_info, |_, marker| (enabled, marker))?;
+        info!(path = %path, enabled, "Break detection set");
+        Ok(())
+    })
+}
+
+/// `set_error_markers` Put `marker` before every byte received with a parity or framing error,
+/// `None` delivers them unmarked again. Needs a native Unix port, whose driver marks them
+#[command]
+pub fn set_error_markers<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    marker: Option<Vec<u8>>,
+) -> Result<(), Error> {
+    if marker.as_ref().map_or(false, |marker| marker.is_empty()) {
+        return Err(Error::InvalidSettings {
+            path,
+            message: "The error marker is empty".to_string(),
+        });
+    }
+    get_serialport(state, path.clone(), |serialport_info| {
+        let enabled = marker.is_some();
+        set_line_marking(path.clone(), serialport_info, |detect_breaks, _| {
+            (detect_breaks, marker)
+        })?;
+        info!(path = %path, enabled, "Error markers set");
+        Ok(())
+    })
+}
+
+/// `line_errors` The parity errors, framing errors, breaks and overruns of the port since it was
+/// opened
+#[command]
+pub fn line_errors<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<LineErrors, Error> {
+    get_serialport(state, path, |serialport_info| {
+        let native = serialport_info.native;
+        match serialport_info.line_status.lock() {
+            Ok(mut status) => Ok(status.errors(native)),
+            Err(error) => Err(Error::String(format!("Cannot get line status lock: {}", error))),
+        }
+    })
+}
+
+/// `set_log_level` Log hex dumps of the port traffic at `level` (`Off`, `Error`, `Warn`, `Info`, `Debug`, `Trace`)
+#[command]
+pub fn set_log_level<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    level: String,
+) -> Result<(), Error> {
+    let level = match parse_level_filter(&level) {
+        Some(level) => level,
+        None => return Err(Error::String(format!("Invalid log level: {}", level))),
+    };
+    get_serialport(state, path.clone(), |serialport_info| {
+        serialport_info
+            .log_level
+            .store(level_filter_to_u8(level), Ordering::Relaxed);
+        info!(path = %path, "Wire logging set to {}", level);
+        Ok(())
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum IdleAction {
+    Notify,
+    Close,
+    Reopen,
+}
+
+fn get_idle_action(value: Option<String>) -> Option<IdleAction> {
+    match value.as_deref() {
+        None | Some("Notify") => Some(IdleAction::Notify),
+        Some("Close") => Some(IdleAction::Close),
+        Some("Reopen") => Some(IdleAction::Reopen),
+        _ => None,
+    }
+}
+
+/// `OpenSettings` The settings a port is opened again with.
+#[derive(Clone)]
+struct OpenSettings {
+    baud_rate: u32,
+    data_bits: usize,
+    flow_control: String,
+    parity: String,
+    stop_bits: usize,
+    timeout: u64,
+    rs485: Option<NativeRs485>,
+    access: Access,
+}
+
+/// `port_settings` The current settings of a port, failing when the driver cannot tell them.
+fn port_settings(serialport_info: &SerialportInfo, path: &str) -> Result<OpenSettings, Error> {
+    let serial = &serialport_info.serialport;
+    let error = |error: serialport::Error| {
+        Error::String(format!("Cannot read settings of port {}: {}", path, error))
+    };
+    let parity = match serialport_info.stick_parity {
+        Some(true) => "Mark".to_string(),
+        Some(false) => "Space".to_string(),
+        None => serial.parity().map_err(error)?.to_string(),
+    };
+    Ok(OpenSettings {
+        baud_rate: serial.baud_rate().map_err(error)?,
+        data_bits: u8::from(serial.data_bits().map_err(error)?) as usize,
+        flow_control: serial.flow_control().map_err(error)?.to_string(),
+        parity,
+        stop_bits: u8::from(serial.stop_bits().map_err(error)?) as usize,
+        timeout: serial.timeout().as_millis() as u64,
+        rs485: serialport_info.rs485,
+        access: serialport_info.access(),
+    })
+}
+
+/// `reopen_with` Close the port and open it again with `settings`, under the same event id.
+fn reopen_with<R: Runtime>(
+    app: &AppHandle<R>,
+    window: &Window<R>,
+    path: &str,
+    settings: OpenSettings,
+) -> Result<(), Error> {
+    let state = app.state::<SerialportState>();
+    let event_id = Some(event_id_of(&state, path));
+    close(app.clone(), window.clone(), state.clone(), path.to_string())?;
+    open(
+        app.clone(),
+        state,
+        window.clone(),
+        path.to_string(),
+        settings.baud_rate,
+        Some(settings.data_bits),
+        Some(settings.flow_control),
+        Some(settings.parity),
+        Some(settings.stop_bits),
+        Some(settings.timeout),
+        settings.rs485,
+        event_id,
+        Some(format!("{:?}", settings.access)),
+    )?;
+    Ok(())
+}
+
+/// `reopen_port` Close the port and open it again with its current settings. Returns the stop
+/// channel and activity clock of the new port for the watchdog.
+fn reopen_port<R: Runtime>(
+    app: &AppHandle<R>,
+    window: &Window<R>,
+    path: &str,
+) -> Result<(Receiver<()>, Arc<AtomicU64>), Error> {
+    let state = app.state::<SerialportState>();
+    let settings = get_serialport(state.clone(), path.to_string(), |serialport_info| {
+        port_settings(serialport_info, path)
+    })?;
+    reopen_with(app, window, path, settings)?;
+    get_serialport(state, path.to_string(), |serialport_info| {
+        let (tx, rx) = mpsc::channel();
+        serialport_info.watchdog = Some(tx);
+        Ok((rx, serialport_info.last_activity.clone()))
+    })
+}
+
+/// `run_idle_watchdog` Watch the activity clock of a port until the port closes or the
+/// watchdog is replaced
+fn run_idle_watchdog<R: Runtime>(
+    app: AppHandle<R>,
+    window: Window<R>,
+    path: String,
+    timeout: Duration,
+    action: IdleAction,
+    mut stop: Receiver<()>,
+    mut last_activity: Arc<AtomicU64>,
+) {
+    let _span = tracing::info_span!("serialport", path = %path).entered();
+    let interval = (timeout / 4).clamp(Duration::from_millis(100), Duration::from_secs(1));
+    let idle_event = event_name(&window, "idle", &path);
+    // one event per idle period
+    let mut notified = false;
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+        let idle_ms = now_millis().saturating_sub(last_activity.load(Ordering::Relaxed));
+        if idle_ms < timeout.as_millis() as u64 {
+            notified = false;
+            continue;
+        }
+        if notified {
+            continue;
+        }
+        notified = true;
+        let name = match action {
+            IdleAction::Notify => "Notify",
+            IdleAction::Close => "Close",
+            IdleAction::Reopen => "Reopen",
+        };
+        warn!(idle_ms, "Port is idle ({})", name);
+        if let Err(error) = window.emit(&idle_event, IdleData { idle_ms, action: name }) {
+            error!("Failed to emit event: {}", error);
+        }
+        match action {
+            IdleAction::Notify => {}
+            IdleAction::Close => {
+                let _ = close(app.clone(), window.clone(), app.state(), path.clone());
+                break;
+            }
+            IdleAction::Reopen => match reopen_port(&app, &window, &path) {
+                Ok((new_stop, new_last_activity)) => {
+                    info!("Reopened idle port");
+                    stop = new_stop;
+                    last_activity = new_last_activity;
+                    notified = false;
+                }
+                Err(error) => {
+                    error!("Failed to reopen idle port: {}", error);
+                    break;
+                }
+            },
+        }
+    }
+}
+
+/// How long a port dropped during a sleep may take to show up again after the resume.
+const RESUME_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `run_power_watch` Check the open ports after each system sleep: emit `suspended`, reopen
+/// the ports the USB stack dropped with the settings they had before, and emit `resumed`
+fn run_power_watch<R: Runtime>(app: AppHandle<R>, window: Window<R>) {
+    let state = app.state::<SerialportState>();
+    let mut clock = Clock::new();
+    // the settings of each port while it was last seen alive
+    let mut known: HashMap<String, OpenSettings> = HashMap::new();
+    loop {
+        let sleep = clock.step();
+        let alive: HashMap<String, bool> = match state.serialports.lock() {
+            Ok(serialports) => {
+                known.retain(|path, _| serialports.contains_key(path));
+                serialports
+                    .iter()
+                    .map(|(path, serialport_info)| {
+                        let settings = port_settings(serialport_info, path);
+                        let alive = settings.is_ok();
+                        if let Ok(settings) = settings {
+                            // settings seen after a sleep, the reopen needs those before it
+                            if sleep.is_none() || !known.contains_key(path) {
+                                known.insert(path.clone(), settings);
+                            }
+                        }
+                        (path.clone(), alive)
+                    })
+                    .collect()
+            }
+            Err(error) => {
+                error!("Cannot get serialports lock: {}", error);
+                continue;
+            }
+        };
+        let sleep = match sleep {
+            Some(sleep) => sleep,
+            None => continue,
+        };
+        let slept_ms = sleep.slept.as_millis() as u64;
+        info!(slept_ms, "System resumed from sleep");
+        for (path, alive) in alive {
+            let suspended = SuspendedData {
+                timestamp: sleep.suspended_at,
+            };
+            if let Err(error) = window.emit(&event_name(&window, "suspended", &path), suspended) {
+                error!("Failed to emit event: {}", error);
+            }
+            let mut error = None;
+            if !alive {
+                warn!(path = %path, "Port was dropped during the sleep");
+                error = match known.get(&path) {
+                    Some(settings) => {
+                        reopen_after_sleep(&app, &window, &path, settings.clone()).err()
+                    }
+                    None => Some(format!("Settings of port {} are unknown", path)),
+                };
+            }
+            let resumed = ResumedData {
+                slept_ms,
+                alive,
+                reopened: !alive && error.is_none(),
+                error,
+                timestamp: now_millis(),
+            };
+            if let Err(error) = window.emit(&event_name(&window, "resumed", &path), resumed) {
+                error!("Failed to emit event: {}", error);
+            }
+        }
+        // the ports just reopened are not seen asleep again
+        clock = Clock::new();
+    }
+}
+
+/// `reopen_after_sleep` Wait for a dropped port to be enumerated again, then reopen it
+fn reopen_after_sleep<R: Runtime>(
+    app: &AppHandle<R>,
+    window: &Window<R>,
+    path: &str,
+    settings: OpenSettings,
+) -> Result<(), String> {
+    let deadline = Instant::now() + RESUME_TIMEOUT;
+    loop {
+        let listed = serialport::available_ports()
+            .map(|ports| ports.iter().any(|port| port.port_name == path))
+            .unwrap_or(false);
+        if listed {
+            break;
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("Port {} did not come back after the sleep", path));
+        }
+        thread::sleep(Duration::from_millis(250));
+    }
+    reopen_with(app, window, path, settings).map_err(|error| error.to_string())?;
+    info!(path = %path, "Reopened port dropped during the sleep");
+    Ok(())
+}
+
+/// `set_idle_timeout` Emit an idle event when the port has no traffic for `seconds` (0 turns the
+/// watchdog off); with `action` `Close` or `Reopen` the port is also closed or reopened
+#[command]
+pub fn set_idle_timeout<R: Runtime>(
+    app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    seconds: u64,
+    action: Option<String>,
+) -> Result<(), Error> {
+    let action = match get_idle_action(action.clone()) {
+        Some(action) => action,
+        None => {
+            return Err(Error::String(format!(
+                "Invalid idle action: {}",
+                action.unwrap_or_default()
+            )))
+        }
+    };
+    get_serialport(state, path.clone(), |serialport_info| {
+        // dropping the sender stops the previous watchdog
+        serialport_info.watchdog = None;
+        if seconds == 0 {
+            info!(path = %path, "Idle watchdog disabled");
+            return Ok(());
+        }
+        let (tx, rx) = mpsc::channel();
+        serialport_info.watchdog = Some(tx);
+        let last_activity = serialport_info.last_activity.clone();
+        info!(path = %path, seconds, "Idle watchdog enabled");
+        thread::spawn(move || {
+            run_idle_watchdog(
+                app,
+                window,
+                path,
+                Duration::from_secs(seconds),
+                action,
+                rx,
+                last_activity,
+            )
+        });
+        Ok(())
+    })
+}
+
+/// `start_heartbeat` Write `bytes` every `interval_ms` from a background thread until
+/// `stop_heartbeat`; heartbeats do not count as activity for the idle watchdog
+#[command]
+pub fn start_heartbeat<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    bytes: Vec<u8>,
+    interval_ms: u64,
+) -> Result<(), Error> {
+    if interval_ms == 0 {
+        return Err(Error::String("Heartbeat interval must not be 0".to_string()));
+    }
+    get_serialport(state, path.clone(), |serialport_info| {
+        let mut serial = serialport_info
+            .serialport
+            .try_clone()
+            .map_err(|error| Error::String(format!("Failed to clone port {}: {}", path, error)))?;
+        let (tx, rx): (Sender<()>, Receiver<()>) = mpsc::channel();
+        // replacing the sender stops a running heartbeat
+        serialport_info.heartbeat = Some(tx);
+        let monitor = serialport_info.monitor.clone();
+        let log_level = serialport_info.log_level.clone();
+        info!(path = %path, interval_ms, "Heartbeat started");
+        thread::spawn(move || {
+            let _span = tracing::info_span!("serialport", path = %path).entered();
+            let interval = Duration::from_millis(interval_ms);
+            // scheduled by deadline so the interval does not drift with write time
+            let mut deadline = Instant::now();
+            loop {
+                match serial.write_all(&bytes) {
+                    Ok(()) => {
+                        log_traffic(log_level.load(Ordering::Relaxed), &path, "TX", &bytes);
+                        if monitor.load(Ordering::Relaxed) {
+                            emit_monitor_data(&window, &path, "TX", &bytes);
+                        }
+                    }
+                    Err(error) => warn!("Heartbeat write failed: {}", error),
+                }
+                deadline += interval;
+                let now = Instant::now();
+                if deadline < now {
+                    // a slow write, skip the missed beats instead of bursting them
+                    deadline = now;
+                }
+                match rx.recv_timeout(deadline - now) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+            }
+            info!("Heartbeat stopped");
+        });
+        Ok(())
+    })
+}
+
+/// `stop_heartbeat` Stop the heartbeat of the port
+#[command]
+pub fn stop_heartbeat<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<(), Error> {
+    get_serialport(state, path, |serialport_info| {
+        serialport_info.heartbeat = None;
+        Ok(())
+    })
+}
+
+/// `start_dmx` Switch the port to DMX512 and send the channel frame `refresh_hz` times per
+/// second (default and maximum 44) from a dedicated thread until `stop_dmx`. The port must not
+/// be reading or driven by another thread, a running output is restarted
+#[command]
+pub fn start_dmx<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    refresh_hz: Option<u32>,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        refuse_worker(serialport_info, &path, Some(Worker::Dmx))?;
+        // a restart keeps the channel values, the old thread is gone before the port is
+        // reconfigured
+        let frame = match serialport_info.dmx.take() {
+            Some(dmx) => dmx.frame.clone(),
+            None => Arc::new(Mutex::new([0; dmx::CHANNELS])),
+        };
+        dmx::configure(serialport_info.serialport.as_mut()).map_err(|error| {
+            Error::String(format!("Failed to configure {} for DMX: {}", path, error))
+        })?;
+        let serial = serialport_info
+            .serialport
+            .try_clone()
+            .map_err(|error| Error::String(format!("Failed to clone port {}: {}", path, error)))?;
+        let (tx, rx): (Sender<()>, Receiver<()>) = mpsc::channel();
+        let refresh = refresh_hz.unwrap_or(dmx::MAX_REFRESH);
+        info!(path = %path, refresh, "DMX output started");
+        let output = frame.clone();
+        let thread = thread::spawn(move || {
+            let _span = tracing::info_span!("serialport", path = %path).entered();
+            dmx::run(serial, output, refresh, rx);
+        });
+        serialport_info.dmx = Some(Dmx {
+            frame,
+            stop: tx,
+            thread: Some(thread),
+        });
+        Ok(())
+    })
+}
+
+/// `stop_dmx` Stop the DMX output of the port
+#[command]
+pub fn stop_dmx<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<(), Error> {
+    get_serialport(state, path, |serialport_info| {
+        serialport_info.dmx = None;
+        Ok(())
+    })
+}
+
+/// `set_channels` Set DMX channels from `start` (1 to 512, default 1) on, the next packet
+/// carries them
+#[command]
+pub fn set_channels<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    start: Option<usize>,
+    values: Vec<u8>,
+) -> Result<(), Error> {
+    let start = start.unwrap_or(1);
+    if start == 0 || start - 1 + values.len() > dmx::CHANNELS {
+        return Err(Error::String(format!(
+            "Channels {} to {} are outside 1 to {}",
+            start,
+            start + values.len().saturating_sub(1),
+            dmx::CHANNELS
+        )));
+    }
+    get_serialport(state, path.clone(), |serialport_info| {
+        let dmx = serialport_info
+            .dmx
+            .as_ref()
+            .ok_or_else(|| Error::String(format!("DMX output is not started on {}", path)))?;
+        let mut frame = dmx
+            .frame
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get DMX lock: {}", error)))?;
+        frame[start - 1..start - 1 + values.len()].copy_from_slice(&values);
+        Ok(())
+    })
+}
+
+/// `blackout` Set all DMX channels to 0
+#[command]
+pub fn blackout<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let dmx = serialport_info
+            .dmx
+            .as_ref()
+            .ok_or_else(|| Error::String(format!("DMX output is not started on {}", path)))?;
+        let mut frame = dmx
+            .frame
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get DMX lock: {}", error)))?;
+        *frame = [0; dmx::CHANNELS];
+        Ok(())
+    })
+}
+
+/// `start_gateway` Listen on `address` (`127.0.0.1:502` by default) for Modbus TCP clients and
+/// forward their requests as RTU transactions on the port until `stop_gateway`. The gateway has
+/// no authentication, so addresses other than loopback need `allow_remote`
+#[command]
+pub fn start_gateway<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    address: Option<String>,
+    timeout_ms: Option<u64>,
+    allow_remote: Option<bool>,
+) -> Result<(), Error> {
+    let address = address.unwrap_or_else(|| "127.0.0.1:502".to_string());
+    let addresses: Vec<std::net::SocketAddr> = std::net::ToSocketAddrs::to_socket_addrs(&address)
+        .map_err(|error| Error::InvalidSettings {
+            path: path.clone(),
+            message: format!("Invalid gateway address {}: {}", address, error),
+        })?
+        .collect();
+    if !allow_remote.unwrap_or(false) && addresses.iter().any(|socket| !socket.ip().is_loopback()) {
+        return Err(Error::InvalidSettings {
+            path,
+            message: format!(
+                "The gateway has no authentication, listening on {} needs allow_remote",
+                address
+            ),
+        });
+    }
+    let listener = std::net::TcpListener::bind(&addresses[..])
+        .map_err(|error| Error::String(format!("Cannot listen on {}: {}", address, error)))?;
+    let serial = get_idle_port(state.clone(), &path)?;
+    get_serialport(state, path.clone(), |serialport_info| {
+        let (tx, rx): (Sender<()>, Receiver<()>) = mpsc::channel();
+        // replacing the sender stops a running gateway
+        serialport_info.gateway = Some(tx);
+        let timeout = Duration::from_millis(timeout_ms.unwrap_or(1000));
+        info!(path = %path, address = %address, "Modbus gateway started");
+        thread::spawn(move || {
+            let _span = tracing::info_span!("serialport", path = %path).entered();
+            gateway::run(listener, serial, timeout, rx);
+        });
+        Ok(())
+    })
+}
+
+/// `stop_gateway` Stop the Modbus TCP gateway of the port
+#[command]
+pub fn stop_gateway<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<(), Error> {
+    get_serialport(state, path, |serialport_info| {
+        serialport_info.gateway = None;
+        Ok(())
+    })
+}
+
+/// `start_mux` Split the port into channels of the channel-id framing described in the README,
+/// each opened as `mux://<path>:<channel>`. Bytes outside frames go to the `unframed` channel,
+/// or are dropped without one. The port itself is no longer read until `stop_mux`
+#[command]
+pub fn start_mux<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    unframed: Option<u8>,
+) -> Result<(), Error> {
+    let serial = get_idle_port(state.clone(), &path)?;
+    let writer = get_idle_port(state.clone(), &path)?;
+    get_serialport(state, path.clone(), |serialport_info| {
+        if serialport_info.mux.is_some() {
+            return Err(Error::String(format!("Port {} is already split", path)));
+        }
+        let demux = Arc::new(Demux::new("Multiplexed", writer, Box::new(mux::encode)));
+        let (tx, rx): (Sender<()>, Receiver<()>) = mpsc::channel();
+        serialport_info.mux = Some(Mux {
+            demux: demux.clone(),
+            _stop: tx,
+        });
+        info!(path = %path, "Split into channels");
+        thread::spawn(move || {
+            let _span = tracing::info_span!("serialport", path = %path).entered();
+            mux::run(serial, demux, Deframer::new(unframed), rx);
+        });
+        Ok(())
+    })
+}
+
+/// `start_cmux` Switch a cellular modem to the 3GPP TS 27.010 multiplexer with `command`
+/// (`AT+CMUX=0` by default, `""` when it already is) and establish the DLCs `channels`, each
+/// opened as `mux://<path>:<dlci>`; returns those the modem accepted. `frame_size` is the N1
+/// the command set, 31 by default. `stop_mux` closes the multiplexer on the modem as well
+#[command]
+pub async fn start_cmux<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    channels: Vec<u8>,
+    command: Option<String>,
+    frame_size: Option<usize>,
+) -> Result<Vec<u8>, Error> {
+    if let Some(dlci) = channels
+        .iter()
+        .find(|dlci| **dlci == 0 || **dlci > cmux::MAX_DLCI)
+    {
+        return Err(Error::InvalidSettings {
+            path,
+            message: format!("DLCIs go from 1 to {}, not {}", cmux::MAX_DLCI, dlci),
+        });
+    }
+    let split = get_serialport(state.clone(), path.clone(), |serialport_info| {
+        Ok(serialport_info.mux.is_some())
+    })?;
+    if split {
+        return Err(Error::String(format!("Port {} is already split", path)));
+    }
+    let mut serial = get_idle_port(state.clone(), &path)?;
+    let writer = get_idle_port(state.clone(), &path)?;
+    let command = command.unwrap_or_else(|| "AT+CMUX=0".to_string());
+    let frame_size = frame_size.unwrap_or(cmux::DEFAULT_FRAME_SIZE);
+    let thread_path = path.clone();
+    let (demux, opened, tx) = blocking(move || {
+        if !command.is_empty() {
+            cmux::switch(serial.as_mut(), &command).map_err(Error::String)?;
+        }
+        let demux = Arc::new(Demux::new(
+            "CMUX",
+            writer,
+            Box::new(cmux::encoder(frame_size)),
+        ));
+        demux.limit(Vec::new());
+        let session = Arc::new(Session::new(demux.clone()));
+        let (tx, rx): (Sender<()>, Receiver<()>) = mpsc::channel();
+        let reader = session.clone();
+        let path = thread_path.clone();
+        thread::spawn(move || {
+            let _span = tracing::info_span!("serialport", path = %thread_path).entered();
+            cmux::run(serial, reader, rx);
+        });
+        // dropping the sender on failure closes the multiplexer again
+        session.connect(0).map_err(Error::String)?;
+        let mut opened = Vec::new();
+        for dlci in channels {
+            match session.connect(dlci) {
+                Ok(()) => opened.push(dlci),
+                Err(error) => warn!(path = %path, dlci, "{}", error),
+            }
+        }
+        demux.limit(opened.clone());
+        Ok((demux, opened, tx))
+    })
+    .await?;
+    get_serialport(state, path.clone(), |serialport_info| {
+        serialport_info.mux = Some(Mux { demux, _stop: tx });
+        Ok(())
+    })?;
+    info!(path = %path, channels = ?opened, "CMUX multiplexer started");
+    Ok(opened)
+}
+
+/// `stop_mux` Stop splitting the port; its channels fail from now on and should be closed. A
+/// CMUX multiplexer is closed on the modem as well
+#[command]
+pub fn stop_mux<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<(), Error> {
+    get_serialport(state, path, |serialport_info| {
+        serialport_info.mux = None;
+        Ok(())
+    })
+}
+
+/// `emit_poll_data` Send the answer to a poll to the frontend
+fn emit_poll_data<R: Runtime>(window: &Window<R>, path: &str, data: PollData) {
+    trace!(path = %path, id = %data.id, size = data.size, "Poll answered");
+    if let Err(error) = window.emit(&event_name(window, "poll", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `run_poll_scheduler` Write due poll requests and time out unanswered ones, until the port
+/// closes or the last poll is removed
+fn run_poll_scheduler<R: Runtime>(
+    window: Window<R>,
+    path: String,
+    mut serial: Box<dyn serialport::SerialPort>,
+    poller: Arc<Mutex<Poller>>,
+    wakeup: Receiver<()>,
+) {
+    let _span = tracing::info_span!("serialport", path = %path).entered();
+    loop {
+        let now = Instant::now();
+        let (request, expired, next_wakeup) = match poller.lock() {
+            Ok(mut poller) => {
+                if poller.is_empty() {
+                    // dropped under the lock, so `add_poll` sees a stopped scheduler
+                    drop(wakeup);
+                    break;
+                }
+                let expired = poller.expire(now);
+                (poller.due(now), expired, poller.next_wakeup())
+            }
+            Err(error) => {
+                error!("Cannot get poller lock: {}", error);
+                break;
+            }
+        };
+        if let Some(expired) = expired {
+            emit_poll_data(&window, &path, expired);
+        }
+        if let Some(request) = request {
+            if let Err(error) = serial.write_all(&request) {
+                warn!("Poll write failed: {}", error);
+            }
+            continue;
+        }
+        let wait = next_wakeup
+            .map(|next| next.saturating_duration_since(Instant::now()))
+            .unwrap_or(Duration::from_secs(1));
+        if let Err(RecvTimeoutError::Disconnected) = wakeup.recv_timeout(wait) {
+            break;
+        }
+    }
+    debug!("Poll scheduler stopped");
+}
+
+/// `add_poll` Write `request` every `interval_ms` and emit the answers as poll events. An
+/// answer ends with `terminator`, after `response_length` bytes or at the response timeout;
+/// answers are taken from the background read, which has to be running
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub fn add_poll<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: String,
+    request: Vec<u8>,
+    interval_ms: u64,
+    terminator: Option<Vec<u8>>,
+    response_length: Option<usize>,
+    response_timeout_ms: Option<u64>,
+) -> Result<(), Error> {
+    if interval_ms == 0 {
+        return Err(Error::String("Poll interval must not be 0".to_string()));
+    }
+    let interval = Duration::from_millis(interval_ms);
+    let mut poll = Poll::new(id.clone(), request, interval);
+    poll.terminator = terminator;
+    poll.length = response_length;
+    if let Some(response_timeout_ms) = response_timeout_ms {
+        poll.response_timeout = Duration::from_millis(response_timeout_ms);
+    }
+    get_serialport(state, path.clone(), |serialport_info| {
+        if serialport_info.data_ready.is_some() {
+            return Err(Error::String(format!(
+                "Port {} is read in Notify mode, which does not receive poll answers",
+                path
+            )));
+        }
+        let mut poller = serialport_info
+            .poller
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get poller lock: {}", error)))?;
+        poller.add(poll);
+        info!(path = %path, id = %id, interval_ms, "Poll added");
+        let running = match &serialport_info.poll_wakeup {
+            Some(wakeup) => wakeup.send(()).is_ok(),
+            None => false,
+        };
+        if !running {
+            let serial = serialport_info.serialport.try_clone().map_err(|error| {
+                Error::String(format!("Failed to clone port {}: {}", path, error))
+            })?;
+            let (tx, rx) = mpsc::channel();
+            serialport_info.poll_wakeup = Some(tx);
+            let poller = serialport_info.poller.clone();
+            thread::spawn(move || run_poll_scheduler(window, path, serial, poller, rx));
+        }
+        Ok(())
+    })
+}
+
+/// `remove_poll` Stop a poll registered with `add_poll`
+#[command]
+pub fn remove_poll<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: String,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let mut poller = serialport_info
+            .poller
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get poller lock: {}", error)))?;
+        if !poller.remove(&id) {
+            return Err(Error::String(format!("Poll {} not found on port {}", id, path)));
+        }
+        if let Some(wakeup) = &serialport_info.poll_wakeup {
+            let _ = wakeup.send(());
+        }
+        info!(path = %path, id = %id, "Poll removed");
+        Ok(())
+    })
+}
+
+/// `emit_match_data` Send a pattern match to the frontend
+fn emit_match_data<R: Runtime>(window: &Window<R>, path: &str, data: MatchData) {
+    debug!(path = %path, id = %data.id, "Pattern matched");
+    if let Err(error) = window.emit(&event_name(window, "match", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `get_matcher` A byte sequence, or a regex over the raw bytes
+fn get_matcher(pattern: Option<Vec<u8>>, regex: Option<String>) -> Result<Matcher, Error> {
+    match (pattern, regex) {
+        (_, Some(regex)) => regex::bytes::Regex::new(&regex)
+            .map(Matcher::Regex)
+            .map_err(|error| Error::String(format!("Invalid regex: {}", error))),
+        (Some(pattern), None) if !pattern.is_empty() => Ok(Matcher::Bytes(pattern)),
+        _ => Err(Error::String("Either pattern or regex is required".to_string())),
+    }
+}
+
+/// `watch_pattern` Emit a match event whenever the received data contains `pattern` (bytes) or
+/// matches `regex`, with up to `context` bytes around it; needs the background read
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub fn watch_pattern<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: String,
+    pattern: Option<Vec<u8>>,
+    regex: Option<String>,
+    context: Option<usize>,
+) -> Result<(), Error> {
+    let matcher = get_matcher(pattern, regex)?;
+    get_serialport(state, path.clone(), |serialport_info| {
+        let mut watchers = serialport_info
+            .watchers
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get watcher lock: {}", error)))?;
+        watchers.retain(|watcher| watcher.id != id);
+        watchers.push(Watcher::new(id.clone(), matcher, context.unwrap_or(32)));
+        info!(path = %path, id = %id, "Watching pattern");
+        Ok(())
+    })
+}
+
+/// `unwatch_pattern` Remove a watcher registered with `watch_pattern`
+#[command]
+pub fn unwatch_pattern<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: String,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let mut watchers = serialport_info
+            .watchers
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get watcher lock: {}", error)))?;
+        let count = watchers.len();
+        watchers.retain(|watcher| watcher.id != id);
+        if watchers.len() == count {
+            return Err(Error::String(format!("Watcher {} not found on port {}", id, path)));
+        }
+        Ok(())
+    })
+}
+
+/// `emit_capture_data` Send the start or the result of a triggered capture to the frontend
+fn emit_capture_data<R: Runtime>(window: &Window<R>, path: &str, data: CaptureData) {
+    if let Err(error) = window.emit(&event_name(window, "capture", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `finish_capture` Write the recorded bytes to the capture file, or send them with the event
+fn finish_capture<R: Runtime>(window: &Window<R>, path: &str, mut capture: Capture, reason: &str) {
+    let data = capture.take();
+    let size = data.len();
+    info!(path = %path, size, "Capture ended ({})", reason);
+    let data = match &capture.file {
+        Some(file) => {
+            if let Err(error) = std::fs::write(file, &data) {
+                error!(path = %path, "Cannot write capture to {}: {}", file, error);
+            }
+            Vec::new()
+        }
+        None => data,
+    };
+    emit_capture_data(
+        window,
+        path,
+        CaptureData {
+            reason: reason.to_string(),
+            data,
+            size,
+            file: capture.file.clone(),
+            timestamp: now_millis(),
+        },
+    );
+}
+
+/// `get_optional_matcher` Like `get_matcher`, but neither argument is fine
+fn get_optional_matcher(
+    pattern: Option<Vec<u8>>,
+    regex: Option<String>,
+) -> Result<Option<Matcher>, Error> {
+    match (&pattern, &regex) {
+        (None, None) => Ok(None),
+        _ => get_matcher(pattern, regex).map(Some),
+    }
+}
+
+/// `start_capture` Arm a triggered capture: nothing is recorded until the start pattern arrives,
+/// then the data is recorded until the stop pattern or `max_bytes` and sent as a capture event,
+/// or written to `file`; needs the background read
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub fn start_capture<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    start_pattern: Option<Vec<u8>>,
+    start_regex: Option<String>,
+    stop_pattern: Option<Vec<u8>>,
+    stop_regex: Option<String>,
+    max_bytes: Option<usize>,
+    file: Option<String>,
+) -> Result<(), Error> {
+    let start = get_matcher(start_pattern, start_regex)?;
+    let stop = get_optional_matcher(stop_pattern, stop_regex)?;
+    get_serialport(state, path.clone(), |serialport_info| {
+        let mut capture = serialport_info
+            .capture
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get capture lock: {}", error)))?;
+        *capture = Some(Capture::new(start, stop, max_bytes, file));
+        info!(path = %path, "Capture armed");
+        Ok(())
+    })
+}
+
+/// `stop_capture` Disarm the capture, data recorded so far is sent with reason `Cancelled`
+#[command]
+pub fn stop_capture<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<(), Error> {
+    let capture = get_serialport(state, path.clone(), |serialport_info| {
+        serialport_info
+            .capture
+            .lock()
+            .map(|mut capture| capture.take())
+            .map_err(|error| Error::String(format!("Cannot get capture lock: {}", error)))
+    })?;
+    match capture {
+        Some(capture) if capture.is_recording() => {
+            finish_capture(&window, &path, capture, "Cancelled");
+        }
+        Some(_) => info!(path = %path, "Capture disarmed"),
+        None => {}
+    }
+    Ok(())
+}
+
+/// `emit_frame_data` Send a frame decoded by the port codec to the frontend
+fn emit_frame_data<R: Runtime>(window: &Window<R>, path: &str, data: Vec<u8>) {
+    trace!(path = %path, size = data.len(), "Decoded frame");
+    let frame = FrameData {
+        size: data.len(),
+        data,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "frame", path), frame) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `get_codec_factory` The codec registered under `name`, or under the port path
+fn get_codec_factory(
+    state: &SerialportState,
+    path: &str,
+    name: Option<String>,
+) -> Result<Option<Arc<CodecFactory>>, Error> {
+    match name {
+        Some(name) => match state.codecs.get(&name) {
+            Some(factory) => Ok(Some(factory.clone())),
+            None => Err(Error::InvalidSettings {
+                path: path.to_string(),
+                message: format!("No codec {} is registered", name),
+            }),
+        },
+        None => Ok(state.codecs.get(path).cloned()),
+    }
+}
+
+/// `get_framing` `Raw` by default, `Codec` uses the named or the port's codec
+fn get_framing(
+    state: &SerialportState,
+    path: &str,
+    framing: Option<String>,
+    codec: Option<String>,
+) -> Result<Framing, Error> {
+    match framing.as_deref() {
+        None | Some("Raw") => Ok(Framing::Raw),
+        Some("Lines") => Ok(Framing::Lines),
+        Some("Codec") => match get_codec_factory(state, path, codec)? {
+            Some(factory) => Ok(Framing::Codec(factory())),
+            None => Err(Error::String(format!("No codec for port {}", path))),
+        },
+        Some(other) => Err(Error::InvalidSettings {
+            path: path.to_string(),
+            message: format!("Unknown framing: {}", other),
+        }),
+    }
+}
+
+/// `emit_packet` Send a frame parsed with the schema, or why it was rejected, to the frontend
+fn emit_packet<R: Runtime>(window: &Window<R>, path: &str, packet: Parsed) {
+    let result = match packet {
+        Parsed::Packet { fields, raw } => window.emit(
+            &event_name(window, "packet", path),
+            PacketData {
+                fields,
+                raw,
+                timestamp: now_millis(),
+            },
+        ),
+        Parsed::Invalid { message, raw } => {
+            debug!(path = %path, "Invalid packet: {}", message);
+            window.emit(
+                &event_name(window, "packet-error", path),
+                PacketError { message, raw },
+            )
+        }
+    };
+    if let Err(error) = result {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_decoded` Send a decoded value, or why it could not be decoded, to the frontend
+fn emit_decoded<R: Runtime>(
+    window: &Window<R>,
+    path: &str,
+    value: Result<serde_json::Value, DecodeError>,
+) {
+    let result = match value {
+        Ok(value) => window.emit(
+            &event_name(window, "decoded", path),
+            DecodedData {
+                value,
+                timestamp: now_millis(),
+            },
+        ),
+        Err(error) => {
+            debug!(path = %path, "Cannot decode payload: {}", error.message);
+            window.emit(&event_name(window, "decode-error", path), error)
+        }
+    };
+    if let Err(error) = result {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `set_schema` Parse the received data with a frame description, given directly or as a JSON
+/// file; without either the raw data is emitted again
+#[command]
+pub fn set_schema<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    schema: Option<Schema>,
+    file: Option<String>,
+) -> Result<(), Error> {
+    let schema = match (schema, file) {
+        (Some(schema), _) => Some(schema),
+        (None, Some(file)) => {
+            let text = std::fs::read_to_string(&file)?;
+            Some(serde_json::from_str::<Schema>(&text).map_err(|error| {
+                Error::String(format!("Invalid schema in {}: {}", file, error))
+            })?)
+        }
+        (None, None) => None,
+    };
+    if let Some(schema) = &schema {
+        schema.validate().map_err(|message| Error::InvalidSettings {
+            path: path.clone(),
+            message,
+        })?;
+    }
+    get_serialport(state, path.clone(), |serialport_info| {
+        let mut parser = serialport_info
+            .schema
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get schema lock: {}", error)))?;
+        *parser = schema.map(SchemaParser::new);
+        info!(path = %path, enabled = parser.is_some(), "Packet schema set");
+        Ok(())
+    })
+}
+
+/// `read_emitter` Deliver chunks of a port as read events, through its event rate limit.
+fn read_emitter<'a, R: Runtime>(
+    window: &'a Window<R>,
+    event: &'a str,
+    sequence: &'a AtomicU64,
+    backpressure: &'a Mutex<Backpressure>,
+) -> impl Fn(&[u8], u64, Stamp) + Copy + 'a {
+    move |data: &[u8], suppressed: u64, stamp: Stamp| {
+        deliver_read_data(
+            window,
+            event,
+            sequence,
+            backpressure,
+            data,
+            suppressed,
+            stamp,
+            false,
+        )
+    }
+}
+
+/// `ReadTask` The read of a port started by `read`, with everything its received data passes.
+struct ReadTask<R: Runtime> {
+    span: tracing::Span,
+    window: Window<R>,
+    path: String,
+    serial: Box<dyn SerialPort>,
+    /// Stops the read when a value arrives or the sender is dropped.
+    rx: Receiver<usize>,
+    read_event: String,
+    error_event: String,
+    /// Reused for every read, only the filled slice is emitted.
+    serial_buf: Vec<u8>,
+    batch: ReadBatch,
+    throttle: ReadThrottle,
+    error_count: u64,
+    decoder: Option<Decoder>,
+    midi: Option<MidiParser>,
+    firmata: Option<FirmataParser>,
+    ubx: Option<UbxParser>,
+    xbee: Option<XBeeParser>,
+    pipeline: Pipeline,
+    codec: Option<Arc<Mutex<Box<dyn Codec>>>>,
+    schema: Arc<Mutex<Option<SchemaParser>>>,
+    at: Arc<AtChannel>,
+    ring_buffer: Option<Arc<Mutex<RingBuffer>>>,
+    history: Arc<Mutex<History>>,
+    stream: Arc<Mutex<Stream>>,
+    monitor: Arc<AtomicBool>,
+    log_level: Arc<AtomicU8>,
+    last_activity: Arc<AtomicU64>,
+    poller: Arc<Mutex<Poller>>,
+    watchers: Arc<Mutex<Vec<Watcher>>>,
+    capture: Arc<Mutex<Option<Capture>>>,
+    address_filter: Arc<Mutex<Option<Filter>>>,
+    expectation: SharedExpectation,
+    opened: Instant,
+    sequence: Arc<AtomicU64>,
+    backpressure: Arc<Mutex<Backpressure>>,
+    line_status: Arc<Mutex<LineStatus>>,
+    native: Option<isize>,
+    interval: Duration,
+    /// Duplicate of the descriptor polled for data, closed with the task.
+    fd: Option<i32>,
+    /// Waits for data on Windows.
+    #[cfg(windows)]
+    waiter: Option<crate::overlapped::Waiter>,
+    /// Driven by a reactor worker, so reads must not wait for data.
+    shared: bool,
+    /// The device went away, which ends the read.
+    disconnected: bool,
+    /// Present in `Notify` mode, which only reports that data is waiting.
+    data_ready: Option<Arc<AtomicBool>>,
+    middleware: SharedChain,
+}
+
+/// `queued_input` The bytes the driver holds for reading, on Windows asked through `linestatus`
+/// so the errors reported with them are still counted. `None` when the port cannot tell.
+fn queued_input(
+    serial: &dyn SerialPort,
+    line_status: &Mutex<LineStatus>,
+    native: Option<isize>,
+) -> Option<u32> {
+    #[cfg(windows)]
+    if let Some((queued, _)) = linestatus::queued(line_status, native) {
+        return Some(queued);
+    }
+    #[cfg(not(windows))]
+    let _ = (line_status, native);
+    serial.bytes_to_read().ok()
+}
+
+impl<R: Runtime> ReadTask<R> {
+    /// `queued` The bytes the driver holds, a read is tried when it cannot tell.
+    fn queued(&self) -> u32 {
+        queued_input(self.serial.as_ref(), &self.line_status, self.native).unwrap_or(1)
+    }
+
+    /// `announce` Report waiting data once the input buffer is no longer empty, leaving the data
+    /// to `read_once`. `false` while it waits, so it is not polled again before the next tick.
+    fn announce(&self, data_ready: &AtomicBool) -> bool {
+        let queued = queued_input(self.serial.as_ref(), &self.line_status, self.native);
+        match queued {
+            Some(0) | None => {
+                data_ready.store(false, Ordering::Relaxed);
+                true
+            }
+            Some(bytes) => {
+                if !data_ready.swap(true, Ordering::Relaxed) {
+                    emit_data_ready(&self.window, &self.path, bytes);
+                }
+                false
+            }
+        }
+    }
+}
+
+impl<R: Runtime> reactor::Task for ReadTask<R> {
+    fn pollable(&mut self) -> Option<isize> {
+        #[cfg(windows)]
+        if let Some(waiter) = self.waiter.as_mut() {
+            match waiter.arm() {
+                Ok(event) => {
+                    // data that arrived before the wait does not complete it
+                    if self.queued() > 0 {
+                        if let Some(waiter) = &self.waiter {
+                            waiter.signal();
+                        }
+                    }
+                    return Some(event);
+                }
+                Err(error) => {
+                    let _span = self.span.clone().entered();
+                    warn!("Cannot wait for data, reading on every tick: {}", error);
+                    self.waiter = None;
+                }
+            }
+        }
+        self.fd.map(|fd| fd as isize)
+    }
+
+    fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn stopped(&mut self) -> bool {
+        let _span = self.span.clone().entered();
+        if self.disconnected {
+            return true;
+        }
+        match self.rx.try_recv() {
+            Ok(_) => {
+                info!("Stopped reading data");
+                true
+            }
+            Err(TryRecvError::Disconnected) => {
+                warn!("Port is disconnected");
+                true
+            }
+            Err(TryRecvError::Empty) => false,
+        }
+    }
+
+    fn read(&mut self) -> bool {
+        let _span = self.span.clone().entered();
+        if let Some(data_ready) = self.data_ready.clone() {
+            return self.announce(&data_ready);
+        }
+        // without a descriptor to poll only what the driver holds is read
+        if self.shared && self.fd.is_none() && self.queued() == 0 {
+            return true;
+        }
+        let opened = self.opened;
+        let ReadTask {
+            window,
+            path,
+            serial,
+            read_event,
+            error_event,
+            serial_buf,
+            batch,
+            throttle,
+            error_count,
+            decoder,
+            midi,
+            firmata,
+            ubx,
+            xbee,
+            pipeline,
+            codec,
+            schema,
+            at,
+            ring_buffer,
+            history,
+            stream,
+            monitor,
+            log_level,
+            last_activity,
+            poller,
+            watchers,
+            capture,
+            address_filter,
+            expectation,
+            sequence,
+            backpressure,
+            line_status,
+            disconnected,
+            middleware,
+            ..
+        } = self;
+        let emit = read_emitter(window, read_event, sequence, backpressure);
+        match classify(serial.read(serial_buf.as_mut_slice())) {
+            ReadOutcome::Data(size) => {
+                let stamp = Stamp::now(opened);
+                trace!(size, "Read data");
+                last_activity.store(now_millis(), Ordering::Relaxed);
+                for segment in linestatus::split(line_status, &serial_buf[..size]) {
+                    let segment = match segment {
+                        Marked::Data(data) => data,
+                        Marked::Break { count } => {
+                            emit_break(window, path, count);
+                            continue;
+                        }
+                    };
+                    let segment = match middleware::incoming(middleware, &segment) {
+                        Some(segment) => segment,
+                        None => continue,
+                    };
+                    let data: &[u8] = &segment;
+                    log_traffic(log_level.load(Ordering::Relaxed), path, "RX", data);
+                    if let Ok(mut history) = history.lock() {
+                        history.push(data, stamp.timestamp);
+                    }
+                    if let Ok(mut stream) = stream.lock() {
+                        stream.push(data);
+                    }
+                    if monitor.load(Ordering::Relaxed) {
+                        emit_monitor_data(window, path, "RX", data);
+                    }
+                    let answer = match poller.lock() {
+                        Ok(mut poller) => poller.feed(data),
+                        Err(_) => None,
+                    };
+                    if let Some(answer) = answer {
+                        emit_poll_data(window, path, answer);
+                    }
+                    let matches: Vec<MatchData> = match watchers.lock() {
+                        Ok(mut watchers) => watchers
+                            .iter_mut()
+                            .flat_map(|watcher| watcher.feed(data))
+                            .collect(),
+                        Err(_) => Vec::new(),
+                    };
+                    for found in matches {
+                        emit_match_data(window, path, found);
+                    }
+                    if let Ok(mut expectation) = expectation.lock() {
+                        if let Some(expectation) = expectation.as_mut() {
+                            expectation.feed(data);
+                        }
+                    }
+                    let (urcs, sms_enabled, stack, module) = match at.engine.lock() {
+                        Ok(mut engine) if engine.enabled => (
+                            engine.feed(data),
+                            engine.sms,
+                            engine.cellular,
+                            engine.lora,
+                        ),
+                        _ => (Vec::new(), false, None, None),
+                    };
+                    for urc in urcs {
+                        emit_urc(window, path, &urc);
+                        if let Some(module) = module {
+                            if let Some(downlink) = lora::parse_urc(module, &urc.line) {
+                                emit_lora(window, path, downlink);
+                            }
+                        }
+                        if let Some(stack) = stack {
+                            handle_socket_urc(window, path, at, serial.as_ref(), stack, &urc);
+                        }
+                        if sms_enabled {
+                            handle_sms_urc(window, path, at, serial.as_ref(), urc);
+                        }
+                    }
+                    let (started, finished) = match capture.lock() {
+                        Ok(mut capture) => {
+                            let (started, reason) = match capture.as_mut() {
+                                Some(active) => active.feed(data),
+                                None => (false, None),
+                            };
+                            let finished = reason.and_then(|reason| {
+                                capture.take().map(|done| (done, reason))
+                            });
+                            (started, finished)
+                        }
+                        Err(_) => (false, None),
+                    };
+                    if started {
+                        info!("Capture triggered");
+                        emit_capture_data(
+                            window,
+                            path,
+                            CaptureData {
+                                reason: "Started".to_string(),
+                                data: Vec::new(),
+                                size: 0,
+                                file: None,
+                                timestamp: now_millis(),
+                            },
+                        );
+                    }
+                    if let Some((done, reason)) = finished {
+                        finish_capture(window, path, done, reason);
+                    }
+                    // drops frames of other stations, the codec frames when a codec splits them
+                    let accepts = |frame: &[u8]| match address_filter.lock() {
+                        Ok(mut filter) => filter
+                            .as_mut()
+                            .map_or(true, |filter| filter.accepts(frame)),
+                        Err(_) => true,
+                    };
+                    let mut deliver = |data: &[u8]| {
+                        if codec.is_none() && !accepts(data) {
+                            return;
+                        }
+                        if let Some(midi) = midi.as_mut() {
+                            for message in midi.feed(data) {
+                                emit_midi(window, path, message);
+                            }
+                            return;
+                        }
+                        if let Some(firmata) = firmata.as_mut() {
+                            for message in firmata.feed(data) {
+                                emit_firmata(window, path, message);
+                            }
+                            return;
+                        }
+                        if let Some(ubx) = ubx.as_mut() {
+                            for frame in ubx.feed(data) {
+                                emit_ubx(window, path, frame.message());
+                            }
+                            return;
+                        }
+                        if let Some(xbee) = xbee.as_mut() {
+                            for frame in xbee.feed(data) {
+                                emit_xbee(window, path, frame);
+                            }
+                            return;
+                        }
+                        if let Some(codec) = &codec {
+                            let frames = match codec.lock() {
+                                Ok(mut codec) => codec.decode(data),
+                                Err(error) => {
+                                    error!("Cannot get codec lock: {}", error);
+                                    Vec::new()
+                                }
+                            };
+                            for frame in frames.into_iter().filter(|frame| accepts(frame)) {
+                                match &decoder {
+                                    Some(decoder) => emit_decoded(
+                                        window,
+                                        path,
+                                        decode_frame(decoder.format, &frame),
+                                    ),
+                                    None => emit_frame_data(window, path, frame),
+                                }
+                            }
+                            return;
+                        }
+                        let packets = match schema.lock() {
+                            Ok(mut schema) => schema
+                                .as_mut()
+                                .map(|schema| schema.feed(data)),
+                            Err(_) => None,
+                        };
+                        if let Some(packets) = packets {
+                            for packet in packets {
+                                emit_packet(window, path, packet);
+                            }
+                        } else if let Some(decoder) = decoder.as_mut() {
+                            for value in decoder.feed(data) {
+                                emit_decoded(window, path, value);
+                            }
+                        } else if let Some(ring_buffer) = &ring_buffer {
+                            match ring_buffer.lock() {
+                                Ok(mut ring_buffer) => ring_buffer.push(data),
+                                Err(error) => {
+                                    error!("Cannot get buffer lock: {}", error);
+                                }
+                            }
+                        } else if batch.is_enabled() {
+                            batch.push(data, stamp);
+                        } else {
+                            throttle.offer(data, stamp, emit);
+                        }
+                    };
+                    let received = match address_filter.lock() {
+                        Ok(mut filter) => match filter.as_mut() {
+                            Some(filter) => Cow::Owned(filter.feed(data)),
+                            None => Cow::Borrowed(data),
+                        },
+                        Err(_) => Cow::Borrowed(data),
+                    };
+                    if !pipeline.is_empty() {
+                        for chunk in pipeline.process(&received) {
+                            deliver(&chunk);
+                        }
+                    } else if !received.is_empty() {
+                        deliver(&received);
+                    }
+                }
+            }
+            ReadOutcome::Idle => {}
+            ReadOutcome::Disconnected(reason) => {
+                emit_disconnect(window, path, reason);
+                *disconnected = true;
+                return false;
+            }
+            ReadOutcome::Failed(error) => {
+                *error_count += 1;
+                warn!("Read failed: {}", error);
+                if let Err(error) = window.emit(
+                    error_event,
+                    ReadError {
+                        kind: format!("{:?}", error.kind()),
+                        message: error.to_string(),
+                        count: *error_count,
+                    },
+                ) {
+                    error!("Failed to emit event: {}", error);
+                }
+                return false;
+            }
+        }
+        true
+    }
+
+    fn tick(&mut self) {
+        let _span = self.span.clone().entered();
+        // the buffer may have been emptied without `read_once`, e.g. by `clear_buffer`
+        if let Some(data_ready) = self.data_ready.clone() {
+            if data_ready.load(Ordering::Relaxed) && self.queued() == 0 {
+                data_ready.store(false, Ordering::Relaxed);
+            }
+        }
+        let polled = linestatus::poll(&self.line_status, self.native);
+        if let Some(count) = polled.break_count {
+            emit_break(&self.window, &self.path, count);
+        }
+        if let Some(errors) = polled.overruns {
+            emit_overrun(&self.window, &self.path, errors);
+        }
+        let emit = read_emitter(
+            &self.window,
+            &self.read_event,
+            &self.sequence,
+            &self.backpressure,
+        );
+        if self.batch.is_ready() {
+            let (data, stamp) = self.batch.take();
+            self.throttle.offer(&data, stamp, emit);
+        }
+        self.throttle.poll(emit);
+    }
+
+    fn finish(&mut self) {
+        let _span = self.span.clone().entered();
+        if let Some(segment) = self.pipeline.flush() {
+            match &self.ring_buffer {
+                Some(ring_buffer) => {
+                    if let Ok(mut ring_buffer) = ring_buffer.lock() {
+                        ring_buffer.push(&segment);
+                    }
+                }
+                None => self.batch.push(&segment, Stamp::now(self.opened)),
+            }
+        }
+        let emit = read_emitter(
+            &self.window,
+            &self.read_event,
+            &self.sequence,
+            &self.backpressure,
+        );
+        if !self.batch.is_empty() {
+            let (data, stamp) = self.batch.take();
+            self.throttle.offer(&data, stamp, emit);
+        }
+        self.throttle.flush(emit);
+    }
+}
+
+impl<R: Runtime> Drop for ReadTask<R> {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(fd) = self.fd {
+            unsafe {
+                libc::close(fd);
+            }
+        }
+    }
+}
+
+/// `read` Read data from serial port, returns the id its events are named with
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub fn read<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    timeout: Option<u64>,
+    size: Option<usize>,
+    priority: Option<String>,
+    max_batch_bytes: Option<usize>,
+    max_batch_latency_ms: Option<u64>,
+    max_event_rate: Option<u32>,
+    throttle_policy: Option<String>,
+    mode: Option<String>,
+    buffer_size: Option<usize>,
+    transforms: Option<Vec<String>>,
+    byte_map: Option<Vec<[u8; 2]>>,
+    codec: Option<String>,
+    decode: Option<String>,
+    when_hidden: Option<String>,
+) -> Result<String, Error> {
+    let factory = get_codec_factory(&state, &path, codec)?;
+    let format = decode
+        .as_deref()
+        .map(Format::parse)
+        .transpose()
+        .map_err(|message| Error::InvalidSettings {
+            path: path.clone(),
+            message,
+        })?;
+    let when_hidden =
+        WhenHidden::parse(when_hidden.as_deref()).map_err(|message| Error::InvalidSettings {
+            path: path.clone(),
+            message,
+        })?;
+    let event_id = event_id_of(&state, &path);
+    get_serialport(state.clone(), path.clone(), |serialport_info| {
+        if serialport_info.access() == Access::WriteOnly {
+            return Err(Error::String(format!("Port {} was opened write-only", path)));
+        }
+        if serialport_info.mux.is_some() {
+            return Err(Error::String(format!(
+                "Port {} is split into channels, read them instead",
+                path
+            )));
+        }
+        if serialport_info.gateway.is_some() {
+            return Err(Error::String(format!(
+                "Port {} is read by the Modbus gateway, stop it first",
+                path
+            )));
+        }
+        if serialport_info.mdb.is_some() {
+            return Err(Error::String(format!(
+                "Port {} is read by the MDB poller, stop it first",
+                path
+            )));
+        }
+        if serialport_info.sender.is_some() {
+            debug!(path = %path, "Port is already reading");
+            Ok(())
+        } else {
+            // refused before anything of the port is changed
+            let polled = serialport_info
+                .poller
+                .lock()
+                .map_or(false, |poller| !poller.is_empty());
+            if mode.as_deref() == Some("Notify") && polled {
+                return Err(Error::String(format!(
+                    "Port {} has polls, whose answers Notify mode does not receive",
+                    path
+                )));
+            }
+            let pipeline =
+                Pipeline::new(transforms.unwrap_or_default(), byte_map.unwrap_or_default())
+                    .map_err(|message| Error::InvalidSettings {
+                        path: path.clone(),
+                        message,
+                    })?;
+            info!(path = %path, "Start reading data");
+            if when_hidden != WhenHidden::Emit && !serialport_info.watches_visibility {
+                watch_visibility(&window, &path, serialport_info);
+            }
+            if let Ok(mut backpressure) = serialport_info.backpressure.lock() {
+                backpressure.set_when_hidden(when_hidden);
+            }
+            drain_read_data(
+                &window,
+                &path,
+                &serialport_info.sequence,
+                &serialport_info.backpressure,
+            )?;
+            match serialport_info.serialport.try_clone() {
+                Ok(serial) => {
+                    // the port is only marked as reading once the task runs
+                    let echo = pipeline.echo();
+                    let codec = factory.map(|factory| Arc::new(Mutex::new(factory())));
+                    let schema = serialport_info.schema.clone();
+                    let at = serialport_info.at.clone();
+                    let read_event = event_name(&window, "read", &path);
+                    let (tx, rx): (Sender<usize>, Receiver<usize>) = mpsc::channel();
+                    let ring_buffer = if mode.as_deref() == Some("Buffer") {
+                        serialport_info.ring_buffer.clone().or_else(|| {
+                            Some(Arc::new(Mutex::new(RingBuffer::new(
+                                buffer_size.unwrap_or(64 * 1024),
+                            ))))
+                        })
+                    } else {
+                        None
+                    };
+                    let data_ready = (mode.as_deref() == Some("Notify"))
+                        .then(|| Arc::new(AtomicBool::new(false)));
+                    let native = serialport_info.native;
+                    let mut task = ReadTask {
+                        span: tracing::info_span!("serialport", path = %path),
+                        window: window.clone(),
+                        path: path.clone(),
+                        serial,
+                        rx,
+                        read_event,
+                        error_event: event_name(&window, "error", &path),
+                        // never empty, a read of nothing would look like the end of the stream
+                        serial_buf: vec![0; size.unwrap_or(1024).max(1)],
+                        batch: ReadBatch::new(max_batch_bytes, max_batch_latency_ms),
+                        throttle: ReadThrottle::new(
+                            max_event_rate,
+                            get_throttle_policy(throttle_policy),
+                        ),
+                        error_count: 0,
+                        decoder: format.map(Decoder::new),
+                        midi: (mode.as_deref() == Some("Midi")).then(MidiParser::default),
+                        firmata: (mode.as_deref() == Some("Firmata")).then(FirmataParser::default),
+                        ubx: (mode.as_deref() == Some("Ubx")).then(UbxParser::default),
+                        xbee: match mode.as_deref() {
+                            Some("XBee") => Some(XBeeParser::new(false)),
+                            Some("XBeeEscaped") => Some(XBeeParser::new(true)),
+                            _ => None,
+                        },
+                        pipeline,
+                        codec: codec.clone(),
+                        schema,
+                        at,
+                        ring_buffer: ring_buffer.clone(),
+                        history: serialport_info.history.clone(),
+                        stream: serialport_info.stream.clone(),
+                        monitor: serialport_info.monitor.clone(),
+                        log_level: serialport_info.log_level.clone(),
+                        last_activity: serialport_info.last_activity.clone(),
+                        poller: serialport_info.poller.clone(),
+                        watchers: serialport_info.watchers.clone(),
+                        capture: serialport_info.capture.clone(),
+                        address_filter: serialport_info.address_filter.clone(),
+                        expectation: serialport_info.expectation.clone(),
+                        opened: serialport_info.opened,
+                        sequence: serialport_info.sequence.clone(),
+                        backpressure: serialport_info.backpressure.clone(),
+                        line_status: serialport_info.line_status.clone(),
+                        native,
+                        interval: Duration::from_millis(timeout.unwrap_or(200)),
+                        fd: None,
+                        #[cfg(windows)]
+                        waiter: None,
+                        shared: false,
+                        disconnected: false,
+                        data_ready: data_ready.clone(),
+                        middleware: serialport_info.middleware.clone(),
+                    };
+                    #[cfg(unix)]
+                    {
+                        task.fd = native
+                            .map(|fd| unsafe { libc::dup(fd as i32) })
+                            .filter(|fd| *fd >= 0);
+                    }
+                    #[cfg(windows)]
+                    {
+                        task.waiter = native
+                            .and_then(|handle| crate::overlapped::Waiter::new(handle).ok());
+                    }
+                    // a thread priority and ports without a native descriptor need a thread
+                    // of their own, the others share the reactor
+                    if get_thread_priority(priority.clone()).is_some() || native.is_none() {
+                        thread::spawn(move || {
+                            set_read_thread_priority(priority);
+                            reactor::run_alone(Box::new(task));
+                        });
+                    } else {
+                        task.shared = true;
+                        state.reactor.spawn(Box::new(task)).map_err(Error::String)?;
+                    }
+                    serialport_info.echo = echo;
+                    serialport_info.codec = codec;
+                    serialport_info.ring_buffer = ring_buffer;
+                    serialport_info.data_ready = data_ready;
+                    serialport_info.sender = Some(tx);
+                }
+                Err(error) => {
+                    return Err(Error::String(format!("Failed to read port {}: {}", path, error)));
+                }
+            }
+            Ok(())
+        }
+    })?;
+    Ok(event_id)
+}
+
+/// Added to the time the written bytes need on the wire, for the default drain timeout.
+const DRAIN_MARGIN: Duration = Duration::from_secs(1);
+
+/// `Drain` What waiting for written bytes to leave the port needs, taken under the port lock
+/// and used after it was released.
+struct Drain {
+    port: Box<dyn SerialPort>,
+    native: Option<isize>,
+    #[cfg(windows)]
+    line_status: Arc<Mutex<LineStatus>>,
+    timeout: Duration,
+}
+
+impl Drain {
+    /// `new` At most `timeout_ms`, or the time `size` bytes need at the baud rate plus
+    /// `DRAIN_MARGIN`.
+    fn new(
+        serialport_info: &SerialportInfo,
+        path: &str,
+        size: usize,
+        timeout_ms: Option<u64>,
+    ) -> Result<Self, Error> {
+        let timeout = match timeout_ms {
+            Some(timeout_ms) => Duration::from_millis(timeout_ms),
+            None => {
+                let baud_rate = serialport_info
+                    .serialport
+                    .baud_rate()
+                    .unwrap_or(9600)
+                    .max(1);
+                Duration::from_micros(size as u64 * 11_000_000 / u64::from(baud_rate))
+                    + DRAIN_MARGIN
+            }
+        };
+        let port = serialport_info
+            .serialport
+            .try_clone()
+            .map_err(|error| Error::String(format!("Failed to clone port {}: {}", path, error)))?;
+        Ok(Drain {
+            port,
+            native: serialport_info.native,
+            #[cfg(windows)]
+            line_status: serialport_info.line_status.clone(),
+            timeout,
+        })
+    }
+
+    /// `wait` Block until the bytes left the port or the timeout passed.
+    fn wait(mut self, path: &str) -> Result<(), Error> {
+        #[cfg(windows)]
+        let (line_status, native) = (self.line_status.clone(), self.native);
+        let mut pending = |port: &dyn SerialPort| {
+            // asked with the line errors on Windows, so they are still counted
+            #[cfg(windows)]
+            if let Some((_, output)) = linestatus::queued(&line_status, native) {
+                return Ok(output);
+            }
+            port.bytes_to_write().map_err(io::Error::from)
+        };
+        crate::rs485::drain(self.port.as_mut(), self.native, &mut pending, self.timeout)
+            .map_err(|error| Error::String(format!("Failed to drain port {}: {}", path, error)))?;
+        debug!(path = %path, "Output drained");
+        Ok(())
+    }
+}
+
+/// `blocking` Run blocking serial I/O on the blocking thread pool instead of the async runtime
+async fn blocking<T: Send + 'static>(
+    task: impl FnOnce() -> Result<T, Error> + Send + 'static,
+) -> Result<T, Error> {
+    tauri::async_runtime::spawn_blocking(task)
+        .await
+        .map_err(|error| Error::String(format!("Blocking task failed: {}", error)))?
+}
+
+/// `write_data` Write to the port under its lock; with `drain` also returns what waiting for
+/// the bytes to leave needs, which must happen once the lock is released
+fn write_data<R: Runtime>(
+    window: &Window<R>,
+    state: State<'_, SerialportState>,
+    path: &str,
+    value: &[u8],
+    drain: bool,
+    drain_timeout_ms: Option<u64>,
+) -> Result<(usize, Option<Drain>), Error> {
+    get_serialport(
+        state,
+        path.to_string(),
+        |serialport_info| match serialport_info.serialport.write(value) {
+            Ok(size) => {
+                serialport_info
+                    .last_activity
+                    .store(now_millis(), Ordering::Relaxed);
+                record_echo(&serialport_info.echo, &value[..size]);
+                if serialport_info.monitor.load(Ordering::Relaxed) {
+                    emit_monitor_data(window, path, "TX", &value[..size]);
+                }
+                if serialport_info.local_echo {
+                    emit_local_echo(window, path, serialport_info, &value[..size]);
+                }
+                debug!(path = %path, size, "Wrote data");
+                log_traffic(
+                    serialport_info.log_level.load(Ordering::Relaxed),
+                    path,
+                    "TX",
+                    &value[..size],
+                );
+                let drain = match drain {
+                    true => Some(Drain::new(serialport_info, path, size, drain_timeout_ms)?),
+                    false => None,
+                };
+                Ok((size, drain))
+            }
+            Err(error) => {
+                error!(path = %path, "Failed to write data: {}", error);
+                Err(Error::String(format!(
+                    "Failed to write data to port {}: {}",
+                    path, error
+                )))
+            }
+        },
+    )
+}
+
+/// `write_value` Write bytes of the other commands, without draining
+fn write_value<R: Runtime>(
+    window: &Window<R>,
+    state: State<'_, SerialportState>,
+    path: &str,
+    value: &[u8],
+) -> Result<usize, Error> {
+    write_data(window, state, path, value, false, None).map(|(size, _)| size)
+}
+
+/// `write` Write data to serial port, with `drain` resolving once it was sent
+#[command]
+pub async fn write<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    value: String,
+    drain: Option<bool>,
+    drain_timeout_ms: Option<u64>,
+) -> Result<usize, Error> {
+    let (size, drain) = write_data(
+        &window,
+        state,
+        &path,
+        value.as_bytes(),
+        drain.unwrap_or(false),
+        drain_timeout_ms,
+    )?;
+    if let Some(drain) = drain {
+        blocking(move || drain.wait(&path)).await?;
+    }
+    Ok(size)
+}
+
+/// `write_reliable` Write a frame until `ack` (bytes) or `ack_regex` is received within
+/// `timeout_ms`, at most `retries` times again; `nak` retries at once. The outcome is returned
+/// and emitted as ack event
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn write_reliable<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: String,
+    value: Vec<u8>,
+    ack: Option<Vec<u8>>,
+    ack_regex: Option<String>,
+    nak: Option<Vec<u8>>,
+    timeout_ms: Option<u64>,
+    retries: Option<u32>,
+) -> Result<AckData, Error> {
+    let expectation = Expectation::new(
+        get_matcher(ack, ack_regex)?,
+        nak.filter(|nak| !nak.is_empty()).map(Matcher::Bytes),
+    );
+    let operation = state.operations.start(&id).map_err(Error::String)?;
+    let (mut port, shared, reading) = get_serialport(state, path.clone(), |serialport_info| {
+        let port = serialport_info
+            .serialport
+            .try_clone()
+            .map_err(|error| Error::String(format!("Failed to clone port {}: {}", path, error)))?;
+        let reading = is_reading(serialport_info);
+        let port: Box<dyn SerialPort> = Box::new(
+            ExchangePort::new(
+                port,
+                (!reading).then(|| serialport_info.short_timeout.clone()),
+            )
+            .map_err(|error| Error::String(format!("Failed to set timeout: {}", error)))?,
+        );
+        Ok((port, serialport_info.expectation.clone(), reading))
+    })?;
+    let size = value.len();
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(1000));
+    let retries = retries.unwrap_or(3);
+    let (outcome, attempts) = blocking(move || {
+        let (outcome, attempts) = ack::write(
+            &operation,
+            port.as_mut(),
+            &shared,
+            expectation,
+            reading,
+            &value,
+            timeout,
+            retries,
+        )
+        .map_err(Error::String)?;
+        if outcome == ack::Outcome::Cancelled {
+            let _ = port.clear(ClearBuffer::Output);
+        }
+        Ok((outcome, attempts))
+    })
+    .await?;
+    if outcome == ack::Outcome::Cancelled {
+        emit_cancelled(&window, &path, &id, attempts as usize * size);
+    }
+    let result = AckData {
+        id,
+        acknowledged: outcome == ack::Outcome::Ack,
+        outcome: format!("{:?}", outcome),
+        attempts,
+        timestamp: now_millis(),
+    };
+    if result.acknowledged {
+        debug!(path = %path, attempts, "Reliable write acknowledged");
+    } else {
+        warn!(path = %path, attempts, "Reliable write failed: {:?}", outcome);
+    }
+    if let Err(error) = window.emit(&event_name(&window, "ack", &path), result.clone()) {
+        error!("Failed to emit event: {}", error);
+    }
+    Ok(result)
+}
+
+/// `run_queue_writer` Write the queued entries by priority until the queue is empty
+fn run_queue_writer<R: Runtime>(
+    window: Window<R>,
+    path: String,
+    mut serial: Box<dyn SerialPort>,
+    queue: Arc<Mutex<TxQueue>>,
+    running: Receiver<()>,
+    last_activity: Arc<AtomicU64>,
+) {
+    let _span = tracing::info_span!("serialport", path = %path).entered();
+    let event = event_name(&window, "queue", &path);
+    let mut running = Some(running);
+    loop {
+        let (cancelled, chunk) = match queue.lock() {
+            Ok(mut queue) => {
+                let cancelled = queue.take_cancelled();
+                let chunk = queue.next();
+                if chunk.is_none() {
+                    // dropped under the lock, so `enqueue` sees a stopped writer
+                    running.take();
+                }
+                (cancelled, chunk)
+            }
+            Err(error) => {
+                error!("Cannot get queue lock: {}", error);
+                break;
+            }
+        };
+        for (id, done) in cancelled {
+            if done > 0 {
+                let _ = serial.clear(ClearBuffer::Output);
+            }
+            emit_cancelled(&window, &path, &id, done);
+        }
+        let chunk = match chunk {
+            Some(chunk) => chunk,
+            None => break,
+        };
+        let error = match serial.write_all(&chunk.data) {
+            Ok(_) => {
+                last_activity.store(now_millis(), Ordering::Relaxed);
+                None
+            }
+            Err(error) => {
+                warn!(id = %chunk.id, "Queued write failed: {}", error);
+                if let Ok(mut queue) = queue.lock() {
+                    queue.remove(&chunk.id);
+                }
+                Some(error.to_string())
+            }
+        };
+        if let (Some(progress), None) = (chunk.progress, &error) {
+            emit_progress(&window, &path, progress);
+        }
+        if chunk.done || error.is_some() {
+            let data = QueueData {
+                id: chunk.id,
+                priority: format!("{:?}", chunk.priority),
+                size: chunk.size,
+                error,
+                timestamp: now_millis(),
+            };
+            if let Err(error) = window.emit(&event, data) {
+                error!("Failed to emit event: {}", error);
+            }
+        }
+    }
+}
+
+/// `emit_progress` Send the progress of a long-running operation to the frontend
+fn emit_progress<R: Runtime>(window: &Window<R>, path: &str, progress: ProgressData) {
+    if let Err(error) = window.emit(&event_name(window, "progress", path), progress) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_cancelled` Tell the frontend an operation stopped by `cancel_operation`, after `done` bytes
+fn emit_cancelled<R: Runtime>(window: &Window<R>, path: &str, id: &str, done: usize) {
+    info!(path = %path, id = %id, done, "Operation cancelled");
+    if let Err(error) = window.emit(
+        &event_name(window, "cancelled", path),
+        CancelledData {
+            id: id.to_string(),
+            done,
+            timestamp: now_millis(),
+        },
+    ) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `cancel_operation` Stop a running operation started with `id`: a queued write, a reliable
+/// write or a transfer. It ends with a cancelled event
+#[command]
+pub fn cancel_operation<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    id: String,
+) -> Result<(), Error> {
+    if state.operations.cancel(&id) {
+        Ok(())
+    } else {
+        Err(Error::String(format!("Operation {} is not running", id)))
+    }
+}
+
+/// `enqueue` Queue a write with priority `Urgent`, `Normal` (default) or `Bulk`; a queue event
+/// is emitted once it is written. Bulk data is written in chunks between the other entries
+#[command]
+pub fn enqueue<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: String,
+    value: Vec<u8>,
+    priority: Option<String>,
+) -> Result<QueueStatus, Error> {
+    let priority = Priority::parse(priority.as_deref()).map_err(|message| Error::InvalidSettings {
+        path: path.clone(),
+        message,
+    })?;
+    let operation = state.operations.start(&id).map_err(Error::String)?;
+    get_serialport(state, path.clone(), |serialport_info| {
+        let mut queue = serialport_info
+            .tx_queue
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get queue lock: {}", error)))?;
+        debug!(path = %path, id = %id, size = value.len(), "Queued {:?} write", priority);
+        queue.push(operation, priority, value);
+        let running = match &serialport_info.queue_writer {
+            Some(running) => running.send(()).is_ok(),
+            None => false,
+        };
+        if !running {
+            let serial = serialport_info.serialport.try_clone().map_err(|error| {
+                Error::String(format!("Failed to clone port {}: {}", path, error))
+            })?;
+            let (tx, rx) = mpsc::channel();
+            serialport_info.queue_writer = Some(tx);
+            let tx_queue = serialport_info.tx_queue.clone();
+            let last_activity = serialport_info.last_activity.clone();
+            thread::spawn(move || {
+                run_queue_writer(window, path, serial, tx_queue, rx, last_activity)
+            });
+        }
+        Ok(queue.status())
+    })
+}
+
+/// `queue_status` Entries and bytes waiting in the transmit queue
+#[command]
+pub fn queue_status<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<QueueStatus, Error> {
+    get_serialport(state, path, |serialport_info| {
+        match serialport_info.tx_queue.lock() {
+            Ok(queue) => Ok(queue.status()),
+            Err(error) => Err(Error::String(format!("Cannot get queue lock: {}", error))),
+        }
+    })
+}
+
+/// `write` Write binary data to serial port, with `drain` resolving once it was sent
+#[command]
+pub async fn write_binary<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    value: Vec<u8>,
+    drain: Option<bool>,
+    drain_timeout_ms: Option<u64>,
+) -> Result<usize, Error> {
+    let (size, drain) = write_data(
+        &window,
+        state,
+        &path,
+        &value,
+        drain.unwrap_or(false),
+        drain_timeout_ms,
+    )?;
+    if let Some(drain) = drain {
+        blocking(move || drain.wait(&path)).await?;
+    }
+    Ok(size)
+}
+
+/// `write_frame` Encode a frame with the codec of the port and write it
+#[command]
+pub fn write_frame<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    frame: Vec<u8>,
+    codec: Option<String>,
+) -> Result<usize, Error> {
+    let factory = get_codec_factory(&state, &path, codec.clone())?;
+    let value = get_serialport(state.clone(), path.clone(), |serialport_info| {
+        match (&serialport_info.codec, factory) {
+            // the codec of the running read, unless another one is asked for
+            (Some(active), _) if codec.is_none() => active
+                .lock()
+                .map(|mut active| active.encode(&frame))
+                .map_err(|error| Error::String(format!("Cannot get codec lock: {}", error))),
+            (_, Some(factory)) => Ok(factory().encode(&frame)),
+            _ => Err(Error::String(format!("No codec for port {}", path))),
+        }
+    })?;
+    write_value(&window, state, &path, &value)
+}
+
+/// `send_file` Stream a file to the port in chunks of `chunk_size` bytes (default 1024), or line by
+/// line with framing `Lines`; framing `Codec` encodes every chunk with the codec like
+/// `write_frame`. Progress events are emitted, `cancel_operation(id)` stops it
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_file<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: String,
+    file_path: String,
+    chunk_size: Option<usize>,
+    delay_ms: Option<u64>,
+    framing: Option<String>,
+    codec: Option<String>,
+) -> Result<u64, Error> {
+    let framing = get_framing(&state, &path, framing, codec)?;
+    let file = std::fs::File::open(&file_path)
+        .map_err(|error| Error::String(format!("Cannot open {}: {}", file_path, error)))?;
+    let mut operation = state.operations.start(&id).map_err(Error::String)?;
+    let mut port = get_serialport(state, path.clone(), |serialport_info| {
+        serialport_info
+            .serialport
+            .try_clone()
+            .map_err(|error| Error::String(format!("Failed to clone port {}: {}", path, error)))
+    })?;
+    info!(path = %path, id = %id, "Sending {}", file_path);
+    let chunk_size = chunk_size.unwrap_or(1024).max(1);
+    let delay = Duration::from_millis(delay_ms.unwrap_or(0));
+    blocking(move || {
+        let sent = transfer::send(
+            port.as_mut(),
+            file,
+            &mut operation,
+            chunk_size,
+            delay,
+            framing,
+            &mut |progress| emit_progress(&window, &path, progress),
+        )
+        .map_err(|error| Error::String(format!("Failed to send {}: {}", file_path, error)))?;
+        if sent.cancelled {
+            let _ = port.clear(ClearBuffer::Output);
+            emit_cancelled(&window, &path, &id, sent.bytes as usize);
+            return Err(Error::String(format!(
+                "Sending {} was cancelled after {} bytes",
+                file_path, sent.bytes
+            )));
+        }
+        Ok(sent.bytes)
+    })
+    .await
+}
+
+/// `receive_to_file` Write the received stream to a file until `until_bytes` arrived, the
+/// `until_pattern` was received or the line was idle for `until_idle_ms`; returns the bytes
+/// written. The port must not be reading, `cancel_operation(id)` stops it
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn receive_to_file<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: String,
+    file_path: String,
+    until_bytes: Option<u64>,
+    until_pattern: Option<Vec<u8>>,
+    until_idle_ms: Option<u64>,
+) -> Result<u64, Error> {
+    let until = Until {
+        bytes: until_bytes,
+        pattern: until_pattern,
+        idle: until_idle_ms.map(Duration::from_millis),
+    };
+    if until.bytes.is_none() && until.pattern.is_none() && until.idle.is_none() {
+        return Err(Error::InvalidSettings {
+            path,
+            message: "No end given: until_bytes, until_pattern or until_idle_ms".to_string(),
+        });
+    }
+    let mut operation = state.operations.start(&id).map_err(Error::String)?;
+    let mut port = get_idle_port(state, &path)?;
+    let file = std::fs::File::create(&file_path)
+        .map_err(|error| Error::String(format!("Cannot create {}: {}", file_path, error)))?;
+    info!(path = %path, id = %id, "Receiving to {}", file_path);
+    blocking(move || {
+        let received = transfer::receive(
+            port.as_mut(),
+            file,
+            &mut operation,
+            &until,
+            &mut |progress| emit_progress(&window, &path, progress),
+        )
+        .map_err(|error| {
+            Error::String(format!("Failed to receive to {}: {}", file_path, error))
+        })?;
+        if received.cancelled {
+            emit_cancelled(&window, &path, &id, received.bytes as usize);
+        }
+        info!(path = %path, bytes = received.bytes, "Received to {}", file_path);
+        Ok(received.bytes)
+    })
+    .await
+}
+
+/// `start_pcap` Record all traffic of the port to a pcapng file for Wireshark, with link type
+/// `link_type` (`LINKTYPE_USER0` by default)
+#[command]
+pub fn start_pcap<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    file: String,
+    link_type: Option<u16>,
+) -> Result<(), Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let writer = PcapWriter::create(&file, &path, link_type.unwrap_or(pcapng::USER0))
+            .map_err(|error| Error::String(format!("Cannot create {}: {}", file, error)))?;
+        match serialport_info.pcap.lock() {
+            Ok(mut pcap) => *pcap = Some(writer),
+            Err(error) => return Err(Error::String(format!("Cannot get capture lock: {}", error))),
+        }
+        info!(path = %path, "Capturing to {}", file);
+        Ok(())
+    })
+}
+
+/// `stop_pcap` End the pcapng capture, returns the number of recorded packets
+#[command]
+pub fn stop_pcap<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<u64, Error> {
+    get_serialport(state, path.clone(), |serialport_info| {
+        let writer = match serialport_info.pcap.lock() {
+            Ok(mut pcap) => pcap.take(),
+            Err(error) => return Err(Error::String(format!("Cannot get capture lock: {}", error))),
+        };
+        match writer {
+            Some(writer) => {
+                info!(path = %path, packets = writer.packets, "Capture stopped");
+                Ok(writer.packets)
+            }
+            None => Err(Error::String(format!("Port {} is not capturing", path))),
+        }
+    })
+}
+
+/// `emit_midi` Send a message parsed in `Midi` read mode to the frontend
+fn emit_midi<R: Runtime>(window: &Window<R>, path: &str, message: MidiMessage) {
+    let data = MidiData {
+        message,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "midi", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_firmata` Send a message parsed in `Firmata` read mode to the frontend
+fn emit_firmata<R: Runtime>(window: &Window<R>, path: &str, message: FirmataMessage) {
+    let data = FirmataData {
+        message,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "firmata", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_ubx` Send a message parsed in `Ubx` read mode to the frontend
+fn emit_ubx<R: Runtime>(window: &Window<R>, path: &str, message: UbxMessage) {
+    let data = UbxData {
+        message,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "ubx", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_xbee` Send an API frame parsed in `XBee` or `XBeeEscaped` read mode to the frontend
+fn emit_xbee<R: Runtime>(window: &Window<R>, path: &str, frame: XBeeFrame) {
+    let data = XBeeData {
+        frame,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "xbee", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_break` Report a break received on the line
+fn emit_break<R: Runtime>(window: &Window<R>, path: &str, count: u64) {
+    debug!(path = %path, count, "Break received");
+    let data = BreakData {
+        count,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "break", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_data_ready` Report in `Notify` mode that data is waiting to be read
+fn emit_data_ready<R: Runtime>(window: &Window<R>, path: &str, bytes: u32) {
+    trace!(path = %path, bytes, "Data ready");
+    let data = DataReadyData {
+        bytes,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "ready", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_disconnect` Report that the device went away while reading, which ends the read
+fn emit_disconnect<R: Runtime>(window: &Window<R>, path: &str, reason: String) {
+    warn!(path = %path, "Port disconnected: {}", reason);
+    let data = DisconnectData {
+        reason,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "disconnect", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_overrun` Report that received data started getting lost to overruns
+fn emit_overrun<R: Runtime>(window: &Window<R>, path: &str, errors: LineErrors) {
+    warn!(
+        path = %path,
+        overrun = errors.overrun,
+        buffer_overrun = errors.buffer_overrun,
+        "Received data lost to overruns"
+    );
+    let data = OverrunData {
+        overrun: errors.overrun,
+        buffer_overrun: errors.buffer_overrun,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "overrun", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `send_midi` Encode MIDI messages and write them in one go
+#[command]
+pub fn send_midi<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    messages: Vec<MidiMessage>,
+) -> Result<usize, Error> {
+    let mut value = Vec::new();
+    for message in &messages {
+        value.extend(message.encode().map_err(Error::String)?);
+    }
+    write_value(&window, state, &path, &value)
+}
+
+/// `send_firmata` Encode Firmata messages and write them in one go
+#[command]
+pub fn send_firmata<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    messages: Vec<FirmataMessage>,
+) -> Result<usize, Error> {
+    let mut value = Vec::new();
+    for message in &messages {
+        value.extend(message.encode().map_err(Error::String)?);
+    }
+    write_value(&window, state, &path, &value)
+}
+
+/// `firmata_handshake` Query the protocol and firmware versions of a Firmata board, waiting
+/// `timeout_ms` (5000 by default) for the board to finish its reset after the port opened
+#[command]
+pub async fn firmata_handshake<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    timeout_ms: Option<u64>,
+) -> Result<FirmataVersion, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(5000));
+    let version =
+        blocking(move || firmata::handshake(port.as_mut(), timeout).map_err(Error::String)).await?;
+    info!(
+        path = %path,
+        firmware = %version.firmware,
+        protocol = %version.protocol,
+        "Firmata board"
+    );
+    Ok(version)
+}
+
+/// `send_ubx` Encode UBX frames and write them in one go
+#[command]
+pub fn send_ubx<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    frames: Vec<UbxFrame>,
+) -> Result<usize, Error> {
+    let mut value = Vec::new();
+    for frame in &frames {
+        value.extend(frame.encode().map_err(Error::String)?);
+    }
+    write_value(&window, state, &path, &value)
+}
+
+/// `ubx_configure` Send a CFG message to a u-blox receiver and wait `timeout_ms` (1000 by
+/// default) for its ACK-ACK; an ACK-NAK or no answer fails
+#[command]
+pub async fn ubx_configure<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    config: UbxConfig,
+    timeout_ms: Option<u64>,
+) -> Result<(), Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(1000));
+    debug!(path = %path, config = ?config, "UBX configuration");
+    blocking(move || ubx::configure(port.as_mut(), &config, timeout).map_err(Error::String)).await
+}
+
+/// `send_xbee` Encode XBee API frames and write them in one go, `escaped` for API mode 2
+#[command]
+pub fn send_xbee<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    frames: Vec<XBeeFrame>,
+    escaped: Option<bool>,
+) -> Result<usize, Error> {
+    let mut value = Vec::new();
+    for frame in &frames {
+        value.extend(frame.encode(escaped.unwrap_or(false)).map_err(Error::String)?);
+    }
+    write_value(&window, state, &path, &value)
+}
+
+/// `xbee_at_command` Send an AT command in API mode to the local XBee, or to the radio with
+/// the 64-bit `destination` as remote command, and wait `timeout_ms` (2000 by default) for its
+/// response
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn xbee_at_command<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    command: String,
+    parameter: Option<Vec<u8>>,
+    destination: Option<u64>,
+    escaped: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<XBeeFrame, Error> {
+    let frame_id = xbee::next_frame_id();
+    let parameter = parameter.unwrap_or_default();
+    let request = match destination {
+        Some(destination) => XBeeFrame::RemoteAtCommand {
+            frame_id,
+            destination,
+            destination_16: None,
+            // applied at once, like a local command
+            options: 0x02,
+            command,
+            parameter,
+        },
+        None => XBeeFrame::AtCommand {
+            frame_id,
+            command,
+            parameter,
+        },
+    };
+    let mut port = get_idle_port(state, &path)?;
+    // remote commands travel the network, allow for a few hops
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(2000));
+    let escaped = escaped.unwrap_or(false);
+    blocking(move || {
+        xbee::at_command(port.as_mut(), &request, escaped, timeout).map_err(Error::String)
+    })
+    .await
+}
+
+/// `get_idle_port` A clone of the port for exchanges that read their response themselves,
+/// with a short timeout for their polling reads
+fn get_idle_port(state: State<'_, SerialportState>, path: &str) -> Result<Box<dyn SerialPort>, Error> {
+    let (port, short_timeout) = get_serialport(state, path.to_string(), |serialport_info| {
+        if serialport_info.sender.is_some() {
+            return Err(Error::String(format!(
+                "Port {} is reading, cancel the read first",
+                path
+            )));
+        }
+        let port = serialport_info
+            .serialport
+            .try_clone()
+            .map_err(|error| Error::String(format!("Failed to clone port {}: {}", path, error)))?;
+        Ok((port, serialport_info.short_timeout.clone()))
+    })?;
+    let port = ExchangePort::new(port, Some(short_timeout))
+        .map_err(|error| Error::String(format!("Failed to set timeout: {}", error)))?;
+    Ok(Box::new(port))
+}
+
+/// `benchmark_latency` Measure the round trip of `pattern` to a device that echoes it,
+/// `iterations` times; iterations not echoed within `timeout_ms` count as lost
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn benchmark_latency<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    pattern: Option<Vec<u8>>,
+    iterations: Option<u32>,
+    timeout_ms: Option<u64>,
+) -> Result<Latency, Error> {
+    let pattern = pattern.unwrap_or_else(|| vec![0x55; 16]);
+    if pattern.is_empty() {
+        return Err(Error::InvalidSettings {
+            path,
+            message: "The pattern is empty".to_string(),
+        });
+    }
+    let mut port = get_idle_port(state, &path)?;
+    let iterations = iterations.unwrap_or(100);
+    info!(path = %path, iterations, size = pattern.len(), "Latency benchmark");
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(1000));
+    let latency = blocking(move || {
+        benchmark::latency(port.as_mut(), &pattern, iterations, timeout).map_err(Error::String)
+    })
+    .await?;
+    info!(
+        path = %path,
+        avg_us = latency.avg_us,
+        jitter_us = latency.jitter_us,
+        lost = latency.lost,
+        "Latency measured"
+    );
+    Ok(latency)
+}
+
+/// `frame_bits` The bits of one character on the line: start, data, parity and stop bits
+fn frame_bits(port: &dyn SerialPort, stick_parity: Option<bool>) -> u32 {
+    let data_bits = port.data_bits().map_or(8, |bits| u32::from(u8::from(bits)));
+    let parity = match port.parity() {
+        Ok(Parity::Odd) | Ok(Parity::Even) => 1,
+        _ => u32::from(stick_parity.is_some()),
+    };
+    let stop_bits = port.stop_bits().map_or(1, |bits| u32::from(u8::from(bits)));
+    1 + data_bits + parity + stop_bits
+}
+
+/// `benchmark_throughput` Saturate the link for `duration_ms` and report the rates achieved:
+/// `Loopback` to a device echoing what it receives (default), `Transmit` or `Receive`
+#[command]
+pub async fn benchmark_throughput<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    duration_ms: Option<u64>,
+    direction: Option<String>,
+) -> Result<Throughput, Error> {
+    let direction = benchmark::Direction::parse(direction.as_deref())
+        .map_err(|message| Error::InvalidSettings {
+            path: path.clone(),
+            message,
+        })?;
+    let (native, stick_parity, line_status) =
+        get_serialport(state.clone(), path.clone(), |serialport_info| {
+            Ok((
+                serialport_info.native,
+                serialport_info.stick_parity,
+                serialport_info.line_status.clone(),
+            ))
+        })?;
+    let mut port = get_idle_port(state, &path)?;
+    let errors = |line_status: &Mutex<LineStatus>| {
+        line_status
+            .lock()
+            .map(|mut status| status.errors(native))
+            .unwrap_or_default()
+    };
+    let before = errors(&line_status);
+    let frame_bits = frame_bits(port.as_ref(), stick_parity);
+    info!(path = %path, "Throughput benchmark");
+    let duration = Duration::from_millis(duration_ms.unwrap_or(5000));
+    let mut throughput = blocking(move || {
+        benchmark::throughput(port.as_mut(), direction, duration, frame_bits).map_err(Error::String)
+    })
+    .await?;
+    throughput.line_errors = errors(&line_status).since(&before);
+    info!(
+        path = %path,
+        sent_per_second = throughput.sent_per_second,
+        received_per_second = throughput.received_per_second,
+        expected_per_second = throughput.expected_per_second,
+        "Throughput measured"
+    );
+    Ok(throughput)
+}
+
+/// `lin_send` Send a LIN master frame: break, sync, protected identifier, data and checksum
+/// (`Enhanced` by default)
+#[command]
+pub async fn lin_send<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: u8,
+    data: Vec<u8>,
+    checksum: Option<ChecksumModel>,
+) -> Result<(), Error> {
+    let mut port = get_idle_port(state, &path)?;
+    debug!(path = %path, id, "LIN send");
+    let checksum = checksum.unwrap_or(ChecksumModel::Enhanced);
+    blocking(move || lin::send(port.as_mut(), id, &data, checksum).map_err(Error::String)).await
+}
+
+/// `lin_request` Send a LIN header and return the data a slave answers with; `length`
+/// defaults to the LIN 1.x length of the identifier
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn lin_request<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    id: u8,
+    length: Option<usize>,
+    checksum: Option<ChecksumModel>,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<u8>, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    debug!(path = %path, id, "LIN request");
+    let length = length.unwrap_or_else(|| lin::default_length(id));
+    let checksum = checksum.unwrap_or(ChecksumModel::Enhanced);
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(100));
+    blocking(move || {
+        lin::request(port.as_mut(), id, length, checksum, timeout).map_err(Error::String)
+    })
+    .await
+}
+
+/// `scpi_query` Write a SCPI query and return its response line; with `check_errors` the
+/// error queue is drained afterwards and its errors fail the query
+#[command]
+pub async fn scpi_query<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    query: String,
+    timeout_ms: Option<u64>,
+    check_errors: Option<bool>,
+) -> Result<String, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(2000));
+    debug!(path = %path, "SCPI query {}", query);
+    blocking(move || {
+        let response = scpi::query(port.as_mut(), &query, timeout).map_err(Error::String)?;
+        if check_errors.unwrap_or(false) {
+            let errors = scpi::errors(port.as_mut(), timeout).map_err(Error::String)?;
+            if !errors.is_empty() {
+                return Err(Error::String(errors.join("; ")));
+            }
+        }
+        Ok(response)
+    })
+    .await
+}
+
+/// `scpi_query_binary` Write a SCPI query answered with a `#` binary block and return the
+/// block's payload
+#[command]
+pub async fn scpi_query_binary<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    query: String,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<u8>, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    debug!(path = %path, "SCPI binary query {}", query);
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(10000));
+    blocking(move || scpi::query_binary(port.as_mut(), &query, timeout).map_err(Error::String))
+        .await
+}
+
+/// `scpi_command` Write a SCPI command; unless `check_errors` is false the error queue is
+/// drained afterwards and its errors fail the command
+#[command]
+pub async fn scpi_command<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    command: String,
+    timeout_ms: Option<u64>,
+    check_errors: Option<bool>,
+) -> Result<(), Error> {
+    let mut port = get_idle_port(state, &path)?;
+    debug!(path = %path, "SCPI command {}", command);
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(2000));
+    let check_errors = check_errors.unwrap_or(true);
+    blocking(move || {
+        scpi::command(port.as_mut(), &command, check_errors, timeout).map_err(Error::String)
+    })
+    .await
+}
+
+/// `scpi_errors` Drain the error queue of the instrument (`SYST:ERR?`)
+#[command]
+pub async fn scpi_errors<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<String>, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(2000));
+    blocking(move || scpi::errors(port.as_mut(), timeout).map_err(Error::String)).await
+}
+
+/// `scan_instruments` Probe every available port that is not opened with `*IDN?` at
+/// `baud_rates` (by default 9600, 115200, 19200, 38400 and 57600 baud), returns the identity
+/// of each port that answered
+#[command]
+pub async fn scan_instruments<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    baud_rates: Option<Vec<u32>>,
+    timeout_ms: Option<u64>,
+) -> Result<HashMap<String, String>, Error> {
+    let baud_rates = baud_rates.unwrap_or_else(|| vec![9600, 115200, 19200, 38400, 57600]);
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(500));
+    let opened: Vec<std::path::PathBuf> = match state.serialports.lock() {
+        Ok(serialports) => serialports
+            .keys()
+            .map(|opened| device_path(opened))
+            .collect(),
+        Err(error) => return Err(Error::String(format!("Cannot get lock: {}", error))),
+    };
+    blocking(move || {
+        let ports = serialport::available_ports()
+            .map_err(|error| Error::String(format!("Failed to list ports: {}", error)))?;
+        // ports are probed in parallel, each probe takes up to a timeout per baud rate
+        let probes: Vec<_> = ports
+            .into_iter()
+            .map(|port| port.port_name)
+            .filter(|name| !opened.contains(&device_path(name)))
+            .map(|name| {
+                let baud_rates = baud_rates.clone();
+                thread::spawn(move || {
+                    let identity = scpi::identify(&name, &baud_rates, timeout);
+                    (name, identity)
+                })
+            })
+            .collect();
+        let mut instruments = HashMap::new();
+        for probe in probes {
+            if let Ok((name, Some(identity))) = probe.join() {
+                info!(path = %name, "Found instrument {}", identity);
+                instruments.insert(name, identity);
+            }
+        }
+        Ok(instruments)
+    })
+    .await
+}
+
+/// `device_path` The device node of a port, so a `/dev/serial/by-id` or by-path link and the
+/// node it points to compare equal; names that are no file are kept as they are
+fn device_path(name: &str) -> std::path::PathBuf {
+    std::fs::canonicalize(name).unwrap_or_else(|_| std::path::PathBuf::from(name))
+}
+
+/// `read_meter` Read an IEC 62056-21 meter: sign on at 300 baud with the optional device
+/// `address`, switch to the rate it offers and parse its OBIS registers. The settings of the
+/// port are restored afterwards
+#[command]
+pub async fn read_meter<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    address: Option<String>,
+    timeout_ms: Option<u64>,
+) -> Result<MeterReading, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let settings = (
+        port.baud_rate(),
+        port.data_bits(),
+        port.parity(),
+        port.stop_bits(),
+    );
+    // 300 baud sign-on and a long readout at the offered rate
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(20000));
+    blocking(move || {
+        let reading = meter::read(port.as_mut(), &address.unwrap_or_default(), timeout);
+        if let (Ok(baud_rate), Ok(data_bits), Ok(parity), Ok(stop_bits)) = settings {
+            let restored = port
+                .set_baud_rate(baud_rate)
+                .and_then(|_| port.set_data_bits(data_bits))
+                .and_then(|_| port.set_parity(parity))
+                .and_then(|_| port.set_stop_bits(stop_bits));
+            if let Err(error) = restored {
+                warn!(path = %path, "Failed to restore settings after meter readout: {}", error);
+            }
+        }
+        reading.map_err(Error::String)
+    })
+    .await
+}
+
+/// `get_mdb_bus` The bus the poller of the port uses, or a new one on an idle port
+fn get_mdb_bus(state: State<'_, SerialportState>, path: &str) -> Result<Arc<Mutex<Bus>>, Error> {
+    let polled = get_serialport(state.clone(), path.to_string(), |serialport_info| {
+        Ok(serialport_info.mdb.as_ref().map(|mdb| mdb.bus.clone()))
+    })?;
+    if let Some(bus) = polled {
+        return Ok(bus);
+    }
+    let port = get_idle_port(state.clone(), path)?;
+    let native = get_serialport(state, path.to_string(), |serialport_info| {
+        Ok(serialport_info.native)
+    })?;
+    let bus = Bus::new(port, native).map_err(Error::String)?;
+    Ok(Arc::new(Mutex::new(bus)))
+}
+
+/// `mdb_command` Send an MDB command (peripheral address plus subcommand, e.g. `0x0b` POLL
+/// of the coin changer) with its data, returns the `Ack`, `Nak` or data answer. The port is
+/// switched to 9600 8E1
+#[command]
+pub async fn mdb_command<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    command: u8,
+    data: Option<Vec<u8>>,
+    timeout_ms: Option<u64>,
+) -> Result<Response, Error> {
+    let bus = get_mdb_bus(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(50));
+    blocking(move || {
+        let mut bus = bus
+            .lock()
+            .map_err(|error| Error::String(format!("Cannot get MDB lock: {}", error)))?;
+        bus.transact(command, &data.unwrap_or_default(), timeout)
+            .map_err(Error::String)
+    })
+    .await
+}
+
+/// `emit_mdb` Send an event of a polled MDB peripheral to the frontend
+fn emit_mdb<R: Runtime>(window: &Window<R>, path: &str, address: u8, event: MdbEvent) {
+    let data = MdbData {
+        address,
+        event,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "mdb", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `start_mdb_poll` POLL the MDB peripherals at `addresses` (by default the coin changer and
+/// the bill validator) every `interval_ms` and emit their events until `stop_mdb_poll`
+#[command]
+pub fn start_mdb_poll<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    addresses: Option<Vec<u8>>,
+    interval_ms: Option<u64>,
+    timeout_ms: Option<u64>,
+) -> Result<(), Error> {
+    let addresses = addresses.unwrap_or_else(|| vec![mdb::COIN_CHANGER, mdb::BILL_VALIDATOR]);
+    if let Some(address) = addresses.iter().find(|address| *address & 0x07 != 0) {
+        return Err(Error::String(format!(
+            "MDB address {:#04x} is not a multiple of 8",
+            address
+        )));
+    }
+    let bus = get_mdb_bus(state.clone(), &path)?;
+    get_serialport(state, path.clone(), |serialport_info| {
+        let (tx, rx): (Sender<()>, Receiver<()>) = mpsc::channel();
+        // replacing the sender stops a running poller
+        serialport_info.mdb = Some(Mdb {
+            bus: bus.clone(),
+            _stop: tx,
+        });
+        let interval = Duration::from_millis(interval_ms.unwrap_or(200));
+        let timeout = Duration::from_millis(timeout_ms.unwrap_or(50));
+        info!(path = %path, "MDB polling started");
+        thread::spawn(move || {
+            let _span = tracing::info_span!("serialport", path = %path).entered();
+            mdb::run(bus, addresses, interval, timeout, rx, |address, event| {
+                emit_mdb(&window, &path, address, event)
+            });
+        });
+        Ok(())
+    })
+}
+
+/// `stop_mdb_poll` Stop polling the MDB peripherals of the port
+#[command]
+pub fn stop_mdb_poll<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<(), Error> {
+    get_serialport(state, path, |serialport_info| {
+        serialport_info.mdb = None;
+        Ok(())
+    })
+}
+
+#[cfg(feature = "ftdi")]
+fn get_bit_mode(value: &str) -> Option<BitMode> {
+    match value {
+        "Reset" | "Uart" => Some(BitMode::Reset),
+        "AsyncBitbang" | "Bitbang" => Some(BitMode::AsyncBitbang),
+        "SyncBitbang" => Some(BitMode::SyncBitbang),
+        "CbusBitbang" => Some(BitMode::CbusBitbang),
+        "Mpsse" => Some(BitMode::Mpsse),
+        _ => None,
+    }
+}
+
+/// `get_ftdi` Run `f` with the FTDI interface that was put into a special mode for `path`
+#[cfg(feature = "ftdi")]
+fn get_ftdi<T, F: FnOnce(&FtdiDevice) -> Result<T, Error>>(
+    state: State<'_, SerialportState>,
+    path: String,
+    f: F,
+) -> Result<T, Error> {
+    match state.ftdi.lock() {
+        Ok(devices) => match devices.get(&path) {
+            Some(device) => f(device),
+            None => Err(Error::String(format!(
+                "Port {} is not in bitbang or MPSSE mode",
+                path
+            ))),
+        },
+        Err(error) => Err(Error::String(format!("Cannot get lock: {}", error))),
+    }
+}
+
+#[cfg(not(feature = "ftdi"))]
+fn ftdi_unavailable() -> Error {
+    Error::String("FTDI bitbang and MPSSE modes need the `ftdi` feature".to_string())
+}
+
+/// `ftdi_set_bitmode` Switch an FTDI adapter to `AsyncBitbang`, `SyncBitbang`, `CbusBitbang` or `Mpsse`, `Reset` returns it to UART mode
+#[command]
+pub fn ftdi_set_bitmode<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    mode: String,
+    mask: Option<u8>,
+    interface: Option<u8>,
+) -> Result<(), Error> {
+    #[cfg(feature = "ftdi")]
+    {
+        let mode = match get_bit_mode(&mode) {
+            Some(mode) => mode,
+            None => return Err(Error::String(format!("Invalid bit mode: {}", mode))),
+        };
+        match state.serialports.lock() {
+            Ok(serialports) if serialports.contains_key(&path) => {
+                return Err(Error::String(format!(
+                    "Port {} is opened, close it before changing the bit mode",
+                    path
+                )));
+            }
+            Ok(_) => {}
+            Err(error) => return Err(Error::String(format!("Cannot get lock: {}", error))),
+        }
+        let mut devices = match state.ftdi.lock() {
+            Ok(devices) => devices,
+            Err(error) => return Err(Error::String(format!("Cannot get lock: {}", error))),
+        };
+        if mode == BitMode::Reset {
+            return match devices.remove(&path) {
+                Some(device) => device.release(),
+                None => Ok(()),
+            };
+        }
+        if !devices.contains_key(&path) {
+            let device = crate::ftdi::open(&path, interface.unwrap_or(0))?;
+            devices.insert(path.clone(), device);
+        }
+        info!(path = %path, "FTDI bit mode changed");
+        devices[&path].set_bitmode(mode, mask.unwrap_or(0))
+    }
+    #[cfg(not(feature = "ftdi"))]
+    {
+        let _ = (state, path, mode, mask, interface);
+        Err(ftdi_unavailable())
+    }
+}
+
+/// `ftdi_read_pins` Read the pin states of an FTDI adapter in bitbang or MPSSE mode
+#[command]
+pub fn ftdi_read_pins<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<u8, Error> {
+    #[cfg(feature = "ftdi")]
+    {
+        get_ftdi(state, path, |device| device.read_pins())
+    }
+    #[cfg(not(feature = "ftdi"))]
+    {
+        let _ = (state, path);
+        Err(ftdi_unavailable())
+    }
+}
+
+/// `ftdi_write_pins` Set the output pins of an FTDI adapter in bitbang mode
+#[command]
+pub fn ftdi_write_pins<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    value: u8,
+) -> Result<(), Error> {
+    #[cfg(feature = "ftdi")]
+    {
+        get_ftdi(state, path, |device| {
+            device.write(&[value], Duration::from_millis(1000))
+        })
+    }
+    #[cfg(not(feature = "ftdi"))]
+    {
+        let _ = (state, path, value);
+        Err(ftdi_unavailable())
+    }
+}
+
+/// `ftdi_mpsse` Send MPSSE command bytes and read `read_size` bytes of answers
+#[command]
+pub fn ftdi_mpsse<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    data: Vec<u8>,
+    read_size: Option<usize>,
+    timeout: Option<u64>,
+) -> Result<Vec<u8>, Error> {
+    #[cfg(feature = "ftdi")]
+    {
+        let timeout = Duration::from_millis(timeout.unwrap_or(1000));
+        get_ftdi(state, path, |device| {
+            device.write(&data, timeout)?;
+            match read_size {
+                Some(read_size) if read_size > 0 => device.read(read_size, timeout),
+                _ => Ok(Vec::new()),
+            }
+        })
+    }
+    #[cfg(not(feature = "ftdi"))]
+    {
+        let _ = (state, path, data, read_size, timeout);
+        Err(ftdi_unavailable())
+    }
+}
+
+/// `emit_urc` Send an unsolicited result code of an AT modem to the frontend
+fn emit_urc<R: Runtime>(window: &Window<R>, path: &str, urc: &Urc) {
+    debug!(path = %path, "URC {}", urc.line);
+    let data = UrcData {
+        line: urc.line.clone(),
+        pdu: urc.pdu.clone(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "urc", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `handle_sms_urc` Fetch a message announced by `+CMTI` and decode delivery reports (`+CDS`)
+fn handle_sms_urc<R: Runtime>(
+    window: &Window<R>,
+    path: &str,
+    at: &Arc<AtChannel>,
+    serial: &dyn SerialPort,
+    urc: Urc,
+) {
+    if let Some(index) = sms::new_message_index(&urc.line) {
+        let mut port = match serial.try_clone() {
+            Ok(port) => port,
+            Err(error) => {
+                error!(path = %path, "Cannot fetch message {}: {}", index, error);
+                return;
+            }
+        };
+        let (window, path, at) = (window.clone(), path.to_string(), at.clone());
+        // the response is fed by the read thread, so wait for it elsewhere
+        thread::spawn(move || match sms::read(&at, port.as_mut(), true, index, &mut |_| {}) {
+            Ok(message) => emit_sms(&window, &path, message),
+            Err(error) => error!(path = %path, "Cannot fetch message {}: {}", index, error),
+        });
+    } else if let (true, Some(pdu)) = (urc.line.starts_with("+CDS:"), &urc.pdu) {
+        match sms::decode_status_report(pdu) {
+            Ok(report) => {
+                let event = event_name(window, "sms-report", path);
+                if let Err(error) = window.emit(&event, report) {
+                    error!("Failed to emit event: {}", error);
+                }
+            }
+            Err(error) => debug!(path = %path, "Cannot decode delivery report: {}", error),
+        }
+    } else if let (true, Some(pdu)) = (urc.line.starts_with("+CMT:"), &urc.pdu) {
+        match sms::decode_deliver(pdu) {
+            Ok(message) => emit_sms(window, path, message),
+            Err(error) => debug!(path = %path, "Cannot decode message: {}", error),
+        }
+    }
+}
+
+fn emit_sms<R: Runtime>(window: &Window<R>, path: &str, message: SmsMessage) {
+    info!(path = %path, sender = %message.sender, "Received SMS");
+    if let Err(error) = window.emit(&event_name(window, "sms", path), message) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// A clone of the port for a transaction, its AT channel and whether the background read runs.
+type AtTarget = (Box<dyn SerialPort>, Arc<AtChannel>, bool);
+
+/// `get_at_channel` What an AT transaction on the port needs
+fn get_at_channel(state: State<'_, SerialportState>, path: &str) -> Result<AtTarget, Error> {
+    get_serialport(state, path.to_string(), |serialport_info| {
+        match serialport_info.serialport.try_clone() {
+            Ok(port) => Ok((port, serialport_info.at.clone(), is_reading(serialport_info))),
+            Err(error) => Err(Error::String(format!("Failed to clone port {}: {}", path, error))),
+        }
+    })
+}
+
+/// `at_command` Run one AT command and wait for its final result code. Responses are taken
+/// from the background read if it runs, unsolicited result codes are emitted as urc events
+#[command]
+pub async fn at_command<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    command: String,
+    timeout_ms: Option<u64>,
+) -> Result<AtResponse, Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(5000));
+    debug!(path = %path, "AT command {}", command);
+    blocking(move || {
+        at.transact(
+            port.as_mut(),
+            reading,
+            &command,
+            None,
+            timeout,
+            &mut |urc| emit_urc(&window, &path, &urc),
+        )
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `send_sms` Send a text message, long texts as a concatenated message; returns the message
+/// reference of every part
+#[command]
+pub async fn send_sms<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    number: String,
+    text: String,
+    delivery_report: Option<bool>,
+) -> Result<Vec<u8>, Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    info!(path = %path, number = %number, "Send SMS");
+    let report = delivery_report.unwrap_or(false);
+    blocking(move || {
+        sms::send(
+            &at,
+            port.as_mut(),
+            reading,
+            &number,
+            &text,
+            report,
+            &mut |urc| emit_urc(&window, &path, &urc),
+        )
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `read_sms` Read the message stored at `index`
+#[command]
+pub async fn read_sms<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    index: u32,
+) -> Result<SmsMessage, Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    blocking(move || {
+        sms::read(&at, port.as_mut(), reading, index, &mut |urc| {
+            emit_urc(&window, &path, &urc)
+        })
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `list_sms` List stored messages: `Unread`, `Read` or `All` (default)
+#[command]
+pub async fn list_sms<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    status: Option<String>,
+) -> Result<Vec<SmsMessage>, Error> {
+    let status = match status.as_deref() {
+        Some("Unread") => 0,
+        Some("Read") => 1,
+        _ => 4,
+    };
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    blocking(move || {
+        sms::list(&at, port.as_mut(), reading, status, &mut |urc| {
+            emit_urc(&window, &path, &urc)
+        })
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `delete_sms` Delete the message stored at `index`
+#[command]
+pub async fn delete_sms<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    index: u32,
+) -> Result<(), Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    blocking(move || {
+        sms::delete(&at, port.as_mut(), reading, index, &mut |urc| {
+            emit_urc(&window, &path, &urc)
+        })
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `set_sms_notifications` Have the modem announce new messages and delivery reports, which the
+/// background read then emits as sms and sms-report events
+#[command]
+pub async fn set_sms_notifications<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    enabled: bool,
+) -> Result<(), Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    if enabled {
+        let at = at.clone();
+        blocking(move || {
+            sms::enable_notifications(&at, port.as_mut(), reading, &mut |urc| {
+                emit_urc(&window, &path, &urc)
+            })
+            .map_err(Error::String)
+        })
+        .await?;
+    }
+    let mut engine = at
+        .engine
+        .lock()
+        .map_err(|error| Error::String(format!("Cannot get AT lock: {}", error)))?;
+    engine.enabled = true;
+    engine.sms = enabled;
+    Ok(())
+}
+
+/// `handle_socket_urc` Fetch socket data a cellular module announced and emit it as socket
+/// events, closed sockets as socket-closed events
+fn handle_socket_urc<R: Runtime>(
+    window: &Window<R>,
+    path: &str,
+    at: &Arc<AtChannel>,
+    serial: &dyn SerialPort,
+    stack: Stack,
+    urc: &Urc,
+) {
+    match stack.parse_urc(&urc.line) {
+        Some(SocketUrc::Data(socket)) => {
+            let mut port = match serial.try_clone() {
+                Ok(port) => port,
+                Err(error) => {
+                    error!(path = %path, "Cannot receive from socket {}: {}", socket, error);
+                    return;
+                }
+            };
+            let (window, path, at) = (window.clone(), path.to_string(), at.clone());
+            // the response is fed by the read thread, so wait for it elsewhere
+            thread::spawn(move || {
+                match cellular::receive(&at, port.as_mut(), true, stack, socket, &mut |_| {}) {
+                    // an earlier fetch may have taken the data already
+                    Ok(data) if data.is_empty() => {}
+                    Ok(data) => emit_socket_data(&window, &path, socket, data),
+                    Err(error) => {
+                        error!(path = %path, "Cannot receive from socket {}: {}", socket, error)
+                    }
+                }
+            });
+        }
+        Some(SocketUrc::Closed(socket)) => {
+            info!(path = %path, "Socket {} closed", socket);
+            let data = SocketClosed {
+                socket,
+                timestamp: now_millis(),
+            };
+            let event = event_name(window, "socket-closed", path);
+            if let Err(error) = window.emit(&event, data) {
+                error!("Failed to emit event: {}", error);
+            }
+        }
+        None => {}
+    }
+}
+
+fn emit_socket_data<R: Runtime>(window: &Window<R>, path: &str, socket: u8, data: Vec<u8>) {
+    let data = SocketData {
+        socket,
+        data,
+        timestamp: now_millis(),
+    };
+    if let Err(error) = window.emit(&event_name(window, "socket", path), data) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `emit_lora` Send a LoRaWAN downlink to the frontend
+fn emit_lora<R: Runtime>(window: &Window<R>, path: &str, downlink: LoraDownlink) {
+    debug!(path = %path, port = downlink.port, size = downlink.data.len(), "LoRaWAN downlink");
+    if let Err(error) = window.emit(&event_name(window, "lora", path), downlink) {
+        error!("Failed to emit event: {}", error);
+    }
+}
+
+/// `handle_lora_urc` Emit a URC seen during a LoRa transaction and the downlink it announces
+fn handle_lora_urc<R: Runtime>(window: &Window<R>, path: &str, module: Module, urc: Urc) {
+    emit_urc(window, path, &urc);
+    if let Some(downlink) = lora::parse_urc(module, &urc.line) {
+        emit_lora(window, path, downlink);
+    }
+}
+
+/// `lora_command` Run one command of an `Rn2483` or `Rak` LoRa module, e.g. `mac set appeui ...`
+/// or `AT+APPEUI=...`, and wait for its answer
+#[command]
+pub async fn lora_command<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    module: Module,
+    command: String,
+    timeout_ms: Option<u64>,
+) -> Result<AtResponse, Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(5000));
+    debug!(path = %path, "LoRa command {}", command);
+    blocking(move || {
+        lora::command(
+            &at,
+            port.as_mut(),
+            reading,
+            module,
+            &command,
+            timeout,
+            &mut |urc| handle_lora_urc(&window, &path, module, urc),
+        )
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `lora_join` Join the LoRaWAN network with the keys configured in the module, `Otaa` waits
+/// for the join accept up to `timeout_ms` (30 s by default)
+#[command]
+pub async fn lora_join<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    module: Module,
+    activation: Activation,
+    timeout_ms: Option<u64>,
+) -> Result<(), Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    let timeout = timeout_ms.map_or(lora::NETWORK_TIMEOUT, Duration::from_millis);
+    info!(path = %path, "LoRaWAN join with {:?}", activation);
+    blocking(move || {
+        lora::join(
+            &at,
+            port.as_mut(),
+            reading,
+            module,
+            activation,
+            timeout,
+            &mut |urc| handle_lora_urc(&window, &path, module, urc),
+        )
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `lora_send` Send a LoRaWAN uplink on the application `port`; returns the downlink an
+/// `Rn2483` received in the RX windows, which is also emitted as lora event like the downlinks
+/// `Rak` modules announce
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn lora_send<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    module: Module,
+    port: u8,
+    data: Vec<u8>,
+    confirmed: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<Option<LoraDownlink>, Error> {
+    let (mut serial, at, reading) = get_at_channel(state, &path)?;
+    let timeout = timeout_ms.map_or(lora::NETWORK_TIMEOUT, Duration::from_millis);
+    let confirmed = confirmed.unwrap_or(false);
+    blocking(move || {
+        let downlink = lora::send(
+            &at,
+            serial.as_mut(),
+            reading,
+            module,
+            port,
+            &data,
+            confirmed,
+            timeout,
+            &mut |urc| handle_lora_urc(&window, &path, module, urc),
+        )
+        .map_err(Error::String)?;
+        if let Some(downlink) = &downlink {
+            emit_lora(&window, &path, downlink.clone());
+        }
+        Ok(downlink)
     })
+    .await
 }
 
-/// `write` Write binary data to serial port
+/// `e22_registers` Read `length` configuration registers of an Ebyte E22 module from `address`,
+/// or write `values` there, `temporary` until the module is powered off; returns the registers.
+/// The module must be in configuration mode
 #[command]
-pub fn write_binary<R: Runtime>(
+#[allow(clippy::too_many_arguments)]
+pub async fn e22_registers<R: Runtime>(
     _app: AppHandle<R>,
     _window: Window<R>,
     state: State<'_, SerialportState>,
     path: String,
-    value: Vec<u8>,
+    address: u8,
+    length: Option<u8>,
+    values: Option<Vec<u8>>,
+    temporary: Option<bool>,
+) -> Result<Vec<u8>, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let length = length.unwrap_or(1);
+    let temporary = temporary.unwrap_or(false);
+    blocking(move || {
+        lora::e22_registers(port.as_mut(), address, length, values.as_deref(), temporary)
+            .map_err(Error::String)
+    })
+    .await
+}
+
+/// `obd_init` Reset the ELM327 adapter on the port and connect to the vehicle with `protocol`
+/// (the `ATSP` number, automatic search by default) and the optional request `header`
+#[command]
+pub async fn obd_init<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    protocol: Option<String>,
+    header: Option<String>,
+    timeout_ms: Option<u64>,
+) -> Result<ObdInfo, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    // the automatic search tries the protocols one after the other
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(10000));
+    let info = blocking(move || {
+        obd::initialize(
+            port.as_mut(),
+            protocol.as_deref(),
+            header.as_deref(),
+            timeout,
+        )
+        .map_err(Error::String)
+    })
+    .await?;
+    info!(path = %path, version = %info.version, protocol = %info.protocol, "OBD connected");
+    Ok(info)
+}
+
+/// `obd_read_pid` Read a mode 01 PID through the ELM327 adapter and decode its value, e.g.
+/// `0x0C` engine speed or `0x05` coolant temperature
+#[command]
+pub async fn obd_read_pid<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    pid: u8,
+    timeout_ms: Option<u64>,
+) -> Result<PidValue, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(2000));
+    blocking(move || obd::read_pid(port.as_mut(), pid, timeout).map_err(Error::String)).await
+}
+
+/// `obd_read_dtcs` Read the stored diagnostic trouble codes, or the `pending` ones, e.g. `P0133`
+#[command]
+pub async fn obd_read_dtcs<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    pending: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<Vec<String>, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(2000));
+    let pending = pending.unwrap_or(false);
+    blocking(move || obd::read_dtcs(port.as_mut(), pending, timeout).map_err(Error::String)).await
+}
+
+/// `obd_clear_dtcs` Clear the diagnostic trouble codes and freeze frames, turning off the MIL
+#[command]
+pub async fn obd_clear_dtcs<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    timeout_ms: Option<u64>,
+) -> Result<(), Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(5000));
+    info!(path = %path, "Clearing OBD trouble codes");
+    blocking(move || obd::clear_dtcs(port.as_mut(), timeout).map_err(Error::String)).await
+}
+
+/// `label_status` Query the `~HS` host status of a Zebra-compatible label printer
+#[command]
+pub async fn label_status<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+) -> Result<LabelStatus, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    blocking(move || label::status(port.as_mut()).map_err(Error::String)).await
+}
+
+/// `print_label` Send a ZPL or EPL job to a label printer. With `check_status` (the default) it
+/// is only sent when the printer is ready, and with `wait` the command returns once the
+/// printer reports it printed, within `timeout_ms` (30000 by default)
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn print_label<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    job: String,
+    language: Option<LabelLanguage>,
+    check_status: Option<bool>,
+    wait: Option<bool>,
+    timeout_ms: Option<u64>,
+) -> Result<Option<LabelStatus>, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let language = language.unwrap_or(LabelLanguage::Zpl);
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(30000));
+    let check_status = check_status.unwrap_or(true);
+    let wait = wait.unwrap_or(false);
+    debug!(path = %path, language = ?language, size = job.len(), "Label job");
+    blocking(move || {
+        label::print(port.as_mut(), &job, language, check_status, wait, timeout)
+            .map_err(Error::String)
+    })
+    .await
+}
+
+/// `calibrate_label` Let the label printer measure its media, feeding a few labels
+#[command]
+pub async fn calibrate_label<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    language: Option<LabelLanguage>,
+) -> Result<(), Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let language = language.unwrap_or(LabelLanguage::Zpl);
+    blocking(move || label::calibrate(port.as_mut(), language).map_err(Error::String)).await
+}
+
+/// `printer_status` Poll the paper, cover, drawer and error status of an ESC/POS printer with
+/// `DLE EOT`, each answer within `timeout_ms` (500 by default)
+#[command]
+pub async fn printer_status<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    timeout_ms: Option<u64>,
+) -> Result<PrinterStatus, Error> {
+    let mut port = get_idle_port(state, &path)?;
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(500));
+    blocking(move || escpos::status(port.as_mut(), timeout).map_err(Error::String)).await
+}
+
+/// `kick_cash_drawer` Open the cash drawer on `pin` 2 (the default) or 5 of the drawer kick
+/// connector of an ESC/POS printer, with a `pulse` of 100 ms by default
+#[command]
+pub fn kick_cash_drawer<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    pin: Option<u8>,
+    pulse: Option<u64>,
 ) -> Result<usize, Error> {
-    get_serialport(state, path.clone(), |serialport_info| match serialport_info
-        .serialport
-        .write(&value)
+    let pulse = Duration::from_millis(pulse.unwrap_or(100));
+    let value =
+        escpos::drawer_kick(pin.unwrap_or(2), pulse).map_err(|message| Error::InvalidSettings {
+            path: path.clone(),
+            message,
+        })?;
+    info!(path = %path, "Kicking cash drawer");
+    write_value(&window, state, &path, &value)
+}
+
+/// `get_cellular_stack` The socket stack `cellular_connect` configured for the port
+fn get_cellular_stack(at: &AtChannel, path: &str) -> Result<Stack, Error> {
+    at.engine
+        .lock()
+        .map_err(|error| Error::String(format!("Cannot get AT lock: {}", error)))?
+        .cellular
+        .ok_or_else(|| Error::String(format!("Cellular data is not connected on {}", path)))
+}
+
+/// `cellular_connect` Attach the module to the packet network and configure its socket stack:
+/// `Sim800`, `Quectel` or `Ublox`; returns the local IP address. With the background read,
+/// received socket data is emitted as socket events
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn cellular_connect<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    stack: Stack,
+    apn: String,
+    user: Option<String>,
+    password: Option<String>,
+) -> Result<String, Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    info!(path = %path, apn = %apn, "Cellular connect with {:?}", stack);
+    let user = user.unwrap_or_default();
+    let password = password.unwrap_or_default();
+    blocking(move || {
+        cellular::connect(
+            &at,
+            port.as_mut(),
+            reading,
+            stack,
+            &apn,
+            &user,
+            &password,
+            &mut |urc| emit_urc(&window, &path, &urc),
+        )
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `socket_open` Open a `Tcp` or `Udp` socket to `host`; returns the socket number
+#[command]
+#[allow(clippy::too_many_arguments)]
+pub async fn socket_open<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    protocol: Protocol,
+    host: String,
+    port: u16,
+    socket: Option<u8>,
+) -> Result<u8, Error> {
+    let (mut serial, at, reading) = get_at_channel(state, &path)?;
+    let stack = get_cellular_stack(&at, &path)?;
+    info!(path = %path, host = %host, port = port, "Open socket");
+    let socket = socket.unwrap_or(0);
+    blocking(move || {
+        cellular::open(
+            &at,
+            serial.as_mut(),
+            reading,
+            stack,
+            protocol,
+            &host,
+            port,
+            socket,
+            &mut |urc| emit_urc(&window, &path, &urc),
+        )
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `socket_send` Send data through an open socket
+#[command]
+pub async fn socket_send<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    socket: u8,
+    data: Vec<u8>,
+) -> Result<(), Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    let stack = get_cellular_stack(&at, &path)?;
+    blocking(move || {
+        cellular::send(
+            &at,
+            port.as_mut(),
+            reading,
+            stack,
+            socket,
+            &data,
+            &mut |urc| emit_urc(&window, &path, &urc),
+        )
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `socket_receive` Read the data the module buffered for the socket, for ports without the
+/// background read
+#[command]
+pub async fn socket_receive<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    socket: u8,
+) -> Result<Vec<u8>, Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    let stack = get_cellular_stack(&at, &path)?;
+    blocking(move || {
+        cellular::receive(&at, port.as_mut(), reading, stack, socket, &mut |urc| {
+            emit_urc(&window, &path, &urc)
+        })
+        .map_err(Error::String)
+    })
+    .await
+}
+
+/// `socket_close` Close an open socket
+#[command]
+pub async fn socket_close<R: Runtime>(
+    _app: AppHandle<R>,
+    window: Window<R>,
+    state: State<'_, SerialportState>,
+    path: String,
+    socket: u8,
+) -> Result<(), Error> {
+    let (mut port, at, reading) = get_at_channel(state, &path)?;
+    let stack = get_cellular_stack(&at, &path)?;
+    info!(path = %path, "Close socket {}", socket);
+    blocking(move || {
+        cellular::close(&at, port.as_mut(), reading, stack, socket, &mut |urc| {
+            emit_urc(&window, &path, &urc)
+        })
+        .map_err(Error::String)
+    })
+    .await
+}
+
+#[cfg(not(feature = "emulator"))]
+fn emulator_unavailable() -> Error {
+    Error::String("Emulated ports need the `emulator` feature".to_string())
+}
+
+/// `define_emulator` Define the scripted device opened as `emu://<name>`, replacing a previous
+/// script of that name for ports opened afterwards
+#[command]
+pub fn define_emulator<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    name: String,
+    script: serde_json::Value,
+) -> Result<(), Error> {
+    #[cfg(feature = "emulator")]
     {
-        Ok(size) => {
-            Ok(size)
+        let script: crate::emulator::Script = serde_json::from_value(script)
+            .map_err(|error| Error::String(format!("Invalid emulator script: {}", error)))?;
+        script.validate().map_err(Error::String)?;
+        match state.emulators.lock() {
+            Ok(mut emulators) => {
+                info!("Defined emulator {}", name);
+                emulators.insert(name, script);
+                Ok(())
+            }
+            Err(error) => Err(Error::String(format!("Cannot get lock: {}", error))),
         }
-        Err(error) => {
-            Err(Error::String(format!(
-                "Failed to write data to port {}: {}",
-                &path, error
-            )))
+    }
+    #[cfg(not(feature = "emulator"))]
+    {
+        let _ = (state, name, script);
+        Err(emulator_unavailable())
+    }
+}
+
+/// `remove_emulator` Forget the script of an emulated device, ports already open keep running it
+#[command]
+pub fn remove_emulator<R: Runtime>(
+    _app: AppHandle<R>,
+    _window: Window<R>,
+    state: State<'_, SerialportState>,
+    name: String,
+) -> Result<(), Error> {
+    #[cfg(feature = "emulator")]
+    {
+        match state.emulators.lock() {
+            Ok(mut emulators) => match emulators.remove(&name) {
+                Some(_) => Ok(()),
+                None => Err(Error::String(format!("No emulator {}", name))),
+            },
+            Err(error) => Err(Error::String(format!("Cannot get lock: {}", error))),
         }
-    })
+    }
+    #[cfg(not(feature = "emulator"))]
+    {
+        let _ = (state, name);
+        Err(emulator_unavailable())
+    }
 }
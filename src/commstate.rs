@@ -0,0 +1,106 @@
+//! Settings of native Windows ports that `serialport` does not offer, applied to the handle
+//! kept in `SerialportInfo::native`.
+
+use crate::capabilities::{Capabilities, BAUD_RATES};
+use std::io;
+use windows_sys::Win32::Devices::Communication::{
+    ClearCommError, GetCommProperties, GetCommState, SetCommState, COMMPROP, COMSTAT, DCB,
+    MARKPARITY, PARITY_EVEN, PARITY_MARK, PARITY_NONE, PARITY_ODD, PARITY_SPACE, SPACEPARITY,
+    STOPBITS_10, STOPBITS_20,
+};
+use windows_sys::Win32::Foundation::HANDLE;
+
+/// `fParity` in the flags of the DCB.
+const PARITY_CHECK: u32 = 1 << 1;
+
+/// `set_stick_parity` Mark (`true`) or Space parity, received parity is not checked.
+pub fn set_stick_parity(handle: HANDLE, mark: bool) -> io::Result<()> {
+    let mut dcb: DCB = unsafe { std::mem::zeroed() };
+    dcb.DCBlength = std::mem::size_of::<DCB>() as u32;
+    if unsafe { GetCommState(handle, &mut dcb) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    dcb.Parity = if mark { MARKPARITY } else { SPACEPARITY };
+    dcb._bitfield &= !PARITY_CHECK;
+    if unsafe { SetCommState(handle, &dcb) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// `BAUD_*` bits of `dwSettableBaud`, defined in `WindowsProgramming` of `windows-sys`.
+const SETTABLE_BAUD: &[(u32, u32)] = &[
+    (0x0000_0002, 110),
+    (0x0000_0010, 300),
+    (0x0000_0020, 600),
+    (0x0000_0040, 1200),
+    (0x0000_0100, 2400),
+    (0x0000_0200, 4800),
+    (0x0000_0800, 9600),
+    (0x0000_1000, 14400),
+    (0x0000_2000, 19200),
+    (0x0000_4000, 38400),
+    (0x0004_0000, 57600),
+    (0x0002_0000, 115200),
+];
+/// Any rate can be set.
+const BAUD_USER: u32 = 0x1000_0000;
+/// `DATABITS_5` to `DATABITS_8` bits of `wSettableData`.
+const SETTABLE_DATA: &[(u16, u8)] = &[(0x0001, 5), (0x0002, 6), (0x0004, 7), (0x0008, 8)];
+
+/// `capabilities` The settings the driver reports with `GetCommProperties`.
+pub fn capabilities(handle: HANDLE) -> io::Result<Capabilities> {
+    let mut properties: COMMPROP = unsafe { std::mem::zeroed() };
+    if unsafe { GetCommProperties(handle, &mut properties) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let custom_baud_rates = properties.dwSettableBaud & BAUD_USER != 0;
+    let baud_rates = if custom_baud_rates {
+        BAUD_RATES.to_vec()
+    } else {
+        SETTABLE_BAUD
+            .iter()
+            .filter(|(bit, _)| properties.dwSettableBaud & bit != 0)
+            .map(|&(_, rate)| rate)
+            .collect()
+    };
+    let stop_parity = properties.wSettableStopParity;
+    let parities = [
+        (PARITY_NONE, "None"),
+        (PARITY_ODD, "Odd"),
+        (PARITY_EVEN, "Even"),
+        (PARITY_MARK, "Mark"),
+        (PARITY_SPACE, "Space"),
+    ];
+    Ok(Capabilities {
+        baud_rates,
+        custom_baud_rates,
+        data_bits: SETTABLE_DATA
+            .iter()
+            .filter(|(bit, _)| properties.wSettableData & bit != 0)
+            .map(|&(_, bits)| bits)
+            .collect(),
+        parities: parities
+            .iter()
+            .filter(|(bit, _)| stop_parity & bit != 0)
+            .map(|(_, name)| name.to_string())
+            .collect(),
+        stop_bits: [(STOPBITS_10, 1), (STOPBITS_20, 2)]
+            .iter()
+            .filter(|(bit, _)| stop_parity & bit != 0)
+            .map(|&(_, bits)| bits)
+            .collect(),
+        source: "Driver".to_string(),
+    })
+}
+
+/// `clear_errors` The `CE_*` errors of the port since the last call, which clears them, and the
+/// bytes waiting to be read and to be sent.
+pub fn clear_errors(handle: HANDLE) -> io::Result<(u32, u32, u32)> {
+    let mut errors = 0;
+    let mut status: COMSTAT = unsafe { std::mem::zeroed() };
+    if unsafe { ClearCommError(handle, &mut errors, &mut status) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((errors, status.cbInQue, status.cbOutQue))
+}
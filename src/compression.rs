@@ -0,0 +1,210 @@
+//! Compressed framing for slow links, matching a codec on the firmware side: with compression
+//! set, every write is sent as frames of zlib or LZ4 compressed payloads, and received frames
+//! are decompressed before they are read.
+//!
+//! A frame is the magic `5A C3`, the method (0 stored, 1 zlib, 2 LZ4 block), the payload length
+//! as u16 little endian, the payload and the CRC-32 of the uncompressed data as u32 little
+//! endian. Each frame holds at most `MAX_CHUNK` bytes before compression and is compressed on
+//! its own, so a lost frame does not affect the next one; data that does not get smaller is
+//! stored.
+
+use crate::lz4;
+use crate::schema::{self, Algorithm as Checksum};
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+const MAGIC: [u8; 2] = [0x5a, 0xc3];
+const HEADER_SIZE: usize = 5;
+const CRC_SIZE: usize = 4;
+/// Most bytes a frame carries before compression, longer writes are split.
+pub const MAX_CHUNK: usize = 4096;
+
+const STORED: u8 = 0;
+const ZLIB: u8 = 1;
+const LZ4: u8 = 2;
+
+/// `Algorithm` How writes are compressed. Received frames are decompressed with the method they
+/// name, whichever is set.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    Zlib,
+    Lz4,
+}
+
+/// `CompressionStats` What the compression saved since it was set.
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct CompressionStats {
+    /// Bytes written before and after compression, frames included.
+    pub written_bytes: u64,
+    pub sent_bytes: u64,
+    /// Bytes of the frames received and after decompression.
+    pub received_bytes: u64,
+    pub read_bytes: u64,
+    /// Frames with a wrong CRC or a payload that did not decompress.
+    pub rejected_frames: u64,
+    /// Bytes skipped while looking for the next frame.
+    pub skipped_bytes: u64,
+}
+
+/// `Compression` The method and level of a compressed port and the data received but not yet
+/// read.
+pub struct Compression {
+    algorithm: Algorithm,
+    /// zlib level from 0 (fastest) to 9 (smallest).
+    level: u32,
+    /// Raw bytes of a frame not received completely.
+    raw: Vec<u8>,
+    /// Decompressed data the reads did not take yet.
+    plaintext: Vec<u8>,
+    stats: CompressionStats,
+}
+
+/// `SharedCompression` Set while the port is compressed, shared by the port and its clones.
+pub type SharedCompression = Arc<Mutex<Option<Compression>>>;
+
+fn crc(data: &[u8]) -> [u8; CRC_SIZE] {
+    (schema::checksum(Checksum::Crc32, data) as u32).to_le_bytes()
+}
+
+fn deflate(data: &[u8], level: u32) -> Option<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::new(level));
+    encoder.write_all(data).ok()?;
+    encoder.finish().ok()
+}
+
+fn inflate(payload: &[u8]) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    ZlibDecoder::new(payload)
+        .take(MAX_CHUNK as u64 + 1)
+        .read_to_end(&mut data)
+        .map_err(|error| error.to_string())?;
+    if data.len() > MAX_CHUNK {
+        return Err(format!("zlib payload longer than {} bytes", MAX_CHUNK));
+    }
+    Ok(data)
+}
+
+impl Compression {
+    /// `new` Compression with `algorithm`, at zlib `level` 6 unless given.
+    pub fn new(algorithm: Algorithm, level: Option<u32>) -> Result<Compression, String> {
+        let level = level.unwrap_or(6);
+        if level > 9 {
+            return Err(format!("zlib levels go from 0 to 9, not {}", level));
+        }
+        Ok(Compression {
+            algorithm,
+            level,
+            raw: Vec::new(),
+            plaintext: Vec::new(),
+            stats: CompressionStats::default(),
+        })
+    }
+
+    pub fn stats(&self) -> CompressionStats {
+        self.stats
+    }
+
+    /// `pending` Decompressed bytes waiting for a read.
+    pub fn pending(&self) -> usize {
+        self.plaintext.len()
+    }
+
+    /// `seal` The frames that carry `data`.
+    pub fn seal(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut frames = Vec::new();
+        for chunk in data.chunks(MAX_CHUNK) {
+            let compressed = match self.algorithm {
+                Algorithm::Zlib => deflate(chunk, self.level).map(|payload| (ZLIB, payload)),
+                Algorithm::Lz4 => Some((LZ4, lz4::compress(chunk))),
+            };
+            let (method, payload) = match compressed {
+                Some((method, payload)) if payload.len() < chunk.len() => (method, payload),
+                _ => (STORED, chunk.to_vec()),
+            };
+            frames.extend_from_slice(&MAGIC);
+            frames.push(method);
+            frames.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+            frames.extend_from_slice(&payload);
+            frames.extend_from_slice(&crc(chunk));
+        }
+        self.stats.written_bytes += data.len() as u64;
+        self.stats.sent_bytes += frames.len() as u64;
+        frames
+    }
+
+    /// `receive` Add raw bytes, decompressing the frames they complete.
+    pub fn receive(&mut self, data: &[u8]) {
+        self.raw.extend_from_slice(data);
+        let mut start = 0;
+        while let Some(size) = self.next_frame(start) {
+            start += size;
+        }
+        self.raw.drain(..start);
+    }
+
+    /// `next_frame` Decompress the frame at or after `start`, returns the bytes consumed or
+    /// `None` until more is received.
+    fn next_frame(&mut self, start: usize) -> Option<usize> {
+        let raw = &self.raw[start..];
+        let found = match raw.windows(2).position(|window| window == MAGIC) {
+            Some(found) => found,
+            None => {
+                // a magic may begin with the last byte
+                let skipped = raw.len().saturating_sub(1);
+                self.stats.skipped_bytes += skipped as u64;
+                return if skipped > 0 { Some(skipped) } else { None };
+            }
+        };
+        if found > 0 {
+            self.stats.skipped_bytes += found as u64;
+            return Some(found);
+        }
+        if raw.len() < HEADER_SIZE {
+            return None;
+        }
+        let method = raw[2];
+        let length = u16::from_le_bytes([raw[3], raw[4]]) as usize;
+        if method > LZ4 || length > MAX_CHUNK {
+            self.stats.skipped_bytes += 1;
+            return Some(1);
+        }
+        let size = HEADER_SIZE + length + CRC_SIZE;
+        if raw.len() < size {
+            return None;
+        }
+        let payload = &raw[HEADER_SIZE..HEADER_SIZE + length];
+        let data = match method {
+            STORED => Ok(payload.to_vec()),
+            ZLIB => inflate(payload),
+            _ => lz4::decompress(payload, MAX_CHUNK),
+        };
+        match data {
+            Ok(data) if crc(&data)[..] == raw[HEADER_SIZE + length..size] => {
+                self.stats.received_bytes += size as u64;
+                self.stats.read_bytes += data.len() as u64;
+                self.plaintext.extend_from_slice(&data);
+                Some(size)
+            }
+            // no frame after all, or a damaged one, the next may start inside it
+            result => {
+                if let Err(error) = result {
+                    debug!("Compressed frame rejected: {}", error);
+                }
+                self.stats.rejected_frames += 1;
+                self.stats.skipped_bytes += 1;
+                Some(1)
+            }
+        }
+    }
+
+    /// `take` Move decompressed data into `buf`, returns its size.
+    pub fn take(&mut self, buf: &mut [u8]) -> usize {
+        let size = buf.len().min(self.plaintext.len());
+        buf[..size].copy_from_slice(&self.plaintext[..size]);
+        self.plaintext.drain(..size);
+        size
+    }
+}
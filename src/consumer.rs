@@ -0,0 +1,147 @@
+//! Named consumers of a port. The received stream is kept once, every consumer reads it at its
+//! own cursor and frames it its own way, so a raw logger and a line based view do not compete
+//! for the same bytes.
+
+use crate::transfer::Framing;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+
+/// Bytes of the stream kept by default.
+pub const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+struct Consumer {
+    /// Stream offset of the next byte to read.
+    cursor: u64,
+    framing: Framing,
+    /// Start of a line not terminated yet.
+    partial: Vec<u8>,
+}
+
+/// `Consumed` What a consumer took from the stream.
+#[derive(Serialize, Clone)]
+pub struct Consumed {
+    pub frames: Vec<Vec<u8>>,
+    /// Bytes the stream discarded before the consumer read them.
+    pub skipped: u64,
+    /// Bytes still ahead of the cursor.
+    pub available: usize,
+}
+
+/// `Stream` The received bytes of a port, addressed by offset since the first consumer was added.
+pub struct Stream {
+    data: VecDeque<u8>,
+    /// Offset of the first byte in `data`.
+    start: u64,
+    capacity: usize,
+    consumers: HashMap<String, Consumer>,
+}
+
+impl Default for Stream {
+    fn default() -> Self {
+        Stream {
+            data: VecDeque::new(),
+            start: 0,
+            capacity: DEFAULT_CAPACITY,
+            consumers: HashMap::new(),
+        }
+    }
+}
+
+impl Stream {
+    fn end(&self) -> u64 {
+        self.start + self.data.len() as u64
+    }
+
+    fn trim(&mut self) {
+        let excess = self.data.len().saturating_sub(self.capacity);
+        self.data.drain(..excess);
+        self.start += excess as u64;
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+
+    /// `push` Append received bytes, kept only while there are consumers.
+    pub fn push(&mut self, data: &[u8]) {
+        if self.consumers.is_empty() {
+            return;
+        }
+        self.data.extend(data);
+        self.trim();
+    }
+
+    /// `add` Register a consumer, starting at the oldest kept byte or at the next received one.
+    pub fn add(&mut self, name: &str, framing: Framing, from_oldest: bool) -> Result<(), String> {
+        if self.consumers.contains_key(name) {
+            return Err(format!("Consumer {} already exists", name));
+        }
+        let cursor = if from_oldest { self.start } else { self.end() };
+        self.consumers.insert(
+            name.to_string(),
+            Consumer {
+                cursor,
+                framing,
+                partial: Vec::new(),
+            },
+        );
+        Ok(())
+    }
+
+    /// `remove` Unregister a consumer, the stream is cleared with the last one.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let removed = self.consumers.remove(name).is_some();
+        if self.consumers.is_empty() {
+            self.start = self.end();
+            self.data.clear();
+        }
+        removed
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.consumers.keys().cloned().collect()
+    }
+
+    /// `consume` Advance the cursor of `name` by up to `max_bytes` and frame what it passed.
+    pub fn consume(&mut self, name: &str, max_bytes: usize) -> Result<Consumed, String> {
+        let (start, end) = (self.start, self.end());
+        let consumer = self
+            .consumers
+            .get_mut(name)
+            .ok_or_else(|| format!("No consumer {}", name))?;
+        let skipped = start.saturating_sub(consumer.cursor);
+        if skipped > 0 {
+            // a line cut by the gap would be wrong
+            consumer.partial.clear();
+            consumer.cursor = start;
+        }
+        let from = (consumer.cursor - start) as usize;
+        let count = max_bytes.min((end - consumer.cursor) as usize);
+        let data: Vec<u8> = self.data.range(from..from + count).copied().collect();
+        consumer.cursor += count as u64;
+        let frames = match &mut consumer.framing {
+            Framing::Raw if data.is_empty() => Vec::new(),
+            Framing::Raw => vec![data],
+            Framing::Lines => {
+                consumer.partial.extend_from_slice(&data);
+                let mut lines = Vec::new();
+                while let Some(position) = consumer.partial.iter().position(|&byte| byte == b'\n') {
+                    let mut line: Vec<u8> = consumer.partial.drain(..=position).collect();
+                    line.pop();
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+                    lines.push(line);
+                }
+                lines
+            }
+            Framing::Codec(codec) => codec.decode(&data),
+        };
+        Ok(Consumed {
+            frames,
+            skipped,
+            available: (end - consumer.cursor) as usize,
+        })
+    }
+}
@@ -0,0 +1,595 @@
+//! CBOR, MessagePack and JSON-lines payloads decoded into JSON values in the read thread.
+
+use crate::state::DecodeError;
+use serde_json::{Map, Number, Value};
+
+/// Received bytes kept while a value is incomplete.
+const MAX_PENDING: usize = 64 * 1024;
+/// Nesting deeper than this is rejected instead of overflowing the stack.
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Cbor,
+    MessagePack,
+    /// One JSON document per line.
+    JsonLines,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "Cbor" => Ok(Format::Cbor),
+            "MessagePack" => Ok(Format::MessagePack),
+            "JsonLines" => Ok(Format::JsonLines),
+            _ => Err(format!("Unknown decode format {}", name)),
+        }
+    }
+}
+
+enum Failure {
+    /// More bytes are needed.
+    Incomplete,
+    Invalid(String),
+}
+
+/// `Input` The bytes of one value with a read position.
+struct Input<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], Failure> {
+        if size > MAX_PENDING {
+            return Err(Failure::Invalid(format!("Length {} is too large", size)));
+        }
+        let bytes = self
+            .data
+            .get(self.position..self.position + size)
+            .ok_or(Failure::Incomplete)?;
+        self.position += size;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, Failure> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn peek(&self) -> Result<u8, Failure> {
+        self.data
+            .get(self.position)
+            .copied()
+            .ok_or(Failure::Incomplete)
+    }
+
+    /// `uint` Big-endian unsigned integer of `size` bytes.
+    fn uint(&mut self, size: usize) -> Result<u64, Failure> {
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0, |value, byte| (value << 8) | *byte as u64))
+    }
+}
+
+fn float(value: f64) -> Value {
+    Number::from_f64(value)
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+fn half(bits: u16) -> f64 {
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f64;
+    let value = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mantissa + 1024.0) * 2f64.powi(exponent - 25),
+    };
+    if bits & 0x8000 != 0 {
+        -value
+    } else {
+        value
+    }
+}
+
+fn bytes(data: &[u8]) -> Value {
+    Value::from(data.to_vec())
+}
+
+fn text(data: &[u8]) -> Result<Value, Failure> {
+    std::str::from_utf8(data)
+        .map(Value::from)
+        .map_err(|_| Failure::Invalid("Invalid UTF-8 in text".to_string()))
+}
+
+/// `key` JSON object keys are strings, other map keys are written as JSON.
+fn key(value: Value) -> String {
+    match value {
+        Value::String(key) => key,
+        other => other.to_string(),
+    }
+}
+
+fn cbor_argument(input: &mut Input, info: u8) -> Result<u64, Failure> {
+    match info {
+        0..=23 => Ok(info as u64),
+        24 => input.uint(1),
+        25 => input.uint(2),
+        26 => input.uint(4),
+        27 => input.uint(8),
+        _ => Err(Failure::Invalid(format!(
+            "Invalid additional info {}",
+            info
+        ))),
+    }
+}
+
+/// `cbor_length` An item count that fits into memory, checked against the input size.
+fn cbor_length(input: &mut Input, info: u8) -> Result<usize, Failure> {
+    let length = cbor_argument(input, info)?;
+    if length > MAX_PENDING as u64 {
+        return Err(Failure::Invalid(format!("Length {} is too large", length)));
+    }
+    Ok(length as usize)
+}
+
+/// `cbor_break` Consume the break byte ending an indefinite-length item.
+fn cbor_break(input: &mut Input) -> Result<bool, Failure> {
+    if input.peek()? == 0xff {
+        input.position += 1;
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+fn cbor(input: &mut Input, depth: usize) -> Result<Value, Failure> {
+    if depth > MAX_DEPTH {
+        return Err(Failure::Invalid("Nesting too deep".to_string()));
+    }
+    let initial = input.byte()?;
+    let (major, info) = (initial >> 5, initial & 0x1f);
+    match (major, info) {
+        (0, _) => Ok(Value::from(cbor_argument(input, info)?)),
+        (1, _) => {
+            let value = cbor_argument(input, info)?;
+            Ok(match i64::try_from(value) {
+                Ok(value) => Value::from(-1 - value),
+                Err(_) => float(-1.0 - value as f64),
+            })
+        }
+        (2, 31) | (3, 31) => {
+            let mut data = Vec::new();
+            while !cbor_break(input)? {
+                let chunk = input.byte()?;
+                if chunk >> 5 != major || chunk & 0x1f == 31 {
+                    return Err(Failure::Invalid(
+                        "Invalid chunk of indefinite string".to_string(),
+                    ));
+                }
+                let length = cbor_length(input, chunk & 0x1f)?;
+                data.extend_from_slice(input.take(length)?);
+            }
+            if major == 2 {
+                Ok(bytes(&data))
+            } else {
+                text(&data)
+            }
+        }
+        (2, _) => {
+            let length = cbor_length(input, info)?;
+            Ok(bytes(input.take(length)?))
+        }
+        (3, _) => {
+            let length = cbor_length(input, info)?;
+            text(input.take(length)?)
+        }
+        (4, _) => {
+            let mut items = Vec::new();
+            if info == 31 {
+                while !cbor_break(input)? {
+                    items.push(cbor(input, depth + 1)?);
+                }
+            } else {
+                for _ in 0..cbor_length(input, info)? {
+                    items.push(cbor(input, depth + 1)?);
+                }
+            }
+            Ok(Value::Array(items))
+        }
+        (5, _) => {
+            let mut map = Map::new();
+            if info == 31 {
+                while !cbor_break(input)? {
+                    let name = key(cbor(input, depth + 1)?);
+                    map.insert(name, cbor(input, depth + 1)?);
+                }
+            } else {
+                for _ in 0..cbor_length(input, info)? {
+                    let name = key(cbor(input, depth + 1)?);
+                    map.insert(name, cbor(input, depth + 1)?);
+                }
+            }
+            Ok(Value::Object(map))
+        }
+        // tags, e.g. date/time, are dropped and only the tagged value is kept
+        (6, _) => {
+            cbor_argument(input, info)?;
+            cbor(input, depth + 1)
+        }
+        (7, 20) => Ok(Value::Bool(false)),
+        (7, 21) => Ok(Value::Bool(true)),
+        (7, 22) | (7, 23) => Ok(Value::Null),
+        (7, 25) => Ok(float(half(input.uint(2)? as u16))),
+        (7, 26) => Ok(float(f32::from_bits(input.uint(4)? as u32) as f64)),
+        (7, 27) => Ok(float(f64::from_bits(input.uint(8)?))),
+        (7, 0..=19) => Ok(Value::from(info)),
+        (7, 24) => Ok(Value::from(input.byte()?)),
+        _ => Err(Failure::Invalid(format!(
+            "Unexpected initial byte {:#04x}",
+            initial
+        ))),
+    }
+}
+
+fn msgpack_array(input: &mut Input, length: usize, depth: usize) -> Result<Value, Failure> {
+    let mut items = Vec::new();
+    for _ in 0..length {
+        items.push(msgpack(input, depth + 1)?);
+    }
+    Ok(Value::Array(items))
+}
+
+fn msgpack_map(input: &mut Input, length: usize, depth: usize) -> Result<Value, Failure> {
+    let mut map = Map::new();
+    for _ in 0..length {
+        let name = key(msgpack(input, depth + 1)?);
+        map.insert(name, msgpack(input, depth + 1)?);
+    }
+    Ok(Value::Object(map))
+}
+
+/// `msgpack_ext` Extension values are kept as their type and raw data.
+fn msgpack_ext(input: &mut Input, length: usize) -> Result<Value, Failure> {
+    let kind = input.byte()? as i8;
+    let mut map = Map::new();
+    map.insert("type".to_string(), Value::from(kind));
+    map.insert("data".to_string(), bytes(input.take(length)?));
+    Ok(Value::Object(map))
+}
+
+fn msgpack(input: &mut Input, depth: usize) -> Result<Value, Failure> {
+    if depth > MAX_DEPTH {
+        return Err(Failure::Invalid("Nesting too deep".to_string()));
+    }
+    let marker = input.byte()?;
+    let length = |input: &mut Input, size: usize| -> Result<usize, Failure> {
+        let length = input.uint(size)?;
+        if length > MAX_PENDING as u64 {
+            return Err(Failure::Invalid(format!("Length {} is too large", length)));
+        }
+        Ok(length as usize)
+    };
+    match marker {
+        0x00..=0x7f => Ok(Value::from(marker)),
+        0x80..=0x8f => msgpack_map(input, (marker & 0x0f) as usize, depth),
+        0x90..=0x9f => msgpack_array(input, (marker & 0x0f) as usize, depth),
+        0xa0..=0xbf => text(input.take((marker & 0x1f) as usize)?),
+        0xc0 => Ok(Value::Null),
+        0xc2 => Ok(Value::Bool(false)),
+        0xc3 => Ok(Value::Bool(true)),
+        0xc4..=0xc6 => {
+            let length = length(input, 1 << (marker - 0xc4))?;
+            Ok(bytes(input.take(length)?))
+        }
+        0xc7..=0xc9 => {
+            let length = length(input, 1 << (marker - 0xc7))?;
+            msgpack_ext(input, length)
+        }
+        0xca => Ok(float(f32::from_bits(input.uint(4)? as u32) as f64)),
+        0xcb => Ok(float(f64::from_bits(input.uint(8)?))),
+        0xcc..=0xcf => Ok(Value::from(input.uint(1 << (marker - 0xcc))?)),
+        0xd0..=0xd3 => {
+            let size = 1 << (marker - 0xd0);
+            let value = input.uint(size)?;
+            // sign-extend from the encoded width
+            let shift = 64 - size * 8;
+            Ok(Value::from(((value << shift) as i64) >> shift))
+        }
+        0xd4..=0xd8 => msgpack_ext(input, 1 << (marker - 0xd4)),
+        0xd9..=0xdb => {
+            let length = length(input, 1 << (marker - 0xd9))?;
+            text(input.take(length)?)
+        }
+        0xdc | 0xdd => {
+            let count = length(input, 2 << (marker - 0xdc))?;
+            msgpack_array(input, count, depth)
+        }
+        0xde | 0xdf => {
+            let count = length(input, 2 << (marker - 0xde))?;
+            msgpack_map(input, count, depth)
+        }
+        0xe0..=0xff => Ok(Value::from(marker as i8)),
+        _ => Err(Failure::Invalid(format!(
+            "Unexpected marker {:#04x}",
+            marker
+        ))),
+    }
+}
+
+/// `invalid` A decode error without the offending line.
+fn invalid(message: String) -> DecodeError {
+    DecodeError {
+        message,
+        line: None,
+    }
+}
+
+/// `json_line` Parse one line, `None` if it is blank.
+fn json_line(line: &[u8]) -> Option<Result<Value, DecodeError>> {
+    let text = String::from_utf8_lossy(line);
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some(serde_json::from_str(text).map_err(|error| DecodeError {
+        message: error.to_string(),
+        line: Some(text.to_string()),
+    }))
+}
+
+/// `decode_one` The first value in `data` with its encoded length, `None` if it is incomplete.
+fn decode_one(format: Format, data: &[u8]) -> Result<Option<(Value, usize)>, String> {
+    let mut input = Input { data, position: 0 };
+    let result = match format {
+        Format::Cbor => cbor(&mut input, 0),
+        Format::MessagePack => msgpack(&mut input, 0),
+        Format::JsonLines => unreachable!("JSON lines are split by the decoder"),
+    };
+    match result {
+        Ok(value) => Ok(Some((value, input.position))),
+        Err(Failure::Incomplete) => Ok(None),
+        Err(Failure::Invalid(message)) => Err(message),
+    }
+}
+
+/// `decode_frame` A payload holding exactly one value.
+pub fn decode_frame(format: Format, data: &[u8]) -> Result<Value, DecodeError> {
+    if let Format::JsonLines = format {
+        return json_line(data).unwrap_or_else(|| Err(invalid("Empty payload".to_string())));
+    }
+    match decode_one(format, data).map_err(invalid)? {
+        Some((value, size)) if size == data.len() => Ok(value),
+        Some((_, size)) => Err(invalid(format!(
+            "{} trailing bytes after the value",
+            data.len() - size
+        ))),
+        None => Err(invalid("Truncated value".to_string())),
+    }
+}
+
+/// `Decoder` Decodes a stream of concatenated values, or of JSON lines, which need no other
+/// framing.
+pub struct Decoder {
+    pub format: Format,
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    pub fn new(format: Format) -> Self {
+        Decoder {
+            format,
+            pending: Vec::new(),
+        }
+    }
+
+    /// `feed` Collect received bytes, returns the values they complete. After invalid binary
+    /// data the pending bytes are dropped, a malformed JSON line only loses that line.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Result<Value, DecodeError>> {
+        self.pending.extend_from_slice(data);
+        let mut values = Vec::new();
+        let mut start = 0;
+        while start < self.pending.len() {
+            if let Format::JsonLines = self.format {
+                match self.pending[start..].iter().position(|byte| *byte == b'\n') {
+                    Some(end) => {
+                        values.extend(json_line(&self.pending[start..start + end]));
+                        start += end + 1;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+            match decode_one(self.format, &self.pending[start..]) {
+                Ok(Some((value, size))) => {
+                    values.push(Ok(value));
+                    start += size;
+                }
+                Ok(None) => break,
+                Err(message) => {
+                    values.push(Err(invalid(message)));
+                    start = self.pending.len();
+                }
+            }
+        }
+        self.pending.drain(..start);
+        if self.pending.len() > MAX_PENDING {
+            values.push(Err(invalid("Value larger than the receive limit".to_string())));
+            self.pending.clear();
+        }
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|index| u8::from_str_radix(&text[index..index + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn decode(format: Format, text: &str) -> Value {
+        match decode_frame(format, &hex(text)) {
+            Ok(value) => value,
+            Err(error) => panic!("{}: {}", text, error.message),
+        }
+    }
+
+    fn error(format: Format, text: &str) -> String {
+        match decode_frame(format, &hex(text)) {
+            Ok(value) => panic!("{} decoded as {}", text, value),
+            Err(error) => error.message,
+        }
+    }
+
+    /// The examples of RFC 8949, appendix A.
+    #[test]
+    fn cbor_examples() {
+        let cbor = |text| decode(Format::Cbor, text);
+        assert_eq!(cbor("00"), json!(0));
+        assert_eq!(cbor("17"), json!(23));
+        assert_eq!(cbor("1818"), json!(24));
+        assert_eq!(cbor("1903e8"), json!(1000));
+        assert_eq!(cbor("1a000f4240"), json!(1000000));
+        assert_eq!(cbor("1bffffffffffffffff"), json!(u64::MAX));
+        assert_eq!(cbor("20"), json!(-1));
+        assert_eq!(cbor("3863"), json!(-100));
+        assert_eq!(cbor("3bffffffffffffffff"), json!(-18446744073709551616.0));
+        assert_eq!(cbor("f90000"), json!(0.0));
+        assert_eq!(cbor("f93c00"), json!(1.0));
+        assert_eq!(cbor("f9c400"), json!(-4.0));
+        assert_eq!(cbor("f97bff"), json!(65504.0));
+        assert_eq!(cbor("f90001"), json!(5.960464477539063e-8));
+        assert_eq!(cbor("f97c00"), Value::Null);
+        assert_eq!(cbor("fa47c35000"), json!(100000.0));
+        assert_eq!(cbor("fb3ff199999999999a"), json!(1.1));
+        assert_eq!(cbor("f4"), json!(false));
+        assert_eq!(cbor("f5"), json!(true));
+        assert_eq!(cbor("f6"), Value::Null);
+        assert_eq!(cbor("f7"), Value::Null);
+        assert_eq!(cbor("f0"), json!(16));
+        assert_eq!(cbor("f8ff"), json!(255));
+        assert_eq!(cbor("4401020304"), json!([1, 2, 3, 4]));
+        assert_eq!(cbor("6449455446"), json!("IETF"));
+        assert_eq!(cbor("62c3bc"), json!("ü"));
+        assert_eq!(cbor("8301820203820405"), json!([1, [2, 3], [4, 5]]));
+        assert_eq!(cbor("a201020304"), json!({"1": 2, "3": 4}));
+        assert_eq!(cbor("a26161016162820203"), json!({"a": 1, "b": [2, 3]}));
+        assert_eq!(
+            cbor("c074323031332d30332d32315432303a30343a30305a"),
+            json!("2013-03-21T20:04:00Z")
+        );
+        assert_eq!(cbor("5f42010243030405ff"), json!([1, 2, 3, 4, 5]));
+        assert_eq!(cbor("7f657374726561646d696e67ff"), json!("streaming"));
+        assert_eq!(cbor("9f018202039f0405ffff"), json!([1, [2, 3], [4, 5]]));
+        assert_eq!(cbor("bf61610161629f0203ffff"), json!({"a": 1, "b": [2, 3]}));
+    }
+
+    #[test]
+    fn cbor_errors() {
+        assert!(error(Format::Cbor, "1c").contains("additional info"));
+        assert!(error(Format::Cbor, "ff").contains("0xff"));
+        assert!(error(Format::Cbor, "5f6161ff").contains("indefinite"));
+        assert!(error(Format::Cbor, "62c328").contains("UTF-8"));
+        assert!(error(Format::Cbor, "5a00100001").contains("too large"));
+        assert_eq!(error(Format::Cbor, "1903"), "Truncated value");
+        assert_eq!(
+            error(Format::Cbor, "0000"),
+            "1 trailing bytes after the value"
+        );
+        assert_eq!(error(Format::Cbor, &"81".repeat(100)), "Nesting too deep");
+    }
+
+    /// Values of the MessagePack specification, one per marker family.
+    #[test]
+    fn msgpack_examples() {
+        let msgpack = |text| decode(Format::MessagePack, text);
+        assert_eq!(msgpack("7f"), json!(127));
+        assert_eq!(msgpack("ff"), json!(-1));
+        assert_eq!(msgpack("e0"), json!(-32));
+        assert_eq!(msgpack("ccff"), json!(255));
+        assert_eq!(msgpack("cd0100"), json!(256));
+        assert_eq!(msgpack("ce00010000"), json!(65536));
+        assert_eq!(msgpack("cfffffffffffffffff"), json!(u64::MAX));
+        assert_eq!(msgpack("d080"), json!(-128));
+        assert_eq!(msgpack("d18000"), json!(-32768));
+        assert_eq!(msgpack("d2ffffffff"), json!(-1));
+        assert_eq!(msgpack("d38000000000000000"), json!(i64::MIN));
+        assert_eq!(msgpack("ca3fc00000"), json!(1.5));
+        assert_eq!(msgpack("cb3ff199999999999a"), json!(1.1));
+        assert_eq!(msgpack("c0"), Value::Null);
+        assert_eq!(msgpack("c2"), json!(false));
+        assert_eq!(msgpack("c3"), json!(true));
+        assert_eq!(msgpack("a3616263"), json!("abc"));
+        assert_eq!(msgpack("d903616263"), json!("abc"));
+        assert_eq!(msgpack("da0003616263"), json!("abc"));
+        assert_eq!(msgpack("c4020102"), json!([1, 2]));
+        assert_eq!(msgpack("920102"), json!([1, 2]));
+        assert_eq!(msgpack("dc00020102"), json!([1, 2]));
+        assert_eq!(msgpack("dd000000020102"), json!([1, 2]));
+        assert_eq!(msgpack("82a16101a162920203"), json!({"a": 1, "b": [2, 3]}));
+        assert_eq!(msgpack("de0001a16101"), json!({"a": 1}));
+        assert_eq!(msgpack("810102"), json!({"1": 2}));
+        assert_eq!(msgpack("d40105"), json!({"type": 1, "data": [5]}));
+        assert_eq!(msgpack("c702ff0102"), json!({"type": -1, "data": [1, 2]}));
+    }
+
+    #[test]
+    fn msgpack_errors() {
+        assert!(error(Format::MessagePack, "c1").contains("0xc1"));
+        assert!(error(Format::MessagePack, "a2c328").contains("UTF-8"));
+        assert!(error(Format::MessagePack, "c600100001").contains("too large"));
+        assert_eq!(error(Format::MessagePack, "cd01"), "Truncated value");
+        assert_eq!(
+            error(Format::MessagePack, &"91".repeat(100)),
+            "Nesting too deep"
+        );
+    }
+
+    #[test]
+    fn stream_split_across_reads() {
+        let mut decoder = Decoder::new(Format::Cbor);
+        assert!(decoder.feed(&hex("8301")).is_empty());
+        let values = decoder.feed(&hex("020318"));
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0].as_ref().ok(), Some(&json!([1, 2, 3])));
+        let values = decoder.feed(&hex("18f5"));
+        let values: Vec<Value> = values
+            .into_iter()
+            .map(|value| value.ok().unwrap())
+            .collect();
+        assert_eq!(values, vec![json!(24), json!(true)]);
+
+        // invalid data drops what is pending, the next value decodes again
+        let mut decoder = Decoder::new(Format::MessagePack);
+        let values = decoder.feed(&hex("c101"));
+        assert!(values.len() == 1 && values[0].is_err());
+        let values = decoder.feed(&hex("a26f6b"));
+        assert_eq!(values[0].as_ref().ok(), Some(&json!("ok")));
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut decoder = Decoder::new(Format::JsonLines);
+        let values = decoder.feed(b"{\"a\":1}\r\n\n{bad\n[1");
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].as_ref().ok(), Some(&json!({"a": 1})));
+        match &values[1] {
+            Err(error) => assert_eq!(error.line.as_deref(), Some("{bad")),
+            Ok(value) => panic!("decoded {}", value),
+        }
+        let values = decoder.feed(b",2]\n");
+        assert_eq!(values[0].as_ref().ok(), Some(&json!([1, 2])));
+        assert!(decode_frame(Format::JsonLines, b" \r\n").is_err());
+        assert_eq!(
+            decode_frame(Format::JsonLines, b"42\r\n").ok(),
+            Some(json!(42))
+        );
+    }
+}
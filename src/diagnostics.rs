@@ -0,0 +1,215 @@
+use serde::Serialize;
+
+/// `PortHolder` A process that currently has the device open.
+pub struct PortHolder {
+    pub pid: u32,
+    pub name: String,
+}
+
+/// `find_port_holder` Look through `/proc/<pid>/fd` for a process that has `path` open.
+#[cfg(target_os = "linux")]
+pub fn find_port_holder(path: &str) -> Option<PortHolder> {
+    use std::fs;
+    let device = fs::canonicalize(path).ok()?;
+    let own_pid = std::process::id();
+    for entry in fs::read_dir("/proc").ok()?.flatten() {
+        let pid = match entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) {
+            Some(pid) if pid != own_pid => pid,
+            _ => continue,
+        };
+        // Processes of other users are not readable without privileges, skip them.
+        let fds = match fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        for fd in fds.flatten() {
+            if fs::read_link(fd.path()).ok().as_ref() == Some(&device) {
+                let name = fs::read_to_string(entry.path().join("comm"))
+                    .map(|name| name.trim().to_string())
+                    .unwrap_or_default();
+                return Some(PortHolder { pid, name });
+            }
+        }
+    }
+    None
+}
+
+/// `find_port_holder` Ask `lsof` which process has `path` open.
+#[cfg(target_os = "macos")]
+pub fn find_port_holder(path: &str) -> Option<PortHolder> {
+    let output = std::process::Command::new("lsof")
+        .args(["-F", "pc", path])
+        .output()
+        .ok()?;
+    let mut pid = None;
+    let mut name = String::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(value) = line.strip_prefix('p') {
+            if pid.is_some() {
+                break;
+            }
+            pid = value.parse::<u32>().ok();
+        } else if let Some(value) = line.strip_prefix('c') {
+            name = value.to_string();
+        }
+    }
+    pid.map(|pid| PortHolder { pid, name })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn find_port_holder(_path: &str) -> Option<PortHolder> {
+    None
+}
+
+/// `DiagnoseReport` Linux checks explaining why a port cannot be opened.
+#[derive(Serialize, Clone, Default)]
+pub struct DiagnoseReport {
+    pub path: String,
+    pub exists: bool,
+    /// Permission bits of the device node, e.g. `crw-rw----`.
+    pub mode: Option<String>,
+    pub owner_group: Option<String>,
+    pub readable: bool,
+    pub writable: bool,
+    pub user_groups: Vec<String>,
+    pub udev_running: bool,
+    pub brltty_running: bool,
+    /// Human readable problems with a suggested fix, empty when nothing was found.
+    pub issues: Vec<String>,
+}
+
+#[cfg(target_os = "linux")]
+fn group_names() -> std::collections::HashMap<u32, String> {
+    std::fs::read_to_string("/etc/group")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let gid = fields.nth(1)?.parse::<u32>().ok()?;
+            Some((gid, name.to_string()))
+        })
+        .collect()
+}
+
+/// `process_ids` The effective uid and all group ids of this process, read from `/proc/self/status`.
+#[cfg(target_os = "linux")]
+fn process_ids() -> (u32, Vec<u32>) {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap_or_default();
+    let field = |name: &str| -> Vec<u32> {
+        status
+            .lines()
+            .find(|line| line.starts_with(name))
+            .map(|line| {
+                line[name.len()..]
+                    .split_whitespace()
+                    .filter_map(|id| id.parse::<u32>().ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    let uid = field("Uid:").get(1).copied().unwrap_or(u32::MAX);
+    let mut gids = field("Groups:");
+    if let Some(gid) = field("Gid:").get(1) {
+        gids.push(*gid);
+    }
+    (uid, gids)
+}
+
+#[cfg(target_os = "linux")]
+fn format_mode(mode: u32) -> String {
+    let kind = if mode & 0o170000 == 0o020000 { 'c' } else { '-' };
+    let mut text = kind.to_string();
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        text.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    text
+}
+
+/// `diagnose` Check device node permissions, group membership, udev and brltty for `path`.
+#[cfg(target_os = "linux")]
+pub fn diagnose(path: &str) -> DiagnoseReport {
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+
+    let mut report = DiagnoseReport {
+        path: path.to_string(),
+        ..Default::default()
+    };
+    let groups = group_names();
+    let (uid, gids) = process_ids();
+    report.user_groups = gids
+        .iter()
+        .filter_map(|gid| groups.get(gid).cloned())
+        .collect();
+    report.udev_running = fs::metadata("/run/udev/control").is_ok();
+    report.brltty_running = fs::read_dir("/proc")
+        .map(|entries| {
+            entries.flatten().any(|entry| {
+                fs::read_to_string(entry.path().join("comm"))
+                    .map(|name| name.trim() == "brltty")
+                    .unwrap_or(false)
+            })
+        })
+        .unwrap_or(false);
+
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            let mode = metadata.mode();
+            report.exists = true;
+            report.mode = Some(format_mode(mode));
+            report.owner_group = groups.get(&metadata.gid()).cloned();
+            let shift = if uid == 0 || uid == metadata.uid() {
+                6
+            } else if gids.contains(&metadata.gid()) {
+                3
+            } else {
+                0
+            };
+            report.readable = uid == 0 || (mode >> shift) & 0o4 != 0;
+            report.writable = uid == 0 || (mode >> shift) & 0o2 != 0;
+            if !report.readable || !report.writable {
+                match &report.owner_group {
+                    Some(group) if !gids.contains(&metadata.gid()) => report.issues.push(format!(
+                        "The user is not in the `{}` group that owns {}. Run `sudo usermod -aG {} $USER` and log in again.",
+                        group, path, group
+                    )),
+                    _ => report.issues.push(format!(
+                        "{} is not readable and writable by this user. Check its udev rule or permissions.",
+                        path
+                    )),
+                }
+            }
+        }
+        Err(_) => {
+            report.issues.push(format!(
+                "{} does not exist. Check that the device is plugged in and its driver is loaded.",
+                path
+            ));
+        }
+    }
+    if !report.udev_running {
+        report.issues.push(
+            "udev is not running, device nodes and their permissions may not be set up.".to_string(),
+        );
+    }
+    if report.brltty_running {
+        report.issues.push(
+            "brltty is running and may claim USB serial adapters. Remove it or disable its udev rules."
+                .to_string(),
+        );
+    }
+    report
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn diagnose(path: &str) -> DiagnoseReport {
+    DiagnoseReport {
+        path: path.to_string(),
+        issues: vec!["Permission diagnostics are only available on Linux.".to_string()],
+        ..Default::default()
+    }
+}
@@ -0,0 +1,90 @@
+//! DMX512 output on RS-485 adapters: a 512 channel frame sent continuously from a dedicated
+//! thread, each packet preceded by a break and the mark after break.
+
+use serialport::{DataBits, Parity, SerialPort, StopBits};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+pub const CHANNELS: usize = 512;
+pub const BAUD_RATE: u32 = 250_000;
+/// At least 92 µs, the sleep of the thread usually makes it longer.
+const BREAK: Duration = Duration::from_micros(120);
+/// At least 12 µs, waited by spinning since it must stay short.
+const MARK_AFTER_BREAK: Duration = Duration::from_micros(16);
+/// Highest refresh rate, a full packet takes 22.7 ms on the wire.
+pub const MAX_REFRESH: u32 = 44;
+
+/// `Dmx` The frame the output thread sends, stops the thread and waits for it when dropped, so
+/// the port is free once the handle is gone.
+pub struct Dmx {
+    pub frame: Arc<Mutex<[u8; CHANNELS]>>,
+    pub stop: Sender<()>,
+    pub thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Dmx {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// `configure` Switch the port to 250 kbaud 8N2.
+pub fn configure(port: &mut dyn SerialPort) -> serialport::Result<()> {
+    port.set_baud_rate(BAUD_RATE)?;
+    port.set_data_bits(DataBits::Eight)?;
+    port.set_parity(Parity::None)?;
+    port.set_stop_bits(StopBits::Two)
+}
+
+/// `send_packet` One break, mark after break, the start code 0 and the channels.
+fn send_packet(port: &mut dyn SerialPort, frame: &[u8; CHANNELS]) -> serialport::Result<()> {
+    port.set_break()?;
+    std::thread::sleep(BREAK);
+    port.clear_break()?;
+    let mark_end = Instant::now() + MARK_AFTER_BREAK;
+    while Instant::now() < mark_end {
+        std::hint::spin_loop();
+    }
+    let mut packet = Vec::with_capacity(CHANNELS + 1);
+    packet.push(0);
+    packet.extend_from_slice(frame);
+    port.write_all(&packet)?;
+    // the next break must not cut off the end of the packet
+    port.flush()?;
+    Ok(())
+}
+
+/// `run` Send the frame `refresh` times per second until `stop` fires or is dropped.
+pub fn run(
+    mut port: Box<dyn SerialPort>,
+    frame: Arc<Mutex<[u8; CHANNELS]>>,
+    refresh: u32,
+    stop: Receiver<()>,
+) {
+    let interval = Duration::from_secs(1) / refresh.clamp(1, MAX_REFRESH);
+    let mut deadline = Instant::now();
+    loop {
+        let current = match frame.lock() {
+            Ok(frame) => *frame,
+            Err(_) => break,
+        };
+        if let Err(error) = send_packet(port.as_mut(), &current) {
+            warn!("DMX packet failed: {}", error);
+        }
+        deadline += interval;
+        let now = Instant::now();
+        if deadline < now {
+            deadline = now;
+        }
+        match stop.recv_timeout(deadline - now) {
+            Err(RecvTimeoutError::Timeout) => {}
+            _ => break,
+        }
+    }
+    info!("DMX output stopped");
+}
@@ -0,0 +1,328 @@
+//! Scripted devices for end-to-end tests without hardware, opened with paths like
+//! `emu://<name>` once a script was defined under that name. The device answers what is
+//! written to it according to its rules and sends periodic messages on its own.
+
+use crate::transport::{LineSettings, Transport, TransportPort};
+use crate::watcher::Matcher;
+use regex::bytes::Regex;
+use serde::Deserialize;
+use serialport::{ClearBuffer, SerialPort};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+pub const PREFIX: &str = "emu://";
+
+/// Written bytes kept for the rules, so a pattern may span several writes.
+const WINDOW: usize = 4096;
+/// Bytes the device holds for the reader, like the receive buffer of a driver.
+const CAPACITY: usize = 64 * 1024;
+
+/// `Rule` An answer to written data containing `pattern` (bytes) or matching `regex`.
+#[derive(Deserialize, Clone)]
+pub struct Rule {
+    pub pattern: Option<Vec<u8>>,
+    pub regex: Option<String>,
+    /// Sent back, with `$1` or `${name}` replaced by the groups of `regex`.
+    pub response: Vec<u8>,
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+/// `Periodic` A message the device sends every `interval_ms` on its own.
+#[derive(Deserialize, Clone)]
+pub struct Periodic {
+    pub data: Vec<u8>,
+    pub interval_ms: u64,
+}
+
+/// `Script` The behaviour of an emulated device, given to `define_emulator`.
+#[derive(Deserialize, Clone, Default)]
+pub struct Script {
+    /// Tried in order, the first that matches answers.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    #[serde(default)]
+    pub periodic: Vec<Periodic>,
+    /// Sent once the port is opened, e.g. a boot banner.
+    #[serde(default)]
+    pub greeting: Vec<u8>,
+    /// Send every written byte back before answering.
+    #[serde(default)]
+    pub echo: bool,
+}
+
+struct CompiledRule {
+    matcher: Matcher,
+    response: Vec<u8>,
+    delay: Duration,
+}
+
+impl Script {
+    /// `validate` Compile the patterns, failing on the first rule without a valid one.
+    pub fn validate(&self) -> Result<(), String> {
+        self.compile().map(|_| ())
+    }
+
+    fn compile(&self) -> Result<Vec<CompiledRule>, String> {
+        self.rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                let matcher = match (&rule.pattern, &rule.regex) {
+                    (_, Some(regex)) => Regex::new(regex)
+                        .map(Matcher::Regex)
+                        .map_err(|error| format!("Invalid regex of rule {}: {}", index, error))?,
+                    (Some(pattern), None) if !pattern.is_empty() => {
+                        Matcher::Bytes(pattern.clone())
+                    }
+                    _ => return Err(format!("Rule {} needs a pattern or a regex", index)),
+                };
+                Ok(CompiledRule {
+                    matcher,
+                    response: rule.response.clone(),
+                    delay: Duration::from_millis(rule.delay_ms),
+                })
+            })
+            .collect()
+    }
+}
+
+/// `Device` The state of an emulated device, shared by a port and its clones.
+struct Device {
+    rules: Vec<CompiledRule>,
+    /// Periodic messages with the time each is due next.
+    periodic: Vec<(Vec<u8>, Duration, Instant)>,
+    echo: bool,
+    /// Written bytes no rule has matched yet.
+    written: Vec<u8>,
+    /// Answers waiting for their delay, in the order they were scheduled.
+    scheduled: Vec<(Instant, Vec<u8>)>,
+    /// Bytes ready for the reader.
+    output: VecDeque<u8>,
+    rts: bool,
+    dtr: bool,
+}
+
+impl Device {
+    fn push(&mut self, data: &[u8]) {
+        self.output.extend(data);
+        let excess = self.output.len().saturating_sub(CAPACITY);
+        self.output.drain(..excess);
+    }
+
+    /// `release` Move what is due by `now` to the output, the time the next answer is due.
+    fn release(&mut self, now: Instant) -> Option<Instant> {
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.scheduled)
+            .into_iter()
+            .partition(|(at, _)| *at <= now);
+        self.scheduled = waiting;
+        for (_, data) in due {
+            self.push(&data);
+        }
+        let mut sent = Vec::new();
+        for (data, interval, next) in self.periodic.iter_mut() {
+            if *next <= now {
+                sent.push(data.clone());
+                // a reader that was away gets one message, not all it missed
+                *next = (*next + *interval).max(now);
+            }
+        }
+        for data in sent {
+            self.push(&data);
+        }
+        let scheduled = self.scheduled.iter().map(|(at, _)| *at);
+        let periodic = self.periodic.iter().map(|(_, _, next)| *next);
+        scheduled.chain(periodic).min()
+    }
+
+    /// `receive` Take written bytes and schedule the answers of the rules they match.
+    fn receive(&mut self, data: &[u8], now: Instant) {
+        if self.echo {
+            self.push(data);
+        }
+        self.written.extend_from_slice(data);
+        loop {
+            let found = self.rules.iter().find_map(|rule| {
+                rule.matcher
+                    .find(&self.written)
+                    .map(|(start, end)| (rule, start, end))
+            });
+            let (rule, start, end) = match found {
+                Some(found) => found,
+                None => break,
+            };
+            let mut response = Vec::new();
+            match &rule.matcher {
+                Matcher::Regex(regex) => match regex.captures(&self.written[start..]) {
+                    Some(captures) => captures.expand(&rule.response, &mut response),
+                    None => response.extend_from_slice(&rule.response),
+                },
+                Matcher::Bytes(_) => response.extend_from_slice(&rule.response),
+            }
+            self.scheduled.push((now + rule.delay, response));
+            self.written.drain(..end);
+        }
+        let excess = self.written.len().saturating_sub(WINDOW);
+        self.written.drain(..excess);
+    }
+}
+
+struct Shared {
+    device: Mutex<Device>,
+    /// Signalled when written data may have produced an answer.
+    changed: Condvar,
+}
+
+/// `Emulator` One end of an emulated device.
+struct Emulator {
+    shared: Arc<Shared>,
+}
+
+fn poisoned() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "Emulator lock poisoned")
+}
+
+impl Transport for Emulator {
+    fn kind(&self) -> &'static str {
+        "emulated"
+    }
+
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let deadline = Instant::now() + timeout;
+        let mut device = self.shared.device.lock().map_err(|_| poisoned())?;
+        loop {
+            let now = Instant::now();
+            let next = device.release(now);
+            if !device.output.is_empty() {
+                let size = buf.len().min(device.output.len());
+                for (target, byte) in buf.iter_mut().zip(device.output.drain(..size)) {
+                    *target = byte;
+                }
+                return Ok(size);
+            }
+            if now >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "Operation timed out"));
+            }
+            let until = next.map_or(deadline, |next| next.min(deadline));
+            device = self
+                .shared
+                .changed
+                .wait_timeout(device, until.saturating_duration_since(now))
+                .map_err(|_| poisoned())?
+                .0;
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut device = self.shared.device.lock().map_err(|_| poisoned())?;
+        device.receive(buf, Instant::now());
+        self.shared.changed.notify_all();
+        Ok(buf.len())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn Transport>> {
+        Ok(Box::new(Emulator {
+            shared: self.shared.clone(),
+        }))
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self
+            .shared
+            .device
+            .lock()
+            .map(|mut device| {
+                device.release(Instant::now());
+                device.output.len() as u32
+            })
+            .unwrap_or(0))
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        if let Ok(mut device) = self.shared.device.lock() {
+            if let ClearBuffer::Input | ClearBuffer::All = buffer_to_clear {
+                device.output.clear();
+            }
+        }
+        Ok(())
+    }
+
+    // the control lines are looped back, RTS to CTS and DTR to DSR and CD
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        if let Ok(mut device) = self.shared.device.lock() {
+            device.rts = level;
+        }
+        Ok(())
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        if let Ok(mut device) = self.shared.device.lock() {
+            device.dtr = level;
+        }
+        Ok(())
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Ok(self.shared.device.lock().map_or(false, |device| device.rts))
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Ok(self.shared.device.lock().map_or(false, |device| device.dtr))
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Ok(false)
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Ok(self.shared.device.lock().map_or(false, |device| device.dtr))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        Ok(())
+    }
+}
+
+/// `open` Start the device `script` describes, line settings are accepted and ignored.
+pub fn open(
+    path: &str,
+    script: &Script,
+    settings: LineSettings,
+) -> serialport::Result<Box<dyn SerialPort>> {
+    let rules = script.compile().map_err(|message| {
+        serialport::Error::new(serialport::ErrorKind::InvalidInput, message)
+    })?;
+    let now = Instant::now();
+    let mut device = Device {
+        rules,
+        periodic: script
+            .periodic
+            .iter()
+            .filter(|periodic| periodic.interval_ms > 0)
+            .map(|periodic| {
+                let interval = Duration::from_millis(periodic.interval_ms);
+                (periodic.data.clone(), interval, now + interval)
+            })
+            .collect(),
+        echo: script.echo,
+        written: Vec::new(),
+        scheduled: Vec::new(),
+        output: VecDeque::new(),
+        rts: false,
+        dtr: false,
+    };
+    device.push(&script.greeting);
+    let emulator = Emulator {
+        shared: Arc::new(Shared {
+            device: Mutex::new(device),
+            changed: Condvar::new(),
+        }),
+    };
+    TransportPort::open(path, Box::new(emulator), settings)
+}
@@ -0,0 +1,320 @@
+//! Encrypted link layer for firmware that implements the same framing: with a pre-shared AES key
+//! set, every write is sent as authenticated AES-GCM frames and only data of frames that
+//! authenticate is read, so neither direction can be sniffed or forged on the wire.
+//!
+//! A frame is the magic `A5 5A`, the ciphertext length as u16 little endian, a 12-byte nonce,
+//! the ciphertext and the 16-byte tag, with the first four bytes as associated data. The nonce
+//! is a random 8-byte prefix of the sender and a u32 big endian counter starting at 1; a receiver
+//! accepts only rising counters of the newest prefix of the other side, and never a prefix it has
+//! seen replaced, so an earlier session cannot be replayed either.
+//!
+//! The other side switches to a new prefix only with the first frame of its session, counter 1,
+//! so a replayed frame from the middle of an old session cannot take over the link. Without a
+//! handshake the protection has limits: until the first frame of the other side arrives any
+//! prefix is taken, the first frame of an old session whose prefix was never seen or was
+//! forgotten again (only the newest 64 are kept) still replaces the current session, and when
+//! the first frame of a new session is lost, its further frames are dropped until the other side
+//! starts over once more.
+
+use crate::aesgcm::{AesGcm, NONCE_SIZE, TAG_SIZE};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+const MAGIC: [u8; 2] = [0xa5, 0x5a];
+const HEADER_SIZE: usize = 4;
+/// Largest ciphertext of a frame, longer writes are split.
+pub const MAX_PAYLOAD: usize = 1024;
+const PREFIX_SIZE: usize = 8;
+/// Retired prefixes remembered, the oldest is forgotten first.
+const MAX_RETIRED: usize = 64;
+
+/// `EncryptionStats` What the link sent and received since the key was set.
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct EncryptionStats {
+    pub sent_frames: u64,
+    pub received_frames: u64,
+    /// Frames whose tag did not match, sent with another key or corrupted on the wire.
+    pub rejected_frames: u64,
+    /// Authentic frames received again, or the own frames reflected back.
+    pub replayed_frames: u64,
+    /// Bytes skipped while looking for the next frame.
+    pub skipped_bytes: u64,
+}
+
+/// `Link` The key and nonces of an encrypted port and the data received but not yet read.
+pub struct Link {
+    cipher: AesGcm,
+    prefix: [u8; PREFIX_SIZE],
+    counter: u32,
+    /// Newest nonce accepted from the other side.
+    received: Option<([u8; PREFIX_SIZE], u32)>,
+    /// Prefixes used before, the own ones and those the other side replaced, newest last; their
+    /// frames are replays.
+    retired: VecDeque<[u8; PREFIX_SIZE]>,
+    /// Raw bytes of a frame not received completely.
+    raw: Vec<u8>,
+    /// Decrypted data the reads did not take yet.
+    plaintext: Vec<u8>,
+    stats: EncryptionStats,
+}
+
+/// `SharedLink` Set while the port is encrypted, shared by the port and its clones.
+pub type SharedLink = Arc<Mutex<Option<Link>>>;
+
+fn random_prefix() -> Result<[u8; PREFIX_SIZE], String> {
+    let mut prefix = [0u8; PREFIX_SIZE];
+    getrandom::getrandom(&mut prefix)
+        .map_err(|error| format!("No random numbers for the nonces: {}", error))?;
+    Ok(prefix)
+}
+
+impl Link {
+    /// `new` The link of a 16, 24 or 32 byte key, for AES-128, AES-192 or AES-256.
+    pub fn new(key: &[u8]) -> Result<Link, String> {
+        Ok(Link {
+            cipher: AesGcm::new(key)?,
+            prefix: random_prefix()?,
+            counter: 0,
+            received: None,
+            retired: VecDeque::new(),
+            raw: Vec::new(),
+            plaintext: Vec::new(),
+            stats: EncryptionStats::default(),
+        })
+    }
+
+    pub fn stats(&self) -> EncryptionStats {
+        self.stats
+    }
+
+    /// `pending` Decrypted bytes waiting for a read.
+    pub fn pending(&self) -> usize {
+        self.plaintext.len()
+    }
+
+    fn retire(&mut self, prefix: [u8; PREFIX_SIZE]) {
+        if self.retired.len() == MAX_RETIRED {
+            self.retired.pop_front();
+        }
+        self.retired.push_back(prefix);
+    }
+
+    /// `seal` The frames that carry `data`.
+    pub fn seal(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut frames = Vec::with_capacity(data.len() + HEADER_SIZE + NONCE_SIZE + TAG_SIZE);
+        for chunk in data.chunks(MAX_PAYLOAD) {
+            if self.counter == u32::MAX {
+                // a fresh prefix instead of reusing a nonce
+                self.retire(self.prefix);
+                self.prefix = random_prefix()?;
+                self.counter = 0;
+            }
+            self.counter += 1;
+            let mut nonce = [0u8; NONCE_SIZE];
+            nonce[..PREFIX_SIZE].copy_from_slice(&self.prefix);
+            nonce[PREFIX_SIZE..].copy_from_slice(&self.counter.to_be_bytes());
+            let mut header = [0u8; HEADER_SIZE];
+            header[..2].copy_from_slice(&MAGIC);
+            header[2..].copy_from_slice(&(chunk.len() as u16).to_le_bytes());
+            let mut ciphertext = chunk.to_vec();
+            let tag = self.cipher.seal(&nonce, &header, &mut ciphertext)?;
+            frames.extend_from_slice(&header);
+            frames.extend_from_slice(&nonce);
+            frames.extend_from_slice(&ciphertext);
+            frames.extend_from_slice(&tag);
+            self.stats.sent_frames += 1;
+        }
+        Ok(frames)
+    }
+
+    /// `receive` Add raw bytes from the wire, decrypting the frames they complete.
+    pub fn receive(&mut self, data: &[u8]) {
+        self.raw.extend_from_slice(data);
+        let mut start = 0;
+        while let Some(size) = self.next_frame(start) {
+            start += size;
+        }
+        self.raw.drain(..start);
+    }
+
+    /// `next_frame` Decrypt the frame at or after `start`, returns the bytes consumed or `None`
+    /// until more is received.
+    fn next_frame(&mut self, start: usize) -> Option<usize> {
+        let raw = &self.raw[start..];
+        let found = match raw.windows(2).position(|window| window == MAGIC) {
+            Some(found) => found,
+            None => {
+                // a magic may begin with the last byte
+                let skipped = raw.len().saturating_sub(1);
+                self.stats.skipped_bytes += skipped as u64;
+                return if skipped > 0 { Some(skipped) } else { None };
+            }
+        };
+        if found > 0 {
+            self.stats.skipped_bytes += found as u64;
+            return Some(found);
+        }
+        if raw.len() < HEADER_SIZE {
+            return None;
+        }
+        let length = u16::from_le_bytes([raw[2], raw[3]]) as usize;
+        if length > MAX_PAYLOAD {
+            self.stats.skipped_bytes += 1;
+            return Some(1);
+        }
+        let size = HEADER_SIZE + NONCE_SIZE + length + TAG_SIZE;
+        if raw.len() < size {
+            return None;
+        }
+        let mut nonce = [0u8; NONCE_SIZE];
+        nonce.copy_from_slice(&raw[HEADER_SIZE..HEADER_SIZE + NONCE_SIZE]);
+        let body = HEADER_SIZE + NONCE_SIZE;
+        let mut data = raw[body..body + length].to_vec();
+        if !self.cipher.open(
+            &nonce,
+            &raw[..HEADER_SIZE],
+            &mut data,
+            &raw[body + length..size],
+        ) {
+            // no frame after all, or a damaged one, the next may start inside it
+            self.stats.rejected_frames += 1;
+            self.stats.skipped_bytes += 1;
+            debug!("Encrypted frame rejected");
+            return Some(1);
+        }
+        let mut prefix = [0u8; PREFIX_SIZE];
+        prefix.copy_from_slice(&nonce[..PREFIX_SIZE]);
+        let mut counter = [0u8; 4];
+        counter.copy_from_slice(&nonce[PREFIX_SIZE..]);
+        let counter = u32::from_be_bytes(counter);
+        let replayed = prefix == self.prefix
+            || self.retired.contains(&prefix)
+            || match self.received {
+                Some((last, newest)) if last == prefix => counter <= newest,
+                // only the first frame of a session starts it
+                Some(_) => counter != 1,
+                None => false,
+            };
+        if replayed {
+            self.stats.replayed_frames += 1;
+            debug!("Replayed encrypted frame dropped");
+            return Some(size);
+        }
+        if let Some((last, _)) = self.received {
+            if last != prefix {
+                // the other side started over, its earlier frames are no longer accepted
+                self.retire(last);
+            }
+        }
+        self.received = Some((prefix, counter));
+        self.plaintext.extend_from_slice(&data);
+        self.stats.received_frames += 1;
+        Some(size)
+    }
+
+    /// `take` Move decrypted data into `buf`, returns its size.
+    pub fn take(&mut self, buf: &mut [u8]) -> usize {
+        let size = buf.len().min(self.plaintext.len());
+        buf[..size].copy_from_slice(&self.plaintext[..size]);
+        self.plaintext.drain(..size);
+        size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 16] = [0x42; 16];
+
+    fn read(link: &mut Link) -> Vec<u8> {
+        let mut buf = [0u8; 64];
+        let size = link.take(&mut buf);
+        buf[..size].to_vec()
+    }
+
+    #[test]
+    fn frames_are_read_once() {
+        let mut sender = Link::new(&KEY).unwrap();
+        let mut receiver = Link::new(&KEY).unwrap();
+        let first = sender.seal(b"one").unwrap();
+        let second = sender.seal(b"two").unwrap();
+        receiver.receive(&first);
+        receiver.receive(&second);
+        assert_eq!(read(&mut receiver), b"onetwo");
+        receiver.receive(&first);
+        receiver.receive(&second);
+        assert_eq!(read(&mut receiver), b"");
+        assert_eq!(receiver.stats().replayed_frames, 2);
+    }
+
+    #[test]
+    fn own_frames_are_not_read() {
+        let mut link = Link::new(&KEY).unwrap();
+        let frame = link.seal(b"echo").unwrap();
+        link.receive(&frame);
+        assert_eq!(read(&mut link), b"");
+        assert_eq!(link.stats().replayed_frames, 1);
+    }
+
+    #[test]
+    fn earlier_sessions_are_not_replayed() {
+        let mut receiver = Link::new(&KEY).unwrap();
+        let mut before = Link::new(&KEY).unwrap();
+        let delivered = before.seal(b"open").unwrap();
+        // captured on the wire but held back by the attacker
+        let withheld = before.seal(b"unlock").unwrap();
+        receiver.receive(&delivered);
+        // the other side restarts with a new prefix
+        let mut after = Link::new(&KEY).unwrap();
+        receiver.receive(&after.seal(b"close").unwrap());
+        assert_eq!(read(&mut receiver), b"openclose");
+
+        receiver.receive(&withheld);
+        receiver.receive(&delivered);
+        assert_eq!(read(&mut receiver), b"");
+        assert_eq!(receiver.stats().replayed_frames, 2);
+        // the current session goes on
+        receiver.receive(&after.seal(b"again").unwrap());
+        assert_eq!(read(&mut receiver), b"again");
+    }
+
+    #[test]
+    fn old_frames_do_not_take_over_the_session() {
+        let mut receiver = Link::new(&KEY).unwrap();
+        let mut old = Link::new(&KEY).unwrap();
+        old.seal(b"first").unwrap();
+        // recorded from a session the receiver never saw
+        let recorded = old.seal(b"second").unwrap();
+        let mut current = Link::new(&KEY).unwrap();
+        receiver.receive(&current.seal(b"one").unwrap());
+        receiver.receive(&recorded);
+        receiver.receive(&current.seal(b"two").unwrap());
+        assert_eq!(read(&mut receiver), b"onetwo");
+        assert_eq!(receiver.stats().replayed_frames, 1);
+    }
+
+    #[test]
+    fn retired_prefixes_are_bounded() {
+        let mut receiver = Link::new(&KEY).unwrap();
+        for _ in 0..MAX_RETIRED + 10 {
+            let mut session = Link::new(&KEY).unwrap();
+            receiver.receive(&session.seal(b"x").unwrap());
+        }
+        assert_eq!(receiver.retired.len(), MAX_RETIRED);
+        assert_eq!(receiver.stats().received_frames, MAX_RETIRED as u64 + 10);
+    }
+
+    #[test]
+    fn forged_frames_are_rejected() {
+        let mut sender = Link::new(&KEY).unwrap();
+        let mut receiver = Link::new(&KEY).unwrap();
+        let mut frame = sender.seal(b"data").unwrap();
+        let last = frame.len() - 1;
+        frame[last] ^= 1;
+        receiver.receive(&frame);
+        assert_eq!(read(&mut receiver), b"");
+        assert_eq!(receiver.stats().rejected_frames, 1);
+    }
+}
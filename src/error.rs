@@ -1,4 +1,4 @@
-use serde::{Serialize, Serializer};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
 
 #[derive(Serialize, Clone)]
 pub struct InvokeResult {
@@ -18,6 +18,32 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error("{0}")]
     String(String),
+    /// The port does not exist or was unplugged.
+    #[error("Port {path} not found: {message}")]
+    NoDevice { path: String, message: String },
+    /// The user may not access the device node.
+    #[error("Permission denied for port {path}: {message}")]
+    PermissionDenied { path: String, message: String },
+    /// Another program already holds the port.
+    #[error("Port {path} is in use by another program: {message}")]
+    Busy { path: String, message: String },
+    /// The driver rejected the requested line settings.
+    #[error("Invalid settings for port {path}: {message}")]
+    InvalidSettings { path: String, message: String },
+}
+
+impl Error {
+    /// `kind` Stable name of the error variant, sent to the frontend next to the message.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Io(_) => "Io",
+            Error::String(_) => "Other",
+            Error::NoDevice { .. } => "NoDevice",
+            Error::PermissionDenied { .. } => "PermissionDenied",
+            Error::Busy { .. } => "Busy",
+            Error::InvalidSettings { .. } => "InvalidSettings",
+        }
+    }
 }
 
 impl Serialize for Error {
@@ -25,6 +51,9 @@ impl Serialize for Error {
     where
         S: Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
     }
 }
@@ -0,0 +1,102 @@
+//! ESC/POS receipt printers: the real-time `DLE EOT` status transmission and the drawer kick
+//! pulse. Each status byte has bits 1 and 4 set and bits 0 and 7 clear, which tells it apart
+//! from other data the printer may send.
+
+use serde::Serialize;
+use serialport::{ClearBuffer, SerialPort};
+use std::io;
+use std::time::{Duration, Instant};
+
+const DLE: u8 = 0x10;
+const EOT: u8 = 0x04;
+const ESC: u8 = 0x1b;
+/// The fixed bits of a status byte and their values.
+const FIXED_MASK: u8 = 0x93;
+const FIXED_BITS: u8 = 0x12;
+
+/// `PrinterStatus` The four status bytes of `DLE EOT 1` to `4`.
+#[derive(Serialize, Clone, Debug)]
+pub struct PrinterStatus {
+    pub online: bool,
+    /// The level of pin 3 of the drawer kick connector, the drawer switch; whether high means
+    /// open depends on the drawer.
+    pub drawer_signal: bool,
+    pub cover_open: bool,
+    /// Paper is fed with the feed button.
+    pub feeding: bool,
+    /// Printing stopped because the paper ran out.
+    pub paper_end_stop: bool,
+    pub error: bool,
+    pub cutter_error: bool,
+    pub unrecoverable_error: bool,
+    /// An error that clears by itself, e.g. an overheated head.
+    pub recoverable_error: bool,
+    pub paper_near_end: bool,
+    pub paper_end: bool,
+}
+
+/// `query` Send `DLE EOT n` and wait for its status byte.
+fn query(port: &mut dyn SerialPort, n: u8, timeout: Duration) -> Result<u8, String> {
+    port.write_all(&[DLE, EOT, n])
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write: {}", error))?;
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0u8; 16];
+    while Instant::now() < deadline {
+        match port.read(&mut buf) {
+            // anything else is data of an earlier command, e.g. an automatic status
+            Ok(size) => {
+                if let Some(status) = buf[..size]
+                    .iter()
+                    .rev()
+                    .find(|byte| **byte & FIXED_MASK == FIXED_BITS)
+                {
+                    return Ok(*status);
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read: {}", error)),
+        }
+    }
+    Err(format!(
+        "The printer did not answer DLE EOT {}, is it switched on and connected?",
+        n
+    ))
+}
+
+/// `status` Poll the four status bytes, each within `timeout`.
+pub fn status(port: &mut dyn SerialPort, timeout: Duration) -> Result<PrinterStatus, String> {
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    let printer = query(port, 1, timeout)?;
+    let offline = query(port, 2, timeout)?;
+    let error = query(port, 3, timeout)?;
+    let paper = query(port, 4, timeout)?;
+    let bit = |byte: u8, bit: u8| byte & 1 << bit != 0;
+    Ok(PrinterStatus {
+        online: !bit(printer, 3),
+        drawer_signal: bit(printer, 2),
+        cover_open: bit(offline, 2),
+        feeding: bit(offline, 3),
+        paper_end_stop: bit(offline, 5),
+        error: bit(offline, 6),
+        cutter_error: bit(error, 3),
+        unrecoverable_error: bit(error, 5),
+        recoverable_error: bit(error, 6),
+        paper_near_end: bit(paper, 2) || bit(paper, 3),
+        paper_end: bit(paper, 5) || bit(paper, 6),
+    })
+}
+
+/// `drawer_kick` `ESC p` pulsing pin 2 or pin 5 of the drawer kick connector for `pulse`
+/// (2 to 510 ms, in steps of 2 ms), followed by at least 500 ms off.
+pub fn drawer_kick(pin: u8, pulse: Duration) -> Result<Vec<u8>, String> {
+    let m = match pin {
+        2 => 0,
+        5 => 1,
+        _ => return Err(format!("The drawer is kicked on pin 2 or 5, not {}", pin)),
+    };
+    let on = (pulse.as_millis() / 2).clamp(1, 255) as u8;
+    let off = on.max(250);
+    Ok(vec![ESC, b'p', m, on, off])
+}
@@ -0,0 +1,187 @@
+//! Clones of a port for the exchanges that read their answers themselves, polling with a short
+//! timeout. On Windows the timeouts belong to the device and not to the handle, so the clones of
+//! a port count themselves in one `ShortTimeout` and the last one dropped sets the timeout the
+//! port had before the first.
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Read timeout of the clones that exchanges poll for their answers.
+pub const EXCHANGE_TIMEOUT: Duration = Duration::from_millis(5);
+
+/// `ShortTimeout` The clones of a port with `EXCHANGE_TIMEOUT` and the timeout before them.
+#[derive(Default)]
+pub struct ShortTimeout {
+    clones: usize,
+    saved: Duration,
+}
+
+/// `SharedTimeout` One per port, shared by the clones of all its exchanges.
+pub type SharedTimeout = Arc<Mutex<ShortTimeout>>;
+
+fn lock(shared: &SharedTimeout) -> std::sync::MutexGuard<'_, ShortTimeout> {
+    match shared.lock() {
+        Ok(guard) => guard,
+        Err(error) => error.into_inner(),
+    }
+}
+
+/// `ExchangePort` A clone of a port, with `EXCHANGE_TIMEOUT` until it is dropped when it is given
+/// the `SharedTimeout` of the port.
+pub struct ExchangePort {
+    inner: Box<dyn SerialPort>,
+    short_timeout: Option<SharedTimeout>,
+}
+
+impl ExchangePort {
+    pub fn new(
+        mut inner: Box<dyn SerialPort>,
+        short_timeout: Option<SharedTimeout>,
+    ) -> serialport::Result<Self> {
+        if let Some(shared) = &short_timeout {
+            let mut timeout = lock(shared);
+            if timeout.clones == 0 {
+                timeout.saved = inner.timeout();
+            }
+            inner.set_timeout(EXCHANGE_TIMEOUT)?;
+            timeout.clones += 1;
+        }
+        Ok(ExchangePort {
+            inner,
+            short_timeout,
+        })
+    }
+}
+
+impl Drop for ExchangePort {
+    fn drop(&mut self) {
+        if let Some(shared) = &self.short_timeout {
+            let mut timeout = lock(shared);
+            timeout.clones -= 1;
+            if timeout.clones == 0 {
+                let _ = self.inner.set_timeout(timeout.saved);
+            }
+        }
+    }
+}
+
+impl io::Read for ExchangePort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl io::Write for ExchangePort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl SerialPort for ExchangePort {
+    fn name(&self) -> Option<String> {
+        self.inner.name()
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        self.inner.baud_rate()
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        self.inner.data_bits()
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        self.inner.flow_control()
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        self.inner.parity()
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        self.inner.stop_bits()
+    }
+
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.inner.set_baud_rate(baud_rate)
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.inner.set_data_bits(data_bits)
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.inner.set_flow_control(flow_control)
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.inner.set_parity(parity)
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.inner.set_stop_bits(stop_bits)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_request_to_send(level)
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_data_terminal_ready(level)
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.inner.read_clear_to_send()
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.inner.read_data_set_ready()
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.inner.read_ring_indicator()
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.inner.read_carrier_detect()
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_read()
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_write()
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        self.inner.clear(buffer_to_clear)
+    }
+
+    /// A plain clone, the timeout is restored by this port only.
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        self.inner.try_clone()
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        self.inner.set_break()
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.inner.clear_break()
+    }
+}
@@ -0,0 +1,664 @@
+//! The Firmata protocol of the StandardFirmata sketches: MIDI-like messages for pin modes,
+//! digital ports and analog values, and system exclusive messages for everything else. Data
+//! bytes carry 7 bits, wider values are split into 7-bit groups, least significant first.
+
+use serde::{Deserialize, Serialize};
+use serialport::{ClearBuffer, SerialPort};
+use std::io;
+use std::time::{Duration, Instant};
+
+const START_SYSEX: u8 = 0xf0;
+const END_SYSEX: u8 = 0xf7;
+const REPORT_VERSION: u8 = 0xf9;
+const SYSTEM_RESET: u8 = 0xff;
+const SET_PIN_MODE: u8 = 0xf4;
+const SET_DIGITAL_PIN: u8 = 0xf5;
+const CAPABILITY_QUERY: u8 = 0x6b;
+const CAPABILITY_RESPONSE: u8 = 0x6c;
+const PIN_STATE_QUERY: u8 = 0x6d;
+const PIN_STATE_RESPONSE: u8 = 0x6e;
+const EXTENDED_ANALOG: u8 = 0x6f;
+const STRING_DATA: u8 = 0x71;
+const REPORT_FIRMWARE: u8 = 0x79;
+const SAMPLING_INTERVAL: u8 = 0x7a;
+/// Ends the modes of a pin in the capability response.
+const END_OF_PIN: u8 = 0x7f;
+/// Longest system exclusive message kept, longer ones are dropped.
+const MAX_SYSEX: usize = 4096;
+/// The board resets when the port opens and answers once its bootloader is done.
+const QUERY_INTERVAL: Duration = Duration::from_millis(500);
+
+/// `PinMode` The modes of the Firmata 2.x pin mode message.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum PinMode {
+    Input,
+    Output,
+    Analog,
+    Pwm,
+    Servo,
+    Shift,
+    I2c,
+    OneWire,
+    Stepper,
+    Encoder,
+    Serial,
+    InputPullup,
+}
+
+const PIN_MODES: [PinMode; 12] = [
+    PinMode::Input,
+    PinMode::Output,
+    PinMode::Analog,
+    PinMode::Pwm,
+    PinMode::Servo,
+    PinMode::Shift,
+    PinMode::I2c,
+    PinMode::OneWire,
+    PinMode::Stepper,
+    PinMode::Encoder,
+    PinMode::Serial,
+    PinMode::InputPullup,
+];
+
+impl PinMode {
+    fn from_u8(mode: u8) -> Option<Self> {
+        PIN_MODES.get(mode as usize).copied()
+    }
+
+    fn to_u8(self) -> u8 {
+        PIN_MODES.iter().position(|mode| *mode == self).unwrap_or(0) as u8
+    }
+}
+
+/// `PinCapability` A mode a pin supports and its resolution in bits.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PinCapability {
+    pub mode: PinMode,
+    pub resolution: u8,
+}
+
+/// `FirmataMessage` One message in either direction.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum FirmataMessage {
+    /// The value of analog input `pin` (A0 is 0), or the PWM or servo value written to it.
+    Analog {
+        pin: u8,
+        value: u16,
+    },
+    /// The 8 pins of digital port `port`, pin 0 of the port in bit 0.
+    DigitalPort {
+        port: u8,
+        value: u8,
+    },
+    ReportAnalog {
+        pin: u8,
+        enable: bool,
+    },
+    ReportDigital {
+        port: u8,
+        enable: bool,
+    },
+    SetPinMode {
+        pin: u8,
+        mode: PinMode,
+    },
+    SetDigitalPin {
+        pin: u8,
+        value: bool,
+    },
+    /// The protocol version; sent to the board it asks for it.
+    Version {
+        major: u8,
+        minor: u8,
+    },
+    QueryFirmware,
+    Firmware {
+        major: u8,
+        minor: u8,
+        name: String,
+    },
+    QueryCapabilities,
+    /// The modes of every pin, by pin number.
+    Capabilities {
+        pins: Vec<Vec<PinCapability>>,
+    },
+    QueryPinState {
+        pin: u8,
+    },
+    /// The mode of a pin and its state: the output value, or whether the pull-up is on.
+    PinState {
+        pin: u8,
+        mode: Option<PinMode>,
+        state: u32,
+    },
+    /// An analog or servo value for any pin and of any width.
+    ExtendedAnalog {
+        pin: u8,
+        value: u32,
+    },
+    StringData {
+        text: String,
+    },
+    /// How often analog inputs and I2C devices are reported, in milliseconds.
+    SamplingInterval {
+        interval: u16,
+    },
+    /// Any other system exclusive message: its command and 7-bit data bytes.
+    SysEx {
+        command: u8,
+        data: Vec<u8>,
+    },
+    SystemReset,
+}
+
+/// `join_7bit` A value from its 7-bit groups, least significant first.
+fn join_7bit(data: &[u8]) -> u32 {
+    data.iter()
+        .take(5)
+        .rev()
+        .fold(0, |value, byte| value << 7 | (*byte & 0x7f) as u32)
+}
+
+/// `split_7bit` The 7-bit groups of a value, at least `minimum` of them.
+fn split_7bit(mut value: u32, minimum: usize) -> Vec<u8> {
+    let mut groups = Vec::new();
+    while value > 0 || groups.len() < minimum {
+        groups.push((value & 0x7f) as u8);
+        value >>= 7;
+    }
+    groups
+}
+
+/// `text` Characters sent as two 7-bit bytes each.
+fn text(data: &[u8]) -> String {
+    data.chunks_exact(2)
+        .filter_map(|pair| char::from_u32(join_7bit(pair)))
+        .collect()
+}
+
+fn encode_text(text: &str) -> Vec<u8> {
+    text.chars()
+        .flat_map(|char| split_7bit(char as u32 & 0x3fff, 2))
+        .collect()
+}
+
+/// `sysex` The message of a system exclusive command and its data.
+fn sysex(command: u8, data: &[u8]) -> FirmataMessage {
+    match (command, data) {
+        (REPORT_FIRMWARE, []) => FirmataMessage::QueryFirmware,
+        (REPORT_FIRMWARE, [major, minor, name @ ..]) => FirmataMessage::Firmware {
+            major: *major,
+            minor: *minor,
+            name: text(name),
+        },
+        (CAPABILITY_QUERY, []) => FirmataMessage::QueryCapabilities,
+        (CAPABILITY_RESPONSE, _) => {
+            let pins = data
+                .split(|byte| *byte == END_OF_PIN)
+                // the response ends with the marker of the last pin
+                .take(data.iter().filter(|byte| **byte == END_OF_PIN).count())
+                .map(|modes| {
+                    modes
+                        .chunks_exact(2)
+                        .filter_map(|pair| {
+                            Some(PinCapability {
+                                mode: PinMode::from_u8(pair[0])?,
+                                resolution: pair[1],
+                            })
+                        })
+                        .collect()
+                })
+                .collect();
+            FirmataMessage::Capabilities { pins }
+        }
+        (PIN_STATE_QUERY, [pin]) => FirmataMessage::QueryPinState { pin: *pin },
+        (PIN_STATE_RESPONSE, [pin, mode, state @ ..]) => FirmataMessage::PinState {
+            pin: *pin,
+            mode: PinMode::from_u8(*mode),
+            state: join_7bit(state),
+        },
+        (EXTENDED_ANALOG, [pin, value @ ..]) => FirmataMessage::ExtendedAnalog {
+            pin: *pin,
+            value: join_7bit(value),
+        },
+        (STRING_DATA, _) => FirmataMessage::StringData { text: text(data) },
+        (SAMPLING_INTERVAL, [_, _]) => FirmataMessage::SamplingInterval {
+            interval: join_7bit(data) as u16,
+        },
+        _ => FirmataMessage::SysEx {
+            command,
+            data: data.to_vec(),
+        },
+    }
+}
+
+/// `data_length` Data bytes following a status byte.
+fn data_length(status: u8) -> usize {
+    match status {
+        0x90..=0x9f | 0xe0..=0xef | SET_PIN_MODE | SET_DIGITAL_PIN | REPORT_VERSION => 2,
+        0xc0..=0xdf => 1,
+        _ => 0,
+    }
+}
+
+fn message(status: u8, data: &[u8]) -> Option<FirmataMessage> {
+    let first = data.first().copied().unwrap_or(0);
+    let second = data.get(1).copied().unwrap_or(0);
+    Some(match status {
+        0x90..=0x9f => FirmataMessage::DigitalPort {
+            port: status & 0x0f,
+            value: first | (second & 1) << 7,
+        },
+        0xe0..=0xef => FirmataMessage::Analog {
+            pin: status & 0x0f,
+            value: join_7bit(data) as u16,
+        },
+        0xc0..=0xcf => FirmataMessage::ReportAnalog {
+            pin: status & 0x0f,
+            enable: first != 0,
+        },
+        0xd0..=0xdf => FirmataMessage::ReportDigital {
+            port: status & 0x0f,
+            enable: first != 0,
+        },
+        SET_PIN_MODE => FirmataMessage::SetPinMode {
+            pin: first,
+            mode: PinMode::from_u8(second)?,
+        },
+        SET_DIGITAL_PIN => FirmataMessage::SetDigitalPin {
+            pin: first,
+            value: second != 0,
+        },
+        REPORT_VERSION => FirmataMessage::Version {
+            major: first,
+            minor: second,
+        },
+        SYSTEM_RESET => FirmataMessage::SystemReset,
+        _ => return None,
+    })
+}
+
+/// `FirmataParser` The state of the received stream between reads.
+#[derive(Default)]
+pub struct FirmataParser {
+    status: Option<u8>,
+    data: Vec<u8>,
+    sysex: Option<Vec<u8>>,
+}
+
+impl FirmataParser {
+    /// `feed` Collect received bytes, returns the messages they complete.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<FirmataMessage> {
+        let mut messages = Vec::new();
+        for &byte in bytes {
+            match byte {
+                START_SYSEX => {
+                    self.status = None;
+                    self.sysex = Some(Vec::new());
+                }
+                END_SYSEX => {
+                    if let Some(data) = self.sysex.take() {
+                        if let Some((command, data)) = data.split_first() {
+                            messages.push(sysex(*command, data));
+                        }
+                    }
+                }
+                0x80..=0xff => {
+                    // any other status ends an unterminated system exclusive message
+                    self.sysex = None;
+                    self.data.clear();
+                    if data_length(byte) == 0 {
+                        self.status = None;
+                        messages.extend(message(byte, &[]));
+                    } else {
+                        self.status = Some(byte);
+                    }
+                }
+                _ => {
+                    if let Some(sysex) = self.sysex.as_mut() {
+                        if sysex.len() < MAX_SYSEX {
+                            sysex.push(byte);
+                        } else {
+                            self.sysex = None;
+                        }
+                        continue;
+                    }
+                    let status = match self.status {
+                        Some(status) => status,
+                        // data without status, e.g. after connecting mid-message
+                        None => continue,
+                    };
+                    self.data.push(byte);
+                    if self.data.len() == data_length(status) {
+                        messages.extend(message(status, &self.data));
+                        self.data.clear();
+                        // Firmata has no running status
+                        self.status = None;
+                    }
+                }
+            }
+        }
+        messages
+    }
+}
+
+fn check(name: &str, value: u32, maximum: u32) -> Result<u8, String> {
+    if value > maximum {
+        Err(format!("{} {} is above {}", name, value, maximum))
+    } else {
+        Ok(value as u8)
+    }
+}
+
+impl FirmataMessage {
+    /// `encode` The bytes of the message.
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let data = |value: u8| check("Data byte", value as u32, 0x7f);
+        let sysex = |command: u8, data: Vec<u8>| {
+            let mut encoded = vec![START_SYSEX, command];
+            encoded.extend(data);
+            encoded.push(END_SYSEX);
+            encoded
+        };
+        Ok(match self {
+            FirmataMessage::Analog { pin, value } => {
+                let pin = check("Analog pin", *pin as u32, 15)?;
+                check("Analog value", *value as u32, 0x3fff)?;
+                vec![0xe0 | pin, (*value & 0x7f) as u8, (*value >> 7) as u8]
+            }
+            FirmataMessage::DigitalPort { port, value } => {
+                let port = check("Port", *port as u32, 15)?;
+                vec![0x90 | port, value & 0x7f, value >> 7]
+            }
+            FirmataMessage::ReportAnalog { pin, enable } => {
+                vec![0xc0 | check("Analog pin", *pin as u32, 15)?, *enable as u8]
+            }
+            FirmataMessage::ReportDigital { port, enable } => {
+                vec![0xd0 | check("Port", *port as u32, 15)?, *enable as u8]
+            }
+            FirmataMessage::SetPinMode { pin, mode } => {
+                vec![SET_PIN_MODE, data(*pin)?, mode.to_u8()]
+            }
+            FirmataMessage::SetDigitalPin { pin, value } => {
+                vec![SET_DIGITAL_PIN, data(*pin)?, *value as u8]
+            }
+            FirmataMessage::Version { major, minor } => {
+                vec![REPORT_VERSION, data(*major)?, data(*minor)?]
+            }
+            FirmataMessage::QueryFirmware => sysex(REPORT_FIRMWARE, Vec::new()),
+            FirmataMessage::Firmware { major, minor, name } => {
+                let mut bytes = vec![data(*major)?, data(*minor)?];
+                bytes.extend(encode_text(name));
+                sysex(REPORT_FIRMWARE, bytes)
+            }
+            FirmataMessage::QueryCapabilities => sysex(CAPABILITY_QUERY, Vec::new()),
+            FirmataMessage::Capabilities { pins } => {
+                let mut bytes = Vec::new();
+                for modes in pins {
+                    for capability in modes {
+                        bytes.push(capability.mode.to_u8());
+                        bytes.push(data(capability.resolution)?);
+                    }
+                    bytes.push(END_OF_PIN);
+                }
+                sysex(CAPABILITY_RESPONSE, bytes)
+            }
+            FirmataMessage::QueryPinState { pin } => sysex(PIN_STATE_QUERY, vec![data(*pin)?]),
+            FirmataMessage::PinState { pin, mode, state } => {
+                let mut bytes = vec![data(*pin)?, mode.map_or(END_OF_PIN, PinMode::to_u8)];
+                bytes.extend(split_7bit(*state, 1));
+                sysex(PIN_STATE_RESPONSE, bytes)
+            }
+            FirmataMessage::ExtendedAnalog { pin, value } => {
+                let mut bytes = vec![data(*pin)?];
+                bytes.extend(split_7bit(*value, 1));
+                sysex(EXTENDED_ANALOG, bytes)
+            }
+            FirmataMessage::StringData { text } => sysex(STRING_DATA, encode_text(text)),
+            FirmataMessage::SamplingInterval { interval } => {
+                check("Sampling interval", *interval as u32, 0x3fff)?;
+                sysex(SAMPLING_INTERVAL, split_7bit(*interval as u32, 2))
+            }
+            FirmataMessage::SysEx {
+                command,
+                data: bytes,
+            } => {
+                let command = data(*command)?;
+                let mut encoded = Vec::new();
+                for byte in bytes {
+                    encoded.push(data(*byte)?);
+                }
+                sysex(command, encoded)
+            }
+            FirmataMessage::SystemReset => vec![SYSTEM_RESET],
+        })
+    }
+}
+
+/// `FirmataVersion` The answer of the board to the version handshake.
+#[derive(Serialize, Clone, Debug)]
+pub struct FirmataVersion {
+    /// The protocol version, e.g. `2.5`.
+    pub protocol: String,
+    /// The sketch, e.g. `StandardFirmata.ino`.
+    pub firmware: String,
+    pub firmware_version: String,
+}
+
+/// `handshake` Ask the board for its protocol and firmware versions until it answered both,
+/// repeating the queries while it is still in its bootloader.
+pub fn handshake(port: &mut dyn SerialPort, timeout: Duration) -> Result<FirmataVersion, String> {
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    let mut query = vec![REPORT_VERSION];
+    query.extend(FirmataMessage::QueryFirmware.encode()?);
+    let deadline = Instant::now() + timeout;
+    let mut parser = FirmataParser::default();
+    let mut protocol = None;
+    let mut firmware = None;
+    let mut buf = [0u8; 256];
+    let mut queried: Option<Instant> = None;
+    while Instant::now() < deadline {
+        if queried.map_or(true, |queried| queried.elapsed() >= QUERY_INTERVAL) {
+            port.write_all(&query)
+                .and_then(|_| port.flush())
+                .map_err(|error| format!("Failed to write: {}", error))?;
+            queried = Some(Instant::now());
+        }
+        match port.read(&mut buf) {
+            Ok(size) => {
+                for message in parser.feed(&buf[..size]) {
+                    match message {
+                        FirmataMessage::Version { major, minor } => {
+                            protocol = Some(format!("{}.{}", major, minor))
+                        }
+                        FirmataMessage::Firmware { major, minor, name } => {
+                            firmware = Some((name, format!("{}.{}", major, minor)))
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read: {}", error)),
+        }
+        if let (Some(protocol), Some((firmware, firmware_version))) = (&protocol, &firmware) {
+            return Ok(FirmataVersion {
+                protocol: protocol.clone(),
+                firmware: firmware.clone(),
+                firmware_version: firmware_version.clone(),
+            });
+        }
+    }
+    Err("The board did not answer the Firmata version query".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seven_bit_groups() {
+        assert_eq!(join_7bit(&[0x7f, 0x01]), 0xff);
+        assert_eq!(join_7bit(&[0x00, 0x00, 0x01]), 0x4000);
+        // the high bit of a data byte is not part of the value
+        assert_eq!(join_7bit(&[0xff]), 0x7f);
+        assert_eq!(split_7bit(0xff, 1), vec![0x7f, 0x01]);
+        assert_eq!(split_7bit(0, 1), vec![0]);
+        assert_eq!(split_7bit(5, 2), vec![5, 0]);
+        assert_eq!(split_7bit(u32::MAX, 1).len(), 5);
+        assert_eq!(join_7bit(&split_7bit(u32::MAX, 1)), u32::MAX);
+        assert_eq!(text(&encode_text("Firmata")), "Firmata");
+    }
+
+    #[test]
+    fn capability_response_is_split_by_pin() {
+        let mut parser = FirmataParser::default();
+        let mut bytes = vec![START_SYSEX, CAPABILITY_RESPONSE];
+        // pin 0 digital in and out, pin 1 no modes, pin 2 analog with an unknown mode
+        bytes.extend([0x00, 0x01, 0x01, 0x01, END_OF_PIN]);
+        bytes.extend([END_OF_PIN]);
+        bytes.extend([0x02, 0x0a, 0x60, 0x01, END_OF_PIN]);
+        bytes.push(END_SYSEX);
+        let capability = |mode, resolution| PinCapability { mode, resolution };
+        assert_eq!(
+            parser.feed(&bytes),
+            vec![FirmataMessage::Capabilities {
+                pins: vec![
+                    vec![
+                        capability(PinMode::Input, 1),
+                        capability(PinMode::Output, 1)
+                    ],
+                    vec![],
+                    vec![capability(PinMode::Analog, 10)],
+                ]
+            }]
+        );
+    }
+
+    #[test]
+    fn capability_response_without_end_marker() {
+        let mut parser = FirmataParser::default();
+        let bytes = [START_SYSEX, CAPABILITY_RESPONSE, 0x00, 0x01, END_SYSEX];
+        assert_eq!(
+            parser.feed(&bytes),
+            vec![FirmataMessage::Capabilities { pins: vec![] }]
+        );
+    }
+
+    #[test]
+    fn digital_port_carries_the_eighth_pin() {
+        let mut parser = FirmataParser::default();
+        assert_eq!(
+            parser.feed(&[0x91, 0x05, 0x01]),
+            vec![FirmataMessage::DigitalPort {
+                port: 1,
+                value: 0x85
+            }]
+        );
+        // no running status, data without a status byte is skipped
+        assert_eq!(parser.feed(&[0x05, 0x01]), vec![]);
+    }
+
+    #[test]
+    fn encoded_messages_parse_back() {
+        let messages = vec![
+            FirmataMessage::Analog {
+                pin: 3,
+                value: 1023,
+            },
+            FirmataMessage::DigitalPort {
+                port: 2,
+                value: 0xa5,
+            },
+            FirmataMessage::ReportAnalog {
+                pin: 0,
+                enable: true,
+            },
+            FirmataMessage::ReportDigital {
+                port: 1,
+                enable: false,
+            },
+            FirmataMessage::SetPinMode {
+                pin: 13,
+                mode: PinMode::Output,
+            },
+            FirmataMessage::SetDigitalPin {
+                pin: 13,
+                value: true,
+            },
+            FirmataMessage::Version { major: 2, minor: 5 },
+            FirmataMessage::QueryFirmware,
+            FirmataMessage::Firmware {
+                major: 2,
+                minor: 5,
+                name: "StandardFirmata.ino".to_string(),
+            },
+            FirmataMessage::QueryCapabilities,
+            FirmataMessage::Capabilities {
+                pins: vec![
+                    vec![],
+                    vec![PinCapability {
+                        mode: PinMode::Pwm,
+                        resolution: 8,
+                    }],
+                ],
+            },
+            FirmataMessage::QueryPinState { pin: 9 },
+            FirmataMessage::PinState {
+                pin: 9,
+                mode: Some(PinMode::Servo),
+                state: 1500,
+            },
+            FirmataMessage::PinState {
+                pin: 10,
+                mode: None,
+                state: 0,
+            },
+            FirmataMessage::ExtendedAnalog {
+                pin: 20,
+                value: 0x12345,
+            },
+            FirmataMessage::StringData {
+                text: "hello".to_string(),
+            },
+            FirmataMessage::SamplingInterval { interval: 19 },
+            FirmataMessage::SysEx {
+                command: 0x01,
+                data: vec![0x7f, 0x00],
+            },
+            FirmataMessage::SystemReset,
+        ];
+        let mut bytes = Vec::new();
+        for message in &messages {
+            bytes.extend(message.encode().unwrap());
+        }
+        assert_eq!(FirmataParser::default().feed(&bytes), messages);
+    }
+
+    #[test]
+    fn out_of_range_values_are_not_encoded() {
+        assert!(FirmataMessage::Analog { pin: 16, value: 0 }
+            .encode()
+            .is_err());
+        assert!(FirmataMessage::Analog {
+            pin: 0,
+            value: 0x4000
+        }
+        .encode()
+        .is_err());
+        assert!(FirmataMessage::SetDigitalPin {
+            pin: 0x80,
+            value: true
+        }
+        .encode()
+        .is_err());
+        assert!(FirmataMessage::SysEx {
+            command: 0x01,
+            data: vec![0x80]
+        }
+        .encode()
+        .is_err());
+    }
+}
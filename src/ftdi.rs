@@ -0,0 +1,190 @@
+//! FTDI bitbang and MPSSE modes for FT232/FT2232/FT4232 adapters.
+//!
+//! The adapter is addressed by the serial port it normally provides. While a special mode is
+//! active the interface is claimed over USB (detaching the kernel driver on Linux), so the
+//! serial port itself is unavailable until the mode is reset.
+
+use crate::adapter::FTDI_VID;
+use crate::blocking::wait;
+use crate::error::Error;
+use nusb::transfer::{Control, ControlType, Direction, EndpointType, Recipient, RequestBuffer};
+use nusb::{Device, Interface};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+const SIO_RESET: u8 = 0x00;
+const SIO_SET_LATENCY_TIMER: u8 = 0x09;
+const SIO_SET_BITMODE: u8 = 0x0b;
+const SIO_READ_PINS: u8 = 0x0c;
+
+const SIO_RESET_PURGE_RX: u16 = 1;
+const SIO_RESET_PURGE_TX: u16 = 2;
+
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(1);
+/// Every bulk IN packet starts with two modem status bytes.
+const STATUS_BYTES: usize = 2;
+
+/// `BitMode` Modes accepted by `SIO_SET_BITMODE`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum BitMode {
+    Reset = 0x00,
+    AsyncBitbang = 0x01,
+    Mpsse = 0x02,
+    SyncBitbang = 0x04,
+    CbusBitbang = 0x20,
+}
+
+/// `FtdiDevice` An FTDI interface claimed for bitbang or MPSSE use.
+pub struct FtdiDevice {
+    device: Device,
+    interface: Interface,
+    /// `wIndex` of vendor requests, 1 for interface A.
+    index: u16,
+    in_endpoint: u8,
+    out_endpoint: u8,
+    max_packet_size: usize,
+}
+
+/// `open` Find the USB device behind the serial port `path` and claim one of its interfaces.
+pub fn open(path: &str, interface: u8) -> Result<FtdiDevice, Error> {
+    let info = crate::adapter::find_device(path)?;
+    if info.vendor_id() != FTDI_VID {
+        return Err(Error::String(format!("{} is not an FTDI adapter", path)));
+    }
+    let device = info.open()?;
+    let claimed = device.detach_and_claim_interface(interface)?;
+    let mut in_endpoint = None;
+    let mut out_endpoint = None;
+    for alt_setting in claimed.descriptors() {
+        for endpoint in alt_setting.endpoints() {
+            if endpoint.transfer_type() != EndpointType::Bulk {
+                continue;
+            }
+            match endpoint.direction() {
+                Direction::In => {
+                    in_endpoint = Some((endpoint.address(), endpoint.max_packet_size()))
+                }
+                Direction::Out => out_endpoint = Some(endpoint.address()),
+            }
+        }
+    }
+    match (in_endpoint, out_endpoint) {
+        (Some((in_endpoint, max_packet_size)), Some(out_endpoint)) => Ok(FtdiDevice {
+            device,
+            interface: claimed,
+            index: interface as u16 + 1,
+            in_endpoint,
+            out_endpoint,
+            max_packet_size,
+        }),
+        _ => Err(Error::String(format!(
+            "Interface {} of {} has no bulk endpoints",
+            interface, path
+        ))),
+    }
+}
+
+impl FtdiDevice {
+    fn control_out(&self, request: u8, value: u16) -> Result<(), Error> {
+        self.interface
+            .control_out_blocking(
+                Control {
+                    control_type: ControlType::Vendor,
+                    recipient: Recipient::Device,
+                    request,
+                    value,
+                    index: self.index,
+                },
+                &[],
+                CONTROL_TIMEOUT,
+            )
+            .map(|_| ())
+            .map_err(|error| Error::String(format!("FTDI request failed: {}", error)))
+    }
+
+    /// `set_bitmode` Switch the mode, `mask` sets the pin directions (1 = output).
+    pub fn set_bitmode(&self, mode: BitMode, mask: u8) -> Result<(), Error> {
+        self.control_out(SIO_SET_BITMODE, ((mode as u16) << 8) | mask as u16)?;
+        if mode == BitMode::Mpsse {
+            // answers to MPSSE commands are short, don't let them wait for the 16 ms default
+            self.control_out(SIO_SET_LATENCY_TIMER, 1)?;
+            self.control_out(SIO_RESET, SIO_RESET_PURGE_RX)?;
+            self.control_out(SIO_RESET, SIO_RESET_PURGE_TX)?;
+        }
+        Ok(())
+    }
+
+    /// `read_pins` Sample the pins of the interface directly.
+    pub fn read_pins(&self) -> Result<u8, Error> {
+        let mut value = [0u8; 1];
+        self.interface
+            .control_in_blocking(
+                Control {
+                    control_type: ControlType::Vendor,
+                    recipient: Recipient::Device,
+                    request: SIO_READ_PINS,
+                    value: 0,
+                    index: self.index,
+                },
+                &mut value,
+                CONTROL_TIMEOUT,
+            )
+            .map_err(|error| Error::String(format!("FTDI request failed: {}", error)))?;
+        Ok(value[0])
+    }
+
+    /// `write` Send pin values in bitbang mode or command bytes in MPSSE mode.
+    pub fn write(&self, data: &[u8], timeout: Duration) -> Result<(), Error> {
+        let mut transfer = self.interface.bulk_out(self.out_endpoint, data.to_vec());
+        wait(|context| Pin::new(&mut transfer).poll(context), timeout)
+            .ok_or_else(|| Error::String("FTDI write timed out".to_string()))?
+            .status
+            .map_err(|error| Error::String(format!("FTDI write failed: {}", error)))
+    }
+
+    /// `read` Collect `size` bytes, with the modem status of every packet removed.
+    pub fn read(&self, size: usize, timeout: Duration) -> Result<Vec<u8>, Error> {
+        let deadline = Instant::now() + timeout;
+        let mut data = Vec::with_capacity(size);
+        while data.len() < size {
+            let mut transfer = self
+                .interface
+                .bulk_in(self.in_endpoint, RequestBuffer::new(self.max_packet_size));
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let completion = wait(|context| Pin::new(&mut transfer).poll(context), remaining)
+                .ok_or_else(|| {
+                    Error::String(format!(
+                        "FTDI read timed out after {} of {} bytes",
+                        data.len(),
+                        size
+                    ))
+                })?;
+            completion
+                .status
+                .map_err(|error| Error::String(format!("FTDI read failed: {}", error)))?;
+            for packet in completion.data.chunks(self.max_packet_size) {
+                data.extend(packet.iter().skip(STATUS_BYTES));
+            }
+        }
+        data.truncate(size);
+        Ok(data)
+    }
+
+    /// `release` Reset the mode and give the interface back to the serial driver.
+    pub fn release(self) -> Result<(), Error> {
+        let result = self.set_bitmode(BitMode::Reset, 0);
+        let FtdiDevice {
+            device,
+            interface,
+            index,
+            ..
+        } = self;
+        drop(interface);
+        #[cfg(target_os = "linux")]
+        device.attach_kernel_driver(index as u8 - 1)?;
+        #[cfg(not(target_os = "linux"))]
+        let _ = (device, index);
+        result
+    }
+}
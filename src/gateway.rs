@@ -0,0 +1,204 @@
+//! Modbus TCP to RTU gateway: requests from TCP clients are forwarded as RTU transactions on
+//! the serial port, the answers are returned with the transaction identifier of the request.
+
+use crate::schema::{checksum, Algorithm};
+use serialport::{ClearBuffer, SerialPort};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Exception code when the serial device does not answer correctly.
+const TARGET_FAILED: u8 = 0x0b;
+/// MBAP header without the unit identifier, which the RTU frame also carries.
+const MBAP_LENGTH: usize = 6;
+/// How often the listener and the connections look whether the gateway was stopped.
+const STOP_INTERVAL: Duration = Duration::from_millis(200);
+
+fn crc(frame: &[u8]) -> [u8; 2] {
+    (checksum(Algorithm::Crc16Modbus, frame) as u16).to_le_bytes()
+}
+
+/// `response_length` Length of the RTU answer to `function` once `received` tells it, CRC
+/// included.
+fn response_length(received: &[u8]) -> Option<usize> {
+    let function = *received.get(1)?;
+    if function & 0x80 != 0 {
+        return Some(5);
+    }
+    match function {
+        0x01..=0x04 | 0x17 => received.get(2).map(|count| 5 + *count as usize),
+        0x05 | 0x06 | 0x0f | 0x10 => Some(8),
+        _ => None,
+    }
+}
+
+/// `transact` Write an RTU request and read its answer, without CRC.
+fn transact(
+    port: &mut dyn SerialPort,
+    unit: u8,
+    pdu: &[u8],
+    timeout: Duration,
+    silence: Duration,
+) -> Result<Vec<u8>, String> {
+    let mut request = vec![unit];
+    request.extend_from_slice(pdu);
+    request.extend_from_slice(&crc(&request));
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    port.write_all(&request)
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write request: {}", error))?;
+    if unit == 0 {
+        // broadcasts are not answered
+        return Ok(Vec::new());
+    }
+    let deadline = Instant::now() + timeout;
+    let mut last_byte = Instant::now();
+    let mut received = Vec::new();
+    let mut buffer = [0u8; 260];
+    loop {
+        if let Some(length) = response_length(&received) {
+            if received.len() >= length {
+                received.truncate(length);
+                break;
+            }
+        } else if !received.is_empty() && last_byte.elapsed() >= silence {
+            // unknown function code, the frame ends with the silence
+            break;
+        }
+        if Instant::now() >= deadline {
+            return Err("Timeout waiting for the answer".to_string());
+        }
+        match port.read(&mut buffer) {
+            Ok(size) => {
+                received.extend_from_slice(&buffer[..size]);
+                last_byte = Instant::now();
+            }
+            Err(error) if error.kind() == ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read answer: {}", error)),
+        }
+    }
+    if received.len() < 4 {
+        return Err("Answer too short".to_string());
+    }
+    let (frame, check) = received.split_at(received.len() - 2);
+    if crc(frame) != check {
+        return Err("CRC mismatch in answer".to_string());
+    }
+    if frame[0] != unit {
+        return Err(format!("Answer from unit {} instead of {}", frame[0], unit));
+    }
+    Ok(frame[1..].to_vec())
+}
+
+/// `Gateway` What every connection shares.
+struct Gateway {
+    port: Mutex<Box<dyn SerialPort>>,
+    timeout: Duration,
+    silence: Duration,
+    stopped: AtomicBool,
+}
+
+impl Gateway {
+    /// `serve` Answer the requests of one client until it disconnects or the gateway stops.
+    fn serve(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(STOP_INTERVAL))?;
+        let mut pending = Vec::new();
+        let mut buffer = [0u8; 512];
+        while !self.stopped.load(Ordering::Relaxed) {
+            match stream.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(size) => pending.extend_from_slice(&buffer[..size]),
+                Err(error)
+                    if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+                {
+                    continue;
+                }
+                Err(error) => return Err(error),
+            }
+            while pending.len() > MBAP_LENGTH {
+                let length = u16::from_be_bytes([pending[4], pending[5]]) as usize;
+                if pending[2..4] != [0, 0] || !(2..=254).contains(&length) {
+                    return Err(std::io::Error::new(
+                        ErrorKind::InvalidData,
+                        "Invalid MBAP header",
+                    ));
+                }
+                if pending.len() < MBAP_LENGTH + length {
+                    break;
+                }
+                let request: Vec<u8> = pending.drain(..MBAP_LENGTH + length).collect();
+                let (header, unit, pdu) = (&request[..4], request[6], &request[7..]);
+                let answer = match self.port.lock() {
+                    Ok(mut port) => transact(port.as_mut(), unit, pdu, self.timeout, self.silence),
+                    Err(error) => Err(format!("Cannot get port lock: {}", error)),
+                };
+                let pdu = match answer {
+                    Ok(_) if unit == 0 => {
+                        debug!("Forwarded broadcast");
+                        continue;
+                    }
+                    Ok(answer) => answer,
+                    Err(error) => {
+                        warn!("Gateway request to unit {} failed: {}", unit, error);
+                        vec![pdu[0] | 0x80, TARGET_FAILED]
+                    }
+                };
+                let mut response = header.to_vec();
+                response.extend_from_slice(&((pdu.len() + 1) as u16).to_be_bytes());
+                response.push(unit);
+                response.extend_from_slice(&pdu);
+                stream.write_all(&response)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `run` Accept Modbus TCP clients until `stop` is dropped.
+pub fn run(
+    listener: TcpListener,
+    port: Box<dyn SerialPort>,
+    timeout: Duration,
+    stop: Receiver<()>,
+) {
+    let baud_rate = port.baud_rate().unwrap_or(9600).max(1);
+    // 3.5 characters of 11 bits, at least the 1.75 ms fixed for fast rates
+    let silence = Duration::from_micros((38_500_000 / baud_rate as u64).max(1750));
+    let gateway = Arc::new(Gateway {
+        port: Mutex::new(port),
+        timeout,
+        silence,
+        stopped: AtomicBool::new(false),
+    });
+    if let Err(error) = listener.set_nonblocking(true) {
+        error!("Cannot poll the gateway listener: {}", error);
+        return;
+    }
+    while let Err(TryRecvError::Empty) = stop.try_recv() {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                info!("Gateway client {} connected", address);
+                let gateway = gateway.clone();
+                thread::spawn(move || {
+                    let _ = stream.set_nonblocking(false);
+                    if let Err(error) = gateway.serve(stream) {
+                        warn!("Gateway client {}: {}", address, error);
+                    }
+                    info!("Gateway client {} disconnected", address);
+                });
+            }
+            Err(error) if error.kind() == ErrorKind::WouldBlock => thread::sleep(STOP_INTERVAL),
+            Err(error) => {
+                error!("Gateway accept failed: {}", error);
+                thread::sleep(STOP_INTERVAL);
+            }
+        }
+    }
+    gateway.stopped.store(true, Ordering::Relaxed);
+    info!("Gateway stopped");
+}
@@ -0,0 +1,230 @@
+//! HID-class UART bridges that do not show up as COM / tty devices, opened with
+//! `hid://<path>` using the HID device path reported by `available_ports`.
+//!
+//! Supported chips speak the SiLabs CP2110/CP2114 protocol (AN434): data travels in
+//! interrupt reports whose report id is the payload length, the UART is configured
+//! with feature reports.
+
+use crate::transport::{LineSettings, Transport, TransportPort};
+use hidapi::{HidApi, HidDevice};
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const PREFIX: &str = "hid://";
+
+/// Vendor and product ids of chips using the CP2110 report protocol.
+const SUPPORTED: [(u16, u16); 2] = [(0x10c4, 0xea80), (0x10c4, 0xeab0)];
+
+const UART_ENABLE: u8 = 0x41;
+const PURGE_FIFOS: u8 = 0x43;
+const UART_CONFIG: u8 = 0x50;
+const SET_BREAK: u8 = 0x51;
+const STOP_BREAK: u8 = 0x52;
+/// Largest payload of a single data report.
+const MAX_REPORT: usize = 63;
+/// How long one read holds the device lock, so writes are not starved.
+const POLL_SLICE: Duration = Duration::from_millis(10);
+
+/// `HidUart` A HID UART bridge found by `available_ports`.
+pub struct HidUart {
+    pub path: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+}
+
+fn hid_error(error: hidapi::HidError) -> serialport::Error {
+    serialport::Error::new(serialport::ErrorKind::Unknown, error.to_string())
+}
+
+fn to_io_error(error: hidapi::HidError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error.to_string())
+}
+
+/// `available_ports` List connected HID UART bridges.
+pub fn available_ports() -> Vec<HidUart> {
+    let api = match HidApi::new() {
+        Ok(api) => api,
+        Err(error) => {
+            debug!("Cannot enumerate HID devices: {}", error);
+            return Vec::new();
+        }
+    };
+    api.device_list()
+        .filter(|device| SUPPORTED.contains(&(device.vendor_id(), device.product_id())))
+        .map(|device| HidUart {
+            path: format!("{}{}", PREFIX, device.path().to_string_lossy()),
+            vid: device.vendor_id(),
+            pid: device.product_id(),
+            manufacturer: device.manufacturer_string().map(String::from),
+            product: device.product_string().map(String::from),
+            serial_number: device.serial_number().map(String::from),
+        })
+        .collect()
+}
+
+/// `Cp2110` A handle to an opened bridge, clones share the device.
+struct Cp2110 {
+    device: Arc<Mutex<HidDevice>>,
+    /// Bytes of the last input report that did not fit into the caller's buffer.
+    pending: Arc<Mutex<VecDeque<u8>>>,
+}
+
+impl Cp2110 {
+    fn device(&self) -> io::Result<std::sync::MutexGuard<'_, HidDevice>> {
+        self.device
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "HID device lock poisoned"))
+    }
+
+    fn feature(&self, report: &[u8]) -> serialport::Result<()> {
+        self.device()?
+            .send_feature_report(report)
+            .map_err(hid_error)
+    }
+}
+
+impl Transport for Cp2110 {
+    fn kind(&self) -> &'static str {
+        "HID UART"
+    }
+
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let mut pending = self
+            .pending
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "HID buffer lock poisoned"))?;
+        let started = Instant::now();
+        while pending.is_empty() {
+            let mut report = [0u8; MAX_REPORT + 1];
+            let size = self
+                .device()?
+                .read_timeout(&mut report, POLL_SLICE.as_millis() as i32)
+                .map_err(to_io_error)?;
+            if size > 1 {
+                let length = (report[0] as usize).min(size - 1);
+                pending.extend(&report[1..1 + length]);
+            } else if started.elapsed() >= timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "Operation timed out",
+                ));
+            }
+        }
+        let size = buf.len().min(pending.len());
+        for (target, byte) in buf.iter_mut().zip(pending.drain(..size)) {
+            *target = byte;
+        }
+        Ok(size)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let device = self.device()?;
+        for chunk in buf.chunks(MAX_REPORT) {
+            let mut report = Vec::with_capacity(chunk.len() + 1);
+            report.push(chunk.len() as u8);
+            report.extend_from_slice(chunk);
+            device.write(&report).map_err(to_io_error)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn Transport>> {
+        Ok(Box::new(Cp2110 {
+            device: self.device.clone(),
+            pending: self.pending.clone(),
+        }))
+    }
+
+    fn configure(&mut self, settings: &LineSettings) -> serialport::Result<()> {
+        let parity = match settings.parity {
+            Parity::None => 0,
+            Parity::Odd => 1,
+            Parity::Even => 2,
+        };
+        let flow_control = match settings.flow_control {
+            FlowControl::Hardware => 1,
+            // CP2110 has no XON/XOFF support
+            FlowControl::None | FlowControl::Software => 0,
+        };
+        let data_bits = match settings.data_bits {
+            DataBits::Five => 0,
+            DataBits::Six => 1,
+            DataBits::Seven => 2,
+            DataBits::Eight => 3,
+        };
+        let stop_bits = match settings.stop_bits {
+            StopBits::One => 0,
+            StopBits::Two => 1,
+        };
+        let baud_rate = settings.baud_rate.to_be_bytes();
+        self.feature(&[
+            UART_CONFIG,
+            baud_rate[0],
+            baud_rate[1],
+            baud_rate[2],
+            baud_rate[3],
+            parity,
+            flow_control,
+            data_bits,
+            stop_bits,
+        ])
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self
+            .pending
+            .lock()
+            .map(|pending| pending.len() as u32)
+            .unwrap_or(0))
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        let fifos = match buffer_to_clear {
+            ClearBuffer::Output => 0x01,
+            ClearBuffer::Input => 0x02,
+            ClearBuffer::All => 0x03,
+        };
+        if fifos & 0x02 != 0 {
+            if let Ok(mut pending) = self.pending.lock() {
+                pending.clear();
+            }
+        }
+        self.feature(&[PURGE_FIFOS, fifos])
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        // a duration of zero keeps the break until it is stopped
+        self.feature(&[SET_BREAK, 0])
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.feature(&[STOP_BREAK, 0])
+    }
+}
+
+/// `open` Open a HID UART bridge, enable its UART and apply the line settings.
+pub fn open(path: &str, settings: LineSettings) -> serialport::Result<Box<dyn SerialPort>> {
+    let device_path = CString::new(path.trim_start_matches(PREFIX)).map_err(|_| {
+        serialport::Error::new(
+            serialport::ErrorKind::InvalidInput,
+            format!("Invalid HID path: {}", path),
+        )
+    })?;
+    let api = HidApi::new().map_err(hid_error)?;
+    let device = api.open_path(&device_path).map_err(|error| {
+        serialport::Error::new(serialport::ErrorKind::NoDevice, error.to_string())
+    })?;
+    let transport = Cp2110 {
+        device: Arc::new(Mutex::new(device)),
+        pending: Arc::new(Mutex::new(VecDeque::new())),
+    };
+    transport.feature(&[UART_ENABLE, 0x01])?;
+    TransportPort::open(path, Box::new(transport), settings)
+}
@@ -0,0 +1,57 @@
+//! IOKit metadata of USB serial ports on macOS, read from the `ioreg` tool.
+
+use std::collections::HashMap;
+
+/// `UsbInterface` Properties of the USB interface a serial port belongs to.
+#[derive(Clone, Default)]
+pub struct UsbInterface {
+    /// `USB Interface Name`, e.g. `FT232R USB UART` or the name of one function of a
+    /// composite device.
+    pub interface_name: Option<String>,
+    /// `locationID` as hex, encodes the bus and hub chain.
+    pub location_id: Option<String>,
+}
+
+/// `property` Split `| |   "key" = value` into key and value, string values unquoted.
+fn property(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start_matches(|c| c == ' ' || c == '|').trim_end();
+    let (key, value) = line.strip_prefix('"')?.split_once("\" = ")?;
+    Some((key, value.trim_matches('"')))
+}
+
+/// `usb_interfaces` Interface properties keyed by the `cu.*` and `tty.*` nodes below each
+/// USB interface.
+pub fn usb_interfaces() -> HashMap<String, UsbInterface> {
+    let mut interfaces = HashMap::new();
+    let output = match std::process::Command::new("ioreg")
+        .args(["-r", "-l", "-w0", "-c", "IOUSBHostInterface"])
+        .output()
+    {
+        Ok(output) => output,
+        Err(error) => {
+            debug!("Cannot run ioreg: {}", error);
+            return interfaces;
+        }
+    };
+    let mut current = UsbInterface::default();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        // every matched interface starts a new tree at the first column
+        if line.starts_with("+-o") {
+            current = UsbInterface::default();
+            continue;
+        }
+        match property(line) {
+            Some(("USB Interface Name", value)) if current.interface_name.is_none() => {
+                current.interface_name = Some(value.to_string());
+            }
+            Some(("locationID", value)) if current.location_id.is_none() => {
+                current.location_id = value.parse::<u32>().ok().map(|id| format!("{:#010x}", id));
+            }
+            Some(("IOCalloutDevice", value)) | Some(("IODialinDevice", value)) => {
+                interfaces.insert(value.to_string(), current.clone());
+            }
+            _ => {}
+        }
+    }
+    interfaces
+}
@@ -0,0 +1,198 @@
+//! Zebra-compatible label printers: ZPL and EPL jobs, the `~HS` host status and media
+//! calibration. The host status answers with three `STX ... ETX` strings of flags and counters,
+//! it is understood by ZPL firmware whichever language the job is in.
+
+use serde::{Deserialize, Serialize};
+use serialport::{ClearBuffer, SerialPort};
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STX: u8 = 0x02;
+const ETX: u8 = 0x03;
+const STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+/// Pause between status queries while waiting for a job to be printed.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum LabelLanguage {
+    Zpl,
+    Epl,
+}
+
+/// `LabelStatus` The fields of the `~HS` host status.
+#[derive(Serialize, Clone, Debug)]
+pub struct LabelStatus {
+    pub paper_out: bool,
+    pub paused: bool,
+    /// In dots.
+    pub label_length: u32,
+    /// Formats received but not yet printed.
+    pub formats_in_buffer: u32,
+    pub buffer_full: bool,
+    /// A format is being received.
+    pub partial_format: bool,
+    pub corrupt_ram: bool,
+    pub under_temperature: bool,
+    pub over_temperature: bool,
+    pub head_up: bool,
+    pub ribbon_out: bool,
+    /// Thermal transfer, otherwise direct thermal without ribbon.
+    pub thermal_transfer: bool,
+    /// A peeled label waits to be taken.
+    pub label_waiting: bool,
+    /// Labels left in the batch being printed.
+    pub labels_remaining: u32,
+}
+
+impl LabelStatus {
+    /// `problems` What keeps the printer from printing.
+    pub fn problems(&self) -> Vec<&'static str> {
+        [
+            (self.paper_out, "paper out"),
+            (self.ribbon_out && self.thermal_transfer, "ribbon out"),
+            (self.head_up, "head open"),
+            (self.paused, "paused"),
+            (self.buffer_full, "buffer full"),
+            (self.corrupt_ram, "corrupt RAM"),
+            (self.under_temperature, "head too cold"),
+            (self.over_temperature, "head too hot"),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| *flag)
+        .map(|(_, problem)| problem)
+        .collect()
+    }
+
+    /// `printed` Nothing is left to print.
+    fn printed(&self) -> bool {
+        self.formats_in_buffer == 0 && self.labels_remaining == 0 && !self.partial_format
+    }
+}
+
+/// `parse_status` The host status from its three strings, e.g.
+/// `030,0,0,1245,000,0,0,0,000,0,0,0`, `001,0,0,0,1,2,6,0,00000000,1,000` and `1234,0`.
+fn parse_status(answer: &[u8]) -> Result<LabelStatus, String> {
+    let strings: Vec<Vec<String>> = answer
+        .split(|byte| *byte == STX)
+        .skip(1)
+        .filter_map(|string| {
+            let end = string.iter().position(|byte| *byte == ETX)?;
+            let fields = String::from_utf8_lossy(&string[..end])
+                .split(',')
+                .map(|field| field.trim().to_string())
+                .collect();
+            Some(fields)
+        })
+        .collect();
+    let (first, second) = match &strings[..] {
+        [first, second, ..] if first.len() >= 12 && second.len() >= 9 => (first, second),
+        _ => return Err("Unexpected host status".to_string()),
+    };
+    let flag = |fields: &[String], index: usize| fields[index] == "1";
+    let number = |fields: &[String], index: usize| fields[index].parse().unwrap_or(0);
+    Ok(LabelStatus {
+        paper_out: flag(first, 1),
+        paused: flag(first, 2),
+        label_length: number(first, 3),
+        formats_in_buffer: number(first, 4),
+        buffer_full: flag(first, 5),
+        partial_format: flag(first, 7),
+        corrupt_ram: flag(first, 9),
+        under_temperature: flag(first, 10),
+        over_temperature: flag(first, 11),
+        head_up: flag(second, 2),
+        ribbon_out: flag(second, 3),
+        thermal_transfer: flag(second, 4),
+        label_waiting: flag(second, 7),
+        labels_remaining: number(second, 8),
+    })
+}
+
+/// `write` Write `data` and wait until it left the port.
+fn write(port: &mut dyn SerialPort, data: &[u8]) -> Result<(), String> {
+    port.write_all(data)
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write: {}", error))
+}
+
+/// `status` Query and parse the host status.
+pub fn status(port: &mut dyn SerialPort) -> Result<LabelStatus, String> {
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    write(port, b"~HS")?;
+    let deadline = Instant::now() + STATUS_TIMEOUT;
+    let mut answer = Vec::new();
+    let mut buf = [0u8; 256];
+    while answer.iter().filter(|byte| **byte == ETX).count() < 3 {
+        if Instant::now() >= deadline {
+            return Err("The printer did not answer ~HS, is it in ZPL mode?".to_string());
+        }
+        match port.read(&mut buf) {
+            Ok(size) => answer.extend_from_slice(&buf[..size]),
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read: {}", error)),
+        }
+    }
+    parse_status(&answer)
+}
+
+/// `ready` Fail with what keeps the printer from printing, if anything does.
+fn ready(status: &LabelStatus) -> Result<(), String> {
+    match status.problems() {
+        problems if problems.is_empty() => Ok(()),
+        problems => Err(format!("The printer is not ready: {}", problems.join(", "))),
+    }
+}
+
+/// `print` Send a job once the printer is ready and, with `wait`, until it has been printed.
+/// Without `check_status` the job is sent blindly, e.g. to printers that only know EPL, and
+/// nothing is waited for.
+pub fn print(
+    port: &mut dyn SerialPort,
+    job: &str,
+    language: LabelLanguage,
+    check_status: bool,
+    wait: bool,
+    timeout: Duration,
+) -> Result<Option<LabelStatus>, String> {
+    let mut job = job.to_string();
+    match language {
+        LabelLanguage::Zpl if !job.contains("^XA") || !job.contains("^XZ") => {
+            return Err("A ZPL job starts with ^XA and ends with ^XZ".to_string())
+        }
+        // EPL executes a command at its line feed, the last one included
+        LabelLanguage::Epl if !job.ends_with('\n') => job.push('\n'),
+        _ => {}
+    }
+    if !check_status {
+        write(port, job.as_bytes())?;
+        return Ok(None);
+    }
+    ready(&status(port)?)?;
+    write(port, job.as_bytes())?;
+    let deadline = Instant::now() + timeout;
+    loop {
+        let status = status(port)?;
+        ready(&status)?;
+        if !wait || status.printed() {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            return Err(format!(
+                "The job was not printed in time, {} labels remaining",
+                status.labels_remaining
+            ));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// `calibrate` Measure the label and gap lengths by feeding a few labels: `~JC` in ZPL, the
+/// AutoSense `xa` in EPL.
+pub fn calibrate(port: &mut dyn SerialPort, language: LabelLanguage) -> Result<(), String> {
+    match language {
+        LabelLanguage::Zpl => write(port, b"~JC"),
+        LabelLanguage::Epl => write(port, b"\nxa\n"),
+    }
+}
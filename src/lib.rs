@@ -1,38 +1,423 @@
 use tauri::{
-    plugin::{Builder, TauriPlugin},
+    plugin::{Builder as PluginBuilder, TauriPlugin},
     Manager, Runtime,
 };
+pub use autoopen::{AutoOpen, Config};
+pub use codec::{Codec, CodecFactory};
+pub use middleware::{Middleware, MiddlewareFactory};
+pub use profile::Profile;
+pub use selector::Selector;
+pub use traffic::{Traffic, TrafficDirection, TrafficObserver};
+pub use tracing::level_filters::LevelFilter;
 
-use command::{available_ports, cancel_read, close, close_all, force_close, open, read, write, write_binary};
+use command::{
+    ack_read, adapter_info, add_consumer, add_poll, at_command, auto_opened, available_ports,
+    benchmark_latency, benchmark_throughput, blackout, calibrate_label, cancel_operation,
+    cancel_read, capabilities, cellular_connect, close, close_all, compression_stats, consume,
+    define_emulator, delete_sms, diagnose, e22_registers, encryption_stats, enqueue, export_config,
+    fetch, firmata_handshake, force_close, ftdi_mpsse, ftdi_read_pins, ftdi_set_bitmode,
+    ftdi_write_pins, get_history, import_config, kick_cash_drawer, label_status, lin_request,
+    lin_send, line_errors, list_consumers, list_profiles, list_sms, lora_command, lora_join,
+    lora_send, managed_ports, mdb_command, obd_clear_dtcs, obd_init, obd_read_dtcs, obd_read_pid,
+    open, open_profile, open_with_retry, open_with_saved_settings, print_label, printer_status,
+    queue_status, read, read_meter, read_once, read_sms, receive_to_file, remove_consumer,
+    remove_emulator, remove_poll, remove_profile, save_profile, scan_instruments, scpi_command,
+    scpi_errors, scpi_query, scpi_query_binary, send_file, send_firmata, send_midi, send_sms,
+    send_ubx, send_xbee, set_address_filter, set_backpressure, set_break_detection, set_channels,
+    set_compression, set_encryption, set_error_markers, set_half_duplex, set_hidden, set_history,
+    set_idle_timeout, set_local_echo, set_log_level, set_metadata, set_monitor, set_rs485,
+    set_schema, set_sms_notifications, socket_close, socket_open, socket_receive, socket_send,
+    start_capture, start_cmux, start_dmx, start_gateway, start_heartbeat, start_mdb_poll, start_mux,
+    start_pcap, stop_capture, stop_dmx, stop_gateway, stop_heartbeat, stop_mdb_poll, stop_mux,
+    stop_pcap, ubx_configure, unwatch_pattern, usb_reset, validate_settings, watch_pattern, write,
+    write_binary, write_frame, write_reliable, xbee_at_command,
+};
+use operation::Operations;
+use reactor::Reactor;
 use state::SerialportState;
+use store::{SettingsStore, Store};
 use std::{
     collections::HashMap,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU64},
+        Arc, Mutex,
+    },
 };
+#[macro_use]
+mod log;
+mod ack;
+#[cfg(feature = "usb")]
+mod adapter;
+mod address;
+mod aesgcm;
+mod at;
+mod autoopen;
+mod backpressure;
+mod benchmark;
+#[cfg(feature = "ble")]
+mod ble;
+#[cfg(feature = "bluetooth")]
+mod bluetooth;
+#[cfg(feature = "usb")]
+mod blocking;
+mod capabilities;
+mod capture;
+mod cellular;
+mod cmux;
+mod codec;
 mod command;
+mod compression;
+mod consumer;
+#[cfg(windows)]
+mod commstate;
+mod decode;
+mod diagnostics;
+mod dmx;
+#[cfg(feature = "emulator")]
+mod emulator;
+mod encryption;
 mod error;
+mod escpos;
+mod exchange;
+mod firmata;
+#[cfg(feature = "ftdi")]
+mod ftdi;
+mod gateway;
+#[cfg(feature = "hid")]
+mod hid;
+#[cfg(target_os = "macos")]
+mod ioreg;
+mod label;
+mod lin;
+mod linestatus;
+mod lora;
+mod lz4;
+mod mdb;
+mod meter;
+mod middleware;
+mod midi;
+mod mux;
+mod obd;
+mod operation;
+#[cfg(windows)]
+mod overlapped;
+mod pcapng;
+mod poller;
+mod profile;
+mod provision;
+mod power;
+mod queue;
+mod reactor;
+mod reader;
+mod rs485;
+mod schema;
+mod scpi;
+mod selector;
+#[cfg(windows)]
+mod setupapi;
+mod sms;
 mod state;
+mod store;
+#[cfg(unix)]
+mod termios;
 mod test;
+mod traffic;
+mod transfer;
+mod transform;
+mod transport;
+mod ubx;
+#[cfg(feature = "usb")]
+mod usb;
+mod watcher;
+mod xbee;
 
 /// Initializes the plugin.
-pub fn init<R: Runtime>() -> TauriPlugin<R> {
-    Builder::new("serialport")
-        .invoke_handler(tauri::generate_handler![
-            available_ports,
-            cancel_read,
-            close,
-            close_all,
-            force_close,
-            open,
-            read,
-            write,
-            write_binary,
-        ])
-        .setup(move |app_handle| {
-            app_handle.manage(SerialportState {
-                serialports: Arc::new(Mutex::new(HashMap::new())),
-            });
-            Ok(())
-        })
-        .build()
+pub fn init<R: Runtime>() -> TauriPlugin<R, Option<Config>> {
+    Builder::default().build()
+}
+
+/// Builds the plugin with custom options.
+pub struct Builder {
+    log_level: LevelFilter,
+    busy_diagnostics: bool,
+    codecs: HashMap<String, Arc<CodecFactory>>,
+    opaque_event_ids: bool,
+    auto_open: Vec<AutoOpen>,
+    remember_settings: bool,
+    observers: Vec<Arc<TrafficObserver>>,
+    middleware: Vec<Arc<MiddlewareFactory>>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder {
+            log_level: LevelFilter::INFO,
+            busy_diagnostics: false,
+            codecs: HashMap::new(),
+            opaque_event_ids: false,
+            auto_open: Vec::new(),
+            remember_settings: false,
+            observers: Vec::new(),
+            middleware: Vec::new(),
+        }
+    }
 }
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Maximum verbosity of the `tracing` events emitted by the plugin, `INFO` by default.
+    /// Nothing is printed unless the application installs a `tracing` subscriber.
+    pub fn log_level(mut self, level: LevelFilter) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// When opening fails because the port is busy, find the process holding it
+    /// (through `/proc` on Linux and `lsof` on macOS) and name it in the error.
+    pub fn busy_diagnostics(mut self, enabled: bool) -> Self {
+        self.busy_diagnostics = enabled;
+        self
+    }
+
+    /// Name the events of ports opened without an `event_id` `port-<n>`, returned by `open`,
+    /// instead of by their path, which may contain characters Tauri rejects in event names
+    /// (e.g. `\\.\COM10`).
+    pub fn opaque_event_ids(mut self, enabled: bool) -> Self {
+        self.opaque_event_ids = enabled;
+        self
+    }
+
+    /// Register a [`Codec`] under a port path or a protocol name. A port whose path is
+    /// registered is read as frames by default, other ports select the codec by name in `read`.
+    pub fn codec<F>(mut self, name: impl Into<String>, factory: F) -> Self
+    where
+        F: Fn() -> Box<dyn Codec> + Send + Sync + 'static,
+    {
+        self.codecs.insert(name.into(), Arc::new(factory));
+        self
+    }
+
+    /// Open a port when the application starts, before any frontend code runs. Ports declared
+    /// under `plugins > serialport > auto_open` in `tauri.conf.json` are opened after these.
+    pub fn auto_open(mut self, port: AutoOpen) -> Self {
+        self.auto_open.push(port);
+        self
+    }
+
+    /// Save the settings each USB device with a serial number was last opened with, in
+    /// `serialport-settings.json` in the app data directory, for `open_with_saved_settings`.
+    pub fn remember_settings(mut self, enabled: bool) -> Self {
+        self.remember_settings = enabled;
+        self
+    }
+
+    /// Hand every chunk written to or read from any port to `callback`, with the port path,
+    /// direction and timestamp. It is called on the thread that moved the data, before the
+    /// frontend sees it, so it should return quickly. Several callbacks are called in turn.
+    pub fn on_traffic<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Traffic) + Send + Sync + 'static,
+    {
+        self.observers.push(Arc::new(callback));
+        self
+    }
+
+    /// Register [`Middleware`] for the ports `factory` returns one for when they open. Writes
+    /// pass it before they reach the driver, received data before the read emits it. Several
+    /// middleware apply in the order they were registered.
+    pub fn middleware<F>(mut self, factory: F) -> Self
+    where
+        F: Fn(&str) -> Option<Box<dyn Middleware>> + Send + Sync + 'static,
+    {
+        self.middleware.push(Arc::new(factory));
+        self
+    }
+
+    pub fn build<R: Runtime>(self) -> TauriPlugin<R, Option<Config>> {
+        log::set_max_level(self.log_level);
+        PluginBuilder::<R, Option<Config>>::new("serialport")
+            .invoke_handler(tauri::generate_handler![
+                ack_read,
+                adapter_info,
+                add_consumer,
+                add_poll,
+                at_command,
+                auto_opened,
+                available_ports,
+                benchmark_latency,
+                benchmark_throughput,
+                blackout,
+                calibrate_label,
+                cancel_operation,
+                cancel_read,
+                capabilities,
+                cellular_connect,
+                close,
+                close_all,
+                compression_stats,
+                consume,
+                define_emulator,
+                delete_sms,
+                diagnose,
+                e22_registers,
+                encryption_stats,
+                enqueue,
+                export_config,
+                fetch,
+                firmata_handshake,
+                force_close,
+                ftdi_mpsse,
+                ftdi_read_pins,
+                ftdi_set_bitmode,
+                ftdi_write_pins,
+                get_history,
+                import_config,
+                kick_cash_drawer,
+                label_status,
+                lin_request,
+                lin_send,
+                line_errors,
+                list_consumers,
+                list_profiles,
+                list_sms,
+                lora_command,
+                lora_join,
+                lora_send,
+                managed_ports,
+                mdb_command,
+                obd_clear_dtcs,
+                obd_init,
+                obd_read_dtcs,
+                obd_read_pid,
+                open,
+                open_profile,
+                open_with_retry,
+                open_with_saved_settings,
+                print_label,
+                printer_status,
+                queue_status,
+                read,
+                read_meter,
+                read_once,
+                read_sms,
+                receive_to_file,
+                remove_consumer,
+                remove_emulator,
+                remove_poll,
+                remove_profile,
+                save_profile,
+                scan_instruments,
+                scpi_command,
+                scpi_errors,
+                scpi_query,
+                scpi_query_binary,
+                send_file,
+                send_firmata,
+                send_midi,
+                send_sms,
+                send_ubx,
+                send_xbee,
+                set_address_filter,
+                set_backpressure,
+                set_break_detection,
+                set_channels,
+                set_compression,
+                set_encryption,
+                set_error_markers,
+                set_half_duplex,
+                set_hidden,
+                set_history,
+                set_idle_timeout,
+                set_local_echo,
+                set_log_level,
+                set_metadata,
+                set_monitor,
+                set_rs485,
+                set_schema,
+                set_sms_notifications,
+                socket_close,
+                socket_open,
+                socket_receive,
+                socket_send,
+                start_capture,
+                start_cmux,
+                start_dmx,
+                start_gateway,
+                start_heartbeat,
+                start_mdb_poll,
+                start_mux,
+                start_pcap,
+                stop_capture,
+                stop_dmx,
+                stop_gateway,
+                stop_heartbeat,
+                stop_mdb_poll,
+                stop_mux,
+                stop_pcap,
+                ubx_configure,
+                unwatch_pattern,
+                usb_reset,
+                validate_settings,
+                watch_pattern,
+                write,
+                write_binary,
+                write_frame,
+                write_reliable,
+                xbee_at_command,
+            ])
+            .setup_with_config(move |app_handle, config| {
+                let data_dir = app_handle.path_resolver().app_data_dir();
+                let settings_store = match &data_dir {
+                    Some(directory) if self.remember_settings => Some(Arc::new(
+                        SettingsStore::load(Some(directory.join(store::SETTINGS_FILE))),
+                    )),
+                    _ => None,
+                };
+                // without a data directory the profiles last for the session
+                let stored = |name: &str| data_dir.as_ref().map(|directory| directory.join(name));
+                let profiles = Store::load(stored(store::PROFILES_FILE));
+                let provisioned = Store::load(stored(store::AUTO_OPEN_FILE));
+                let mut declared_auto_open = self.auto_open;
+                declared_auto_open.extend(config.unwrap_or_default().auto_open);
+                app_handle.manage(SerialportState {
+                    serialports: Arc::new(Mutex::new(HashMap::new())),
+                    busy_diagnostics: self.busy_diagnostics,
+                    codecs: self.codecs,
+                    #[cfg(feature = "emulator")]
+                    emulators: Arc::new(Mutex::new(HashMap::new())),
+                    #[cfg(feature = "ftdi")]
+                    ftdi: Arc::new(Mutex::new(HashMap::new())),
+                    reactor: Arc::new(Reactor::default()),
+                    operations: Arc::new(Operations::default()),
+                    event_ids: Arc::new(Mutex::new(HashMap::new())),
+                    opaque_event_ids: self.opaque_event_ids,
+                    next_event_id: AtomicU64::new(1),
+                    power_watch: AtomicBool::new(false),
+                    auto_opened: Arc::new(Mutex::new(Vec::new())),
+                    settings_store,
+                    profiles: Arc::new(profiles),
+                    declared_auto_open,
+                    provisioned: Arc::new(provisioned),
+                    observers: Arc::new(self.observers),
+                    middleware: self.middleware,
+                });
+                command::auto_open(app_handle);
+                Ok(())
+            })
+            .on_webview_ready(|window| {
+                // the ports opened at startup are checked after sleeps like those opened later
+                let state = window.state::<SerialportState>();
+                let opened = state
+                    .auto_opened
+                    .lock()
+                    .map_or(false, |auto_opened| !auto_opened.is_empty());
+                if opened {
+                    command::watch_power(&window.app_handle(), &window);
+                }
+            })
+            .build()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,193 @@
+//! LIN master frames over a UART LIN transceiver: the break, sync and protected identifier
+//! header, and the classic and enhanced checksums.
+
+use serde::Deserialize;
+use serialport::{ClearBuffer, SerialPort};
+use std::time::{Duration, Instant};
+
+/// The break must be dominant for at least 13 bit times.
+const BREAK_BITS: u32 = 13;
+const SYNC: u8 = 0x55;
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum ChecksumModel {
+    /// LIN 1.x, over the data bytes only.
+    Classic,
+    /// LIN 2.x, the protected identifier included.
+    Enhanced,
+}
+
+/// `protected_id` The identifier with its parity bits P0 and P1.
+pub fn protected_id(id: u8) -> Result<u8, String> {
+    if id > 0x3f {
+        return Err(format!("LIN identifier {:#x} is above 0x3f", id));
+    }
+    let bit = |n: u8| (id >> n) & 1;
+    let p0 = bit(0) ^ bit(1) ^ bit(2) ^ bit(4);
+    let p1 = !(bit(1) ^ bit(3) ^ bit(4) ^ bit(5)) & 1;
+    Ok(id | p0 << 6 | p1 << 7)
+}
+
+/// `checksum` The inverted sum with carry; diagnostic frames 0x3c and 0x3d always use the
+/// classic model.
+pub fn checksum(model: ChecksumModel, pid: u8, data: &[u8]) -> u8 {
+    let enhanced = model == ChecksumModel::Enhanced && !matches!(pid & 0x3f, 0x3c | 0x3d);
+    let start = if enhanced { pid as u16 } else { 0 };
+    let sum = data.iter().fold(start, |sum, byte| {
+        let sum = sum + *byte as u16;
+        if sum > 0xff {
+            sum - 0xff
+        } else {
+            sum
+        }
+    });
+    !(sum as u8)
+}
+
+/// `default_length` The data length LIN 1.x derives from the identifier.
+pub fn default_length(id: u8) -> usize {
+    match id {
+        0..=0x1f => 2,
+        0x20..=0x2f => 4,
+        _ => 8,
+    }
+}
+
+/// `send_header` The break, its delimiter, the sync byte and the protected identifier.
+pub fn send_header(port: &mut dyn SerialPort, pid: u8) -> serialport::Result<()> {
+    let baud_rate = port.baud_rate()?.max(1);
+    let bit = Duration::from_nanos(1_000_000_000 / baud_rate as u64);
+    port.set_break()?;
+    std::thread::sleep(bit * BREAK_BITS);
+    port.clear_break()?;
+    // the break delimiter, at least one recessive bit
+    let delimiter_end = Instant::now() + bit * 2;
+    while Instant::now() < delimiter_end {
+        std::hint::spin_loop();
+    }
+    port.write_all(&[SYNC, pid])?;
+    port.flush()?;
+    Ok(())
+}
+
+/// `send` Write a master frame: header, data and checksum.
+pub fn send(
+    port: &mut dyn SerialPort,
+    id: u8,
+    data: &[u8],
+    model: ChecksumModel,
+) -> Result<(), String> {
+    if data.is_empty() || data.len() > 8 {
+        return Err("LIN frames carry 1 to 8 data bytes".to_string());
+    }
+    let pid = protected_id(id)?;
+    send_header(port, pid).map_err(|error| format!("Failed to send LIN header: {}", error))?;
+    let mut response = data.to_vec();
+    response.push(checksum(model, pid, data));
+    port.write_all(&response)
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to send LIN data: {}", error))
+}
+
+/// `request` Send a header and read the `length` data bytes a slave answers with. The echo
+/// of the header that single wire transceivers return is skipped.
+pub fn request(
+    port: &mut dyn SerialPort,
+    id: u8,
+    length: usize,
+    model: ChecksumModel,
+    timeout: Duration,
+) -> Result<Vec<u8>, String> {
+    if length == 0 || length > 8 {
+        return Err("LIN frames carry 1 to 8 data bytes".to_string());
+    }
+    let pid = protected_id(id)?;
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    send_header(port, pid).map_err(|error| format!("Failed to send LIN header: {}", error))?;
+    // data and checksum
+    let frame = length + 1;
+    let deadline = Instant::now() + timeout;
+    let mut received = Vec::new();
+    let mut buffer = [0u8; 32];
+    let response = loop {
+        let echo = received
+            .windows(2)
+            .position(|window| window == [SYNC, pid])
+            .map(|position| position + 2);
+        match echo {
+            Some(start) if received.len() >= start + frame => {
+                break received[start..start + frame].to_vec();
+            }
+            _ => {}
+        }
+        if Instant::now() >= deadline {
+            // an adapter without echo
+            if echo.is_none() && received.len() >= frame {
+                break received[received.len() - frame..].to_vec();
+            }
+            return Err(format!("No response to LIN identifier {:#x}", id));
+        }
+        match port.read(&mut buffer) {
+            Ok(size) => received.extend_from_slice(&buffer[..size]),
+            Err(error) if error.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read LIN response: {}", error)),
+        }
+    };
+    let (data, check) = response.split_at(length);
+    let expected = checksum(model, pid, data);
+    if check[0] != expected {
+        return Err(format!(
+            "LIN checksum mismatch: expected {:#04x}, got {:#04x}",
+            expected, check[0]
+        ));
+    }
+    Ok(data.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn protected_ids() {
+        let known = [
+            (0x00, 0x80),
+            (0x01, 0xc1),
+            (0x10, 0x50),
+            (0x3c, 0x3c),
+            (0x3d, 0x7d),
+            (0x3f, 0xbf),
+        ];
+        for (id, pid) in known {
+            assert_eq!(protected_id(id).unwrap(), pid);
+        }
+        assert!(protected_id(0x40).is_err());
+    }
+
+    /// The example of the LIN 2.x specification, with two carries.
+    #[test]
+    fn checksums() {
+        let data = [0x55, 0x93, 0xe5];
+        assert_eq!(checksum(ChecksumModel::Enhanced, 0x4a, &data), 0xe6);
+        assert_eq!(checksum(ChecksumModel::Classic, 0x4a, &data), 0x31);
+        // diagnostic frames stay classic
+        assert_eq!(checksum(ChecksumModel::Enhanced, 0x3c, &data), 0x31);
+        assert_eq!(checksum(ChecksumModel::Enhanced, 0x7d, &data), 0x31);
+        assert_eq!(checksum(ChecksumModel::Classic, 0x80, &[0xff; 8]), 0x00);
+        assert_eq!(checksum(ChecksumModel::Classic, 0x80, &[0x00]), 0xff);
+        // data and checksum sum up to 0xff, so their checksum is 0
+        let pid = protected_id(0x22).unwrap();
+        let mut frame = vec![0x01, 0xfe, 0x80, 0x7f];
+        frame.push(checksum(ChecksumModel::Enhanced, pid, &frame));
+        assert_eq!(checksum(ChecksumModel::Enhanced, pid, &frame), 0x00);
+    }
+
+    #[test]
+    fn default_lengths() {
+        assert_eq!(default_length(0x00), 2);
+        assert_eq!(default_length(0x1f), 2);
+        assert_eq!(default_length(0x20), 4);
+        assert_eq!(default_length(0x30), 8);
+    }
+}
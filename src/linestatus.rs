@@ -0,0 +1,282 @@
+//! Conditions of the line the driver reports: breaks, bytes received with a parity or framing
+//! error, and data lost to overruns. On Unix ports with `termios::mark_errors` a break arrives in the stream as
+//! `FF 00 00`, unlike a received NUL byte, and an erroneous byte as `FF 00 <byte>`. Windows only
+//! flags them with `ClearCommError`, after the data read with them.
+
+use crate::mdb::Unmarker;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Time without overruns after which new ones are reported again.
+const OVERRUN_QUIET: Duration = Duration::from_secs(1);
+
+/// `Marked` A piece of the received stream without the marks of the driver.
+pub enum Marked<'a> {
+    Data(Cow<'a, [u8]>),
+    /// `count` breaks received so far, this one included; Windows reports breaks with `poll`.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Break {
+        count: u64,
+    },
+}
+
+/// `Counters` Errors counted by the driver.
+#[derive(Clone, Copy, Default)]
+pub struct Counters {
+    pub parity: u64,
+    pub framing: u64,
+    pub breaks: u64,
+    pub overrun: u64,
+    pub buffer_overrun: u64,
+}
+
+impl Counters {
+    #[cfg(target_os = "linux")]
+    fn since(&self, base: &Counters) -> Counters {
+        Counters {
+            parity: self.parity.wrapping_sub(base.parity),
+            framing: self.framing.wrapping_sub(base.framing),
+            breaks: self.breaks.wrapping_sub(base.breaks),
+            overrun: self.overrun.wrapping_sub(base.overrun),
+            buffer_overrun: self.buffer_overrun.wrapping_sub(base.buffer_overrun),
+        }
+    }
+}
+
+/// `LineErrors` The error counts of a port since it was opened.
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct LineErrors {
+    pub parity: u64,
+    pub framing: u64,
+    pub breaks: u64,
+    /// Characters the UART received before the previous one was read from it.
+    pub overrun: u64,
+    /// Times the receive buffer of the driver was full.
+    pub buffer_overrun: u64,
+    /// Bytes with a parity or framing error found marked in the stream, while error markers or
+    /// break detection are enabled.
+    pub marked: u64,
+    /// Whether the driver counts the errors, otherwise only `marked` is known.
+    pub driver_counts: bool,
+}
+
+impl LineErrors {
+    /// `since` The errors counted after `before`.
+    pub fn since(&self, before: &LineErrors) -> LineErrors {
+        LineErrors {
+            parity: self.parity.saturating_sub(before.parity),
+            framing: self.framing.saturating_sub(before.framing),
+            breaks: self.breaks.saturating_sub(before.breaks),
+            overrun: self.overrun.saturating_sub(before.overrun),
+            buffer_overrun: self.buffer_overrun.saturating_sub(before.buffer_overrun),
+            marked: self.marked.saturating_sub(before.marked),
+            driver_counts: self.driver_counts,
+        }
+    }
+}
+
+/// `LineStatus` Break detection and error markers of a port, with the state of the received
+/// stream and the error counts.
+#[derive(Default)]
+pub struct LineStatus {
+    unmarker: Unmarker,
+    /// Breaks are reported as events instead of delivered as data.
+    pub detect_breaks: bool,
+    /// Put before every byte received with a parity or framing error.
+    pub marker: Option<Vec<u8>>,
+    /// Breaks reported so far.
+    pub breaks: u64,
+    marked: u64,
+    /// On Linux the driver counters when the port was opened, on Windows the errors
+    /// `ClearCommError` has flagged so far.
+    driver: Option<Counters>,
+    /// Overruns last seen by `poll`, and when they last increased.
+    overruns: u64,
+    last_overrun: Option<Instant>,
+    /// A break `queued` counted, reported by the next `poll`.
+    #[cfg(windows)]
+    pending_break: bool,
+}
+
+/// `Polled` What the read thread learns from the driver between reads.
+#[derive(Default)]
+pub struct Polled {
+    /// The breaks received so far when Windows flagged one and breaks are detected.
+    pub break_count: Option<u64>,
+    /// The error counts when overruns start occurring.
+    pub overruns: Option<LineErrors>,
+}
+
+impl LineStatus {
+    /// `new` The status of a port just opened with `native`.
+    pub fn new(native: Option<isize>) -> Self {
+        let driver = match native {
+            #[cfg(target_os = "linux")]
+            Some(fd) => crate::termios::icount(fd as i32).ok(),
+            #[cfg(windows)]
+            Some(_) => Some(Counters::default()),
+            _ => None,
+        };
+        LineStatus {
+            driver,
+            ..LineStatus::default()
+        }
+    }
+
+    /// `is_marking` Whether the driver has to mark breaks and errors in the stream.
+    pub fn is_marking(&self) -> bool {
+        self.detect_breaks || self.marker.is_some()
+    }
+
+    /// `reset_marks` Forget a mark cut by a change of the settings.
+    pub fn reset_marks(&mut self) {
+        self.unmarker = Unmarker::default();
+    }
+
+    /// `feed` Remove the marks from received bytes, splitting them at breaks and putting the
+    /// marker before erroneous bytes.
+    #[cfg(unix)]
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Marked<'static>> {
+        let mut marked = Vec::new();
+        let mut data = Vec::new();
+        for (byte, error) in self.unmarker.feed(bytes) {
+            // a framing error on a NUL byte is marked the same way
+            if error && byte == 0 && self.detect_breaks {
+                if !data.is_empty() {
+                    marked.push(Marked::Data(Cow::Owned(std::mem::take(&mut data))));
+                }
+                self.breaks += 1;
+                marked.push(Marked::Break { count: self.breaks });
+                continue;
+            }
+            if error {
+                self.marked += 1;
+                if let Some(marker) = &self.marker {
+                    data.extend_from_slice(marker);
+                }
+            }
+            data.push(byte);
+        }
+        if !data.is_empty() {
+            marked.push(Marked::Data(Cow::Owned(data)));
+        }
+        marked
+    }
+
+    /// `counters` What the driver counted since the port was opened.
+    fn counters(&self, native: Option<isize>) -> Option<Counters> {
+        match (self.driver, native) {
+            #[cfg(target_os = "linux")]
+            (Some(base), Some(fd)) => crate::termios::icount(fd as i32)
+                .ok()
+                .map(|counters| counters.since(&base)),
+            #[cfg(windows)]
+            (Some(counted), Some(_)) => Some(counted),
+            _ => None,
+        }
+    }
+
+    fn to_errors(&self, counted: Option<Counters>) -> LineErrors {
+        let counters = counted.unwrap_or_default();
+        LineErrors {
+            parity: counters.parity,
+            framing: counters.framing,
+            breaks: counters.breaks,
+            overrun: counters.overrun,
+            buffer_overrun: counters.buffer_overrun,
+            marked: self.marked,
+            driver_counts: counted.is_some(),
+        }
+    }
+
+    /// `errors` The error counts, `native` the descriptor or handle of the port.
+    pub fn errors(&mut self, native: Option<isize>) -> LineErrors {
+        #[cfg(windows)]
+        if let Some(handle) = native {
+            self.poll(handle);
+        }
+        self.to_errors(self.counters(native))
+    }
+
+    /// `poll` Count the errors `ClearCommError` flagged since the last call, whether a break was
+    /// among them.
+    #[cfg(windows)]
+    fn poll(&mut self, handle: isize) -> bool {
+        let pending = std::mem::take(&mut self.pending_break);
+        match crate::commstate::clear_errors(handle as _) {
+            Ok((errors, _, _)) => self.count(errors) || pending,
+            Err(_) => pending,
+        }
+    }
+
+    #[cfg(windows)]
+    fn count(&mut self, errors: u32) -> bool {
+        use windows_sys::Win32::Devices::Communication::{
+            CE_BREAK, CE_FRAME, CE_OVERRUN, CE_RXOVER, CE_RXPARITY,
+        };
+        let counted = self.driver.get_or_insert_with(Counters::default);
+        let flag = |bit: u32| u64::from(errors & bit != 0);
+        counted.parity += flag(CE_RXPARITY);
+        counted.framing += flag(CE_FRAME);
+        counted.breaks += flag(CE_BREAK);
+        counted.overrun += flag(CE_OVERRUN);
+        counted.buffer_overrun += flag(CE_RXOVER);
+        errors & CE_BREAK != 0
+    }
+}
+
+/// `split` The received bytes as they are, or without the marks while the driver sets them.
+pub fn split<'a>(status: &Mutex<LineStatus>, bytes: &'a [u8]) -> Vec<Marked<'a>> {
+    match status.lock() {
+        #[cfg(unix)]
+        Ok(mut status) if status.is_marking() => status.feed(bytes),
+        _ => vec![Marked::Data(Cow::Borrowed(bytes))],
+    }
+}
+
+/// `queued` The bytes the driver holds for reading and for sending. Windows reports them with the
+/// errors, which are counted and a break kept for the next `poll`, `None` without a native handle.
+#[cfg(windows)]
+pub fn queued(status: &Mutex<LineStatus>, native: Option<isize>) -> Option<(u32, u32)> {
+    let (errors, input, output) = crate::commstate::clear_errors(native? as _).ok()?;
+    if let Ok(mut status) = status.lock() {
+        if status.count(errors) {
+            status.pending_break = true;
+        }
+    }
+    Some((input, output))
+}
+
+/// `poll` Ask the driver for new errors, reporting overruns once they occur after a quiet
+/// second and, on Windows, the breaks.
+pub fn poll(status: &Mutex<LineStatus>, native: Option<isize>) -> Polled {
+    let mut polled = Polled::default();
+    let mut status = match status.lock() {
+        Ok(status) => status,
+        Err(_) => return polled,
+    };
+    #[cfg(windows)]
+    if let Some(handle) = native {
+        if status.poll(handle) && status.detect_breaks {
+            status.breaks += 1;
+            polled.break_count = Some(status.breaks);
+        }
+    }
+    let counted = status.counters(native);
+    if let Some(counters) = counted {
+        let overruns = counters.overrun + counters.buffer_overrun;
+        if overruns > status.overruns {
+            let quiet = status
+                .last_overrun
+                .map_or(true, |last| last.elapsed() >= OVERRUN_QUIET);
+            if quiet {
+                polled.overruns = Some(status.to_errors(counted));
+            }
+            status.last_overrun = Some(Instant::now());
+        }
+        status.overruns = overruns;
+    }
+    polled
+}
@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+
+/// Maximum verbosity of the plugin, set once by the plugin `Builder`.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(3);
+
+pub fn level_filter_to_u8(level: LevelFilter) -> u8 {
+    match level.into_level() {
+        None => 0,
+        Some(Level::ERROR) => 1,
+        Some(Level::WARN) => 2,
+        Some(Level::INFO) => 3,
+        Some(Level::DEBUG) => 4,
+        Some(Level::TRACE) => 5,
+    }
+}
+
+fn level_to_u8(level: Level) -> u8 {
+    level_filter_to_u8(LevelFilter::from_level(level))
+}
+
+/// `parse_level_filter` Parse a level name coming from the frontend, e.g. `Debug` or `Off`.
+pub fn parse_level_filter(value: &str) -> Option<LevelFilter> {
+    match value.to_ascii_lowercase().as_str() {
+        "off" => Some(LevelFilter::OFF),
+        "error" => Some(LevelFilter::ERROR),
+        "warn" => Some(LevelFilter::WARN),
+        "info" => Some(LevelFilter::INFO),
+        "debug" => Some(LevelFilter::DEBUG),
+        "trace" => Some(LevelFilter::TRACE),
+        _ => None,
+    }
+}
+
+/// `hex_dump` Format bytes as space separated hex, e.g. `01 a0 ff`.
+pub fn hex_dump(data: &[u8]) -> String {
+    data.iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// `log_traffic` Write a hex dump of port traffic at the diagnostic level of the port.
+/// This ignores the plugin verbosity so a single port can be inspected in the field.
+pub fn log_traffic(level: u8, path: &str, direction: &str, data: &[u8]) {
+    if level == 0 || data.is_empty() {
+        return;
+    }
+    let dump = hex_dump(data);
+    match level {
+        1 => tracing::error!(path, direction, size = data.len(), "{}", dump),
+        2 => tracing::warn!(path, direction, size = data.len(), "{}", dump),
+        3 => tracing::info!(path, direction, size = data.len(), "{}", dump),
+        4 => tracing::debug!(path, direction, size = data.len(), "{}", dump),
+        _ => tracing::trace!(path, direction, size = data.len(), "{}", dump),
+    }
+}
+
+pub fn set_max_level(level: LevelFilter) {
+    MAX_LEVEL.store(level_filter_to_u8(level), Ordering::Relaxed);
+}
+
+/// `enabled` Whether events of `level` pass the verbosity configured on the plugin `Builder`.
+pub fn enabled(level: Level) -> bool {
+    level_to_u8(level) <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled(tracing::Level::ERROR) {
+            tracing::error!($($arg)*);
+        }
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled(tracing::Level::WARN) {
+            tracing::warn!($($arg)*);
+        }
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled(tracing::Level::INFO) {
+            tracing::info!($($arg)*);
+        }
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled(tracing::Level::DEBUG) {
+            tracing::debug!($($arg)*);
+        }
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled(tracing::Level::TRACE) {
+            tracing::trace!($($arg)*);
+        }
+    };
+}
@@ -0,0 +1,296 @@
+//! LoRaWAN through the command sets of UART LoRa modules, on the AT engine: the Microchip
+//! RN2483 with its `mac` commands and lowercase answers, and RAK modules with the RUI3 AT
+//! commands and `+EVT:` events. Ebyte E22 modules are transparent radios without LoRaWAN, only
+//! their configuration registers are exchanged.
+
+use crate::at::{AtChannel, Urc};
+use crate::reader::now_millis;
+use crate::sms::{from_hex, to_hex};
+use crate::state::{AtResponse, LoraDownlink};
+use serde::Deserialize;
+use serialport::{ClearBuffer, SerialPort};
+use std::io;
+use std::time::{Duration, Instant};
+
+/// The RX2 window of a join accept or a downlink opens 6 s after the uplink, retries add more.
+pub const NETWORK_TIMEOUT: Duration = Duration::from_secs(30);
+const TIMEOUT: Duration = Duration::from_secs(2);
+/// Prefix of the RAK downlink events, e.g. `+EVT:RX_1:-70:8:UNICAST:2:1234`.
+const RAK_RX: &str = "+EVT:RX_";
+
+/// `Module` The command set of a LoRaWAN module.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Module {
+    /// Microchip RN2483/RN2903, `mac join otaa`.
+    Rn2483,
+    /// RAK3172 and other RUI3 modules, `AT+JOIN`.
+    Rak,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Activation {
+    Otaa,
+    Abp,
+}
+
+/// `prepare` Set up the engine of the port for `module`.
+fn prepare(channel: &AtChannel, module: Module) -> Result<(), String> {
+    let mut engine = channel
+        .engine
+        .lock()
+        .map_err(|error| format!("Cannot get AT lock: {}", error))?;
+    engine.lora = Some(module);
+    match module {
+        Module::Rn2483 => engine.line_ending = Some("\r\n"),
+        Module::Rak => {
+            if !engine.urc_prefixes.iter().any(|prefix| prefix == RAK_RX) {
+                engine.urc_prefixes.push(RAK_RX.to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `parse_urc` The downlink a RAK `+EVT:RX_<window>:<rssi>:<snr>:<type>:<port>:<hex>` line
+/// announces, if it is one.
+pub fn parse_urc(module: Module, line: &str) -> Option<LoraDownlink> {
+    if module != Module::Rak {
+        return None;
+    }
+    let fields: Vec<&str> = line.strip_prefix(RAK_RX)?.split(':').collect();
+    match fields[..] {
+        [_, rssi, snr, _, port, hex] => Some(LoraDownlink {
+            port: port.trim().parse().ok()?,
+            data: from_hex(hex).ok()?,
+            rssi: rssi.trim().parse().ok(),
+            snr: snr.trim().parse().ok(),
+            timestamp: now_millis(),
+        }),
+        _ => None,
+    }
+}
+
+/// `command` Run one command of the module. RN2483 commands end with their single answer line,
+/// e.g. `ok` or `invalid_param`, RAK commands with a result code.
+pub fn command(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    module: Module,
+    command: &str,
+    timeout: Duration,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<AtResponse, String> {
+    prepare(channel, module)?;
+    match module {
+        Module::Rn2483 => channel.transact_until(
+            port,
+            reading,
+            command,
+            None,
+            timeout,
+            Some(|_| true),
+            on_urc,
+        ),
+        Module::Rak => channel.transact(port, reading, command, None, timeout, on_urc),
+    }
+}
+
+/// `expect_ok` Run a command the module answers with its success code.
+fn expect_ok(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    module: Module,
+    line: &str,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<(), String> {
+    let response = command(channel, port, reading, module, line, TIMEOUT, on_urc)?;
+    match (module, response.result.as_str()) {
+        (Module::Rn2483, "ok") | (Module::Rak, "OK") => Ok(()),
+        (_, result) => Err(format!("{} failed: {}", line, result)),
+    }
+}
+
+/// `join` Join the network with the keys stored in the module. ABP joins at once, OTAA waits
+/// for the join accept.
+pub fn join(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    module: Module,
+    activation: Activation,
+    timeout: Duration,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<(), String> {
+    prepare(channel, module)?;
+    let response = match module {
+        Module::Rn2483 => {
+            let line = match activation {
+                Activation::Otaa => "mac join otaa",
+                Activation::Abp => "mac join abp",
+            };
+            // `ok` when the join starts, then `accepted` or `denied`
+            channel.transact_until(
+                port,
+                reading,
+                line,
+                None,
+                timeout,
+                Some(|line| line != "ok"),
+                on_urc,
+            )?
+        }
+        Module::Rak => {
+            let mode = match activation {
+                Activation::Otaa => "AT+NJM=1",
+                Activation::Abp => "AT+NJM=0",
+            };
+            expect_ok(channel, port, reading, module, mode, on_urc)?;
+            if activation == Activation::Abp {
+                return Ok(());
+            }
+            // join now, without auto join, one attempt
+            channel.transact_until(
+                port,
+                reading,
+                "AT+JOIN=1:0:8:1",
+                None,
+                timeout,
+                Some(|line| line.starts_with("+EVT:JOIN")),
+                on_urc,
+            )?
+        }
+    };
+    match response.result.as_str() {
+        "accepted" | "+EVT:JOINED" => Ok(()),
+        "denied" => Err("The network denied the join".to_string()),
+        result => Err(format!("Join failed: {}", result)),
+    }
+}
+
+/// `send` Send an uplink on the application `port`, returns the downlink received in its RX
+/// windows. Only RN2483 answers with the downlink, RAK modules announce it as a URC.
+#[allow(clippy::too_many_arguments)]
+pub fn send(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    module: Module,
+    application_port: u8,
+    data: &[u8],
+    confirmed: bool,
+    timeout: Duration,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<Option<LoraDownlink>, String> {
+    if !(1..=223).contains(&application_port) {
+        return Err(format!(
+            "LoRaWAN ports go from 1 to 223, not {}",
+            application_port
+        ));
+    }
+    prepare(channel, module)?;
+    match module {
+        Module::Rn2483 => {
+            let kind = if confirmed { "cnf" } else { "uncnf" };
+            let line = format!("mac tx {} {} {}", kind, application_port, to_hex(data));
+            // `ok` when the uplink is queued, then `mac_tx_ok`, `mac_rx` or `mac_err`
+            let response = channel.transact_until(
+                port,
+                reading,
+                &line,
+                None,
+                timeout,
+                Some(|line| line != "ok"),
+                on_urc,
+            )?;
+            let result = response.result;
+            if result == "mac_tx_ok" {
+                return Ok(None);
+            }
+            let downlink = result.strip_prefix("mac_rx ").and_then(|rest| {
+                let (port, hex) = rest.split_once(' ')?;
+                Some(LoraDownlink {
+                    port: port.parse().ok()?,
+                    data: from_hex(hex).ok()?,
+                    rssi: None,
+                    snr: None,
+                    timestamp: now_millis(),
+                })
+            });
+            downlink
+                .map(Some)
+                .ok_or_else(|| format!("Uplink failed: {}", result))
+        }
+        Module::Rak => {
+            let mode = if confirmed { "AT+CFM=1" } else { "AT+CFM=0" };
+            expect_ok(channel, port, reading, module, mode, on_urc)?;
+            let line = format!("AT+SEND={}:{}", application_port, to_hex(data));
+            let response = channel.transact_until(
+                port,
+                reading,
+                &line,
+                None,
+                timeout,
+                Some(|line| {
+                    line.starts_with("+EVT:TX_DONE") || line.starts_with("+EVT:SEND_CONFIRMED")
+                }),
+                on_urc,
+            )?;
+            match response.result.as_str() {
+                "+EVT:TX_DONE" | "+EVT:SEND_CONFIRMED_OK" => Ok(None),
+                result => Err(format!("Uplink failed: {}", result)),
+            }
+        }
+    }
+}
+
+/// `e22_registers` Read `length` configuration registers of an E22 module from `address`, or
+/// write `values` there, `temporary` until it is powered off; returns the registers as the
+/// module answers. The module must be in configuration mode (M0 low, M1 high) at 9600 baud.
+pub fn e22_registers(
+    port: &mut dyn SerialPort,
+    address: u8,
+    length: u8,
+    values: Option<&[u8]>,
+    temporary: bool,
+) -> Result<Vec<u8>, String> {
+    let request = match values {
+        Some(values) => {
+            let head = if temporary { 0xc2 } else { 0xc0 };
+            let mut request = vec![head, address, values.len() as u8];
+            request.extend_from_slice(values);
+            request
+        }
+        None => vec![0xc1, address, length],
+    };
+    // the answer repeats address and length
+    let expected = 3 + request[2] as usize;
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    port.write_all(&request)
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write: {}", error))?;
+    let deadline = Instant::now() + TIMEOUT;
+    let mut answer = Vec::new();
+    let mut buf = [0u8; 64];
+    while answer.len() < expected && Instant::now() < deadline {
+        match port.read(&mut buf) {
+            Ok(size) => answer.extend_from_slice(&buf[..size]),
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read: {}", error)),
+        }
+        if answer.starts_with(&[0xff, 0xff, 0xff]) {
+            return Err(
+                "The module rejected the command, is it in configuration mode?".to_string(),
+            );
+        }
+    }
+    match answer.get(..3) {
+        Some([0xc1, answered, _]) if *answered == address && answer.len() >= expected => {
+            Ok(answer[3..expected].to_vec())
+        }
+        Some(_) => Err("Unexpected answer of the module".to_string()),
+        None => Err("No answer from the module, is it in configuration mode?".to_string()),
+    }
+}
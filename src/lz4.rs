@@ -0,0 +1,237 @@
+//! The LZ4 block format, without the frame format around it: sequences of a token, literals and
+//! a match copied from up to 64 KiB back, the last sequence literals only.
+
+/// Shortest match the format can express.
+const MIN_MATCH: usize = 4;
+/// The last bytes of a block are always literals.
+const LAST_LITERALS: usize = 5;
+/// The last match starts at least this far from the end of the block.
+const MATCH_LIMIT: usize = 12;
+const MAX_OFFSET: usize = 65535;
+const HASH_BITS: u32 = 12;
+
+fn read_u32(data: &[u8], position: usize) -> u32 {
+    u32::from_le_bytes([
+        data[position],
+        data[position + 1],
+        data[position + 2],
+        data[position + 3],
+    ])
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+/// `push_length` The bytes that extend a length beyond 15.
+fn push_length(output: &mut Vec<u8>, mut length: usize) {
+    while length >= 255 {
+        output.push(255);
+        length -= 255;
+    }
+    output.push(length as u8);
+}
+
+fn push_sequence(output: &mut Vec<u8>, literals: &[u8], offset_length: Option<(usize, usize)>) {
+    let match_length = offset_length.map_or(0, |(_, length)| length - MIN_MATCH);
+    output.push(((literals.len().min(15) as u8) << 4) | match_length.min(15) as u8);
+    if literals.len() >= 15 {
+        push_length(output, literals.len() - 15);
+    }
+    output.extend_from_slice(literals);
+    if let Some((offset, _)) = offset_length {
+        output.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_length >= 15 {
+            push_length(output, match_length - 15);
+        }
+    }
+}
+
+/// `compress` The block of `input`, found with one hash table lookup per position.
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() + input.len() / 255 + 16);
+    let mut table = vec![usize::MAX; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut position = 0;
+    while position + MATCH_LIMIT < input.len() {
+        let sequence = read_u32(input, position);
+        let slot = &mut table[hash(sequence)];
+        let candidate = *slot;
+        *slot = position;
+        if candidate == usize::MAX
+            || position - candidate > MAX_OFFSET
+            || read_u32(input, candidate) != sequence
+        {
+            position += 1;
+            continue;
+        }
+        let mut length = MIN_MATCH;
+        while position + length < input.len() - LAST_LITERALS
+            && input[candidate + length] == input[position + length]
+        {
+            length += 1;
+        }
+        push_sequence(
+            &mut output,
+            &input[anchor..position],
+            Some((position - candidate, length)),
+        );
+        position += length;
+        anchor = position;
+    }
+    push_sequence(&mut output, &input[anchor..], None);
+    output
+}
+
+/// `Reader` The position in a block being decompressed.
+struct Reader<'a> {
+    input: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.input.get(self.position).ok_or_else(truncated)?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .input
+            .get(self.position..self.position + count)
+            .ok_or_else(truncated)?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    /// `length` A length of the token, extended by the following bytes when it is 15.
+    fn length(&mut self, base: u8) -> Result<usize, String> {
+        let mut length = base as usize;
+        if base == 15 {
+            loop {
+                let byte = self.byte()?;
+                length += byte as usize;
+                if byte != 255 {
+                    break;
+                }
+            }
+        }
+        Ok(length)
+    }
+}
+
+fn truncated() -> String {
+    "Truncated LZ4 block".to_string()
+}
+
+/// `decompress` The data of a block, failing on damaged blocks and on data beyond `limit`.
+pub fn decompress(input: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let too_long = || format!("LZ4 block longer than {} bytes", limit);
+    let mut reader = Reader { input, position: 0 };
+    let mut output = Vec::new();
+    loop {
+        let token = reader.byte()?;
+        let literals = reader.length(token >> 4)?;
+        if output.len() + literals > limit {
+            return Err(too_long());
+        }
+        output.extend_from_slice(reader.bytes(literals)?);
+        if reader.position == input.len() {
+            return Ok(output);
+        }
+        let offset = u16::from_le_bytes([reader.byte()?, reader.byte()?]) as usize;
+        if offset == 0 || offset > output.len() {
+            return Err(format!("LZ4 match offset {} out of range", offset));
+        }
+        let length = reader.length(token & 15)? + MIN_MATCH;
+        if output.len() + length > limit {
+            return Err(too_long());
+        }
+        // byte by byte, a match may overlap the bytes it produces
+        let start = output.len() - offset;
+        for index in start..start + length {
+            output.push(output[index]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes of a linear congruential generator, incompressible for the hash table.
+    fn noise(length: usize, mut seed: u32) -> Vec<u8> {
+        (0..length)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn known_blocks() {
+        assert_eq!(decompress(&[0x00], 0).unwrap(), b"");
+        assert_eq!(decompress(b"\x50hello", 5).unwrap(), b"hello");
+        // a literal, a match overlapping the byte it copies and the last literals
+        let block = b"\x14a\x01\x00\x50bbbbb";
+        assert_eq!(decompress(block, 14).unwrap(), b"aaaaaaaaabbbbb");
+        // 15 + 3 literals, a match of 4 + 15 + 255 + 1 bytes and an empty last sequence
+        let mut block = vec![0xff, 0x03];
+        block.extend_from_slice(b"0123456789abcdefgh");
+        block.extend_from_slice(&[0x0a, 0x00, 0xff, 0x01, 0x00]);
+        let data = decompress(&block, 1000).unwrap();
+        assert_eq!(data.len(), 18 + 275);
+        assert_eq!(&data[18..28], b"89abcdefgh");
+        assert_eq!(&data[data.len() - 3..], b"abc");
+    }
+
+    #[test]
+    fn compressed_blocks() {
+        assert_eq!(compress(b""), vec![0x00]);
+        assert_eq!(compress(b"hello"), b"\x50hello");
+        // a literal and a match of 26 bytes at offset 1, the last 5 bytes literals
+        assert_eq!(
+            compress(&[b'a'; 32]),
+            b"\x1fa\x01\x00\x07\x50aaaaa".to_vec()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = b"GET /sensors/temperature HTTP/1.1\r\nHost: 192.168.1.20\r\n\r\n".repeat(40);
+        let mut mixed = noise(70_000, 1);
+        mixed.extend(noise(1000, 1));
+        mixed.extend(vec![0u8; 600]);
+        let inputs = [
+            Vec::new(),
+            b"x".to_vec(),
+            noise(12, 2),
+            noise(13, 3),
+            noise(300, 4),
+            vec![7u8; 15],
+            vec![7u8; 19 + 255],
+            vec![7u8; 100_000],
+            text,
+            mixed,
+        ];
+        for input in inputs.iter() {
+            let block = compress(input);
+            assert_eq!(&decompress(&block, input.len()).unwrap(), input);
+        }
+        assert!(compress(&vec![7u8; 100_000]).len() < 500);
+    }
+
+    #[test]
+    fn damaged_blocks() {
+        assert!(decompress(&[], 10).is_err());
+        assert!(decompress(b"\x50hel", 10).is_err());
+        assert!(decompress(b"\xf0", 100).is_err());
+        assert!(decompress(b"\x14a\x00\x00\x50bbbbb", 100).is_err());
+        assert!(decompress(b"\x14a\x02\x00\x50bbbbb", 100).is_err());
+        assert!(decompress(b"\x14a\x01", 100).is_err());
+        assert!(decompress(b"\x50hello", 4).is_err());
+        assert!(decompress(b"\x14a\x01\x00\x50bbbbb", 8).is_err());
+    }
+}
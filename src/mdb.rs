@@ -0,0 +1,596 @@
+//! MDB (Multi-Drop Bus) master for vending peripherals: 9600 baud with a ninth mode bit that
+//! marks address bytes from the VMC and the last byte of a peripheral answer.
+//!
+//! The mode bit is sent as the parity bit, odd or even parity is picked per byte so it comes
+//! out as mark or space. Received mode bits are recovered from the parity errors the driver
+//! marks, which needs the descriptor of a native Unix port. Every parity change waits for the
+//! output to drain, on USB adapters this can stretch the gaps beyond the 1 ms MDB allows.
+
+use serde::Serialize;
+use serialport::{ClearBuffer, DataBits, Parity, SerialPort, StopBits};
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const BAUD_RATE: u32 = 9600;
+const ACK: u8 = 0x00;
+const NAK: u8 = 0xff;
+/// Asks the peripheral to send its last answer again.
+const RET: u8 = 0xaa;
+/// Longest block on the bus, checksum included.
+const MAX_BLOCK: usize = 36;
+/// Silence that ends an answer whose last byte was lost.
+const INTER_BYTE: Duration = Duration::from_millis(5);
+
+pub const COIN_CHANGER: u8 = 0x08;
+pub const CASHLESS_1: u8 = 0x10;
+pub const BILL_VALIDATOR: u8 = 0x30;
+pub const CASHLESS_2: u8 = 0x60;
+
+/// `Response` What a peripheral answered, `data` without the checksum.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum Response {
+    Ack,
+    Nak,
+    Data { data: Vec<u8> },
+}
+
+/// `MdbEvent` One item of a poll answer, or a peripheral that appeared or went silent.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum MdbEvent {
+    Online,
+    Offline,
+    /// `tube_count` is the number of coins of the type left in its tube.
+    CoinDeposited {
+        coin_type: u8,
+        routing: &'static str,
+        tube_count: u8,
+    },
+    CoinsDispensed {
+        coin_type: u8,
+        count: u8,
+        tube_count: u8,
+    },
+    Slugs {
+        count: u8,
+    },
+    BillRouted {
+        bill_type: u8,
+        routing: &'static str,
+    },
+    DisabledBillAttempts {
+        count: u8,
+    },
+    Status {
+        code: u8,
+        description: &'static str,
+    },
+    /// Poll answers of peripherals without a decoder, e.g. cashless devices.
+    Data {
+        data: Vec<u8>,
+    },
+}
+
+/// `Unmarker` Splits the stream of a port with `termios::mark_errors` back into bytes and
+/// their error flag, also across reads.
+#[derive(Default)]
+pub(crate) struct Unmarker {
+    pending: Vec<u8>,
+}
+
+impl Unmarker {
+    pub(crate) fn feed(&mut self, bytes: &[u8]) -> Vec<(u8, bool)> {
+        self.pending.extend_from_slice(bytes);
+        let mut decoded = Vec::new();
+        let mut index = 0;
+        while index < self.pending.len() {
+            if self.pending[index] != 0xff {
+                decoded.push((self.pending[index], false));
+                index += 1;
+                continue;
+            }
+            match self.pending.get(index + 1) {
+                Some(0xff) => {
+                    decoded.push((0xff, false));
+                    index += 2;
+                }
+                Some(0) => match self.pending.get(index + 2) {
+                    Some(byte) => {
+                        decoded.push((*byte, true));
+                        index += 3;
+                    }
+                    None => break,
+                },
+                // not produced by the driver, taken as is
+                Some(_) => {
+                    decoded.push((0xff, false));
+                    index += 1;
+                }
+                None => break,
+            }
+        }
+        self.pending.drain(..index);
+        decoded
+    }
+}
+
+fn odd_ones(byte: u8) -> bool {
+    byte.count_ones() % 2 == 1
+}
+
+/// `mode_bit` The ninth bit of a byte received at even parity, from its parity error flag.
+pub(crate) fn mode_bit(byte: u8, error: bool) -> bool {
+    odd_ones(byte) != error
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// `request` The bytes of a command and its data with their mode bits, the checksum last.
+fn request(command: u8, data: &[u8]) -> Vec<(u8, bool)> {
+    let mut request = vec![(command, true)];
+    request.extend(data.iter().map(|byte| (*byte, false)));
+    let check = checksum(&[&[command], data].concat());
+    request.push((check, false));
+    request
+}
+
+/// `answer` The response in a received block, `None` when its checksum is wrong.
+fn answer(block: &[(u8, bool)]) -> Result<Option<Response>, String> {
+    if let [(byte, _)] = block[..] {
+        return match byte {
+            ACK => Ok(Some(Response::Ack)),
+            NAK => Ok(Some(Response::Nak)),
+            _ => Err(format!("Invalid MDB answer {:#04x}", byte)),
+        };
+    }
+    let bytes: Vec<u8> = block.iter().map(|(byte, _)| *byte).collect();
+    let (data, check) = bytes.split_at(bytes.len() - 1);
+    if checksum(data) != check[0] {
+        return Ok(None);
+    }
+    Ok(Some(Response::Data {
+        data: data.to_vec(),
+    }))
+}
+
+/// `poll_command` The POLL command of the peripheral at `address`.
+pub fn poll_command(address: u8) -> u8 {
+    match address {
+        CASHLESS_1 | CASHLESS_2 => address + 2,
+        _ => address + 3,
+    }
+}
+
+/// `mark_parity_errors` Let the driver mark the bytes received with a parity error.
+#[cfg(unix)]
+fn mark_parity_errors(native: Option<isize>) -> Result<(), String> {
+    let fd = native.ok_or_else(|| "MDB needs a native Unix serial port".to_string())?;
+    crate::termios::mark_errors(fd as i32, true)
+        .map_err(|error| format!("Failed to enable parity error marking: {}", error))
+}
+
+#[cfg(not(unix))]
+fn mark_parity_errors(_native: Option<isize>) -> Result<(), String> {
+    Err("MDB needs a native Unix serial port".to_string())
+}
+
+/// `Bus` A port switched to MDB framing.
+pub struct Bus {
+    port: Box<dyn SerialPort>,
+    unmarker: Unmarker,
+}
+
+impl Bus {
+    /// `new` Switch the port to 9600 8E1 with marked parity errors; the receiving side stays at
+    /// even parity, so a mode bit shows as parity error on bytes with an even number of ones.
+    pub fn new(mut port: Box<dyn SerialPort>, native: Option<isize>) -> Result<Bus, String> {
+        mark_parity_errors(native)?;
+        port.set_baud_rate(BAUD_RATE)
+            .and_then(|_| port.set_data_bits(DataBits::Eight))
+            .and_then(|_| port.set_parity(Parity::Even))
+            .and_then(|_| port.set_stop_bits(StopBits::One))
+            .map_err(|error| format!("Failed to configure port: {}", error))?;
+        Ok(Bus {
+            port,
+            unmarker: Unmarker::default(),
+        })
+    }
+
+    /// `write` Send bytes with their mode bit, one parity change per run of equal parity.
+    fn write(&mut self, bytes: &[(u8, bool)]) -> Result<(), String> {
+        let mut start = 0;
+        while start < bytes.len() {
+            // even parity sends a one as parity bit when the count of ones is odd
+            let even = |&(byte, mode): &(u8, bool)| odd_ones(byte) == mode;
+            let parity = even(&bytes[start]);
+            let end = bytes[start..]
+                .iter()
+                .position(|byte| even(byte) != parity)
+                .map_or(bytes.len(), |length| start + length);
+            let run: Vec<u8> = bytes[start..end].iter().map(|(byte, _)| *byte).collect();
+            self.port
+                .set_parity(if parity { Parity::Even } else { Parity::Odd })
+                .and_then(|_| self.port.write_all(&run).map_err(Into::into))
+                .and_then(|_| self.port.flush().map_err(Into::into))
+                .map_err(|error| format!("Failed to write to the bus: {}", error))?;
+            start = end;
+        }
+        self.port
+            .set_parity(Parity::Even)
+            .map_err(|error| format!("Failed to restore parity: {}", error))
+    }
+
+    /// `read_block` Receive bytes up to the one with the mode bit, `None` without answer.
+    fn read_block(&mut self, timeout: Duration) -> Result<Option<Vec<(u8, bool)>>, String> {
+        let deadline = Instant::now() + timeout;
+        let mut last_byte = Instant::now();
+        let mut block = Vec::new();
+        let mut buffer = [0u8; 64];
+        loop {
+            if block.is_empty() && Instant::now() >= deadline {
+                return Ok(None);
+            }
+            if !block.is_empty() && last_byte.elapsed() >= INTER_BYTE {
+                return Err("Answer ended without mode bit".to_string());
+            }
+            let size = match self.port.read(&mut buffer) {
+                Ok(size) => size,
+                Err(error) if error.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(error) => return Err(format!("Failed to read from the bus: {}", error)),
+            };
+            last_byte = Instant::now();
+            for (byte, error) in self.unmarker.feed(&buffer[..size]) {
+                // even parity, a parity error means the ninth bit differs from the expected one
+                let mode = mode_bit(byte, error);
+                block.push((byte, mode));
+                if mode {
+                    return Ok(Some(block));
+                }
+                if block.len() >= MAX_BLOCK {
+                    return Err("Answer longer than an MDB block".to_string());
+                }
+            }
+        }
+    }
+
+    /// `transact` Send a command (address and subcommand in the first byte) with its data and
+    /// checksum, and acknowledge the answer. A checksum error is retried once with RET.
+    pub fn transact(
+        &mut self,
+        command: u8,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<Response, String> {
+        if data.len() >= MAX_BLOCK {
+            return Err(format!(
+                "MDB blocks carry at most {} data bytes",
+                MAX_BLOCK - 2
+            ));
+        }
+        let request = request(command, data);
+        self.port
+            .clear(ClearBuffer::Input)
+            .map_err(|error| format!("Failed to clear input: {}", error))?;
+        self.unmarker = Unmarker::default();
+        self.write(&request)?;
+        for retry in 0..2 {
+            let block = self
+                .read_block(timeout)?
+                .ok_or_else(|| format!("No answer to MDB command {:#04x}", command))?;
+            match answer(&block)? {
+                Some(Response::Data { data }) => {
+                    self.write(&[(ACK, false)])?;
+                    return Ok(Response::Data { data });
+                }
+                Some(response) => return Ok(response),
+                None => {}
+            }
+            if retry == 0 {
+                warn!(
+                    "MDB checksum error in answer to {:#04x}, retransmit",
+                    command
+                );
+                self.write(&[(RET, false)])?;
+            }
+        }
+        self.write(&[(NAK, false)])?;
+        Err(format!("MDB checksum error in answer to {:#04x}", command))
+    }
+}
+
+fn coin_status(code: u8) -> &'static str {
+    match code {
+        1 => "Escrow request",
+        2 => "Changer payout busy",
+        3 => "No credit",
+        4 => "Defective tube sensor",
+        5 => "Double arrival",
+        6 => "Acceptor unplugged",
+        7 => "Tube jam",
+        8 => "ROM checksum error",
+        9 => "Coin routing error",
+        10 => "Changer busy",
+        11 => "Changer was reset",
+        12 => "Coin jam",
+        13 => "Possible credited coin removal",
+        _ => "Unknown status",
+    }
+}
+
+fn bill_status(code: u8) -> &'static str {
+    match code {
+        1 => "Defective motor",
+        2 => "Sensor problem",
+        3 => "Validator busy",
+        4 => "ROM checksum error",
+        5 => "Validator jammed",
+        6 => "Validator was reset",
+        7 => "Bill removed",
+        8 => "Cash box out of position",
+        9 => "Validator disabled",
+        10 => "Invalid escrow request",
+        11 => "Bill rejected",
+        12 => "Possible credited bill removal",
+        _ => "Unknown status",
+    }
+}
+
+/// `decode_poll` The events in the poll answer of the peripheral at `address`.
+pub fn decode_poll(address: u8, data: &[u8]) -> Vec<MdbEvent> {
+    let mut events = Vec::new();
+    let mut bytes = data.iter().copied();
+    match address {
+        COIN_CHANGER => {
+            while let Some(byte) = bytes.next() {
+                events.push(if byte & 0x80 != 0 {
+                    MdbEvent::CoinsDispensed {
+                        coin_type: byte & 0x0f,
+                        count: (byte >> 4) & 0x07,
+                        tube_count: bytes.next().unwrap_or(0),
+                    }
+                } else if byte & 0xc0 == 0x40 {
+                    MdbEvent::CoinDeposited {
+                        coin_type: byte & 0x0f,
+                        routing: match (byte >> 4) & 0x03 {
+                            0 => "CashBox",
+                            1 => "Tubes",
+                            2 => "NotUsed",
+                            _ => "Rejected",
+                        },
+                        tube_count: bytes.next().unwrap_or(0),
+                    }
+                } else if byte & 0xe0 == 0x20 {
+                    MdbEvent::Slugs { count: byte & 0x1f }
+                } else {
+                    MdbEvent::Status {
+                        code: byte,
+                        description: coin_status(byte),
+                    }
+                });
+            }
+        }
+        BILL_VALIDATOR => {
+            for byte in bytes {
+                events.push(if byte & 0x80 != 0 {
+                    MdbEvent::BillRouted {
+                        bill_type: byte & 0x0f,
+                        routing: match (byte >> 4) & 0x07 {
+                            0 => "Stacked",
+                            1 => "Escrow",
+                            2 => "Returned",
+                            3 => "ToRecycler",
+                            4 => "DisabledBillRejected",
+                            5 => "ToRecyclerManualFill",
+                            6 => "ManualDispense",
+                            _ => "FromRecyclerToCashBox",
+                        },
+                    }
+                } else if byte & 0xe0 == 0x40 {
+                    MdbEvent::DisabledBillAttempts { count: byte & 0x1f }
+                } else {
+                    MdbEvent::Status {
+                        code: byte,
+                        description: bill_status(byte),
+                    }
+                });
+            }
+        }
+        _ => events.push(MdbEvent::Data {
+            data: data.to_vec(),
+        }),
+    }
+    events
+}
+
+/// `Mdb` The bus of a polled port, shared with `mdb_command`; polling stops when dropped.
+pub struct Mdb {
+    pub bus: Arc<Mutex<Bus>>,
+    pub _stop: Sender<()>,
+}
+
+/// `run` Poll the peripherals every `interval` until `stop` is dropped.
+pub fn run<F: Fn(u8, MdbEvent)>(
+    bus: Arc<Mutex<Bus>>,
+    addresses: Vec<u8>,
+    interval: Duration,
+    timeout: Duration,
+    stop: Receiver<()>,
+    emit: F,
+) {
+    let mut online: HashMap<u8, bool> = HashMap::new();
+    loop {
+        for address in &addresses {
+            let response = match bus.lock() {
+                Ok(mut bus) => bus.transact(poll_command(*address), &[], timeout),
+                Err(_) => return,
+            };
+            let answered = match response {
+                Ok(Response::Ack) => true,
+                Ok(Response::Data { data }) => {
+                    for event in decode_poll(*address, &data) {
+                        emit(*address, event);
+                    }
+                    true
+                }
+                Ok(Response::Nak) => {
+                    debug!("MDB peripheral {:#04x} answered NAK", address);
+                    true
+                }
+                Err(error) => {
+                    trace!("MDB poll of {:#04x} failed: {}", address, error);
+                    false
+                }
+            };
+            if online.insert(*address, answered) != Some(answered) {
+                let event = if answered {
+                    MdbEvent::Online
+                } else {
+                    MdbEvent::Offline
+                };
+                emit(*address, event);
+            }
+        }
+        match stop.recv_timeout(interval) {
+            Err(RecvTimeoutError::Timeout) => {}
+            _ => break,
+        }
+    }
+    info!("MDB polling stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(bytes: &[u8]) -> Vec<(u8, bool)> {
+        let last = bytes.len() - 1;
+        bytes
+            .iter()
+            .enumerate()
+            .map(|(index, byte)| (*byte, index == last))
+            .collect()
+    }
+
+    #[test]
+    fn request_ends_with_the_checksum() {
+        assert_eq!(request(0x0b, &[]), vec![(0x0b, true), (0x0b, false)]);
+        // COIN TYPE with enable 0xffff and manual dispense 0x0000, the sum wraps
+        assert_eq!(
+            request(0x0c, &[0xff, 0xff, 0x00, 0x00]),
+            vec![
+                (0x0c, true),
+                (0xff, false),
+                (0xff, false),
+                (0x00, false),
+                (0x00, false),
+                (0x0a, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn single_byte_answers() {
+        assert_eq!(answer(&block(&[ACK])), Ok(Some(Response::Ack)));
+        assert_eq!(answer(&block(&[NAK])), Ok(Some(Response::Nak)));
+        assert!(answer(&block(&[0x42])).is_err());
+    }
+
+    #[test]
+    fn data_answers_are_checked() {
+        assert_eq!(
+            answer(&block(&[0x52, 0x03, 0x55])),
+            Ok(Some(Response::Data {
+                data: vec![0x52, 0x03]
+            }))
+        );
+        assert_eq!(
+            answer(&block(&[0xf0, 0x20, 0x10])),
+            Ok(Some(Response::Data {
+                data: vec![0xf0, 0x20]
+            }))
+        );
+        assert_eq!(answer(&block(&[0x52, 0x03, 0x56])), Ok(None));
+    }
+
+    #[test]
+    fn marked_errors_are_unmarked_across_reads() {
+        let mut unmarker = Unmarker::default();
+        assert_eq!(unmarker.feed(&[0x01, 0xff]), vec![(0x01, false)]);
+        assert_eq!(unmarker.feed(&[0xff, 0xff, 0x00]), vec![(0xff, false)]);
+        assert_eq!(unmarker.feed(&[0x03]), vec![(0x03, true)]);
+        assert_eq!(
+            unmarker.feed(&[0xff, 0x05]),
+            vec![(0xff, false), (0x05, false)]
+        );
+    }
+
+    #[test]
+    fn mode_bit_from_the_parity_error() {
+        // even parity expects the parity bit to make the ones even
+        assert!(!mode_bit(0x03, false));
+        assert!(mode_bit(0x03, true));
+        assert!(mode_bit(0x01, false));
+        assert!(!mode_bit(0x01, true));
+    }
+
+    #[test]
+    fn poll_commands() {
+        assert_eq!(poll_command(COIN_CHANGER), 0x0b);
+        assert_eq!(poll_command(BILL_VALIDATOR), 0x33);
+        assert_eq!(poll_command(CASHLESS_1), 0x12);
+        assert_eq!(poll_command(CASHLESS_2), 0x62);
+    }
+
+    #[test]
+    fn coin_changer_poll() {
+        assert_eq!(
+            decode_poll(COIN_CHANGER, &[0x52, 0x07, 0x93, 0x04, 0x22, 0x0b]),
+            vec![
+                MdbEvent::CoinDeposited {
+                    coin_type: 2,
+                    routing: "Tubes",
+                    tube_count: 7
+                },
+                MdbEvent::CoinsDispensed {
+                    coin_type: 3,
+                    count: 1,
+                    tube_count: 4
+                },
+                MdbEvent::Slugs { count: 2 },
+                MdbEvent::Status {
+                    code: 0x0b,
+                    description: "Changer was reset"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bill_validator_poll() {
+        assert_eq!(
+            decode_poll(BILL_VALIDATOR, &[0x91, 0x43, 0x06]),
+            vec![
+                MdbEvent::BillRouted {
+                    bill_type: 1,
+                    routing: "Escrow"
+                },
+                MdbEvent::DisabledBillAttempts { count: 3 },
+                MdbEvent::Status {
+                    code: 0x06,
+                    description: "Validator was reset"
+                },
+            ]
+        );
+        assert_eq!(
+            decode_poll(CASHLESS_1, &[0x03, 0x00]),
+            vec![MdbEvent::Data {
+                data: vec![0x03, 0x00]
+            }]
+        );
+    }
+}
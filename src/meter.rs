@@ -0,0 +1,261 @@
+//! IEC 62056-21 (formerly IEC 61107, FLAG) readout of utility meters through an optical probe:
+//! the 300 baud sign-on, the switch to the baud rate the meter offers and the parsing of the
+//! OBIS data block.
+
+use serde::Serialize;
+use serialport::{ClearBuffer, DataBits, Parity, SerialPort, StopBits};
+use std::time::{Duration, Instant};
+
+const SIGN_ON_BAUD_RATE: u32 = 300;
+const STX: u8 = 0x02;
+const ETX: u8 = 0x03;
+const ACK: u8 = 0x06;
+/// The meter waits 200 ms to 1.5 s after its identification before listening again.
+const REACTION_TIME: Duration = Duration::from_millis(300);
+
+/// `Register` One value of the data block; lines with several values give a register each.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Register {
+    /// The OBIS code, e.g. `1.8.0`, empty for values that continue the previous line.
+    pub obis: String,
+    pub value: String,
+    pub unit: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MeterReading {
+    /// The identification line without `/`, e.g. `ISK5ME162-0033`.
+    pub identification: String,
+    pub manufacturer: String,
+    pub baud_rate: u32,
+    pub registers: Vec<Register>,
+}
+
+/// `baud_rate` The rate an identification offers: digits in protocol mode C, letters in
+/// mode B, where the switch is not acknowledged. Any other character is a mode A meter, which
+/// stays at 300 baud.
+fn baud_rate(code: u8) -> (u32, bool) {
+    let rates = [300, 600, 1200, 2400, 4800, 9600, 19200];
+    match code {
+        b'0'..=b'6' => (rates[(code - b'0') as usize], true),
+        b'A'..=b'F' => (rates[(code - b'A' + 1) as usize], false),
+        _ => (SIGN_ON_BAUD_RATE, false),
+    }
+}
+
+/// `parse_data` The registers of a data block, e.g. `1.8.0(001234.5*kWh)`.
+pub fn parse_data(data: &str) -> Vec<Register> {
+    let mut registers = Vec::new();
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line == "!" {
+            continue;
+        }
+        let (obis, mut rest) = match line.find('(') {
+            Some(start) => (line[..start].trim(), &line[start..]),
+            None => continue,
+        };
+        while let Some(start) = rest.find('(') {
+            let end = match rest[start..].find(')') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let value = &rest[start + 1..end];
+            let (value, unit) = match value.split_once('*') {
+                Some((value, unit)) => (value, Some(unit.to_string())),
+                None => (value, None),
+            };
+            registers.push(Register {
+                obis: obis.to_string(),
+                value: value.to_string(),
+                unit,
+            });
+            rest = &rest[end + 1..];
+        }
+    }
+    registers
+}
+
+/// `data_block` The data between STX and ETX of a block ending with ETX and the BCC, the XOR
+/// of everything after STX up to and including ETX.
+fn data_block(block: &[u8]) -> Result<&[u8], String> {
+    let start = block[..block.len().saturating_sub(2)]
+        .iter()
+        .position(|byte| *byte == STX)
+        .ok_or("Data block without STX")?;
+    let (body, check) = block[start + 1..].split_at(block.len() - start - 2);
+    let bcc = body.iter().fold(0u8, |bcc, byte| bcc ^ byte);
+    if bcc != check[0] {
+        return Err(format!(
+            "Block check mismatch: expected {:#04x}, got {:#04x}",
+            bcc, check[0]
+        ));
+    }
+    Ok(&body[..body.len() - 1])
+}
+
+struct Reader<'a> {
+    port: &'a mut dyn SerialPort,
+    pending: Vec<u8>,
+    deadline: Instant,
+}
+
+impl<'a> Reader<'a> {
+    /// `until` The received bytes up to and including `end`, plus `extra` bytes after it.
+    fn until(&mut self, end: u8, extra: usize) -> Result<Vec<u8>, String> {
+        let mut buffer = [0u8; 256];
+        loop {
+            if let Some(position) = self.pending.iter().position(|byte| *byte == end) {
+                if self.pending.len() > position + extra {
+                    return Ok(self.pending.drain(..=position + extra).collect());
+                }
+            }
+            if Instant::now() >= self.deadline {
+                return Err("Timeout waiting for the meter".to_string());
+            }
+            match self.port.read(&mut buffer) {
+                // 7 data bits, whatever the driver leaves in the eighth is noise
+                Ok(size) => self
+                    .pending
+                    .extend(buffer[..size].iter().map(|byte| byte & 0x7f)),
+                Err(error) if error.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(error) => return Err(format!("Failed to read from the meter: {}", error)),
+            }
+        }
+    }
+}
+
+fn write(port: &mut dyn SerialPort, bytes: &[u8]) -> Result<(), String> {
+    port.write_all(bytes)
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write to the meter: {}", error))
+}
+
+/// `read` Sign on at 300 baud 7E1, switch to the offered rate, if any, and read the data block.
+/// The port is left at 7E1 and the rate of the readout.
+pub fn read(
+    port: &mut dyn SerialPort,
+    address: &str,
+    timeout: Duration,
+) -> Result<MeterReading, String> {
+    port.set_baud_rate(SIGN_ON_BAUD_RATE)
+        .and_then(|_| port.set_data_bits(DataBits::Seven))
+        .and_then(|_| port.set_parity(Parity::Even))
+        .and_then(|_| port.set_stop_bits(StopBits::One))
+        .and_then(|_| port.clear(ClearBuffer::Input))
+        .map_err(|error| format!("Failed to configure port: {}", error))?;
+    write(port, format!("/?{}!\r\n", address).as_bytes())?;
+    let mut reader = Reader {
+        port,
+        pending: Vec::new(),
+        deadline: Instant::now() + timeout,
+    };
+    let identification = loop {
+        let line = reader.until(b'\n', 0)?;
+        let line = String::from_utf8_lossy(&line);
+        // an optical head may echo the request
+        match line.trim().strip_prefix('/') {
+            Some(identification) if !identification.starts_with('?') => {
+                if identification.len() < 5 {
+                    return Err(format!("Invalid identification: {}", identification));
+                }
+                break identification.to_string();
+            }
+            _ => {}
+        }
+    };
+    let (rate, acknowledged) = baud_rate(identification.as_bytes()[3]);
+    if acknowledged {
+        std::thread::sleep(REACTION_TIME);
+        // normal protocol, offered rate, data readout
+        let code = identification.as_bytes()[3];
+        write(reader.port, &[ACK, b'0', code, b'0', b'\r', b'\n'])?;
+    }
+    reader
+        .port
+        .set_baud_rate(rate)
+        .map_err(|error| format!("Failed to switch to {} baud: {}", rate, error))?;
+    let block = reader.until(ETX, 1)?;
+    let data = data_block(&block)?;
+    Ok(MeterReading {
+        manufacturer: identification[..3].to_string(),
+        baud_rate: rate,
+        registers: parse_data(&String::from_utf8_lossy(data)),
+        identification,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(obis: &str, value: &str, unit: Option<&str>) -> Register {
+        Register {
+            obis: obis.to_string(),
+            value: value.to_string(),
+            unit: unit.map(str::to_string),
+        }
+    }
+
+    fn block(data: &[u8]) -> Vec<u8> {
+        let mut block = vec![STX];
+        block.extend_from_slice(data);
+        block.push(ETX);
+        let bcc = block[1..].iter().fold(0u8, |bcc, byte| bcc ^ byte);
+        block.push(bcc);
+        block
+    }
+
+    #[test]
+    fn registers_are_parsed() {
+        let data = "0.0.0(12345678)\r\n1.8.0(001234.567*kWh)\r\n\
+                    0.9.1(123456)(7)\r\n(000.12*kW)\r\n!\r\n";
+        assert_eq!(
+            parse_data(data),
+            vec![
+                register("0.0.0", "12345678", None),
+                register("1.8.0", "001234.567", Some("kWh")),
+                register("0.9.1", "123456", None),
+                register("0.9.1", "7", None),
+                register("", "000.12", Some("kW")),
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_lines_are_skipped() {
+        assert_eq!(
+            parse_data("garbage\r\n1.8.1(12\r\n1.8.2(5*kWh)"),
+            vec![register("1.8.2", "5", Some("kWh"))]
+        );
+    }
+
+    #[test]
+    fn data_block_is_checked() {
+        let data = b"1.8.0(001234.567*kWh)\r\n!\r\n";
+        let mut received = b"noise".to_vec();
+        received.extend(block(data));
+        assert_eq!(data_block(&received).unwrap(), data);
+        assert_eq!(data_block(&block(b"")).unwrap(), b"");
+
+        let last = received.len() - 1;
+        received[last] ^= 1;
+        assert!(data_block(&received).is_err());
+        assert!(data_block(&[b'x', ETX, 0x7b]).is_err());
+        // an STX that is the check byte does not start the block
+        assert!(data_block(&[b'x', ETX, STX]).is_err());
+    }
+
+    #[test]
+    fn baud_rate_of_the_protocol_mode() {
+        assert_eq!(baud_rate(b'0'), (300, true));
+        assert_eq!(baud_rate(b'5'), (9600, true));
+        assert_eq!(baud_rate(b'6'), (19200, true));
+        assert_eq!(baud_rate(b'A'), (600, false));
+        assert_eq!(baud_rate(b'E'), (9600, false));
+        assert_eq!(baud_rate(b'F'), (19200, false));
+        // mode A
+        assert_eq!(baud_rate(b'\\'), (300, false));
+        assert_eq!(baud_rate(b'M'), (300, false));
+    }
+}
@@ -0,0 +1,79 @@
+//! Rust middleware registered with `Builder::middleware`, which sees the data of a port before
+//! it crosses the IPC boundary: every write before it reaches the driver, and what the read
+//! receives before it is emitted, e.g. to add sequence numbers, enforce a whitelist of commands
+//! or scrub secrets.
+
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+/// `Middleware` Inspects, changes or refuses the data of one port. Both directions default to
+/// passing the data on unchanged.
+pub trait Middleware: Send {
+    /// `outgoing` Data about to be written. Returns the bytes to write instead, or why the write
+    /// is refused, which fails it.
+    fn outgoing(&mut self, data: Vec<u8>) -> Result<Vec<u8>, String> {
+        Ok(data)
+    }
+
+    /// `incoming` Data the read received, breaks and error marks taken out. Returns what the read
+    /// goes on with, `None` drops the data.
+    fn incoming(&mut self, data: Vec<u8>) -> Option<Vec<u8>> {
+        Some(data)
+    }
+}
+
+/// `MiddlewareFactory` Creates the middleware of a port when it opens, `None` for ports it
+/// leaves alone.
+pub type MiddlewareFactory = dyn Fn(&str) -> Option<Box<dyn Middleware>> + Send + Sync;
+
+/// `Chain` The middleware of a port, in the order they were registered.
+pub struct Chain {
+    middleware: Vec<Box<dyn Middleware>>,
+}
+
+/// `SharedChain` Set once the port is registered, shared by the port, its clones and the read.
+pub type SharedChain = Arc<Mutex<Option<Chain>>>;
+
+impl Chain {
+    /// `build` The middleware the factories create for `path`, `None` when none applies.
+    pub fn build(factories: &[Arc<MiddlewareFactory>], path: &str) -> Option<Chain> {
+        let middleware: Vec<Box<dyn Middleware>> =
+            factories.iter().filter_map(|factory| factory(path)).collect();
+        if middleware.is_empty() {
+            return None;
+        }
+        Some(Chain { middleware })
+    }
+
+    fn outgoing(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        self.middleware
+            .iter_mut()
+            .try_fold(data.to_vec(), |data, middleware| middleware.outgoing(data))
+    }
+
+    fn incoming(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        self.middleware
+            .iter_mut()
+            .try_fold(data.to_vec(), |data, middleware| middleware.incoming(data))
+    }
+}
+
+/// `outgoing` What to write instead of `data`, `None` without middleware.
+pub fn outgoing(chain: &SharedChain, data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    match chain.lock() {
+        Ok(mut chain) => chain.as_mut().map(|chain| chain.outgoing(data)).transpose(),
+        Err(_) => Err("Middleware lock poisoned".to_string()),
+    }
+}
+
+/// `incoming` What the read goes on with instead of `data`, `None` when it was dropped.
+pub fn incoming<'a>(chain: &SharedChain, data: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+    match chain.lock() {
+        Ok(mut chain) => match chain.as_mut() {
+            Some(chain) => chain.incoming(data).map(Cow::Owned),
+            None => Some(Cow::Borrowed(data)),
+        },
+        // a panicking middleware drops the data instead of letting it pass unchecked
+        Err(_) => None,
+    }
+}
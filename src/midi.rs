@@ -0,0 +1,473 @@
+//! MIDI 1.0 over serial: parsing of the byte stream, running status included, and encoding of
+//! outgoing messages. The MIDI current loop runs at 31250 baud, USB adapters of DIY
+//! instruments often use other rates.
+
+use serde::{Deserialize, Serialize};
+
+/// Longest system exclusive message kept, longer ones are dropped.
+const MAX_SYSEX: usize = 64 * 1024;
+
+/// `MidiMessage` One message; channels are 0 to 15.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum MidiMessage {
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    /// A note on with velocity 0 is received as note off.
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    PolyPressure {
+        channel: u8,
+        note: u8,
+        pressure: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ChannelPressure {
+        channel: u8,
+        pressure: u8,
+    },
+    /// -8192 to 8191, 0 is the center.
+    PitchBend {
+        channel: u8,
+        value: i16,
+    },
+    /// The bytes between `F0` and `F7`.
+    SysEx {
+        data: Vec<u8>,
+    },
+    TimeCode {
+        value: u8,
+    },
+    SongPosition {
+        beats: u16,
+    },
+    SongSelect {
+        song: u8,
+    },
+    TuneRequest,
+    Clock,
+    Start,
+    Continue,
+    Stop,
+    ActiveSensing,
+    Reset,
+}
+
+/// `data_length` Data bytes following a channel or system common status.
+fn data_length(status: u8) -> usize {
+    match status {
+        0x80..=0xbf | 0xe0..=0xef | 0xf2 => 2,
+        0xc0..=0xdf | 0xf1 | 0xf3 => 1,
+        _ => 0,
+    }
+}
+
+fn message(status: u8, data: &[u8]) -> Option<MidiMessage> {
+    let channel = status & 0x0f;
+    let first = data.first().copied().unwrap_or(0);
+    let second = data.get(1).copied().unwrap_or(0);
+    Some(match status {
+        0x80..=0x8f => MidiMessage::NoteOff {
+            channel,
+            note: first,
+            velocity: second,
+        },
+        0x90..=0x9f if second == 0 => MidiMessage::NoteOff {
+            channel,
+            note: first,
+            velocity: 0,
+        },
+        0x90..=0x9f => MidiMessage::NoteOn {
+            channel,
+            note: first,
+            velocity: second,
+        },
+        0xa0..=0xaf => MidiMessage::PolyPressure {
+            channel,
+            note: first,
+            pressure: second,
+        },
+        0xb0..=0xbf => MidiMessage::ControlChange {
+            channel,
+            controller: first,
+            value: second,
+        },
+        0xc0..=0xcf => MidiMessage::ProgramChange {
+            channel,
+            program: first,
+        },
+        0xd0..=0xdf => MidiMessage::ChannelPressure {
+            channel,
+            pressure: first,
+        },
+        0xe0..=0xef => MidiMessage::PitchBend {
+            channel,
+            value: ((second as i16) << 7 | first as i16) - 8192,
+        },
+        0xf1 => MidiMessage::TimeCode { value: first },
+        0xf2 => MidiMessage::SongPosition {
+            beats: (second as u16) << 7 | first as u16,
+        },
+        0xf3 => MidiMessage::SongSelect { song: first },
+        0xf6 => MidiMessage::TuneRequest,
+        0xf8 => MidiMessage::Clock,
+        0xfa => MidiMessage::Start,
+        0xfb => MidiMessage::Continue,
+        0xfc => MidiMessage::Stop,
+        0xfe => MidiMessage::ActiveSensing,
+        0xff => MidiMessage::Reset,
+        _ => return None,
+    })
+}
+
+/// `MidiParser` The state of the received stream between reads.
+#[derive(Default)]
+pub struct MidiParser {
+    /// The running status, also kept for system common messages until they are complete.
+    status: Option<u8>,
+    data: Vec<u8>,
+    sysex: Option<Vec<u8>>,
+}
+
+impl MidiParser {
+    /// `feed` Collect received bytes, returns the messages they complete.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<MidiMessage> {
+        let mut messages = Vec::new();
+        for &byte in bytes {
+            match byte {
+                // real time messages may appear anywhere, even inside other messages
+                0xf8..=0xff => messages.extend(message(byte, &[])),
+                0xf0 => {
+                    self.status = None;
+                    self.sysex = Some(Vec::new());
+                }
+                0xf7 => {
+                    if let Some(data) = self.sysex.take() {
+                        messages.push(MidiMessage::SysEx { data });
+                    }
+                }
+                0x80..=0xf6 => {
+                    // any other status ends an unterminated system exclusive message
+                    self.sysex = None;
+                    self.data.clear();
+                    if data_length(byte) == 0 {
+                        self.status = None;
+                        messages.extend(message(byte, &[]));
+                    } else {
+                        self.status = Some(byte);
+                    }
+                }
+                _ => {
+                    if let Some(sysex) = self.sysex.as_mut() {
+                        if sysex.len() < MAX_SYSEX {
+                            sysex.push(byte);
+                        } else {
+                            self.sysex = None;
+                        }
+                        continue;
+                    }
+                    let status = match self.status {
+                        Some(status) => status,
+                        // data without status, e.g. after connecting mid-message
+                        None => continue,
+                    };
+                    self.data.push(byte);
+                    if self.data.len() == data_length(status) {
+                        messages.extend(message(status, &self.data));
+                        self.data.clear();
+                        if status >= 0xf0 {
+                            // system common messages cancel the running status
+                            self.status = None;
+                        }
+                    }
+                }
+            }
+        }
+        messages
+    }
+}
+
+fn check(name: &str, value: u8, maximum: u8) -> Result<u8, String> {
+    if value > maximum {
+        Err(format!("{} {} is above {}", name, value, maximum))
+    } else {
+        Ok(value)
+    }
+}
+
+impl MidiMessage {
+    /// `encode` The bytes of the message, always with its status byte.
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        let channel = |channel: u8, status: u8| check("Channel", channel, 15).map(|c| status | c);
+        let data = |value: u8| check("Data byte", value, 0x7f);
+        Ok(match self {
+            MidiMessage::NoteOff {
+                channel: c,
+                note,
+                velocity,
+            } => vec![channel(*c, 0x80)?, data(*note)?, data(*velocity)?],
+            MidiMessage::NoteOn {
+                channel: c,
+                note,
+                velocity,
+            } => vec![channel(*c, 0x90)?, data(*note)?, data(*velocity)?],
+            MidiMessage::PolyPressure {
+                channel: c,
+                note,
+                pressure,
+            } => vec![channel(*c, 0xa0)?, data(*note)?, data(*pressure)?],
+            MidiMessage::ControlChange {
+                channel: c,
+                controller,
+                value,
+            } => vec![channel(*c, 0xb0)?, data(*controller)?, data(*value)?],
+            MidiMessage::ProgramChange {
+                channel: c,
+                program,
+            } => vec![channel(*c, 0xc0)?, data(*program)?],
+            MidiMessage::ChannelPressure {
+                channel: c,
+                pressure,
+            } => vec![channel(*c, 0xd0)?, data(*pressure)?],
+            MidiMessage::PitchBend { channel: c, value } => {
+                if !(-8192..=8191).contains(value) {
+                    return Err(format!("Pitch bend {} is outside -8192 to 8191", value));
+                }
+                let raw = (value + 8192) as u16;
+                vec![channel(*c, 0xe0)?, (raw & 0x7f) as u8, (raw >> 7) as u8]
+            }
+            MidiMessage::SysEx { data: bytes } => {
+                let mut encoded = vec![0xf0];
+                for byte in bytes {
+                    encoded.push(data(*byte)?);
+                }
+                encoded.push(0xf7);
+                encoded
+            }
+            MidiMessage::TimeCode { value } => vec![0xf1, data(*value)?],
+            MidiMessage::SongPosition { beats } => {
+                if *beats > 0x3fff {
+                    return Err(format!("Song position {} is above 16383", beats));
+                }
+                vec![0xf2, (beats & 0x7f) as u8, (beats >> 7) as u8]
+            }
+            MidiMessage::SongSelect { song } => vec![0xf3, data(*song)?],
+            MidiMessage::TuneRequest => vec![0xf6],
+            MidiMessage::Clock => vec![0xf8],
+            MidiMessage::Start => vec![0xfa],
+            MidiMessage::Continue => vec![0xfb],
+            MidiMessage::Stop => vec![0xfc],
+            MidiMessage::ActiveSensing => vec![0xfe],
+            MidiMessage::Reset => vec![0xff],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_status_repeats_the_last_status() {
+        let mut parser = MidiParser::default();
+        let messages = parser.feed(&[0x91, 60, 100, 62, 90]);
+        assert_eq!(
+            messages,
+            vec![
+                MidiMessage::NoteOn {
+                    channel: 1,
+                    note: 60,
+                    velocity: 100
+                },
+                MidiMessage::NoteOn {
+                    channel: 1,
+                    note: 62,
+                    velocity: 90
+                },
+            ]
+        );
+        // split across reads
+        assert_eq!(parser.feed(&[64]), vec![]);
+        assert_eq!(
+            parser.feed(&[80]),
+            vec![MidiMessage::NoteOn {
+                channel: 1,
+                note: 64,
+                velocity: 80
+            }]
+        );
+    }
+
+    #[test]
+    fn system_common_cancels_running_status() {
+        let mut parser = MidiParser::default();
+        assert_eq!(
+            parser.feed(&[0xf3, 5, 7]),
+            vec![MidiMessage::SongSelect { song: 5 }]
+        );
+    }
+
+    #[test]
+    fn real_time_bytes_inside_messages() {
+        let mut parser = MidiParser::default();
+        let messages = parser.feed(&[0xb0, 0xf8, 7, 0xfe, 127]);
+        assert_eq!(
+            messages,
+            vec![
+                MidiMessage::Clock,
+                MidiMessage::ActiveSensing,
+                MidiMessage::ControlChange {
+                    channel: 0,
+                    controller: 7,
+                    value: 127
+                },
+            ]
+        );
+        let messages = parser.feed(&[0xf0, 0x7e, 0xf8, 0x01, 0xf7]);
+        assert_eq!(
+            messages,
+            vec![
+                MidiMessage::Clock,
+                MidiMessage::SysEx {
+                    data: vec![0x7e, 0x01]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn note_on_with_velocity_zero_is_note_off() {
+        let mut parser = MidiParser::default();
+        assert_eq!(
+            parser.feed(&[0x9f, 60, 0]),
+            vec![MidiMessage::NoteOff {
+                channel: 15,
+                note: 60,
+                velocity: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn pitch_bend_is_signed() {
+        let mut parser = MidiParser::default();
+        let messages = parser.feed(&[0xe0, 0x00, 0x00, 0x00, 0x40, 0x7f, 0x7f]);
+        let values: Vec<i16> = messages
+            .iter()
+            .map(|message| match message {
+                MidiMessage::PitchBend { value, .. } => *value,
+                _ => panic!("not a pitch bend"),
+            })
+            .collect();
+        assert_eq!(values, vec![-8192, 0, 8191]);
+        let bend = |value| MidiMessage::PitchBend { channel: 0, value }.encode();
+        assert_eq!(bend(-8192).unwrap(), vec![0xe0, 0x00, 0x00]);
+        assert_eq!(bend(8191).unwrap(), vec![0xe0, 0x7f, 0x7f]);
+        assert!(bend(8192).is_err());
+        assert!(bend(-8193).is_err());
+    }
+
+    #[test]
+    fn overlong_sysex_is_dropped() {
+        let mut parser = MidiParser::default();
+        let mut bytes = vec![0xf0];
+        bytes.extend(std::iter::repeat(0x11).take(MAX_SYSEX + 1));
+        bytes.push(0xf7);
+        assert_eq!(parser.feed(&bytes), vec![]);
+        assert_eq!(
+            parser.feed(&[0xc2, 9]),
+            vec![MidiMessage::ProgramChange {
+                channel: 2,
+                program: 9
+            }]
+        );
+    }
+
+    #[test]
+    fn encoded_messages_parse_back() {
+        let messages = vec![
+            MidiMessage::NoteOff {
+                channel: 3,
+                note: 64,
+                velocity: 12,
+            },
+            MidiMessage::NoteOn {
+                channel: 3,
+                note: 64,
+                velocity: 1,
+            },
+            MidiMessage::PolyPressure {
+                channel: 4,
+                note: 1,
+                pressure: 2,
+            },
+            MidiMessage::ControlChange {
+                channel: 5,
+                controller: 64,
+                value: 127,
+            },
+            MidiMessage::ProgramChange {
+                channel: 6,
+                program: 0,
+            },
+            MidiMessage::ChannelPressure {
+                channel: 7,
+                pressure: 99,
+            },
+            MidiMessage::PitchBend {
+                channel: 8,
+                value: -1234,
+            },
+            MidiMessage::SysEx {
+                data: vec![0x43, 0x10, 0x4c],
+            },
+            MidiMessage::TimeCode { value: 0x35 },
+            MidiMessage::SongPosition { beats: 0x3fff },
+            MidiMessage::SongSelect { song: 3 },
+            MidiMessage::TuneRequest,
+            MidiMessage::Clock,
+            MidiMessage::Start,
+            MidiMessage::Continue,
+            MidiMessage::Stop,
+            MidiMessage::ActiveSensing,
+            MidiMessage::Reset,
+        ];
+        let mut bytes = Vec::new();
+        for message in &messages {
+            bytes.extend(message.encode().unwrap());
+        }
+        assert_eq!(MidiParser::default().feed(&bytes), messages);
+    }
+
+    #[test]
+    fn out_of_range_values_are_not_encoded() {
+        let note = |channel, note| {
+            MidiMessage::NoteOn {
+                channel,
+                note,
+                velocity: 1,
+            }
+            .encode()
+        };
+        assert!(note(16, 60).is_err());
+        assert!(note(0, 128).is_err());
+        assert!(MidiMessage::SongPosition { beats: 0x4000 }
+            .encode()
+            .is_err());
+        assert!(MidiMessage::SysEx { data: vec![0xf7] }.encode().is_err());
+    }
+}
@@ -0,0 +1,424 @@
+//! Several virtual streams sharing one physical port, e.g. the debug console and the data
+//! channel of a device. Once a port is split, each channel is opened as its own port with a
+//! path like `mux://<port>:<channel>` and read, written and closed like any other.
+//!
+//! The framing is the magic `A5 6D`, the channel, the payload length as u16 little endian, the
+//! payload and the CRC-16/CCITT-FALSE of channel, length and payload as u16 little endian.
+//! Bytes outside valid frames are dropped, or delivered to the `unframed` channel, e.g. for
+//! boot messages printed before the firmware starts framing.
+
+use crate::reader::{classify, ReadOutcome};
+use crate::schema::{checksum, Algorithm};
+use crate::transport::{LineSettings, Transport, TransportPort};
+use serialport::{ClearBuffer, SerialPort};
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+pub const PREFIX: &str = "mux://";
+
+const MAGIC: [u8; 2] = [0xa5, 0x6d];
+const HEADER_SIZE: usize = 5;
+const CRC_SIZE: usize = 2;
+/// Longest payload of a frame, longer writes are split.
+pub const MAX_PAYLOAD: usize = 1024;
+/// Bytes a channel holds for its reader, the oldest are dropped beyond.
+const CAPACITY: usize = 64 * 1024;
+
+/// `Encoder` The frames that carry data written to a channel.
+pub type Encoder = dyn Fn(u8, &[u8]) -> Vec<u8> + Send + Sync;
+
+/// `Inbox` Data received per channel and why the port stopped delivering, once it did.
+#[derive(Default)]
+struct Inbox {
+    channels: HashMap<u8, VecDeque<u8>>,
+    closed: Option<String>,
+}
+
+/// `Demux` The channels of a split port: the data received for each of them and the port writes
+/// go to, shared by the reading thread and the channel ports.
+pub struct Demux {
+    kind: &'static str,
+    writer: Mutex<Box<dyn SerialPort>>,
+    encode: Box<Encoder>,
+    inbox: Mutex<Inbox>,
+    arrived: Condvar,
+    /// The channels that may be opened, all unless limited.
+    channels: Mutex<Option<Vec<u8>>>,
+}
+
+fn lock_error<T: std::fmt::Display>(error: T) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error.to_string())
+}
+
+impl Demux {
+    /// `new` Channels written through `writer`, a clone of the split port.
+    pub fn new(kind: &'static str, writer: Box<dyn SerialPort>, encode: Box<Encoder>) -> Self {
+        Demux {
+            kind,
+            writer: Mutex::new(writer),
+            encode,
+            inbox: Mutex::new(Inbox::default()),
+            arrived: Condvar::new(),
+            channels: Mutex::new(None),
+        }
+    }
+
+    /// `limit` Only `channels` may be opened from now on.
+    pub fn limit(&self, channels: Vec<u8>) {
+        if let Ok(mut limited) = self.channels.lock() {
+            *limited = Some(channels);
+        }
+    }
+
+    /// `accepts` Whether `channel` may be opened.
+    pub fn accepts(&self, channel: u8) -> bool {
+        match self.channels.lock() {
+            Ok(limited) => limited.as_ref().map_or(true, |channels| channels.contains(&channel)),
+            Err(_) => false,
+        }
+    }
+
+    /// `deliver` Data received for `channel`.
+    pub fn deliver(&self, channel: u8, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        if let Ok(mut inbox) = self.inbox.lock() {
+            let buffer = inbox.channels.entry(channel).or_default();
+            buffer.extend(data);
+            if buffer.len() > CAPACITY {
+                let overflow = buffer.len() - CAPACITY;
+                buffer.drain(..overflow);
+                warn!(channel, overflow, "Channel buffer full, oldest data dropped");
+            }
+            self.arrived.notify_all();
+        }
+    }
+
+    /// `close` The port stopped delivering, the channel reads fail from now on.
+    pub fn close(&self, reason: String) {
+        if let Ok(mut inbox) = self.inbox.lock() {
+            inbox.closed = Some(reason);
+            self.arrived.notify_all();
+        }
+    }
+
+    /// `reset` Forget what was received for `channel` before it was opened.
+    fn reset(&self, channel: u8) {
+        if let Ok(mut inbox) = self.inbox.lock() {
+            inbox.channels.remove(&channel);
+        }
+    }
+
+    /// `send` Write raw frames to the port.
+    pub fn send(&self, frames: &[u8]) -> io::Result<()> {
+        let mut writer = self.writer.lock().map_err(lock_error)?;
+        writer.write_all(frames)?;
+        writer.flush()
+    }
+
+    fn read(&self, channel: u8, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let deadline = Instant::now() + timeout;
+        let mut inbox = self.inbox.lock().map_err(lock_error)?;
+        loop {
+            if let Some(buffer) = inbox.channels.get_mut(&channel) {
+                if !buffer.is_empty() {
+                    let size = buf.len().min(buffer.len());
+                    for (byte, received) in buf.iter_mut().zip(buffer.drain(..size)) {
+                        *byte = received;
+                    }
+                    return Ok(size);
+                }
+            }
+            if let Some(reason) = &inbox.closed {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, reason.clone()));
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "No data received"));
+            }
+            inbox = self
+                .arrived
+                .wait_timeout(inbox, deadline - now)
+                .map_err(lock_error)?
+                .0;
+        }
+    }
+
+    fn pending(&self, channel: u8) -> u32 {
+        match self.inbox.lock() {
+            Ok(inbox) => inbox.channels.get(&channel).map_or(0, |buffer| buffer.len() as u32),
+            Err(_) => 0,
+        }
+    }
+}
+
+/// `Mux` The channels of a split port, the reading thread stops when dropped.
+pub struct Mux {
+    pub demux: Arc<Demux>,
+    pub _stop: Sender<()>,
+}
+
+/// `ChannelTransport` One channel of a split port.
+struct ChannelTransport {
+    demux: Arc<Demux>,
+    channel: u8,
+}
+
+impl Transport for ChannelTransport {
+    fn kind(&self) -> &'static str {
+        self.demux.kind
+    }
+
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        self.demux.read(self.channel, buf, timeout)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.demux.send(&(self.demux.encode)(self.channel, buf))?;
+        Ok(buf.len())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn Transport>> {
+        Ok(Box::new(ChannelTransport {
+            demux: self.demux.clone(),
+            channel: self.channel,
+        }))
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self.demux.pending(self.channel))
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        if buffer_to_clear != ClearBuffer::Output {
+            self.demux.reset(self.channel);
+        }
+        Ok(())
+    }
+}
+
+/// `parse_path` The split port and the channel of a `mux://<port>:<channel>` path.
+pub fn parse_path(path: &str) -> Option<(&str, u8)> {
+    let (port, channel) = path.strip_prefix(PREFIX)?.rsplit_once(':')?;
+    Some((port, channel.parse().ok()?))
+}
+
+/// `open` The port of one channel, data received for it before is dropped.
+pub fn open(
+    path: &str,
+    demux: Arc<Demux>,
+    channel: u8,
+    settings: LineSettings,
+) -> serialport::Result<Box<dyn SerialPort>> {
+    demux.reset(channel);
+    TransportPort::open(path, Box::new(ChannelTransport { demux, channel }), settings)
+}
+
+fn crc(data: &[u8]) -> [u8; CRC_SIZE] {
+    (checksum(Algorithm::Crc16Ccitt, data) as u16).to_le_bytes()
+}
+
+/// `encode` The frames of the channel-id framing.
+pub fn encode(channel: u8, data: &[u8]) -> Vec<u8> {
+    let mut frames = Vec::with_capacity(data.len() + HEADER_SIZE + CRC_SIZE);
+    for chunk in data.chunks(MAX_PAYLOAD) {
+        let start = frames.len();
+        frames.extend_from_slice(&MAGIC);
+        frames.push(channel);
+        frames.extend_from_slice(&(chunk.len() as u16).to_le_bytes());
+        frames.extend_from_slice(chunk);
+        let crc = crc(&frames[start + MAGIC.len()..]);
+        frames.extend_from_slice(&crc);
+    }
+    frames
+}
+
+/// `Deframer` Splits the received stream into the payloads of the channels.
+pub struct Deframer {
+    raw: Vec<u8>,
+    unframed: Option<u8>,
+}
+
+impl Deframer {
+    pub fn new(unframed: Option<u8>) -> Self {
+        Deframer {
+            raw: Vec::new(),
+            unframed,
+        }
+    }
+
+    /// `feed` Collect received bytes, returns the channels and payloads they complete.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        self.raw.extend_from_slice(data);
+        let mut payloads = Vec::new();
+        let mut start = 0;
+        while let Some(size) = self.next_frame(start, &mut payloads) {
+            start += size;
+        }
+        self.raw.drain(..start);
+        payloads
+    }
+
+    /// `skip` Bytes that are no frame.
+    fn skip(&self, bytes: &[u8], payloads: &mut Vec<(u8, Vec<u8>)>) -> Option<usize> {
+        if bytes.is_empty() {
+            return None;
+        }
+        if let Some(channel) = self.unframed {
+            payloads.push((channel, bytes.to_vec()));
+        }
+        Some(bytes.len())
+    }
+
+    fn next_frame(&mut self, start: usize, payloads: &mut Vec<(u8, Vec<u8>)>) -> Option<usize> {
+        let raw = &self.raw[start..];
+        let found = match raw.windows(2).position(|window| window == MAGIC) {
+            Some(found) => found,
+            // a magic may begin with the last byte
+            None if raw.last() == Some(&MAGIC[0]) => {
+                return self.skip(&raw[..raw.len() - 1], payloads)
+            }
+            None => return self.skip(raw, payloads),
+        };
+        if found > 0 {
+            return self.skip(&raw[..found], payloads);
+        }
+        if raw.len() < HEADER_SIZE {
+            return None;
+        }
+        let length = u16::from_le_bytes([raw[3], raw[4]]) as usize;
+        if length > MAX_PAYLOAD {
+            return self.skip(&raw[..1], payloads);
+        }
+        let size = HEADER_SIZE + length + CRC_SIZE;
+        if raw.len() < size {
+            return None;
+        }
+        if crc(&raw[MAGIC.len()..size - CRC_SIZE])[..] != raw[size - CRC_SIZE..size] {
+            // no frame after all, or a damaged one, the next may start inside it
+            debug!("Multiplexed frame with a wrong CRC skipped");
+            return self.skip(&raw[..1], payloads);
+        }
+        payloads.push((raw[2], raw[HEADER_SIZE..HEADER_SIZE + length].to_vec()));
+        Some(size)
+    }
+}
+
+/// `run` Read the split port and deliver the frames to the channels until `stop` is dropped or
+/// the port fails.
+pub fn run(
+    mut port: Box<dyn SerialPort>,
+    demux: Arc<Demux>,
+    mut deframer: Deframer,
+    stop: Receiver<()>,
+) {
+    let mut buf = [0u8; 4096];
+    let reason = loop {
+        if !matches!(stop.try_recv(), Err(TryRecvError::Empty)) {
+            break "The multiplexer was stopped".to_string();
+        }
+        match classify(port.read(&mut buf)) {
+            ReadOutcome::Data(size) => {
+                for (channel, payload) in deframer.feed(&buf[..size]) {
+                    demux.deliver(channel, &payload);
+                }
+            }
+            ReadOutcome::Idle => {}
+            ReadOutcome::Disconnected(reason) => break format!("Port disconnected: {}", reason),
+            ReadOutcome::Failed(error) => break format!("Failed to read port: {}", error),
+        }
+    };
+    info!(reason = %reason, "Multiplexer stopped");
+    demux.close(reason);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crc_of(data: &[u8]) -> u16 {
+        u16::from_le_bytes(crc(data))
+    }
+
+    #[test]
+    fn frame_layout() {
+        let frame = encode(3, b"hi");
+        assert_eq!(&frame[..7], &[0xa5, 0x6d, 0x03, 0x02, 0x00, b'h', b'i']);
+        let crc = checksum(Algorithm::Crc16Ccitt, &[0x03, 0x02, 0x00, b'h', b'i']) as u16;
+        assert_eq!(&frame[7..], &crc.to_le_bytes());
+        // CRC-16/CCITT-FALSE check value
+        assert_eq!(crc_of(b"123456789"), 0x29b1);
+    }
+
+    #[test]
+    fn long_writes_are_split() {
+        let data = vec![0x55; MAX_PAYLOAD + 10];
+        let frames = encode(1, &data);
+        assert_eq!(frames.len(), data.len() + 2 * (HEADER_SIZE + CRC_SIZE));
+        let payloads = Deframer::new(None).feed(&frames);
+        assert_eq!(payloads.len(), 2);
+        assert_eq!(payloads[0], (1, vec![0x55; MAX_PAYLOAD]));
+        assert_eq!(payloads[1], (1, vec![0x55; 10]));
+    }
+
+    #[test]
+    fn frames_across_reads() {
+        let mut stream = encode(0, b"console");
+        stream.extend(encode(7, b"data"));
+        let mut deframer = Deframer::new(None);
+        let mut payloads = Vec::new();
+        for byte in &stream {
+            payloads.extend(deframer.feed(&[*byte]));
+        }
+        assert_eq!(
+            payloads,
+            vec![(0, b"console".to_vec()), (7, b"data".to_vec())]
+        );
+    }
+
+    #[test]
+    fn unframed_bytes_go_to_their_channel() {
+        let mut stream = b"boot\r\n".to_vec();
+        stream.extend(encode(1, b"framed"));
+        stream.push(b'!');
+        let payloads = Deframer::new(Some(9)).feed(&stream);
+        assert_eq!(
+            payloads,
+            vec![
+                (9, b"boot\r\n".to_vec()),
+                (1, b"framed".to_vec()),
+                (9, b"!".to_vec())
+            ]
+        );
+        assert!(Deframer::new(None).feed(b"boot\r\n").is_empty());
+    }
+
+    #[test]
+    fn damaged_frames_are_skipped() {
+        let mut damaged = encode(1, b"lost");
+        damaged[6] ^= 0x01;
+        let mut stream = damaged.clone();
+        stream.extend(encode(2, b"kept"));
+        let mut deframer = Deframer::new(None);
+        assert_eq!(deframer.feed(&stream), vec![(2, b"kept".to_vec())]);
+        // a magic with an impossible length is no frame either
+        let mut stream = vec![0xa5, 0x6d, 0x01, 0xff, 0xff];
+        stream.extend(encode(3, b"x"));
+        assert_eq!(deframer.feed(&stream), vec![(3, b"x".to_vec())]);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            parse_path("mux:///dev/ttyUSB0:2"),
+            Some(("/dev/ttyUSB0", 2))
+        );
+        assert_eq!(parse_path("mux://COM3:255"), Some(("COM3", 255)));
+        assert_eq!(parse_path("mux://COM3:256"), None);
+        assert_eq!(parse_path("/dev/ttyUSB0:2"), None);
+    }
+}
@@ -0,0 +1,385 @@
+//! OBD-II through an ELM327 adapter: the initialization of the adapter, the protocol search,
+//! the decoding of the mode 01 PIDs and the diagnostic trouble codes of modes 03, 07 and 04.
+//!
+//! The adapter answers each command with its lines and the `>` prompt. Echo, linefeeds, spaces
+//! and headers are switched off, so every line of a response is the hex of one message.
+
+use serde::Serialize;
+use serialport::{ClearBuffer, SerialPort};
+use std::io;
+use std::time::{Duration, Instant};
+
+const PROMPT: u8 = b'>';
+/// `ATZ` restarts the adapter, which takes about a second.
+const RESET_TIMEOUT: Duration = Duration::from_secs(3);
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// `ObdInfo` The adapter and the vehicle bus it found.
+#[derive(Serialize, Clone, Debug)]
+pub struct ObdInfo {
+    /// The identification the adapter prints on reset, e.g. `ELM327 v1.5`.
+    pub version: String,
+    /// The protocol of `ATDP`, e.g. `ISO 15765-4 (CAN 11/500)`.
+    pub protocol: String,
+    /// The battery voltage of `ATRV`, e.g. `12.6V`.
+    pub voltage: Option<String>,
+}
+
+/// `PidValue` A decoded mode 01 PID.
+#[derive(Serialize, Clone, Debug)]
+pub struct PidValue {
+    pub pid: u8,
+    pub name: Option<&'static str>,
+    /// `None` for PIDs without a known formula, `data` holds their bytes.
+    pub value: Option<f64>,
+    pub unit: Option<&'static str>,
+    pub data: Vec<u8>,
+}
+
+/// `exchange` Send `command` and return the lines of the answer up to the prompt.
+fn exchange(
+    port: &mut dyn SerialPort,
+    command: &str,
+    timeout: Duration,
+) -> Result<Vec<String>, String> {
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    port.write_all(format!("{}\r", command).as_bytes())
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write {}: {}", command, error))?;
+    let deadline = Instant::now() + timeout;
+    let mut received = Vec::new();
+    let mut buf = [0u8; 256];
+    while !received.contains(&PROMPT) {
+        if Instant::now() >= deadline {
+            return Err(format!("The adapter did not answer {}", command));
+        }
+        match port.read(&mut buf) {
+            Ok(size) => received.extend_from_slice(&buf[..size]),
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read the answer: {}", error)),
+        }
+    }
+    let end = received
+        .iter()
+        .position(|byte| *byte == PROMPT)
+        .unwrap_or(0);
+    let lines: Vec<String> = String::from_utf8_lossy(&received[..end])
+        .split(['\r', '\n'])
+        .map(|line| line.trim().to_string())
+        // the echo, before `ATE0` took effect
+        .filter(|line| !line.is_empty() && !line.eq_ignore_ascii_case(command))
+        .collect();
+    if lines.iter().any(|line| line == "?") {
+        return Err(format!("The adapter does not know {}", command));
+    }
+    Ok(lines)
+}
+
+/// `expect_ok` Run a setting command of the adapter.
+fn expect_ok(port: &mut dyn SerialPort, command: &str) -> Result<(), String> {
+    let lines = exchange(port, command, TIMEOUT)?;
+    match lines.last().map(String::as_str) {
+        Some("OK") => Ok(()),
+        _ => Err(format!("{} failed: {}", command, lines.join(" "))),
+    }
+}
+
+/// `initialize` Reset the adapter, set it up for parsing and connect to the vehicle with
+/// `protocol` (`ATSP` number, `0` searches) and the optional request `header`.
+pub fn initialize(
+    port: &mut dyn SerialPort,
+    protocol: Option<&str>,
+    header: Option<&str>,
+    timeout: Duration,
+) -> Result<ObdInfo, String> {
+    let version = exchange(port, "ATZ", RESET_TIMEOUT)?
+        .into_iter()
+        .find(|line| line.starts_with("ELM"))
+        .unwrap_or_default();
+    for command in ["ATE0", "ATL0", "ATS0", "ATH0"] {
+        expect_ok(port, command)?;
+    }
+    expect_ok(port, &format!("ATSP{}", protocol.unwrap_or("0")))?;
+    if let Some(header) = header {
+        expect_ok(port, &format!("ATSH{}", header))?;
+    }
+    // the first request connects, searching through the protocols when automatic
+    let messages = request(port, "0100", timeout)?;
+    if !messages
+        .iter()
+        .any(|message| message.starts_with(&[0x41, 0x00]))
+    {
+        return Err("The vehicle did not answer PID 00".to_string());
+    }
+    let protocol = exchange(port, "ATDP", TIMEOUT)?.join(" ");
+    let voltage = exchange(port, "ATRV", TIMEOUT)
+        .ok()
+        .and_then(|lines| lines.into_iter().next());
+    Ok(ObdInfo {
+        version,
+        protocol: protocol.trim_start_matches("AUTO, ").to_string(),
+        voltage,
+    })
+}
+
+/// `request` Send an OBD request and return the messages of the ECUs that answered.
+fn request(
+    port: &mut dyn SerialPort,
+    command: &str,
+    timeout: Duration,
+) -> Result<Vec<Vec<u8>>, String> {
+    let lines = exchange(port, command, timeout)?;
+    messages(command, &lines)
+}
+
+/// `messages` The messages in the answer lines to `command`. Multi frame CAN answers, a byte
+/// count line followed by `0:`, `1:`, ..., are joined into one message.
+fn messages(command: &str, lines: &[String]) -> Result<Vec<Vec<u8>>, String> {
+    let mut messages = Vec::new();
+    let mut multi: Option<(usize, Vec<u8>)> = None;
+    for line in lines {
+        let upper = line.to_ascii_uppercase();
+        if upper.starts_with("SEARCHING") || upper.starts_with("BUS INIT") {
+            continue;
+        }
+        if upper.contains("NO DATA") {
+            return Err(format!("No ECU answered {}", command));
+        }
+        if upper.contains("UNABLE TO CONNECT") {
+            return Err("The adapter found no vehicle bus".to_string());
+        }
+        if upper.contains("ERROR") || upper.contains("STOPPED") || upper.contains("BUS BUSY") {
+            return Err(format!("{} failed: {}", command, line));
+        }
+        if line.len() == 3 && multi.is_none() {
+            if let Ok(length) = usize::from_str_radix(line, 16) {
+                multi = Some((length, Vec::new()));
+                continue;
+            }
+        }
+        match (&mut multi, line.split_once(':')) {
+            (Some((_, data)), Some((_, hex))) => data.extend(from_hex(hex.trim())?),
+            _ => messages.push(from_hex(line)?),
+        }
+    }
+    if let Some((length, mut data)) = multi {
+        data.truncate(length);
+        messages.push(data);
+    }
+    Ok(messages)
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let hex: String = hex.chars().filter(|char| !char.is_whitespace()).collect();
+    crate::sms::from_hex(&hex).map_err(|_| format!("Unexpected answer: {}", hex))
+}
+
+/// `decode` Name, value and unit of a PID from its data bytes `A`, `B`, ...
+fn decode(pid: u8, data: &[u8]) -> Option<(&'static str, f64, &'static str)> {
+    let a = *data.first()? as f64;
+    let ab = || data.get(1).map(|b| a * 256.0 + *b as f64);
+    let trim = (a - 128.0) * 100.0 / 128.0;
+    let decoded = match pid {
+        0x04 => ("Engine load", a * 100.0 / 255.0, "%"),
+        0x05 => ("Coolant temperature", a - 40.0, "°C"),
+        0x06 => ("Short term fuel trim bank 1", trim, "%"),
+        0x07 => ("Long term fuel trim bank 1", trim, "%"),
+        0x08 => ("Short term fuel trim bank 2", trim, "%"),
+        0x09 => ("Long term fuel trim bank 2", trim, "%"),
+        0x0a => ("Fuel pressure", a * 3.0, "kPa"),
+        0x0b => ("Intake manifold pressure", a, "kPa"),
+        0x0c => ("Engine speed", ab()? / 4.0, "rpm"),
+        0x0d => ("Vehicle speed", a, "km/h"),
+        0x0e => ("Timing advance", a / 2.0 - 64.0, "°"),
+        0x0f => ("Intake air temperature", a - 40.0, "°C"),
+        0x10 => ("Mass air flow", ab()? / 100.0, "g/s"),
+        0x11 => ("Throttle position", a * 100.0 / 255.0, "%"),
+        0x1f => ("Run time since engine start", ab()?, "s"),
+        0x21 => ("Distance with MIL on", ab()?, "km"),
+        0x2f => ("Fuel level", a * 100.0 / 255.0, "%"),
+        0x31 => ("Distance since codes cleared", ab()?, "km"),
+        0x33 => ("Barometric pressure", a, "kPa"),
+        0x42 => ("Control module voltage", ab()? / 1000.0, "V"),
+        0x45 => ("Relative throttle position", a * 100.0 / 255.0, "%"),
+        0x46 => ("Ambient air temperature", a - 40.0, "°C"),
+        0x5c => ("Engine oil temperature", a - 40.0, "°C"),
+        0x5e => ("Engine fuel rate", ab()? / 20.0, "L/h"),
+        _ => return None,
+    };
+    Some(decoded)
+}
+
+/// `read_pid` Read and decode a mode 01 PID; the first ECU answering it is used.
+pub fn read_pid(port: &mut dyn SerialPort, pid: u8, timeout: Duration) -> Result<PidValue, String> {
+    let messages = request(port, &format!("01{:02X}", pid), timeout)?;
+    let data = messages
+        .into_iter()
+        .find_map(|message| match message[..] {
+            [0x41, answered, ..] if answered == pid => Some(message[2..].to_vec()),
+            _ => None,
+        })
+        .ok_or_else(|| format!("No ECU answered PID {:02X}", pid))?;
+    let decoded = decode(pid, &data);
+    Ok(PidValue {
+        pid,
+        name: decoded.map(|(name, _, _)| name),
+        value: decoded.map(|(_, value, _)| value),
+        unit: decoded.map(|(_, _, unit)| unit),
+        data,
+    })
+}
+
+/// `dtc` A trouble code from its two bytes, e.g. `01 33` is `P0133`.
+fn dtc(high: u8, low: u8) -> String {
+    let system = ['P', 'C', 'B', 'U'][(high >> 6) as usize];
+    format!(
+        "{}{}{:01X}{:02X}",
+        system,
+        (high >> 4) & 0x03,
+        high & 0x0f,
+        low
+    )
+}
+
+/// `read_dtcs` The stored trouble codes (mode 03), or the pending ones (mode 07), of all ECUs.
+pub fn read_dtcs(
+    port: &mut dyn SerialPort,
+    pending: bool,
+    timeout: Duration,
+) -> Result<Vec<String>, String> {
+    let (mode, answer) = if pending { ("07", 0x47) } else { ("03", 0x43) };
+    let messages = match request(port, mode, timeout) {
+        Ok(messages) => messages,
+        // ECUs without codes may stay silent
+        Err(error) if error.starts_with("No ECU answered") => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    Ok(dtcs(messages, answer))
+}
+
+/// `dtcs` The trouble codes in the answers of mode 03 or 07, `answer` is the mode plus `0x40`.
+fn dtcs(messages: Vec<Vec<u8>>, answer: u8) -> Vec<String> {
+    let mut codes = Vec::new();
+    for message in messages {
+        let data = match message.split_first() {
+            Some((first, data)) if *first == answer => data,
+            _ => continue,
+        };
+        // CAN answers count the codes first, the older buses fill three pairs per message
+        let pairs = if data.len() % 2 == 1 {
+            &data[1..]
+        } else {
+            data
+        };
+        for pair in pairs.chunks_exact(2) {
+            if pair != [0, 0] {
+                codes.push(dtc(pair[0], pair[1]));
+            }
+        }
+    }
+    codes
+}
+
+/// `clear_dtcs` Clear the trouble codes and the freeze frames (mode 04), which also turns off
+/// the MIL.
+pub fn clear_dtcs(port: &mut dyn SerialPort, timeout: Duration) -> Result<(), String> {
+    let messages = request(port, "04", timeout)?;
+    if messages
+        .iter()
+        .any(|message| message.first() == Some(&0x44))
+    {
+        Ok(())
+    } else {
+        Err("No ECU acknowledged clearing the trouble codes".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn single_frame_answers_of_several_ecus() {
+        let answer = lines(&["SEARCHING...", "410C1AF8", "41 0C 0F A0"]);
+        assert_eq!(
+            messages("010C", &answer).unwrap(),
+            vec![vec![0x41, 0x0c, 0x1a, 0xf8], vec![0x41, 0x0c, 0x0f, 0xa0]]
+        );
+    }
+
+    #[test]
+    fn multi_frame_answer_is_joined() {
+        // the VIN, 20 bytes over three CAN frames
+        let answer = lines(&[
+            "014",
+            "0: 49 02 01 57 50 30",
+            "1: 5A 5A 5A 39 39 5A 54",
+            "2: 53 33 39 32 31 32 34",
+        ]);
+        let messages = messages("0902", &answer).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].len(), 0x14);
+        assert_eq!(&messages[0][..3], &[0x49, 0x02, 0x01]);
+        assert_eq!(&messages[0][3..], b"WP0ZZZ99ZTS392124");
+    }
+
+    #[test]
+    fn adapter_errors() {
+        assert!(messages("0100", &lines(&["NO DATA"]))
+            .unwrap_err()
+            .starts_with("No ECU answered"));
+        assert!(messages("0100", &lines(&["SEARCHING...", "UNABLE TO CONNECT"])).is_err());
+        assert!(messages("0100", &lines(&["CAN ERROR"])).is_err());
+        assert!(messages("0100", &lines(&["41 0X"])).is_err());
+    }
+
+    #[test]
+    fn pids_are_decoded() {
+        assert_eq!(
+            decode(0x0c, &[0x1a, 0xf8]),
+            Some(("Engine speed", 1726.0, "rpm"))
+        );
+        assert_eq!(
+            decode(0x05, &[0x7b]),
+            Some(("Coolant temperature", 83.0, "°C"))
+        );
+        assert_eq!(
+            decode(0x06, &[0x80]),
+            Some(("Short term fuel trim bank 1", 0.0, "%"))
+        );
+        assert_eq!(
+            decode(0x42, &[0x31, 0x2d]),
+            Some(("Control module voltage", 12.589, "V"))
+        );
+        // a two byte PID with one byte, and an unknown PID
+        assert_eq!(decode(0x0c, &[0x1a]), None);
+        assert_eq!(decode(0x20, &[0x00, 0x00, 0x00, 0x00]), None);
+        assert_eq!(decode(0x05, &[]), None);
+    }
+
+    #[test]
+    fn trouble_codes() {
+        assert_eq!(dtc(0x01, 0x33), "P0133");
+        assert_eq!(dtc(0x41, 0x23), "C0123");
+        assert_eq!(dtc(0x92, 0x34), "B1234");
+        assert_eq!(dtc(0xc1, 0x00), "U0100");
+        // CAN with the count first, a CAN ECU without codes, an older bus with padded pairs
+        // and another mode
+        let messages = vec![
+            vec![0x43, 0x02, 0x01, 0x33, 0x41, 0x23],
+            vec![0x43, 0x00],
+            vec![0x43, 0xc1, 0x00, 0x00, 0x00, 0x00, 0x00],
+            vec![0x47, 0x01, 0x01, 0x71],
+        ];
+        assert_eq!(
+            dtcs(messages.clone(), 0x43),
+            vec!["P0133", "C0123", "U0100"]
+        );
+        assert_eq!(dtcs(messages, 0x47), vec!["P0171"]);
+    }
+}
@@ -0,0 +1,104 @@
+//! Long-running operations with an id, which `cancel_operation` aborts between two steps and
+//! which report their progress in the same shape.
+
+use crate::reader::now_millis;
+use crate::state::ProgressData;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Progress is reported at most this often, besides the final step.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// `Operations` The cancel flags of the running operations, by id.
+#[derive(Default)]
+pub struct Operations {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl Operations {
+    /// `start` Register an operation, the id must not be in use.
+    pub fn start(self: &Arc<Self>, id: &str) -> Result<Operation, String> {
+        let mut running = self
+            .running
+            .lock()
+            .map_err(|error| format!("Cannot get operations lock: {}", error))?;
+        if running.contains_key(id) {
+            return Err(format!("Operation {} is already running", id));
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        running.insert(id.to_string(), cancelled.clone());
+        Ok(Operation {
+            id: id.to_string(),
+            cancelled,
+            operations: self.clone(),
+            started: Instant::now(),
+            reported: None,
+        })
+    }
+
+    /// `cancel` Ask a running operation to stop, `false` if none has the id.
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.running.lock() {
+            Ok(running) => match running.get(id) {
+                Some(cancelled) => {
+                    cancelled.store(true, Ordering::Relaxed);
+                    true
+                }
+                None => false,
+            },
+            Err(_) => false,
+        }
+    }
+}
+
+/// `Operation` A registered operation, unregistered when dropped.
+pub struct Operation {
+    pub id: String,
+    cancelled: Arc<AtomicBool>,
+    operations: Arc<Operations>,
+    started: Instant,
+    reported: Option<Instant>,
+}
+
+impl Operation {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// `progress` The progress event after `done` of `total` bytes, `None` while the last one
+    /// is recent. The ETA assumes the rate so far.
+    pub fn progress(&mut self, done: u64, total: Option<u64>, phase: &str) -> Option<ProgressData> {
+        let finished = total.map_or(false, |total| done >= total);
+        if !finished
+            && self
+                .reported
+                .map_or(false, |at| at.elapsed() < PROGRESS_INTERVAL)
+        {
+            return None;
+        }
+        self.reported = Some(Instant::now());
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        let eta_ms = match total {
+            Some(total) if done > 0 => Some(elapsed * total.saturating_sub(done) / done),
+            _ => None,
+        };
+        Some(ProgressData {
+            id: self.id.clone(),
+            done,
+            total,
+            phase: phase.to_string(),
+            eta_ms,
+            timestamp: now_millis(),
+        })
+    }
+}
+
+impl Drop for Operation {
+    fn drop(&mut self) {
+        if let Ok(mut running) = self.operations.running.lock() {
+            running.remove(&self.id);
+        }
+    }
+}
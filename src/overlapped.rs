@@ -0,0 +1,410 @@
+//! Native Windows ports opened for overlapped I/O, so a read waits for its own completion
+//! instead of blocking the handle, and reads can wait with `WaitCommEvent` until data arrives.
+
+use serialport::{ClearBuffer, COMPort, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::io;
+use std::os::windows::io::{AsRawHandle, FromRawHandle};
+use std::time::Duration;
+use windows_sys::Win32::Devices::Communication::{
+    GetCommState, SetCommMask, SetCommState, WaitCommEvent, DCB, EV_RXCHAR,
+};
+use windows_sys::Win32::Foundation::{
+    CloseHandle, DuplicateHandle, GetLastError, DUPLICATE_SAME_ACCESS, ERROR_IO_INCOMPLETE,
+    ERROR_IO_PENDING, FALSE, GENERIC_READ, GENERIC_WRITE, HANDLE, INVALID_HANDLE_VALUE, TRUE,
+};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, ReadFile, WriteFile, FILE_FLAG_OVERLAPPED, OPEN_EXISTING,
+};
+use windows_sys::Win32::System::Threading::{CreateEventW, GetCurrentProcess, ResetEvent, SetEvent};
+use windows_sys::Win32::System::IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED};
+
+/// Flags of the DCB `serialport` clears when it opens a port: `fOutxDsrFlow`, `fDtrControl`,
+/// `fDsrSensitivity`, `fErrorChar`, `fNull` and `fAbortOnError`.
+const CLEARED_FLAGS: u32 = 1 << 3 | 0b11 << 4 | 1 << 6 | 1 << 10 | 1 << 11 | 1 << 14;
+/// `fBinary`, always set for communication resources.
+const BINARY: u32 = 1 << 0;
+
+fn last_error() -> io::Error {
+    io::Error::last_os_error()
+}
+
+/// `Event` A manual-reset event the operations of a handle complete with.
+struct Event(HANDLE);
+
+impl Event {
+    fn new() -> io::Result<Self> {
+        let event = unsafe { CreateEventW(std::ptr::null(), TRUE, FALSE, std::ptr::null()) };
+        if event == 0 {
+            return Err(last_error());
+        }
+        Ok(Event(event))
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        unsafe {
+            CloseHandle(self.0);
+        }
+    }
+}
+
+fn duplicate(handle: HANDLE) -> io::Result<HANDLE> {
+    let process = unsafe { GetCurrentProcess() };
+    let mut duplicated = INVALID_HANDLE_VALUE;
+    let copied = unsafe {
+        DuplicateHandle(
+            process,
+            handle,
+            process,
+            &mut duplicated,
+            0,
+            FALSE,
+            DUPLICATE_SAME_ACCESS,
+        )
+    };
+    if copied == 0 {
+        return Err(last_error());
+    }
+    Ok(duplicated)
+}
+
+/// `OverlappedPort` A COM port whose reads and writes are overlapped, the settings and control
+/// lines are those of `serialport`.
+pub struct OverlappedPort {
+    inner: COMPort,
+    name: String,
+    event: Event,
+}
+
+/// `open` Open `path` for overlapped I/O with the settings `serialport::new` would apply.
+#[allow(clippy::too_many_arguments)]
+pub fn open(
+    path: &str,
+    baud_rate: u32,
+    data_bits: DataBits,
+    flow_control: FlowControl,
+    parity: Parity,
+    stop_bits: StopBits,
+    timeout: Duration,
+) -> serialport::Result<OverlappedPort> {
+    let mut name: Vec<u16> = Vec::with_capacity(path.len() + 5);
+    if !path.starts_with('\\') {
+        name.extend(r"\\.\".encode_utf16());
+    }
+    name.extend(path.encode_utf16());
+    name.push(0);
+    let handle = unsafe {
+        CreateFileW(
+            name.as_ptr(),
+            GENERIC_READ | GENERIC_WRITE,
+            0,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            FILE_FLAG_OVERLAPPED,
+            0,
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(last_error().into());
+    }
+    // owned from here, so the handle is closed when a setting fails
+    let mut inner = unsafe { COMPort::from_raw_handle(handle as _) };
+    let mut dcb: DCB = unsafe { std::mem::zeroed() };
+    dcb.DCBlength = std::mem::size_of::<DCB>() as u32;
+    if unsafe { GetCommState(handle, &mut dcb) } == 0 {
+        return Err(last_error().into());
+    }
+    dcb.XonChar = 17;
+    dcb.XoffChar = 19;
+    dcb.ErrorChar = 0;
+    dcb.EofChar = 26;
+    dcb._bitfield = (dcb._bitfield & !CLEARED_FLAGS) | BINARY;
+    if unsafe { SetCommState(handle, &dcb) } == 0 {
+        return Err(last_error().into());
+    }
+    inner.set_baud_rate(baud_rate)?;
+    inner.set_data_bits(data_bits)?;
+    inner.set_parity(parity)?;
+    inner.set_stop_bits(stop_bits)?;
+    inner.set_flow_control(flow_control)?;
+    inner.set_timeout(timeout)?;
+    Ok(OverlappedPort {
+        inner,
+        name: path.to_string(),
+        event: Event::new()?,
+    })
+}
+
+impl OverlappedPort {
+    /// `complete` Wait for an operation that was started with `started`, the bytes it moved.
+    fn complete(&self, overlapped: &OVERLAPPED, started: i32, moved: u32) -> io::Result<u32> {
+        if started != 0 {
+            return Ok(moved);
+        }
+        if unsafe { GetLastError() } != ERROR_IO_PENDING {
+            return Err(last_error());
+        }
+        let mut moved = 0;
+        let handle = self.inner.as_raw_handle() as HANDLE;
+        if unsafe { GetOverlappedResult(handle, overlapped, &mut moved, TRUE) } == 0 {
+            return Err(last_error());
+        }
+        Ok(moved)
+    }
+
+    fn overlapped(&self) -> io::Result<OVERLAPPED> {
+        let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+        overlapped.hEvent = self.event.0;
+        if unsafe { ResetEvent(self.event.0) } == 0 {
+            return Err(last_error());
+        }
+        Ok(overlapped)
+    }
+}
+
+impl AsRawHandle for OverlappedPort {
+    fn as_raw_handle(&self) -> std::os::windows::io::RawHandle {
+        self.inner.as_raw_handle()
+    }
+}
+
+impl io::Read for OverlappedPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut overlapped = self.overlapped()?;
+        let mut read = 0;
+        let handle = self.inner.as_raw_handle() as HANDLE;
+        let started = unsafe {
+            ReadFile(
+                handle,
+                buf.as_mut_ptr(),
+                buf.len() as u32,
+                &mut read,
+                &mut overlapped,
+            )
+        };
+        // the timeouts of the port still end the read, with nothing read
+        match self.complete(&overlapped, started, read)? {
+            0 => Err(io::Error::new(io::ErrorKind::TimedOut, "Operation timed out")),
+            read => Ok(read as usize),
+        }
+    }
+}
+
+impl io::Write for OverlappedPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut overlapped = self.overlapped()?;
+        let mut written = 0;
+        let handle = self.inner.as_raw_handle() as HANDLE;
+        let started = unsafe {
+            WriteFile(
+                handle,
+                buf.as_ptr(),
+                buf.len() as u32,
+                &mut written,
+                &mut overlapped,
+            )
+        };
+        self.complete(&overlapped, started, written)
+            .map(|written| written as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl SerialPort for OverlappedPort {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        self.inner.baud_rate()
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        self.inner.data_bits()
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        self.inner.flow_control()
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        self.inner.parity()
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        self.inner.stop_bits()
+    }
+
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.inner.set_baud_rate(baud_rate)
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.inner.set_data_bits(data_bits)
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.inner.set_flow_control(flow_control)
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.inner.set_parity(parity)
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.inner.set_stop_bits(stop_bits)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_request_to_send(level)
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_data_terminal_ready(level)
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.inner.read_clear_to_send()
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.inner.read_data_set_ready()
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.inner.read_ring_indicator()
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.inner.read_carrier_detect()
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_read()
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_write()
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        self.inner.clear(buffer_to_clear)
+    }
+
+    // a clone of the inner port would read without waiting for completion
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(OverlappedPort {
+            inner: self.inner.try_clone_native()?,
+            name: self.name.clone(),
+            event: Event::new()?,
+        }))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        self.inner.set_break()
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.inner.clear_break()
+    }
+}
+
+/// `Waiter` Waits with `WaitCommEvent` until data arrives on a port. The event it was armed
+/// with is signalled then.
+pub struct Waiter {
+    /// A duplicate of the handle of the port, closed with the waiter.
+    handle: HANDLE,
+    event: Event,
+    // boxed, the driver writes to them until the wait completes or is cancelled
+    overlapped: Box<OVERLAPPED>,
+    mask: Box<u32>,
+    pending: bool,
+}
+
+// the handles are owned and only used by the thread holding the waiter
+unsafe impl Send for Waiter {}
+
+impl Waiter {
+    /// `new` A waiter on the port of `native`, the handle of an overlapped port.
+    pub fn new(native: isize) -> io::Result<Self> {
+        let handle = duplicate(native as HANDLE)?;
+        if unsafe { SetCommMask(handle, EV_RXCHAR) } == 0 {
+            let error = last_error();
+            unsafe {
+                CloseHandle(handle);
+            }
+            return Err(error);
+        }
+        let event = Event::new()?;
+        let mut overlapped: Box<OVERLAPPED> = Box::new(unsafe { std::mem::zeroed() });
+        overlapped.hEvent = event.0;
+        Ok(Waiter {
+            handle,
+            event,
+            overlapped,
+            mask: Box::new(0),
+            pending: false,
+        })
+    }
+
+    /// `arm` Start waiting unless a wait is pending, the event signalled once data arrived.
+    pub fn arm(&mut self) -> io::Result<isize> {
+        if self.pending {
+            let mut transferred = 0;
+            let done = unsafe {
+                GetOverlappedResult(self.handle, &*self.overlapped, &mut transferred, FALSE)
+            };
+            if done == 0 && unsafe { GetLastError() } == ERROR_IO_INCOMPLETE {
+                return Ok(self.event.0);
+            }
+            // completed, or failed and worth a read that reports it
+            self.pending = false;
+            self.signal();
+            return Ok(self.event.0);
+        }
+        if unsafe { ResetEvent(self.event.0) } == 0 {
+            return Err(last_error());
+        }
+        let waited = unsafe { WaitCommEvent(self.handle, &mut *self.mask, &mut *self.overlapped) };
+        if waited != 0 {
+            self.signal();
+        } else if unsafe { GetLastError() } == ERROR_IO_PENDING {
+            self.pending = true;
+        } else {
+            return Err(last_error());
+        }
+        Ok(self.event.0)
+    }
+
+    /// `signal` Wake whoever waits on the event, e.g. for data that arrived before the wait.
+    pub fn signal(&self) {
+        unsafe {
+            SetEvent(self.event.0);
+        }
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        unsafe {
+            if self.pending {
+                let mut transferred = 0;
+                CancelIoEx(self.handle, &*self.overlapped);
+                GetOverlappedResult(self.handle, &*self.overlapped, &mut transferred, TRUE);
+            }
+            CloseHandle(self.handle);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Traffic capture to pcapng files for Wireshark: one interface named after the port, every
+//! read and write an enhanced packet block with its direction in the `epb_flags` option.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `LINKTYPE_USER0`, Wireshark's "DLT User" preferences pick the dissector for it.
+pub const USER0: u16 = 147;
+
+const SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const INTERFACE_DESCRIPTION: u32 = 1;
+const ENHANCED_PACKET: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1a2b_3c4d;
+const IF_NAME: u16 = 2;
+const EPB_FLAGS: u16 = 2;
+const INBOUND: u32 = 1;
+const OUTBOUND: u32 = 2;
+
+fn padded(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+/// `option` An option with its value padded to 32 bits.
+fn option(block: &mut Vec<u8>, code: u16, value: &[u8]) {
+    block.extend_from_slice(&code.to_le_bytes());
+    block.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    block.extend_from_slice(value);
+    block.resize(block.len() + padded(value.len()) - value.len(), 0);
+}
+
+/// `block` Frame a block body with its type and the total length before and after it.
+fn block(kind: u32, mut body: Vec<u8>) -> Vec<u8> {
+    // opt_endofopt
+    body.extend_from_slice(&[0; 4]);
+    let length = (body.len() + 12) as u32;
+    let mut block = Vec::with_capacity(length as usize);
+    block.extend_from_slice(&kind.to_le_bytes());
+    block.extend_from_slice(&length.to_le_bytes());
+    block.extend(body);
+    block.extend_from_slice(&length.to_le_bytes());
+    block
+}
+
+/// `PcapWriter` An open capture file.
+pub struct PcapWriter {
+    file: BufWriter<File>,
+    pub packets: u64,
+}
+
+impl PcapWriter {
+    /// `create` Start a capture file with one interface of `link_type`.
+    pub fn create(path: &str, interface: &str, link_type: u16) -> io::Result<PcapWriter> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let mut header = Vec::new();
+        header.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        // version 1.0, section length not given
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(&(-1i64).to_le_bytes());
+        file.write_all(&block(SECTION_HEADER, header))?;
+        let mut description = Vec::new();
+        description.extend_from_slice(&link_type.to_le_bytes());
+        description.extend_from_slice(&0u16.to_le_bytes());
+        // no snapshot length limit
+        description.extend_from_slice(&0u32.to_le_bytes());
+        option(&mut description, IF_NAME, interface.as_bytes());
+        file.write_all(&block(INTERFACE_DESCRIPTION, description))?;
+        file.flush()?;
+        Ok(PcapWriter { file, packets: 0 })
+    }
+
+    /// `record` Append a packet, timestamped now in microseconds.
+    pub fn record(&mut self, outgoing: bool, data: &[u8]) -> io::Result<()> {
+        let micros = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_micros() as u64)
+            .unwrap_or(0);
+        let mut packet = Vec::with_capacity(32 + padded(data.len()));
+        // interface 0
+        packet.extend_from_slice(&0u32.to_le_bytes());
+        packet.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        packet.extend_from_slice(&(micros as u32).to_le_bytes());
+        packet.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        packet.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        packet.extend_from_slice(data);
+        packet.resize(packet.len() + padded(data.len()) - data.len(), 0);
+        let direction = if outgoing { OUTBOUND } else { INBOUND };
+        option(&mut packet, EPB_FLAGS, &direction.to_le_bytes());
+        self.file.write_all(&block(ENHANCED_PACKET, packet))?;
+        // a capture stays readable if the application ends without stopping it
+        self.file.flush()?;
+        self.packets += 1;
+        Ok(())
+    }
+}
+
+/// Shared by a port and its clones, like `SharedDirection`.
+pub type SharedPcap = Arc<Mutex<Option<PcapWriter>>>;
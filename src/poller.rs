@@ -0,0 +1,161 @@
+//! Request frames the plugin writes periodically, with their answers matched from the
+//! receive stream of the background read.
+
+use crate::state::PollData;
+use std::time::{Duration, Instant};
+
+/// `Poll` A request written every `interval`.
+pub struct Poll {
+    pub id: String,
+    pub request: Vec<u8>,
+    pub interval: Duration,
+    /// The answer is complete once this sequence was received.
+    pub terminator: Option<Vec<u8>>,
+    /// The answer is complete after this many bytes.
+    pub length: Option<usize>,
+    pub response_timeout: Duration,
+    next: Instant,
+}
+
+impl Poll {
+    pub fn new(id: String, request: Vec<u8>, interval: Duration) -> Self {
+        Poll {
+            id,
+            request,
+            interval,
+            terminator: None,
+            length: None,
+            response_timeout: interval,
+            next: Instant::now(),
+        }
+    }
+}
+
+/// `Outstanding` A written request waiting for its answer.
+struct Outstanding {
+    id: String,
+    data: Vec<u8>,
+    terminator: Option<Vec<u8>>,
+    length: Option<usize>,
+    deadline: Instant,
+}
+
+impl Outstanding {
+    /// `complete_at` Length of the answer when it is complete.
+    fn complete_at(&self) -> Option<usize> {
+        if let Some(terminator) = &self.terminator {
+            if let Some(position) = self
+                .data
+                .windows(terminator.len())
+                .position(|window| window == terminator.as_slice())
+            {
+                return Some(position + terminator.len());
+            }
+        }
+        match self.length {
+            Some(length) if self.data.len() >= length => Some(length),
+            _ => None,
+        }
+    }
+}
+
+/// `Poller` The polls of one port; only one request is outstanding at a time so answers
+/// cannot be mixed up.
+#[derive(Default)]
+pub struct Poller {
+    polls: Vec<Poll>,
+    outstanding: Option<Outstanding>,
+}
+
+impl Poller {
+    /// `add` Register a poll, replacing one with the same id.
+    pub fn add(&mut self, poll: Poll) {
+        self.remove(&poll.id);
+        self.polls.push(poll);
+    }
+
+    pub fn remove(&mut self, id: &str) -> bool {
+        let count = self.polls.len();
+        self.polls.retain(|poll| poll.id != id);
+        if self
+            .outstanding
+            .as_ref()
+            .map(|outstanding| outstanding.id == id)
+            == Some(true)
+        {
+            self.outstanding = None;
+        }
+        self.polls.len() != count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.polls.is_empty()
+    }
+
+    /// `due` The request to write now, if no answer is awaited and a poll is due.
+    pub fn due(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if self.outstanding.is_some() {
+            return None;
+        }
+        let poll = self
+            .polls
+            .iter_mut()
+            .filter(|poll| poll.next <= now)
+            .min_by_key(|poll| poll.next)?;
+        poll.next += poll.interval;
+        if poll.next <= now {
+            // fell behind, skip the missed polls
+            poll.next = now + poll.interval;
+        }
+        self.outstanding = Some(Outstanding {
+            id: poll.id.clone(),
+            data: Vec::new(),
+            terminator: poll
+                .terminator
+                .clone()
+                .filter(|terminator| !terminator.is_empty()),
+            length: poll.length,
+            deadline: now + poll.response_timeout,
+        });
+        Some(poll.request.clone())
+    }
+
+    /// `feed` Collect received bytes, returns the answer once it is complete.
+    pub fn feed(&mut self, data: &[u8]) -> Option<PollData> {
+        let outstanding = self.outstanding.as_mut()?;
+        outstanding.data.extend_from_slice(data);
+        let size = outstanding.complete_at()?;
+        let mut outstanding = self.outstanding.take()?;
+        outstanding.data.truncate(size);
+        Some(PollData {
+            id: outstanding.id,
+            size,
+            data: outstanding.data,
+            timed_out: false,
+        })
+    }
+
+    /// `expire` End the outstanding request once its response timeout passed. Without a
+    /// terminator or length the answer is whatever arrived until then.
+    pub fn expire(&mut self, now: Instant) -> Option<PollData> {
+        if self.outstanding.as_ref()?.deadline > now {
+            return None;
+        }
+        let outstanding = self.outstanding.take()?;
+        let delimited = outstanding.terminator.is_some() || outstanding.length.is_some();
+        Some(PollData {
+            id: outstanding.id,
+            size: outstanding.data.len(),
+            timed_out: delimited || outstanding.data.is_empty(),
+            data: outstanding.data,
+        })
+    }
+
+    /// `next_wakeup` When `due` or `expire` have something to do next.
+    pub fn next_wakeup(&self) -> Option<Instant> {
+        match &self.outstanding {
+            Some(outstanding) => Some(outstanding.deadline),
+            None => self.polls.iter().map(|poll| poll.next).min(),
+        }
+    }
+}
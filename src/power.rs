@@ -0,0 +1,113 @@
+//! System sleep noticed from two clocks: one that goes on counting while the system is suspended
+//! and one that stops, `CLOCK_BOOTTIME` and `CLOCK_MONOTONIC` on Linux, `CLOCK_MONOTONIC` and
+//! `CLOCK_UPTIME_RAW` on macOS, the tick count and the unbiased interrupt time on Windows. When
+//! the first ran ahead of the second between two looks, the system slept for the difference.
+//! This needs no platform power notifications, which Tauri does not forward to plugins, and
+//! unlike the wall clock it is not fooled by clock adjustments or a busy system.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long the watching thread sleeps between two looks at the clocks.
+const STEP: Duration = Duration::from_secs(2);
+/// Shortest suspension reported, above the differing resolutions of the two clocks.
+const MIN_SLEEP: Duration = Duration::from_secs(1);
+
+/// `Sleep` A suspension the clocks revealed.
+pub struct Sleep {
+    /// When the system was last seen awake, in milliseconds since the epoch.
+    pub suspended_at: u64,
+    pub slept: Duration,
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios"
+))]
+fn clock(id: libc::clockid_t) -> Duration {
+    let mut time: libc::timespec = unsafe { std::mem::zeroed() };
+    if unsafe { libc::clock_gettime(id, &mut time) } != 0 {
+        return Duration::ZERO;
+    }
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
+
+/// `clocks` The time since boot with and without the time suspended.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn clocks() -> (Duration, Duration) {
+    (clock(libc::CLOCK_BOOTTIME), clock(libc::CLOCK_MONOTONIC))
+}
+
+/// `clocks` The time since boot with and without the time suspended.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn clocks() -> (Duration, Duration) {
+    (clock(libc::CLOCK_MONOTONIC), clock(libc::CLOCK_UPTIME_RAW))
+}
+
+/// `clocks` The time since boot with and without the time suspended.
+#[cfg(windows)]
+fn clocks() -> (Duration, Duration) {
+    use windows_sys::Win32::System::SystemInformation::GetTickCount64;
+    use windows_sys::Win32::System::WindowsProgramming::QueryUnbiasedInterruptTime;
+
+    let mut unbiased = 0u64;
+    let total = Duration::from_millis(unsafe { GetTickCount64() });
+    unsafe { QueryUnbiasedInterruptTime(&mut unbiased) };
+    // in units of 100 ns
+    (total, Duration::from_nanos(unbiased * 100))
+}
+
+/// `clocks` No clock here leaves out the time suspended, so no sleep is noticed.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    windows
+)))]
+fn clocks() -> (Duration, Duration) {
+    (Duration::ZERO, Duration::ZERO)
+}
+
+/// `Clock` The clocks as last seen by the watching thread.
+pub struct Clock {
+    last: SystemTime,
+    total: Duration,
+    awake: Duration,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        let (total, awake) = clocks();
+        Clock {
+            last: SystemTime::now(),
+            total,
+            awake,
+        }
+    }
+
+    /// `step` Sleep one `STEP`, the sleep of the system if it was suspended meanwhile.
+    pub fn step(&mut self) -> Option<Sleep> {
+        std::thread::sleep(STEP);
+        let (total, awake) = clocks();
+        let slept = total
+            .saturating_sub(self.total)
+            .saturating_sub(awake.saturating_sub(self.awake));
+        let suspended_at = self
+            .last
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_millis() as u64;
+        self.last = SystemTime::now();
+        self.total = total;
+        self.awake = awake;
+        if slept < MIN_SLEEP {
+            return None;
+        }
+        Some(Sleep {
+            suspended_at,
+            slept,
+        })
+    }
+}
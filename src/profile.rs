@@ -0,0 +1,50 @@
+//! Named connection profiles: a complete configuration, the port and how to open it, that
+//! applications talking to several kinds of devices switch in one call.
+
+use crate::rs485::NativeRs485;
+use crate::selector::Selector;
+use serde::{Deserialize, Serialize};
+
+/// `Profile` A port, by path or USB identity, with the settings `open` takes.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Profile {
+    #[serde(flatten)]
+    pub selector: Selector,
+    pub baud_rate: u32,
+    pub data_bits: Option<usize>,
+    pub flow_control: Option<String>,
+    pub parity: Option<String>,
+    pub stop_bits: Option<usize>,
+    pub timeout: Option<u64>,
+    pub rs485: Option<NativeRs485>,
+    /// Names the events of the port, so the frontend finds them whatever path was selected.
+    pub event_id: Option<String>,
+    pub access: Option<String>,
+}
+
+impl Profile {
+    pub fn new(selector: Selector, baud_rate: u32) -> Self {
+        Profile {
+            selector,
+            baud_rate,
+            data_bits: None,
+            flow_control: None,
+            parity: None,
+            stop_bits: None,
+            timeout: None,
+            rs485: None,
+            event_id: None,
+            access: None,
+        }
+    }
+
+    pub fn event_id(mut self, event_id: impl Into<String>) -> Self {
+        self.event_id = Some(event_id.into());
+        self
+    }
+}
+
+/// `is_profile_name` Names that survive the JSON file and read well in a list.
+pub fn is_profile_name(name: &str) -> bool {
+    !name.trim().is_empty() && name.len() <= 128 && !name.chars().any(char::is_control)
+}
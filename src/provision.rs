@@ -0,0 +1,65 @@
+//! The configuration of the plugin as one JSON document, exported from a machine that was set
+//! up and imported on the others of a fleet, so they all talk to their devices the same way.
+
+use crate::autoopen::AutoOpen;
+use crate::profile::{is_profile_name, Profile};
+use crate::store::SavedSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Version of the document format, raised on changes older plugins cannot read.
+pub const VERSION: u32 = 1;
+
+/// `Document` Everything the plugin is configured with.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Document {
+    pub version: u32,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// The ports opened at startup, declared in the application and imported.
+    #[serde(default)]
+    pub auto_open: Vec<AutoOpen>,
+    /// The last settings by device, with `Builder::remember_settings`.
+    #[serde(default)]
+    pub saved_settings: BTreeMap<String, SavedSettings>,
+}
+
+/// `ImportReport` How many entries of each kind an import stored.
+#[derive(Serialize, Clone, Default)]
+pub struct ImportReport {
+    pub profiles: usize,
+    pub auto_open: usize,
+    /// Zero when settings are not remembered, the entries are then dropped.
+    pub saved_settings: usize,
+}
+
+impl Document {
+    /// `validate` Fail on the first entry that could not be used, before anything is stored.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version > VERSION {
+            return Err(format!(
+                "Configuration version {} is newer than the supported {}",
+                self.version, VERSION
+            ));
+        }
+        for (name, profile) in &self.profiles {
+            if !is_profile_name(name) {
+                return Err(format!("Invalid profile name {:?}", name));
+            }
+            profile
+                .selector
+                .validate()
+                .map_err(|message| format!("Profile {}: {}", name, message))?;
+        }
+        for port in &self.auto_open {
+            port.profile.selector.validate().map_err(|message| {
+                format!(
+                    "Port {} to open at startup: {}",
+                    port.profile.selector.describe(),
+                    message
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
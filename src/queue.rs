@@ -0,0 +1,164 @@
+//! Prioritized transmit queue of a port: urgent frames go out before normal ones, and both
+//! before bulk data, which is written in chunks so a long upload does not hold up the others.
+
+use crate::operation::Operation;
+use crate::state::ProgressData;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// A bulk entry yields to urgent and normal frames after this many bytes.
+pub const BULK_CHUNK: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Priority {
+    Urgent,
+    Normal,
+    Bulk,
+}
+
+impl Priority {
+    pub fn parse(value: Option<&str>) -> Result<Priority, String> {
+        match value {
+            Some("Urgent") => Ok(Priority::Urgent),
+            None | Some("Normal") => Ok(Priority::Normal),
+            Some("Bulk") => Ok(Priority::Bulk),
+            Some(other) => Err(format!("Unknown queue priority: {}", other)),
+        }
+    }
+}
+
+/// `Entry` A queued write, `offset` bytes of it are written.
+struct Entry {
+    operation: Operation,
+    priority: Priority,
+    data: Vec<u8>,
+    offset: usize,
+}
+
+/// `Chunk` The next bytes to write, `done` when they complete their entry.
+pub struct Chunk {
+    pub id: String,
+    pub priority: Priority,
+    pub data: Vec<u8>,
+    pub done: bool,
+    /// Size of the whole entry.
+    pub size: usize,
+    /// Progress of a bulk entry once the chunk is written.
+    pub progress: Option<ProgressData>,
+}
+
+/// `QueueStatus` Entries waiting per priority, a partly written bulk entry included.
+#[derive(Serialize, Clone, Default)]
+pub struct QueueStatus {
+    pub urgent: usize,
+    pub normal: usize,
+    pub bulk: usize,
+    /// Bytes still to be written.
+    pub pending_bytes: usize,
+    /// Bytes written since the port was opened.
+    pub written_bytes: u64,
+}
+
+#[derive(Default)]
+pub struct TxQueue {
+    urgent: VecDeque<Entry>,
+    normal: VecDeque<Entry>,
+    bulk: VecDeque<Entry>,
+    written: u64,
+}
+
+impl TxQueue {
+    /// `push` Queue a write, it can be cancelled with the id of `operation` until written.
+    pub fn push(&mut self, operation: Operation, priority: Priority, data: Vec<u8>) {
+        let entry = Entry {
+            operation,
+            priority,
+            data,
+            offset: 0,
+        };
+        match priority {
+            Priority::Urgent => self.urgent.push_back(entry),
+            Priority::Normal => self.normal.push_back(entry),
+            Priority::Bulk => self.bulk.push_back(entry),
+        }
+    }
+
+    /// `remove` Drop a waiting entry, the rest of a partly written one included.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let mut removed = false;
+        for entries in [&mut self.urgent, &mut self.normal, &mut self.bulk] {
+            let count = entries.len();
+            entries.retain(|entry| entry.operation.id != id);
+            removed |= entries.len() != count;
+        }
+        removed
+    }
+
+    /// `take_cancelled` Remove the cancelled entries, returns their ids and how many bytes of
+    /// them were written.
+    pub fn take_cancelled(&mut self) -> Vec<(String, usize)> {
+        let mut cancelled = Vec::new();
+        for entries in [&mut self.urgent, &mut self.normal, &mut self.bulk] {
+            entries.retain(|entry| {
+                let keep = !entry.operation.is_cancelled();
+                if !keep {
+                    cancelled.push((entry.operation.id.clone(), entry.offset));
+                }
+                keep
+            });
+        }
+        cancelled
+    }
+
+    /// `next` Take the next bytes to write: a whole urgent or normal frame, or a chunk of the
+    /// oldest bulk entry.
+    pub fn next(&mut self) -> Option<Chunk> {
+        if let Some(entry) = self.urgent.pop_front().or_else(|| self.normal.pop_front()) {
+            self.written += entry.data.len() as u64;
+            return Some(Chunk {
+                id: entry.operation.id.clone(),
+                priority: entry.priority,
+                size: entry.data.len(),
+                data: entry.data,
+                done: true,
+                progress: None,
+            });
+        }
+        let entry = self.bulk.front_mut()?;
+        let end = (entry.offset + BULK_CHUNK).min(entry.data.len());
+        let data = entry.data[entry.offset..end].to_vec();
+        entry.offset = end;
+        self.written += data.len() as u64;
+        let size = entry.data.len();
+        let chunk = Chunk {
+            id: entry.operation.id.clone(),
+            priority: Priority::Bulk,
+            data,
+            done: end == size,
+            size,
+            progress: entry
+                .operation
+                .progress(end as u64, Some(size as u64), "Writing"),
+        };
+        if chunk.done {
+            self.bulk.pop_front();
+        }
+        Some(chunk)
+    }
+
+    pub fn status(&self) -> QueueStatus {
+        let pending = |entries: &VecDeque<Entry>| {
+            entries
+                .iter()
+                .map(|entry| entry.data.len() - entry.offset)
+                .sum::<usize>()
+        };
+        QueueStatus {
+            urgent: self.urgent.len(),
+            normal: self.normal.len(),
+            bulk: self.bulk.len(),
+            pending_bytes: pending(&self.urgent) + pending(&self.normal) + pending(&self.bulk),
+            written_bytes: self.written,
+        }
+    }
+}
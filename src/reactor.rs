@@ -0,0 +1,480 @@
+//! Reads of many ports on a few threads. A worker serves up to `PORTS_PER_WORKER` ports; it
+//! waits until data arrives on one of them or the next tick is due, with `poll(2)` on their
+//! descriptors on Unix and `WaitForMultipleObjects` on the events of their `WaitCommEvent` on
+//! Windows. Ports without a descriptor or handle, and reads asking for a thread priority, keep a
+//! thread of their own.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Ports a worker serves before another worker is started.
+const PORTS_PER_WORKER: usize = 8;
+
+/// `Task` The read of one port, driven by a worker or its own thread.
+pub trait Task: Send {
+    /// Descriptor a worker polls on Unix, on Windows the event signalled once data arrives,
+    /// armed by the call. `None` when the task reads on every tick instead.
+    fn pollable(&mut self) -> Option<isize>;
+
+    /// Time between ticks.
+    fn interval(&self) -> Duration;
+
+    /// Whether the read was cancelled or the port closed.
+    fn stopped(&mut self) -> bool;
+
+    /// Read what arrived and handle it. `false` after an error, the task is then not woken
+    /// before its next tick.
+    fn read(&mut self) -> bool;
+
+    /// Periodic work between reads, such as flushing a batch.
+    fn tick(&mut self);
+
+    /// Deliver what is still pending once the task stopped.
+    fn finish(&mut self);
+}
+
+/// `run_alone` The loop of a task with a thread of its own. Reads wait for data up to the
+/// interval, or up to the port timeout without anything to wait on.
+pub fn run_alone(mut task: Box<dyn Task>) {
+    while !task.stopped() {
+        let interval = task.interval();
+        match task.pollable() {
+            Some(pollable) => {
+                // a failed read is not retried before the interval passed
+                if wait(pollable, interval) && !task.read() {
+                    thread::sleep(interval);
+                }
+            }
+            None => {
+                task.read();
+                thread::sleep(interval);
+            }
+        }
+        task.tick();
+    }
+    task.finish();
+}
+
+/// `wait` Wait up to `timeout` for data on what `Task::pollable` returned.
+#[cfg(unix)]
+fn wait(pollable: isize, timeout: Duration) -> bool {
+    let mut pollfd = libc::pollfd {
+        fd: pollable as i32,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut pollfd, 1, timeout_ms(timeout)) > 0 }
+}
+
+#[cfg(windows)]
+fn wait(pollable: isize, timeout: Duration) -> bool {
+    use windows_sys::Win32::Foundation::WAIT_OBJECT_0;
+    use windows_sys::Win32::System::Threading::WaitForSingleObject;
+    unsafe { WaitForSingleObject(pollable, timeout_ms(timeout) as u32) == WAIT_OBJECT_0 }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn wait(_pollable: isize, timeout: Duration) -> bool {
+    thread::sleep(timeout);
+    true
+}
+
+/// `timeout_ms` A wait in milliseconds, rounded up so a tick is never run before it is due.
+fn timeout_ms(timeout: Duration) -> i32 {
+    ((timeout.as_micros() as u64 + 999) / 1000).min(i32::MAX as u64) as i32
+}
+
+struct Entry {
+    task: Box<dyn Task>,
+    next_tick: Instant,
+    /// Cleared after a failed read until the next tick.
+    waiting: bool,
+}
+
+struct Worker {
+    tasks: Sender<Box<dyn Task>>,
+    /// Tasks assigned to the worker and not finished yet.
+    load: Arc<AtomicUsize>,
+    #[cfg(any(unix, windows))]
+    wakeup: Arc<Wakeup>,
+}
+
+/// `Reactor` The workers shared by all ports.
+#[derive(Default)]
+pub struct Reactor {
+    workers: Mutex<Vec<Worker>>,
+}
+
+impl Reactor {
+    /// `spawn` Hand a task to the least loaded worker, starting one when all are full.
+    pub fn spawn(&self, task: Box<dyn Task>) -> Result<(), String> {
+        let mut workers = self
+            .workers
+            .lock()
+            .map_err(|error| format!("Cannot get reactor lock: {}", error))?;
+        let index = workers
+            .iter()
+            .enumerate()
+            .map(|(index, worker)| (worker.load.load(Ordering::Relaxed), index))
+            .filter(|(load, _)| *load < PORTS_PER_WORKER)
+            .min()
+            .map(|(_, index)| index);
+        let index = match index {
+            Some(index) => index,
+            None => {
+                let worker = start_worker(workers.len())?;
+                workers.push(worker);
+                workers.len() - 1
+            }
+        };
+        let worker = &workers[index];
+        worker.load.fetch_add(1, Ordering::Relaxed);
+        if let Err(error) = worker.tasks.send(task) {
+            worker.load.fetch_sub(1, Ordering::Relaxed);
+            return Err(format!("Reactor worker stopped: {}", error));
+        }
+        #[cfg(any(unix, windows))]
+        worker.wakeup.wake();
+        Ok(())
+    }
+
+    /// `wake` Have every worker look for stopped tasks now, so a cancelled read or a closed port
+    /// ends without waiting for data or the next tick.
+    pub fn wake(&self) {
+        #[cfg(any(unix, windows))]
+        if let Ok(workers) = self.workers.lock() {
+            for worker in workers.iter() {
+                worker.wakeup.wake();
+            }
+        }
+    }
+}
+
+fn start_worker(index: usize) -> Result<Worker, String> {
+    let (tx, rx) = mpsc::channel();
+    let load = Arc::new(AtomicUsize::new(0));
+    #[cfg(any(unix, windows))]
+    let wakeup = Arc::new(Wakeup::new().map_err(|error| format!("Cannot create wakeup: {}", error))?);
+    let worker = Worker {
+        tasks: tx,
+        load: load.clone(),
+        #[cfg(any(unix, windows))]
+        wakeup: wakeup.clone(),
+    };
+    thread::Builder::new()
+        .name(format!("serialport-reactor-{}", index))
+        .spawn(move || {
+            #[cfg(any(unix, windows))]
+            run_worker(rx, load, wakeup);
+            #[cfg(not(any(unix, windows)))]
+            run_worker(rx, load);
+        })
+        .map_err(|error| format!("Cannot start reactor worker: {}", error))?;
+    debug!("Started reactor worker {}", index);
+    Ok(worker)
+}
+
+/// `accept` Take the tasks handed to the worker, `false` once the reactor is gone.
+fn accept(tasks: &Receiver<Box<dyn Task>>, entries: &mut Vec<Entry>) -> bool {
+    loop {
+        match tasks.try_recv() {
+            Ok(task) => entries.push(Entry {
+                task,
+                next_tick: Instant::now(),
+                waiting: true,
+            }),
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Disconnected) => return false,
+        }
+    }
+}
+
+/// `tick` Run the ticks that are due, dropping stopped tasks; the time of the next tick.
+fn tick(entries: &mut Vec<Entry>, load: &AtomicUsize) -> Option<Instant> {
+    let now = Instant::now();
+    let mut index = 0;
+    while index < entries.len() {
+        let entry = &mut entries[index];
+        if entry.next_tick > now {
+            index += 1;
+            continue;
+        }
+        if entry.task.stopped() {
+            entries.remove(index).task.finish();
+            load.fetch_sub(1, Ordering::Relaxed);
+            continue;
+        }
+        if entry.task.pollable().is_none() {
+            entry.task.read();
+        }
+        entry.task.tick();
+        entry.next_tick = (entry.next_tick + entry.task.interval()).max(now);
+        entry.waiting = true;
+        index += 1;
+    }
+    entries.iter().map(|entry| entry.next_tick).min()
+}
+
+/// `sweep` Drop the stopped tasks after a wakeup, whether or not their tick is due.
+#[cfg(any(unix, windows))]
+fn sweep(entries: &mut Vec<Entry>, load: &AtomicUsize) {
+    let mut index = 0;
+    while index < entries.len() {
+        if entries[index].task.stopped() {
+            entries.remove(index).task.finish();
+            load.fetch_sub(1, Ordering::Relaxed);
+        } else {
+            index += 1;
+        }
+    }
+}
+
+/// `first` Wait for the first task, `false` once the reactor is gone.
+#[cfg(any(unix, windows))]
+fn first(tasks: &Receiver<Box<dyn Task>>, entries: &mut Vec<Entry>) -> bool {
+    match tasks.recv() {
+        Ok(task) => {
+            entries.push(Entry {
+                task,
+                next_tick: Instant::now(),
+                waiting: true,
+            });
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(unix)]
+fn run_worker(tasks: Receiver<Box<dyn Task>>, load: Arc<AtomicUsize>, wakeup: Arc<Wakeup>) {
+    let mut entries: Vec<Entry> = Vec::new();
+    loop {
+        // nothing to poll, wait for the next port
+        if entries.is_empty() && !first(&tasks, &mut entries) {
+            return;
+        }
+        if !accept(&tasks, &mut entries) && entries.is_empty() {
+            return;
+        }
+        let next_tick = match tick(&mut entries, &load) {
+            Some(next_tick) => next_tick,
+            None => continue,
+        };
+        let mut fds = vec![libc::pollfd {
+            fd: wakeup.read_fd,
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        // the entry of each descriptor after the wakeup pipe
+        let mut polled = Vec::new();
+        for (index, entry) in entries.iter_mut().enumerate() {
+            if !entry.waiting {
+                continue;
+            }
+            if let Some(fd) = entry.task.pollable() {
+                fds.push(libc::pollfd {
+                    fd: fd as i32,
+                    events: libc::POLLIN,
+                    revents: 0,
+                });
+                polled.push(index);
+            }
+        }
+        let timeout = next_tick.saturating_duration_since(Instant::now());
+        let ready = unsafe {
+            libc::poll(
+                fds.as_mut_ptr(),
+                fds.len() as libc::nfds_t,
+                timeout_ms(timeout),
+            )
+        };
+        if ready <= 0 {
+            continue;
+        }
+        for (pollfd, &index) in fds[1..].iter().zip(&polled) {
+            if pollfd.revents & (libc::POLLIN | libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+                let entry = &mut entries[index];
+                entry.waiting = entry.task.read();
+            }
+        }
+        // dropping tasks shifts the entries `polled` points to, so it waits for the reads
+        if fds[0].revents != 0 {
+            wakeup.drain();
+            sweep(&mut entries, &load);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn run_worker(tasks: Receiver<Box<dyn Task>>, load: Arc<AtomicUsize>, wakeup: Arc<Wakeup>) {
+    use windows_sys::Win32::Foundation::{FALSE, WAIT_OBJECT_0};
+    use windows_sys::Win32::System::Threading::{WaitForMultipleObjects, WaitForSingleObject};
+    let mut entries: Vec<Entry> = Vec::new();
+    loop {
+        if entries.is_empty() && !first(&tasks, &mut entries) {
+            return;
+        }
+        if !accept(&tasks, &mut entries) && entries.is_empty() {
+            return;
+        }
+        let next_tick = match tick(&mut entries, &load) {
+            Some(next_tick) => next_tick,
+            None => continue,
+        };
+        let mut events = vec![wakeup.event];
+        // the entry of each event after the wakeup
+        let mut polled = Vec::new();
+        for (index, entry) in entries.iter_mut().enumerate() {
+            if !entry.waiting {
+                continue;
+            }
+            if let Some(event) = entry.task.pollable() {
+                events.push(event);
+                polled.push(index);
+            }
+        }
+        let timeout = next_tick.saturating_duration_since(Instant::now());
+        let signalled = unsafe {
+            WaitForMultipleObjects(
+                events.len() as u32,
+                events.as_ptr(),
+                FALSE,
+                timeout_ms(timeout) as u32,
+            )
+        };
+        if signalled.wrapping_sub(WAIT_OBJECT_0) as usize >= events.len() {
+            continue;
+        }
+        // only the first signalled event is returned, the others are checked so none starves
+        for (&event, &index) in events[1..].iter().zip(&polled) {
+            if unsafe { WaitForSingleObject(event, 0) } == WAIT_OBJECT_0 {
+                let entry = &mut entries[index];
+                entry.waiting = entry.task.read();
+            }
+        }
+        if signalled == WAIT_OBJECT_0 {
+            sweep(&mut entries, &load);
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn run_worker(tasks: Receiver<Box<dyn Task>>, load: Arc<AtomicUsize>) {
+    use std::sync::mpsc::RecvTimeoutError;
+    let mut entries: Vec<Entry> = Vec::new();
+    loop {
+        if !accept(&tasks, &mut entries) && entries.is_empty() {
+            return;
+        }
+        let timeout = match tick(&mut entries, &load) {
+            Some(next_tick) => next_tick.saturating_duration_since(Instant::now()),
+            None => Duration::from_secs(3600),
+        };
+        match tasks.recv_timeout(timeout) {
+            Ok(task) => entries.push(Entry {
+                task,
+                next_tick: Instant::now(),
+                waiting: true,
+            }),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) if entries.is_empty() => return,
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(timeout),
+        }
+    }
+}
+
+/// `Wakeup` A pipe whose read end is polled with the ports, written to when a task arrives or
+/// one may have stopped.
+#[cfg(unix)]
+struct Wakeup {
+    read_fd: i32,
+    write_fd: i32,
+}
+
+#[cfg(unix)]
+impl Wakeup {
+    fn new() -> std::io::Result<Self> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        for &fd in &fds {
+            unsafe {
+                let flags = libc::fcntl(fd, libc::F_GETFL);
+                libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK);
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+        }
+        Ok(Wakeup {
+            read_fd: fds[0],
+            write_fd: fds[1],
+        })
+    }
+
+    fn wake(&self) {
+        let byte = 1u8;
+        unsafe {
+            libc::write(self.write_fd, &byte as *const u8 as *const libc::c_void, 1);
+        }
+    }
+
+    fn drain(&self) {
+        let mut buffer = [0u8; 64];
+        while unsafe {
+            libc::read(
+                self.read_fd,
+                buffer.as_mut_ptr() as *mut libc::c_void,
+                buffer.len(),
+            )
+        } > 0
+        {}
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Wakeup {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.read_fd);
+            libc::close(self.write_fd);
+        }
+    }
+}
+
+/// `Wakeup` An auto-reset event waited on with the ports, set when a task arrives or one may
+/// have stopped.
+#[cfg(windows)]
+struct Wakeup {
+    event: isize,
+}
+
+#[cfg(windows)]
+impl Wakeup {
+    fn new() -> std::io::Result<Self> {
+        use windows_sys::Win32::Foundation::FALSE;
+        use windows_sys::Win32::System::Threading::CreateEventW;
+        let event = unsafe { CreateEventW(std::ptr::null(), FALSE, FALSE, std::ptr::null()) };
+        if event == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Wakeup { event })
+    }
+
+    fn wake(&self) {
+        unsafe {
+            windows_sys::Win32::System::Threading::SetEvent(self.event);
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Wakeup {
+    fn drop(&mut self) {
+        unsafe {
+            windows_sys::Win32::Foundation::CloseHandle(self.event);
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use crate::state::HistoryFrame;
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// `ReadOutcome` How a read of the port ended.
+pub enum ReadOutcome {
+    Data(usize),
+    /// Nothing arrived within the timeout, which is routine and reported to no one.
+    Idle,
+    /// The device is gone: end of file, a hangup or a broken link, with the reason.
+    Disconnected(String),
+    Failed(io::Error),
+}
+
+/// `is_gone` Whether an OS error means the device was removed.
+fn is_gone(error: &io::Error) -> bool {
+    match error.raw_os_error() {
+        #[cfg(unix)]
+        Some(code) => [libc::EIO, libc::ENXIO, libc::ENODEV].contains(&code),
+        // ERROR_BAD_COMMAND, ERROR_GEN_FAILURE, ERROR_DEVICE_NOT_CONNECTED, ERROR_DEVICE_REMOVED
+        #[cfg(windows)]
+        Some(code) => [22, 31, 1167, 1617].contains(&code),
+        _ => false,
+    }
+}
+
+/// `classify` The outcome of a read into a buffer that is not empty, where `Ok(0)` can only mean
+/// the end of the stream.
+pub fn classify(result: io::Result<usize>) -> ReadOutcome {
+    match result {
+        Ok(0) => ReadOutcome::Disconnected("End of file".to_string()),
+        Ok(size) => ReadOutcome::Data(size),
+        Err(error) => match error.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted => {
+                ReadOutcome::Idle
+            }
+            io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted => ReadOutcome::Disconnected(error.to_string()),
+            _ if is_gone(&error) => ReadOutcome::Disconnected(error.to_string()),
+            _ => ReadOutcome::Failed(error),
+        },
+    }
+}
+
+/// `now_millis` Wall-clock time in milliseconds since the Unix epoch.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// `Stamp` When data was received, taken in the read thread so IPC delays do not distort it.
+#[derive(Clone, Copy, Default)]
+pub struct Stamp {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Microseconds since the port was opened, unaffected by changes of the system clock.
+    pub monotonic_us: u64,
+}
+
+impl Stamp {
+    pub fn now(opened: Instant) -> Self {
+        Stamp {
+            timestamp: now_millis(),
+            monotonic_us: opened.elapsed().as_micros() as u64,
+        }
+    }
+}
+
+/// `ReadBatch` Coalesces several small reads into a single emitted event.
+pub struct ReadBatch {
+    data: Vec<u8>,
+    max_bytes: Option<usize>,
+    max_latency: Option<Duration>,
+    started: Option<Instant>,
+    /// Reception of the oldest byte.
+    stamp: Stamp,
+}
+
+impl ReadBatch {
+    pub fn new(max_bytes: Option<usize>, max_latency_ms: Option<u64>) -> Self {
+        ReadBatch {
+            data: Vec::with_capacity(max_bytes.unwrap_or(0)),
+            max_bytes,
+            max_latency: max_latency_ms.map(Duration::from_millis),
+            started: None,
+            stamp: Stamp::default(),
+        }
+    }
+
+    /// `is_enabled` Whether any coalescing option was given, otherwise every read is emitted directly.
+    pub fn is_enabled(&self) -> bool {
+        self.max_bytes.is_some() || self.max_latency.is_some()
+    }
+
+    pub fn push(&mut self, data: &[u8], stamp: Stamp) {
+        if data.is_empty() {
+            return;
+        }
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+            self.stamp = stamp;
+        }
+        self.data.extend_from_slice(data);
+    }
+
+    /// `is_ready` The batch is full, or its oldest byte has waited long enough.
+    pub fn is_ready(&self) -> bool {
+        if self.data.is_empty() {
+            return false;
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            if self.data.len() >= max_bytes {
+                return true;
+            }
+        }
+        match (self.max_latency, self.started) {
+            (Some(max_latency), Some(started)) => started.elapsed() >= max_latency,
+            _ => false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// `take` Hand out the collected bytes with the reception of the oldest, and start a new
+    /// batch.
+    pub fn take(&mut self) -> (Vec<u8>, Stamp) {
+        self.started = None;
+        let data = std::mem::replace(
+            &mut self.data,
+            Vec::with_capacity(self.max_bytes.unwrap_or(0)),
+        );
+        (data, self.stamp)
+    }
+}
+
+/// `ThrottlePolicy` What happens to data that arrives while the event rate limit is reached.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ThrottlePolicy {
+    /// Keep everything and deliver it in the next allowed event.
+    Merge,
+    /// Replace the waiting chunk with the newer one.
+    DropOldest,
+    /// Keep the waiting chunk and discard the newer one.
+    DropNewest,
+}
+
+/// `ReadThrottle` Limits how many read events per second are sent to the frontend.
+pub struct ReadThrottle {
+    interval: Option<Duration>,
+    policy: ThrottlePolicy,
+    last_emit: Option<Instant>,
+    pending: Vec<u8>,
+    /// Reception of the oldest pending byte.
+    pending_stamp: Stamp,
+    suppressed: u64,
+}
+
+impl ReadThrottle {
+    pub fn new(max_event_rate: Option<u32>, policy: ThrottlePolicy) -> Self {
+        ReadThrottle {
+            interval: max_event_rate
+                .filter(|rate| *rate > 0)
+                .map(|rate| Duration::from_secs(1) / rate),
+            policy,
+            last_emit: None,
+            pending: Vec::new(),
+            pending_stamp: Stamp::default(),
+            suppressed: 0,
+        }
+    }
+
+    fn is_slot_open(&self) -> bool {
+        match (self.interval, self.last_emit) {
+            (Some(interval), Some(last_emit)) => last_emit.elapsed() >= interval,
+            _ => true,
+        }
+    }
+
+    /// `offer` Emit `data` right away if the rate allows it, otherwise apply the policy.
+    /// `emit` receives the data, the total number of suppressed bytes so far and when the data
+    /// was received.
+    pub fn offer<F: FnMut(&[u8], u64, Stamp)>(&mut self, data: &[u8], stamp: Stamp, mut emit: F) {
+        if data.is_empty() {
+            return;
+        }
+        if self.interval.is_none() {
+            emit(data, self.suppressed, stamp);
+            return;
+        }
+        if self.pending.is_empty() && self.is_slot_open() {
+            self.last_emit = Some(Instant::now());
+            emit(data, self.suppressed, stamp);
+            return;
+        }
+        if self.pending.is_empty() {
+            self.pending_stamp = stamp;
+        }
+        match self.policy {
+            ThrottlePolicy::Merge => self.pending.extend_from_slice(data),
+            ThrottlePolicy::DropOldest => {
+                self.suppressed += self.pending.len() as u64;
+                self.pending.clear();
+                self.pending.extend_from_slice(data);
+                self.pending_stamp = stamp;
+            }
+            ThrottlePolicy::DropNewest => {
+                if self.pending.is_empty() {
+                    self.pending.extend_from_slice(data);
+                } else {
+                    self.suppressed += data.len() as u64;
+                }
+            }
+        }
+    }
+
+    /// `poll` Emit the waiting chunk once the next event is allowed.
+    pub fn poll<F: FnMut(&[u8], u64, Stamp)>(&mut self, emit: F) {
+        if !self.pending.is_empty() && self.is_slot_open() {
+            self.flush(emit);
+        }
+    }
+
+    /// `flush` Emit the waiting chunk regardless of the rate limit.
+    pub fn flush<F: FnMut(&[u8], u64, Stamp)>(&mut self, mut emit: F) {
+        if self.pending.is_empty() {
+            return;
+        }
+        self.last_emit = Some(Instant::now());
+        emit(&self.pending, self.suppressed, self.pending_stamp);
+        self.pending.clear();
+    }
+}
+
+/// `RingBuffer` Bounded receive buffer drained by the frontend with `fetch`.
+pub struct RingBuffer {
+    data: VecDeque<u8>,
+    capacity: usize,
+    overwritten: u64,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RingBuffer {
+            data: VecDeque::with_capacity(capacity),
+            capacity,
+            overwritten: 0,
+        }
+    }
+
+    /// `push` Append data, discarding the oldest bytes once the capacity is reached.
+    pub fn push(&mut self, data: &[u8]) {
+        let data = if data.len() > self.capacity {
+            self.overwritten += (data.len() - self.capacity) as u64;
+            &data[data.len() - self.capacity..]
+        } else {
+            data
+        };
+        let overflow = (self.data.len() + data.len()).saturating_sub(self.capacity);
+        if overflow > 0 {
+            self.data.drain(..overflow);
+            self.overwritten += overflow as u64;
+        }
+        self.data.extend(data);
+    }
+
+    /// `drain` Remove and return up to `max_bytes` of the oldest data.
+    pub fn drain(&mut self, max_bytes: usize) -> Vec<u8> {
+        let count = max_bytes.min(self.data.len());
+        self.data.drain(..count).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// `overwritten` Bytes lost because the frontend did not fetch them in time.
+    pub fn overwritten(&self) -> u64 {
+        self.overwritten
+    }
+}
+
+/// `History` Bounded list of received chunks with their arrival time.
+/// A capacity of zero disables recording.
+#[derive(Default)]
+pub struct History {
+    frames: VecDeque<HistoryFrame>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    /// `push` Record a chunk received at `timestamp`, the one its read events carry.
+    pub fn push(&mut self, data: &[u8], timestamp: u64) {
+        if self.capacity == 0 || data.is_empty() {
+            return;
+        }
+        self.frames.push_back(HistoryFrame {
+            timestamp,
+            data: data.to_vec(),
+        });
+        self.trim();
+    }
+
+    /// `query` The most recent `limit` frames received between `since` and `until` (inclusive),
+    /// oldest first.
+    pub fn query(
+        &self,
+        since: Option<u64>,
+        until: Option<u64>,
+        limit: Option<usize>,
+    ) -> Vec<HistoryFrame> {
+        let mut frames: Vec<HistoryFrame> = self
+            .frames
+            .iter()
+            .rev()
+            .filter(|frame| since.map_or(true, |since| frame.timestamp >= since))
+            .filter(|frame| until.map_or(true, |until| frame.timestamp <= until))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect();
+        frames.reverse();
+        frames
+    }
+}
@@ -0,0 +1,669 @@
+//! Half-duplex RS-485 with the transceiver direction driven by RTS: the line is asserted before
+//! a write and released once the last byte has left the UART. UARTs that switch the direction
+//! themselves are configured with `NativeRs485` instead. On half-duplex buses transmissions
+//! and receptions take turns, see `HalfDuplex`. The same wrapper records the traffic while a
+//! pcapng capture runs, hands it to the observers of `Builder::on_traffic`, passes writes
+//! through the middleware of the port, compresses and encrypts the link when that is set and
+//! refuses the direction a port was not opened for, see `Access`. Captures and observers see
+//! the traffic as it is on the wire, so compressed and encrypted.
+
+use crate::compression::SharedCompression;
+use crate::encryption::SharedLink;
+use crate::middleware::{self, SharedChain};
+use crate::pcapng::SharedPcap;
+use crate::traffic::{SharedTap, TrafficDirection};
+use serde::{Deserialize, Serialize};
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Longest wait for the transmitter to report empty after the output was drained.
+const MAX_DRAIN: Duration = Duration::from_millis(100);
+/// Longest wait for a quiet bus before a write fails.
+const MAX_BUS_WAIT: Duration = Duration::from_secs(1);
+
+/// `Direction` How a port drives RTS while it transmits.
+#[derive(Clone, Copy)]
+pub struct Direction {
+    /// Between asserting RTS and the first byte, for slow transceivers.
+    pub pre_delay: Duration,
+    /// Between the last byte and releasing RTS.
+    pub post_delay: Duration,
+    /// RTS is released (instead of asserted) while transmitting.
+    pub invert: bool,
+    /// Descriptor or handle of the native port, to read the transmitter state.
+    pub native: Option<isize>,
+}
+
+/// `NativeRs485` The kernel RS-485 mode (`TIOCSRS485`) of Linux UARTs that drive the
+/// transceiver themselves, given as `rs485` when opening.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct NativeRs485 {
+    /// Logical RTS level while sending, asserted by default.
+    pub rts_on_send: Option<bool>,
+    /// Logical RTS level after sending, released by default.
+    pub rts_after_send: Option<bool>,
+    #[serde(default)]
+    pub delay_before_send_ms: u32,
+    #[serde(default)]
+    pub delay_after_send_ms: u32,
+    /// Keep receiving while sending, e.g. to see the own transmission.
+    #[serde(default)]
+    pub rx_during_tx: bool,
+    /// Switch on the bus termination, on boards with a GPIO for it.
+    #[serde(default)]
+    pub terminate_bus: bool,
+}
+
+/// `Access` The directions a port may be used in, given as `access` when opening.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    ReadWrite,
+    /// Nothing is transmitted, breaks included, e.g. for monitoring a sensitive bus.
+    ReadOnly,
+    /// Nothing is read, so no read thread is started.
+    WriteOnly,
+}
+
+impl Access {
+    pub fn parse(value: Option<&str>) -> Result<Access, String> {
+        match value {
+            None | Some("ReadWrite") => Ok(Access::ReadWrite),
+            Some("ReadOnly") => Ok(Access::ReadOnly),
+            Some("WriteOnly") => Ok(Access::WriteOnly),
+            Some(other) => Err(format!("Unknown access: {}", other)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Access::ReadWrite => "read-write",
+            Access::ReadOnly => "read-only",
+            Access::WriteOnly => "write-only",
+        }
+    }
+}
+
+/// Shared by a port and its clones, set once the port is opened.
+pub type SharedAccess = Arc<Mutex<Access>>;
+
+/// Shared by a port and its clones; writes hold the lock, so transmissions never overlap.
+pub type SharedDirection = Arc<Mutex<Option<Direction>>>;
+
+/// `HalfDuplex` Transmissions wait until the bus was quiet for `turnaround`. Their echo is
+/// discarded byte for byte as it was sent, while it arrives during a transmission or within
+/// `turnaround` after it, so a reply following right after the echo is kept.
+pub struct HalfDuplex {
+    pub turnaround: Duration,
+    transmitting: bool,
+    /// End of the last transmission.
+    last_transmit: Option<Instant>,
+    last_receive: Option<Instant>,
+    /// Bytes sent whose echo was not received yet.
+    echo: VecDeque<u8>,
+}
+
+impl HalfDuplex {
+    pub fn new(turnaround: Duration) -> Self {
+        HalfDuplex {
+            turnaround,
+            transmitting: false,
+            last_transmit: None,
+            last_receive: None,
+            echo: VecDeque::new(),
+        }
+    }
+
+    /// `discard_echo` How many bytes at the start of `data`, received at `received`, are the
+    /// echo of the own transmission. A byte other than the one sent, e.g. after a collision,
+    /// ends the echo.
+    fn discard_echo(&mut self, data: &[u8], received: Instant) -> usize {
+        let expired = !self.transmitting
+            && self.last_transmit.map_or(true, |transmitted| {
+                received.saturating_duration_since(transmitted) > self.turnaround
+            });
+        if expired {
+            self.echo.clear();
+        }
+        let mut count = 0;
+        for byte in data {
+            match self.echo.front() {
+                Some(sent) if sent == byte => {
+                    self.echo.pop_front();
+                    count += 1;
+                }
+                Some(_) => {
+                    self.echo.clear();
+                    break;
+                }
+                None => break,
+            }
+        }
+        count
+    }
+}
+
+/// Shared by a port and its clones, like `SharedDirection`.
+pub type SharedHalfDuplex = Arc<Mutex<Option<HalfDuplex>>>;
+
+fn lock_error<T: std::fmt::Display>(error: T) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error.to_string())
+}
+
+/// `transmitter_empty` The line status of native Linux ports, `None` where it is unknown.
+fn transmitter_empty(native: Option<isize>) -> Option<io::Result<bool>> {
+    match native {
+        #[cfg(target_os = "linux")]
+        Some(fd) => Some(crate::termios::transmitter_empty(fd as i32)),
+        _ => None,
+    }
+}
+
+/// `wait_sent` Wait until the shift register is empty as well: drivers report the output
+/// drained while the last character is still on the wire.
+fn wait_sent(port: &mut dyn SerialPort, direction: &Direction) -> io::Result<()> {
+    port.flush()?;
+    let deadline = Instant::now() + MAX_DRAIN;
+    loop {
+        match transmitter_empty(direction.native) {
+            Some(Ok(false)) if Instant::now() < deadline => std::hint::spin_loop(),
+            Some(Ok(_)) => return Ok(()),
+            // USB adapters usually have no line status, one character time after the drain
+            _ => {
+                let baud_rate = port.baud_rate().unwrap_or(9600).max(1);
+                std::thread::sleep(Duration::from_micros(11_000_000 / baud_rate as u64));
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// `drain` Wait until the bytes `pending` reports were sent and the transmitter is empty, at
+/// most `timeout`. Without a line status one character time is added after the output drained.
+pub fn drain(
+    port: &mut dyn SerialPort,
+    native: Option<isize>,
+    pending: &mut dyn FnMut(&dyn SerialPort) -> io::Result<u32>,
+    timeout: Duration,
+) -> io::Result<()> {
+    let deadline = Instant::now() + timeout;
+    let timed_out = || io::Error::new(io::ErrorKind::TimedOut, "Output not drained in time");
+    while pending(&*port)? > 0 {
+        if Instant::now() >= deadline {
+            return Err(timed_out());
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    loop {
+        match transmitter_empty(native) {
+            Some(Ok(false)) if Instant::now() < deadline => std::hint::spin_loop(),
+            Some(Ok(false)) => return Err(timed_out()),
+            Some(Ok(true)) => return Ok(()),
+            _ => {
+                let baud_rate = port.baud_rate().unwrap_or(9600).max(1);
+                std::thread::sleep(Duration::from_micros(11_000_000 / baud_rate as u64));
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// `Rs485Port` Wraps every opened port; writes switch the direction once `Direction` is set and
+/// take turns with receptions once `HalfDuplex` is set.
+pub struct Rs485Port {
+    inner: Box<dyn SerialPort>,
+    access: SharedAccess,
+    direction: SharedDirection,
+    half_duplex: SharedHalfDuplex,
+    pcap: SharedPcap,
+    tap: SharedTap,
+    middleware: SharedChain,
+    link: SharedLink,
+    compression: SharedCompression,
+}
+
+impl Rs485Port {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        inner: Box<dyn SerialPort>,
+        access: SharedAccess,
+        direction: SharedDirection,
+        half_duplex: SharedHalfDuplex,
+        pcap: SharedPcap,
+        tap: SharedTap,
+        middleware: SharedChain,
+        link: SharedLink,
+        compression: SharedCompression,
+    ) -> Self {
+        Rs485Port {
+            inner,
+            access,
+            direction,
+            half_duplex,
+            pcap,
+            tap,
+            middleware,
+            link,
+            compression,
+        }
+    }
+
+    /// `refuse` Fail unless the port was opened for more than `forbidden`.
+    fn refuse(&self, forbidden: Access) -> io::Result<()> {
+        let access = *self.access.lock().map_err(lock_error)?;
+        if access == forbidden {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("the port was opened {}", access.name()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `record` Add traffic to the running capture and hand it to the observers; a failing
+    /// capture is stopped, the port keeps working.
+    fn record(&self, outgoing: bool, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        if let Ok(tap) = self.tap.lock() {
+            if let Some(tap) = tap.as_ref() {
+                let direction = if outgoing {
+                    TrafficDirection::Tx
+                } else {
+                    TrafficDirection::Rx
+                };
+                tap.observe(direction, data);
+            }
+        }
+        if let Ok(mut pcap) = self.pcap.lock() {
+            if let Some(writer) = pcap.as_mut() {
+                if let Err(error) = writer.record(outgoing, data) {
+                    error!("Capture stopped: {}", error);
+                    *pcap = None;
+                }
+            }
+        }
+    }
+
+    /// `begin_transmit` Wait for a quiet bus and claim it for sending `data`, returns whether
+    /// the port is in half-duplex mode.
+    fn begin_transmit(&self, data: &[u8]) -> io::Result<bool> {
+        let deadline = Instant::now() + MAX_BUS_WAIT;
+        loop {
+            {
+                let mut guard = self.half_duplex.lock().map_err(lock_error)?;
+                let half_duplex = match guard.as_mut() {
+                    Some(half_duplex) => half_duplex,
+                    None => return Ok(false),
+                };
+                let quiet = half_duplex.last_receive.map_or(true, |received| {
+                    received.elapsed() >= half_duplex.turnaround
+                });
+                if !half_duplex.transmitting && quiet {
+                    half_duplex.transmitting = true;
+                    half_duplex.echo.extend(data);
+                    return Ok(true);
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "the half-duplex bus did not go quiet",
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// `end_transmit` Release the bus, the echo still to come is discarded by the reads. After
+    /// a failed write it is unknown what was sent, so the input is cleared instead.
+    fn end_transmit(&mut self, sent: bool) -> io::Result<()> {
+        let cleared = if sent {
+            Ok(())
+        } else {
+            self.inner.clear(ClearBuffer::Input)
+        };
+        let mut guard = self.half_duplex.lock().map_err(lock_error)?;
+        if let Some(half_duplex) = guard.as_mut() {
+            half_duplex.transmitting = false;
+            half_duplex.last_transmit = Some(Instant::now());
+            if !sent {
+                half_duplex.echo.clear();
+            }
+        }
+        cleared.map_err(Into::into)
+    }
+
+    /// `send` Write to the port, switching the direction or taking turns as configured.
+    fn send(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let half_duplex = self.begin_transmit(buf)?;
+        let written = self.transmit(buf, half_duplex);
+        if let Ok(size) = written {
+            self.record(true, &buf[..size]);
+        }
+        if half_duplex {
+            let ended = self.end_transmit(written.is_ok());
+            written.and_then(|size| ended.map(|_| size))
+        } else {
+            written
+        }
+    }
+
+    fn transmit(&mut self, buf: &[u8], half_duplex: bool) -> io::Result<usize> {
+        let guard = self.direction.lock().map_err(lock_error)?;
+        let direction = match *guard {
+            Some(direction) => direction,
+            // the echo is only complete once the output is drained
+            None if half_duplex => {
+                return self
+                    .inner
+                    .write_all(buf)
+                    .and(self.inner.flush())
+                    .map(|_| buf.len())
+            }
+            None => return self.inner.write(buf),
+        };
+        self.inner.write_request_to_send(!direction.invert)?;
+        if !direction.pre_delay.is_zero() {
+            std::thread::sleep(direction.pre_delay);
+        }
+        // the whole buffer, a partial write would release RTS in the middle of a frame
+        let written = self
+            .inner
+            .write_all(buf)
+            .and_then(|_| wait_sent(self.inner.as_mut(), &direction));
+        if !direction.post_delay.is_zero() {
+            std::thread::sleep(direction.post_delay);
+        }
+        self.inner.write_request_to_send(direction.invert)?;
+        written.map(|_| buf.len())
+    }
+
+    /// `unwrap` Pass `raw` from the wire through the encryption and the compression and move
+    /// what comes out into `buf`, `None` when neither is set. Data that does not fit is kept for
+    /// the next read.
+    fn unwrap(&self, raw: &[u8], buf: &mut [u8]) -> io::Result<Option<usize>> {
+        let mut link = self.link.lock().map_err(lock_error)?;
+        let mut compression = self.compression.lock().map_err(lock_error)?;
+        match (link.as_mut(), compression.as_mut()) {
+            (None, None) => Ok(None),
+            (Some(link), None) => {
+                link.receive(raw);
+                Ok(Some(link.take(buf)))
+            }
+            (link, Some(compression)) => {
+                match link {
+                    Some(link) => {
+                        link.receive(raw);
+                        let mut decrypted = vec![0; link.pending()];
+                        link.take(&mut decrypted);
+                        compression.receive(&decrypted);
+                    }
+                    None => compression.receive(raw),
+                }
+                Ok(Some(compression.take(buf)))
+            }
+        }
+    }
+}
+
+impl io::Read for Rs485Port {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.refuse(Access::WriteOnly)?;
+        match self.unwrap(&[], buf)? {
+            Some(size) if size > 0 => return Ok(size),
+            _ => {}
+        }
+        let size = self.inner.read(buf)?;
+        let returned = Instant::now();
+        self.record(false, &buf[..size]);
+        let mut guard = self.half_duplex.lock().map_err(lock_error)?;
+        let mut echo = 0;
+        if let Some(half_duplex) = guard.as_mut() {
+            echo = half_duplex.discard_echo(&buf[..size], returned);
+            if size > 0 && echo == size {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "echo of the own transmission discarded",
+                ));
+            }
+            if size > 0 {
+                half_duplex.last_receive = Some(returned);
+            }
+        }
+        drop(guard);
+        // the end of the stream stays one
+        if size == 0 {
+            return Ok(0);
+        }
+        let raw = buf[echo..size].to_vec();
+        match self.unwrap(&raw, buf)? {
+            None => {
+                buf[..raw.len()].copy_from_slice(&raw);
+                Ok(raw.len())
+            }
+            Some(0) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "no complete frame received",
+            )),
+            Some(size) => Ok(size),
+        }
+    }
+}
+
+impl io::Write for Rs485Port {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.refuse(Access::ReadOnly)?;
+        let rewritten = middleware::outgoing(&self.middleware, buf).map_err(|reason| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("the middleware refused the write: {}", reason),
+            )
+        })?;
+        let compressed = self
+            .compression
+            .lock()
+            .map_err(lock_error)?
+            .as_mut()
+            .map(|compression| compression.seal(rewritten.as_deref().unwrap_or(buf)));
+        let rewritten = compressed.or(rewritten);
+        let sealed = match self.link.lock().map_err(lock_error)?.as_mut() {
+            Some(link) => Some(
+                link.seal(rewritten.as_deref().unwrap_or(buf))
+                    .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?,
+            ),
+            None => None,
+        };
+        let data = match sealed.or(rewritten) {
+            Some(data) => data,
+            None => return self.send(buf),
+        };
+        // the caller wrote `buf`, so all the middleware, the compression or the link made of it
+        // is written in its place
+        let mut rest: &[u8] = &data;
+        while !rest.is_empty() {
+            match self.send(rest) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(size) => rest = &rest[size..],
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl SerialPort for Rs485Port {
+    fn name(&self) -> Option<String> {
+        self.inner.name()
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        self.inner.baud_rate()
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        self.inner.data_bits()
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        self.inner.flow_control()
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        self.inner.parity()
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        self.inner.stop_bits()
+    }
+
+    fn timeout(&self) -> Duration {
+        self.inner.timeout()
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.inner.set_baud_rate(baud_rate)
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.inner.set_data_bits(data_bits)
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.inner.set_flow_control(flow_control)
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.inner.set_parity(parity)
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.inner.set_stop_bits(stop_bits)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.inner.set_timeout(timeout)
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_request_to_send(level)
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.inner.write_data_terminal_ready(level)
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.inner.read_clear_to_send()
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.inner.read_data_set_ready()
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.inner.read_ring_indicator()
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.inner.read_carrier_detect()
+    }
+
+    /// Decrypted or decompressed data not read yet is added to the raw bytes the driver holds.
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        let decrypted = match self.link.lock() {
+            Ok(link) => link.as_ref().map_or(0, |link| link.pending()),
+            Err(_) => 0,
+        };
+        let decompressed = match self.compression.lock() {
+            Ok(compression) => compression.as_ref().map_or(0, |compression| compression.pending()),
+            Err(_) => 0,
+        };
+        Ok(self.inner.bytes_to_read()? + (decrypted + decompressed) as u32)
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        self.inner.bytes_to_write()
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        self.inner.clear(buffer_to_clear)
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(Rs485Port {
+            inner: self.inner.try_clone()?,
+            access: self.access.clone(),
+            direction: self.direction.clone(),
+            half_duplex: self.half_duplex.clone(),
+            pcap: self.pcap.clone(),
+            tap: self.tap.clone(),
+            middleware: self.middleware.clone(),
+            link: self.link.clone(),
+            compression: self.compression.clone(),
+        }))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        self.refuse(Access::ReadOnly)?;
+        self.inner.set_break()
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.inner.clear_break()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sent(data: &[u8], turnaround: Duration) -> (HalfDuplex, Instant) {
+        let mut half_duplex = HalfDuplex::new(turnaround);
+        half_duplex.echo.extend(data);
+        let now = Instant::now();
+        half_duplex.last_transmit = Some(now);
+        (half_duplex, now)
+    }
+
+    #[test]
+    fn echo_and_reply_in_one_read() {
+        let (mut half_duplex, now) = sent(&[0x01, 0x03, 0x00, 0x00], Duration::from_millis(5));
+        assert_eq!(half_duplex.discard_echo(&[0x01, 0x03], now), 2);
+        // the rest of the echo with the first bytes of the reply
+        assert_eq!(
+            half_duplex.discard_echo(&[0x00, 0x00, 0x01, 0x03, 0x02], now),
+            2
+        );
+        assert_eq!(half_duplex.discard_echo(&[0x01, 0x03], now), 0);
+    }
+
+    #[test]
+    fn echo_ends_at_a_different_byte() {
+        let (mut half_duplex, now) = sent(b"abc", Duration::from_millis(5));
+        assert_eq!(half_duplex.discard_echo(b"aXc", now), 1);
+        assert_eq!(half_duplex.discard_echo(b"c", now), 0);
+    }
+
+    #[test]
+    fn echo_expires_after_the_turnaround() {
+        let (mut half_duplex, now) = sent(b"abc", Duration::from_millis(5));
+        let later = now + Duration::from_millis(6);
+        assert_eq!(half_duplex.discard_echo(b"abc", later), 0);
+        // not while transmitting
+        let (mut half_duplex, now) = sent(b"abc", Duration::from_millis(5));
+        half_duplex.transmitting = true;
+        assert_eq!(
+            half_duplex.discard_echo(b"abc", now + Duration::from_secs(1)),
+            3
+        );
+    }
+}
@@ -0,0 +1,500 @@
+//! Declarative description of binary frames, parsed in the read thread into field maps.
+
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+
+/// Received bytes kept while looking for a complete frame.
+const MAX_PENDING: usize = 64 * 1024;
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+impl Default for Endian {
+    fn default() -> Self {
+        Endian::Little
+    }
+}
+
+/// `LengthField` Where the frame carries its own length.
+#[derive(Deserialize)]
+pub struct LengthField {
+    /// Offset from the start of the frame, sync bytes included.
+    pub offset: usize,
+    /// 1, 2 or 4 bytes.
+    pub size: usize,
+    pub endian: Option<Endian>,
+    /// Added to the value to get the length of the whole frame.
+    #[serde(default)]
+    pub adjust: i64,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum FieldType {
+    #[serde(rename = "u8")]
+    U8,
+    #[serde(rename = "i8")]
+    I8,
+    #[serde(rename = "u16")]
+    U16,
+    #[serde(rename = "i16")]
+    I16,
+    #[serde(rename = "u32")]
+    U32,
+    #[serde(rename = "i32")]
+    I32,
+    #[serde(rename = "u64")]
+    U64,
+    #[serde(rename = "i64")]
+    I64,
+    #[serde(rename = "f32")]
+    F32,
+    #[serde(rename = "f64")]
+    F64,
+    #[serde(rename = "bytes")]
+    Bytes,
+    #[serde(rename = "string")]
+    String,
+}
+
+/// `Field` One value of the frame.
+#[derive(Deserialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    /// Offset from the start of the frame, sync bytes included.
+    pub offset: usize,
+    /// Length of `bytes` and `string` fields, by default up to the checksum.
+    pub size: Option<usize>,
+    pub endian: Option<Endian>,
+    /// Numbers are multiplied with it, e.g. `0.1` for tenths of a degree.
+    pub scale: Option<f64>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum Algorithm {
+    Sum8,
+    Xor8,
+    Crc16Modbus,
+    Crc16Ccitt,
+    Crc32,
+}
+
+/// `Checksum` Stored in the last bytes of the frame.
+#[derive(Deserialize)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    /// First byte covered, by default the start of the frame.
+    #[serde(default)]
+    pub start: usize,
+    pub endian: Option<Endian>,
+}
+
+/// `Schema` The frame description loaded from JSON.
+#[derive(Deserialize)]
+pub struct Schema {
+    /// Bytes every frame starts with.
+    #[serde(default)]
+    pub sync: Vec<u8>,
+    pub length: Option<LengthField>,
+    /// Length of every frame when there is no length field.
+    pub frame_length: Option<usize>,
+    /// Byte order of fields without their own `endian`.
+    #[serde(default)]
+    pub endian: Endian,
+    #[serde(default)]
+    pub fields: Vec<Field>,
+    pub checksum: Option<Checksum>,
+}
+
+/// `Parsed` What the parser found in the stream.
+pub enum Parsed {
+    Packet {
+        fields: Map<String, Value>,
+        raw: Vec<u8>,
+    },
+    Invalid {
+        message: String,
+        raw: Vec<u8>,
+    },
+}
+
+fn width(algorithm: Algorithm) -> usize {
+    match algorithm {
+        Algorithm::Sum8 | Algorithm::Xor8 => 1,
+        Algorithm::Crc16Modbus | Algorithm::Crc16Ccitt => 2,
+        Algorithm::Crc32 => 4,
+    }
+}
+
+pub(crate) fn checksum(algorithm: Algorithm, data: &[u8]) -> u64 {
+    match algorithm {
+        Algorithm::Sum8 => data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) as u64,
+        Algorithm::Xor8 => data.iter().fold(0u8, |sum, byte| sum ^ byte) as u64,
+        Algorithm::Crc16Modbus => {
+            let mut crc: u16 = 0xffff;
+            for byte in data {
+                crc ^= *byte as u16;
+                for _ in 0..8 {
+                    crc = if crc & 1 != 0 {
+                        (crc >> 1) ^ 0xa001
+                    } else {
+                        crc >> 1
+                    };
+                }
+            }
+            crc as u64
+        }
+        Algorithm::Crc16Ccitt => {
+            let mut crc: u16 = 0xffff;
+            for byte in data {
+                crc ^= (*byte as u16) << 8;
+                for _ in 0..8 {
+                    crc = if crc & 0x8000 != 0 {
+                        (crc << 1) ^ 0x1021
+                    } else {
+                        crc << 1
+                    };
+                }
+            }
+            crc as u64
+        }
+        Algorithm::Crc32 => {
+            let mut crc: u32 = 0xffff_ffff;
+            for byte in data {
+                crc ^= *byte as u32;
+                for _ in 0..8 {
+                    crc = if crc & 1 != 0 {
+                        (crc >> 1) ^ 0xedb8_8320
+                    } else {
+                        crc >> 1
+                    };
+                }
+            }
+            (!crc) as u64
+        }
+    }
+}
+
+/// `unsigned` Read an unsigned integer of `data.len()` bytes.
+fn unsigned(data: &[u8], endian: Endian) -> u64 {
+    let fold = |value: u64, byte: &u8| (value << 8) | *byte as u64;
+    match endian {
+        Endian::Big => data.iter().fold(0, fold),
+        Endian::Little => data.iter().rev().fold(0, fold),
+    }
+}
+
+fn number(value: f64, scale: Option<f64>) -> Value {
+    Number::from_f64(value * scale.unwrap_or(1.0))
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+impl Schema {
+    /// `validate` Reject descriptions that cannot delimit a frame.
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.length, self.frame_length) {
+            (None, None) => return Err("Either length or frame_length is required".to_string()),
+            (Some(length), _) if ![1, 2, 4].contains(&length.size) => {
+                return Err("The length field must have 1, 2 or 4 bytes".to_string());
+            }
+            (None, Some(0)) => return Err("frame_length must not be 0".to_string()),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// `frame_length` Length of the frame at the start of `data`, once it is known.
+    fn frame_length(&self, data: &[u8]) -> Option<Result<usize, String>> {
+        match &self.length {
+            Some(length) => {
+                let bytes = data.get(length.offset..length.offset + length.size)?;
+                let value = unsigned(bytes, length.endian.unwrap_or(self.endian)) as i64;
+                let total = value + length.adjust;
+                let minimum = (length.offset + length.size) as i64;
+                if total < minimum || total > MAX_PENDING as i64 {
+                    return Some(Err(format!("Invalid frame length {}", total)));
+                }
+                Some(Ok(total as usize))
+            }
+            None => self.frame_length.map(Ok),
+        }
+    }
+
+    /// `parse` Check the checksum and extract the fields of a complete frame.
+    fn parse(&self, frame: &[u8]) -> Result<Map<String, Value>, String> {
+        let mut end = frame.len();
+        if let Some(check) = &self.checksum {
+            let width = width(check.algorithm);
+            if frame.len() < width + check.start {
+                return Err("Frame too short for its checksum".to_string());
+            }
+            end = frame.len() - width;
+            let expected = unsigned(&frame[end..], check.endian.unwrap_or(self.endian));
+            let actual = checksum(check.algorithm, &frame[check.start..end]);
+            if expected != actual {
+                return Err(format!(
+                    "Checksum mismatch: expected {:#x}, got {:#x}",
+                    expected, actual
+                ));
+            }
+        }
+        let mut fields = Map::new();
+        for field in &self.fields {
+            let endian = field.endian.unwrap_or(self.endian);
+            let size = match field.field_type {
+                FieldType::U8 | FieldType::I8 => 1,
+                FieldType::U16 | FieldType::I16 => 2,
+                FieldType::U32 | FieldType::I32 | FieldType::F32 => 4,
+                FieldType::U64 | FieldType::I64 | FieldType::F64 => 8,
+                FieldType::Bytes | FieldType::String => field
+                    .size
+                    .unwrap_or_else(|| end.saturating_sub(field.offset)),
+            };
+            let bytes = frame
+                .get(field.offset..field.offset + size)
+                .ok_or_else(|| format!("Field {} is outside the frame", field.name))?;
+            let raw = unsigned(bytes, endian);
+            let value = match field.field_type {
+                FieldType::U8 | FieldType::U16 | FieldType::U32 | FieldType::U64 => {
+                    match field.scale {
+                        Some(_) => number(raw as f64, field.scale),
+                        None => Value::from(raw),
+                    }
+                }
+                FieldType::I8 | FieldType::I16 | FieldType::I32 | FieldType::I64 => {
+                    // sign-extend from the field width
+                    let shift = 64 - size * 8;
+                    let signed = ((raw << shift) as i64) >> shift;
+                    match field.scale {
+                        Some(_) => number(signed as f64, field.scale),
+                        None => Value::from(signed),
+                    }
+                }
+                FieldType::F32 => number(f32::from_bits(raw as u32) as f64, field.scale),
+                FieldType::F64 => number(f64::from_bits(raw), field.scale),
+                FieldType::Bytes => Value::from(bytes.to_vec()),
+                FieldType::String => Value::from(String::from_utf8_lossy(bytes).into_owned()),
+            };
+            fields.insert(field.name.clone(), value);
+        }
+        Ok(fields)
+    }
+}
+
+/// `SchemaParser` A schema with the received bytes of the frame in progress.
+pub struct SchemaParser {
+    schema: Schema,
+    pending: Vec<u8>,
+}
+
+impl SchemaParser {
+    pub fn new(schema: Schema) -> Self {
+        SchemaParser {
+            schema,
+            pending: Vec::new(),
+        }
+    }
+
+    /// `feed` Collect received bytes, returns the frames they complete. After an invalid frame
+    /// the parser resynchronizes on the next sync bytes.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<Parsed> {
+        self.pending.extend_from_slice(data);
+        let mut parsed = Vec::new();
+        loop {
+            let sync = &self.schema.sync;
+            if !sync.is_empty() {
+                match self
+                    .pending
+                    .windows(sync.len())
+                    .position(|window| window == sync.as_slice())
+                {
+                    Some(start) => {
+                        self.pending.drain(..start);
+                    }
+                    None => {
+                        // keep what may be the start of the next sync sequence
+                        let keep = (sync.len() - 1).min(self.pending.len());
+                        let drop = self.pending.len() - keep;
+                        self.pending.drain(..drop);
+                        break;
+                    }
+                }
+            }
+            let length = match self.schema.frame_length(&self.pending) {
+                Some(Ok(length)) => length,
+                Some(Err(message)) => {
+                    let raw = self.pending.drain(..1).collect();
+                    parsed.push(Parsed::Invalid { message, raw });
+                    continue;
+                }
+                None => break,
+            };
+            if self.pending.len() < length {
+                break;
+            }
+            match self.schema.parse(&self.pending[..length]) {
+                Ok(fields) => {
+                    let raw = self.pending.drain(..length).collect();
+                    parsed.push(Parsed::Packet { fields, raw });
+                }
+                Err(message) => {
+                    let raw = self.pending[..length].to_vec();
+                    // the sync bytes may have been part of the payload, search again after them
+                    self.pending.drain(..1);
+                    parsed.push(Parsed::Invalid { message, raw });
+                }
+            }
+        }
+        if self.pending.len() > MAX_PENDING {
+            let excess = self.pending.len() - MAX_PENDING;
+            self.pending.drain(..excess);
+        }
+        parsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parser(schema: Value) -> SchemaParser {
+        let schema: Schema = serde_json::from_value(schema).unwrap();
+        schema.validate().unwrap();
+        SchemaParser::new(schema)
+    }
+
+    /// Sync `AA 55`, the length of the whole frame in byte 2, a u16, an i16 in tenths, a
+    /// string and a Modbus CRC.
+    fn sensor() -> SchemaParser {
+        parser(json!({
+            "sync": [0xaa, 0x55],
+            "length": { "offset": 2, "size": 1 },
+            "fields": [
+                { "name": "id", "type": "u16", "offset": 3, "endian": "Big" },
+                { "name": "temperature", "type": "i16", "offset": 5, "scale": 0.1 },
+                { "name": "name", "type": "string", "offset": 7 }
+            ],
+            "checksum": { "algorithm": "Crc16Modbus" }
+        }))
+    }
+
+    fn frame(id: u16, temperature: i16, name: &str) -> Vec<u8> {
+        let mut frame = vec![0xaa, 0x55, (9 + name.len()) as u8];
+        frame.extend(id.to_be_bytes());
+        frame.extend(temperature.to_le_bytes());
+        frame.extend(name.as_bytes());
+        let crc = checksum(Algorithm::Crc16Modbus, &frame) as u16;
+        frame.extend(crc.to_le_bytes());
+        frame
+    }
+
+    fn packets(parsed: Vec<Parsed>) -> Vec<Map<String, Value>> {
+        parsed
+            .into_iter()
+            .map(|parsed| match parsed {
+                Parsed::Packet { fields, .. } => fields,
+                Parsed::Invalid { message, .. } => panic!("invalid frame: {}", message),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn checksums_of_the_check_string() {
+        let data = b"123456789";
+        assert_eq!(checksum(Algorithm::Sum8, data), 0xdd);
+        assert_eq!(checksum(Algorithm::Xor8, data), 0x31);
+        assert_eq!(checksum(Algorithm::Crc16Modbus, data), 0x4b37);
+        assert_eq!(checksum(Algorithm::Crc16Ccitt, data), 0x29b1);
+        assert_eq!(checksum(Algorithm::Crc32, data), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn fields_are_extracted() {
+        let mut parser = sensor();
+        let fields = packets(parser.feed(&frame(0x1234, -215, "T1")));
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0]["id"], json!(0x1234));
+        assert_eq!(fields[0]["temperature"], json!(-21.5));
+        assert_eq!(fields[0]["name"], json!("T1"));
+    }
+
+    #[test]
+    fn frames_are_found_in_noise_and_across_reads() {
+        let mut parser = sensor();
+        let mut data = vec![0x00, 0xaa, 0x13];
+        data.extend(frame(1, 0, "a"));
+        data.extend(frame(2, 0, "bc"));
+        let (first, second) = data.split_at(7);
+        assert!(parser.feed(first).is_empty());
+        let fields = packets(parser.feed(second));
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0]["id"], json!(1));
+        assert_eq!(fields[1]["name"], json!("bc"));
+    }
+
+    #[test]
+    fn bad_checksum_resynchronizes() {
+        let mut parser = sensor();
+        let mut data = frame(1, 0, "x");
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        data.extend(frame(2, 0, "y"));
+        let parsed = parser.feed(&data);
+        assert_eq!(parsed.len(), 2);
+        match &parsed[0] {
+            Parsed::Invalid { message, raw } => {
+                assert!(message.starts_with("Checksum mismatch"));
+                assert_eq!(raw.len(), 10);
+            }
+            Parsed::Packet { .. } => panic!("corrupted frame accepted"),
+        }
+        assert!(matches!(&parsed[1], Parsed::Packet { fields, .. } if fields["id"] == json!(2)));
+    }
+
+    #[test]
+    fn invalid_length_is_skipped() {
+        let mut parser = sensor();
+        let mut data = vec![0xaa, 0x55, 0x01];
+        data.extend(frame(3, 0, ""));
+        let parsed = parser.feed(&data);
+        assert!(
+            matches!(&parsed[0], Parsed::Invalid { message, .. } if message.contains("length"))
+        );
+        assert!(matches!(&parsed[1], Parsed::Packet { fields, .. } if fields["id"] == json!(3)));
+    }
+
+    #[test]
+    fn fixed_length_frames_and_wide_types() {
+        let mut parser = parser(json!({
+            "frame_length": 13,
+            "endian": "Big",
+            "fields": [
+                { "name": "flag", "type": "i8", "offset": 0 },
+                { "name": "value", "type": "f32", "offset": 1 },
+                { "name": "count", "type": "u64", "offset": 5, "endian": "Little" }
+            ]
+        }));
+        let mut data = vec![0xff];
+        data.extend(1.5f32.to_be_bytes());
+        data.extend(u64::MAX.to_le_bytes());
+        let fields = packets(parser.feed(&data));
+        assert_eq!(fields[0]["flag"], json!(-1));
+        assert_eq!(fields[0]["value"], json!(1.5));
+        assert_eq!(fields[0]["count"], json!(u64::MAX));
+    }
+
+    #[test]
+    fn schemas_without_framing_are_rejected() {
+        let schema = |value| serde_json::from_value::<Schema>(value).unwrap().validate();
+        assert!(schema(json!({})).is_err());
+        assert!(schema(json!({ "frame_length": 0 })).is_err());
+        assert!(schema(json!({ "length": { "offset": 0, "size": 3 } })).is_err());
+        assert!(schema(json!({ "length": { "offset": 0, "size": 2 } })).is_ok());
+    }
+}
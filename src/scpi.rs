@@ -0,0 +1,215 @@
+//! SCPI instruments on serial: newline terminated commands and queries, draining of the error
+//! queue and IEEE 488.2 definite and indefinite length binary blocks.
+
+use serialport::{ClearBuffer, SerialPort};
+use std::time::{Duration, Instant};
+
+/// Stop draining the error queue of an instrument that never reports `0,"No error"`.
+const MAX_ERRORS: usize = 32;
+
+/// `Reader` The response bytes of one query, read until `deadline`.
+struct Reader<'a> {
+    port: &'a mut dyn SerialPort,
+    pending: Vec<u8>,
+    deadline: Instant,
+}
+
+impl<'a> Reader<'a> {
+    fn new(port: &'a mut dyn SerialPort, timeout: Duration) -> Self {
+        Reader {
+            port,
+            pending: Vec::new(),
+            deadline: Instant::now() + timeout,
+        }
+    }
+
+    fn fill(&mut self) -> Result<(), String> {
+        if Instant::now() >= self.deadline {
+            return Err("Timeout waiting for the instrument".to_string());
+        }
+        let mut buffer = [0u8; 1024];
+        match self.port.read(&mut buffer) {
+            Ok(size) => self.pending.extend_from_slice(&buffer[..size]),
+            Err(error) if error.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(error) => return Err(format!("Failed to read response: {}", error)),
+        }
+        Ok(())
+    }
+
+    /// `line` Up to the newline, `\r\n` is accepted too.
+    fn line(&mut self) -> Result<String, String> {
+        loop {
+            if let Some(end) = self.pending.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = self.pending.drain(..=end).collect();
+                return Ok(String::from_utf8_lossy(&line).trim_end().to_string());
+            }
+            self.fill()?;
+        }
+    }
+}
+
+fn send(port: &mut dyn SerialPort, command: &str) -> Result<(), String> {
+    port.write_all(format!("{}\n", command.trim_end()).as_bytes())
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write {}: {}", command, error))
+}
+
+/// `query` Write a query and return its response line.
+pub fn query(port: &mut dyn SerialPort, query: &str, timeout: Duration) -> Result<String, String> {
+    // an answer to an earlier query that timed out must not be taken for this one
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    send(port, query)?;
+    Reader::new(port, timeout).line()
+}
+
+/// `query_binary` Write a query answered with a binary block, e.g. `CURV?`, and return its
+/// payload.
+pub fn query_binary(
+    port: &mut dyn SerialPort,
+    query: &str,
+    timeout: Duration,
+) -> Result<Vec<u8>, String> {
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    send(port, query)?;
+    let mut reader = Reader::new(port, timeout);
+    loop {
+        if let Some(data) = binary_block(&reader.pending)? {
+            return Ok(data);
+        }
+        reader.fill()?;
+    }
+}
+
+/// `binary_block` The payload of the binary block in `data`, `None` until it is complete.
+fn binary_block(data: &[u8]) -> Result<Option<Vec<u8>>, String> {
+    // skip whitespace before the block
+    let start = match data.iter().position(|byte| !byte.is_ascii_whitespace()) {
+        Some(start) => start,
+        None => return Ok(None),
+    };
+    let data = &data[start..];
+    if data.len() < 2 {
+        return Ok(None);
+    }
+    if data[0] != b'#' || !data[1].is_ascii_digit() {
+        return Err(format!(
+            "Response is not a binary block: {}",
+            String::from_utf8_lossy(&data[..2])
+        ));
+    }
+    let digits = (data[1] - b'0') as usize;
+    if digits == 0 {
+        // indefinite length, ends with the newline
+        return Ok(data[2..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map(|end| data[2..2 + end].to_vec()));
+    }
+    let length = match data.get(2..2 + digits) {
+        Some(length) => length,
+        None => return Ok(None),
+    };
+    let length: usize = std::str::from_utf8(length)
+        .ok()
+        .and_then(|length| length.parse().ok())
+        .ok_or("Invalid length in binary block header")?;
+    Ok(data
+        .get(2 + digits..2 + digits + length)
+        .map(|payload| payload.to_vec()))
+}
+
+/// `errors` Read `SYST:ERR?` until the queue is empty, returns the errors in order.
+pub fn errors(port: &mut dyn SerialPort, timeout: Duration) -> Result<Vec<String>, String> {
+    let mut errors = Vec::new();
+    for _ in 0..MAX_ERRORS {
+        let error = query(port, "SYST:ERR?", timeout)?;
+        let code = error.split(',').next().unwrap_or("").trim();
+        if code.parse::<i32>() == Ok(0) {
+            break;
+        }
+        errors.push(error);
+    }
+    Ok(errors)
+}
+
+/// `command` Write a command without response; with `check_errors` the error queue is drained
+/// afterwards and its errors are returned as failure.
+pub fn command(
+    port: &mut dyn SerialPort,
+    command: &str,
+    check_errors: bool,
+    timeout: Duration,
+) -> Result<(), String> {
+    send(port, command)?;
+    if check_errors {
+        let errors = errors(port, timeout)?;
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+    }
+    Ok(())
+}
+
+/// `identify` Probe a closed port with `*IDN?` at each baud rate, returns the first answer.
+pub fn identify(path: &str, baud_rates: &[u32], timeout: Duration) -> Option<String> {
+    for baud_rate in baud_rates {
+        let mut port = match serialport::new(path, *baud_rate)
+            .timeout(Duration::from_millis(5))
+            .open()
+        {
+            Ok(port) => port,
+            // busy or gone, other rates will not help
+            Err(_) => return None,
+        };
+        match query(port.as_mut(), "*IDN?", timeout) {
+            // answers at the wrong rate are garbage without commas
+            Ok(identity) if identity.contains(',') && identity.is_ascii() => return Some(identity),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn definite_length_block() {
+        let response = b"#3010\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\n";
+        assert_eq!(
+            binary_block(response).unwrap(),
+            Some((0..10).collect::<Vec<u8>>())
+        );
+        // the payload may contain newlines and `#`
+        assert_eq!(
+            binary_block(b"\r\n #14\n#\n\r").unwrap(),
+            Some(b"\n#\n\r".to_vec())
+        );
+        assert_eq!(binary_block(b"#10").unwrap(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn incomplete_block_waits_for_more() {
+        let response = b"#3010\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09";
+        for end in 0..response.len() {
+            assert_eq!(binary_block(&response[..end]).unwrap(), None);
+        }
+        assert_eq!(binary_block(b"  \r\n").unwrap(), None);
+    }
+
+    #[test]
+    fn indefinite_length_block() {
+        assert_eq!(binary_block(b"#0abc\ndef").unwrap(), Some(b"abc".to_vec()));
+        assert_eq!(binary_block(b"#0abc").unwrap(), None);
+    }
+
+    #[test]
+    fn invalid_blocks() {
+        assert!(binary_block(b"1.234E+00\n").is_err());
+        assert!(binary_block(b"#A12").is_err());
+        assert!(binary_block(b"#2x1abc").is_err());
+    }
+}
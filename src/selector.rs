@@ -0,0 +1,112 @@
+//! Ports chosen by the USB device behind them, as their path may change between boots and
+//! every time the device is plugged in.
+
+use serde::{Deserialize, Serialize};
+use serialport::SerialPortType;
+
+/// `Selector` A port by `path`, or the first USB port matching all the identifiers given.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Selector {
+    pub path: Option<String>,
+    /// Vendor id in hexadecimal, as `available_ports` reports it, e.g. `0403`.
+    pub vid: Option<String>,
+    pub pid: Option<String>,
+    pub serial_number: Option<String>,
+    /// USB interface number, tells apart the ports of a composite device.
+    pub interface: Option<u8>,
+}
+
+fn parse_id(name: &str, value: &Option<String>) -> Result<Option<u16>, String> {
+    value
+        .as_ref()
+        .map(|value| {
+            let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+            u16::from_str_radix(digits, 16)
+                .map_err(|_| format!("Invalid {} {}, use 4 hexadecimal digits", name, value))
+        })
+        .transpose()
+}
+
+impl Selector {
+    /// `path` The port at `path`.
+    pub fn path(path: impl Into<String>) -> Self {
+        Selector {
+            path: Some(path.into()),
+            ..Selector::default()
+        }
+    }
+
+    /// `usb` The first port of the USB device `vid`:`pid`.
+    pub fn usb(vid: u16, pid: u16) -> Self {
+        Selector {
+            vid: Some(format!("{:04x}", vid)),
+            pid: Some(format!("{:04x}", pid)),
+            ..Selector::default()
+        }
+    }
+
+    /// `serial_number` Only match the device with this serial number.
+    pub fn serial_number(mut self, serial_number: impl Into<String>) -> Self {
+        self.serial_number = Some(serial_number.into());
+        self
+    }
+
+    /// `describe` The selector as told in messages, e.g. `0403:6001 A702TB52`.
+    pub fn describe(&self) -> String {
+        if let Some(path) = &self.path {
+            return path.clone();
+        }
+        let mut parts = vec![format!(
+            "{}:{}",
+            self.vid.as_deref().unwrap_or("*"),
+            self.pid.as_deref().unwrap_or("*")
+        )];
+        if let Some(serial_number) = &self.serial_number {
+            parts.push(serial_number.clone());
+        }
+        if let Some(interface) = self.interface {
+            parts.push(format!("interface {}", interface));
+        }
+        parts.join(" ")
+    }
+
+    /// `validate` Fail when the selector names no port or has malformed ids.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.path.is_some() {
+            return Ok(());
+        }
+        parse_id("vid", &self.vid)?;
+        parse_id("pid", &self.pid)?;
+        if self.vid.is_none() && self.pid.is_none() && self.serial_number.is_none() {
+            return Err("Select a port by path, vid, pid or serial number".to_string());
+        }
+        Ok(())
+    }
+
+    /// `resolve` The path of the port matching now, `None` when no port does. A `path` is
+    /// taken as it is, so ports that are not listed can be selected too.
+    pub fn resolve(&self) -> Result<Option<String>, String> {
+        if let Some(path) = &self.path {
+            return Ok(Some(path.clone()));
+        }
+        self.validate()?;
+        let vid = parse_id("vid", &self.vid)?;
+        let pid = parse_id("pid", &self.pid)?;
+        let ports = serialport::available_ports()
+            .map_err(|error| format!("Cannot list ports: {}", error))?;
+        let found = ports.into_iter().find(|port| match &port.port_type {
+            SerialPortType::UsbPort(info) => {
+                vid.map_or(true, |vid| vid == info.vid)
+                    && pid.map_or(true, |pid| pid == info.pid)
+                    && self.serial_number.as_ref().map_or(true, |serial_number| {
+                        info.serial_number.as_ref() == Some(serial_number)
+                    })
+                    && self
+                        .interface
+                        .map_or(true, |interface| info.interface == Some(interface))
+            }
+            _ => false,
+        });
+        Ok(found.map(|port| port.port_name))
+    }
+}
@@ -0,0 +1,136 @@
+//! Device Manager metadata of COM ports, read through SetupAPI.
+
+use std::collections::HashMap;
+use std::ptr;
+use windows_sys::core::GUID;
+use windows_sys::Win32::Devices::DeviceAndDriverInstallation::{
+    SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo, SetupDiGetClassDevsW,
+    SetupDiGetDeviceInstanceIdW, SetupDiGetDeviceRegistryPropertyW, SetupDiOpenDevRegKey,
+    DICS_FLAG_GLOBAL, DIGCF_PRESENT, DIREG_DEV, GUID_DEVCLASS_MODEM, GUID_DEVCLASS_PORTS,
+    HDEVINFO, MAX_DEVICE_ID_LEN, SPDRP_FRIENDLYNAME, SPDRP_SERVICE, SP_DEVINFO_DATA,
+};
+use windows_sys::Win32::Foundation::{FALSE, INVALID_HANDLE_VALUE, MAX_PATH};
+use windows_sys::Win32::System::Registry::{RegCloseKey, RegQueryValueExW, KEY_READ, REG_SZ};
+
+/// `PortDetails` What Device Manager shows for a COM port.
+#[derive(Default)]
+pub struct PortDetails {
+    /// e.g. `USB Serial Port (COM7)`
+    pub friendly_name: Option<String>,
+    /// e.g. `FTDIBUS\VID_0403+PID_6001+A702TB52A\0000`
+    pub instance_id: Option<String>,
+    /// Name of the driver service, e.g. `FTDIBUS` or `usbser`
+    pub driver: Option<String>,
+}
+
+fn from_utf16(buffer: &[u16]) -> String {
+    let end = buffer.iter().position(|unit| *unit == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..end])
+}
+
+fn property(devices: HDEVINFO, data: &SP_DEVINFO_DATA, property: u32) -> Option<String> {
+    let mut value_type = 0;
+    let mut buffer = [0u16; MAX_PATH as usize];
+    let result = unsafe {
+        SetupDiGetDeviceRegistryPropertyW(
+            devices,
+            data,
+            property,
+            &mut value_type,
+            buffer.as_mut_ptr() as *mut u8,
+            (buffer.len() * 2) as u32,
+            ptr::null_mut(),
+        )
+    };
+    if result == FALSE || value_type != REG_SZ {
+        return None;
+    }
+    Some(from_utf16(&buffer))
+}
+
+fn instance_id(devices: HDEVINFO, data: &SP_DEVINFO_DATA) -> Option<String> {
+    let mut buffer = [0u16; MAX_DEVICE_ID_LEN as usize];
+    let result = unsafe {
+        SetupDiGetDeviceInstanceIdW(
+            devices,
+            data,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            ptr::null_mut(),
+        )
+    };
+    if result == FALSE {
+        return None;
+    }
+    Some(from_utf16(&buffer))
+}
+
+/// `port_name` The `PortName` value of the device key, e.g. `COM7`.
+fn port_name(devices: HDEVINFO, data: &SP_DEVINFO_DATA) -> Option<String> {
+    let key = unsafe {
+        SetupDiOpenDevRegKey(devices, data, DICS_FLAG_GLOBAL, 0, DIREG_DEV, KEY_READ)
+    };
+    if key == INVALID_HANDLE_VALUE {
+        return None;
+    }
+    let name: Vec<u16> = "PortName".encode_utf16().chain(Some(0)).collect();
+    let mut buffer = [0u16; MAX_PATH as usize];
+    let mut size = (buffer.len() * 2) as u32;
+    let mut value_type = 0;
+    let error = unsafe {
+        RegQueryValueExW(
+            key,
+            name.as_ptr(),
+            ptr::null_mut(),
+            &mut value_type,
+            buffer.as_mut_ptr() as *mut u8,
+            &mut size,
+        )
+    };
+    unsafe { RegCloseKey(key) };
+    if error != 0 || value_type != REG_SZ {
+        return None;
+    }
+    Some(from_utf16(&buffer))
+}
+
+fn collect(class: &GUID, ports: &mut HashMap<String, PortDetails>) {
+    let devices = unsafe { SetupDiGetClassDevsW(class, ptr::null(), 0, DIGCF_PRESENT) };
+    if devices == INVALID_HANDLE_VALUE {
+        debug!("SetupDiGetClassDevsW failed: {}", std::io::Error::last_os_error());
+        return;
+    }
+    let mut index = 0;
+    loop {
+        let mut data = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ClassGuid: GUID::from_u128(0),
+            DevInst: 0,
+            Reserved: 0,
+        };
+        if unsafe { SetupDiEnumDeviceInfo(devices, index, &mut data) } == FALSE {
+            break;
+        }
+        index += 1;
+        if let Some(name) = port_name(devices, &data) {
+            ports.insert(
+                name,
+                PortDetails {
+                    friendly_name: property(devices, &data, SPDRP_FRIENDLYNAME),
+                    instance_id: instance_id(devices, &data),
+                    driver: property(devices, &data, SPDRP_SERVICE),
+                },
+            );
+        }
+    }
+    unsafe { SetupDiDestroyDeviceInfoList(devices) };
+}
+
+/// `port_details` Device Manager metadata of present COM ports (class Ports and Modem),
+/// keyed by port name.
+pub fn port_details() -> HashMap<String, PortDetails> {
+    let mut ports = HashMap::new();
+    collect(&GUID_DEVCLASS_PORTS, &mut ports);
+    collect(&GUID_DEVCLASS_MODEM, &mut ports);
+    ports
+}
@@ -0,0 +1,739 @@
+//! SMS in PDU mode (3GPP TS 23.040) over the AT transaction engine.
+
+use crate::at::{AtChannel, Urc};
+use crate::state::{SmsMessage, SmsReport};
+use serialport::SerialPort;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+
+/// The GSM 7-bit default alphabet, `\u{1b}` escapes to the extension table.
+const GSM7: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞ\u{1b}ÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?\
+¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+/// Characters of the extension table with their codes.
+const GSM7_EXTENSION: &[(char, u8)] = &[
+    ('\u{c}', 0x0a),
+    ('^', 0x14),
+    ('{', 0x28),
+    ('}', 0x29),
+    ('\\', 0x2f),
+    ('[', 0x3c),
+    ('~', 0x3d),
+    (']', 0x3e),
+    ('|', 0x40),
+    ('€', 0x65),
+];
+
+/// Reference of the next concatenated message.
+static REFERENCE: AtomicU8 = AtomicU8::new(0);
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(60);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `to_gsm7` Septets of `text`, `None` if a character is not in the GSM alphabet.
+fn to_gsm7(text: &str) -> Option<Vec<u8>> {
+    let mut septets = Vec::new();
+    for character in text.chars() {
+        if let Some(code) = GSM7
+            .chars()
+            .position(|gsm| gsm == character && gsm != '\u{1b}')
+        {
+            septets.push(code as u8);
+        } else {
+            let (_, code) = GSM7_EXTENSION.iter().find(|(gsm, _)| *gsm == character)?;
+            septets.push(0x1b);
+            septets.push(*code);
+        }
+    }
+    Some(septets)
+}
+
+fn from_gsm7(septets: &[u8]) -> String {
+    let table: Vec<char> = GSM7.chars().collect();
+    let mut text = String::new();
+    let mut escape = false;
+    for septet in septets {
+        if escape {
+            escape = false;
+            match GSM7_EXTENSION.iter().find(|(_, code)| code == septet) {
+                Some((character, _)) => text.push(*character),
+                None => text.push(table[*septet as usize & 0x7f]),
+            }
+        } else if *septet == 0x1b {
+            escape = true;
+        } else {
+            text.push(table[*septet as usize & 0x7f]);
+        }
+    }
+    text
+}
+
+/// `pack_septets` Pack septets LSB first, after `fill` zero bits.
+fn pack_septets(septets: &[u8], fill: usize) -> Vec<u8> {
+    let bits = fill + septets.len() * 7;
+    let mut octets = vec![0u8; (bits + 7) / 8];
+    for (index, septet) in septets.iter().enumerate() {
+        for bit in 0..7 {
+            if septet >> bit & 1 == 1 {
+                let position = fill + index * 7 + bit;
+                octets[position / 8] |= 1 << (position % 8);
+            }
+        }
+    }
+    octets
+}
+
+fn unpack_septets(octets: &[u8], fill: usize, count: usize) -> Vec<u8> {
+    (0..count)
+        .map(|index| {
+            (0..7).fold(0u8, |septet, bit| {
+                let position = fill + index * 7 + bit;
+                match octets.get(position / 8) {
+                    Some(octet) if octet >> (position % 8) & 1 == 1 => septet | 1 << bit,
+                    _ => septet,
+                }
+            })
+        })
+        .collect()
+}
+
+/// `encode_address` Length in digits, type of number and the swapped semi-octets.
+fn encode_address(number: &str) -> Result<Vec<u8>, String> {
+    let international = number.starts_with('+');
+    let digits: Vec<u8> = number
+        .trim_start_matches('+')
+        .chars()
+        .map(|digit| match digit {
+            '0'..='9' => Ok(digit as u8 - b'0'),
+            '*' => Ok(0x0a),
+            '#' => Ok(0x0b),
+            _ => Err(format!("Invalid phone number {}", number)),
+        })
+        .collect::<Result<_, _>>()?;
+    if digits.is_empty() {
+        return Err("The phone number is empty".to_string());
+    }
+    let mut address = vec![digits.len() as u8, if international { 0x91 } else { 0x81 }];
+    for pair in digits.chunks(2) {
+        address.push(pair[0] | pair.get(1).copied().unwrap_or(0x0f) << 4);
+    }
+    Ok(address)
+}
+
+/// `decode_address` The number and the length of the address field at the start of `data`.
+fn decode_address(data: &[u8]) -> Result<(String, usize), String> {
+    let digits = *data.first().ok_or("Truncated address")? as usize;
+    let kind = *data.get(1).ok_or("Truncated address")?;
+    let octets = (digits + 1) / 2;
+    let value = data.get(2..2 + octets).ok_or("Truncated address")?;
+    let number = if kind & 0x70 == 0x50 {
+        // alphanumeric sender, e.g. the name of the operator
+        from_gsm7(&unpack_septets(value, 0, digits * 4 / 7))
+    } else {
+        let mut number = String::new();
+        if kind & 0x70 == 0x10 {
+            number.push('+');
+        }
+        for octet in value {
+            for nibble in [octet & 0x0f, octet >> 4] {
+                match nibble {
+                    0..=9 => number.push((b'0' + nibble) as char),
+                    0x0a => number.push('*'),
+                    0x0b => number.push('#'),
+                    _ => {}
+                }
+            }
+        }
+        number
+    };
+    Ok((number, 2 + octets))
+}
+
+/// `decode_time` A service centre time stamp as ISO 8601.
+fn decode_time(data: &[u8]) -> Result<String, String> {
+    let data = data.get(..7).ok_or("Truncated time stamp")?;
+    let value = |octet: u8| (octet & 0x0f) * 10 + (octet >> 4);
+    let zone = value(data[6] & 0xf7) as i32 * 15;
+    let zone = if data[6] & 0x08 != 0 { -zone } else { zone };
+    Ok(format!(
+        "20{:02}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+        value(data[0]),
+        value(data[1]),
+        value(data[2]),
+        value(data[3]),
+        value(data[4]),
+        value(data[5]),
+        if zone < 0 { '-' } else { '+' },
+        zone.abs() / 60,
+        zone.abs() % 60
+    ))
+}
+
+pub(crate) fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+pub(crate) fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    if text.len() % 2 != 0 {
+        return Err("Odd length of PDU".to_string());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| {
+            u8::from_str_radix(text.get(index..index + 2).unwrap_or(""), 16)
+                .map_err(|_| "Invalid hex in PDU".to_string())
+        })
+        .collect()
+}
+
+/// `submit_pdus` The SMS-SUBMIT PDUs of a message, several for a long one, each with the
+/// length `AT+CMGS` expects.
+pub fn submit_pdus(number: &str, text: &str, report: bool) -> Result<Vec<(String, usize)>, String> {
+    let address = encode_address(number)?;
+    let gsm7 = to_gsm7(text);
+    // the user data of each part: septets or UCS-2 octets, and how many septets/octets it has
+    let parts: Vec<Vec<u8>> = match &gsm7 {
+        Some(septets) if septets.len() <= 160 => vec![septets.clone()],
+        Some(septets) => {
+            let mut parts = Vec::new();
+            let mut start = 0;
+            while start < septets.len() {
+                let mut end = (start + 153).min(septets.len());
+                // an escape and its character stay in one part
+                if end < septets.len() && septets[end - 1] == 0x1b {
+                    end -= 1;
+                }
+                parts.push(septets[start..end].to_vec());
+                start = end;
+            }
+            parts
+        }
+        None => {
+            let units: Vec<u16> = text.encode_utf16().collect();
+            let size = if units.len() <= 70 { 70 } else { 67 };
+            let mut parts = Vec::new();
+            let mut start = 0;
+            while start < units.len() {
+                let mut end = (start + size).min(units.len());
+                // a surrogate pair stays in one part
+                if end < units.len() && (0xd800..0xdc00).contains(&units[end - 1]) {
+                    end -= 1;
+                }
+                parts.push(
+                    units[start..end]
+                        .iter()
+                        .flat_map(|unit| unit.to_be_bytes())
+                        .collect(),
+                );
+                start = end;
+            }
+            parts
+        }
+    };
+    if parts.len() > 255 {
+        return Err("The message is too long".to_string());
+    }
+    let reference = REFERENCE.fetch_add(1, Ordering::Relaxed);
+    let total = parts.len();
+    parts
+        .iter()
+        .enumerate()
+        .map(|(index, part)| {
+            let header = if total > 1 {
+                vec![0x05, 0x00, 0x03, reference, total as u8, index as u8 + 1]
+            } else {
+                Vec::new()
+            };
+            let mut first = 0x01;
+            if report {
+                first |= 0x20;
+            }
+            if !header.is_empty() {
+                first |= 0x40;
+            }
+            let mut pdu = vec![0x00, first, 0x00];
+            pdu.extend_from_slice(&address);
+            pdu.push(0x00);
+            let (coding, length, data) = match gsm7 {
+                Some(_) => {
+                    let fill = (7 - header.len() * 8 % 7) % 7;
+                    let header_septets = (header.len() * 8 + fill) / 7;
+                    let mut data = header.clone();
+                    data.extend(pack_septets(part, fill));
+                    (0x00, header_septets + part.len(), data)
+                }
+                None => {
+                    let mut data = header.clone();
+                    data.extend_from_slice(part);
+                    (0x08, data.len(), data)
+                }
+            };
+            pdu.push(coding);
+            pdu.push(length as u8);
+            pdu.extend(data);
+            // `AT+CMGS` counts the octets after the service centre address
+            Ok((to_hex(&pdu), pdu.len() - 1))
+        })
+        .collect()
+}
+
+/// `decode_deliver` Decode a received SMS-DELIVER PDU, service centre address included.
+pub fn decode_deliver(pdu: &str) -> Result<SmsMessage, String> {
+    let data = from_hex(pdu)?;
+    let mut position = 1 + *data.first().ok_or("Empty PDU")? as usize;
+    let first = *data.get(position).ok_or("Truncated PDU")?;
+    if first & 0x03 != 0x00 {
+        return Err(format!("Not an SMS-DELIVER PDU: {:#04x}", first));
+    }
+    position += 1;
+    let (sender, size) = decode_address(data.get(position..).ok_or("Truncated PDU")?)?;
+    position += size;
+    let coding = *data.get(position + 1).ok_or("Truncated PDU")?;
+    let timestamp = decode_time(data.get(position + 2..).ok_or("Truncated PDU")?)?;
+    position += 9;
+    let length = *data.get(position).ok_or("Truncated PDU")? as usize;
+    let user_data = data.get(position + 1..).ok_or("Truncated PDU")?;
+    let mut header_size = 0;
+    let (mut reference, mut part, mut parts) = (None, None, None);
+    if first & 0x40 != 0 {
+        header_size = 1 + *user_data.first().ok_or("Truncated header")? as usize;
+        let header = user_data.get(1..header_size).ok_or("Truncated header")?;
+        let mut index = 0;
+        while index + 1 < header.len() {
+            let (id, size) = (header[index], header[index + 1] as usize);
+            let element = header.get(index + 2..index + 2 + size).unwrap_or(&[]);
+            match (id, element) {
+                (0x00, [value, total, sequence]) => {
+                    reference = Some(*value as u16);
+                    parts = Some(*total);
+                    part = Some(*sequence);
+                }
+                (0x08, [high, low, total, sequence]) => {
+                    reference = Some(u16::from_be_bytes([*high, *low]));
+                    parts = Some(*total);
+                    part = Some(*sequence);
+                }
+                _ => {}
+            }
+            index += 2 + size;
+        }
+    }
+    // alphabet from the data coding scheme, general and message class groups
+    let alphabet = match coding {
+        0x00..=0x7f => (coding >> 2) & 0x03,
+        0xf0..=0xff => (coding >> 2) & 0x01,
+        _ => 0,
+    };
+    let text = match alphabet {
+        0 => {
+            let fill = (7 - header_size * 8 % 7) % 7;
+            let header_septets = (header_size * 8 + fill) / 7;
+            let septets = unpack_septets(
+                user_data.get(header_size..).unwrap_or(&[]),
+                fill,
+                length.saturating_sub(header_septets),
+            );
+            from_gsm7(&septets)
+        }
+        2 => {
+            let octets = user_data.get(header_size..length).unwrap_or(&[]);
+            let units: Vec<u16> = octets
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair.get(1).copied().unwrap_or(0)]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => {
+            String::from_utf8_lossy(user_data.get(header_size..length).unwrap_or(&[])).into_owned()
+        }
+    };
+    Ok(SmsMessage {
+        index: None,
+        sender,
+        text,
+        timestamp,
+        reference,
+        part,
+        parts,
+    })
+}
+
+/// `decode_status_report` Decode an SMS-STATUS-REPORT PDU, service centre address included.
+pub fn decode_status_report(pdu: &str) -> Result<SmsReport, String> {
+    let data = from_hex(pdu)?;
+    let mut position = 1 + *data.first().ok_or("Empty PDU")? as usize;
+    let first = *data.get(position).ok_or("Truncated PDU")?;
+    if first & 0x03 != 0x02 {
+        return Err(format!("Not an SMS-STATUS-REPORT PDU: {:#04x}", first));
+    }
+    let reference = *data.get(position + 1).ok_or("Truncated PDU")?;
+    position += 2;
+    let (recipient, size) = decode_address(data.get(position..).ok_or("Truncated PDU")?)?;
+    position += size;
+    let timestamp = decode_time(data.get(position..).ok_or("Truncated PDU")?)?;
+    let discharge_time = decode_time(data.get(position + 7..).ok_or("Truncated PDU")?)?;
+    let status = *data.get(position + 14).ok_or("Truncated PDU")?;
+    Ok(SmsReport {
+        reference,
+        recipient,
+        status,
+        delivered: status == 0x00,
+        timestamp,
+        discharge_time,
+    })
+}
+
+/// `check` The response lines of a transaction that ended with `OK`.
+pub(crate) fn check(result: Result<crate::state::AtResponse, String>) -> Result<Vec<String>, String> {
+    let response = result?;
+    if response.result == "OK" {
+        Ok(response.lines)
+    } else {
+        Err(response.result)
+    }
+}
+
+/// `send` Send a message, returns the reference of every part.
+pub fn send(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    number: &str,
+    text: &str,
+    report: bool,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<Vec<u8>, String> {
+    check(channel.transact(port, reading, "AT+CMGF=0", None, TIMEOUT, on_urc))?;
+    let mut references = Vec::new();
+    for (pdu, length) in submit_pdus(number, text, report)? {
+        let payload = format!("{}\u{1a}", pdu);
+        let lines = check(channel.transact(
+            port,
+            reading,
+            &format!("AT+CMGS={}", length),
+            Some(payload.as_bytes()),
+            SEND_TIMEOUT,
+            on_urc,
+        ))?;
+        let reference = lines
+            .iter()
+            .find_map(|line| line.strip_prefix("+CMGS:"))
+            .and_then(|value| value.trim().parse().ok())
+            .ok_or("The modem did not return a message reference")?;
+        references.push(reference);
+    }
+    Ok(references)
+}
+
+/// `parse_listing` Messages of `+CMGR`/`+CMGL` responses, each header followed by its PDU.
+fn parse_listing(lines: &[String], prefix: &str, index: Option<u32>) -> Vec<SmsMessage> {
+    let mut messages = Vec::new();
+    let mut lines = lines.iter();
+    while let Some(line) = lines.next() {
+        let header = match line.strip_prefix(prefix) {
+            Some(header) => header,
+            None => continue,
+        };
+        let pdu = match lines.next() {
+            Some(pdu) => pdu,
+            None => break,
+        };
+        match decode_deliver(pdu) {
+            Ok(mut message) => {
+                message.index = index.or_else(|| header.split(',').next()?.trim().parse().ok());
+                messages.push(message);
+            }
+            Err(error) => debug!("Cannot decode stored message: {}", error),
+        }
+    }
+    messages
+}
+
+/// `read` The message stored at `index`.
+pub fn read(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    index: u32,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<SmsMessage, String> {
+    check(channel.transact(port, reading, "AT+CMGF=0", None, TIMEOUT, on_urc))?;
+    let command = format!("AT+CMGR={}", index);
+    let lines = check(channel.transact(port, reading, &command, None, TIMEOUT, on_urc))?;
+    parse_listing(&lines, "+CMGR:", Some(index))
+        .pop()
+        .ok_or_else(|| format!("No message at index {}", index))
+}
+
+/// `list` Stored messages, `status` 0 unread, 1 read, 4 all.
+pub fn list(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    status: u8,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<Vec<SmsMessage>, String> {
+    check(channel.transact(port, reading, "AT+CMGF=0", None, TIMEOUT, on_urc))?;
+    let command = format!("AT+CMGL={}", status);
+    let lines = check(channel.transact(port, reading, &command, None, TIMEOUT, on_urc))?;
+    Ok(parse_listing(&lines, "+CMGL:", None))
+}
+
+/// `delete` Delete the message stored at `index`.
+pub fn delete(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    index: u32,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<(), String> {
+    let command = format!("AT+CMGD={}", index);
+    check(channel.transact(port, reading, &command, None, TIMEOUT, on_urc)).map(|_| ())
+}
+
+/// `enable_notifications` Switch to PDU mode and have new messages and delivery reports
+/// announced with `+CMTI` and `+CDS`.
+pub fn enable_notifications(
+    channel: &AtChannel,
+    port: &mut dyn SerialPort,
+    reading: bool,
+    on_urc: &mut dyn FnMut(Urc),
+) -> Result<(), String> {
+    check(channel.transact(port, reading, "AT+CMGF=0", None, TIMEOUT, on_urc))?;
+    check(channel.transact(port, reading, "AT+CNMI=2,1,0,1,0", None, TIMEOUT, on_urc))?;
+    Ok(())
+}
+
+/// `new_message_index` The storage index announced by `+CMTI: "SM",3`.
+pub fn new_message_index(line: &str) -> Option<u32> {
+    line.strip_prefix("+CMTI:")?
+        .rsplit(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The SMS-DELIVER a recipient gets for an SMS-SUBMIT, stamped `2024-05-12T14:09:52+02:00`.
+    fn as_deliver(submit: &str) -> String {
+        let data = from_hex(submit).unwrap();
+        let address = 2 + (data[3] as usize + 1) / 2;
+        let mut deliver = vec![0x00, data[1] & 0x40];
+        // address, protocol identifier and data coding scheme
+        deliver.extend_from_slice(&data[3..3 + address + 2]);
+        deliver.extend_from_slice(&[0x42, 0x50, 0x21, 0x41, 0x90, 0x25, 0x80]);
+        deliver.extend_from_slice(&data[3 + address + 2..]);
+        to_hex(&deliver)
+    }
+
+    #[test]
+    fn gsm7_round_trip() {
+        let text = "Hello @£$ {ÄÖÜ} [€5] ~^|\\ ñ";
+        let septets = to_gsm7(text).unwrap();
+        assert_eq!(&septets[..6], &[0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x20]);
+        assert_eq!(
+            to_gsm7("{€}").unwrap(),
+            vec![0x1b, 0x28, 0x1b, 0x65, 0x1b, 0x29]
+        );
+        assert_eq!(from_gsm7(&septets), text);
+        assert_eq!(to_gsm7("Привет"), None);
+    }
+
+    #[test]
+    fn septet_packing() {
+        let septets = to_gsm7("hellohello").unwrap();
+        let packed = pack_septets(&septets, 0);
+        assert_eq!(to_hex(&packed), "E8329BFD4697D9EC37");
+        assert_eq!(unpack_septets(&packed, 0, septets.len()), septets);
+        // after a 6 octet user data header, aligned on the next septet
+        let septets = to_gsm7("hello").unwrap();
+        let packed = pack_septets(&septets, 1);
+        assert_eq!(to_hex(&packed), "D06536FB0D");
+        assert_eq!(unpack_septets(&packed, 1, septets.len()), septets);
+        for fill in 0..7 {
+            let septets: Vec<u8> = (0..=0x7f).collect();
+            let packed = pack_septets(&septets, fill);
+            assert_eq!(packed.len(), (fill + septets.len() * 7 + 7) / 8);
+            assert_eq!(unpack_septets(&packed, fill, septets.len()), septets);
+        }
+    }
+
+    #[test]
+    fn addresses() {
+        // odd number of digits, padded with 0xF
+        let address = encode_address("+46708251358").unwrap();
+        assert_eq!(to_hex(&address), "0B916407281553F8");
+        assert_eq!(
+            decode_address(&address).unwrap(),
+            ("+46708251358".to_string(), 8)
+        );
+        let address = encode_address("1234*#").unwrap();
+        assert_eq!(to_hex(&address), "06812143BA");
+        assert_eq!(decode_address(&address).unwrap(), ("1234*#".to_string(), 5));
+        assert!(encode_address("").is_err());
+        assert!(encode_address("+49 151").is_err());
+        // alphanumeric sender
+        let address = from_hex("0ED0D637396C7EBBCB").unwrap();
+        assert_eq!(
+            decode_address(&address).unwrap(),
+            ("Vodafone".to_string(), 9)
+        );
+        assert!(decode_address(&from_hex("0B916407").unwrap()).is_err());
+    }
+
+    #[test]
+    fn time_stamps() {
+        let stamp = [0x42, 0x50, 0x21, 0x41, 0x90, 0x25, 0x80];
+        assert_eq!(decode_time(&stamp).unwrap(), "2024-05-12T14:09:52+02:00");
+        let stamp = [0x42, 0x50, 0x21, 0x41, 0x90, 0x25, 0x0a];
+        assert_eq!(decode_time(&stamp).unwrap(), "2024-05-12T14:09:52-05:00");
+        assert!(decode_time(&stamp[..6]).is_err());
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(to_hex(&[0x00, 0x0b, 0xf8]), "000BF8");
+        assert_eq!(from_hex(" 000bF8\r\n").unwrap(), vec![0x00, 0x0b, 0xf8]);
+        assert!(from_hex("000").is_err());
+        assert!(from_hex("0G").is_err());
+    }
+
+    #[test]
+    fn submit_single() {
+        let pdus = submit_pdus("+46708251358", "hellohello", false).unwrap();
+        assert_eq!(
+            pdus,
+            vec![(
+                "0001000B916407281553F800000AE8329BFD4697D9EC37".to_string(),
+                22
+            )]
+        );
+        let pdus = submit_pdus("+46708251358", "hellohello", true).unwrap();
+        assert!(pdus[0].0.starts_with("0021"));
+        // UCS-2, the length in octets
+        let pdus = submit_pdus("+4915", "Привет", false).unwrap();
+        assert_eq!(
+            pdus,
+            vec![(
+                "0001000491945100080C041F04400438043204350442".to_string(),
+                21
+            )]
+        );
+    }
+
+    #[test]
+    fn submit_concatenated() {
+        let text: String = (0..400u32)
+            .map(|index| (b'a' + (index % 26) as u8) as char)
+            .collect();
+        let pdus = submit_pdus("+46708251358", &text, false).unwrap();
+        assert_eq!(pdus.len(), 3);
+        let mut received = String::new();
+        let mut references = Vec::new();
+        for (index, (pdu, length)) in pdus.iter().enumerate() {
+            assert_eq!(from_hex(pdu).unwrap().len(), length + 1);
+            let message = decode_deliver(&as_deliver(pdu)).unwrap();
+            assert_eq!(message.sender, "+46708251358");
+            assert_eq!(message.timestamp, "2024-05-12T14:09:52+02:00");
+            assert_eq!(message.part, Some(index as u8 + 1));
+            assert_eq!(message.parts, Some(3));
+            references.push(message.reference);
+            received.push_str(&message.text);
+        }
+        assert_eq!(received, text);
+        assert!(references
+            .iter()
+            .all(|reference| *reference == references[0]));
+        // 7 septets of header and 153 of text
+        let data = from_hex(&pdus[0].0).unwrap();
+        assert_eq!(data[1], 0x41);
+        assert_eq!(data[13], 160);
+        assert_eq!(&data[14..20], &[0x05, 0x00, 0x03, data[17], 0x03, 0x01]);
+    }
+
+    #[test]
+    fn submit_keeps_escapes_and_surrogates_whole() {
+        let text = format!("{}€{}", "a".repeat(152), "b".repeat(10));
+        let pdus = submit_pdus("12345", &text, false).unwrap();
+        let first = decode_deliver(&as_deliver(&pdus[0].0)).unwrap();
+        let second = decode_deliver(&as_deliver(&pdus[1].0)).unwrap();
+        assert_eq!(first.text, "a".repeat(152));
+        assert_eq!(second.text, format!("€{}", "b".repeat(10)));
+        assert_eq!(first.sender, "12345");
+
+        let text = format!("{}😀{}", "Ж".repeat(66), "Ж".repeat(10));
+        let pdus = submit_pdus("12345", &text, false).unwrap();
+        let first = decode_deliver(&as_deliver(&pdus[0].0)).unwrap();
+        let second = decode_deliver(&as_deliver(&pdus[1].0)).unwrap();
+        assert_eq!(first.text, "Ж".repeat(66));
+        assert_eq!(second.text, format!("😀{}", "Ж".repeat(10)));
+    }
+
+    #[test]
+    fn deliver_known_pdus() {
+        let message = decode_deliver(
+            "07917283010010F5040BC87238880900F10000993092516195800AE8329BFD4697D9EC37",
+        )
+        .unwrap();
+        assert_eq!(message.sender, "27838890001");
+        assert_eq!(message.text, "hellohello");
+        assert_eq!(message.reference, None);
+        // UCS-2 from an alphanumeric sender
+        let message = decode_deliver("00000ED0D637396C7EBBCB000842502141902580040416043B").unwrap();
+        assert_eq!(message.sender, "Vodafone");
+        assert_eq!(message.text, "Жл");
+        assert_eq!(message.timestamp, "2024-05-12T14:09:52+02:00");
+        // 16 bit reference in a 7 octet header, the text starts on a septet boundary
+        let message =
+            decode_deliver("00400B916407281553F80000425021419025800A06080412340201E834").unwrap();
+        assert_eq!(message.text, "hi");
+        assert_eq!(message.reference, Some(0x1234));
+        assert_eq!((message.part, message.parts), (Some(1), Some(2)));
+        assert!(decode_deliver("0001000B916407281553F800000AE8329BFD4697D9EC37").is_err());
+        assert!(decode_deliver("00040B916407").is_err());
+    }
+
+    #[test]
+    fn status_report() {
+        let report =
+            decode_status_report("00062A0B916407281553F8425021419025804250214191258000").unwrap();
+        assert_eq!(report.reference, 0x2a);
+        assert_eq!(report.recipient, "+46708251358");
+        assert_eq!(report.timestamp, "2024-05-12T14:09:52+02:00");
+        assert_eq!(report.discharge_time, "2024-05-12T14:19:52+02:00");
+        assert!(report.delivered);
+        let report =
+            decode_status_report("00062A0B916407281553F8425021419025804250214191258046").unwrap();
+        assert_eq!(report.status, 0x46);
+        assert!(!report.delivered);
+        assert!(decode_status_report("00040B916407281553F8").is_err());
+    }
+
+    #[test]
+    fn listings() {
+        let pdu = "00040B916407281553F80000425021419025800AE8329BFD4697D9EC37".to_string();
+        let lines = vec![
+            "+CMGL: 2,1,,23".to_string(),
+            pdu.clone(),
+            "+CMGL: 5,0,,3".to_string(),
+            "00040B".to_string(),
+            "+CMGL: 7,0,,23".to_string(),
+            pdu.clone(),
+        ];
+        let messages = parse_listing(&lines, "+CMGL:", None);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].index, Some(2));
+        assert_eq!(messages[1].index, Some(7));
+        assert_eq!(messages[1].text, "hellohello");
+        let lines = vec!["+CMGR: 0,,23".to_string(), pdu];
+        assert_eq!(parse_listing(&lines, "+CMGR:", Some(4))[0].index, Some(4));
+
+        assert_eq!(new_message_index("+CMTI: \"SM\",3"), Some(3));
+        assert_eq!(new_message_index("+CMTI: \"ME\", 12"), Some(12));
+        assert_eq!(new_message_index("+CDS: 25"), None);
+    }
+}
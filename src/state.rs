@@ -1,18 +1,314 @@
+use crate::ack::SharedExpectation;
+use crate::address::Filter;
+use crate::at::AtChannel;
+use crate::autoopen::AutoOpen;
+use crate::backpressure::Backpressure;
+use crate::capture::Capture;
+use crate::codec::{Codec, CodecFactory};
+use crate::compression::SharedCompression;
+use crate::consumer::Stream;
+use crate::dmx::Dmx;
+use crate::encryption::SharedLink;
+use crate::exchange::SharedTimeout;
+use crate::firmata::FirmataMessage;
+use crate::linestatus::LineStatus;
+use crate::mdb::{Mdb, MdbEvent};
+use crate::middleware::{MiddlewareFactory, SharedChain};
+use crate::midi::MidiMessage;
+use crate::mux::Mux;
+use crate::operation::Operations;
+use crate::pcapng::SharedPcap;
+use crate::poller::Poller;
+use crate::profile::Profile;
+use crate::queue::TxQueue;
+use crate::reactor::Reactor;
+use crate::reader::{now_millis, History, RingBuffer};
+use crate::rs485::{
+    Access, NativeRs485, Rs485Port, SharedAccess, SharedDirection, SharedHalfDuplex,
+};
+use crate::schema::SchemaParser;
+use crate::store::{SavedSettings, SettingsStore, Store};
+use crate::traffic::{SharedTap, TrafficObserver};
+use crate::ubx::UbxMessage;
+use crate::watcher::Watcher;
+use crate::xbee::XBeeFrame;
 use serde::Serialize;
+use serde_json::{Map, Value};
 use serialport::{self, SerialPort};
 use std::{
-    collections::HashMap,
-    sync::{mpsc::Sender, Arc, Mutex},
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8},
+        mpsc::Sender,
+        Arc, Mutex,
+    },
+    time::Instant,
 };
 
 #[derive(Default)]
 pub struct SerialportState {
     // plugin state, configuration fields
     pub serialports: Arc<Mutex<HashMap<String, SerialportInfo>>>,
+    /// Look up the process holding a busy port when opening fails.
+    pub busy_diagnostics: bool,
+    /// Codecs registered with the plugin `Builder`, by port path or protocol name.
+    pub codecs: HashMap<String, Arc<CodecFactory>>,
+    /// Scripts of the emulated devices, by name.
+    #[cfg(feature = "emulator")]
+    pub emulators: Arc<Mutex<HashMap<String, crate::emulator::Script>>>,
+    /// FTDI interfaces claimed for bitbang or MPSSE mode, by serial port path.
+    #[cfg(feature = "ftdi")]
+    pub ftdi: Arc<Mutex<HashMap<String, crate::ftdi::FtdiDevice>>>,
+    /// Workers reading the native ports.
+    pub reactor: Arc<Reactor>,
+    /// Long-running operations of all ports, by id for `cancel_operation`.
+    pub operations: Arc<Operations>,
+    /// Ids the events of the open ports are named with, by path.
+    pub event_ids: Arc<Mutex<HashMap<String, String>>>,
+    /// Name the events of ports opened without an id `port-<n>` instead of by path.
+    pub opaque_event_ids: bool,
+    pub next_event_id: AtomicU64,
+    /// Set once the thread checking the ports after a system sleep runs.
+    pub power_watch: AtomicBool,
+    /// The ports declared for startup, in the order they were declared.
+    pub auto_opened: Arc<Mutex<Vec<AutoOpenedData>>>,
+    /// The last settings of each device, with `Builder::remember_settings`.
+    pub settings_store: Option<Arc<SettingsStore>>,
+    /// Connection profiles by name.
+    pub profiles: Arc<Store<Profile>>,
+    /// The ports to open at startup declared with the `Builder` and in `tauri.conf.json`.
+    pub declared_auto_open: Vec<AutoOpen>,
+    /// The ports to open at startup imported with `import_config`, by selector.
+    pub provisioned: Arc<Store<AutoOpen>>,
+    /// Callbacks of `Builder::on_traffic`.
+    pub observers: Arc<Vec<Arc<TrafficObserver>>>,
+    /// Factories of `Builder::middleware`, in the order they were registered.
+    pub middleware: Vec<Arc<MiddlewareFactory>>,
 }
 pub struct SerialportInfo {
     pub serialport: Box<dyn SerialPort>,
+    /// Descriptor of native Unix ports or handle of native Windows ports, for the settings
+    /// `serialport` does not offer.
+    pub native: Option<isize>,
+    /// Mark (`true`) or Space parity the port was opened with, `serialport` reports neither.
+    pub stick_parity: Option<bool>,
+    /// Kernel RS-485 mode the port was opened with.
+    pub rs485: Option<NativeRs485>,
     pub sender: Option<Sender<usize>>,
+    /// Present when the port is read in `Buffer` mode.
+    pub ring_buffer: Option<Arc<Mutex<RingBuffer>>>,
+    /// Present when the port is read in `Notify` mode, set once waiting data was reported.
+    pub data_ready: Option<Arc<AtomicBool>>,
+    /// Received chunks kept for `get_history`, shared with the read thread.
+    pub history: Arc<Mutex<History>>,
+    /// Received stream read by the named consumers.
+    pub stream: Arc<Mutex<Stream>>,
+    /// Whether TX and RX data is also sent as one monitor event stream.
+    pub monitor: Arc<AtomicBool>,
+    /// Whether written data is also sent as outgoing read event.
+    pub local_echo: bool,
+    /// Level of the wire-level hex dumps of this port, 0 when disabled.
+    pub log_level: Arc<AtomicU8>,
+    /// Milliseconds since the Unix epoch of the last received or written data.
+    pub last_activity: Arc<AtomicU64>,
+    /// Origin of the monotonic timestamps of the read events.
+    pub opened: Instant,
+    /// Sequence number of the next read event.
+    pub sequence: Arc<AtomicU64>,
+    /// Read events held back until the frontend acknowledges earlier ones.
+    pub backpressure: Arc<Mutex<Backpressure>>,
+    /// Whether minimizing the window pauses the read events.
+    pub watches_visibility: bool,
+    /// Set by the application with `set_metadata`, e.g. the device role or firmware version.
+    pub metadata: Value,
+    /// Stops the idle watchdog when dropped.
+    pub watchdog: Option<Sender<()>>,
+    /// Stops the heartbeat transmitter when dropped.
+    pub heartbeat: Option<Sender<()>>,
+    /// DMX512 output, its thread stops when dropped.
+    pub dmx: Option<Dmx>,
+    /// Stops the Modbus TCP gateway when dropped.
+    pub gateway: Option<Sender<()>>,
+    /// MDB bus of the port while its peripherals are polled.
+    pub mdb: Option<Mdb>,
+    /// Channels of the port while it is split, its reading thread stops when dropped.
+    pub mux: Option<Mux>,
+    /// Periodic requests, their answers are taken from the read thread.
+    pub poller: Arc<Mutex<Poller>>,
+    /// Wakes the poll scheduler after a change, stops it when dropped.
+    pub poll_wakeup: Option<Sender<()>>,
+    /// Patterns the read thread looks for in the received data.
+    pub watchers: Arc<Mutex<Vec<Watcher>>>,
+    /// Triggered capture armed or recording in the read thread.
+    pub capture: Arc<Mutex<Option<Capture>>>,
+    /// Written bytes the `RemoveEcho` transform of the read thread waits for.
+    pub echo: Option<Arc<Mutex<VecDeque<u8>>>>,
+    /// Codec of the running read, also used by `write_frame`.
+    pub codec: Option<Arc<Mutex<Box<dyn Codec>>>>,
+    /// Frame description the read thread parses the received data with.
+    pub schema: Arc<Mutex<Option<SchemaParser>>>,
+    /// AT command transactions, fed by the read thread once they are used.
+    pub at: Arc<AtChannel>,
+    /// Directions the port was opened for, enforced by the wrapper around `serialport`.
+    pub access: SharedAccess,
+    /// RS-485 direction control, applied by the same wrapper and its clones.
+    pub direction: SharedDirection,
+    /// Half-duplex turnaround, applied by the same wrapper.
+    pub half_duplex: SharedHalfDuplex,
+    /// pcapng capture the same wrapper records all traffic to.
+    pub pcap: SharedPcap,
+    /// Observers of `Builder::on_traffic` the same wrapper hands all traffic to.
+    pub tap: SharedTap,
+    /// Middleware of `Builder::middleware`, applied to writes by the same wrapper and to
+    /// received data by the read.
+    pub middleware: SharedChain,
+    /// Key and nonces while the link is encrypted, applied by the same wrapper.
+    pub link: SharedLink,
+    /// Compression of the framed payloads, applied by the same wrapper before the encryption.
+    pub compression: SharedCompression,
+    /// Station address the read thread delivers frames for.
+    pub address_filter: Arc<Mutex<Option<Filter>>>,
+    /// Break detection, error markers and error counts.
+    pub line_status: Arc<Mutex<LineStatus>>,
+    /// Acknowledgement a reliable write waits for, fed by the read thread while it runs.
+    pub expectation: SharedExpectation,
+    /// Timeout of the port while exchanges poll clones of it with a short one.
+    pub short_timeout: SharedTimeout,
+    /// Prioritized writes waiting for the queue writer.
+    pub tx_queue: Arc<Mutex<TxQueue>>,
+    /// Present while the queue writer runs, which ends once the queue is empty.
+    pub queue_writer: Option<Sender<()>>,
+}
+
+impl SerialportInfo {
+    pub fn new(serialport: Box<dyn SerialPort>) -> Self {
+        let access: SharedAccess = Arc::new(Mutex::new(Access::ReadWrite));
+        let direction: SharedDirection = Arc::new(Mutex::new(None));
+        let half_duplex: SharedHalfDuplex = Arc::new(Mutex::new(None));
+        let pcap: SharedPcap = Arc::new(Mutex::new(None));
+        let tap: SharedTap = Arc::new(Mutex::new(None));
+        let middleware: SharedChain = Arc::new(Mutex::new(None));
+        let link: SharedLink = Arc::new(Mutex::new(None));
+        let compression: SharedCompression = Arc::new(Mutex::new(None));
+        let serialport = Rs485Port::new(
+            serialport,
+            access.clone(),
+            direction.clone(),
+            half_duplex.clone(),
+            pcap.clone(),
+            tap.clone(),
+            middleware.clone(),
+            link.clone(),
+            compression.clone(),
+        );
+        SerialportInfo {
+            serialport: Box::new(serialport),
+            native: None,
+            stick_parity: None,
+            rs485: None,
+            sender: None,
+            ring_buffer: None,
+            data_ready: None,
+            history: Arc::new(Mutex::new(History::default())),
+            stream: Arc::new(Mutex::new(Stream::default())),
+            monitor: Arc::new(AtomicBool::new(false)),
+            local_echo: false,
+            log_level: Arc::new(AtomicU8::new(0)),
+            last_activity: Arc::new(AtomicU64::new(now_millis())),
+            opened: Instant::now(),
+            sequence: Arc::new(AtomicU64::new(0)),
+            backpressure: Arc::new(Mutex::new(Backpressure::default())),
+            watches_visibility: false,
+            metadata: Value::Null,
+            watchdog: None,
+            heartbeat: None,
+            dmx: None,
+            gateway: None,
+            mdb: None,
+            mux: None,
+            poller: Arc::new(Mutex::new(Poller::default())),
+            poll_wakeup: None,
+            watchers: Arc::new(Mutex::new(Vec::new())),
+            capture: Arc::new(Mutex::new(None)),
+            echo: None,
+            codec: None,
+            schema: Arc::new(Mutex::new(None)),
+            at: Arc::new(AtChannel::default()),
+            access,
+            direction,
+            half_duplex,
+            pcap,
+            tap,
+            middleware,
+            link,
+            compression,
+            address_filter: Arc::new(Mutex::new(None)),
+            line_status: Arc::new(Mutex::new(LineStatus::default())),
+            expectation: Arc::new(Mutex::new(None)),
+            short_timeout: SharedTimeout::default(),
+            tx_queue: Arc::new(Mutex::new(TxQueue::default())),
+            queue_writer: None,
+        }
+    }
+
+    pub fn access(&self) -> Access {
+        match self.access.lock() {
+            Ok(access) => *access,
+            Err(error) => *error.into_inner(),
+        }
+    }
+
+    /// `worker` The thread driving the port on its own, `None` when there is none. The DMX
+    /// output comes last, so a restart of it finds the others first.
+    pub fn worker(&self) -> Option<Worker> {
+        let polled = self
+            .poller
+            .lock()
+            .map_or(false, |poller| !poller.is_empty());
+        if self.sender.is_some() {
+            Some(Worker::Read)
+        } else if self.gateway.is_some() {
+            Some(Worker::Gateway)
+        } else if self.mdb.is_some() {
+            Some(Worker::Mdb)
+        } else if self.mux.is_some() {
+            Some(Worker::Mux)
+        } else if self.heartbeat.is_some() {
+            Some(Worker::Heartbeat)
+        } else if polled {
+            Some(Worker::Polls)
+        } else if self.dmx.is_some() {
+            Some(Worker::Dmx)
+        } else {
+            None
+        }
+    }
+}
+
+/// `Worker` A thread that reads or writes a port on its own.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Worker {
+    Read,
+    Gateway,
+    Mdb,
+    Mux,
+    Heartbeat,
+    Polls,
+    Dmx,
+}
+
+impl Worker {
+    pub fn name(self) -> &'static str {
+        match self {
+            Worker::Read => "read",
+            Worker::Gateway => "Modbus gateway",
+            Worker::Mdb => "MDB poller",
+            Worker::Mux => "channel split",
+            Worker::Heartbeat => "heartbeat",
+            Worker::Polls => "polls",
+            Worker::Dmx => "DMX output",
+        }
+    }
 }
 
 #[derive(Serialize, Clone)]
@@ -23,6 +319,403 @@ pub struct InvokeResult {
 
 #[derive(Serialize, Clone)]
 pub struct ReadData<'a> {
+    /// Counts the read events of the port, a gap means events were lost.
+    pub sequence: u64,
+    pub data: &'a [u8],
+    pub size: usize,
+    /// Bytes discarded so far by the event rate limit.
+    pub suppressed: u64,
+    /// Written data echoed by `set_local_echo`.
+    pub outgoing: bool,
+    /// Milliseconds since the Unix epoch when the data was received, or written.
+    pub timestamp: u64,
+    /// Microseconds since the port was opened, for intervals between events.
+    pub monotonic_us: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ManagedPort {
+    pub path: String,
+    /// Id the events of the port are named with.
+    pub event_id: String,
+    pub reading: bool,
+    /// What the application attached with `set_metadata`, `null` by default.
+    pub metadata: Value,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BufferOverflowData {
+    /// Read data dropped since the backpressure buffer filled up.
+    pub dropped: u64,
+    /// Bytes waiting for the frontend.
+    pub buffered_bytes: usize,
+    /// The buffer drained, `dropped` is the total of the overflow.
+    pub recovered: bool,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FetchData {
+    pub data: Vec<u8>,
+    pub size: usize,
+    /// Bytes still waiting in the buffer.
+    pub available: usize,
+    /// Bytes dropped because the buffer was full.
+    pub overwritten: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct HistoryFrame {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MonitorData<'a> {
+    /// `TX` for written data, `RX` for received data.
+    pub direction: &'a str,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
     pub data: &'a [u8],
     pub size: usize,
 }
+
+#[derive(Serialize, Clone)]
+pub struct ReadError {
+    /// The `std::io::ErrorKind` of the failure, e.g. `BrokenPipe`.
+    pub kind: String,
+    pub message: String,
+    /// Number of read errors since reading started.
+    pub count: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PollData {
+    /// Id the poll was registered with.
+    pub id: String,
+    pub data: Vec<u8>,
+    pub size: usize,
+    /// No complete answer arrived within the response timeout.
+    pub timed_out: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MatchData {
+    /// Id the watcher was registered with.
+    pub id: String,
+    /// The matched bytes.
+    pub data: Vec<u8>,
+    /// Received bytes right before and after the match.
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PacketData {
+    /// Values of the schema fields by name.
+    pub fields: Map<String, Value>,
+    pub raw: Vec<u8>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PacketError {
+    pub message: String,
+    pub raw: Vec<u8>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DecodedData {
+    pub value: Value,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DecodeError {
+    pub message: String,
+    /// The malformed line in `JsonLines` mode.
+    pub line: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FrameData {
+    pub data: Vec<u8>,
+    pub size: usize,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CaptureData {
+    /// `Started` when the start pattern arrived; `Stop`, `Limit` or `Cancelled` when the
+    /// capture ended.
+    pub reason: String,
+    /// The recorded bytes, empty when they were written to `file`.
+    pub data: Vec<u8>,
+    pub size: usize,
+    pub file: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct AtResponse {
+    /// Information lines, without the echo of the command.
+    pub lines: Vec<String>,
+    /// The final result code, e.g. `OK` or `+CMS ERROR: 500`.
+    pub result: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UrcData {
+    pub line: String,
+    /// The PDU following `+CMT` or `+CDS` in PDU mode.
+    pub pdu: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SmsMessage {
+    /// Storage index, `None` for messages delivered directly.
+    pub index: Option<u32>,
+    pub sender: String,
+    pub text: String,
+    /// Service centre time stamp, ISO 8601.
+    pub timestamp: String,
+    /// Set for one part of a concatenated message.
+    pub reference: Option<u16>,
+    pub part: Option<u8>,
+    pub parts: Option<u8>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SmsReport {
+    /// The reference `send_sms` returned for the message.
+    pub reference: u8,
+    pub recipient: String,
+    /// TP-Status, 0 when the message was delivered.
+    pub status: u8,
+    pub delivered: bool,
+    pub timestamp: String,
+    pub discharge_time: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MidiData {
+    pub message: MidiMessage,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FirmataData {
+    pub message: FirmataMessage,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UbxData {
+    pub message: UbxMessage,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct XBeeData {
+    pub frame: XBeeFrame,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct AckData {
+    /// Id the reliable write was started with.
+    pub id: String,
+    pub acknowledged: bool,
+    /// `Ack`, `Nak` or `Timeout` of the last attempt.
+    pub outcome: String,
+    pub attempts: u32,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ProgressData {
+    /// Id the operation was started with.
+    pub id: String,
+    /// Bytes transferred so far.
+    pub done: u64,
+    /// `None` while the size is not known, e.g. until a pattern arrives.
+    pub total: Option<u64>,
+    /// What the operation is doing, e.g. `Writing`.
+    pub phase: String,
+    /// Estimated milliseconds until it finishes.
+    pub eta_ms: Option<u64>,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct CancelledData {
+    /// Id the operation was started with.
+    pub id: String,
+    /// Bytes transferred before it stopped.
+    pub done: usize,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct QueueData {
+    /// Id the write was queued with.
+    pub id: String,
+    /// `Urgent`, `Normal` or `Bulk`.
+    pub priority: String,
+    pub size: usize,
+    /// Why the write failed, the rest of the entry is dropped.
+    pub error: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MdbData {
+    pub address: u8,
+    pub event: MdbEvent,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SocketData {
+    pub socket: u8,
+    pub data: Vec<u8>,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct LoraDownlink {
+    /// LoRaWAN application port.
+    pub port: u8,
+    pub data: Vec<u8>,
+    /// dBm and dB, when the module reports them.
+    pub rssi: Option<i16>,
+    pub snr: Option<i16>,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SocketClosed {
+    pub socket: u8,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DataReadyData {
+    /// Bytes waiting in the input buffer of the driver.
+    pub bytes: u32,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DisconnectData {
+    /// Why the port is considered gone, e.g. `End of file`.
+    pub reason: String,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct AutoOpenedData {
+    /// The port as declared, e.g. its path or `0403:6001 A702TB52`.
+    pub selector: String,
+    /// The port that was selected.
+    pub path: Option<String>,
+    pub event_id: Option<String>,
+    pub baud_rate: u32,
+    pub opened: bool,
+    /// Missing so far, opened once it appears.
+    pub waiting: bool,
+    pub error: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SavedOpen {
+    pub path: String,
+    pub event_id: String,
+    /// The key the settings are saved under, the serial number of the device.
+    pub device: String,
+    pub settings: SavedSettings,
+}
+
+#[derive(Serialize, Clone)]
+pub struct NamedProfile {
+    pub name: String,
+    pub profile: Profile,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ProfileOpen {
+    pub name: String,
+    /// The port the profile selected.
+    pub path: String,
+    pub event_id: String,
+    pub profile: Profile,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SuspendedData {
+    /// When the system was last seen awake.
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ResumedData {
+    pub slept_ms: u64,
+    /// The port still answered after the sleep.
+    pub alive: bool,
+    /// The port was dropped and opened again, a read must be started anew.
+    pub reopened: bool,
+    /// Why a dropped port could not be opened again.
+    pub error: Option<String>,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct BreakData {
+    /// Breaks received since detection was enabled.
+    pub count: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct OverrunData {
+    /// Overruns of the UART since the port was opened.
+    pub overrun: u64,
+    /// Overruns of the receive buffer of the driver since the port was opened.
+    pub buffer_overrun: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct IdleData<'a> {
+    /// Milliseconds since the last received or written data.
+    pub idle_ms: u64,
+    /// What the watchdog does about it: `Notify`, `Close` or `Reopen`.
+    pub action: &'a str,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct AdapterInfo {
+    pub vid: Option<String>,
+    pub pid: Option<String>,
+    /// Strings reported by the USB device descriptor.
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    /// Chip family, e.g. `FT232R` or `CP2102N`.
+    pub chip: Option<String>,
+    pub firmware_version: Option<String>,
+    /// Strings programmed into the FTDI EEPROM.
+    pub eeprom_manufacturer: Option<String>,
+    pub eeprom_product: Option<String>,
+    pub eeprom_serial_number: Option<String>,
+    /// Whether the FTDI EEPROM checksum matches its contents.
+    pub eeprom_checksum_valid: Option<bool>,
+}
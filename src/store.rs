@@ -0,0 +1,145 @@
+//! What the plugin keeps between sessions in small JSON files: the last settings each device was
+//! opened with, so they need not be entered again, the named connection profiles and the
+//! imported ports to open at startup. Devices are told apart by their USB serial number, which follows them from socket to socket, unlike
+//! the port path.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub const SETTINGS_FILE: &str = "serialport-settings.json";
+pub const PROFILES_FILE: &str = "serialport-profiles.json";
+pub const AUTO_OPEN_FILE: &str = "serialport-auto-open.json";
+
+/// `SavedSettings` The line settings a device was last opened with.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedSettings {
+    pub baud_rate: u32,
+    pub data_bits: usize,
+    pub flow_control: String,
+    pub parity: String,
+    pub stop_bits: usize,
+    pub timeout: u64,
+    /// The port the device was on.
+    pub path: String,
+    pub saved_at: u64,
+}
+
+/// `Store` Entries by key, mirrored to a JSON file at `path`, or kept in memory without one.
+pub struct Store<T> {
+    path: Option<PathBuf>,
+    entries: Mutex<HashMap<String, T>>,
+}
+
+/// `SettingsStore` The saved settings by device key.
+pub type SettingsStore = Store<SavedSettings>;
+
+impl<T> Default for Store<T> {
+    fn default() -> Self {
+        Store {
+            path: None,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+fn poisoned() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "Store lock poisoned")
+}
+
+impl<T: Serialize + DeserializeOwned + Clone> Store<T> {
+    /// `load` The store kept in `path`, empty when the file is missing or unreadable.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let entries = match path.as_ref().map(|path| (path, fs::read(path))) {
+            Some((path, Ok(data))) => serde_json::from_slice(&data).unwrap_or_else(|error| {
+                warn!("Ignoring invalid store {}: {}", path.display(), error);
+                HashMap::new()
+            }),
+            _ => HashMap::new(),
+        };
+        Store {
+            path,
+            entries: Mutex::new(entries),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<T> {
+        self.entries.lock().ok()?.get(key).cloned()
+    }
+
+    /// `list` All entries, sorted by key.
+    pub fn list(&self) -> Vec<(String, T)> {
+        let mut entries: Vec<(String, T)> = match self.entries.lock() {
+            Ok(entries) => entries
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// `save` Set the entry of `key` and write the file.
+    pub fn save(&self, key: String, value: T) -> io::Result<()> {
+        let mut entries = self.entries.lock().map_err(|_| poisoned())?;
+        entries.insert(key, value);
+        self.write(&entries)
+    }
+
+    /// `import` Add `entries`, in place of all others with `replace`, and write the file once.
+    pub fn import(&self, entries: Vec<(String, T)>, replace: bool) -> io::Result<usize> {
+        let mut stored = self.entries.lock().map_err(|_| poisoned())?;
+        if replace {
+            stored.clear();
+        }
+        let count = entries.len();
+        stored.extend(entries);
+        self.write(&stored).map(|_| count)
+    }
+
+    /// `remove` Drop the entry of `key`, whether there was one.
+    pub fn remove(&self, key: &str) -> io::Result<bool> {
+        let mut entries = self.entries.lock().map_err(|_| poisoned())?;
+        if entries.remove(key).is_none() {
+            return Ok(false);
+        }
+        self.write(&entries).map(|_| true)
+    }
+
+    /// `write` Write the file next to itself and rename it, so a crash leaves the previous
+    /// version.
+    fn write(&self, entries: &HashMap<String, T>) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let data = serde_json::to_vec_pretty(entries)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, data)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+/// `device_key` The key of the device behind the USB port at `path`, its serial number with the
+/// interface for the ports of a composite device. `None` for ports without a serial number.
+pub fn device_key(path: &str) -> Option<String> {
+    let ports = serialport::available_ports().ok()?;
+    ports.into_iter().find_map(|port| match port.port_type {
+        serialport::SerialPortType::UsbPort(info) if port.port_name == path => {
+            let serial_number = info.serial_number.filter(|serial| !serial.is_empty())?;
+            Some(match info.interface {
+                Some(interface) if interface > 0 => format!("{}:{}", serial_number, interface),
+                _ => serial_number,
+            })
+        }
+        _ => None,
+    })
+}
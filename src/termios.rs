@@ -0,0 +1,168 @@
+//! Terminal settings of native Unix ports that `serialport` does not offer, applied to the
+//! descriptor kept in `SerialportInfo::fd`.
+
+use std::io;
+use std::os::unix::io::RawFd;
+
+fn get(fd: RawFd) -> io::Result<libc::termios> {
+    let mut termios = std::mem::MaybeUninit::<libc::termios>::uninit();
+    if unsafe { libc::tcgetattr(fd, termios.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { termios.assume_init() })
+}
+
+fn set(fd: RawFd, termios: &libc::termios) -> io::Result<()> {
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// `mark_errors` Deliver bytes with a parity or framing error as `FF 00 <byte>` instead of
+/// `00`, a received `FF` then arrives as `FF FF`.
+pub fn mark_errors(fd: RawFd, enable: bool) -> io::Result<()> {
+    let mut termios = get(fd)?;
+    if enable {
+        termios.c_iflag |= libc::PARMRK | libc::INPCK;
+        termios.c_iflag &= !(libc::IGNPAR | libc::ISTRIP);
+    } else {
+        termios.c_iflag &= !libc::PARMRK;
+    }
+    set(fd, &termios)
+}
+
+/// `set_stick_parity` Mark (`true`) or Space parity, the parity bit is then constant; received
+/// parity is not checked.
+#[cfg(target_os = "linux")]
+pub fn set_stick_parity(fd: RawFd, mark: bool) -> io::Result<()> {
+    let mut termios = get(fd)?;
+    termios.c_cflag |= libc::PARENB | libc::CMSPAR;
+    if mark {
+        termios.c_cflag |= libc::PARODD;
+    } else {
+        termios.c_cflag &= !libc::PARODD;
+    }
+    termios.c_iflag &= !libc::INPCK;
+    set(fd, &termios)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_stick_parity(_fd: RawFd, _mark: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "this system has no CMSPAR termios flag",
+    ))
+}
+
+/// `accepts_stick_parity` Whether the driver keeps `CMSPAR` once set, the settings are restored.
+#[cfg(target_os = "linux")]
+pub fn accepts_stick_parity(fd: RawFd) -> io::Result<bool> {
+    let saved = get(fd)?;
+    let mut termios = saved;
+    termios.c_cflag |= libc::PARENB | libc::CMSPAR;
+    set(fd, &termios)?;
+    let accepted = get(fd)?.c_cflag & libc::CMSPAR != 0;
+    set(fd, &saved)?;
+    Ok(accepted)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn accepts_stick_parity(_fd: RawFd) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// `transmitter_empty` Whether the UART has sent its last bit, from the line status register.
+#[cfg(target_os = "linux")]
+pub fn transmitter_empty(fd: RawFd) -> io::Result<bool> {
+    const TIOCSER_TEMT: i32 = 1;
+    let mut status: i32 = 0;
+    if unsafe { libc::ioctl(fd, libc::TIOCSERGETLSR, &mut status) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(status & TIOCSER_TEMT != 0)
+}
+
+/// `struct serial_rs485` of `linux/serial.h`, the last words hold addresses in newer kernels.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct SerialRs485 {
+    flags: u32,
+    delay_rts_before_send: u32,
+    delay_rts_after_send: u32,
+    padding: [u32; 5],
+}
+
+#[cfg(target_os = "linux")]
+const SER_RS485_ENABLED: u32 = 1 << 0;
+#[cfg(target_os = "linux")]
+const SER_RS485_RTS_ON_SEND: u32 = 1 << 1;
+#[cfg(target_os = "linux")]
+const SER_RS485_RTS_AFTER_SEND: u32 = 1 << 2;
+#[cfg(target_os = "linux")]
+const SER_RS485_RX_DURING_TX: u32 = 1 << 4;
+#[cfg(target_os = "linux")]
+const SER_RS485_TERMINATE_BUS: u32 = 1 << 5;
+
+/// `set_rs485` Enable the RS-485 mode of the UART driver.
+#[cfg(target_os = "linux")]
+pub fn set_rs485(fd: RawFd, rs485: &crate::rs485::NativeRs485) -> io::Result<()> {
+    let flag = |enabled: bool, flag: u32| if enabled { flag } else { 0 };
+    let config = SerialRs485 {
+        flags: SER_RS485_ENABLED
+            | flag(rs485.rts_on_send.unwrap_or(true), SER_RS485_RTS_ON_SEND)
+            | flag(rs485.rts_after_send.unwrap_or(false), SER_RS485_RTS_AFTER_SEND)
+            | flag(rs485.rx_during_tx, SER_RS485_RX_DURING_TX)
+            | flag(rs485.terminate_bus, SER_RS485_TERMINATE_BUS),
+        delay_rts_before_send: rs485.delay_before_send_ms,
+        delay_rts_after_send: rs485.delay_after_send_ms,
+        ..SerialRs485::default()
+    };
+    if unsafe { libc::ioctl(fd, libc::TIOCSRS485, &config) } != 0 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(libc::ENOTTY) {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "the UART driver has no RS-485 mode",
+            ));
+        }
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// `struct serial_icounter_struct` of `linux/serial.h`.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct SerialIcounter {
+    cts: i32,
+    dsr: i32,
+    rng: i32,
+    dcd: i32,
+    rx: i32,
+    tx: i32,
+    frame: i32,
+    overrun: i32,
+    parity: i32,
+    brk: i32,
+    buf_overrun: i32,
+    reserved: [i32; 9],
+}
+
+/// `icount` The line errors the driver counted since it was loaded, `TIOCGICOUNT`.
+#[cfg(target_os = "linux")]
+pub fn icount(fd: RawFd) -> io::Result<crate::linestatus::Counters> {
+    let mut counter = SerialIcounter::default();
+    if unsafe { libc::ioctl(fd, libc::TIOCGICOUNT, &mut counter) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(crate::linestatus::Counters {
+        parity: counter.parity as u32 as u64,
+        framing: counter.frame as u32 as u64,
+        breaks: counter.brk as u32 as u64,
+        overrun: counter.overrun as u32 as u64,
+        buffer_overrun: counter.buf_overrun as u32 as u64,
+    })
+}
@@ -0,0 +1,61 @@
+//! Traffic of every port handed to host Rust code registered with `Builder::on_traffic`, for
+//! logging, auditing or analytics without the events of the frontend.
+
+use crate::reader::now_millis;
+use std::sync::{Arc, Mutex};
+
+/// `TrafficDirection` Whether a chunk was written or received.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrafficDirection {
+    Tx,
+    Rx,
+}
+
+impl TrafficDirection {
+    pub fn name(self) -> &'static str {
+        match self {
+            TrafficDirection::Tx => "TX",
+            TrafficDirection::Rx => "RX",
+        }
+    }
+}
+
+/// `Traffic` A chunk as it was written to or read from the driver.
+pub struct Traffic<'a> {
+    pub path: &'a str,
+    pub direction: TrafficDirection,
+    pub data: &'a [u8],
+    /// Milliseconds since the epoch.
+    pub timestamp: u64,
+}
+
+/// `TrafficObserver` A callback of `Builder::on_traffic`, called on the thread that moved the
+/// data, so it should return quickly.
+pub type TrafficObserver = dyn Fn(&Traffic) + Send + Sync;
+
+/// `Tap` The observers of one port.
+pub struct Tap {
+    path: String,
+    observers: Arc<Vec<Arc<TrafficObserver>>>,
+}
+
+/// `SharedTap` Set once the port is registered, shared by the port and its clones.
+pub type SharedTap = Arc<Mutex<Option<Tap>>>;
+
+impl Tap {
+    pub fn new(path: String, observers: Arc<Vec<Arc<TrafficObserver>>>) -> Self {
+        Tap { path, observers }
+    }
+
+    pub fn observe(&self, direction: TrafficDirection, data: &[u8]) {
+        let traffic = Traffic {
+            path: &self.path,
+            direction,
+            data,
+            timestamp: now_millis(),
+        };
+        for observer in self.observers.iter() {
+            observer(&traffic);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! File transfers between disk and port in Rust, so large payloads never cross the IPC
+//! boundary. Both are operations: cancellable by id and reporting their progress.
+
+use crate::codec::Codec;
+use crate::operation::Operation;
+use crate::state::ProgressData;
+use serialport::SerialPort;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+/// `Framing` How a sent file is cut into chunks.
+pub enum Framing {
+    /// Chunks of `chunk_size` bytes.
+    Raw,
+    /// One line per chunk, e.g. for G-code or Intel HEX.
+    Lines,
+    /// Chunks of `chunk_size` bytes, each encoded as frame by the codec.
+    Codec(Box<dyn Codec>),
+}
+
+/// `Transferred` How a transfer ended.
+pub struct Transferred {
+    /// Bytes of the file that were sent or received.
+    pub bytes: u64,
+    pub cancelled: bool,
+}
+
+/// `send` Write `file` chunk by chunk, waiting `delay` after each, until it ends or the
+/// operation is cancelled.
+pub fn send(
+    port: &mut dyn SerialPort,
+    file: File,
+    operation: &mut Operation,
+    chunk_size: usize,
+    delay: Duration,
+    mut framing: Framing,
+    on_progress: &mut dyn FnMut(ProgressData),
+) -> Result<Transferred, String> {
+    let total = file.metadata().map(|metadata| metadata.len()).ok();
+    let mut reader = BufReader::new(file);
+    let mut chunk = Vec::with_capacity(chunk_size);
+    let mut bytes = 0u64;
+    loop {
+        if operation.is_cancelled() {
+            return Ok(Transferred {
+                bytes,
+                cancelled: true,
+            });
+        }
+        chunk.clear();
+        let size = match framing {
+            Framing::Lines => reader.read_until(b'\n', &mut chunk),
+            _ => (&mut reader)
+                .take(chunk_size as u64)
+                .read_to_end(&mut chunk),
+        }
+        .map_err(|error| format!("Failed to read file: {}", error))?;
+        if size == 0 {
+            break;
+        }
+        let result = match &mut framing {
+            Framing::Codec(codec) => port.write_all(&codec.encode(&chunk)),
+            _ => port.write_all(&chunk),
+        };
+        result
+            .and_then(|_| port.flush())
+            .map_err(|error| format!("Failed to write after {} bytes: {}", bytes, error))?;
+        bytes += size as u64;
+        if let Some(progress) = operation.progress(bytes, total, "Sending") {
+            on_progress(progress);
+        }
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+    Ok(Transferred {
+        bytes,
+        cancelled: false,
+    })
+}
+
+/// `Until` When a reception ends, whichever comes first.
+pub struct Until {
+    /// Received bytes, more are discarded.
+    pub bytes: Option<u64>,
+    /// A sequence that ends the file, written with it.
+    pub pattern: Option<Vec<u8>>,
+    /// Silence on the line, counted from the start until the first byte arrives.
+    pub idle: Option<Duration>,
+}
+
+/// `receive` Write the received stream to `file` until one of the `until` conditions is met or
+/// the operation is cancelled.
+pub fn receive(
+    port: &mut dyn SerialPort,
+    file: File,
+    operation: &mut Operation,
+    until: &Until,
+    on_progress: &mut dyn FnMut(ProgressData),
+) -> Result<Transferred, String> {
+    let mut writer = BufWriter::new(file);
+    let pattern = until
+        .pattern
+        .as_deref()
+        .filter(|pattern| !pattern.is_empty());
+    // the end of what was received, a pattern may span two reads
+    let mut tail: Vec<u8> = Vec::new();
+    let mut buffer = [0u8; 4096];
+    let mut bytes = 0u64;
+    let mut last_byte = Instant::now();
+    let mut cancelled = false;
+    loop {
+        if operation.is_cancelled() {
+            cancelled = true;
+            break;
+        }
+        if until.idle.map_or(false, |idle| last_byte.elapsed() >= idle) {
+            break;
+        }
+        let size = match port.read(&mut buffer) {
+            Ok(0) => continue,
+            Ok(size) => size,
+            Err(error) if error.kind() == ErrorKind::TimedOut => continue,
+            Err(error) => return Err(format!("Failed to read after {} bytes: {}", bytes, error)),
+        };
+        last_byte = Instant::now();
+        let mut data = &buffer[..size];
+        let mut done = false;
+        if let Some(limit) = until.bytes {
+            if bytes + data.len() as u64 >= limit {
+                data = &data[..(limit - bytes) as usize];
+                done = true;
+            }
+        }
+        if let Some(pattern) = pattern {
+            let offset = tail.len();
+            tail.extend_from_slice(data);
+            if let Some(start) = tail
+                .windows(pattern.len())
+                .position(|window| window == pattern)
+            {
+                data = &data[..start + pattern.len() - offset];
+                done = true;
+            }
+            let keep = tail.len().min(pattern.len() - 1);
+            tail.drain(..tail.len() - keep);
+        }
+        writer
+            .write_all(data)
+            .map_err(|error| format!("Failed to write file: {}", error))?;
+        bytes += data.len() as u64;
+        if let Some(progress) = operation.progress(bytes, until.bytes, "Receiving") {
+            on_progress(progress);
+        }
+        if done {
+            break;
+        }
+    }
+    writer
+        .flush()
+        .map_err(|error| format!("Failed to write file: {}", error))?;
+    Ok(Transferred { bytes, cancelled })
+}
@@ -0,0 +1,146 @@
+//! Transforms applied to received data in the read thread before it is emitted.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Written bytes remembered for `RemoveEcho`.
+const MAX_ECHO: usize = 64 * 1024;
+/// An unterminated `SplitControl` segment is emitted once it grows this large.
+const MAX_SEGMENT: usize = 64 * 1024;
+
+/// `Transform` One step of the receive pipeline.
+pub enum Transform {
+    /// Drop every `\r`.
+    StripCr,
+    /// Drop every NUL byte.
+    TrimNulls,
+    /// Drop received bytes that repeat what was last written.
+    RemoveEcho,
+    /// Replace bytes with the configured byte map.
+    Map,
+    /// Emit one event per segment between ASCII control characters.
+    SplitControl,
+}
+
+/// `Pipeline` The transforms of one read thread with the state they keep between reads.
+pub struct Pipeline {
+    transforms: Vec<Transform>,
+    map: [u8; 256],
+    /// Written bytes not yet seen echoed, shared with the write commands.
+    echo: Option<Arc<Mutex<VecDeque<u8>>>>,
+    segment: Vec<u8>,
+}
+
+impl Pipeline {
+    /// `new` Parse the transform names, `byte_map` holds the `[from, to]` pairs used by `Map`.
+    pub fn new(transforms: Vec<String>, byte_map: Vec<[u8; 2]>) -> Result<Self, String> {
+        let transforms = transforms
+            .iter()
+            .map(|name| match name.as_str() {
+                "StripCr" => Ok(Transform::StripCr),
+                "TrimNulls" => Ok(Transform::TrimNulls),
+                "RemoveEcho" => Ok(Transform::RemoveEcho),
+                "Map" => Ok(Transform::Map),
+                "SplitControl" => Ok(Transform::SplitControl),
+                _ => Err(format!("Unknown transform {}", name)),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut map = [0u8; 256];
+        for (index, to) in map.iter_mut().enumerate() {
+            *to = index as u8;
+        }
+        for [from, to] in byte_map {
+            map[from as usize] = to;
+        }
+        let echo = transforms
+            .iter()
+            .any(|transform| matches!(transform, Transform::RemoveEcho))
+            .then(|| Arc::new(Mutex::new(VecDeque::new())));
+        Ok(Pipeline {
+            transforms,
+            map,
+            echo,
+            segment: Vec::new(),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// `echo` Where the write commands record written bytes, if `RemoveEcho` is used.
+    pub fn echo(&self) -> Option<Arc<Mutex<VecDeque<u8>>>> {
+        self.echo.clone()
+    }
+
+    /// `process` Run received bytes through the pipeline, returns the chunks to emit.
+    pub fn process(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut data = data.to_vec();
+        let mut split = false;
+        for transform in &self.transforms {
+            match transform {
+                Transform::StripCr => data.retain(|byte| *byte != b'\r'),
+                Transform::TrimNulls => data.retain(|byte| *byte != 0),
+                Transform::RemoveEcho => {
+                    if let Some(Ok(mut echo)) = self.echo.as_ref().map(|echo| echo.lock()) {
+                        data.retain(|byte| match echo.front() {
+                            Some(expected) if expected == byte => {
+                                echo.pop_front();
+                                false
+                            }
+                            Some(_) => {
+                                // the device did not echo, stop waiting for it
+                                echo.clear();
+                                true
+                            }
+                            None => true,
+                        });
+                    }
+                }
+                Transform::Map => data
+                    .iter_mut()
+                    .for_each(|byte| *byte = self.map[*byte as usize]),
+                Transform::SplitControl => split = true,
+            }
+        }
+        if !split {
+            return if data.is_empty() {
+                Vec::new()
+            } else {
+                vec![data]
+            };
+        }
+        let mut chunks = Vec::new();
+        for byte in data {
+            if byte.is_ascii_control() {
+                if !self.segment.is_empty() {
+                    chunks.push(std::mem::take(&mut self.segment));
+                }
+            } else {
+                self.segment.push(byte);
+                if self.segment.len() >= MAX_SEGMENT {
+                    chunks.push(std::mem::take(&mut self.segment));
+                }
+            }
+        }
+        chunks
+    }
+
+    /// `flush` The unterminated segment, emitted when reading stops.
+    pub fn flush(&mut self) -> Option<Vec<u8>> {
+        if self.segment.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.segment))
+        }
+    }
+}
+
+/// `record_echo` Remember written bytes so `RemoveEcho` can drop them when they come back.
+pub fn record_echo(echo: &Option<Arc<Mutex<VecDeque<u8>>>>, data: &[u8]) {
+    if let Some(Ok(mut echo)) = echo.as_ref().map(|echo| echo.lock()) {
+        echo.extend(data.iter().copied());
+        let excess = echo.len().saturating_sub(MAX_ECHO);
+        echo.drain(..excess);
+    }
+}
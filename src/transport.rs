@@ -0,0 +1,257 @@
+//! Adapter presenting non-UART links (Bluetooth, BLE, ...) through the `serialport::SerialPort`
+//! trait, so the commands and the read thread can treat every port the same way.
+
+use serialport::{ClearBuffer, DataBits, FlowControl, Parity, SerialPort, StopBits};
+use std::io;
+use std::time::Duration;
+
+/// `LineSettings` The UART settings requested for a port.
+#[derive(Clone, Copy)]
+pub struct LineSettings {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub flow_control: FlowControl,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub timeout: Duration,
+}
+
+impl LineSettings {
+    pub fn new(baud_rate: u32, timeout: Duration) -> Self {
+        LineSettings {
+            baud_rate,
+            data_bits: DataBits::Eight,
+            flow_control: FlowControl::None,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            timeout,
+        }
+    }
+}
+
+pub fn unsupported(kind: &str, what: &str) -> serialport::Error {
+    serialport::Error::new(
+        serialport::ErrorKind::Unknown,
+        format!("{} is not supported on {} ports", what, kind),
+    )
+}
+
+/// `Transport` The operations a link has to provide, everything a link cannot do falls back
+/// to an "unsupported" error.
+pub trait Transport: Send {
+    /// Name used in error messages, e.g. `Bluetooth SPP`.
+    fn kind(&self) -> &'static str;
+
+    /// Read available data, waiting at most `timeout`. Returns `TimedOut` when nothing arrived.
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize>;
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn Transport>>;
+
+    /// Apply new line settings, links without a physical UART ignore them.
+    fn configure(&mut self, _settings: &LineSettings) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        Ok(0)
+    }
+
+    fn clear(&self, _buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, _level: bool) -> serialport::Result<()> {
+        Err(unsupported(self.kind(), "RTS"))
+    }
+
+    fn write_data_terminal_ready(&mut self, _level: bool) -> serialport::Result<()> {
+        Err(unsupported(self.kind(), "DTR"))
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        Err(unsupported(self.kind(), "CTS"))
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        Err(unsupported(self.kind(), "DSR"))
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        Err(unsupported(self.kind(), "RI"))
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        Err(unsupported(self.kind(), "CD"))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        Err(unsupported(self.kind(), "Break"))
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        Err(unsupported(self.kind(), "Break"))
+    }
+}
+
+/// `TransportPort` A `SerialPort` backed by a `Transport`.
+pub struct TransportPort {
+    name: String,
+    transport: Box<dyn Transport>,
+    settings: LineSettings,
+}
+
+impl TransportPort {
+    pub fn open(
+        name: &str,
+        mut transport: Box<dyn Transport>,
+        settings: LineSettings,
+    ) -> serialport::Result<Box<dyn SerialPort>> {
+        transport.configure(&settings)?;
+        Ok(Box::new(TransportPort {
+            name: name.to_string(),
+            transport,
+            settings,
+        }))
+    }
+
+    fn update<F: FnOnce(&mut LineSettings)>(&mut self, f: F) -> serialport::Result<()> {
+        let mut settings = self.settings;
+        f(&mut settings);
+        self.transport.configure(&settings)?;
+        self.settings = settings;
+        Ok(())
+    }
+}
+
+impl io::Read for TransportPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.transport.read(buf, self.settings.timeout)
+    }
+}
+
+impl io::Write for TransportPort {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.transport.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.transport.flush()
+    }
+}
+
+impl SerialPort for TransportPort {
+    fn name(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+
+    fn baud_rate(&self) -> serialport::Result<u32> {
+        Ok(self.settings.baud_rate)
+    }
+
+    fn data_bits(&self) -> serialport::Result<DataBits> {
+        Ok(self.settings.data_bits)
+    }
+
+    fn flow_control(&self) -> serialport::Result<FlowControl> {
+        Ok(self.settings.flow_control)
+    }
+
+    fn parity(&self) -> serialport::Result<Parity> {
+        Ok(self.settings.parity)
+    }
+
+    fn stop_bits(&self) -> serialport::Result<StopBits> {
+        Ok(self.settings.stop_bits)
+    }
+
+    fn timeout(&self) -> Duration {
+        self.settings.timeout
+    }
+
+    fn set_baud_rate(&mut self, baud_rate: u32) -> serialport::Result<()> {
+        self.update(|settings| settings.baud_rate = baud_rate)
+    }
+
+    fn set_data_bits(&mut self, data_bits: DataBits) -> serialport::Result<()> {
+        self.update(|settings| settings.data_bits = data_bits)
+    }
+
+    fn set_flow_control(&mut self, flow_control: FlowControl) -> serialport::Result<()> {
+        self.update(|settings| settings.flow_control = flow_control)
+    }
+
+    fn set_parity(&mut self, parity: Parity) -> serialport::Result<()> {
+        self.update(|settings| settings.parity = parity)
+    }
+
+    fn set_stop_bits(&mut self, stop_bits: StopBits) -> serialport::Result<()> {
+        self.update(|settings| settings.stop_bits = stop_bits)
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) -> serialport::Result<()> {
+        self.settings.timeout = timeout;
+        Ok(())
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.transport.write_request_to_send(level)
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.transport.write_data_terminal_ready(level)
+    }
+
+    fn read_clear_to_send(&mut self) -> serialport::Result<bool> {
+        self.transport.read_clear_to_send()
+    }
+
+    fn read_data_set_ready(&mut self) -> serialport::Result<bool> {
+        self.transport.read_data_set_ready()
+    }
+
+    fn read_ring_indicator(&mut self) -> serialport::Result<bool> {
+        self.transport.read_ring_indicator()
+    }
+
+    fn read_carrier_detect(&mut self) -> serialport::Result<bool> {
+        self.transport.read_carrier_detect()
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        self.transport.bytes_to_read()
+    }
+
+    fn bytes_to_write(&self) -> serialport::Result<u32> {
+        self.transport.bytes_to_write()
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        self.transport.clear(buffer_to_clear)
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn SerialPort>> {
+        Ok(Box::new(TransportPort {
+            name: self.name.clone(),
+            transport: self.transport.try_clone()?,
+            settings: self.settings,
+        }))
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        self.transport.set_break()
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.transport.clear_break()
+    }
+}
@@ -0,0 +1,544 @@
+//! The u-blox UBX protocol of GNSS receivers: the sync chars `B5 62`, class, id, the payload
+//! length as u16 little endian, the payload and the 8-bit Fletcher checksum of class to payload.
+//! Received frames are parsed into typed messages where the plugin knows them, configuration
+//! messages are encoded from their fields.
+
+use serde::{Deserialize, Serialize};
+use serialport::{ClearBuffer, SerialPort};
+use std::io;
+use std::time::{Duration, Instant};
+
+const SYNC: [u8; 2] = [0xb5, 0x62];
+const HEADER_SIZE: usize = 6;
+const CHECKSUM_SIZE: usize = 2;
+/// Longest payload accepted, longer lengths are taken for noise.
+const MAX_PAYLOAD: usize = 8 * 1024;
+
+const CLASS_NAV: u8 = 0x01;
+const CLASS_ACK: u8 = 0x05;
+const CLASS_CFG: u8 = 0x06;
+const NAV_PVT: u8 = 0x07;
+const ACK_NAK: u8 = 0x00;
+const ACK_ACK: u8 = 0x01;
+const NAV_PVT_SIZE: usize = 92;
+
+/// `UbxFrame` A message by class and id, its payload as sent.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct UbxFrame {
+    pub class: u8,
+    pub id: u8,
+    pub payload: Vec<u8>,
+}
+
+/// `UbxMessage` A received message, `Frame` for those without a typed form.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum UbxMessage {
+    /// NAV-PVT, the navigation solution. Positions in degrees and meters, speeds in m/s.
+    NavPvt {
+        /// GPS time of week of the solution, ms.
+        itow: u32,
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+        nano: i32,
+        /// Date and time confirmed valid.
+        valid_time: bool,
+        /// 0 no fix, 2 2D, 3 3D, 4 GNSS and dead reckoning, 5 time only.
+        fix_type: u8,
+        /// Fix within the accuracy masks.
+        fix_ok: bool,
+        satellites: u8,
+        longitude: f64,
+        latitude: f64,
+        height: f64,
+        height_msl: f64,
+        horizontal_accuracy: f64,
+        vertical_accuracy: f64,
+        velocity_north: f64,
+        velocity_east: f64,
+        velocity_down: f64,
+        ground_speed: f64,
+        /// Heading of motion, degrees.
+        heading: f64,
+        speed_accuracy: f64,
+        pdop: f64,
+    },
+    /// ACK-ACK of the configuration message `class`/`id`.
+    Ack {
+        class: u8,
+        id: u8,
+    },
+    /// ACK-NAK, the receiver rejected the configuration message.
+    Nak {
+        class: u8,
+        id: u8,
+    },
+    Frame(UbxFrame),
+}
+
+/// `UbxConfig` A CFG message, answered by an ACK-ACK or ACK-NAK.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type")]
+pub enum UbxConfig {
+    /// CFG-MSG, how often the port outputs a message: every `rate` navigation solutions, 0 off.
+    Msg { class: u8, id: u8, rate: u8 },
+    /// CFG-RATE, the measurement period in ms, the measurements per navigation solution and the
+    /// time reference (0 UTC, 1 GPS).
+    Rate {
+        measurement_ms: u16,
+        navigation_cycles: Option<u16>,
+        time_reference: Option<u16>,
+    },
+    /// CFG-PRT of a UART port (1 or 2): 8N1 at `baud_rate` with the protocol masks (bit 0 UBX,
+    /// 1 NMEA, 5 RTCM3). The receiver switches before it answers, so the ACK may be lost.
+    Prt {
+        port: u8,
+        baud_rate: u32,
+        in_protocols: u16,
+        out_protocols: u16,
+    },
+    /// CFG-CFG, save the current configuration to non-volatile memory, or clear and load it.
+    Save {
+        clear_mask: Option<u32>,
+        save_mask: Option<u32>,
+        load_mask: Option<u32>,
+    },
+    /// CFG-VALSET of generation 9 receivers: key ids and values, the value size follows from
+    /// the key. `layers` bit 0 RAM, 1 BBR, 2 flash, RAM by default.
+    Valset {
+        layers: Option<u8>,
+        items: Vec<(u32, u64)>,
+    },
+}
+
+/// `checksum` The 8-bit Fletcher checksum.
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_SIZE] {
+    let (mut a, mut b) = (0u8, 0u8);
+    for byte in data {
+        a = a.wrapping_add(*byte);
+        b = b.wrapping_add(a);
+    }
+    [a, b]
+}
+
+impl UbxFrame {
+    /// `encode` The frame with sync chars, length and checksum.
+    pub fn encode(&self) -> Result<Vec<u8>, String> {
+        if self.payload.len() > u16::MAX as usize {
+            return Err(format!(
+                "UBX payload of {} bytes is too long",
+                self.payload.len()
+            ));
+        }
+        let mut frame = Vec::with_capacity(HEADER_SIZE + self.payload.len() + CHECKSUM_SIZE);
+        frame.extend_from_slice(&SYNC);
+        frame.push(self.class);
+        frame.push(self.id);
+        frame.extend_from_slice(&(self.payload.len() as u16).to_le_bytes());
+        frame.extend_from_slice(&self.payload);
+        let checksum = checksum(&frame[SYNC.len()..]);
+        frame.extend_from_slice(&checksum);
+        Ok(frame)
+    }
+
+    /// `message` The typed form of the frame, if the plugin knows one.
+    pub fn message(self) -> UbxMessage {
+        match (self.class, self.id, self.payload.len()) {
+            (CLASS_NAV, NAV_PVT, NAV_PVT_SIZE) => nav_pvt(&self.payload),
+            (CLASS_ACK, ACK_ACK, 2) => UbxMessage::Ack {
+                class: self.payload[0],
+                id: self.payload[1],
+            },
+            (CLASS_ACK, ACK_NAK, 2) => UbxMessage::Nak {
+                class: self.payload[0],
+                id: self.payload[1],
+            },
+            _ => UbxMessage::Frame(self),
+        }
+    }
+}
+
+/// `value_size` Bytes of a CFG-VALSET value, from the size bits of its key.
+fn value_size(key: u32) -> Result<usize, String> {
+    match key >> 28 & 0x07 {
+        1 | 2 => Ok(1),
+        3 => Ok(2),
+        4 => Ok(4),
+        5 => Ok(8),
+        _ => Err(format!("Key 0x{:08x} has no valid size", key)),
+    }
+}
+
+impl UbxConfig {
+    /// `frame` The CFG message.
+    pub fn frame(&self) -> Result<UbxFrame, String> {
+        let (id, payload) = match self {
+            UbxConfig::Msg { class, id, rate } => (0x01, vec![*class, *id, *rate]),
+            UbxConfig::Rate {
+                measurement_ms,
+                navigation_cycles,
+                time_reference,
+            } => {
+                let mut payload = measurement_ms.to_le_bytes().to_vec();
+                payload.extend_from_slice(&navigation_cycles.unwrap_or(1).to_le_bytes());
+                payload.extend_from_slice(&time_reference.unwrap_or(1).to_le_bytes());
+                (0x08, payload)
+            }
+            UbxConfig::Prt {
+                port,
+                baud_rate,
+                in_protocols,
+                out_protocols,
+            } => {
+                let mut payload = vec![*port, 0, 0, 0];
+                // 8 data bits, no parity, 1 stop bit
+                payload.extend_from_slice(&0x08d0u32.to_le_bytes());
+                payload.extend_from_slice(&baud_rate.to_le_bytes());
+                payload.extend_from_slice(&in_protocols.to_le_bytes());
+                payload.extend_from_slice(&out_protocols.to_le_bytes());
+                payload.extend_from_slice(&[0; 4]);
+                (0x00, payload)
+            }
+            UbxConfig::Save {
+                clear_mask,
+                save_mask,
+                load_mask,
+            } => {
+                let mut payload = clear_mask.unwrap_or(0).to_le_bytes().to_vec();
+                payload.extend_from_slice(&save_mask.unwrap_or(0xffff).to_le_bytes());
+                payload.extend_from_slice(&load_mask.unwrap_or(0).to_le_bytes());
+                (0x09, payload)
+            }
+            UbxConfig::Valset { layers, items } => {
+                let mut payload = vec![0, layers.unwrap_or(1), 0, 0];
+                for (key, value) in items {
+                    payload.extend_from_slice(&key.to_le_bytes());
+                    payload.extend_from_slice(&value.to_le_bytes()[..value_size(*key)?]);
+                }
+                (0x8a, payload)
+            }
+        };
+        Ok(UbxFrame {
+            class: CLASS_CFG,
+            id,
+            payload,
+        })
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn i32_at(data: &[u8], offset: usize) -> i32 {
+    u32_at(data, offset) as i32
+}
+
+/// `nav_pvt` The typed NAV-PVT, millimeters and 1e-7 degrees converted.
+fn nav_pvt(p: &[u8]) -> UbxMessage {
+    let mm = |offset: usize| i32_at(p, offset) as f64 / 1000.0;
+    let accuracy = |offset: usize| u32_at(p, offset) as f64 / 1000.0;
+    UbxMessage::NavPvt {
+        itow: u32_at(p, 0),
+        year: u16_at(p, 4),
+        month: p[6],
+        day: p[7],
+        hour: p[8],
+        minute: p[9],
+        second: p[10],
+        nano: i32_at(p, 16),
+        valid_time: p[11] & 0x03 == 0x03,
+        fix_type: p[20],
+        fix_ok: p[21] & 0x01 != 0,
+        satellites: p[23],
+        longitude: i32_at(p, 24) as f64 * 1e-7,
+        latitude: i32_at(p, 28) as f64 * 1e-7,
+        height: mm(32),
+        height_msl: mm(36),
+        horizontal_accuracy: accuracy(40),
+        vertical_accuracy: accuracy(44),
+        velocity_north: mm(48),
+        velocity_east: mm(52),
+        velocity_down: mm(56),
+        ground_speed: mm(60),
+        heading: i32_at(p, 64) as f64 * 1e-5,
+        speed_accuracy: accuracy(68),
+        pdop: u16_at(p, 76) as f64 * 0.01,
+    }
+}
+
+/// `UbxParser` Splits the received stream into UBX frames. NMEA sentences and other bytes
+/// between them are skipped.
+#[derive(Default)]
+pub struct UbxParser {
+    raw: Vec<u8>,
+}
+
+impl UbxParser {
+    /// `feed` Collect received bytes, returns the frames they complete.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<UbxFrame> {
+        self.raw.extend_from_slice(data);
+        let mut frames = Vec::new();
+        let mut start = 0;
+        while let Some(size) = self.next_frame(start, &mut frames) {
+            start += size;
+        }
+        self.raw.drain(..start);
+        frames
+    }
+
+    /// `next_frame` Parse the frame at or after `start`, returns the bytes consumed or `None`
+    /// until more is received.
+    fn next_frame(&mut self, start: usize, frames: &mut Vec<UbxFrame>) -> Option<usize> {
+        let raw = &self.raw[start..];
+        let found = match raw.windows(2).position(|window| window == SYNC) {
+            Some(found) => found,
+            // a sync may begin with the last byte
+            None => {
+                let skipped = raw.len().saturating_sub(1);
+                return if skipped > 0 { Some(skipped) } else { None };
+            }
+        };
+        if found > 0 {
+            return Some(found);
+        }
+        if raw.len() < HEADER_SIZE {
+            return None;
+        }
+        let length = u16_at(raw, 4) as usize;
+        if length > MAX_PAYLOAD {
+            return Some(1);
+        }
+        let size = HEADER_SIZE + length + CHECKSUM_SIZE;
+        if raw.len() < size {
+            return None;
+        }
+        if checksum(&raw[SYNC.len()..size - CHECKSUM_SIZE])[..] != raw[size - CHECKSUM_SIZE..size] {
+            debug!("UBX frame with a wrong checksum skipped");
+            return Some(1);
+        }
+        frames.push(UbxFrame {
+            class: raw[2],
+            id: raw[3],
+            payload: raw[HEADER_SIZE..size - CHECKSUM_SIZE].to_vec(),
+        });
+        Some(size)
+    }
+}
+
+/// `configure` Send a CFG message and wait for the receiver to acknowledge it. Other messages
+/// received meanwhile are dropped.
+pub fn configure(
+    port: &mut dyn SerialPort,
+    config: &UbxConfig,
+    timeout: Duration,
+) -> Result<(), String> {
+    let frame = config.frame()?;
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    port.write_all(&frame.encode()?)
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write: {}", error))?;
+    let deadline = Instant::now() + timeout;
+    let mut parser = UbxParser::default();
+    let mut buf = [0u8; 1024];
+    while Instant::now() < deadline {
+        let size = match port.read(&mut buf) {
+            Ok(size) => size,
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => continue,
+            Err(error) => return Err(format!("Failed to read: {}", error)),
+        };
+        for received in parser.feed(&buf[..size]) {
+            match received.message() {
+                UbxMessage::Ack { class, id } if (class, id) == (frame.class, frame.id) => {
+                    return Ok(())
+                }
+                UbxMessage::Nak { class, id } if (class, id) == (frame.class, frame.id) => {
+                    return Err(format!("The receiver rejected CFG message 0x{:02x}", id))
+                }
+                _ => {}
+            }
+        }
+    }
+    Err(format!(
+        "No acknowledgment of CFG message 0x{:02x}",
+        frame.id
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(class: u8, id: u8, payload: &[u8]) -> UbxFrame {
+        UbxFrame {
+            class,
+            id,
+            payload: payload.to_vec(),
+        }
+    }
+
+    #[test]
+    fn known_frames() {
+        // MON-VER poll
+        assert_eq!(
+            frame(0x0a, 0x04, &[]).encode().unwrap(),
+            vec![0xb5, 0x62, 0x0a, 0x04, 0x00, 0x00, 0x0e, 0x34]
+        );
+        // CFG-RATE of 200 ms on GPS time
+        let rate = UbxConfig::Rate {
+            measurement_ms: 200,
+            navigation_cycles: None,
+            time_reference: None,
+        };
+        assert_eq!(
+            rate.frame().unwrap().encode().unwrap(),
+            vec![
+                0xb5, 0x62, 0x06, 0x08, 0x06, 0x00, 0xc8, 0x00, 0x01, 0x00, 0x01, 0x00, 0xde, 0x6a
+            ]
+        );
+        // its ACK-ACK
+        assert_eq!(
+            frame(CLASS_ACK, ACK_ACK, &[0x06, 0x08]).encode().unwrap(),
+            vec![0xb5, 0x62, 0x05, 0x01, 0x02, 0x00, 0x06, 0x08, 0x16, 0x3f]
+        );
+        assert_eq!(checksum(&[0xff; 300]), [0xd4, 0xa2]);
+    }
+
+    #[test]
+    fn config_payloads() {
+        let prt = UbxConfig::Prt {
+            port: 1,
+            baud_rate: 115_200,
+            in_protocols: 0x03,
+            out_protocols: 0x01,
+        };
+        let prt = prt.frame().unwrap();
+        assert_eq!((prt.class, prt.id), (CLASS_CFG, 0x00));
+        assert_eq!(
+            prt.payload,
+            vec![1, 0, 0, 0, 0xd0, 0x08, 0, 0, 0x00, 0xc2, 0x01, 0x00, 3, 0, 1, 0, 0, 0, 0, 0]
+        );
+        let valset = UbxConfig::Valset {
+            layers: None,
+            items: vec![
+                (0x1011_0025, 1),
+                (0x2091_0007, 5),
+                (0x3021_0001, 0x00c8),
+                (0x4052_0001, 115_200),
+            ],
+        };
+        let valset = valset.frame().unwrap();
+        assert_eq!(valset.id, 0x8a);
+        assert_eq!(valset.payload.len(), 4 + 5 + 5 + 6 + 8);
+        assert_eq!(
+            &valset.payload[..9],
+            &[0, 1, 0, 0, 0x25, 0x00, 0x11, 0x10, 1]
+        );
+        assert_eq!(&valset.payload[24..], &[0x00, 0xc2, 0x01, 0x00]);
+        let invalid = UbxConfig::Valset {
+            layers: Some(4),
+            items: vec![(0x0011_0025, 1)],
+        };
+        assert!(invalid.frame().is_err());
+    }
+
+    #[test]
+    fn parser_skips_noise_and_bad_checksums() {
+        let ack = frame(CLASS_ACK, ACK_ACK, &[0x06, 0x08]).encode().unwrap();
+        let nak = frame(CLASS_ACK, ACK_NAK, &[0x06, 0x00]).encode().unwrap();
+        let mut damaged = ack.clone();
+        damaged[6] ^= 0x01;
+        let mut stream = b"$GPGGA,,,,,,0,00,,,M,,M,,*66\r\n\xb5".to_vec();
+        stream.extend_from_slice(&damaged);
+        stream.extend_from_slice(&ack);
+        stream.extend_from_slice(&[0xb5, 0x62, 0x01, 0x07, 0xff, 0xff]);
+        stream.extend_from_slice(&nak);
+
+        let mut parser = UbxParser::default();
+        let mut frames = Vec::new();
+        // byte by byte, every frame is split across reads
+        for byte in &stream {
+            frames.extend(parser.feed(&[*byte]));
+        }
+        let messages: Vec<UbxMessage> = frames.into_iter().map(UbxFrame::message).collect();
+        assert_eq!(
+            messages,
+            vec![
+                UbxMessage::Ack { class: 6, id: 8 },
+                UbxMessage::Nak { class: 6, id: 0 }
+            ]
+        );
+        let frames = UbxParser::default().feed(&stream);
+        assert_eq!(frames.len(), 2);
+    }
+
+    #[test]
+    fn nav_pvt_units() {
+        let mut payload = vec![0u8; NAV_PVT_SIZE];
+        let mut put = |offset: usize, bytes: &[u8]| {
+            payload[offset..offset + bytes.len()].copy_from_slice(bytes)
+        };
+        put(0, &345_600_000u32.to_le_bytes());
+        put(4, &2024u16.to_le_bytes());
+        put(6, &[5, 12, 14, 9, 52, 0x07]);
+        put(16, &(-5i32).to_le_bytes());
+        put(20, &[3, 0x01, 0, 12]);
+        put(24, &134_050_000i32.to_le_bytes());
+        put(28, &(-525_200_000i32).to_le_bytes());
+        put(32, &45_000i32.to_le_bytes());
+        put(40, &1_500u32.to_le_bytes());
+        put(48, &(-250i32).to_le_bytes());
+        put(64, &2_500_000i32.to_le_bytes());
+        put(76, &135u16.to_le_bytes());
+        let encoded = frame(CLASS_NAV, NAV_PVT, &payload).encode().unwrap();
+        let frames = UbxParser::default().feed(&encoded);
+        let message = frames.into_iter().next().unwrap().message();
+        let near = |value: f64, expected: f64| (value - expected).abs() < 1e-9;
+        match message {
+            UbxMessage::NavPvt {
+                itow,
+                year,
+                second,
+                nano,
+                valid_time,
+                fix_type,
+                fix_ok,
+                satellites,
+                longitude,
+                latitude,
+                height,
+                horizontal_accuracy,
+                velocity_north,
+                heading,
+                pdop,
+                ..
+            } => {
+                assert_eq!((itow, year, second, nano), (345_600_000, 2024, 52, -5));
+                assert!(valid_time && fix_ok);
+                assert_eq!((fix_type, satellites), (3, 12));
+                assert!(near(longitude, 13.405) && near(latitude, -52.52));
+                assert!(near(height, 45.0) && near(horizontal_accuracy, 1.5));
+                assert!(near(velocity_north, -0.25) && near(heading, 25.0));
+                assert!(near(pdop, 1.35));
+            }
+            other => panic!("{:?}", other),
+        }
+        // a NAV-PVT of another size stays a frame
+        let short = frame(CLASS_NAV, NAV_PVT, &payload[..84]);
+        assert_eq!(short.clone().message(), UbxMessage::Frame(short));
+    }
+}
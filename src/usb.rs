@@ -0,0 +1,393 @@
+//! CDC-ACM devices driven directly over USB, bypassing the COM / tty driver of the OS.
+//! Ports are opened as `usb://<vid>:<pid>` or `usb://<vid>:<pid>/<serial number>`
+//! with the ids in hex. Composite devices with several CDC-ACM functions get a
+//! `#<interface>` suffix naming the control interface of the function.
+
+use crate::blocking::wait;
+use crate::transport::{LineSettings, Transport, TransportPort};
+use nusb::transfer::{
+    Control, ControlType, Direction, EndpointType, Queue, Recipient, RequestBuffer,
+};
+use nusb::Interface;
+use serialport::{ClearBuffer, DataBits, Parity, SerialPort, StopBits};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const PREFIX: &str = "usb://";
+
+const CLASS_COMMUNICATION: u8 = 0x02;
+const SUBCLASS_ACM: u8 = 0x02;
+const CLASS_DATA: u8 = 0x0a;
+
+const SET_LINE_CODING: u8 = 0x20;
+const SET_CONTROL_LINE_STATE: u8 = 0x22;
+const SEND_BREAK: u8 = 0x23;
+
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(1);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Bulk IN transfers kept queued so no packet is missed between reads.
+const READ_TRANSFERS: usize = 2;
+
+/// `CdcDevice` A USB device offering a CDC-ACM interface.
+pub struct CdcDevice {
+    pub path: String,
+    pub vid: u16,
+    pub pid: u16,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial_number: Option<String>,
+    pub bus: u8,
+    pub address: u8,
+    /// Number of the CDC-ACM control interface.
+    pub interface: u8,
+}
+
+/// `acm_interfaces` Numbers of the CDC-ACM control interfaces of a device.
+fn acm_interfaces(device: &nusb::DeviceInfo) -> Vec<u8> {
+    let mut numbers: Vec<u8> = device
+        .interfaces()
+        .filter(|interface| {
+            interface.class() == CLASS_COMMUNICATION && interface.subclass() == SUBCLASS_ACM
+        })
+        .map(|interface| interface.interface_number())
+        .collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    numbers
+}
+
+fn is_acm(device: &nusb::DeviceInfo) -> bool {
+    !acm_interfaces(device).is_empty()
+}
+
+fn device_path(device: &nusb::DeviceInfo) -> String {
+    match device.serial_number() {
+        Some(serial_number) => format!(
+            "{}{:04x}:{:04x}/{}",
+            PREFIX,
+            device.vendor_id(),
+            device.product_id(),
+            serial_number
+        ),
+        None => format!(
+            "{}{:04x}:{:04x}",
+            PREFIX,
+            device.vendor_id(),
+            device.product_id()
+        ),
+    }
+}
+
+/// `available_ports` List USB devices with a CDC-ACM interface.
+pub fn available_ports() -> Vec<CdcDevice> {
+    let devices = match nusb::list_devices() {
+        Ok(devices) => devices,
+        Err(error) => {
+            debug!("Cannot enumerate USB devices: {}", error);
+            return Vec::new();
+        }
+    };
+    let mut ports = Vec::new();
+    for device in devices {
+        let interfaces = acm_interfaces(&device);
+        let composite = interfaces.len() > 1;
+        for interface in interfaces {
+            let path = if composite {
+                format!("{}#{}", device_path(&device), interface)
+            } else {
+                device_path(&device)
+            };
+            ports.push(CdcDevice {
+                path,
+                vid: device.vendor_id(),
+                pid: device.product_id(),
+                manufacturer: device.manufacturer_string().map(String::from),
+                product: device.product_string().map(String::from),
+                serial_number: device.serial_number().map(String::from),
+                bus: device.bus_number(),
+                address: device.device_address(),
+                interface,
+            });
+        }
+    }
+    ports
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "Operation timed out")
+}
+
+fn to_io_error<E: std::fmt::Display>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error.to_string())
+}
+
+struct Reader {
+    queue: Queue<RequestBuffer>,
+    /// Received bytes that did not fit into the caller's buffer.
+    pending: VecDeque<u8>,
+    transfer_size: usize,
+}
+
+/// `CdcAcm` A claimed CDC-ACM function, clones share the interfaces.
+struct CdcAcm {
+    control: Interface,
+    data: Interface,
+    out_endpoint: u8,
+    reader: Arc<Mutex<Reader>>,
+    /// DTR and RTS as last set, `SET_CONTROL_LINE_STATE` always sends both.
+    lines: Arc<Mutex<u16>>,
+}
+
+impl CdcAcm {
+    fn request(&self, request: u8, value: u16, data: &[u8]) -> serialport::Result<()> {
+        self.control
+            .control_out_blocking(
+                Control {
+                    control_type: ControlType::Class,
+                    recipient: Recipient::Interface,
+                    request,
+                    value,
+                    index: self.control.interface_number() as u16,
+                },
+                data,
+                CONTROL_TIMEOUT,
+            )
+            .map(|_| ())
+            .map_err(|error| {
+                serialport::Error::new(serialport::ErrorKind::Unknown, error.to_string())
+            })
+    }
+
+    fn set_line(&mut self, bit: u16, level: bool) -> serialport::Result<()> {
+        let state = {
+            let mut lines = self.lines.lock().map_err(|_| {
+                serialport::Error::new(serialport::ErrorKind::Unknown, "USB lock poisoned")
+            })?;
+            if level {
+                *lines |= bit;
+            } else {
+                *lines &= !bit;
+            }
+            *lines
+        };
+        self.request(SET_CONTROL_LINE_STATE, state, &[])
+    }
+}
+
+impl Transport for CdcAcm {
+    fn kind(&self) -> &'static str {
+        "USB CDC-ACM"
+    }
+
+    fn read(&mut self, buf: &mut [u8], timeout: Duration) -> io::Result<usize> {
+        let mut reader = self
+            .reader
+            .lock()
+            .map_err(|_| to_io_error("USB lock poisoned"))?;
+        let deadline = Instant::now() + timeout;
+        while reader.pending.is_empty() {
+            while reader.queue.pending() < READ_TRANSFERS {
+                let size = reader.transfer_size;
+                reader.queue.submit(RequestBuffer::new(size));
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let completion =
+                wait(|context| reader.queue.poll_next(context), remaining).ok_or_else(timed_out)?;
+            completion.status.map_err(to_io_error)?;
+            reader.pending.extend(&completion.data);
+        }
+        let size = buf.len().min(reader.pending.len());
+        for (target, byte) in buf.iter_mut().zip(reader.pending.drain(..size)) {
+            *target = byte;
+        }
+        Ok(size)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut transfer = self.data.bulk_out(self.out_endpoint, buf.to_vec());
+        let completion = wait(
+            |context| Pin::new(&mut transfer).poll(context),
+            WRITE_TIMEOUT,
+        )
+        .ok_or_else(timed_out)?;
+        completion.status.map_err(to_io_error)?;
+        Ok(completion.data.actual_length())
+    }
+
+    fn try_clone(&self) -> serialport::Result<Box<dyn Transport>> {
+        Ok(Box::new(CdcAcm {
+            control: self.control.clone(),
+            data: self.data.clone(),
+            out_endpoint: self.out_endpoint,
+            reader: self.reader.clone(),
+            lines: self.lines.clone(),
+        }))
+    }
+
+    fn configure(&mut self, settings: &LineSettings) -> serialport::Result<()> {
+        let mut coding = [0u8; 7];
+        coding[..4].copy_from_slice(&settings.baud_rate.to_le_bytes());
+        coding[4] = match settings.stop_bits {
+            StopBits::One => 0,
+            StopBits::Two => 2,
+        };
+        coding[5] = match settings.parity {
+            Parity::None => 0,
+            Parity::Odd => 1,
+            Parity::Even => 2,
+        };
+        coding[6] = match settings.data_bits {
+            DataBits::Five => 5,
+            DataBits::Six => 6,
+            DataBits::Seven => 7,
+            DataBits::Eight => 8,
+        };
+        self.request(SET_LINE_CODING, 0, &coding)
+    }
+
+    fn bytes_to_read(&self) -> serialport::Result<u32> {
+        Ok(self
+            .reader
+            .lock()
+            .map(|reader| reader.pending.len() as u32)
+            .unwrap_or(0))
+    }
+
+    fn clear(&self, buffer_to_clear: ClearBuffer) -> serialport::Result<()> {
+        if buffer_to_clear != ClearBuffer::Output {
+            if let Ok(mut reader) = self.reader.lock() {
+                reader.pending.clear();
+            }
+        }
+        Ok(())
+    }
+
+    fn write_data_terminal_ready(&mut self, level: bool) -> serialport::Result<()> {
+        self.set_line(0x01, level)
+    }
+
+    fn write_request_to_send(&mut self, level: bool) -> serialport::Result<()> {
+        self.set_line(0x02, level)
+    }
+
+    fn set_break(&self) -> serialport::Result<()> {
+        // 0xffff keeps the break until it is cleared
+        self.request(SEND_BREAK, 0xffff, &[])
+    }
+
+    fn clear_break(&self) -> serialport::Result<()> {
+        self.request(SEND_BREAK, 0, &[])
+    }
+}
+
+fn no_device(message: String) -> serialport::Error {
+    serialport::Error::new(serialport::ErrorKind::NoDevice, message)
+}
+
+/// `UsbPath` The parts of `usb://vid:pid[/serial][#interface]`.
+struct UsbPath<'a> {
+    vid: u16,
+    pid: u16,
+    serial_number: Option<&'a str>,
+    interface: Option<u8>,
+}
+
+/// `parse_path` Split `usb://vid:pid[/serial][#interface]` into its parts.
+fn parse_path(path: &str) -> serialport::Result<UsbPath<'_>> {
+    let invalid = || {
+        serialport::Error::new(
+            serialport::ErrorKind::InvalidInput,
+            format!("Invalid USB path: {}", path),
+        )
+    };
+    let rest = path.strip_prefix(PREFIX).ok_or_else(invalid)?;
+    let (rest, interface) = match rest.rsplit_once('#') {
+        Some((rest, interface)) => (rest, Some(interface.parse::<u8>().map_err(|_| invalid())?)),
+        None => (rest, None),
+    };
+    let (ids, serial_number) = match rest.split_once('/') {
+        Some((ids, serial_number)) => (ids, Some(serial_number)),
+        None => (rest, None),
+    };
+    let (vid, pid) = ids.split_once(':').ok_or_else(invalid)?;
+    let vid = u16::from_str_radix(vid, 16).map_err(|_| invalid())?;
+    let pid = u16::from_str_radix(pid, 16).map_err(|_| invalid())?;
+    Ok(UsbPath {
+        vid,
+        pid,
+        serial_number,
+        interface,
+    })
+}
+
+/// `open` Claim the CDC-ACM interfaces of a device, detaching the kernel driver where needed.
+pub fn open(path: &str, settings: LineSettings) -> serialport::Result<Box<dyn SerialPort>> {
+    let target = parse_path(path)?;
+    let info = nusb::list_devices()
+        .map_err(|error| no_device(error.to_string()))?
+        .filter(is_acm)
+        .find(|device| {
+            device.vendor_id() == target.vid
+                && device.product_id() == target.pid
+                && (target.serial_number.is_none()
+                    || device.serial_number() == target.serial_number)
+        })
+        .ok_or_else(|| no_device(format!("USB device {} not found", path)))?;
+    let interfaces = acm_interfaces(&info);
+    let control_number = match target.interface {
+        Some(number) if interfaces.contains(&number) => number,
+        Some(number) => {
+            return Err(no_device(format!(
+                "Interface {} of {} is not a CDC-ACM interface",
+                number, path
+            )))
+        }
+        None => interfaces[0],
+    };
+    // the data interface of a function follows its control interface
+    let data_number = info
+        .interfaces()
+        .filter(|interface| interface.class() == CLASS_DATA)
+        .map(|interface| interface.interface_number())
+        .filter(|number| *number > control_number)
+        .min()
+        .ok_or_else(|| no_device(format!("{} has no CDC data interface", path)))?;
+    let device = info.open()?;
+    let control = device.detach_and_claim_interface(control_number)?;
+    let data = device.detach_and_claim_interface(data_number)?;
+    let mut in_endpoint = None;
+    let mut out_endpoint = None;
+    for alt_setting in data.descriptors() {
+        for endpoint in alt_setting.endpoints() {
+            if endpoint.transfer_type() != EndpointType::Bulk {
+                continue;
+            }
+            match endpoint.direction() {
+                Direction::In => {
+                    in_endpoint = Some((endpoint.address(), endpoint.max_packet_size()))
+                }
+                Direction::Out => out_endpoint = Some(endpoint.address()),
+            }
+        }
+    }
+    let (in_endpoint, max_packet_size) =
+        in_endpoint.ok_or_else(|| no_device(format!("{} has no bulk IN endpoint", path)))?;
+    let out_endpoint =
+        out_endpoint.ok_or_else(|| no_device(format!("{} has no bulk OUT endpoint", path)))?;
+    let transport = CdcAcm {
+        reader: Arc::new(Mutex::new(Reader {
+            queue: data.bulk_in_queue(in_endpoint),
+            pending: VecDeque::new(),
+            transfer_size: max_packet_size.max(64) * 16,
+        })),
+        control,
+        data,
+        out_endpoint,
+        lines: Arc::new(Mutex::new(0)),
+    };
+    TransportPort::open(path, Box::new(transport), settings)
+}
@@ -0,0 +1,77 @@
+//! Byte sequence and regex watchers that scan the receive stream across read boundaries.
+
+use crate::reader::now_millis;
+use crate::state::MatchData;
+use regex::bytes::Regex;
+
+/// Received bytes kept per watcher, so matches may span several reads.
+pub const WINDOW: usize = 4096;
+
+/// `Matcher` What a watcher looks for.
+pub enum Matcher {
+    Bytes(Vec<u8>),
+    Regex(Regex),
+}
+
+impl Matcher {
+    /// `find` Start and end of the first match in `data`.
+    pub fn find(&self, data: &[u8]) -> Option<(usize, usize)> {
+        match self {
+            Matcher::Bytes(pattern) if pattern.is_empty() => None,
+            Matcher::Bytes(pattern) => data
+                .windows(pattern.len())
+                .position(|window| window == pattern.as_slice())
+                .map(|start| (start, start + pattern.len())),
+            Matcher::Regex(regex) => regex
+                .find(data)
+                .filter(|found| found.end() > found.start())
+                .map(|found| (found.start(), found.end())),
+        }
+    }
+}
+
+/// `Watcher` A matcher with the tail of the stream it has seen.
+pub struct Watcher {
+    pub id: String,
+    matcher: Matcher,
+    /// Bytes of context sent before and after a match.
+    context: usize,
+    buffer: Vec<u8>,
+    /// Searching resumes here, bytes before it belong to an earlier match.
+    start: usize,
+}
+
+impl Watcher {
+    pub fn new(id: String, matcher: Matcher, context: usize) -> Self {
+        Watcher {
+            id,
+            matcher,
+            context,
+            buffer: Vec::new(),
+            start: 0,
+        }
+    }
+
+    /// `feed` Scan newly received bytes, returns every match they complete.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<MatchData> {
+        self.buffer.extend_from_slice(data);
+        let mut matches = Vec::new();
+        while let Some((start, end)) = self.matcher.find(&self.buffer[self.start..]) {
+            let (start, end) = (self.start + start, self.start + end);
+            matches.push(MatchData {
+                id: self.id.clone(),
+                data: self.buffer[start..end].to_vec(),
+                before: self.buffer[start.saturating_sub(self.context)..start].to_vec(),
+                after: self.buffer[end..(end + self.context).min(self.buffer.len())].to_vec(),
+                timestamp: now_millis(),
+            });
+            self.start = end;
+        }
+        if self.buffer.len() > WINDOW {
+            let excess = self.buffer.len() - WINDOW;
+            self.buffer.drain(..excess);
+            self.start = self.start.saturating_sub(excess);
+        }
+        matches
+    }
+}
@@ -0,0 +1,581 @@
+//! The API mode of Digi XBee radios: the delimiter `7E`, the length of the frame data as u16 big
+//! endian, the frame data starting with its type, and a checksum making the frame data sum to
+//! `FF`. In API mode 2 (`AP=2`) the bytes `7E`, `7D`, `11` and `13` after the delimiter are
+//! escaped as `7D` followed by the byte XOR `20`.
+
+use serde::{Deserialize, Serialize};
+use serialport::{ClearBuffer, SerialPort};
+use std::io;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+const DELIMITER: u8 = 0x7e;
+const ESCAPE: u8 = 0x7d;
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+/// Longest frame data accepted, longer lengths are taken for noise.
+const MAX_FRAME: usize = 2048;
+
+const AT_COMMAND: u8 = 0x08;
+const TX_REQUEST: u8 = 0x10;
+const REMOTE_AT_COMMAND: u8 = 0x17;
+const AT_RESPONSE: u8 = 0x88;
+const MODEM_STATUS: u8 = 0x8a;
+const TRANSMIT_STATUS: u8 = 0x8b;
+const RX_PACKET: u8 = 0x90;
+const REMOTE_AT_RESPONSE: u8 = 0x97;
+
+/// 16-bit address of a radio when it is not known.
+const UNKNOWN_ADDRESS: u16 = 0xfffe;
+
+/// `XBeeFrame` An API frame. Frame id 0 asks the radio not to answer; commands are two
+/// characters like `NI`. `Frame` holds the types without a typed form.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type")]
+pub enum XBeeFrame {
+    AtCommand {
+        frame_id: u8,
+        command: String,
+        #[serde(default)]
+        parameter: Vec<u8>,
+    },
+    /// Sent to the 64-bit `destination`, 0 for the coordinator, `FFFF` for all radios. Options 0
+    /// use the radio defaults.
+    TxRequest {
+        frame_id: u8,
+        destination: u64,
+        destination_16: Option<u16>,
+        #[serde(default)]
+        radius: u8,
+        #[serde(default)]
+        options: u8,
+        data: Vec<u8>,
+    },
+    /// Options bit 1 applies the change immediately, without a separate `AC`.
+    RemoteAtCommand {
+        frame_id: u8,
+        destination: u64,
+        destination_16: Option<u16>,
+        #[serde(default)]
+        options: u8,
+        command: String,
+        #[serde(default)]
+        parameter: Vec<u8>,
+    },
+    /// Status 0 OK, 1 error, 2 invalid command, 3 invalid parameter.
+    AtResponse {
+        frame_id: u8,
+        command: String,
+        status: u8,
+        data: Vec<u8>,
+    },
+    RemoteAtResponse {
+        frame_id: u8,
+        source: u64,
+        source_16: u16,
+        command: String,
+        status: u8,
+        data: Vec<u8>,
+    },
+    /// E.g. 0 hardware reset, 2 joined network, 6 coordinator started.
+    ModemStatus {
+        status: u8,
+    },
+    /// Delivery status 0 is success.
+    TransmitStatus {
+        frame_id: u8,
+        destination_16: u16,
+        retries: u8,
+        delivery: u8,
+        discovery: u8,
+    },
+    /// Options bit 0 acknowledged, bit 1 broadcast.
+    RxPacket {
+        source: u64,
+        source_16: u16,
+        options: u8,
+        data: Vec<u8>,
+    },
+    Frame {
+        frame_type: u8,
+        data: Vec<u8>,
+    },
+}
+
+fn command_bytes(command: &str) -> Result<[u8; 2], String> {
+    match command.as_bytes() {
+        [first, second] => Ok([*first, *second]),
+        _ => Err(format!("AT command {:?} is not two characters", command)),
+    }
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_be_bytes(bytes)
+}
+
+fn command_at(data: &[u8], offset: usize) -> String {
+    String::from_utf8_lossy(&data[offset..offset + 2]).into_owned()
+}
+
+impl XBeeFrame {
+    /// `frame_id` The id an answer refers to, 0 for frames without one.
+    pub fn frame_id(&self) -> u8 {
+        match self {
+            XBeeFrame::AtCommand { frame_id, .. }
+            | XBeeFrame::TxRequest { frame_id, .. }
+            | XBeeFrame::RemoteAtCommand { frame_id, .. }
+            | XBeeFrame::AtResponse { frame_id, .. }
+            | XBeeFrame::RemoteAtResponse { frame_id, .. }
+            | XBeeFrame::TransmitStatus { frame_id, .. } => *frame_id,
+            _ => 0,
+        }
+    }
+
+    /// `frame_data` The frame type and its fields.
+    fn frame_data(&self) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        match self {
+            XBeeFrame::AtCommand {
+                frame_id,
+                command,
+                parameter,
+            } => {
+                data.extend_from_slice(&[AT_COMMAND, *frame_id]);
+                data.extend_from_slice(&command_bytes(command)?);
+                data.extend_from_slice(parameter);
+            }
+            XBeeFrame::TxRequest {
+                frame_id,
+                destination,
+                destination_16,
+                radius,
+                options,
+                data: payload,
+            } => {
+                data.extend_from_slice(&[TX_REQUEST, *frame_id]);
+                data.extend_from_slice(&destination.to_be_bytes());
+                data.extend_from_slice(&destination_16.unwrap_or(UNKNOWN_ADDRESS).to_be_bytes());
+                data.extend_from_slice(&[*radius, *options]);
+                data.extend_from_slice(payload);
+            }
+            XBeeFrame::RemoteAtCommand {
+                frame_id,
+                destination,
+                destination_16,
+                options,
+                command,
+                parameter,
+            } => {
+                data.extend_from_slice(&[REMOTE_AT_COMMAND, *frame_id]);
+                data.extend_from_slice(&destination.to_be_bytes());
+                data.extend_from_slice(&destination_16.unwrap_or(UNKNOWN_ADDRESS).to_be_bytes());
+                data.push(*options);
+                data.extend_from_slice(&command_bytes(command)?);
+                data.extend_from_slice(parameter);
+            }
+            XBeeFrame::AtResponse {
+                frame_id,
+                command,
+                status,
+                data: value,
+            } => {
+                data.extend_from_slice(&[AT_RESPONSE, *frame_id]);
+                data.extend_from_slice(&command_bytes(command)?);
+                data.push(*status);
+                data.extend_from_slice(value);
+            }
+            XBeeFrame::RemoteAtResponse {
+                frame_id,
+                source,
+                source_16,
+                command,
+                status,
+                data: value,
+            } => {
+                data.extend_from_slice(&[REMOTE_AT_RESPONSE, *frame_id]);
+                data.extend_from_slice(&source.to_be_bytes());
+                data.extend_from_slice(&source_16.to_be_bytes());
+                data.extend_from_slice(&command_bytes(command)?);
+                data.push(*status);
+                data.extend_from_slice(value);
+            }
+            XBeeFrame::ModemStatus { status } => data.extend_from_slice(&[MODEM_STATUS, *status]),
+            XBeeFrame::TransmitStatus {
+                frame_id,
+                destination_16,
+                retries,
+                delivery,
+                discovery,
+            } => {
+                data.extend_from_slice(&[TRANSMIT_STATUS, *frame_id]);
+                data.extend_from_slice(&destination_16.to_be_bytes());
+                data.extend_from_slice(&[*retries, *delivery, *discovery]);
+            }
+            XBeeFrame::RxPacket {
+                source,
+                source_16,
+                options,
+                data: payload,
+            } => {
+                data.push(RX_PACKET);
+                data.extend_from_slice(&source.to_be_bytes());
+                data.extend_from_slice(&source_16.to_be_bytes());
+                data.push(*options);
+                data.extend_from_slice(payload);
+            }
+            XBeeFrame::Frame {
+                frame_type,
+                data: payload,
+            } => {
+                data.push(*frame_type);
+                data.extend_from_slice(payload);
+            }
+        }
+        if data.len() > MAX_FRAME {
+            return Err(format!("XBee frame of {} bytes is too long", data.len()));
+        }
+        Ok(data)
+    }
+
+    /// `encode` The frame with delimiter, length and checksum, escaped for API mode 2.
+    pub fn encode(&self, escaped: bool) -> Result<Vec<u8>, String> {
+        let data = self.frame_data()?;
+        let checksum = 0xff - data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        let mut raw = (data.len() as u16).to_be_bytes().to_vec();
+        raw.extend_from_slice(&data);
+        raw.push(checksum);
+        let mut frame = vec![DELIMITER];
+        for byte in raw {
+            if escaped && matches!(byte, DELIMITER | ESCAPE | XON | XOFF) {
+                frame.extend_from_slice(&[ESCAPE, byte ^ 0x20]);
+            } else {
+                frame.push(byte);
+            }
+        }
+        Ok(frame)
+    }
+
+    /// `decode` The typed form of received frame data, `Frame` when it is too short for its
+    /// type.
+    fn decode(data: &[u8]) -> XBeeFrame {
+        let (frame_type, fields) = (data[0], &data[1..]);
+        match frame_type {
+            AT_COMMAND if fields.len() >= 3 => XBeeFrame::AtCommand {
+                frame_id: fields[0],
+                command: command_at(fields, 1),
+                parameter: fields[3..].to_vec(),
+            },
+            TX_REQUEST if fields.len() >= 13 => XBeeFrame::TxRequest {
+                frame_id: fields[0],
+                destination: u64_at(fields, 1),
+                destination_16: Some(u16_at(fields, 9)),
+                radius: fields[11],
+                options: fields[12],
+                data: fields[13..].to_vec(),
+            },
+            REMOTE_AT_COMMAND if fields.len() >= 14 => XBeeFrame::RemoteAtCommand {
+                frame_id: fields[0],
+                destination: u64_at(fields, 1),
+                destination_16: Some(u16_at(fields, 9)),
+                options: fields[11],
+                command: command_at(fields, 12),
+                parameter: fields[14..].to_vec(),
+            },
+            AT_RESPONSE if fields.len() >= 4 => XBeeFrame::AtResponse {
+                frame_id: fields[0],
+                command: command_at(fields, 1),
+                status: fields[3],
+                data: fields[4..].to_vec(),
+            },
+            REMOTE_AT_RESPONSE if fields.len() >= 14 => XBeeFrame::RemoteAtResponse {
+                frame_id: fields[0],
+                source: u64_at(fields, 1),
+                source_16: u16_at(fields, 9),
+                command: command_at(fields, 11),
+                status: fields[13],
+                data: fields[14..].to_vec(),
+            },
+            MODEM_STATUS if !fields.is_empty() => XBeeFrame::ModemStatus { status: fields[0] },
+            TRANSMIT_STATUS if fields.len() >= 6 => XBeeFrame::TransmitStatus {
+                frame_id: fields[0],
+                destination_16: u16_at(fields, 1),
+                retries: fields[3],
+                delivery: fields[4],
+                discovery: fields[5],
+            },
+            RX_PACKET if fields.len() >= 11 => XBeeFrame::RxPacket {
+                source: u64_at(fields, 0),
+                source_16: u16_at(fields, 8),
+                options: fields[10],
+                data: fields[11..].to_vec(),
+            },
+            _ => XBeeFrame::Frame {
+                frame_type,
+                data: fields.to_vec(),
+            },
+        }
+    }
+}
+
+/// `XBeeParser` Splits the received stream into API frames, unescaping them in API mode 2.
+/// Bytes outside frames and frames with a wrong checksum are skipped.
+pub struct XBeeParser {
+    escaped: bool,
+    /// Unescaped bytes from the last delimiter on.
+    raw: Vec<u8>,
+    /// The previous byte was the escape.
+    escaping: bool,
+}
+
+impl XBeeParser {
+    pub fn new(escaped: bool) -> Self {
+        XBeeParser {
+            escaped,
+            raw: Vec::new(),
+            escaping: false,
+        }
+    }
+
+    /// `feed` Collect received bytes, returns the frames they complete.
+    pub fn feed(&mut self, data: &[u8]) -> Vec<XBeeFrame> {
+        let mut frames = Vec::new();
+        for &byte in data {
+            if byte == DELIMITER {
+                // in API mode 1 a delimiter may also be data, decided by the length
+                if self.escaped || self.raw.is_empty() {
+                    self.raw.clear();
+                    self.raw.push(DELIMITER);
+                    self.escaping = false;
+                    continue;
+                }
+            }
+            if self.raw.is_empty() {
+                continue;
+            }
+            if self.escaped && byte == ESCAPE {
+                self.escaping = true;
+                continue;
+            }
+            let byte = if self.escaping { byte ^ 0x20 } else { byte };
+            self.escaping = false;
+            self.raw.push(byte);
+            if let Some(frame) = self.complete() {
+                frames.extend(frame);
+            }
+        }
+        frames
+    }
+
+    /// `complete` The frame once `raw` holds all of it: `Some(None)` for a rejected one.
+    fn complete(&mut self) -> Option<Option<XBeeFrame>> {
+        if self.raw.len() < 3 {
+            return None;
+        }
+        let length = u16_at(&self.raw, 1) as usize;
+        if length == 0 || length > MAX_FRAME {
+            self.raw.clear();
+            return Some(None);
+        }
+        if self.raw.len() < length + 4 {
+            return None;
+        }
+        let data = &self.raw[3..3 + length];
+        let sum = data
+            .iter()
+            .fold(self.raw[3 + length], |sum, byte| sum.wrapping_add(*byte));
+        let frame = if sum == 0xff {
+            Some(XBeeFrame::decode(data))
+        } else {
+            debug!("XBee frame with a wrong checksum skipped");
+            None
+        };
+        self.raw.clear();
+        Some(frame)
+    }
+}
+
+static NEXT_FRAME_ID: AtomicU8 = AtomicU8::new(1);
+
+/// `next_frame_id` Frame ids for the commands of the plugin, 1 to 255 in turn.
+pub fn next_frame_id() -> u8 {
+    loop {
+        let id = NEXT_FRAME_ID.fetch_add(1, Ordering::Relaxed);
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+/// `at_command` Send an AT command to the local radio, or to `destination` as remote command,
+/// and wait for the response with its frame id.
+pub fn at_command(
+    port: &mut dyn SerialPort,
+    request: &XBeeFrame,
+    escaped: bool,
+    timeout: Duration,
+) -> Result<XBeeFrame, String> {
+    let frame_id = request.frame_id();
+    port.clear(ClearBuffer::Input)
+        .map_err(|error| format!("Failed to clear input: {}", error))?;
+    port.write_all(&request.encode(escaped)?)
+        .and_then(|_| port.flush())
+        .map_err(|error| format!("Failed to write: {}", error))?;
+    let deadline = Instant::now() + timeout;
+    let mut parser = XBeeParser::new(escaped);
+    let mut buf = [0u8; 256];
+    while Instant::now() < deadline {
+        let size = match port.read(&mut buf) {
+            Ok(size) => size,
+            Err(error) if error.kind() == io::ErrorKind::TimedOut => continue,
+            Err(error) => return Err(format!("Failed to read: {}", error)),
+        };
+        for frame in parser.feed(&buf[..size]) {
+            let answer = matches!(
+                frame,
+                XBeeFrame::AtResponse { .. } | XBeeFrame::RemoteAtResponse { .. }
+            );
+            if answer && frame.frame_id() == frame_id {
+                return Ok(frame);
+            }
+        }
+    }
+    Err("No response to the AT command".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(text: &str) -> Vec<u8> {
+        text.split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+            .collect()
+    }
+
+    /// Frames of the Digi documentation.
+    #[test]
+    fn known_frames() {
+        let at = XBeeFrame::AtCommand {
+            frame_id: 0x52,
+            command: "NH".to_string(),
+            parameter: Vec::new(),
+        };
+        assert_eq!(at.encode(false).unwrap(), hex("7E 00 04 08 52 4E 48 0F"));
+        let tx = XBeeFrame::TxRequest {
+            frame_id: 0x01,
+            destination: 0x0013_a200_400a_0127,
+            destination_16: None,
+            radius: 0,
+            options: 0,
+            data: b"TxData0A".to_vec(),
+        };
+        assert_eq!(
+            tx.encode(false).unwrap(),
+            hex("7E 00 16 10 01 00 13 A2 00 40 0A 01 27 FF FE 00 00 54 78 44 61 74 61 30 41 13")
+        );
+        let escaped = XBeeFrame::Frame {
+            frame_type: 0x23,
+            data: vec![0x11],
+        };
+        assert_eq!(escaped.encode(true).unwrap(), hex("7E 00 02 23 7D 31 CB"));
+        assert_eq!(escaped.encode(false).unwrap(), hex("7E 00 02 23 11 CB"));
+        let status = XBeeFrame::ModemStatus { status: 6 };
+        assert_eq!(status.encode(false).unwrap(), hex("7E 00 02 8A 06 6F"));
+        let mut parser = XBeeParser::new(true);
+        assert_eq!(parser.feed(&hex("7E 00 02 23 7D 31 CB")), vec![escaped]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let frames = vec![
+            XBeeFrame::AtCommand {
+                frame_id: 0x7e,
+                command: "NI".to_string(),
+                parameter: b"\x7d\x11\x13".to_vec(),
+            },
+            XBeeFrame::RemoteAtCommand {
+                frame_id: 3,
+                destination: 0x0013_a200_4000_7e7d,
+                destination_16: Some(0x1113),
+                options: 0x02,
+                command: "D0".to_string(),
+                parameter: vec![4],
+            },
+            XBeeFrame::AtResponse {
+                frame_id: 4,
+                command: "SH".to_string(),
+                status: 0,
+                data: vec![0x00, 0x13, 0xa2, 0x00],
+            },
+            XBeeFrame::RemoteAtResponse {
+                frame_id: 5,
+                source: 0x0013_a200_4052_2baa,
+                source_16: 0x7d84,
+                command: "IS".to_string(),
+                status: 0,
+                data: vec![1, 0, 8],
+            },
+            XBeeFrame::TransmitStatus {
+                frame_id: 6,
+                destination_16: 0x7d84,
+                retries: 0,
+                delivery: 0,
+                discovery: 0,
+            },
+            XBeeFrame::RxPacket {
+                source: 0x0013_a200_4052_2baa,
+                source_16: 0x7d84,
+                options: 0x01,
+                data: b"~}\x11\x13 hello".to_vec(),
+            },
+        ];
+        for escaped in [false, true] {
+            let mut stream = b"noise".to_vec();
+            for frame in &frames {
+                stream.extend(frame.encode(escaped).unwrap());
+            }
+            let mut parser = XBeeParser::new(escaped);
+            let mut received = Vec::new();
+            for chunk in stream.chunks(3) {
+                received.extend(parser.feed(chunk));
+            }
+            assert_eq!(received, frames);
+        }
+    }
+
+    #[test]
+    fn parser_rejects_damaged_frames() {
+        let status = XBeeFrame::ModemStatus { status: 2 };
+        let mut damaged = status.encode(false).unwrap();
+        damaged[4] ^= 0x01;
+        let mut parser = XBeeParser::new(false);
+        assert!(parser.feed(&damaged).is_empty());
+        assert!(parser.feed(&hex("7E 00 00")).is_empty());
+        assert_eq!(parser.feed(&status.encode(false).unwrap()), vec![status]);
+        // too short for its type
+        assert_eq!(
+            parser.feed(&hex("7E 00 02 88 01 76")),
+            vec![XBeeFrame::Frame {
+                frame_type: AT_RESPONSE,
+                data: vec![1],
+            }]
+        );
+        let long = XBeeFrame::Frame {
+            frame_type: 0x23,
+            data: vec![0; MAX_FRAME],
+        };
+        assert!(long.encode(false).is_err());
+        let bad_command = XBeeFrame::AtCommand {
+            frame_id: 1,
+            command: "N".to_string(),
+            parameter: Vec::new(),
+        };
+        assert!(bad_command.encode(false).is_err());
+    }
+}
@@ -7,9 +7,725 @@ export interface InvokeResult {
   message: string;
 }
 
+/**
+ * Rejection value of every plugin command.
+ * `kind` is one of `NoDevice`, `PermissionDenied`, `Busy`, `InvalidSettings`, `Io` or `Other`.
+ */
+export interface SerialportError {
+  kind: string;
+  message: string;
+}
+
 export interface ReadDataResult {
+  /** Counts the read events of the port, a gap means events were lost */
+  sequence: number;
+  size: number;
+  data: number[];
+  suppressed: number;
+  /** Written data echoed by `setLocalEcho` */
+  outgoing: boolean;
+  /** Milliseconds since the Unix epoch when the data was received */
+  timestamp: number;
+  /** Microseconds since the port was opened, unaffected by clock changes */
+  monotonic_us: number;
+}
+
+export interface FetchDataResult {
+  size: number;
+  data: number[];
+  available: number;
+  overwritten: number;
+}
+
+export interface HistoryFrame {
+  timestamp: number;
+  data: number[];
+}
+
+export interface MonitorData {
+  direction: 'TX' | 'RX';
+  timestamp: number;
+  size: number;
+  data: number[];
+}
+
+export interface ReadError {
+  kind: string;
+  message: string;
+  count: number;
+}
+
+export interface PollData {
+  id: string;
+  data: number[];
+  size: number;
+  timed_out: boolean;
+}
+
+export interface MatchData {
+  id: string;
+  data: number[];
+  before: number[];
+  after: number[];
+  timestamp: number;
+}
+
+export interface PacketSchema {
+  sync?: number[];
+  length?: { offset: number; size: 1 | 2 | 4; endian?: 'Little' | 'Big'; adjust?: number };
+  frame_length?: number;
+  endian?: 'Little' | 'Big';
+  fields?: {
+    name: string;
+    type: 'u8' | 'i8' | 'u16' | 'i16' | 'u32' | 'i32' | 'u64' | 'i64' | 'f32' | 'f64' | 'bytes' | 'string';
+    offset: number;
+    size?: number;
+    endian?: 'Little' | 'Big';
+    scale?: number;
+  }[];
+  checksum?: {
+    algorithm: 'Sum8' | 'Xor8' | 'Crc16Modbus' | 'Crc16Ccitt' | 'Crc32';
+    start?: number;
+    endian?: 'Little' | 'Big';
+  };
+}
+
+export interface PacketData {
+  fields: Record<string, number | string | number[]>;
+  raw: number[];
+  timestamp: number;
+}
+
+export interface PacketError {
+  message: string;
+  raw: number[];
+}
+
+export interface DecodedData {
+  value: unknown;
+  timestamp: number;
+}
+
+export interface FrameData {
+  data: number[];
+  size: number;
+  timestamp: number;
+}
+
+export interface CaptureData {
+  reason: 'Started' | 'Stop' | 'Limit' | 'Cancelled';
+  data: number[];
+  size: number;
+  file?: string;
+  timestamp: number;
+}
+
+export interface AtResponse {
+  lines: string[];
+  result: string;
+}
+
+export interface UrcData {
+  line: string;
+  pdu?: string;
+}
+
+export interface SmsMessage {
+  index?: number;
+  sender: string;
+  text: string;
+  timestamp: string;
+  reference?: number;
+  part?: number;
+  parts?: number;
+}
+
+export interface SmsReport {
+  reference: number;
+  recipient: string;
+  status: number;
+  delivered: boolean;
+  timestamp: string;
+  discharge_time: string;
+}
+
+export type MidiMessage =
+  | { type: 'NoteOff' | 'NoteOn'; channel: number; note: number; velocity: number }
+  | { type: 'PolyPressure'; channel: number; note: number; pressure: number }
+  | { type: 'ControlChange'; channel: number; controller: number; value: number }
+  | { type: 'ProgramChange'; channel: number; program: number }
+  | { type: 'ChannelPressure'; channel: number; pressure: number }
+  | { type: 'PitchBend'; channel: number; value: number }
+  | { type: 'SysEx'; data: number[] }
+  | { type: 'TimeCode'; value: number }
+  | { type: 'SongPosition'; beats: number }
+  | { type: 'SongSelect'; song: number }
+  | { type: 'TuneRequest' | 'Clock' | 'Start' | 'Continue' | 'Stop' | 'ActiveSensing' | 'Reset' };
+
+export interface DataReadyData {
+  /** Bytes waiting in the input buffer of the driver */
+  bytes: number;
+  timestamp: number;
+}
+
+export interface DisconnectData {
+  /** Why the port is considered gone, e.g. `End of file` */
+  reason: string;
+  timestamp: number;
+}
+
+export interface SuspendedData {
+  /** When the system was last seen awake */
+  timestamp: number;
+}
+
+export interface ResumedData {
+  slept_ms: number;
+  /** The port still answered after the sleep */
+  alive: boolean;
+  /** The port was dropped and opened again, a read must be started anew */
+  reopened: boolean;
+  /** Why a dropped port could not be opened again */
+  error: string | null;
+  timestamp: number;
+}
+
+export interface BreakData {
+  /** Breaks received since detection was enabled */
+  count: number;
+  timestamp: number;
+}
+
+export interface LineErrors {
+  parity: number;
+  framing: number;
+  breaks: number;
+  /** Characters the UART received before the previous one was read from it */
+  overrun: number;
+  /** Times the receive buffer of the driver was full */
+  buffer_overrun: number;
+  /** Bytes with a parity or framing error found marked in the stream */
+  marked: number;
+  /** Whether the driver counts the errors, otherwise only `marked` is known */
+  driver_counts: boolean;
+}
+
+export interface OverrunData {
+  overrun: number;
+  buffer_overrun: number;
+  timestamp: number;
+}
+
+export interface MidiData {
+  message: MidiMessage;
+  timestamp: number;
+}
+
+export type PinMode =
+  | 'Input'
+  | 'Output'
+  | 'Analog'
+  | 'Pwm'
+  | 'Servo'
+  | 'Shift'
+  | 'I2c'
+  | 'OneWire'
+  | 'Stepper'
+  | 'Encoder'
+  | 'Serial'
+  | 'InputPullup';
+
+export type FirmataMessage =
+  | { type: 'Analog'; pin: number; value: number }
+  | { type: 'DigitalPort'; port: number; value: number }
+  | { type: 'ReportAnalog'; pin: number; enable: boolean }
+  | { type: 'ReportDigital'; port: number; enable: boolean }
+  | { type: 'SetPinMode'; pin: number; mode: PinMode }
+  | { type: 'SetDigitalPin'; pin: number; value: boolean }
+  | { type: 'Version'; major: number; minor: number }
+  | { type: 'Firmware'; major: number; minor: number; name: string }
+  | { type: 'Capabilities'; pins: { mode: PinMode; resolution: number }[][] }
+  | { type: 'QueryPinState'; pin: number }
+  | { type: 'PinState'; pin: number; mode: PinMode | null; state: number }
+  | { type: 'ExtendedAnalog'; pin: number; value: number }
+  | { type: 'StringData'; text: string }
+  | { type: 'SamplingInterval'; interval: number }
+  | { type: 'SysEx'; command: number; data: number[] }
+  | { type: 'QueryFirmware' | 'QueryCapabilities' | 'SystemReset' };
+
+export interface FirmataData {
+  message: FirmataMessage;
+  timestamp: number;
+}
+
+export interface FirmataVersion {
+  /** The protocol version, e.g. `2.5` */
+  protocol: string;
+  /** e.g. `StandardFirmata.ino` */
+  firmware: string;
+  firmware_version: string;
+}
+
+export interface UbxFrame {
+  class: number;
+  id: number;
+  payload: number[];
+}
+
+/** Positions in degrees and meters, speeds in m/s */
+export interface NavPvt {
+  type: 'NavPvt';
+  itow: number;
+  year: number;
+  month: number;
+  day: number;
+  hour: number;
+  minute: number;
+  second: number;
+  nano: number;
+  valid_time: boolean;
+  /** 0 no fix, 2 2D, 3 3D, 4 GNSS and dead reckoning, 5 time only */
+  fix_type: number;
+  fix_ok: boolean;
+  satellites: number;
+  longitude: number;
+  latitude: number;
+  height: number;
+  height_msl: number;
+  horizontal_accuracy: number;
+  vertical_accuracy: number;
+  velocity_north: number;
+  velocity_east: number;
+  velocity_down: number;
+  ground_speed: number;
+  /** Heading of motion, degrees */
+  heading: number;
+  speed_accuracy: number;
+  pdop: number;
+}
+
+export type UbxMessage =
+  | NavPvt
+  | { type: 'Ack' | 'Nak'; class: number; id: number }
+  | ({ type: 'Frame' } & UbxFrame);
+
+export type UbxConfig =
+  | { type: 'Msg'; class: number; id: number; rate: number }
+  | { type: 'Rate'; measurement_ms: number; navigation_cycles?: number; time_reference?: number }
+  | { type: 'Prt'; port: number; baud_rate: number; in_protocols: number; out_protocols: number }
+  | { type: 'Save'; clear_mask?: number; save_mask?: number; load_mask?: number }
+  | { type: 'Valset'; layers?: number; items: [number, number][] };
+
+export interface UbxData {
+  message: UbxMessage;
+  timestamp: number;
+}
+
+/** Frame id 0 asks the radio not to answer, commands are two characters like `NI` */
+export type XBeeFrame =
+  | { type: 'AtCommand'; frame_id: number; command: string; parameter?: number[] }
+  | {
+      type: 'TxRequest';
+      frame_id: number;
+      /** 0 for the coordinator, 0xffff for all radios */
+      destination: number;
+      destination_16?: number;
+      radius?: number;
+      options?: number;
+      data: number[];
+    }
+  | {
+      type: 'RemoteAtCommand';
+      frame_id: number;
+      destination: number;
+      destination_16?: number;
+      options?: number;
+      command: string;
+      parameter?: number[];
+    }
+  | { type: 'AtResponse'; frame_id: number; command: string; status: number; data: number[] }
+  | {
+      type: 'RemoteAtResponse';
+      frame_id: number;
+      source: number;
+      source_16: number;
+      command: string;
+      status: number;
+      data: number[];
+    }
+  | { type: 'ModemStatus'; status: number }
+  | {
+      type: 'TransmitStatus';
+      frame_id: number;
+      destination_16: number;
+      retries: number;
+      delivery: number;
+      discovery: number;
+    }
+  | { type: 'RxPacket'; source: number; source_16: number; options: number; data: number[] }
+  | { type: 'Frame'; frame_type: number; data: number[] };
+
+export interface XBeeData {
+  frame: XBeeFrame;
+  timestamp: number;
+}
+
+export interface MeterRegister {
+  obis: string;
+  value: string;
+  unit?: string;
+}
+
+export interface MeterReading {
+  identification: string;
+  manufacturer: string;
+  baud_rate: number;
+  registers: MeterRegister[];
+}
+
+export type MdbResponse = { type: 'Ack' } | { type: 'Nak' } | { type: 'Data'; data: number[] };
+
+export type MdbEvent =
+  | { type: 'Online' | 'Offline' }
+  | { type: 'CoinDeposited'; coin_type: number; routing: string; tube_count: number }
+  | { type: 'CoinsDispensed'; coin_type: number; count: number; tube_count: number }
+  | { type: 'Slugs'; count: number }
+  | { type: 'BillRouted'; bill_type: number; routing: string }
+  | { type: 'DisabledBillAttempts'; count: number }
+  | { type: 'Status'; code: number; description: string }
+  | { type: 'Data'; data: number[] };
+
+export interface WriteOptions {
+  /** Resolve only once the driver reports the data sent, the last character included */
+  drain?: boolean;
+  /** Longest wait for the drain, by default the time the data needs plus one second */
+  drainTimeoutMs?: number;
+}
+
+export interface AckData {
+  id: string;
+  acknowledged: boolean;
+  outcome: 'Ack' | 'Nak' | 'Timeout' | 'Cancelled';
+  attempts: number;
+  timestamp: number;
+}
+
+export interface ProgressData {
+  id: string;
+  done: number;
+  /** Missing while the size is not known */
+  total?: number;
+  phase: string;
+  eta_ms?: number;
+  timestamp: number;
+}
+
+export interface Capabilities {
+  baud_rates: number[];
+  /** Whether rates other than the listed ones are accepted too */
+  custom_baud_rates: boolean;
+  data_bits: number[];
+  parities: string[];
+  stop_bits: number[];
+  /** `Driver` when the driver reported the settings, `Probe` when each was tried */
+  source: 'Driver' | 'Probe';
+}
+
+export interface Latency {
+  iterations: number;
+  /** Iterations whose echo did not arrive within the timeout */
+  lost: number;
+  /** Iterations echoed with different bytes */
+  mismatched: number;
+  min_us: number;
+  avg_us: number;
+  max_us: number;
+  /** Mean difference between consecutive round trips */
+  jitter_us: number;
+}
+
+export interface Throughput {
+  elapsed_ms: number;
+  bytes_sent: number;
+  bytes_received: number;
+  sent_per_second: number;
+  received_per_second: number;
+  /** What the baud rate allows with the configured character frame */
+  expected_per_second: number;
+  /** Places where the echo differs from what was sent, in loopback */
+  mismatches: number;
+  /** Errors the driver counted during the run */
+  line_errors: LineErrors;
+}
+
+export interface SettingCheck {
+  setting: 'baud_rate' | 'data_bits' | 'flow_control' | 'parity' | 'stop_bits';
+  value: string;
+  accepted: boolean;
+  /** Why it was rejected */
+  message: string | null;
+}
+
+export interface Validation {
+  /** Whether every setting was accepted */
+  valid: boolean;
+  checks: SettingCheck[];
+}
+
+export interface ManagedPort {
+  path: string;
+  /** Id the events of the port are named with */
+  event_id: string;
+  reading: boolean;
+  /** Attached with `setMetadata`, `null` by default */
+  metadata: any;
+}
+
+export interface PortSelector {
+  path?: string;
+  /** Vendor id in hexadecimal, as `available_ports` reports it, e.g. `0403` */
+  vid?: string;
+  pid?: string;
+  serial_number?: string;
+  /** USB interface number, tells apart the ports of a composite device */
+  interface?: number;
+}
+
+export interface ConnectionProfile extends PortSelector {
+  baud_rate: number;
+  data_bits?: 5 | 6 | 7 | 8;
+  flow_control?: null | 'Software' | 'Hardware';
+  parity?: null | 'Odd' | 'Even' | 'Mark' | 'Space';
+  stop_bits?: 1 | 2;
+  timeout?: number;
+  rs485?: NativeRs485;
+  /** Names the events of the port, so listeners find it whatever path was selected */
+  event_id?: string;
+  access?: 'ReadWrite' | 'ReadOnly' | 'WriteOnly';
+}
+
+export interface NamedProfile {
+  name: string;
+  profile: ConnectionProfile;
+}
+
+export interface ProfileOpen {
+  name: string;
+  /** The port the profile selected */
+  path: string;
+  event_id: string;
+  profile: ConnectionProfile;
+}
+
+export interface SavedSettings {
+  baud_rate: number;
+  data_bits: number;
+  flow_control: string;
+  parity: string;
+  stop_bits: number;
+  timeout: number;
+  /** The port the device was on */
+  path: string;
+  saved_at: number;
+}
+
+export interface SavedOpen {
+  path: string;
+  event_id: string;
+  /** The key the settings are saved under, the serial number of the device */
+  device: string;
+  settings: SavedSettings;
+}
+
+export interface AutoOpenPort extends ConnectionProfile {
+  /** Keep looking for a port missing at startup and open it once it is plugged in */
+  wait?: boolean;
+}
+
+export interface ConfigDocument {
+  version: number;
+  profiles: { [name: string]: ConnectionProfile };
+  /** The ports opened at startup, declared in the application and imported */
+  auto_open: AutoOpenPort[];
+  /** The last settings by device, with `Builder::remember_settings` */
+  saved_settings: { [device: string]: SavedSettings };
+}
+
+export interface ImportReport {
+  profiles: number;
+  auto_open: number;
+  /** Zero when settings are not remembered, the entries are then dropped */
+  saved_settings: number;
+}
+
+export interface AutoOpenedPort {
+  /** The port as declared, e.g. its path or `0403:6001 A702TB52` */
+  selector: string;
+  /** The port that was selected */
+  path: string | null;
+  event_id: string | null;
+  baud_rate: number;
+  opened: boolean;
+  /** Missing so far, opened once it appears */
+  waiting: boolean;
+  error: string | null;
+  timestamp: number;
+}
+
+export interface Consumed {
+  frames: number[][];
+  /** Bytes discarded by the stream before the consumer read them */
+  skipped: number;
+  /** Bytes still ahead of the cursor */
+  available: number;
+}
+
+export interface BufferOverflowData {
+  /** Read data dropped since the buffer filled up */
+  dropped: number;
+  buffered_bytes: number;
+  /** The buffer drained, `dropped` is the total of the overflow */
+  recovered: boolean;
+  timestamp: number;
+}
+
+export interface CancelledData {
+  id: string;
+  /** Bytes transferred before the operation stopped */
+  done: number;
+  timestamp: number;
+}
+
+export interface QueueData {
+  id: string;
+  priority: 'Urgent' | 'Normal' | 'Bulk';
   size: number;
+  error?: string;
+  timestamp: number;
+}
+
+export interface QueueStatus {
+  urgent: number;
+  normal: number;
+  bulk: number;
+  pending_bytes: number;
+  written_bytes: number;
+}
+
+export interface MdbData {
+  address: number;
+  event: MdbEvent;
+  timestamp: number;
+}
+
+export interface SocketData {
+  socket: number;
+  data: number[];
+  timestamp: number;
+}
+
+export interface SocketClosed {
+  socket: number;
+  timestamp: number;
+}
+
+export type LoraModule = 'Rn2483' | 'Rak';
+
+export interface ObdInfo {
+  /** e.g. `ELM327 v1.5` */
+  version: string;
+  /** e.g. `ISO 15765-4 (CAN 11/500)` */
+  protocol: string;
+  /** Battery voltage, e.g. `12.6V` */
+  voltage: string | null;
+}
+
+export interface LabelStatus {
+  paper_out: boolean;
+  paused: boolean;
+  /** In dots */
+  label_length: number;
+  /** Formats received but not yet printed */
+  formats_in_buffer: number;
+  buffer_full: boolean;
+  partial_format: boolean;
+  corrupt_ram: boolean;
+  under_temperature: boolean;
+  over_temperature: boolean;
+  head_up: boolean;
+  ribbon_out: boolean;
+  /** Otherwise direct thermal, without ribbon */
+  thermal_transfer: boolean;
+  label_waiting: boolean;
+  labels_remaining: number;
+}
+
+export interface PrinterStatus {
+  online: boolean;
+  /** Level of pin 3 of the drawer kick connector; whether high means open depends on the drawer */
+  drawer_signal: boolean;
+  cover_open: boolean;
+  /** Paper is fed with the feed button */
+  feeding: boolean;
+  /** Printing stopped because the paper ran out */
+  paper_end_stop: boolean;
+  error: boolean;
+  cutter_error: boolean;
+  unrecoverable_error: boolean;
+  recoverable_error: boolean;
+  paper_near_end: boolean;
+  paper_end: boolean;
+}
+
+export interface PidValue {
+  pid: number;
+  name: string | null;
+  /** null for PIDs without a known formula */
+  value: number | null;
+  unit: string | null;
+  data: number[];
+}
+
+export interface LoraDownlink {
+  /** LoRaWAN application port */
+  port: number;
   data: number[];
+  /** dBm and dB, when the module reports them */
+  rssi: number | null;
+  snr: number | null;
+  timestamp: number;
+}
+
+export interface IdleData {
+  idle_ms: number;
+  action: 'Notify' | 'Close' | 'Reopen';
+}
+
+export interface DiagnoseReport {
+  path: string;
+  exists: boolean;
+  mode: string | null;
+  owner_group: string | null;
+  readable: boolean;
+  writable: boolean;
+  user_groups: string[];
+  udev_running: boolean;
+  brltty_running: boolean;
+  issues: string[];
+}
+
+export interface AdapterInfo {
+  vid: string | null;
+  pid: string | null;
+  manufacturer: string | null;
+  product: string | null;
+  serial_number: string | null;
+  chip: string | null;
+  firmware_version: string | null;
+  eeprom_manufacturer: string | null;
+  eeprom_product: string | null;
+  eeprom_serial_number: string | null;
+  eeprom_checksum_valid: boolean | null;
 }
 
 export interface SerialPortInfo {
@@ -20,6 +736,78 @@ export interface SerialPortInfo {
   manufacturer: string | null;
   product: string | null;
   serial_number: string | null;
+  bus: string | null;
+  address: number | null;
+  port_path: string | null;
+  interface: number | null;
+  friendly_name: string | null;
+  instance_id: string | null;
+  driver: string | null;
+  by_id: string | null;
+  by_path: string | null;
+  dialin_path: string | null;
+  interface_name: string | null;
+  location_id: string | null;
+}
+
+/** Kernel RS-485 mode (TIOCSRS485) of Linux UARTs that drive the transceiver themselves */
+export interface NativeRs485 {
+  rts_on_send?: boolean;
+  rts_after_send?: boolean;
+  delay_before_send_ms?: number;
+  delay_after_send_ms?: number;
+  rx_during_tx?: boolean;
+  terminate_bus?: boolean;
+}
+
+export interface AddressFilter {
+  address: number;
+  /** Position of the address in each frame, 0 by default */
+  offset?: number;
+  /** Frames start with an address byte that has the ninth bit set */
+  nine_bit?: boolean;
+  broadcast?: number;
+}
+
+/** Frames of the encrypted link since the key was set */
+export interface EncryptionStats {
+  sent_frames: number;
+  received_frames: number;
+  /** Frames with a tag that did not match, sent with another key or damaged */
+  rejected_frames: number;
+  /** Authentic frames received again, or the own frames reflected back */
+  replayed_frames: number;
+  skipped_bytes: number;
+}
+
+/** Bytes of a compressed port before and after compression since it was set */
+export interface CompressionStats {
+  written_bytes: number;
+  sent_bytes: number;
+  received_bytes: number;
+  read_bytes: number;
+  /** Frames with a wrong CRC or a payload that did not decompress */
+  rejected_frames: number;
+  skipped_bytes: number;
+}
+
+export interface EmulatorRule {
+  /** Bytes the written data has to contain, or */
+  pattern?: number[];
+  /** a regex it has to match, `$1` in the response is replaced by its first group */
+  regex?: string;
+  response: number[];
+  delay_ms?: number;
+}
+
+/** A scripted device opened as `emu://<name>`, needs the `emulator` feature */
+export interface EmulatorScript {
+  /** Tried in order, the first that matches answers */
+  rules?: EmulatorRule[];
+  periodic?: { data: number[]; interval_ms: number }[];
+  /** Sent once the port is opened */
+  greeting?: number[];
+  echo?: boolean;
 }
 
 export interface SerialportOptions {
@@ -28,36 +816,96 @@ export interface SerialportOptions {
   encoding?: string;
   dataBits?: 5 | 6 | 7 | 8;
   flowControl?: null | 'Software' | 'Hardware';
-  parity?: null | 'Odd' | 'Even';
+  parity?: null | 'Odd' | 'Even' | 'Mark' | 'Space';
   stopBits?: 1 | 2;
   timeout?: number;
   size?: number;
+  rs485?: NativeRs485;
+  /** Id the events of the port are named with instead of its path */
+  eventId?: string;
+  /** Directions the plugin allows, `ReadWrite` by default */
+  access?: 'ReadWrite' | 'ReadOnly' | 'WriteOnly';
   [key: string]: any;
 }
 
 interface Options {
   dataBits: 5 | 6 | 7 | 8;
   flowControl: null | 'Software' | 'Hardware';
-  parity: null | 'Odd' | 'Even';
+  parity: null | 'Odd' | 'Even' | 'Mark' | 'Space';
   stopBits: 1 | 2;
   timeout: number;
+  rs485?: NativeRs485;
   [key: string]: any;
 }
 
 interface ReadOptions {
   timeout?: number;
   size?: number;
+  priority?: 'Min' | 'Low' | 'Normal' | 'High' | 'Max' | 'Realtime';
+  maxBatchBytes?: number;
+  maxBatchLatencyMs?: number;
+  maxEventRate?: number;
+  throttlePolicy?: 'Merge' | 'DropOldest' | 'DropNewest';
+  mode?: 'Event' | 'Buffer' | 'Midi' | 'Firmata' | 'Ubx' | 'XBee' | 'XBeeEscaped' | 'Notify';
+  bufferSize?: number;
+  transforms?: ('StripCr' | 'TrimNulls' | 'RemoveEcho' | 'Map' | 'SplitControl')[];
+  byteMap?: [number, number][];
+  codec?: string;
+  decode?: 'Cbor' | 'MessagePack' | 'JsonLines';
+  /** What happens to read events while the window is hidden, `Emit` by default */
+  whenHidden?: 'Emit' | 'Buffer' | 'Discard';
 }
 
 class Serialport {
   isOpen: boolean;
   unListen?: UnlistenFn;
+  unListenMonitor?: UnlistenFn;
+  unListenError?: UnlistenFn;
+  unListenIdle?: UnlistenFn;
+  unListenPoll?: UnlistenFn;
+  unListenMatch?: UnlistenFn;
+  unListenCapture?: UnlistenFn;
+  unListenFrame?: UnlistenFn;
+  unListenPacket?: UnlistenFn;
+  unListenPacketError?: UnlistenFn;
+  unListenDecoded?: UnlistenFn;
+  unListenDecodeError?: UnlistenFn;
+  unListenUrc?: UnlistenFn;
+  unListenSms?: UnlistenFn;
+  unListenSmsReport?: UnlistenFn;
+  unListenMidi?: UnlistenFn;
+  unListenFirmata?: UnlistenFn;
+  unListenUbx?: UnlistenFn;
+  unListenXBee?: UnlistenFn;
+  unListenBreak?: UnlistenFn;
+  unListenDisconnect?: UnlistenFn;
+  unListenSuspended?: UnlistenFn;
+  unListenResumed?: UnlistenFn;
+  unListenDataReady?: UnlistenFn;
+  unListenOverrun?: UnlistenFn;
+  unListenMdb?: UnlistenFn;
+  unListenAck?: UnlistenFn;
+  unListenQueue?: UnlistenFn;
+  unListenCancelled?: UnlistenFn;
+  unListenProgress?: UnlistenFn;
+  unListenSocket?: UnlistenFn;
+  unListenSocketClosed?: UnlistenFn;
+  unListenLora?: UnlistenFn;
+  unListenBufferOverflow?: UnlistenFn;
+  /** Reports `document.visibilityState` for `whenHidden` */
+  onVisibilityChange?: () => void;
+  /** Whether `listen` acknowledges every read event, see `setBackpressure` */
+  backpressure: boolean;
+  /** Names the events of the port, returned by `open` */
+  eventId: string;
   encoding: string;
   options: Options;
   size: number;
 
   constructor(options: SerialportOptions) {
     this.isOpen = false;
+    this.backpressure = false;
+    this.eventId = options.eventId || options.path;
     this.encoding = options.encoding || 'utf-8';
     this.options = {
       path: options.path,
@@ -67,259 +915,3572 @@ class Serialport {
       parity: options.parity || null,
       stopBits: options.stopBits || 2,
       timeout: options.timeout || 200,
+      rs485: options.rs485,
+      eventId: options.eventId,
+      access: options.access,
     };
     this.size = options.size || 1024;
   }
 
   /**
-   * @description: Get the list of serial ports
-   * @return {Promise<SerialPortInfo[]>}
+   * @description: Get the list of serial ports
+   * @return {Promise<SerialPortInfo[]>}
+   */
+  static async available_ports(): Promise<SerialPortInfo[]> {
+    try {
+      return await invoke<SerialPortInfo[]>('plugin:serialport|available_ports');
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Read vendor data of the USB adapter behind a port (needs the `usb` feature)
+   * @param {string} path
+   * @return {Promise<AdapterInfo>}
+   */
+  static async adapterInfo(path: string): Promise<AdapterInfo> {
+    return await invoke<AdapterInfo>('plugin:serialport|adapter_info', {
+      path,
+    });
+  }
+
+  /**
+   * @description: Reset the USB device behind a closed port (needs the `usb` feature, Linux and macOS)
+   * @param {string} path
+   * @return {Promise<void>}
+   */
+  static async usbReset(path: string): Promise<void> {
+    return await invoke<void>('plugin:serialport|usb_reset', {
+      path,
+    });
+  }
+
+  /**
+   * @description: Find the baud rates, data bits, parities and stop bits the driver of a port accepts. An open port must not be reading; a closed one is opened for the probe
+   * @param {string} path
+   * @return {Promise<Capabilities>}
+   */
+  static async capabilities(path: string): Promise<Capabilities> {
+    return await invoke<Capabilities>('plugin:serialport|capabilities', {
+      path,
+    });
+  }
+
+  /**
+   * @description: Apply line settings to a port one by one and report which the driver rejected. An open port must not be reading and keeps its settings; a closed one is opened for the check
+   * @param {string} path
+   * @param {Omit<SerialportOptions, 'path'>} settings
+   * @return {Promise<Validation>}
+   */
+  static async validateSettings(
+    path: string,
+    settings: Omit<SerialportOptions, 'path'>,
+  ): Promise<Validation> {
+    return await invoke<Validation>('plugin:serialport|validate_settings', {
+      path,
+      baudRate: settings.baudRate,
+      dataBits: settings.dataBits,
+      flowControl: settings.flowControl,
+      parity: settings.parity,
+      stopBits: settings.stopBits,
+    });
+  }
+
+  /**
+   * @description: Check why a port cannot be opened (permissions, groups, udev, brltty). Linux only.
+   * @param {string} path
+   * @return {Promise<DiagnoseReport>}
+   */
+  static async diagnose(path: string): Promise<DiagnoseReport> {
+    return await invoke<DiagnoseReport>('plugin:serialport|diagnose', {
+      path,
+    });
+  }
+
+  /**
+   * @description: Probe every port that is not open with `*IDN?` and resolve with the identity of each instrument that answered
+   * @param {number[]} baudRates Rates to try in order, by default 9600, 115200, 19200, 38400 and 57600
+   * @param {number} timeoutMs Wait for an answer per rate
+   * @return {Promise<Record<string, string>>}
+   */
+  static async scanInstruments(
+    baudRates?: number[],
+    timeoutMs?: number,
+  ): Promise<Record<string, string>> {
+    return await invoke<Record<string, string>>('plugin:serialport|scan_instruments', {
+      baudRates,
+      timeoutMs,
+    });
+  }
+
+  /**
+   * @description: Force to terminate the serial port
+   * @param {string} path
+   * @return {Promise<void>}
+   */
+  static async forceClose(path: string): Promise<void> {
+    return await invoke<void>('plugin:serialport|force_close', {
+      path,
+    });
+  }
+
+  /**
+   * @description: List the open ports with their event id and metadata
+   * @return {Promise<ManagedPort[]>}
+   */
+  static async managedPorts(): Promise<ManagedPort[]> {
+    return await invoke<ManagedPort[]>('plugin:serialport|managed_ports');
+  }
+
+  /**
+   * @description: List the ports declared to be opened at startup, whether they were opened and under which path
+   * @return {Promise<AutoOpenedPort[]>}
+   */
+  static async autoOpened(): Promise<AutoOpenedPort[]> {
+    return await invoke<AutoOpenedPort[]>('plugin:serialport|auto_opened');
+  }
+
+  /**
+   * @description: Register a listener called when a port declared with `wait` was plugged in and opened, or failed to open
+   * @param {function} fn
+   * @return {Promise<UnlistenFn>}
+   */
+  static async listenAutoOpened(fn: (data: AutoOpenedPort) => void): Promise<UnlistenFn> {
+    return await appWindow.listen<AutoOpenedPort>('plugin-serialport-auto-opened', ({ payload }) =>
+      fn(payload),
+    );
+  }
+
+  /**
+   * @description: Open the port `selector` matches with the settings its device was last opened with, needs `Builder::remember_settings`
+   * @param {PortSelector} selector
+   * @param {object} options Other options of the instance, e.g. `eventId` or `access`
+   * @return {Promise<Serialport>}
+   */
+  static async openWithSavedSettings(
+    selector: PortSelector,
+    options?: Partial<SerialportOptions>,
+  ): Promise<Serialport> {
+    const opened = await invoke<SavedOpen>('plugin:serialport|open_with_saved_settings', {
+      selector,
+      eventId: options?.eventId,
+      access: options?.access,
+    });
+    const serialport = new Serialport({
+      ...options,
+      path: opened.path,
+      baudRate: opened.settings.baud_rate,
+      eventId: opened.event_id,
+    });
+    serialport.isOpen = true;
+    return serialport;
+  }
+
+  /**
+   * @description: Store a connection profile under `name`, replacing one of the same name
+   * @param {string} name
+   * @param {ConnectionProfile} settings
+   * @return {Promise<void>}
+   */
+  static async saveProfile(name: string, settings: ConnectionProfile): Promise<void> {
+    return await invoke<void>('plugin:serialport|save_profile', { name, settings });
+  }
+
+  /**
+   * @description: List the stored connection profiles, sorted by name
+   * @return {Promise<NamedProfile[]>}
+   */
+  static async listProfiles(): Promise<NamedProfile[]> {
+    return await invoke<NamedProfile[]>('plugin:serialport|list_profiles');
+  }
+
+  /**
+   * @description: Remove the profile `name`, resolves to whether it existed
+   * @param {string} name
+   * @return {Promise<boolean>}
+   */
+  static async removeProfile(name: string): Promise<boolean> {
+    return await invoke<boolean>('plugin:serialport|remove_profile', { name });
+  }
+
+  /**
+   * @description: Open the port of the profile `name` with its settings
+   * @param {string} name
+   * @param {object} options Other options of the instance, e.g. `encoding`
+   * @return {Promise<Serialport>}
+   */
+  static async openProfile(name: string, options?: Partial<SerialportOptions>): Promise<Serialport> {
+    const opened = await invoke<ProfileOpen>('plugin:serialport|open_profile', { name });
+    const serialport = new Serialport({
+      ...options,
+      path: opened.path,
+      baudRate: opened.profile.baud_rate,
+      eventId: opened.event_id,
+    });
+    serialport.isOpen = true;
+    return serialport;
+  }
+
+  /**
+   * @description: Export the profiles, ports opened at startup and saved settings as one document
+   * @return {Promise<ConfigDocument>}
+   */
+  static async exportConfig(): Promise<ConfigDocument> {
+    return await invoke<ConfigDocument>('plugin:serialport|export_config');
+  }
+
+  /**
+   * @description: Import an exported document, added to the present configuration or in its place with `replace`
+   * @param {ConfigDocument} document
+   * @param {boolean} replace Drop the present entries first, default false
+   * @return {Promise<ImportReport>}
+   */
+  static async importConfig(document: ConfigDocument, replace?: boolean): Promise<ImportReport> {
+    return await invoke<ImportReport>('plugin:serialport|import_config', { document, replace });
+  }
+
+  /**
+   * @description: An instance for a port opened at startup, to read and write it without opening it again
+   * @param {AutoOpenedPort} port
+   * @param {object} options Other options of the instance, e.g. `encoding`
+   * @return {Serialport}
+   */
+  static attach(port: AutoOpenedPort, options?: Partial<SerialportOptions>): Serialport {
+    if (!port.opened || !port.path) {
+      throw new Error(`Port ${port.selector} is not open: ${port.error}`);
+    }
+    const serialport = new Serialport({
+      ...options,
+      path: port.path,
+      baudRate: port.baud_rate,
+      eventId: port.event_id || undefined,
+    });
+    serialport.isOpen = true;
+    return serialport;
+  }
+
+  /**
+   * @description: Define the scripted device opened as `emu://<name>`, for tests without hardware
+   * @param {string} name
+   * @param {EmulatorScript} script
+   * @return {Promise<void>}
+   */
+  static async defineEmulator(
+    name: string,
+    script: EmulatorScript,
+  ): Promise<void> {
+    return await invoke<void>('plugin:serialport|define_emulator', {
+      name,
+      script,
+    });
+  }
+
+  /**
+   * @description: Remove the script of an emulated device, open ports keep running it
+   * @param {string} name
+   * @return {Promise<void>}
+   */
+  static async removeEmulator(name: string): Promise<void> {
+    return await invoke<void>('plugin:serialport|remove_emulator', { name });
+  }
+
+  /**
+   * @description: Close all serial ports
+   * @return {Promise<void>}
+   */
+  static async closeAll(): Promise<void> {
+    return await invoke<void>('plugin:serialport|close_all');
+  }
+
+  /**
+   * @description: Stop listening to the serial port
+   * @return {Promise<boolean>}
+   */
+  async cancelListen(): Promise<boolean> {
+    try {
+      if (this.unListen) {
+        this.unListen();
+        this.unListen = undefined;
+      }
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to stop listening to the serial port: ' + error);
+    }
+  }
+
+  /**
+   * @description: Cancel read serial port data
+   * @return {Promise<boolean>}
+   */
+  async cancelRead(): Promise<boolean> {
+    try {
+      await invoke<void>('plugin:serialport|cancel_read', {
+        path: this.options.path,
+      });
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description:
+   * @param {object} options
+   * @return {Promise<void>}
+   */
+  async change(options: { path?: string; baudRate?: number }): Promise<void> {
+    try {
+      let isOpened = false;
+      if (this.isOpen) {
+        isOpened = true;
+        await this.close();
+      }
+      if (options.path) {
+        this.options.path = options.path;
+      }
+      if (options.baudRate) {
+        this.options.baudRate = options.baudRate;
+      }
+      if (isOpened) {
+        await this.open();
+      }
+      return Promise.resolve();
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Close serial port
+   * @return {Promise<boolean>}
+   */
+  async close(): Promise<boolean> {
+    try {
+      if (!this.isOpen) {
+        return Promise.resolve(true);
+      }
+      await this.cancelRead();
+      await invoke<void>('plugin:serialport|close', {
+        path: this.options.path,
+      });
+      await this.cancelListen();
+      if (this.unListenError) {
+        this.unListenError();
+        this.unListenError = undefined;
+      }
+      if (this.unListenIdle) {
+        this.unListenIdle();
+        this.unListenIdle = undefined;
+      }
+      if (this.unListenPoll) {
+        this.unListenPoll();
+        this.unListenPoll = undefined;
+      }
+      if (this.unListenMatch) {
+        this.unListenMatch();
+        this.unListenMatch = undefined;
+      }
+      if (this.unListenCapture) {
+        this.unListenCapture();
+        this.unListenCapture = undefined;
+      }
+      if (this.unListenFrame) {
+        this.unListenFrame();
+        this.unListenFrame = undefined;
+      }
+      if (this.unListenPacket) {
+        this.unListenPacket();
+        this.unListenPacket = undefined;
+      }
+      if (this.unListenPacketError) {
+        this.unListenPacketError();
+        this.unListenPacketError = undefined;
+      }
+      if (this.unListenDecoded) {
+        this.unListenDecoded();
+        this.unListenDecoded = undefined;
+      }
+      if (this.unListenDecodeError) {
+        this.unListenDecodeError();
+        this.unListenDecodeError = undefined;
+      }
+      if (this.unListenUrc) {
+        this.unListenUrc();
+        this.unListenUrc = undefined;
+      }
+      if (this.unListenSms) {
+        this.unListenSms();
+        this.unListenSms = undefined;
+      }
+      if (this.unListenSmsReport) {
+        this.unListenSmsReport();
+        this.unListenSmsReport = undefined;
+      }
+      if (this.unListenMidi) {
+        this.unListenMidi();
+        this.unListenMidi = undefined;
+      }
+      if (this.unListenFirmata) {
+        this.unListenFirmata();
+        this.unListenFirmata = undefined;
+      }
+      if (this.unListenUbx) {
+        this.unListenUbx();
+        this.unListenUbx = undefined;
+      }
+      if (this.unListenXBee) {
+        this.unListenXBee();
+        this.unListenXBee = undefined;
+      }
+      if (this.unListenBreak) {
+        this.unListenBreak();
+        this.unListenBreak = undefined;
+      }
+      if (this.unListenDisconnect) {
+        this.unListenDisconnect();
+        this.unListenDisconnect = undefined;
+      }
+      if (this.unListenSuspended) {
+        this.unListenSuspended();
+        this.unListenSuspended = undefined;
+      }
+      if (this.unListenResumed) {
+        this.unListenResumed();
+        this.unListenResumed = undefined;
+      }
+      if (this.unListenDataReady) {
+        this.unListenDataReady();
+        this.unListenDataReady = undefined;
+      }
+      if (this.unListenOverrun) {
+        this.unListenOverrun();
+        this.unListenOverrun = undefined;
+      }
+      if (this.unListenMdb) {
+        this.unListenMdb();
+        this.unListenMdb = undefined;
+      }
+      if (this.unListenAck) {
+        this.unListenAck();
+        this.unListenAck = undefined;
+      }
+      if (this.unListenQueue) {
+        this.unListenQueue();
+        this.unListenQueue = undefined;
+      }
+      if (this.unListenCancelled) {
+        this.unListenCancelled();
+        this.unListenCancelled = undefined;
+      }
+      if (this.unListenProgress) {
+        this.unListenProgress();
+        this.unListenProgress = undefined;
+      }
+      if (this.unListenSocket) {
+        this.unListenSocket();
+        this.unListenSocket = undefined;
+      }
+      if (this.unListenSocketClosed) {
+        this.unListenSocketClosed();
+        this.unListenSocketClosed = undefined;
+      }
+      if (this.unListenLora) {
+        this.unListenLora();
+        this.unListenLora = undefined;
+      }
+      if (this.unListenBufferOverflow) {
+        this.unListenBufferOverflow();
+        this.unListenBufferOverflow = undefined;
+      }
+      if (this.onVisibilityChange) {
+        document.removeEventListener('visibilitychange', this.onVisibilityChange);
+        this.onVisibilityChange = undefined;
+      }
+      this.isOpen = false;
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener to receive data read from the serial port, with `true` as second argument for data echoed by `setLocalEcho`
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listen(fn: (...args: any[]) => void | Promise<void>, isDecode = true): Promise<boolean> {
+    try {
+      await this.cancelListen();
+      let readEvent = 'plugin-serialport-read-' + this.eventId;
+      this.unListen = await appWindow.listen<ReadDataResult>(
+        readEvent,
+        async ({ payload }) => {
+          try {
+            if (isDecode) {
+              const decoder = new TextDecoder(this.encoding);
+              const data = decoder.decode(new Uint8Array(payload.data));
+              await fn(
+                data,
+                payload.outgoing,
+                payload.timestamp,
+                payload.monotonic_us,
+                payload.sequence,
+              );
+            } else {
+              await fn(
+                new Uint8Array(payload.data),
+                payload.outgoing,
+                payload.timestamp,
+                payload.monotonic_us,
+                payload.sequence,
+              );
+            }
+          } catch (error) {
+            console.error(error);
+          }
+          if (this.backpressure) {
+            await invoke<void>('plugin:serialport|ack_read', {
+              path: this.options.path,
+              sequence: payload.sequence,
+            }).catch((error) => console.error(error));
+          }
+        },
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port: ' + error);
+    }
+  }
+
+  /**
+   * @description: Take the data collected while reading in `Buffer` mode
+   * @param {number} maxBytes
+   * @return {Promise<FetchDataResult>}
+   */
+  async fetch(maxBytes?: number): Promise<FetchDataResult> {
+    try {
+      return await invoke<FetchDataResult>('plugin:serialport|fetch', {
+        path: this.options.path,
+        maxBytes,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Read what the driver holds right now without waiting, for ports read in `Notify` mode
+   * @param {number} maxBytes
+   * @return {Promise<FetchDataResult>}
+   */
+  async readOnce(maxBytes?: number): Promise<FetchDataResult> {
+    try {
+      return await invoke<FetchDataResult>('plugin:serialport|read_once', {
+        path: this.options.path,
+        maxBytes,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener called in `Notify` mode when data starts waiting to be read
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenDataReady(fn: (data: DataReadyData) => void): Promise<boolean> {
+    try {
+      if (this.unListenDataReady) {
+        this.unListenDataReady();
+      }
+      this.unListenDataReady = await appWindow.listen<DataReadyData>(
+        'plugin-serialport-ready-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port data ready: ' + error);
+    }
+  }
+
+  /**
+   * @description: Switch the FTDI adapter behind this port to a bitbang or MPSSE mode (needs the `ftdi` feature)
+   * @param {string} mode AsyncBitbang | SyncBitbang | CbusBitbang | Mpsse, Reset returns to UART mode
+   * @param {number} mask pin directions, 1 = output
+   * @param {number} iface interface number, 0 for interface A
+   * @return {Promise<void>}
+   */
+  async ftdiSetBitmode(
+    mode: 'Reset' | 'AsyncBitbang' | 'SyncBitbang' | 'CbusBitbang' | 'Mpsse',
+    mask?: number,
+    iface?: number,
+  ): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|ftdi_set_bitmode', {
+        path: this.options.path,
+        mode,
+        mask,
+        interface: iface,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Read the pin states of an FTDI adapter in bitbang or MPSSE mode
+   * @return {Promise<number>}
+   */
+  async ftdiReadPins(): Promise<number> {
+    try {
+      return await invoke<number>('plugin:serialport|ftdi_read_pins', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Set the output pins of an FTDI adapter in bitbang mode
+   * @param {number} value
+   * @return {Promise<void>}
+   */
+  async ftdiWritePins(value: number): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|ftdi_write_pins', {
+        path: this.options.path,
+        value,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Send MPSSE command bytes and read the answers
+   * @param {Uint8Array | number[]} data
+   * @param {number} readSize number of answer bytes to wait for
+   * @param {number} timeout milliseconds
+   * @return {Promise<number[]>}
+   */
+  async ftdiMpsse(data: Uint8Array | number[], readSize?: number, timeout?: number): Promise<number[]> {
+    try {
+      return await invoke<number[]>('plugin:serialport|ftdi_mpsse', {
+        path: this.options.path,
+        data: Array.from(data),
+        readSize,
+        timeout,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Get the received data recorded between `since` and `until`
+   * @param {number} since Milliseconds since the Unix epoch
+   * @param {number} until Milliseconds since the Unix epoch
+   * @param {number} limit Maximum number of (most recent) frames
+   * @return {Promise<HistoryFrame[]>}
+   */
+  async getHistory(since?: number, until?: number, limit?: number): Promise<HistoryFrame[]> {
+    try {
+      return await invoke<HistoryFrame[]>('plugin:serialport|get_history', {
+        path: this.options.path,
+        since,
+        until,
+        limit,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for errors of the background read
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenError(fn: (error: ReadError) => void): Promise<boolean> {
+    try {
+      if (this.unListenError) {
+        this.unListenError();
+      }
+      this.unListenError = await appWindow.listen<ReadError>(
+        'plugin-serialport-error-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port errors: ' + error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for idle events of the watchdog, see `setIdleTimeout`
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenIdle(fn: (data: IdleData) => void): Promise<boolean> {
+    try {
+      if (this.unListenIdle) {
+        this.unListenIdle();
+      }
+      this.unListenIdle = await appWindow.listen<IdleData>(
+        'plugin-serialport-idle-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port idle events: ' + error);
+    }
+  }
+
+  /**
+   * @description: Write `request` every `intervalMs` from the backend and emit the answers to
+   * `listenPoll`. An answer ends with `terminator`, after `responseLength` bytes or when
+   * `responseTimeoutMs` (default `intervalMs`) passed. Answers are taken from the background
+   * read, so `read` has to be running.
+   * @param {string} id Name of the poll, reusing it replaces the poll
+   * @param {Uint8Array | number[]} request
+   * @param {number} intervalMs
+   * @return {Promise<void>}
+   */
+  async addPoll(
+    id: string,
+    request: Uint8Array | number[],
+    intervalMs: number,
+    options?: { terminator?: Uint8Array | number[]; responseLength?: number; responseTimeoutMs?: number },
+  ): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|add_poll', {
+        path: this.options.path,
+        id,
+        request: Array.from(request),
+        intervalMs,
+        terminator: options?.terminator ? Array.from(options.terminator) : undefined,
+        responseLength: options?.responseLength,
+        responseTimeoutMs: options?.responseTimeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Stop a poll
+   * @param {string} id
+   * @return {Promise<void>}
+   */
+  async removePoll(id: string): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|remove_poll', {
+        path: this.options.path,
+        id,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for the answers to polls, see `addPoll`
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenPoll(fn: (data: PollData) => void): Promise<boolean> {
+    try {
+      if (this.unListenPoll) {
+        this.unListenPoll();
+      }
+      this.unListenPoll = await appWindow.listen<PollData>(
+        'plugin-serialport-poll-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port polls: ' + error);
+    }
+  }
+
+  /**
+   * @description: Emit a match event whenever the received data contains `pattern`. A string is
+   * used as a regex over the raw bytes, bytes are matched literally. Matches may span several
+   * reads; they are found by the background read, so `read` has to be running.
+   * @param {string} id Name of the watcher, reusing it replaces the watcher
+   * @param {string | Uint8Array | number[]} pattern
+   * @param {number} context Bytes sent before and after the match, default 32
+   * @return {Promise<void>}
+   */
+  async watchPattern(id: string, pattern: string | Uint8Array | number[], context?: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|watch_pattern', {
+        path: this.options.path,
+        id,
+        pattern: typeof pattern === 'string' ? undefined : Array.from(pattern),
+        regex: typeof pattern === 'string' ? pattern : undefined,
+        context,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Remove a pattern watcher
+   * @param {string} id
+   * @return {Promise<void>}
+   */
+  async unwatchPattern(id: string): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|unwatch_pattern', {
+        path: this.options.path,
+        id,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for pattern matches, see `watchPattern`
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenMatch(fn: (data: MatchData) => void): Promise<boolean> {
+    try {
+      if (this.unListenMatch) {
+        this.unListenMatch();
+      }
+      this.unListenMatch = await appWindow.listen<MatchData>(
+        'plugin-serialport-match-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port matches: ' + error);
+    }
+  }
+
+  /**
+   * @description: Arm a triggered capture. Nothing is recorded until `start` arrives, then the
+   * data is recorded until `stop` or `maxBytes` and sent to `listenCapture`, or written to `file`.
+   * Strings are used as regexes over the raw bytes, bytes are matched literally. Needs `read`.
+   * @param {string | Uint8Array | number[]} start
+   * @param {object} options `stop`, `maxBytes` (default 16 MiB) and `file`
+   * @return {Promise<void>}
+   */
+  async startCapture(
+    start: string | Uint8Array | number[],
+    options: { stop?: string | Uint8Array | number[]; maxBytes?: number; file?: string } = {},
+  ): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      const { stop, maxBytes, file } = options;
+      await invoke<void>('plugin:serialport|start_capture', {
+        path: this.options.path,
+        startPattern: typeof start === 'string' ? undefined : Array.from(start),
+        startRegex: typeof start === 'string' ? start : undefined,
+        stopPattern: stop === undefined || typeof stop === 'string' ? undefined : Array.from(stop),
+        stopRegex: typeof stop === 'string' ? stop : undefined,
+        maxBytes,
+        file,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Disarm the capture, data recorded so far is sent with reason `Cancelled`
+   * @return {Promise<void>}
+   */
+  async stopCapture(): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|stop_capture', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for capture events, see `startCapture`
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenCapture(fn: (data: CaptureData) => void): Promise<boolean> {
+    try {
+      if (this.unListenCapture) {
+        this.unListenCapture();
+      }
+      this.unListenCapture = await appWindow.listen<CaptureData>(
+        'plugin-serialport-capture-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port captures: ' + error);
+    }
+  }
+
+  /**
+   * @description: Enable the monitor stream and register a listener receiving both written and read data
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async monitor(fn: (data: MonitorData) => void): Promise<boolean> {
+    try {
+      await this.stopMonitor();
+      this.unListenMonitor = await appWindow.listen<MonitorData>(
+        'plugin-serialport-monitor-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      await invoke<void>('plugin:serialport|set_monitor', {
+        path: this.options.path,
+        enabled: true,
+      });
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to monitor the serial port: ' + error);
+    }
+  }
+
+  /**
+   * @description: Disable the monitor stream
+   * @return {Promise<boolean>}
+   */
+  async stopMonitor(): Promise<boolean> {
+    try {
+      if (this.unListenMonitor) {
+        this.unListenMonitor();
+        this.unListenMonitor = undefined;
+        if (this.isOpen) {
+          await invoke<void>('plugin:serialport|set_monitor', {
+            path: this.options.path,
+            enabled: false,
+          });
+        }
+      }
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Also emit every successful `write` and `writeBinary` to the `listen` listener, tagged as outgoing
+   * @param {boolean} enabled
+   * @return {Promise<void>}
+   */
+  async setLocalEcho(enabled: boolean): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_local_echo', {
+        path: this.options.path,
+        enabled,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Attach any JSON value to the open port, e.g. device role or firmware version, returned by `managedPorts`
+   * @param {any} value
+   * @return {Promise<void>}
+   */
+  async setMetadata(value: any): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_metadata', {
+        path: this.options.path,
+        value,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Let at most `maxInFlight` read events wait for the `listen` listener and buffer further data in Rust meanwhile, 0 disables it
+   * @param {number} maxInFlight Events emitted before earlier ones are acknowledged
+   * @param {number} maxBufferedBytes 1 MiB by default, beyond it data is dropped
+   * @return {Promise<void>}
+   */
+  async setBackpressure(maxInFlight: number, maxBufferedBytes?: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      this.backpressure = maxInFlight > 0;
+      await invoke<void>('plugin:serialport|set_backpressure', {
+        path: this.options.path,
+        maxInFlight,
+        maxBufferedBytes,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Pause or resume the read events of a `whenHidden` read, for windows hidden other than by minimizing
+   * @param {boolean} hidden
+   * @return {Promise<void>}
+   */
+  async setHidden(hidden: boolean): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_hidden', {
+        path: this.options.path,
+        hidden,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Listen to read data dropped because the listener fell behind by more than the backpressure buffer
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenBufferOverflow(fn: (data: BufferOverflowData) => void): Promise<boolean> {
+    try {
+      if (this.unListenBufferOverflow) {
+        this.unListenBufferOverflow();
+      }
+      this.unListenBufferOverflow = await appWindow.listen<BufferOverflowData>(
+        'plugin-serialport-buffer-overflow-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port buffer overflow: ' + error);
+    }
+  }
+
+  /**
+   * @description: Open serial port
+   * @return {Promise<boolean>}
+   */
+  async open(): Promise<boolean> {
+    try {
+      if (!this.options.path) {
+        return Promise.reject(`path cannot be empty!`);
+      }
+      if (!this.options.baudRate) {
+        return Promise.reject(`baudRate cannot be empty!`);
+      }
+      if (this.isOpen) {
+        return Promise.resolve(true);
+      }
+      this.eventId = await invoke<string>('plugin:serialport|open', {
+        path: this.options.path,
+        baudRate: this.options.baudRate,
+        dataBits: this.options.dataBits,
+        flowControl: this.options.flowControl,
+        parity: this.options.parity,
+        stopBits: this.options.stopBits,
+        timeout: this.options.timeout,
+        rs485: this.options.rs485,
+        eventId: this.options.eventId,
+        access: this.options.access,
+      });
+      this.isOpen = true;
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+
+  /**
+   * @description: Open serial port, retrying failures that are common right after the device
+   * was plugged in (not enumerated yet, permissions not applied yet, briefly busy)
+   * @param {number} attempts Number of attempts, default 5
+   * @param {number} backoffMs Delay before the second attempt, doubled after every failure, default 200
+   * @return {Promise<boolean>}
+   */
+  async openWithRetry(options?: { attempts?: number; backoffMs?: number }): Promise<boolean> {
+    try {
+      if (!this.options.path) {
+        return Promise.reject(`path cannot be empty!`);
+      }
+      if (!this.options.baudRate) {
+        return Promise.reject(`baudRate cannot be empty!`);
+      }
+      if (this.isOpen) {
+        return Promise.resolve(true);
+      }
+      this.eventId = await invoke<string>('plugin:serialport|open_with_retry', {
+        path: this.options.path,
+        baudRate: this.options.baudRate,
+        dataBits: this.options.dataBits,
+        flowControl: this.options.flowControl,
+        parity: this.options.parity,
+        stopBits: this.options.stopBits,
+        timeout: this.options.timeout,
+        rs485: this.options.rs485,
+        attempts: options?.attempts,
+        backoffMs: options?.backoffMs,
+        eventId: this.options.eventId,
+        access: this.options.access,
+      });
+      this.isOpen = true;
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Tell the backend to start reading the serial port data.
+   * The backend will read the data and send it to the front end through the listen method.
+   * Small reads are merged into one event when `maxBatchBytes` or `maxBatchLatencyMs` is set,
+   * and `maxEventRate` limits the events per second according to `throttlePolicy`.
+   * In `Buffer` mode no events are sent, the data is collected for `fetch` instead.
+   * @param {ReadOptions} options Read options
+   * @return {Promise<boolean>}
+   */
+  async read(options?: ReadOptions): Promise<boolean> {
+    try {
+      this.eventId = await invoke<string>('plugin:serialport|read', {
+        path: this.options.path,
+        timeout: options?.timeout || this.options.timeout,
+        size: options?.size || this.size,
+        priority: options?.priority,
+        maxBatchBytes: options?.maxBatchBytes,
+        maxBatchLatencyMs: options?.maxBatchLatencyMs,
+        maxEventRate: options?.maxEventRate,
+        throttlePolicy: options?.throttlePolicy,
+        mode: options?.mode,
+        bufferSize: options?.bufferSize,
+        transforms: options?.transforms,
+        byteMap: options?.byteMap,
+        codec: options?.codec,
+        decode: options?.decode,
+        whenHidden: options?.whenHidden,
+      });
+      if (options?.whenHidden && options.whenHidden !== 'Emit' && !this.onVisibilityChange) {
+        this.onVisibilityChange = () => {
+          invoke<void>('plugin:serialport|set_hidden', {
+            path: this.options.path,
+            hidden: document.visibilityState === 'hidden',
+          }).catch((error) => console.error(error));
+        };
+        document.addEventListener('visibilitychange', this.onVisibilityChange);
+      }
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Set the serial port baud rate
+   * @param {number} value
+   * @return {Promise<void>}
+   */
+  async setBaudRate(value: number): Promise<void> {
+    try {
+      let isOpened = false;
+      if (this.isOpen) {
+        isOpened = true;
+        await this.close();
+      }
+      this.options.baudRate = value;
+      if (isOpened) {
+        await this.open();
+      }
+      return Promise.resolve();
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Read the received stream at an own cursor and with own framing, next to other consumers
+   * @param {string} name
+   * @param {object} options `fromOldest` starts at the oldest byte kept, `bufferSize` is the stream kept for all consumers (64 KiB)
+   * @return {Promise<void>}
+   */
+  async addConsumer(
+    name: string,
+    options: {
+      framing?: 'Raw' | 'Lines' | 'Codec';
+      codec?: string;
+      fromOldest?: boolean;
+      bufferSize?: number;
+    } = {},
+  ): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      const { framing, codec, fromOldest, bufferSize } = options;
+      await invoke<void>('plugin:serialport|add_consumer', {
+        path: this.options.path,
+        name,
+        framing,
+        codec,
+        fromOldest,
+        bufferSize,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Take the frames a consumer has not read yet
+   * @param {string} name
+   * @param {number} maxBytes All available by default
+   * @return {Promise<Consumed>}
+   */
+  async consume(name: string, maxBytes?: number): Promise<Consumed> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<Consumed>('plugin:serialport|consume', {
+        path: this.options.path,
+        name,
+        maxBytes,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Remove a consumer
+   * @param {string} name
+   * @return {Promise<boolean>} whether it existed
+   */
+  async removeConsumer(name: string): Promise<boolean> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<boolean>('plugin:serialport|remove_consumer', {
+        path: this.options.path,
+        name,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Names of the consumers of the port
+   * @return {Promise<string[]>}
+   */
+  async listConsumers(): Promise<string[]> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<string[]>('plugin:serialport|list_consumers', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Keep the last `maxFrames` received chunks for `getHistory`, 0 disables it
+   * @param {number} maxFrames
+   * @return {Promise<void>}
+   */
+  async setHistory(maxFrames: number): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|set_history', {
+        path: this.options.path,
+        maxFrames,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Emit an idle event when nothing was read or written for `seconds`, 0 turns
+   * the watchdog off. `Close` also closes the port, `Reopen` closes and opens it again with the
+   * same settings (reading has to be started again).
+   * @param {number} seconds
+   * @param {string} action
+   * @return {Promise<void>}
+   */
+  async setIdleTimeout(seconds: number, action?: 'Notify' | 'Close' | 'Reopen'): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_idle_timeout', {
+        path: this.options.path,
+        seconds,
+        action,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Write `bytes` every `intervalMs` from the backend, the timing does not depend
+   * on webview timers, which are throttled while the window is hidden
+   * @param {Uint8Array | number[]} bytes Keep-alive frame
+   * @param {number} intervalMs
+   * @return {Promise<void>}
+   */
+  async startHeartbeat(bytes: Uint8Array | number[], intervalMs: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|start_heartbeat', {
+        path: this.options.path,
+        bytes: Array.from(bytes),
+        intervalMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Stop the heartbeat
+   * @return {Promise<void>}
+   */
+  async stopHeartbeat(): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|stop_heartbeat', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Switch the port to DMX512 (250 kbaud 8N2) and send the channel frame continuously from a backend thread
+   * @param {number} refreshHz Packets per second, at most 44
+   * @return {Promise<void>}
+   */
+  async startDmx(refreshHz?: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|start_dmx', {
+        path: this.options.path,
+        refreshHz,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Stop the DMX output
+   * @return {Promise<void>}
+   */
+  async stopDmx(): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|stop_dmx', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Set DMX channels, the next packet carries them
+   * @param {Uint8Array | number[]} values
+   * @param {number} start First channel, 1 to 512
+   * @return {Promise<void>}
+   */
+  async setChannels(values: Uint8Array | number[], start?: number): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|set_channels', {
+        path: this.options.path,
+        start,
+        values: Array.from(values),
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Set all DMX channels to 0
+   * @return {Promise<void>}
+   */
+  async blackout(): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|blackout', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Listen for Modbus TCP clients and forward their requests as RTU transactions on this port
+   * @param {string} address `127.0.0.1:502` by default
+   * @param {number} timeoutMs Wait for the answer of the serial device, then reply with exception 0x0B
+   * @param {boolean} allowRemote Needed for addresses other than loopback, the gateway has no authentication
+   * @return {Promise<void>}
+   */
+  async startGateway(address?: string, timeoutMs?: number, allowRemote?: boolean): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|start_gateway', {
+        path: this.options.path,
+        address,
+        timeoutMs,
+        allowRemote,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Stop the Modbus TCP gateway
+   * @return {Promise<void>}
+   */
+  async stopGateway(): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|stop_gateway', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Split this port into channels of the channel-id framing, each opened as its
+   * own port with the path `mux://<path>:<channel>`
+   * @param {number} unframed Channel for the bytes outside frames, dropped without one
+   * @return {Promise<void>}
+   */
+  async startMux(unframed?: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|start_mux', {
+        path: this.options.path,
+        unframed,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Switch a cellular modem on this port to the 3GPP TS 27.010 multiplexer (CMUX)
+   * and open its DLCs, each opened as its own port with the path `mux://<path>:<dlci>`
+   * @param {number[]} channels DLCIs to establish, e.g. `[1, 2, 3]` for AT commands, PPP and GNSS
+   * @param {string} command Command switching the modem, `AT+CMUX=0` by default, `''` when it already is
+   * @param {number} frameSize Maximum frame size N1 the command set, 31 by default
+   * @return {Promise<number[]>} The DLCIs the modem accepted
+   */
+  async startCmux(channels: number[], command?: string, frameSize?: number): Promise<number[]> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number[]>('plugin:serialport|start_cmux', {
+        path: this.options.path,
+        channels,
+        command,
+        frameSize,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Stop splitting this port, its channels fail from then on. A CMUX multiplexer is
+   * closed on the modem as well, which returns it to AT commands
+   * @return {Promise<void>}
+   */
+  async stopMux(): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|stop_mux', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Read an IEC 62056-21 meter through an optical probe, the port settings are restored afterwards
+   * @param {string} address Device address for buses with several meters
+   * @param {number} timeoutMs Whole readout, 20 s by default
+   * @return {Promise<MeterReading>}
+   */
+  async readMeter(address?: string, timeoutMs?: number): Promise<MeterReading> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<MeterReading>('plugin:serialport|read_meter', {
+        path: this.options.path,
+        address,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Send an MDB command with its data; the port is switched to 9600 8E1
+   * @param {number} command Peripheral address plus subcommand, e.g. 0x0B POLL of the coin changer
+   * @param {Uint8Array | number[]} data
+   * @param {number} timeoutMs Wait for the answer, 50 ms by default
+   * @return {Promise<MdbResponse>}
+   */
+  async mdbCommand(
+    command: number,
+    data?: Uint8Array | number[],
+    timeoutMs?: number,
+  ): Promise<MdbResponse> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<MdbResponse>('plugin:serialport|mdb_command', {
+        path: this.options.path,
+        command,
+        data: data ? Array.from(data) : undefined,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Poll MDB peripherals and emit their events, see `listenMdb`
+   * @param {number[]} addresses By default the coin changer (0x08) and the bill validator (0x30)
+   * @param {number} intervalMs 200 ms by default
+   * @param {number} timeoutMs
+   * @return {Promise<void>}
+   */
+  async startMdbPoll(addresses?: number[], intervalMs?: number, timeoutMs?: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|start_mdb_poll', {
+        path: this.options.path,
+        addresses,
+        intervalMs,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Stop polling the MDB peripherals
+   * @return {Promise<void>}
+   */
+  async stopMdbPoll(): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|stop_mdb_poll', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for the events of polled MDB peripherals
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenMdb(fn: (data: MdbData) => void): Promise<boolean> {
+    try {
+      if (this.unListenMdb) {
+        this.unListenMdb();
+      }
+      this.unListenMdb = await appWindow.listen<MdbData>(
+        'plugin-serialport-mdb-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port MDB events: ' + error);
+    }
+  }
+
+  /**
+   * @description: Drive the RS-485 transceiver with RTS: asserted before each write, released
+   * once its last byte has left the UART
+   * @param {boolean} enabled
+   * @return {Promise<void>}
+   */
+  async setRs485(
+    enabled: boolean,
+    options?: { preDelayUs?: number; postDelayUs?: number; invert?: boolean },
+  ): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_rs485', {
+        path: this.options.path,
+        enabled,
+        preDelayUs: options?.preDelayUs,
+        postDelayUs: options?.postDelayUs,
+        invert: options?.invert,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Let transmissions and receptions take turns on a half-duplex bus: writes wait
+   * for a quiet bus and the echo of a write received until `turnaroundMs` after it is discarded
+   * @param {boolean} enabled
+   * @param {number} turnaroundMs 5 ms by default
+   * @return {Promise<void>}
+   */
+  async setHalfDuplex(enabled: boolean, turnaroundMs?: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_half_duplex', {
+        path: this.options.path,
+        enabled,
+        turnaroundMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Encrypt all data of the port as AES-GCM frames with a pre-shared key, for
+   * firmware that implements the same framing
+   * @param {string | null} key 32, 48 or 64 hex digits; `null` sends and reads plain data again
+   * @return {Promise<void>}
+   */
+  async setEncryption(key: string | null): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_encryption', {
+        path: this.options.path,
+        key,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Frames the encrypted link sent, received and rejected
+   * @return {Promise<EncryptionStats | null>} `null` while the link is not encrypted
+   */
+  async encryptionStats(): Promise<EncryptionStats | null> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<EncryptionStats | null>('plugin:serialport|encryption_stats', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Send every write as compressed frames and decompress the frames received, for
+   * firmware with the same codec
+   * @param {'Zlib' | 'Lz4' | null} algorithm `null` sends and reads plain data again
+   * @param {number} level zlib level from 0 to 9, 6 by default
+   * @return {Promise<void>}
+   */
+  async setCompression(algorithm: 'Zlib' | 'Lz4' | null, level?: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_compression', {
+        path: this.options.path,
+        algorithm,
+        level,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Bytes before and after compression in both directions
+   * @return {Promise<CompressionStats | null>} `null` while the port is not compressed
+   */
+  async compressionStats(): Promise<CompressionStats | null> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<CompressionStats | null>('plugin:serialport|compression_stats', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Deliver only the received frames addressed to this station
+   * @param {AddressFilter | null} filter `null` delivers all frames again
+   * @return {Promise<void>}
+   */
+  async setAddressFilter(filter: AddressFilter | null): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_address_filter', {
+        path: this.options.path,
+        filter,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Report received breaks as break events and remove them from the data
+   * @param {boolean} enabled
+   * @return {Promise<void>}
+   */
+  async setBreakDetection(enabled: boolean): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_break_detection', {
+        path: this.options.path,
+        enabled,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Put a marker before every byte received with a parity or framing error. Native Unix ports only
+   * @param {Uint8Array | number[] | null} marker `null` delivers the bytes unmarked again
+   * @return {Promise<void>}
+   */
+  async setErrorMarkers(marker: Uint8Array | number[] | null): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_error_markers', {
+        path: this.options.path,
+        marker: marker ? Array.from(marker) : null,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Get the parity errors, framing errors, breaks and overruns of the port since it was opened
+   * @return {Promise<LineErrors>}
+   */
+  async lineErrors(): Promise<LineErrors> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<LineErrors>('plugin:serialport|line_errors', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for the breaks received while break detection is enabled
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenBreak(fn: (data: BreakData) => void): Promise<boolean> {
+    try {
+      if (this.unListenBreak) {
+        this.unListenBreak();
+      }
+      this.unListenBreak = await appWindow.listen<BreakData>(
+        'plugin-serialport-break-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port breaks: ' + error);
+    }
+  }
+
+  /**
+   * @description: Register a listener called when the device goes away while reading, which ends the read
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenDisconnect(fn: (data: DisconnectData) => void): Promise<boolean> {
+    try {
+      if (this.unListenDisconnect) {
+        this.unListenDisconnect();
+      }
+      this.unListenDisconnect = await appWindow.listen<DisconnectData>(
+        'plugin-serialport-disconnect-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port disconnects: ' + error);
+    }
+  }
+
+  /**
+   * @description: Register a listener called when the system resumes from a sleep, with the time it was suspended
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenSuspended(fn: (data: SuspendedData) => void): Promise<boolean> {
+    try {
+      if (this.unListenSuspended) {
+        this.unListenSuspended();
+      }
+      this.unListenSuspended = await appWindow.listen<SuspendedData>(
+        'plugin-serialport-suspended-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port suspensions: ' + error);
+    }
+  }
+
+  /**
+   * @description: Register a listener called once the port was checked, and reopened if dropped, after a system sleep
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenResumed(fn: (data: ResumedData) => void): Promise<boolean> {
+    try {
+      if (this.unListenResumed) {
+        this.unListenResumed();
+      }
+      this.unListenResumed = await appWindow.listen<ResumedData>(
+        'plugin-serialport-resumed-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port resumes: ' + error);
+    }
+  }
+
+  /**
+   * @description: Register a listener called while reading when received data starts getting lost to overruns
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenOverrun(fn: (data: OverrunData) => void): Promise<boolean> {
+    try {
+      if (this.unListenOverrun) {
+        this.unListenOverrun();
+      }
+      this.unListenOverrun = await appWindow.listen<OverrunData>(
+        'plugin-serialport-overrun-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port overruns: ' + error);
+    }
+  }
+
+  /**
+   * @description: Log hex dumps of the port traffic to the Rust `tracing` subscriber
+   * @param {string} level
+   * @return {Promise<void>}
+   */
+  async setLogLevel(level: 'Off' | 'Error' | 'Warn' | 'Info' | 'Debug' | 'Trace'): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|set_log_level', {
+        path: this.options.path,
+        level,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Set the serial port path
+   * @param {string} value
+   * @return {Promise<void>}
+   */
+  async setPath(value: string): Promise<void> {
+    try {
+      let isOpened = false;
+      if (this.isOpen) {
+        isOpened = true;
+        await this.close();
+      }
+      this.options.path = value;
+      if (isOpened) {
+        await this.open();
+      }
+      return Promise.resolve();
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Write data to the serial port
+   * @param {string} value
+   * @param {WriteOptions} options `drain` waits until the data left the port
+   * @return {Promise<number>}
+   */
+  async write(value: string, options: WriteOptions = {}): Promise<number> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number>('plugin:serialport|write', {
+        value,
+        path: this.options.path,
+        drain: options.drain,
+        drainTimeoutMs: options.drainTimeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Write binary data to the serial port
+   * @param {Uint8Array} value
+   * @param {WriteOptions} options `drain` waits until the data left the port
+   * @return {Promise<number>}
+   */
+  async writeBinary(value: Uint8Array | number[], options: WriteOptions = {}): Promise<number> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      if (value instanceof Uint8Array || value instanceof Array) {
+        return await invoke<number>('plugin:serialport|write_binary', {
+          value: Array.from(value),
+          path: this.options.path,
+          drain: options.drain,
+          drainTimeoutMs: options.drainTimeoutMs,
+        });
+      } else {
+        return Promise.reject(
+          'value Parameter must be of type string, Uint8Array, or number[]!',
+        );
+      }
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Write a frame until the acknowledgement arrives, retrying on a timeout or `nak`
+   * @param {string} id Passed back in the result and the ack event
+   * @param {Uint8Array | number[]} value
+   * @param {string | Uint8Array | number[]} ack Bytes, or a regex as string
+   * @param {object} options `timeoutMs` 1000 and `retries` 3 by default
+   * @return {Promise<AckData>}
+   */
+  async writeReliable(
+    id: string,
+    value: Uint8Array | number[],
+    ack: string | Uint8Array | number[],
+    options: { nak?: Uint8Array | number[]; timeoutMs?: number; retries?: number } = {},
+  ): Promise<AckData> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      const { nak, timeoutMs, retries } = options;
+      return await invoke<AckData>('plugin:serialport|write_reliable', {
+        path: this.options.path,
+        id,
+        value: Array.from(value),
+        ack: typeof ack === 'string' ? undefined : Array.from(ack),
+        ackRegex: typeof ack === 'string' ? ack : undefined,
+        nak: nak === undefined ? undefined : Array.from(nak),
+        timeoutMs,
+        retries,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for the outcome of every reliable write
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenAck(fn: (data: AckData) => void): Promise<boolean> {
+    try {
+      if (this.unListenAck) {
+        this.unListenAck();
+      }
+      this.unListenAck = await appWindow.listen<AckData>(
+        'plugin-serialport-ack-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port acknowledgements: ' + error);
+    }
+  }
+
+  /**
+   * @description: Queue a write; urgent entries go before normal ones, bulk data is written in
+   * chunks between them
+   * @param {string} id Passed back in the queue event once the entry is written
+   * @param {Uint8Array | number[]} value
+   * @param {string} priority `Normal` by default
+   * @return {Promise<QueueStatus>}
+   */
+  async enqueue(
+    id: string,
+    value: Uint8Array | number[],
+    priority?: 'Urgent' | 'Normal' | 'Bulk',
+  ): Promise<QueueStatus> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<QueueStatus>('plugin:serialport|enqueue', {
+        path: this.options.path,
+        id,
+        value: Array.from(value),
+        priority,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Entries and bytes waiting in the transmit queue
+   * @return {Promise<QueueStatus>}
+   */
+  async queueStatus(): Promise<QueueStatus> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<QueueStatus>('plugin:serialport|queue_status', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for the queued writes that were written or failed
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenQueue(fn: (data: QueueData) => void): Promise<boolean> {
+    try {
+      if (this.unListenQueue) {
+        this.unListenQueue();
+      }
+      this.unListenQueue = await appWindow.listen<QueueData>(
+        'plugin-serialport-queue-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port queue: ' + error);
+    }
+  }
+
+  /**
+   * @description: Stop a running operation, e.g. a queued or reliable write, by its id
+   * @param {string} id
+   * @return {Promise<void>}
+   */
+  async cancelOperation(id: string): Promise<void> {
+    try {
+      await invoke<void>('plugin:serialport|cancel_operation', { id });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for the operations of the port stopped by `cancelOperation`
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenCancelled(fn: (data: CancelledData) => void): Promise<boolean> {
+    try {
+      if (this.unListenCancelled) {
+        this.unListenCancelled();
+      }
+      this.unListenCancelled = await appWindow.listen<CancelledData>(
+        'plugin-serialport-cancelled-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port cancellations: ' + error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for the progress of the long-running operations of the port
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenProgress(fn: (data: ProgressData) => void): Promise<boolean> {
+    try {
+      if (this.unListenProgress) {
+        this.unListenProgress();
+      }
+      this.unListenProgress = await appWindow.listen<ProgressData>(
+        'plugin-serialport-progress-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port progress: ' + error);
+    }
+  }
+
+  /**
+   * @description: Stream a file from disk to the port in Rust, with progress events
+   * @param {string} id Operation id for `cancelOperation` and the progress events
+   * @param {string} filePath
+   * @param {object} options `chunkSize` 1024 bytes and `delayMs` 0 between chunks by default
+   * @return {Promise<number>} bytes of the file that were sent
+   */
+  async sendFile(
+    id: string,
+    filePath: string,
+    options: {
+      chunkSize?: number;
+      delayMs?: number;
+      framing?: 'Raw' | 'Lines' | 'Codec';
+      codec?: string;
+    } = {},
+  ): Promise<number> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      const { chunkSize, delayMs, framing, codec } = options;
+      return await invoke<number>('plugin:serialport|send_file', {
+        path: this.options.path,
+        id,
+        filePath,
+        chunkSize,
+        delayMs,
+        framing,
+        codec,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Write the received stream straight to a file, the port must not be reading
+   * @param {string} id Operation id for `cancelOperation` and the progress events
+   * @param {string} filePath
+   * @param {object} until At least one of `bytes`, `pattern` or `idleMs`, whichever comes first
+   * @return {Promise<number>} bytes written to the file
+   */
+  async receiveToFile(
+    id: string,
+    filePath: string,
+    until: { bytes?: number; pattern?: Uint8Array | number[]; idleMs?: number },
+  ): Promise<number> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number>('plugin:serialport|receive_to_file', {
+        path: this.options.path,
+        id,
+        filePath,
+        untilBytes: until.bytes,
+        untilPattern: until.pattern === undefined ? undefined : Array.from(until.pattern),
+        untilIdleMs: until.idleMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Record all traffic of the port to a pcapng file for Wireshark
+   * @param {string} file
+   * @param {number} linkType `LINKTYPE_USER0` (147) by default
+   * @return {Promise<void>}
+   */
+  async startPcap(file: string, linkType?: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|start_pcap', {
+        path: this.options.path,
+        file,
+        linkType,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: End the pcapng capture
+   * @return {Promise<number>} number of recorded packets
+   */
+  async stopPcap(): Promise<number> {
+    try {
+      return await invoke<number>('plugin:serialport|stop_pcap', {
+        path: this.options.path,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Encode a frame with the codec registered by the application and write it
+   * @param {Uint8Array | number[]} frame
+   * @param {string} codec Name of the codec, by default the one of the running read or the port
+   * @return {Promise<number>}
+   */
+  async writeFrame(frame: Uint8Array | number[], codec?: string): Promise<number> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number>('plugin:serialport|write_frame', {
+        path: this.options.path,
+        frame: Array.from(frame),
+        codec,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Encode MIDI messages and write them
+   * @param {MidiMessage[]} messages
+   * @return {Promise<number>}
+   */
+  async sendMidi(messages: MidiMessage[]): Promise<number> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number>('plugin:serialport|send_midi', {
+        path: this.options.path,
+        messages,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Send a note on; `velocity` 0 or `noteOff` ends it
+   * @param {number} channel 0 to 15
+   * @param {number} note
+   * @param {number} velocity
+   * @return {Promise<number>}
+   */
+  async noteOn(channel: number, note: number, velocity: number): Promise<number> {
+    return this.sendMidi([{ type: 'NoteOn', channel, note, velocity }]);
+  }
+
+  /**
+   * @description: Send a note off
+   * @param {number} channel 0 to 15
+   * @param {number} note
+   * @param {number} velocity
+   * @return {Promise<number>}
+   */
+  async noteOff(channel: number, note: number, velocity = 0): Promise<number> {
+    return this.sendMidi([{ type: 'NoteOff', channel, note, velocity }]);
+  }
+
+  /**
+   * @description: Send a control change
+   * @param {number} channel 0 to 15
+   * @param {number} controller
+   * @param {number} value
+   * @return {Promise<number>}
+   */
+  async controlChange(channel: number, controller: number, value: number): Promise<number> {
+    return this.sendMidi([{ type: 'ControlChange', channel, controller, value }]);
+  }
+
+  /**
+   * @description: Send a system exclusive message
+   * @param {Uint8Array | number[]} data The bytes between `F0` and `F7`
+   * @return {Promise<number>}
+   */
+  async sendSysEx(data: Uint8Array | number[]): Promise<number> {
+    return this.sendMidi([{ type: 'SysEx', data: Array.from(data) }]);
+  }
+
+  /**
+   * @description: Register a listener for MIDI messages parsed in `Midi` read mode
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenMidi(fn: (data: MidiData) => void): Promise<boolean> {
+    try {
+      if (this.unListenMidi) {
+        this.unListenMidi();
+      }
+      this.unListenMidi = await appWindow.listen<MidiData>(
+        'plugin-serialport-midi-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port MIDI messages: ' + error);
+    }
+  }
+
+  /**
+   * @description: Encode UBX frames and write them
+   * @param {UbxFrame[]} frames
+   * @return {Promise<number>}
+   */
+  async sendUbx(frames: UbxFrame[]): Promise<number> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number>('plugin:serialport|send_ubx', {
+        path: this.options.path,
+        frames,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Send a CFG message to a u-blox receiver and wait for its acknowledgment; the port must not be reading
+   * @param {UbxConfig} config
+   * @param {number} timeoutMs 1000 by default
+   * @return {Promise<void>} Rejected on ACK-NAK or without an answer
+   */
+  async ubxConfigure(config: UbxConfig, timeoutMs?: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|ubx_configure', {
+        path: this.options.path,
+        config,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for UBX messages parsed in `Ubx` read mode
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenUbx(fn: (data: UbxData) => void): Promise<boolean> {
+    try {
+      if (this.unListenUbx) {
+        this.unListenUbx();
+      }
+      this.unListenUbx = await appWindow.listen<UbxData>(
+        'plugin-serialport-ubx-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port UBX messages: ' + error);
+    }
+  }
+
+  /**
+   * @description: Encode XBee API frames and write them
+   * @param {XBeeFrame[]} frames
+   * @param {boolean} escaped For radios in API mode 2 (`AP=2`)
+   * @return {Promise<number>}
+   */
+  async sendXBee(frames: XBeeFrame[], escaped?: boolean): Promise<number> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number>('plugin:serialport|send_xbee', {
+        path: this.options.path,
+        frames,
+        escaped,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Send an AT command to the local XBee in API mode, or to a remote radio, and wait for its response; the port must not be reading
+   * @param {string} command Two characters, e.g. `NI`
+   * @param {Uint8Array | number[]} parameter Sets the value, empty to query it
+   * @param {number} destination 64-bit address of a remote radio
+   * @param {boolean} escaped For radios in API mode 2 (`AP=2`)
+   * @param {number} timeoutMs 2000 by default
+   * @return {Promise<XBeeFrame>} The `AtResponse` or `RemoteAtResponse`
+   */
+  async xbeeAtCommand(
+    command: string,
+    parameter?: Uint8Array | number[],
+    destination?: number,
+    escaped?: boolean,
+    timeoutMs?: number,
+  ): Promise<XBeeFrame> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<XBeeFrame>('plugin:serialport|xbee_at_command', {
+        path: this.options.path,
+        command,
+        parameter: parameter ? Array.from(parameter) : undefined,
+        destination,
+        escaped,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for XBee API frames parsed in `XBee` or `XBeeEscaped` read mode
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenXBee(fn: (data: XBeeData) => void): Promise<boolean> {
+    try {
+      if (this.unListenXBee) {
+        this.unListenXBee();
+      }
+      this.unListenXBee = await appWindow.listen<XBeeData>(
+        'plugin-serialport-xbee-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port XBee frames: ' + error);
+    }
+  }
+
+  /**
+   * @description: Encode Firmata messages and write them
+   * @param {FirmataMessage[]} messages
+   * @return {Promise<number>}
+   */
+  async sendFirmata(messages: FirmataMessage[]): Promise<number> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number>('plugin:serialport|send_firmata', {
+        path: this.options.path,
+        messages,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Ask a Firmata board for its protocol and firmware versions; the port must not be reading
+   * @param {number} timeoutMs 5000 by default, the board resets when the port opens
+   * @return {Promise<FirmataVersion>}
+   */
+  async firmataHandshake(timeoutMs?: number): Promise<FirmataVersion> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<FirmataVersion>('plugin:serialport|firmata_handshake', {
+        path: this.options.path,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Set the mode of a Firmata pin
+   * @param {number} pin
+   * @param {PinMode} mode
+   * @return {Promise<number>}
+   */
+  async pinMode(pin: number, mode: PinMode): Promise<number> {
+    return this.sendFirmata([{ type: 'SetPinMode', pin, mode }]);
+  }
+
+  /**
+   * @description: Set a digital output of a Firmata board
+   * @param {number} pin
+   * @param {boolean} value
+   * @return {Promise<number>}
+   */
+  async digitalWrite(pin: number, value: boolean): Promise<number> {
+    return this.sendFirmata([{ type: 'SetDigitalPin', pin, value }]);
+  }
+
+  /**
+   * @description: Write a PWM or servo value to a Firmata pin
+   * @param {number} pin
+   * @param {number} value
+   * @return {Promise<number>}
+   */
+  async analogWrite(pin: number, value: number): Promise<number> {
+    if (pin > 15 || value > 0x3fff) {
+      return this.sendFirmata([{ type: 'ExtendedAnalog', pin, value }]);
+    }
+    return this.sendFirmata([{ type: 'Analog', pin, value }]);
+  }
+
+  /**
+   * @description: Turn the reports of an analog input (A0 is 0) on or off
+   * @param {number} pin
+   * @param {boolean} enable
+   * @return {Promise<number>}
+   */
+  async reportAnalog(pin: number, enable = true): Promise<number> {
+    return this.sendFirmata([{ type: 'ReportAnalog', pin, enable }]);
+  }
+
+  /**
+   * @description: Turn the reports of a digital port (pins 8 * port to 8 * port + 7) on or off
+   * @param {number} port
+   * @param {boolean} enable
+   * @return {Promise<number>}
+   */
+  async reportDigital(port: number, enable = true): Promise<number> {
+    return this.sendFirmata([{ type: 'ReportDigital', port, enable }]);
+  }
+
+  /**
+   * @description: Register a listener for Firmata messages parsed in `Firmata` read mode
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenFirmata(fn: (data: FirmataData) => void): Promise<boolean> {
+    try {
+      if (this.unListenFirmata) {
+        this.unListenFirmata();
+      }
+      this.unListenFirmata = await appWindow.listen<FirmataData>(
+        'plugin-serialport-firmata-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port Firmata messages: ' + error);
+    }
+  }
+
+  /**
+   * @description: Send a LIN master frame: break, sync, protected identifier, data and checksum
+   * @param {number} id 0 to 0x3f
+   * @param {Uint8Array | number[]} data 1 to 8 bytes
+   * @param {string} checksum `Enhanced` (LIN 2.x, default) or `Classic` (LIN 1.x)
+   * @return {Promise<void>}
+   */
+  async linSend(
+    id: number,
+    data: Uint8Array | number[],
+    checksum?: 'Classic' | 'Enhanced',
+  ): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<void>('plugin:serialport|lin_send', {
+        path: this.options.path,
+        id,
+        data: Array.from(data),
+        checksum,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Measure the round trip to a device that echoes what it receives, e.g. a loopback plug
+   * @param {Uint8Array | number[]} options.pattern Bytes echoed each iteration, by default 16 times 0x55
+   * @param {number} options.iterations Default 100
+   * @param {number} options.timeoutMs Wait for each echo, default 1000
+   * @return {Promise<Latency>}
+   */
+  async benchmarkLatency(options?: {
+    pattern?: Uint8Array | number[];
+    iterations?: number;
+    timeoutMs?: number;
+  }): Promise<Latency> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<Latency>('plugin:serialport|benchmark_latency', {
+        path: this.options.path,
+        pattern: options?.pattern ? Array.from(options.pattern) : undefined,
+        iterations: options?.iterations,
+        timeoutMs: options?.timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Saturate the link and report the rates achieved and the errors counted
+   * @param {number} options.duration Milliseconds, default 5000
+   * @param {string} options.direction `Loopback` to a device echoing what it receives (default), `Transmit` or `Receive`
+   * @return {Promise<Throughput>}
+   */
+  async benchmarkThroughput(options?: {
+    duration?: number;
+    direction?: 'Loopback' | 'Transmit' | 'Receive';
+  }): Promise<Throughput> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<Throughput>('plugin:serialport|benchmark_throughput', {
+        path: this.options.path,
+        durationMs: options?.duration,
+        direction: options?.direction,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Send a LIN header and resolve with the data the slave answers with
+   * @param {number} id 0 to 0x3f
+   * @param {number} length Data bytes, by default the LIN 1.x length of the identifier
+   * @param {string} checksum `Enhanced` (LIN 2.x, default) or `Classic` (LIN 1.x)
+   * @param {number} timeoutMs
+   * @return {Promise<number[]>}
+   */
+  async linRequest(
+    id: number,
+    length?: number,
+    checksum?: 'Classic' | 'Enhanced',
+    timeoutMs?: number,
+  ): Promise<number[]> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number[]>('plugin:serialport|lin_request', {
+        path: this.options.path,
+        id,
+        length,
+        checksum,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Write a SCPI query, e.g. `MEAS:VOLT?`, and resolve with its response line
+   * @param {string} query
+   * @param {number} timeoutMs
+   * @param {boolean} checkErrors Drain `SYST:ERR?` afterwards and reject with its errors
+   * @return {Promise<string>}
+   */
+  async scpiQuery(query: string, timeoutMs?: number, checkErrors?: boolean): Promise<string> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<string>('plugin:serialport|scpi_query', {
+        path: this.options.path,
+        query,
+        timeoutMs,
+        checkErrors,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Write a SCPI query answered with a `#` binary block and resolve with its payload
+   * @param {string} query
+   * @param {number} timeoutMs
+   * @return {Promise<number[]>}
+   */
+  async scpiQueryBinary(query: string, timeoutMs?: number): Promise<number[]> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number[]>('plugin:serialport|scpi_query_binary', {
+        path: this.options.path,
+        query,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Write a SCPI command; unless `checkErrors` is false rejects with the errors the instrument queued
+   * @param {string} command
+   * @param {number} timeoutMs
+   * @param {boolean} checkErrors
+   * @return {Promise<void>}
+   */
+  async scpiCommand(command: string, timeoutMs?: number, checkErrors?: boolean): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<void>('plugin:serialport|scpi_command', {
+        path: this.options.path,
+        command,
+        timeoutMs,
+        checkErrors,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Drain the SCPI error queue (`SYST:ERR?`)
+   * @param {number} timeoutMs
+   * @return {Promise<string[]>}
+   */
+  async scpiErrors(timeoutMs?: number): Promise<string[]> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<string[]>('plugin:serialport|scpi_errors', {
+        path: this.options.path,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for frames decoded by the codec of the read
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenFrame(fn: (data: FrameData) => void): Promise<boolean> {
+    try {
+      if (this.unListenFrame) {
+        this.unListenFrame();
+      }
+      this.unListenFrame = await appWindow.listen<FrameData>(
+        'plugin-serialport-frame-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port frames: ' + error);
+    }
+  }
+
+  /**
+   * @description: Parse the received data with a frame description and emit field maps to
+   * `listenPacket` instead of raw read events. `schema` may be the description or the path of a
+   * JSON file holding it; `null` emits raw data again
+   * @param {PacketSchema | string | null} schema
+   * @return {Promise<void>}
+   */
+  async setSchema(schema: PacketSchema | string | null): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      await invoke<void>('plugin:serialport|set_schema', {
+        path: this.options.path,
+        schema: typeof schema === 'string' ? undefined : schema,
+        file: typeof schema === 'string' ? schema : undefined,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register listeners for parsed packets and for frames the schema rejected
+   * @param {function} fn
+   * @param {function} onError
+   * @return {Promise<boolean>}
+   */
+  async listenPacket(
+    fn: (data: PacketData) => void,
+    onError?: (error: PacketError) => void,
+  ): Promise<boolean> {
+    try {
+      if (this.unListenPacket) {
+        this.unListenPacket();
+      }
+      if (this.unListenPacketError) {
+        this.unListenPacketError();
+        this.unListenPacketError = undefined;
+      }
+      this.unListenPacket = await appWindow.listen<PacketData>(
+        'plugin-serialport-packet-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      if (onError) {
+        this.unListenPacketError = await appWindow.listen<PacketError>(
+          'plugin-serialport-packet-error-' + this.eventId,
+          ({ payload }) => onError(payload),
+        );
+      }
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port packets: ' + error);
+    }
+  }
+
+  /**
+   * @description: Register listeners for values decoded with the `decode` read option and for
+   * payloads that could not be decoded
+   * @param {function} fn
+   * @param {function} onError
+   * @return {Promise<boolean>}
+   */
+  async listenDecoded(
+    fn: (data: DecodedData) => void,
+    onError?: (error: { message: string; line?: string }) => void,
+  ): Promise<boolean> {
+    try {
+      if (this.unListenDecoded) {
+        this.unListenDecoded();
+      }
+      if (this.unListenDecodeError) {
+        this.unListenDecodeError();
+        this.unListenDecodeError = undefined;
+      }
+      this.unListenDecoded = await appWindow.listen<DecodedData>(
+        'plugin-serialport-decoded-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      if (onError) {
+        this.unListenDecodeError = await appWindow.listen<{ message: string; line?: string }>(
+          'plugin-serialport-decode-error-' + this.eventId,
+          ({ payload }) => onError(payload),
+        );
+      }
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the decoded serial port data: ' + error);
+    }
+  }
+
+  /**
+   * @description: Run an AT command, e.g. `AT+CSQ`, and wait for its final result code. With the background read running, unsolicited result codes are not lost between commands
+   * @param {string} command
+   * @param {number} timeoutMs Default 5000
+   * @return {Promise<AtResponse>}
+   */
+  async atCommand(command: string, timeoutMs?: number): Promise<AtResponse> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<AtResponse>('plugin:serialport|at_command', {
+        path: this.options.path,
+        command,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Send an SMS in PDU mode, a long text as a concatenated message. Returns the message reference of every part, which delivery reports refer to
+   * @param {string} number e.g. `+31641600986`
+   * @param {string} text
+   * @param {boolean} deliveryReport
+   * @return {Promise<number[]>}
+   */
+  async sendSms(number: string, text: string, deliveryReport?: boolean): Promise<number[]> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number[]>('plugin:serialport|send_sms', {
+        path: this.options.path,
+        number,
+        text,
+        deliveryReport,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Read the message stored at `index`
+   * @param {number} index
+   * @return {Promise<SmsMessage>}
+   */
+  async readSms(index: number): Promise<SmsMessage> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<SmsMessage>('plugin:serialport|read_sms', {
+        path: this.options.path,
+        index,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: List stored messages
+   * @param {string} status `Unread`, `Read` or `All` (default)
+   * @return {Promise<SmsMessage[]>}
+   */
+  async listSms(status?: 'Unread' | 'Read' | 'All'): Promise<SmsMessage[]> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<SmsMessage[]>('plugin:serialport|list_sms', {
+        path: this.options.path,
+        status,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Delete the message stored at `index`
+   * @param {number} index
+   * @return {Promise<void>}
+   */
+  async deleteSms(index: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<void>('plugin:serialport|delete_sms', {
+        path: this.options.path,
+        index,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Let the modem announce new messages and delivery reports; the background read then emits them to `listenSms` and `listenSmsReport`
+   * @param {boolean} enabled
+   * @return {Promise<void>}
+   */
+  async setSmsNotifications(enabled: boolean): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<void>('plugin:serialport|set_sms_notifications', {
+        path: this.options.path,
+        enabled,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for unsolicited result codes of an AT modem
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenUrc(fn: (data: UrcData) => void): Promise<boolean> {
+    try {
+      if (this.unListenUrc) {
+        this.unListenUrc();
+      }
+      this.unListenUrc = await appWindow.listen<UrcData>(
+        'plugin-serialport-urc-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port URCs: ' + error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for received SMS, see `setSmsNotifications`
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenSms(fn: (data: SmsMessage) => void): Promise<boolean> {
+    try {
+      if (this.unListenSms) {
+        this.unListenSms();
+      }
+      this.unListenSms = await appWindow.listen<SmsMessage>(
+        'plugin-serialport-sms-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port SMS: ' + error);
+    }
+  }
+
+  /**
+   * @description: Register a listener for SMS delivery reports
+   * @param {function} fn
+   * @return {Promise<boolean>}
+   */
+  async listenSmsReport(fn: (data: SmsReport) => void): Promise<boolean> {
+    try {
+      if (this.unListenSmsReport) {
+        this.unListenSmsReport();
+      }
+      this.unListenSmsReport = await appWindow.listen<SmsReport>(
+        'plugin-serialport-sms-report-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port SMS reports: ' + error);
+    }
+  }
+
+  /**
+   * @description: Attach a cellular module to the packet network and configure its socket stack; returns the local IP address
+   * @param {string} stack `Sim800`, `Quectel` or `Ublox`
+   * @param {string} apn
+   * @param {string} user
+   * @param {string} password
+   * @return {Promise<string>}
+   */
+  async cellularConnect(stack: 'Sim800' | 'Quectel' | 'Ublox', apn: string, user?: string, password?: string): Promise<string> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<string>('plugin:serialport|cellular_connect', {
+        path: this.options.path,
+        stack,
+        apn,
+        user,
+        password,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Open a socket through the cellular module; returns the socket number
+   * @param {string} protocol
+   * @param {string} host
+   * @param {number} port
+   * @param {number} socket connection number on SIM800 and Quectel modules, default 0
+   * @return {Promise<number>}
    */
-  static async available_ports(): Promise<SerialPortInfo[]> {
+  async socketOpen(protocol: 'Tcp' | 'Udp', host: string, port: number, socket?: number): Promise<number> {
     try {
-      return await invoke<SerialPortInfo[]>('plugin:serialport|available_ports');
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number>('plugin:serialport|socket_open', {
+        path: this.options.path,
+        protocol,
+        host,
+        port,
+        socket,
+      });
     } catch (error) {
       return Promise.reject(error);
     }
   }
 
   /**
-   * @description: Force to terminate the serial port
-   * @param {string} path
+   * @description: Send data through an open socket
+   * @param {number} socket
+   * @param {Uint8Array | number[]} data
    * @return {Promise<void>}
    */
-  static async forceClose(path: string): Promise<void> {
-    return await invoke<void>('plugin:serialport|force_close', {
-      path,
-    });
+  async socketSend(socket: number, data: Uint8Array | number[]): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<void>('plugin:serialport|socket_send', {
+        path: this.options.path,
+        socket,
+        data: Array.from(data),
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
   }
 
   /**
-   * @description: Close all serial ports
+   * @description: Read the data the module buffered for a socket, for ports without the background read
+   * @param {number} socket
+   * @return {Promise<number[]>}
+   */
+  async socketReceive(socket: number): Promise<number[]> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<number[]>('plugin:serialport|socket_receive', {
+        path: this.options.path,
+        socket,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Close an open socket
+   * @param {number} socket
    * @return {Promise<void>}
    */
-  static async closeAll(): Promise<void> {
-    return await invoke<void>('plugin:serialport|close_all');
+  async socketClose(socket: number): Promise<void> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<void>('plugin:serialport|socket_close', {
+        path: this.options.path,
+        socket,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
   }
 
   /**
-   * @description: Stop listening to the serial port
+   * @description: Register a listener for data received on cellular sockets
+   * @param {function} fn
    * @return {Promise<boolean>}
    */
-  async cancelListen(): Promise<boolean> {
+  async listenSocket(fn: (data: SocketData) => void): Promise<boolean> {
     try {
-      if (this.unListen) {
-        this.unListen();
-        this.unListen = undefined;
+      if (this.unListenSocket) {
+        this.unListenSocket();
       }
+      this.unListenSocket = await appWindow.listen<SocketData>(
+        'plugin-serialport-socket-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
       return Promise.resolve(true);
     } catch (error) {
-      return Promise.reject('Failed to stop listening to the serial port: ' + error);
+      return Promise.reject('Failed to listen to the serial port socket data: ' + error);
     }
   }
 
   /**
-   * @description: Cancel read serial port data
+   * @description: Register a listener for cellular sockets closed by the module
+   * @param {function} fn
    * @return {Promise<boolean>}
    */
-  async cancelRead(): Promise<boolean> {
+  async listenSocketClosed(fn: (data: SocketClosed) => void): Promise<boolean> {
     try {
-      await invoke<void>('plugin:serialport|cancel_read', {
+      if (this.unListenSocketClosed) {
+        this.unListenSocketClosed();
+      }
+      this.unListenSocketClosed = await appWindow.listen<SocketClosed>(
+        'plugin-serialport-socket-closed-' + this.eventId,
+        ({ payload }) => fn(payload),
+      );
+      return Promise.resolve(true);
+    } catch (error) {
+      return Promise.reject('Failed to listen to the serial port closed sockets: ' + error);
+    }
+  }
+
+  /**
+   * @description: Run one command of a LoRa module and wait for its answer, e.g. `mac set deveui ...` or `AT+DEVEUI=...`
+   * @param {LoraModule} module
+   * @param {string} command
+   * @param {number} timeoutMs 5000 by default
+   * @return {Promise<AtResponse>}
+   */
+  async loraCommand(module: LoraModule, command: string, timeoutMs?: number): Promise<AtResponse> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<AtResponse>('plugin:serialport|lora_command', {
         path: this.options.path,
+        module,
+        command,
+        timeoutMs,
       });
-      return Promise.resolve(true);
     } catch (error) {
       return Promise.reject(error);
     }
   }
 
   /**
-   * @description:
-   * @param {object} options
+   * @description: Join the LoRaWAN network with the keys configured in the module
+   * @param {LoraModule} module
+   * @param {string} activation `Otaa` waits for the join accept, `Abp` joins at once
+   * @param {number} timeoutMs 30000 by default
    * @return {Promise<void>}
    */
-  async change(options: { path?: string; baudRate?: number }): Promise<void> {
+  async loraJoin(module: LoraModule, activation: 'Otaa' | 'Abp', timeoutMs?: number): Promise<void> {
     try {
-      let isOpened = false;
-      if (this.isOpen) {
-        isOpened = true;
-        await this.close();
-      }
-      if (options.path) {
-        this.options.path = options.path;
-      }
-      if (options.baudRate) {
-        this.options.baudRate = options.baudRate;
-      }
-      if (isOpened) {
-        await this.open();
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
       }
-      return Promise.resolve();
+      await invoke<void>('plugin:serialport|lora_join', {
+        path: this.options.path,
+        module,
+        activation,
+        timeoutMs,
+      });
     } catch (error) {
       return Promise.reject(error);
     }
   }
 
   /**
-   * @description: Close serial port
-   * @return {Promise<boolean>}
+   * @description: Send a LoRaWAN uplink
+   * @param {LoraModule} module
+   * @param {number} port Application port, 1 to 223
+   * @param {Uint8Array | number[]} data
+   * @param {boolean} confirmed Ask the network to acknowledge it
+   * @param {number} timeoutMs 30000 by default
+   * @return {Promise<LoraDownlink | null>} The downlink an RN2483 received in the RX windows
    */
-  async close(): Promise<boolean> {
+  async loraSend(
+    module: LoraModule,
+    port: number,
+    data: Uint8Array | number[],
+    confirmed?: boolean,
+    timeoutMs?: number,
+  ): Promise<LoraDownlink | null> {
     try {
       if (!this.isOpen) {
-        return Promise.resolve(true);
+        return Promise.reject(`Port ${this.options.path} is not open!`);
       }
-      await this.cancelRead();
-      await invoke<void>('plugin:serialport|close', {
+      return await invoke<LoraDownlink | null>('plugin:serialport|lora_send', {
         path: this.options.path,
+        module,
+        port,
+        data: Array.from(data),
+        confirmed,
+        timeoutMs,
       });
-      await this.cancelListen();
-      this.isOpen = false;
-      return Promise.resolve(true);
     } catch (error) {
       return Promise.reject(error);
     }
   }
 
   /**
-   * @description: Register a listener to receive data read from the serial port
+   * @description: Register a listener for LoRaWAN downlinks
    * @param {function} fn
    * @return {Promise<boolean>}
    */
-  async listen(fn: (...args: any[]) => void, isDecode = true): Promise<boolean> {
+  async listenLora(fn: (data: LoraDownlink) => void): Promise<boolean> {
     try {
-      await this.cancelListen();
-      let readEvent = 'plugin-serialport-read-' + this.options.path;
-      this.unListen = await appWindow.listen<ReadDataResult>(
-        readEvent,
-        ({ payload }) => {
-          try {
-            if (isDecode) {
-              const decoder = new TextDecoder(this.encoding);
-              const data = decoder.decode(new Uint8Array(payload.data));
-              fn(data);
-            } else {
-              fn(new Uint8Array(payload.data));
-            }
-          } catch (error) {
-            console.error(error);
-          }
-        },
+      if (this.unListenLora) {
+        this.unListenLora();
+      }
+      this.unListenLora = await appWindow.listen<LoraDownlink>(
+        'plugin-serialport-lora-' + this.eventId,
+        ({ payload }) => fn(payload),
       );
       return Promise.resolve(true);
     } catch (error) {
-      return Promise.reject('Failed to listen to the serial port: ' + error);
+      return Promise.reject('Failed to listen to the serial port LoRaWAN downlinks: ' + error);
     }
   }
 
   /**
-   * @description: Open serial port
-   * @return {Promise<boolean>}
+   * @description: Read or write the configuration registers of an Ebyte E22 module in configuration mode; the port must not be reading
+   * @param {number} address First register
+   * @param {number} length Registers to read, 1 by default
+   * @param {Uint8Array | number[]} values Written from `address` instead
+   * @param {boolean} temporary Keep written values only until the module is powered off
+   * @return {Promise<number[]>} The registers as the module answers
    */
-  async open(): Promise<boolean> {
+  async e22Registers(
+    address: number,
+    length?: number,
+    values?: Uint8Array | number[],
+    temporary?: boolean,
+  ): Promise<number[]> {
     try {
-      if (!this.options.path) {
-        return Promise.reject(`path cannot be empty!`);
-      }
-      if (!this.options.baudRate) {
-        return Promise.reject(`baudRate cannot be empty!`);
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
       }
-      if (this.isOpen) {
-        return Promise.resolve(true);
+      return await invoke<number[]>('plugin:serialport|e22_registers', {
+        path: this.options.path,
+        address,
+        length,
+        values: values ? Array.from(values) : undefined,
+        temporary,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+
+  /**
+   * @description: Reset the ELM327 adapter and connect to the vehicle; the port must not be reading
+   * @param {string} protocol The `ATSP` protocol number, `0` searches automatically
+   * @param {string} header Request header for `ATSH`, e.g. `7E0` to address the engine ECU
+   * @param {number} timeoutMs 10000 by default
+   * @return {Promise<ObdInfo>}
+   */
+  async obdInit(protocol?: string, header?: string, timeoutMs?: number): Promise<ObdInfo> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
       }
-      const res = await invoke<void>('plugin:serialport|open', {
+      return await invoke<ObdInfo>('plugin:serialport|obd_init', {
         path: this.options.path,
-        baudRate: this.options.baudRate,
-        dataBits: this.options.dataBits,
-        flowControl: this.options.flowControl,
-        parity: this.options.parity,
-        stopBits: this.options.stopBits,
-        timeout: this.options.timeout,
+        protocol,
+        header,
+        timeoutMs,
       });
-      this.isOpen = true;
-      return Promise.resolve(true);
     } catch (error) {
       return Promise.reject(error);
     }
   }
 
+  /**
+   * @description: Read and decode a mode 01 PID, e.g. `0x0C` engine speed or `0x05` coolant temperature
+   * @param {number} pid
+   * @param {number} timeoutMs 2000 by default
+   * @return {Promise<PidValue>}
+   */
+  async obdReadPid(pid: number, timeoutMs?: number): Promise<PidValue> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<PidValue>('plugin:serialport|obd_read_pid', {
+        path: this.options.path,
+        pid,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
 
   /**
-   * @description: Tell the backend to start reading the serial port data.
-   * The backend will read the data and send it to the front end through the listen method.
-   * @param {ReadOptions} options Read options { timeout, size }
-   * @return {Promise<boolean>}
+   * @description: Read the stored diagnostic trouble codes, or the pending ones
+   * @param {boolean} pending
+   * @param {number} timeoutMs 2000 by default
+   * @return {Promise<string[]>} Codes like `P0133`
    */
-  async read(options?: ReadOptions): Promise<boolean> {
+  async obdReadDtcs(pending?: boolean, timeoutMs?: number): Promise<string[]> {
     try {
-      await invoke<void>('plugin:serialport|read', {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<string[]>('plugin:serialport|obd_read_dtcs', {
         path: this.options.path,
-        timeout: options?.timeout || this.options.timeout,
-        size: options?.size || this.size,
+        pending,
+        timeoutMs,
       });
-      return Promise.resolve(true);
     } catch (error) {
       return Promise.reject(error);
     }
   }
 
   /**
-   * @description: Set the serial port baud rate
-   * @param {number} value
+   * @description: Clear the diagnostic trouble codes and freeze frames, turning off the MIL
+   * @param {number} timeoutMs 5000 by default
    * @return {Promise<void>}
    */
-  async setBaudRate(value: number): Promise<void> {
+  async obdClearDtcs(timeoutMs?: number): Promise<void> {
     try {
-      let isOpened = false;
-      if (this.isOpen) {
-        isOpened = true;
-        await this.close();
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
       }
-      this.options.baudRate = value;
-      if (isOpened) {
-        await this.open();
+      await invoke<void>('plugin:serialport|obd_clear_dtcs', {
+        path: this.options.path,
+        timeoutMs,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+  /**
+   * @description: Query the `~HS` host status of a Zebra-compatible label printer; the port must not be reading
+   * @return {Promise<LabelStatus>}
+   */
+  async labelStatus(): Promise<LabelStatus> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
       }
-      return Promise.resolve();
+      return await invoke<LabelStatus>('plugin:serialport|label_status', {
+        path: this.options.path,
+      });
     } catch (error) {
       return Promise.reject(error);
     }
   }
 
   /**
-   * @description: Set the serial port path
-   * @param {string} value
-   * @return {Promise<void>}
+   * @description: Send a ZPL or EPL job to a label printer
+   * @param {string} job
+   * @param {string} language `Zpl` by default
+   * @param {boolean} checkStatus Only send when the printer is ready, true by default
+   * @param {boolean} wait Resolve once the printer reports the job printed
+   * @param {number} timeoutMs 30000 by default
+   * @return {Promise<LabelStatus | null>} The last status, null without `checkStatus`
    */
-  async setPath(value: string): Promise<void> {
+  async printLabel(
+    job: string,
+    language?: 'Zpl' | 'Epl',
+    checkStatus?: boolean,
+    wait?: boolean,
+    timeoutMs?: number,
+  ): Promise<LabelStatus | null> {
     try {
-      let isOpened = false;
-      if (this.isOpen) {
-        isOpened = true;
-        await this.close();
-      }
-      this.options.path = value;
-      if (isOpened) {
-        await this.open();
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
       }
-      return Promise.resolve();
+      return await invoke<LabelStatus | null>('plugin:serialport|print_label', {
+        path: this.options.path,
+        job,
+        language,
+        checkStatus,
+        wait,
+        timeoutMs,
+      });
     } catch (error) {
       return Promise.reject(error);
     }
   }
 
   /**
-   * @description: Write data to the serial port
-   * @param {string} value
-   * @return {Promise<number>}
+   * @description: Let the label printer measure label and gap lengths, feeding a few labels
+   * @param {string} language `Zpl` by default
+   * @return {Promise<void>}
    */
-  async write(value: string): Promise<number> {
+  async calibrateLabel(language?: 'Zpl' | 'Epl'): Promise<void> {
     try {
       if (!this.isOpen) {
         return Promise.reject(`Port ${this.options.path} is not open!`);
       }
-      return await invoke<number>('plugin:serialport|write', {
-        value,
+      await invoke<void>('plugin:serialport|calibrate_label', {
+        path: this.options.path,
+        language,
+      });
+    } catch (error) {
+      return Promise.reject(error);
+    }
+  }
+  /**
+   * @description: Poll the paper, cover, drawer and error status of an ESC/POS printer; the port must not be reading
+   * @param {number} timeoutMs For each of the four answers, 500 by default
+   * @return {Promise<PrinterStatus>}
+   */
+  async printerStatus(timeoutMs?: number): Promise<PrinterStatus> {
+    try {
+      if (!this.isOpen) {
+        return Promise.reject(`Port ${this.options.path} is not open!`);
+      }
+      return await invoke<PrinterStatus>('plugin:serialport|printer_status', {
         path: this.options.path,
+        timeoutMs,
       });
     } catch (error) {
       return Promise.reject(error);
@@ -327,29 +4488,27 @@ class Serialport {
   }
 
   /**
-   * @description: Write binary data to the serial port
-   * @param {Uint8Array} value
+   * @description: Open the cash drawer connected to an ESC/POS printer
+   * @param {number} pin 2 (the default) or 5 of the drawer kick connector
+   * @param {number} pulse In milliseconds, 100 by default
    * @return {Promise<number>}
    */
-  async writeBinary(value: Uint8Array | number[]): Promise<number> {
+  async kickCashDrawer(pin?: 2 | 5, pulse?: number): Promise<number> {
     try {
       if (!this.isOpen) {
         return Promise.reject(`Port ${this.options.path} is not open!`);
       }
-      if (value instanceof Uint8Array || value instanceof Array) {
-        return await invoke<number>('plugin:serialport|write_binary', {
-          value: Array.from(value),
-          path: this.options.path,
-        });
-      } else {
-        return Promise.reject(
-          'value Parameter must be of type string, Uint8Array, or number[]!',
-        );
-      }
+      return await invoke<number>('plugin:serialport|kick_cash_drawer', {
+        path: this.options.path,
+        pin,
+        pulse,
+      });
     } catch (error) {
       return Promise.reject(error);
     }
   }
+
+
 }
 
 export { Serialport };